@@ -2,6 +2,7 @@ use crate::subcommands::CannonSubcommandDispatcher;
 use anyhow::{anyhow, Result};
 use clap::{ArgAction, ColorChoice, Parser};
 use tracing::Level;
+use tracing_subscriber::EnvFilter;
 
 mod subcommands;
 
@@ -31,7 +32,9 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-/// Initializes the tracing subscriber
+/// Initializes the tracing subscriber. If the `RUST_LOG` environment variable is set, its
+/// per-module directives (e.g. `mipsevm::evm=debug,cannon::kernel=info`) take precedence over
+/// the verbosity level.
 ///
 /// # Arguments
 /// * `verbosity_level` - The verbosity level (0-4)
@@ -39,14 +42,15 @@ fn main() -> Result<()> {
 /// # Returns
 /// * `Result<()>` - Ok if successful, Err otherwise.
 fn init_tracing_subscriber(verbosity_level: u8) -> Result<()> {
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(match verbosity_level {
-            0 => Level::ERROR,
-            1 => Level::WARN,
-            2 => Level::INFO,
-            3 => Level::DEBUG,
-            _ => Level::TRACE,
-        })
-        .finish();
+    let level = match verbosity_level {
+        0 => Level::ERROR,
+        1 => Level::WARN,
+        2 => Level::INFO,
+        3 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::default().add_directive(level.into()));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).finish();
     tracing::subscriber::set_global_default(subscriber).map_err(|e| anyhow!(e))
 }
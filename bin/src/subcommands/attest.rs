@@ -0,0 +1,54 @@
+//! The `attest` subcommand for the cannon binary
+
+use super::CannonSubcommandDispatcher;
+use anyhow::Result;
+use cannon::Manifest;
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+/// Command line arguments for `cannon attest`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct AttestArgs {
+    /// The `attest` subcommand to run
+    #[command(subcommand)]
+    command: AttestCommand,
+}
+
+/// The subcommands of `cannon attest`
+#[derive(Subcommand, Debug)]
+enum AttestCommand {
+    /// Verifies that a set of artifacts matches the manifest written by `cannon run --manifest`.
+    Verify(VerifyArgs),
+}
+
+/// Command line arguments for `cannon attest verify`
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    /// The path to the manifest.
+    #[arg(long)]
+    manifest: PathBuf,
+
+    /// The directory that the artifact paths listed in the manifest are relative to. Defaults to
+    /// the current directory, which the paths are relative to when written by `cannon run`.
+    #[arg(long, default_value = ".")]
+    base: PathBuf,
+}
+
+impl CannonSubcommandDispatcher for AttestArgs {
+    fn dispatch(self) -> Result<()> {
+        match self.command {
+            AttestCommand::Verify(args) => args.dispatch(),
+        }
+    }
+}
+
+impl CannonSubcommandDispatcher for VerifyArgs {
+    fn dispatch(self) -> Result<()> {
+        let manifest = Manifest::load(&self.manifest)?;
+        manifest.verify(&self.base)?;
+
+        tracing::info!(target: "cannon-cli::attest", "All {} artifacts match the manifest. input state hash: {}, head: {}", manifest.artifacts.len(), manifest.input_state_hash, manifest.head);
+        Ok(())
+    }
+}
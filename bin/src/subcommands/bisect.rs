@@ -0,0 +1,103 @@
+//! The `bisect` subcommand for the cannon binary
+
+use super::{mem::load_state, CannonSubcommandDispatcher};
+use alloy_primitives::B256;
+use anyhow::Result;
+use cannon::{load_reference_hashes, Bisection, Bisector, ReplayOracle, ShadowVerifier};
+use cannon_mipsevm::InstrumentedState;
+use clap::Args;
+use std::{fs, io, path::PathBuf};
+
+/// Command line arguments for `cannon bisect`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct BisectArgs {
+    /// The path to the initial state. States at `.bin` paths are loaded from the binary state
+    /// file format.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Skip verifying the checksum embedded in the state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The state hash that the reference implementation computed at the final step.
+    #[arg(long)]
+    expected: B256,
+
+    /// The final step. The native VM runs until the program exits if not specified.
+    #[arg(long)]
+    stop_at: Option<u64>,
+
+    /// The `host:port` address of a companion Go Cannon process to fetch intermediate reference
+    /// state hashes from, as with `cannon run --shadow-rpc`.
+    #[arg(
+        long,
+        conflicts_with = "reference",
+        required_unless_present = "reference"
+    )]
+    shadow_rpc: Option<String>,
+
+    /// The path of a JSONL file of intermediate reference state hashes, with one
+    /// `{"step":..,"stateHash":"0x.."}` object per line.
+    #[arg(long)]
+    reference: Option<PathBuf>,
+
+    /// The path of a replay log recorded with `cannon run --preimage-record` to serve preimages
+    /// from. Steps that read a preimage fail without it.
+    #[arg(long)]
+    preimage_replay: Option<PathBuf>,
+
+    /// The path to write the JSON divergence report to, including the proof of the diverging
+    /// step.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+impl CannonSubcommandDispatcher for BisectArgs {
+    fn dispatch(self) -> Result<()> {
+        let state = load_state(&self.input, self.skip_checksum)?;
+        let oracle = self
+            .preimage_replay
+            .as_deref()
+            .map(ReplayOracle::open)
+            .transpose()?
+            .unwrap_or_default();
+
+        let mut bisector = Bisector::new(InstrumentedState::new(
+            state,
+            oracle,
+            io::sink(),
+            io::sink(),
+        ));
+        if let Some(stop_at) = self.stop_at {
+            bisector = bisector.with_stop_at(stop_at);
+        }
+
+        let expected = self.expected.0;
+        let outcome = match (self.shadow_rpc, self.reference) {
+            (Some(addr), _) => bisector.bisect(&mut ShadowVerifier::connect(&addr)?, expected)?,
+            (None, Some(path)) => bisector.bisect(&mut load_reference_hashes(path)?, expected)?,
+            (None, None) => anyhow::bail!("Either --shadow-rpc or --reference is required"),
+        };
+
+        match outcome {
+            Bisection::Matched { step, hash } => {
+                println!(
+                    "No divergence: the state hash at step {} matches {}",
+                    step,
+                    B256::from(hash)
+                );
+            }
+            Bisection::Diverged(divergence) => {
+                println!("{}", divergence);
+                if let Some(ref path) = self.output {
+                    fs::write(path, serde_json::to_vec(&divergence)?)?;
+                    tracing::info!(target: "cannon-cli::bisect", "Wrote the divergence report to {}", path.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+//! The `check-elf` subcommand for the cannon binary
+
+use super::CannonSubcommandDispatcher;
+use anyhow::Result;
+use cannon_mipsevm::CompatReport;
+use clap::Args;
+use std::{fs, path::PathBuf};
+
+/// Command line arguments for `cannon check-elf`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct CheckElfArgs {
+    /// The path to the 32-bit big-endian MIPS ELF file to check.
+    path: PathBuf,
+
+    /// Treat the MIPS32r2 instructions as supported, as when loading with `--mips32r2`.
+    #[arg(long)]
+    mips32r2: bool,
+
+    /// Print the full report as JSON instead of a summary.
+    #[arg(long)]
+    json: bool,
+}
+
+impl CannonSubcommandDispatcher for CheckElfArgs {
+    fn dispatch(self) -> Result<()> {
+        tracing::info!(target: "cannon-cli::check-elf", "Checking ELF file @ {}", self.path.display());
+        let report = CompatReport::from_elf(&fs::read(&self.path)?)?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+
+        anyhow::ensure!(
+            report.is_compatible(self.mips32r2),
+            "{} is not expected to run in the emulator",
+            self.path.display()
+        );
+        println!("{} is expected to run in the emulator", self.path.display());
+        Ok(())
+    }
+}
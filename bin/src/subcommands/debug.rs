@@ -0,0 +1,526 @@
+//! The `debug` subcommand for the cannon binary
+
+use super::{mem::load_state, CannonSubcommandDispatcher};
+use anyhow::Result;
+use cannon::{ReplayOracle, StepRecord};
+use cannon_mipsevm::{
+    disasm::{self, Disassembly, REGISTER_NAMES},
+    Address, InstrumentedState, Metadata, State,
+};
+use clap::Args;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
+};
+use std::{
+    io::{self, Sink},
+    panic,
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Command line arguments for `cannon debug`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct DebugArgs {
+    /// The path to the state to debug. States at `.bin` paths are loaded from the binary state
+    /// file format.
+    input: PathBuf,
+
+    /// Skip verifying the checksum embedded in the state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The path to the metadata of the program, used to symbolize the disassembly.
+    #[arg(long)]
+    meta: Option<PathBuf>,
+
+    /// The path of a replay log recorded with `cannon run --preimage-record` to serve preimages
+    /// from. Steps that read a preimage fail without it.
+    #[arg(long)]
+    preimage_replay: Option<PathBuf>,
+}
+
+impl CannonSubcommandDispatcher for DebugArgs {
+    fn dispatch(self) -> Result<()> {
+        let state = load_state(&self.input, self.skip_checksum)?;
+        let meta = self
+            .meta
+            .as_deref()
+            .map(Metadata::load)
+            .transpose()?
+            .unwrap_or_default();
+        let oracle = self
+            .preimage_replay
+            .as_deref()
+            .map(ReplayOracle::open)
+            .transpose()?
+            .unwrap_or_default();
+
+        let mut debugger = Debugger::new(
+            InstrumentedState::new(state, oracle, io::sink(), io::sink()),
+            meta,
+        );
+
+        // Restore the terminal before a panic is reported, so that the message is readable and
+        // the shell is usable afterwards.
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            hook(info);
+        }));
+
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let result = debugger.run(&mut Terminal::new(CrosstermBackend::new(io::stdout()))?);
+        restore_terminal()?;
+        result
+    }
+}
+
+/// Leaves the raw mode and the alternate screen that the TUI runs in.
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)
+}
+
+/// The number of instructions shown before the program counter in the disassembly pane.
+const DISASSEMBLY_CONTEXT: u32 = 8;
+
+/// The number of steps between checks for a key press that interrupts a run.
+const INTERRUPT_CHECK_INTERVAL: u64 = 1 << 16;
+
+/// The [InstrumentedState] that the debugger steps through.
+type DebugInstrumentedState = InstrumentedState<Sink, Sink, ReplayOracle>;
+
+/// The value that the debugger is prompting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    /// The program counter to run until.
+    RunUntil,
+    /// The address to show in the memory pane.
+    Memory,
+}
+
+/// The [Debugger] holds the state of the `cannon debug` TUI.
+struct Debugger {
+    /// The state being debugged.
+    ins_state: DebugInstrumentedState,
+    /// The metadata of the program, used to symbolize addresses.
+    meta: Metadata,
+    /// The [StepRecord] of the last executed instruction.
+    last: Option<StepRecord>,
+    /// The address shown at the top of the memory pane.
+    mem_address: Address,
+    /// The value being prompted for, if any, and the input typed so far.
+    prompt: Option<(Prompt, String)>,
+    /// The message shown in the status bar.
+    message: String,
+}
+
+impl Debugger {
+    /// Creates a new [Debugger] for the given state, with the memory pane showing the stack.
+    fn new(ins_state: DebugInstrumentedState, meta: Metadata) -> Self {
+        let mem_address = ins_state.state.registers[29] & !0xF;
+        Self {
+            ins_state,
+            meta,
+            last: None,
+            mem_address,
+            prompt: None,
+            message: String::new(),
+        }
+    }
+
+    /// Runs the TUI until the user quits.
+    fn run<W: io::Write>(&mut self, terminal: &mut Terminal<CrosstermBackend<W>>) -> Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Handles a key press.
+    ///
+    /// ### Returns
+    /// - `false` if the user quit, `true` otherwise.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        if let Some((prompt, input)) = self.prompt.as_mut() {
+            match code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    let prompt = *prompt;
+                    let input = std::mem::take(input);
+                    self.prompt = None;
+                    match parse_address(&input) {
+                        Some(address) => self.submit(prompt, address),
+                        None => self.message = format!("Invalid address: {input}"),
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        let result = match code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('s') | KeyCode::Enter => self.step(),
+            KeyCode::Char('n') => self.step_over(),
+            KeyCode::Char('c') => self.run_until(|_| false),
+            KeyCode::Char('g') => {
+                self.prompt = Some((Prompt::RunUntil, String::new()));
+                Ok(())
+            }
+            KeyCode::Char('m') => {
+                self.prompt = Some((Prompt::Memory, String::new()));
+                Ok(())
+            }
+            KeyCode::PageDown | KeyCode::Char('j') => {
+                self.mem_address = self.mem_address.wrapping_add(0x10);
+                Ok(())
+            }
+            KeyCode::PageUp | KeyCode::Char('k') => {
+                self.mem_address = self.mem_address.wrapping_sub(0x10);
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.message = format!("Error: {e:#}");
+        }
+        true
+    }
+
+    /// Acts upon an address entered at a [Prompt].
+    fn submit(&mut self, prompt: Prompt, address: Address) {
+        match prompt {
+            Prompt::RunUntil => {
+                if let Err(e) = self.run_until(|state| state.pc == address) {
+                    self.message = format!("Error: {e:#}");
+                }
+            }
+            Prompt::Memory => self.mem_address = address & !0xF,
+        }
+    }
+
+    /// Executes a single instruction, recording its effects.
+    fn step(&mut self) -> Result<()> {
+        if self.ins_state.state.exited {
+            self.message = format!("Exited with code {}", self.ins_state.state.exit_code);
+            return Ok(());
+        }
+
+        self.ins_state.set_record_mem_access(true);
+        let mut record = StepRecord::begin(&mut self.ins_state.state)?;
+        self.ins_state.step(false)?;
+        record.finish(&mut self.ins_state)?;
+        self.last = Some(record);
+        self.message.clear();
+        Ok(())
+    }
+
+    /// Executes the instruction at the program counter, running until the function returns if it
+    /// is a call.
+    fn step_over(&mut self) -> Result<()> {
+        let pc = self.ins_state.state.pc;
+        let instruction = self.ins_state.state.memory.get_memory(pc)?;
+        if !is_call(instruction) {
+            return self.step();
+        }
+
+        // Calls return past their delay slot. Deeper recursive invocations of the same function
+        // return to the same address, so the stack pointer must also be back at its level.
+        let (return_address, sp) = (pc.wrapping_add(8), self.ins_state.state.registers[29]);
+        self.run_until(|state| state.pc == return_address && state.registers[29] >= sp)
+    }
+
+    /// Runs until the state matches the given condition, the program exits, or a key is pressed.
+    fn run_until<F: FnMut(&State) -> bool>(&mut self, mut done: F) -> Result<()> {
+        let start = self.ins_state.state.step;
+        self.step()?;
+        while !self.ins_state.state.exited && !done(&self.ins_state.state) {
+            if (self.ins_state.state.step - start) % INTERRUPT_CHECK_INTERVAL == 0
+                && event::poll(Duration::ZERO)?
+            {
+                // Consume the key press that interrupted the run.
+                event::read()?;
+                self.message = format!(
+                    "Interrupted after {} steps",
+                    self.ins_state.state.step - start
+                );
+                return Ok(());
+            }
+            self.step()?;
+        }
+
+        if self.ins_state.state.exited {
+            self.message = format!("Exited with code {}", self.ins_state.state.exit_code);
+        } else {
+            self.message = format!("Ran {} steps", self.ins_state.state.step - start);
+        }
+        Ok(())
+    }
+
+    /// Draws the TUI.
+    fn draw(&mut self, f: &mut Frame) {
+        let [main, status] = split(
+            Direction::Vertical,
+            f.size(),
+            [Constraint::Min(0), Constraint::Length(3)],
+        );
+        let [left, right] = split(
+            Direction::Horizontal,
+            main,
+            [Constraint::Percentage(50), Constraint::Percentage(50)],
+        );
+        let [registers, disassembly] = split(
+            Direction::Vertical,
+            left,
+            [Constraint::Length(12), Constraint::Min(0)],
+        );
+
+        f.render_widget(self.registers_pane(), registers);
+        f.render_widget(self.disassembly_pane(disassembly.height), disassembly);
+        f.render_widget(self.memory_pane(right.height), right);
+        f.render_widget(self.status_bar(), status);
+    }
+
+    /// Renders the registers, highlighting those changed by the last instruction.
+    fn registers_pane(&self) -> Paragraph<'static> {
+        let state = &self.ins_state.state;
+        let changed = |i: usize| {
+            self.last
+                .as_ref()
+                .is_some_and(|last| last.pre_registers[i] != last.post_registers[i])
+        };
+
+        let mut lines = vec![Line::from(format!(
+            "pc {:08x}  next_pc {:08x}  lo {:08x}  hi {:08x}",
+            state.pc, state.next_pc, state.lo, state.hi
+        ))];
+        for row in 0..8 {
+            let spans = (0..4)
+                .flat_map(|col| {
+                    let i = col * 8 + row;
+                    let style = if changed(i) {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    };
+                    [
+                        Span::raw(format!("{:>4} ", REGISTER_NAMES[i])),
+                        Span::styled(format!("{:08x}  ", state.registers[i]), style),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(format!(
+            "step {}  heap {:08x}  exited {}",
+            state.step, state.heap, state.exited
+        )));
+
+        Paragraph::new(lines).block(pane("Registers"))
+    }
+
+    /// Renders the instructions around the program counter.
+    fn disassembly_pane(&mut self, height: u16) -> Paragraph<'static> {
+        let pc = self.ins_state.state.pc;
+        let start = pc.saturating_sub(DISASSEMBLY_CONTEXT * 4);
+        let lines = (0..height.saturating_sub(2) as u32)
+            .map(|i| {
+                let address = start.wrapping_add(i * 4);
+                let word = self
+                    .ins_state
+                    .state
+                    .memory
+                    .get_memory(address)
+                    .unwrap_or_default();
+                let line = format!(
+                    "{} {:08x}:  {:08x}  {:<32} {}",
+                    if address == pc { ">" } else { " " },
+                    address,
+                    word,
+                    Disassembly::new(word, address).to_string(),
+                    self.meta.lookup_symbol(address)
+                );
+                if address == pc {
+                    Line::styled(line, Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    Line::from(line)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Paragraph::new(lines).block(pane("Disassembly"))
+    }
+
+    /// Renders a hexdump of memory, highlighting the word accessed by the last instruction.
+    fn memory_pane(&mut self, height: u16) -> Paragraph<'static> {
+        let accessed = self.last.as_ref().and_then(|last| last.mem_address);
+        let lines = (0..height.saturating_sub(2) as u32)
+            .map(|row| {
+                let address = self.mem_address.wrapping_add(row * 16);
+                let mut spans = vec![Span::raw(format!("{:08x}: ", address))];
+                let mut ascii = String::with_capacity(16);
+                for col in 0..4 {
+                    let word_address = address.wrapping_add(col * 4);
+                    let word = self
+                        .ins_state
+                        .state
+                        .memory
+                        .get_memory(word_address)
+                        .unwrap_or_default();
+                    let style = if accessed == Some(word_address) {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default()
+                    };
+                    spans.push(Span::styled(format!("{:08x} ", word), style));
+                    ascii.extend(word.to_be_bytes().iter().map(|&b| {
+                        if b.is_ascii_graphic() {
+                            b as char
+                        } else {
+                            '.'
+                        }
+                    }));
+                }
+                spans.push(Span::raw(format!(" {}", ascii)));
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+
+        Paragraph::new(lines).block(pane("Memory"))
+    }
+
+    /// Renders the status bar, containing the active prompt or the key bindings.
+    fn status_bar(&self) -> Paragraph<'static> {
+        let line = match &self.prompt {
+            Some((Prompt::RunUntil, input)) => format!("Run until pc: {input}_"),
+            Some((Prompt::Memory, input)) => format!("Show memory at: {input}_"),
+            None => {
+                let mut line = String::from(
+                    "[s] step  [n] step over  [c] continue  [g] run until pc  [m] memory  [j/k] scroll  [q] quit",
+                );
+                if let Some((address, pre, post)) = self
+                    .last
+                    .as_ref()
+                    .and_then(|last| Some((last.mem_address?, last.mem_pre?, last.mem_post?)))
+                {
+                    line.push_str(&format!(
+                        "  |  mem[{:08x}]: {:08x} -> {:08x}",
+                        address, pre, post
+                    ));
+                }
+                if !self.message.is_empty() {
+                    line.push_str(&format!("  |  {}", self.message));
+                }
+                line
+            }
+        };
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL))
+    }
+}
+
+/// Creates a bordered pane with the given title.
+fn pane(title: &'static str) -> Block<'static> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+/// Splits an area into `N` parts along the given direction.
+fn split<const N: usize>(
+    direction: Direction,
+    area: Rect,
+    constraints: [Constraint; N],
+) -> [Rect; N] {
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    std::array::from_fn(|i| chunks[i])
+}
+
+/// Returns whether the instruction is a call, i.e. `jal` or `jalr`.
+fn is_call(instruction: u32) -> bool {
+    matches!(disasm::mnemonic(instruction), Some("jal" | "jalr"))
+}
+
+/// Parses a `0x`-prefixed or bare hex address.
+fn parse_address(s: &str) -> Option<Address> {
+    let s = s.trim();
+    Address::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::counting_state;
+    use ratatui::backend::TestBackend;
+
+    fn debugger() -> Debugger {
+        Debugger::new(
+            InstrumentedState::new(
+                counting_state(4),
+                ReplayOracle::default(),
+                io::sink(),
+                io::sink(),
+            ),
+            Metadata::default(),
+        )
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(parse_address("0x1234"), Some(0x1234));
+        assert_eq!(parse_address(" fffc "), Some(0xFFFC));
+        assert_eq!(parse_address("0xzz"), None);
+    }
+
+    #[test]
+    fn handles_keys() {
+        let mut debugger = debugger();
+        assert!(debugger.handle_key(KeyCode::Char('s')));
+        assert_eq!(debugger.ins_state.state.step, 1);
+        assert!(debugger.last.is_some());
+
+        for code in "m0x1234".chars().map(KeyCode::Char).chain([KeyCode::Enter]) {
+            assert!(debugger.handle_key(code));
+        }
+        assert_eq!(debugger.mem_address, 0x1230);
+        assert!(debugger.prompt.is_none());
+
+        for code in "mzz".chars().map(KeyCode::Char).chain([KeyCode::Enter]) {
+            assert!(debugger.handle_key(code));
+        }
+        assert_eq!(debugger.message, "Invalid address: zz");
+
+        assert!(debugger.handle_key(KeyCode::Char('c')));
+        assert!(debugger.ins_state.state.exited);
+        assert!(!debugger.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn draws_panes() {
+        let mut debugger = debugger();
+        debugger.step().unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|f| debugger.draw(f)).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+//! The `hashes` subcommand for the cannon binary
+
+use super::{mem::load_state, CannonSubcommandDispatcher};
+use alloy_primitives::B256;
+use anyhow::Result;
+use cannon::{
+    generate_hash_ladder, HashLadder, HashLadderWriter, HostOracle, ProcessPreimageOracle,
+    ReplayOracle,
+};
+use cannon_mipsevm::InstrumentedState;
+use clap::Args;
+use std::{io, path::PathBuf};
+
+/// Command line arguments for `cannon hashes`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct HashesArgs {
+    /// The path to the initial state. States at `.bin` paths are loaded from the binary state
+    /// file format.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Skip verifying the checksum embedded in the initial state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The number of steps between consecutive state hashes. Hashes are taken at the steps that
+    /// are multiples of this interval.
+    #[arg(long)]
+    every: u64,
+
+    /// The path to write the hash ladder to.
+    #[arg(long, alias = "output")]
+    out: PathBuf,
+
+    /// The last step to take a state hash at. The ladder extends until the program exits if not
+    /// specified.
+    #[arg(long)]
+    stop_at: Option<u64>,
+
+    /// Resume an interrupted run by appending to the existing ladder at `--out`, after
+    /// re-executing the initial state up to its last intact hash.
+    #[arg(long)]
+    resume: bool,
+
+    /// The preimage oracle command
+    #[arg(long, default_value = "")]
+    preimage_server: String,
+
+    /// The preimage server binary and its arguments, passed after `--`. Takes precedence over
+    /// `--preimage-server`.
+    #[arg(last = true)]
+    server_cmd: Vec<String>,
+
+    /// Serve preimages from a replay log recorded with `cannon run --preimage-record` instead of
+    /// the preimage server.
+    #[arg(long, conflicts_with_all = ["preimage_server", "server_cmd"])]
+    preimage_replay: Option<PathBuf>,
+}
+
+impl CannonSubcommandDispatcher for HashesArgs {
+    fn dispatch(self) -> Result<()> {
+        anyhow::ensure!(self.every > 0, "--every must be positive");
+        let state = load_state(&self.input, self.skip_checksum)?;
+
+        // The server's process and its side of the channels must outlive the run.
+        let (oracle, _server_proc) = match self.preimage_replay {
+            Some(ref path) => (HostOracle::Replay(ReplayOracle::open(path)?), None),
+            None => {
+                let cmd = if self.server_cmd.is_empty() {
+                    self.preimage_server
+                        .split(' ')
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                } else {
+                    self.server_cmd.clone()
+                };
+                let (oracle, server_proc) = ProcessPreimageOracle::spawn(&cmd)?;
+                (HostOracle::Process(oracle), server_proc)
+            }
+        };
+
+        let writer = if self.resume && self.out.exists() {
+            let writer = HashLadderWriter::resume(&self.out)?;
+            anyhow::ensure!(
+                writer.interval() == self.every,
+                "The hash ladder at {} has an interval of {} steps",
+                self.out.display(),
+                writer.interval()
+            );
+            tracing::info!(target: "cannon-cli::hashes", "Resuming the hash ladder at step {}", writer.next_step());
+            writer
+        } else {
+            let first_step = state.step.div_ceil(self.every) * self.every;
+            HashLadderWriter::create(&self.out, self.every, first_step)?
+        };
+
+        let mut ins = InstrumentedState::new(state, oracle, io::sink(), io::sink());
+        let pushed = generate_hash_ladder(&mut ins, writer, self.stop_at)?;
+
+        let ladder = HashLadder::load(&self.out)?;
+        tracing::info!(target: "cannon-cli::hashes", "Wrote {} state hashes ({} in this run) to {}", ladder.hashes.len(), pushed, self.out.display());
+        if let Some(last) = ladder.hashes.last() {
+            println!(
+                "{} hashes up to step {}, last: {}",
+                ladder.hashes.len(),
+                ladder.step_of(ladder.hashes.len() - 1),
+                B256::from(*last)
+            );
+        }
+
+        Ok(())
+    }
+}
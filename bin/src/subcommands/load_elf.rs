@@ -1,10 +1,16 @@
 //! The `load-elf` subcommand for the cannon binary
+//!
+//! Flags documented as extensions opt into behavior that the on-chain `MIPS` contract does not
+//! implement, so the resulting state cannot be proven; see
+//! [cannon_mipsevm::State#extensions].
 
 use super::CannonSubcommandDispatcher;
 use alloy_primitives::B256;
 use anyhow::Result;
-use cannon::gz::compress_bytes;
-use cannon_mipsevm::{load_elf, patch_go, patch_stack, StateWitnessHasher};
+use cannon_mipsevm::{
+    load_elf, patch_stack, patch_stack_with_args, ser::Codec, AuxVector, Clock, EntropySource,
+    MemoryRegions, Metadata, PageEncoding, PageProtection, PatchSet,
+};
 use clap::Args;
 use std::{
     fmt::Display,
@@ -22,20 +28,121 @@ pub(crate) struct LoadElfArgs {
     #[arg(long)]
     path: PathBuf,
 
-    /// The type of patch to perform on the ELF file.
-    #[arg(long, default_values = ["go", "stack"])]
+    /// The types of patch to perform on the ELF file (`go`, `stack` or `none`), separated by
+    /// commas or given repeatedly. `none` cannot be combined with other patches.
+    #[arg(long, alias = "patch", value_delimiter = ',', default_values = ["go", "stack"])]
     patch_kind: Vec<PatchKind>,
 
+    /// The runtime patch set applied by the `go` patch: a builtin set (`go` or `none`) or the
+    /// path to a TOML file mapping symbols to their replacements.
+    #[arg(long, default_value = "go")]
+    patch_set: String,
+
     /// The output path to write the JSON state to. State will be dumped to stdout if set to `-`.
     /// Not written if not provided.
-    #[arg(long)]
+    #[arg(long, alias = "out")]
     output: Option<String>,
+
+    /// The output path to write the program's metadata, containing its symbols, to.
+    #[arg(long)]
+    meta: Option<PathBuf>,
+
+    /// The compression codec (`none`, `zlib`, `gzip` or `zstd`) to write the state with. Selected
+    /// by the extension of the output path if not specified, falling back to `gzip`.
+    #[arg(long)]
+    codec: Option<Codec>,
+
+    /// Emulate `mmap`, `munmap` and `brk` with Linux semantics by tracking the guest's address
+    /// space. This is an extension.
+    #[arg(long)]
+    address_space: bool,
+
+    /// Allow the guest to `open` and `read` a small set of whitelisted virtual files with
+    /// deterministic contents. This is an extension.
+    #[arg(long)]
+    virtual_files: bool,
+
+    /// Serve the `getrandom` syscall deterministically, from either the state hash and step
+    /// (`state-hash`) or a stream derived from a 32-byte hex seed. This is an extension.
+    #[arg(long)]
+    entropy: Option<EntropySource>,
+
+    /// Serve the `clock_gettime` and `gettimeofday` syscalls from the step counter, advancing the
+    /// guest's clocks by the given number of nanoseconds per step. This is an extension.
+    #[arg(long)]
+    clock_rate: Option<u64>,
+
+    /// The guest's real time at step zero, in seconds since the Unix epoch. Defaults to the epoch
+    /// itself.
+    #[arg(long, requires = "clock_rate")]
+    clock_epoch: Option<u64>,
+
+    /// Execute the MIPS32r2 bit-manipulation instructions (`seb`, `seh`, `wsbh`, `ext`, `ins`,
+    /// `rotr` and `rotrv`) that LLVM emits for Rust guests. This is an extension.
+    #[arg(long)]
+    mips32r2: bool,
+
+    /// Serve the `futex` syscall with the semantics of a single-threaded guest, as newer Go
+    /// runtimes call it on startup: waits return immediately or time out, and wakes find no
+    /// waiters. This is an extension.
+    #[arg(long)]
+    futex: bool,
+
+    /// Enforce the permissions of the ELF segments: writes to read-only pages such as `.text`
+    /// and execution from non-executable pages fault instead of silently succeeding. Intended to
+    /// catch guest miscompiles early; the resulting state cannot be proven.
+    #[arg(long)]
+    strict_protection: bool,
+
+    /// Name the program, heap and stack regions of the guest's address space in the state, so
+    /// that faults can be attributed to the region they occurred in.
+    #[arg(long)]
+    annotate_regions: bool,
+
+    /// Guard the page below the stack: any guest access to it aborts the run with a guest stack
+    /// overflow. Requires the `stack` patch.
+    #[arg(long)]
+    stack_guard: bool,
+
+    /// An argument to pass to the guest, given repeatedly in order. By convention, the first
+    /// argument is the program name. Laid out on the initial stack by the `stack` patch.
+    #[arg(long = "arg", allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// An environment variable to pass to the guest, as `KEY=VALUE`, given repeatedly. Laid out on
+    /// the initial stack by the `stack` patch.
+    #[arg(long = "env", value_parser = parse_env_var)]
+    env: Vec<String>,
+
+    /// Pass a realistic auxiliary vector to the guest, with the program headers, entry point and
+    /// random bytes derived from the ELF file, instead of only the page size and fixed random
+    /// bytes. Laid out on the initial stack by the `stack` patch.
+    #[arg(long)]
+    auxv: bool,
+
+    /// Serialize zero pages by their index alone, and prefix the data of all other pages with a
+    /// checksummed header. Shrinks the states of sparse address spaces considerably, but the
+    /// state cannot be read by Go Cannon.
+    #[arg(long)]
+    compact_pages: bool,
+}
+
+/// Parses a `KEY=VALUE` environment variable.
+fn parse_env_var(s: &str) -> Result<String> {
+    match s.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(s.to_string()),
+        _ => Err(anyhow::anyhow!(
+            "Invalid environment variable {}, expected KEY=VALUE",
+            s
+        )),
+    }
 }
 
 #[derive(Clone, Debug)]
 enum PatchKind {
     Go,
     Stack,
+    None,
 }
 
 impl FromStr for PatchKind {
@@ -45,6 +152,7 @@ impl FromStr for PatchKind {
         match s {
             "go" => Ok(PatchKind::Go),
             "stack" => Ok(PatchKind::Stack),
+            "none" => Ok(PatchKind::None),
             _ => Err(anyhow::anyhow!("Invalid patch kind: {}", s)),
         }
     }
@@ -55,12 +163,19 @@ impl Display for PatchKind {
         match self {
             PatchKind::Go => write!(f, "Go"),
             PatchKind::Stack => write!(f, "Stack"),
+            PatchKind::None => write!(f, "None"),
         }
     }
 }
 
 impl CannonSubcommandDispatcher for LoadElfArgs {
     fn dispatch(self) -> Result<()> {
+        anyhow::ensure!(
+            self.patch_kind.len() == 1
+                || !self.patch_kind.iter().any(|p| matches!(p, PatchKind::None)),
+            "The `none` patch cannot be combined with other patches"
+        );
+
         tracing::info!(target: "cannon-cli::load-elf", "Loading ELF file @ {}", self.path.display());
         let file = File::open(&self.path)?;
         let file_sz = file.metadata()?.len();
@@ -70,26 +185,102 @@ impl CannonSubcommandDispatcher for LoadElfArgs {
         let mut state = load_elf(&elf_raw)?;
         tracing::info!(target: "cannon-cli::load-elf", "Loaded ELF file and constructed the State");
 
+        let custom_stack = self.auxv || !(self.args.is_empty() && self.env.is_empty());
+        if custom_stack
+            && !self
+                .patch_kind
+                .iter()
+                .any(|p| matches!(p, PatchKind::Stack))
+        {
+            anyhow::bail!(
+                "Passing arguments, environment variables or the auxiliary vector requires the stack patch"
+            );
+        }
         for p in self.patch_kind {
+            if matches!(p, PatchKind::None) {
+                continue;
+            }
             tracing::info!(target: "cannon-cli::load-elf", "Patching the ELF file with patch type = {p}...");
             match p {
-                PatchKind::Go => patch_go(&elf_raw, &mut state),
-                PatchKind::Stack => patch_stack(&mut state),
+                PatchKind::Go => {
+                    let patch_set = PatchSet::resolve(&self.patch_set)?;
+                    let applied = patch_set.apply(&elf_raw, &mut state)?;
+                    tracing::info!(target: "cannon-cli::load-elf", "Applied {} patches of the {} patch set", applied, patch_set.name);
+                    Ok(())
+                }
+                PatchKind::Stack if !custom_stack => patch_stack(&mut state),
+                PatchKind::Stack => {
+                    let auxv = if self.auxv {
+                        AuxVector::from_elf(&elf_raw)?
+                    } else {
+                        AuxVector::default()
+                    };
+                    patch_stack_with_args(&mut state, &self.args, &self.env, &auxv)
+                }
+                PatchKind::None => Ok(()),
             }?;
         }
 
+        if self.address_space {
+            let heap_start = state.heap;
+            state.enable_address_space(heap_start);
+        }
+        if self.virtual_files {
+            state.enable_virtual_files();
+        }
+        if let Some(entropy) = self.entropy {
+            state.enable_entropy(entropy);
+        }
+        if let Some(rate) = self.clock_rate {
+            state.enable_clock(Clock::new(rate).with_epoch(self.clock_epoch.unwrap_or_default()));
+        }
+        if self.mips32r2 {
+            state.enable_mips32r2();
+        }
+        if self.futex {
+            state.enable_futex();
+        }
+        if self.strict_protection {
+            state.enable_protection(PageProtection::from_elf(&elf_raw)?);
+        }
+        if self.annotate_regions {
+            state.regions = Some(MemoryRegions::from_elf(
+                &elf_raw,
+                state.heap,
+                state.registers[29],
+            )?);
+        }
+        if self.stack_guard {
+            if state.registers[29] == 0 {
+                anyhow::bail!("The stack guard requires the stack patch");
+            }
+            state.enable_stack_guard();
+        }
+
+        if let Some(ref meta_path) = self.meta {
+            Metadata::from_elf(&elf_raw)?.save(meta_path)?;
+            tracing::info!(target: "cannon-cli::load-elf", "Wrote metadata to {}", meta_path.display());
+        }
+
         if let Some(ref path_str) = self.output {
+            if self.compact_pages {
+                state.memory.set_page_encoding(PageEncoding::Compact);
+            }
+            state.seal()?;
             if path_str == "-" {
                 println!("{}", serde_json::to_string(&state)?);
             } else {
                 let mut writer = BufWriter::new(File::create(path_str)?);
                 let ser_state = serde_json::to_vec(&state)?;
-                let gz_state = compress_bytes(&ser_state)?;
-                writer.write_all(&gz_state)?;
+                let codec = self
+                    .codec
+                    .or_else(|| Codec::from_path(path_str))
+                    .unwrap_or(Codec::Gzip);
+                writer.write_all(&codec.compress(&ser_state)?)?;
             }
         }
 
-        tracing::info!(target: "cannon-cli::load-elf", "Patched the ELF file and dumped the State successfully. state hash: {} mem size: {} pages: {}", B256::from(state.encode_witness()?.state_hash()), state.memory.usage(), state.memory.page_count());
+        tracing::info!(target: "cannon-cli::load-elf", "Patched the ELF file and dumped the State successfully. state hash: {} mem size: {} pages: {}", B256::from(state.state_hash()?), state.memory.usage(), state.memory.page_count());
 
         Ok(())
     }
@@ -0,0 +1,168 @@
+//! The `mem` subcommand for the cannon binary
+
+use super::CannonSubcommandDispatcher;
+use alloy_primitives::B256;
+use anyhow::Result;
+use cannon_mipsevm::{
+    ser::{self, Codec},
+    Address, Page, State,
+};
+use clap::{Args, Subcommand};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Command line arguments for `cannon mem`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct MemArgs {
+    /// The `mem` subcommand to run
+    #[command(subcommand)]
+    command: MemCommand,
+}
+
+/// The subcommands of `cannon mem`
+#[derive(Subcommand, Debug)]
+enum MemCommand {
+    /// Exports the memory of a state as a sparse image.
+    Export(ExportArgs),
+    /// Imports a sparse image into the memory of a state.
+    Import(ImportArgs),
+}
+
+/// Command line arguments for `cannon mem export`
+#[derive(Args, Debug)]
+struct ExportArgs {
+    /// The path to the input JSON state. States at `.bin` paths are loaded from the binary state
+    /// file format.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Skip verifying the checksum embedded in the state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The path to write the sparse memory image to. Each allocated page is written as its
+    /// big-endian 32-bit base address followed by the page data, in ascending address order.
+    #[arg(long)]
+    output: PathBuf,
+}
+
+/// Command line arguments for `cannon mem import`
+#[derive(Args, Debug)]
+struct ImportArgs {
+    /// The path to the sparse memory image to import, as written by `cannon mem export`.
+    #[arg(long)]
+    image: PathBuf,
+
+    /// The path to the input JSON state whose memory the image is imported into. The image is
+    /// imported into an otherwise empty state if not provided.
+    #[arg(long)]
+    input: Option<PathBuf>,
+
+    /// Skip verifying the checksum embedded in the input state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The path to write the resulting state to. States at `.bin` paths are written in the binary
+    /// state file format.
+    #[arg(long)]
+    output: PathBuf,
+
+    /// The compression codec (`none`, `zlib`, `gzip` or `zstd`) to write the state with. Selected
+    /// by the extension of the output path if not specified, falling back to `gzip`.
+    #[arg(long)]
+    codec: Option<Codec>,
+}
+
+impl CannonSubcommandDispatcher for MemArgs {
+    fn dispatch(self) -> Result<()> {
+        match self.command {
+            MemCommand::Export(args) => args.dispatch(),
+            MemCommand::Import(args) => args.dispatch(),
+        }
+    }
+}
+
+impl CannonSubcommandDispatcher for ExportArgs {
+    fn dispatch(self) -> Result<()> {
+        let state = load_state(&self.input, self.skip_checksum)?;
+        let image = state.memory.dump_sparse();
+
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        for (address, data) in image.iter() {
+            writer.write_all(&address.to_be_bytes())?;
+            writer.write_all(data)?;
+        }
+        writer.flush()?;
+
+        tracing::info!(target: "cannon-cli::mem", "Exported {} pages to {}", image.len(), self.output.display());
+        Ok(())
+    }
+}
+
+impl CannonSubcommandDispatcher for ImportArgs {
+    fn dispatch(self) -> Result<()> {
+        let mut state = match self.input {
+            Some(ref input) => load_state(input, self.skip_checksum)?,
+            None => State::default(),
+        };
+
+        let mut reader = BufReader::new(File::open(&self.image)?);
+        let mut image = Vec::new();
+        loop {
+            let mut address = [0u8; 4];
+            match reader.read_exact(&mut address) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let mut data = [0u8; std::mem::size_of::<Page>()];
+            reader.read_exact(&mut data).map_err(|_| {
+                anyhow::anyhow!(
+                    "Sparse image is truncated in the page at {:08x}",
+                    Address::from_be_bytes(address)
+                )
+            })?;
+            image.push((Address::from_be_bytes(address), data));
+        }
+        let page_count = image.len();
+        state.memory.restore_sparse(image)?;
+
+        state.seal()?;
+        if self.output.extension().is_some_and(|ext| ext == "bin") {
+            state.save_binary(&self.output)?;
+        } else {
+            let codec = self
+                .codec
+                .or_else(|| Codec::from_path(&self.output))
+                .unwrap_or(Codec::Gzip);
+            let raw = codec.compress(&serde_json::to_vec(&state)?)?;
+            ser::replace_file(&self.output, |writer| writer.write_all(&raw))?;
+        }
+
+        tracing::info!(target: "cannon-cli::mem", "Imported {} pages into the state. state hash: {}", page_count, B256::from(state.state_hash()?));
+        Ok(())
+    }
+}
+
+/// Loads a [State] from the given path, memory-mapping states at `.bin` paths and decompressing
+/// JSON states otherwise. The checksum embedded in the state, if any, is verified unless
+/// `skip_checksum` is set.
+pub(super) fn load_state(path: &Path, skip_checksum: bool) -> Result<State> {
+    let mut state = if path.extension().is_some_and(|ext| ext == "bin") {
+        // SAFETY: States are only written with `ser::replace_file`, which renames over the file
+        // rather than modifying it in place.
+        unsafe { State::load_mmapped(path)? }
+    } else {
+        let raw = fs::read(path)?;
+        let codec = Codec::from_path(path).unwrap_or_else(|| Codec::detect(&raw));
+        State::from_json(&codec.decompress(&raw)?)?
+    };
+    if !skip_checksum {
+        state.verify_checksum()?;
+    }
+    Ok(state)
+}
@@ -3,8 +3,19 @@
 use anyhow::Result;
 use clap::Subcommand;
 
+mod attest;
+mod bisect;
+mod check_elf;
+#[cfg(feature = "tui")]
+mod debug;
+mod hashes;
 mod load_elf;
+mod mem;
+mod proof;
+mod resume;
 mod run;
+mod step_one;
+mod verify_proof;
 mod witness;
 
 pub(crate) trait CannonSubcommandDispatcher {
@@ -18,6 +29,26 @@ pub(crate) enum CannonSubcommand {
     Run(run::RunArgs),
     Witness(witness::WitnessArgs),
     LoadElf(load_elf::LoadElfArgs),
+    Mem(mem::MemArgs),
+    Proof(proof::ProofArgs),
+    /// Writes the state hash at every Nth step to a compact, checksummed hash ladder file.
+    Hashes(hashes::HashesArgs),
+    /// Finds the first step at which the native VM diverges from a reference implementation.
+    Bisect(bisect::BisectArgs),
+    /// Steps through the execution of a state in an interactive terminal debugger.
+    #[cfg(feature = "tui")]
+    Debug(debug::DebugArgs),
+    /// Resumes a journaled run from its last durable snapshot after a crash.
+    Resume(resume::ResumeArgs),
+    /// Verifies a memory proof emitted by the VM against a memory root.
+    VerifyProof(verify_proof::VerifyProofArgs),
+    /// Scans an ELF file for features that the emulator does not support before running it.
+    CheckElf(check_elf::CheckElfArgs),
+    /// Checks the artifacts of a run against its chain-of-custody manifest.
+    Attest(attest::AttestArgs),
+    /// Executes a single instruction of a state, printing the registers it changed and optionally
+    /// writing its proof and post-state.
+    StepOne(step_one::StepOneArgs),
 }
 
 impl CannonSubcommandDispatcher for CannonSubcommand {
@@ -26,6 +57,17 @@ impl CannonSubcommandDispatcher for CannonSubcommand {
             CannonSubcommand::Run(args) => args.dispatch(),
             CannonSubcommand::Witness(args) => args.dispatch(),
             CannonSubcommand::LoadElf(args) => args.dispatch(),
+            CannonSubcommand::Mem(args) => args.dispatch(),
+            CannonSubcommand::Proof(args) => args.dispatch(),
+            CannonSubcommand::Hashes(args) => args.dispatch(),
+            CannonSubcommand::Bisect(args) => args.dispatch(),
+            #[cfg(feature = "tui")]
+            CannonSubcommand::Debug(args) => args.dispatch(),
+            CannonSubcommand::Resume(args) => args.dispatch(),
+            CannonSubcommand::VerifyProof(args) => args.dispatch(),
+            CannonSubcommand::CheckElf(args) => args.dispatch(),
+            CannonSubcommand::Attest(args) => args.dispatch(),
+            CannonSubcommand::StepOne(args) => args.dispatch(),
         }
     }
 }
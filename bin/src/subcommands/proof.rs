@@ -0,0 +1,83 @@
+//! The `proof` subcommand for the cannon binary
+
+use super::CannonSubcommandDispatcher;
+use alloy_primitives::{hex, B256};
+use anyhow::Result;
+use cannon::Proof;
+use clap::{Args, ValueEnum};
+use std::{fs, io::Write, path::PathBuf};
+
+/// Command line arguments for `cannon proof`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct ProofArgs {
+    /// The path to the input JSON proof, as written by `cannon run --proof-at`.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// The format to write the proof in.
+    #[arg(long, value_enum, default_value_t = ProofFormat::Json)]
+    format: ProofFormat,
+
+    /// The local context of the dispute game. If specified, the step calldata is encoded for
+    /// versions of the `MIPS` contract whose `step` function takes the local context as a third
+    /// argument, and local preimages are loaded under the key localized with it.
+    #[arg(long)]
+    local_context: Option<B256>,
+
+    /// The path to write the output to. The output is written to stdout if not specified.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// The output formats of `cannon proof`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ProofFormat {
+    /// The proof as JSON, upgraded to the current proof version.
+    Json,
+    /// Ready-to-submit transaction calldata, one `0x`-prefixed hex string per line in the order
+    /// that the transactions must be sent: the `PreimageOracle` load call, if the step reads a
+    /// preimage, followed by the `MIPS.step` call.
+    Calldata,
+}
+
+impl CannonSubcommandDispatcher for ProofArgs {
+    fn dispatch(self) -> Result<()> {
+        let proof = Proof::from_json(&fs::read(&self.input)?)?;
+
+        let output = match self.format {
+            ProofFormat::Json => serde_json::to_vec(&proof)?,
+            ProofFormat::Calldata => {
+                let (oracle_input, step_input) = match self.local_context {
+                    Some(local_context) => (
+                        proof.oracle_input_with_local_context(local_context),
+                        proof
+                            .step_witness()
+                            .encode_with_local_context(local_context.0.into())
+                            .to_vec(),
+                    ),
+                    None => (proof.oracle_input.clone(), proof.step_input.clone()),
+                };
+
+                let mut calldata = String::new();
+                if let Some(ref oracle_input) = oracle_input {
+                    calldata.push_str(&hex::encode_prefixed(oracle_input));
+                    calldata.push('\n');
+                }
+                calldata.push_str(&hex::encode_prefixed(step_input));
+                calldata.push('\n');
+                calldata.into_bytes()
+            }
+        };
+
+        match self.output {
+            Some(ref path) => {
+                fs::write(path, output)?;
+                tracing::info!(target: "cannon-cli::proof", "Wrote the proof at step {} to {}", proof.step, path.display());
+            }
+            None => std::io::stdout().write_all(&output)?,
+        }
+
+        Ok(())
+    }
+}
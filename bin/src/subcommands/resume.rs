@@ -0,0 +1,46 @@
+//! The `resume` subcommand for the cannon binary
+
+use super::{run::RunArgs, CannonSubcommandDispatcher};
+use anyhow::Result;
+use cannon::Journal;
+use clap::Args;
+use std::path::PathBuf;
+
+/// Command line arguments for `cannon resume`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct ResumeArgs {
+    /// The path of the journal written by `cannon run --journal`.
+    #[arg(long)]
+    journal: PathBuf,
+}
+
+impl CannonSubcommandDispatcher for ResumeArgs {
+    fn dispatch(self) -> Result<()> {
+        let journal = Journal::load(&self.journal)?;
+        anyhow::ensure!(
+            !journal.is_finished(),
+            "The run journaled at {} has already finished",
+            self.journal.display()
+        );
+
+        let (entry, _) = journal.recover()?.ok_or(anyhow::anyhow!(
+            "The journal at {} has no intact snapshot to resume from",
+            self.journal.display()
+        ))?;
+        tracing::info!(target: "cannon-cli::resume", "Resuming from the snapshot {} at step {} (state hash {})", entry.snapshot, entry.step, entry.state_hash);
+
+        // The relative paths of the command are resolved against the directory it was run in.
+        let journal_path = std::path::absolute(&self.journal)?;
+        if let Some(ref cwd) = journal.header.cwd {
+            std::env::set_current_dir(cwd)?;
+        }
+
+        RunArgs::resume_from(
+            &journal.header.command,
+            entry.snapshot.clone(),
+            journal_path.display().to_string(),
+        )?
+        .dispatch()
+    }
+}
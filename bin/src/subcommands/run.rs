@@ -2,25 +2,67 @@
 
 use super::CannonSubcommandDispatcher;
 use anyhow::Result;
-use cannon::KernelBuilder;
-use clap::Args;
+use cannon::{CancellationToken, KernelBuilder, Outcome, Progress, DEFAULT_PANIC_SYMBOLS};
+use cannon_mipsevm::{ser::Codec, VMStatus};
+use clap::{Args, Parser};
 
 /// Command line arguments for `cannon run`
+///
+/// The process exit code reflects the status of the program: 0 if it exited with a valid status,
+/// 20 if it exited with an invalid status, 21 if it panicked, 22 if the run stopped before the
+/// program exited, and 130 if the run was interrupted. Other failures exit with 1. If panics are
+/// detected with `--detect-panics` or `--panic-symbol`, a program that exits with a status other
+/// than valid after entering a panic handler crashed rather than proved its claim invalid, and
+/// exits with `--panic-exit-code` (23 by default).
 #[derive(Args, Debug)]
 #[command(author, version, about)]
 pub(crate) struct RunArgs {
     /// The preimage oracle command
-    #[arg(long)]
-    preimage_server: String,
+    #[arg(
+        long,
+        required_unless_present_any = ["server_cmd", "preimage_replay", "preimage_grpc"]
+    )]
+    preimage_server: Option<String>,
+
+    /// The preimage server binary and its arguments, passed after `--`. Takes precedence over
+    /// `--preimage-server`.
+    #[arg(last = true)]
+    server_cmd: Vec<String>,
 
-    /// The path to the input JSON state.
+    /// The path to the input JSON state. States at `.bin` paths are loaded from the binary state
+    /// file format by memory-mapping them.
     #[arg(long)]
     input: String,
 
-    /// The path to the output JSON state.
+    /// The path to the output JSON state. States at `.bin` paths are written in the binary state
+    /// file format.
     #[arg(long)]
     output: Option<String>,
 
+    /// The compression codec (`none`, `zlib`, `gzip` or `zstd`) of the input state, the output
+    /// state and snapshots. Selected by file extension if not specified.
+    #[arg(long)]
+    codec: Option<Codec>,
+
+    /// The path to the metadata of the program, containing its symbols.
+    #[arg(long)]
+    meta: Option<String>,
+
+    /// Detect the guest entering the panic handlers of the Go and Rust runtimes, as with
+    /// `--panic-symbol`. Requires `--meta`.
+    #[arg(long)]
+    detect_panics: bool,
+
+    /// The name of a panic handler of the guest, where `*` matches any sequence of characters.
+    /// A program that exits with a status other than valid after entering one crashed, and the
+    /// run exits with `--panic-exit-code`. Requires `--meta`.
+    #[arg(long = "panic-symbol")]
+    panic_symbols: Vec<String>,
+
+    /// The exit code of a run whose program crashed.
+    #[arg(long, default_value_t = GUEST_PANIC_EXIT_CODE)]
+    panic_exit_code: i32,
+
     /// The step to generate an output proof at.
     #[arg(long)]
     proof_at: Option<String>,
@@ -30,6 +72,29 @@ pub(crate) struct RunArgs {
     #[arg(long, aliases = ["proof-fmt"])]
     proof_format: Option<String>,
 
+    /// The path of a JSONL file that all proofs are written to, one per line, instead of one
+    /// file per proof.
+    #[arg(long, conflicts_with = "proof_format")]
+    proof_jsonl: Option<String>,
+
+    /// The path of a zstd-compressed tar archive that all proofs are bundled into, instead of
+    /// one file per proof. An index of the proofs' offsets is written next to it, at the same
+    /// path followed by `.index.json`. Requires the `zstd` feature.
+    #[arg(long, conflicts_with_all = ["proof_format", "proof_jsonl"])]
+    proof_bundle: Option<String>,
+
+    /// The number of background threads that generate and write proofs, so that merkleizing the
+    /// memory and proof i/o do not stall execution. One thread generates the merkle proofs, and
+    /// any others write proofs to one file each in parallel. Proofs are generated on the
+    /// interpreter thread, and written by a single background thread, if set to 0.
+    #[arg(long, default_value_t = 1)]
+    proof_workers: usize,
+
+    /// Record every memory access of the proven steps, with the accessed word's value before and
+    /// after the access, and attach them to the proofs as `memAccesses`.
+    #[arg(long)]
+    proof_access_log: bool,
+
     /// The step pattern to generate state snapshots at.
     #[arg(long)]
     snapshot_at: Option<String>,
@@ -38,28 +103,248 @@ pub(crate) struct RunArgs {
     #[arg(long, aliases = ["snapshot-fmt"])]
     snapshot_format: Option<String>,
 
-    /// The instruction step to stop running at.
+    /// The instruction step to stop running at, or `hash:<claim>` to stop at the first state whose
+    /// hash equals the claimed state hash.
     #[arg(long)]
     stop_at: Option<String>,
 
-    /// The pattern to print information at.
+    /// The step pattern to print progress reports at, containing the speed of the run, its
+    /// memory usage and, if stopping at a `=<step>` pattern, the estimated time remaining.
     #[arg(long)]
     info_at: Option<String>,
+
+    /// Use the threaded execution mode for steps that do not require a proof.
+    #[arg(long)]
+    threaded: bool,
+
+    /// The `host:port` address of a companion Go Cannon process to cross-verify state hashes
+    /// against.
+    #[arg(long)]
+    shadow_rpc: Option<String>,
+
+    /// The step pattern to cross-verify state hashes with the shadow Cannon at.
+    #[arg(long)]
+    shadow_at: Option<String>,
+
+    /// The path to record every preimage served during the run to, for later replay.
+    #[arg(long)]
+    preimage_record: Option<String>,
+
+    /// Serve preimages from a replay log recorded with `--preimage-record` instead of the
+    /// preimage server.
+    #[arg(long, conflicts_with_all = ["preimage_server", "server_cmd", "preimage_record"])]
+    preimage_replay: Option<String>,
+
+    /// The URL of a gRPC host to request preimages from instead of the preimage server, e.g.
+    /// `https://host:50051`. Requires the `grpc` feature.
+    #[arg(long, conflicts_with_all = ["preimage_server", "server_cmd", "preimage_record", "preimage_replay"])]
+    preimage_grpc: Option<String>,
+
+    /// The maximum number of bytes of preimages to cache in memory, so that preimages that the
+    /// guest reads again are not requested from the host. Disabled if zero.
+    #[arg(long, default_value_t = 0)]
+    preimage_cache: usize,
+
+    /// Execute the precompiles hinted by the guest with `l1-precompile` locally, serving their
+    /// results (preimage key type 6) without requesting them from the host.
+    #[arg(long)]
+    execute_precompiles: bool,
+
+    /// The path to a PEM encoded CA certificate to verify the `--preimage-grpc` host with,
+    /// instead of the system's root certificates.
+    #[arg(long, requires = "preimage_grpc")]
+    preimage_grpc_ca: Option<String>,
+
+    /// The path to export a record of every executed instruction to, for ingestion by zk proving
+    /// pipelines. Written as Parquet if the path ends in `.parquet` (requires the `parquet`
+    /// feature), and as JSONL otherwise. Disables the threaded execution mode.
+    #[arg(long)]
+    trace_out: Option<String>,
+
+    /// The path to write the coverage of the guest program to. Paths ending in `.info` or `.lcov`
+    /// receive an LCOV report symbolized with `--meta`, with one line per instruction of each
+    /// symbol. All other paths receive the executed addresses, one per line, for `addr2line`.
+    #[arg(long)]
+    coverage_out: Option<String>,
+
+    /// The path to write a histogram of the executed instructions to, listing every encounter
+    /// with an unsupported instruction or syscall with its address and symbol from `--meta`.
+    /// Paths ending in `.json` receive a JSON report, all others a text report. The report is
+    /// also written when the run fails on an unsupported instruction.
+    #[arg(long)]
+    histogram_out: Option<String>,
+
+    /// Skip verifying the checksum embedded in the input state. States with a checksum that does
+    /// not match their contents are otherwise rejected as truncated or corrupted.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// Serialize the zero pages of the output state and snapshots by their index alone, and
+    /// prefix the data of all other pages with a checksummed header. The states cannot be read by
+    /// Go Cannon.
+    #[arg(long)]
+    compact_pages: bool,
+
+    /// Forward the JSON log lines that the guest writes to stderr (e.g. `op-program` with
+    /// `--log.format=json`) through the host's logger, at the levels given by the guest.
+    #[arg(long)]
+    guest_logs: bool,
+
+    /// Only forward guest log lines that start with the given prefix, which is stripped before
+    /// parsing.
+    #[arg(long, requires = "guest_logs")]
+    guest_log_prefix: Option<String>,
+
+    /// The path of an append-only journal that every snapshot is recorded in, so that the run
+    /// can be resumed with `cannon resume --journal` after a crash.
+    #[arg(long)]
+    journal: Option<String>,
+
+    /// The number of journal entries that are appended between syncs.
+    #[arg(long, default_value_t = cannon::DEFAULT_JOURNAL_SYNC_EVERY, requires = "journal")]
+    journal_sync_every: usize,
+
+    /// The path to write a hash-linked manifest of the proofs, snapshots and states written by
+    /// the run to, for checking them later with `cannon attest verify`.
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// The path of the ELF that the input state was loaded from, whose hash is listed in the
+    /// manifest.
+    #[arg(long, requires = "manifest")]
+    manifest_elf: Option<String>,
+}
+
+/// The wrapper that the arguments of a journaled run are parsed with when it is resumed.
+#[derive(Parser, Debug)]
+struct JournaledRun {
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+impl RunArgs {
+    /// Parses the arguments of a journaled run, and resumes it from the given snapshot.
+    ///
+    /// ### Takes
+    /// - `command`: The arguments of the run, as recorded in the header of its journal.
+    /// - `input`: The path of the snapshot to resume from.
+    /// - `journal`: The path of the journal, which the resumed run appends to.
+    pub(crate) fn resume_from(command: &[String], input: String, journal: String) -> Result<Self> {
+        let mut run = JournaledRun::try_parse_from(
+            std::iter::once("run".to_string()).chain(command.iter().cloned()),
+        )?
+        .run;
+        run.input = input;
+        run.journal = Some(journal);
+        Ok(run)
+    }
+}
+
+/// The exit code of a run that was interrupted by a signal.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// The exit code of a run that ended with a non-valid [VMStatus], offset by the status.
+const VM_STATUS_EXIT_CODE_BASE: i32 = 19;
+
+/// The default exit code of a run whose program crashed, following the [VMStatus] exit codes.
+const GUEST_PANIC_EXIT_CODE: i32 = VM_STATUS_EXIT_CODE_BASE + 4;
+
+/// Returns the process exit code for a run that ended with the given [VMStatus].
+fn status_exit_code(status: VMStatus) -> i32 {
+    match status {
+        VMStatus::Valid => 0,
+        status => VM_STATUS_EXIT_CODE_BASE + status as i32,
+    }
 }
 
 impl CannonSubcommandDispatcher for RunArgs {
     fn dispatch(self) -> Result<()> {
+        // Upon the first SIGINT / SIGTERM, finish the current step and write a resumable
+        // snapshot. Upon the second, exit immediately.
+        let cancellation = CancellationToken::default();
+        ctrlc::set_handler({
+            let cancellation = cancellation.clone();
+            move || {
+                if cancellation.is_cancelled() {
+                    std::process::exit(INTERRUPTED_EXIT_CODE);
+                }
+                tracing::warn!(target: "cannon-cli::run", "Interrupted, writing a resumable snapshot. Interrupt again to exit immediately.");
+                cancellation.cancel();
+            }
+        })?;
+
+        // The arguments of the run, without the binary and subcommand, are recorded in the
+        // journal so that `cannon resume` can restart it, and listed in the manifest.
+        let command = std::env::args()
+            .skip_while(|arg| arg != "run")
+            .skip(1)
+            .collect::<Vec<_>>();
+
+        let mut panic_symbols = self.panic_symbols;
+        if self.detect_panics {
+            panic_symbols.extend(
+                DEFAULT_PANIC_SYMBOLS
+                    .iter()
+                    .map(|symbol| symbol.to_string()),
+            );
+        }
+
         let kernel = KernelBuilder::default()
-            .with_preimage_server(self.preimage_server.replace('"', ""))
+            .with_preimage_server(self.preimage_server.unwrap_or_default().replace('"', ""))
+            .with_preimage_server_args(self.server_cmd)
+            .with_meta(self.meta)
+            .with_panic_symbols(panic_symbols)
             .with_input(self.input)
             .with_output(self.output)
+            .with_codec(self.codec)
             .with_proof_at(self.proof_at)
             .with_proof_format(self.proof_format)
+            .with_proof_jsonl(self.proof_jsonl)
+            .with_proof_bundle(self.proof_bundle)
+            .with_proof_workers(self.proof_workers)
+            .with_proof_access_log(self.proof_access_log)
             .with_snapshot_at(self.snapshot_at)
             .with_snapshot_format(self.snapshot_format)
             .with_stop_at(self.stop_at)
             .with_info_at(self.info_at)
-            .build()?;
-        kernel.run()
+            .with_progress_sink(|progress: &Progress| {
+                tracing::info!(target: "cannon-cli::run", "{}", progress);
+            })
+            .with_threaded(self.threaded)
+            .with_shadow_rpc(self.shadow_rpc)
+            .with_shadow_at(self.shadow_at)
+            .with_preimage_record(self.preimage_record)
+            .with_preimage_replay(self.preimage_replay)
+            .with_preimage_grpc(self.preimage_grpc, self.preimage_grpc_ca)
+            .with_preimage_cache(self.preimage_cache)
+            .with_execute_precompiles(self.execute_precompiles)
+            .with_cancellation(cancellation)
+            .with_trace_out(self.trace_out)
+            .with_coverage_out(self.coverage_out)
+            .with_histogram_out(self.histogram_out)
+            .with_skip_checksum(self.skip_checksum)
+            .with_compact_pages(self.compact_pages)
+            .with_guest_logs(self.guest_logs, self.guest_log_prefix)
+            .with_manifest(self.manifest, self.manifest_elf, command.clone())
+            .with_journal(self.journal, command)
+            .with_journal_sync_every(self.journal_sync_every)
+            .build_with_guest_logs()?;
+
+        let exit_code = match kernel.run()? {
+            Outcome::Exited(status) => {
+                tracing::info!(target: "cannon-cli::run", "Program exited with status {}", status);
+                status_exit_code(status)
+            }
+            Outcome::Panicked(status) => {
+                tracing::warn!(target: "cannon-cli::run", "Program crashed and exited with status {}", status);
+                self.panic_exit_code
+            }
+            Outcome::Stopped | Outcome::TimedOut => status_exit_code(VMStatus::Unfinished),
+            Outcome::Cancelled => INTERRUPTED_EXIT_CODE,
+        };
+        if exit_code != 0 {
+            std::process::exit(exit_code);
+        }
+        Ok(())
     }
 }
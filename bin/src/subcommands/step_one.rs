@@ -0,0 +1,185 @@
+//! The `step-one` subcommand for the cannon binary
+
+use super::{mem::load_state, CannonSubcommandDispatcher};
+use alloy_primitives::{hex, B256};
+use anyhow::{anyhow, Result};
+use cannon::{Proof, ReplayOracle};
+use cannon_mipsevm::{
+    disasm::{Disassembly, REGISTER_NAMES},
+    ser::{self, Codec},
+    InstrumentedState, Metadata, WitnessState,
+};
+use clap::Args;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Command line arguments for `cannon step-one`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct StepOneArgs {
+    /// The path to the state to step. States at `.bin` paths are loaded from the binary state
+    /// file format.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Skip verifying the checksum embedded in the state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The path to write the post-state to. States at `.bin` paths are written in the binary state
+    /// file format, all others as JSON. Must differ from the input path. Not written if not
+    /// provided.
+    #[arg(long, alias = "out")]
+    output: Option<PathBuf>,
+
+    /// The compression codec (`none`, `zlib`, `gzip` or `zstd`) to write the post-state with.
+    /// Selected by the extension of the output path if not specified, falling back to `gzip`.
+    #[arg(long)]
+    codec: Option<Codec>,
+
+    /// The path to write the JSON proof of the step to, as read by `cannon proof`.
+    #[arg(long)]
+    proof: Option<PathBuf>,
+
+    /// Print the transaction calldata of the step, one `0x`-prefixed hex string per line: the
+    /// `PreimageOracle` load call, if the step reads a preimage, followed by the `MIPS.step` call.
+    #[arg(long)]
+    calldata: bool,
+
+    /// The local context of the dispute game. If specified, the step calldata is encoded for
+    /// versions of the `MIPS` contract whose `step` function takes the local context as a third
+    /// argument, and local preimages are loaded under the key localized with it.
+    #[arg(long, requires = "calldata")]
+    local_context: Option<B256>,
+
+    /// The path to the metadata of the program, used to print the symbol of the instruction.
+    #[arg(long)]
+    meta: Option<PathBuf>,
+
+    /// The path of a replay log recorded with `cannon run --preimage-record` to serve preimages
+    /// from. Steps that read a preimage fail without it.
+    #[arg(long)]
+    preimage_replay: Option<PathBuf>,
+}
+
+impl CannonSubcommandDispatcher for StepOneArgs {
+    fn dispatch(self) -> Result<()> {
+        // The input state is memory-mapped from binary state files, so it must not be overwritten
+        // in place while it is loaded.
+        if let Some(ref output) = self.output {
+            anyhow::ensure!(
+                fs::canonicalize(output).ok() != Some(fs::canonicalize(&self.input)?),
+                "The output path {} must differ from the input path",
+                output.display()
+            );
+        }
+
+        let mut state = load_state(&self.input, self.skip_checksum)?;
+        anyhow::ensure!(!state.exited, "The program exited at step {}", state.step);
+
+        let meta = self
+            .meta
+            .as_deref()
+            .map(Metadata::load)
+            .transpose()?
+            .unwrap_or_default();
+        let instruction = state.memory.get_memory(state.pc)?;
+        println!(
+            "step {}: 0x{:08x}  {}  in {}",
+            state.step,
+            state.pc,
+            Disassembly::new(instruction, state.pc),
+            meta.lookup_symbol(state.pc)
+        );
+
+        let oracle = self
+            .preimage_replay
+            .as_deref()
+            .map(ReplayOracle::open)
+            .transpose()?
+            .unwrap_or_default();
+        // Only the fields of the witness are diffed, so the memory is not copied.
+        let pre = state.witness_state()?;
+        let pre_hash = state.state_hash()?;
+
+        let mut ins = InstrumentedState::new(state, oracle, io::sink(), io::sink());
+        let witness = ins.step(true)?.ok_or(anyhow!("No step witness"))?;
+        let mut post = ins.state;
+        let post_hash = post.state_hash()?;
+
+        print_diff(&pre, &post.witness_state()?);
+        println!("pre:  {}", B256::from(pre_hash));
+        println!("post: {}", B256::from(post_hash));
+
+        let proof = Proof::new(pre.step, pre_hash, post_hash, witness);
+        if self.calldata {
+            let (oracle_input, step_input) = match self.local_context {
+                Some(local_context) => (
+                    proof.oracle_input_with_local_context(local_context),
+                    proof
+                        .step_witness()
+                        .encode_with_local_context(local_context.0.into())
+                        .to_vec(),
+                ),
+                None => (proof.oracle_input.clone(), proof.step_input.clone()),
+            };
+            if let Some(ref oracle_input) = oracle_input {
+                println!("{}", hex::encode_prefixed(oracle_input));
+            }
+            println!("{}", hex::encode_prefixed(step_input));
+        }
+        if let Some(ref path) = self.proof {
+            fs::write(path, serde_json::to_vec(&proof)?)?;
+            tracing::info!(target: "cannon-cli::step-one", "Wrote the proof at step {} to {}", proof.step, path.display());
+        }
+
+        if let Some(ref path) = self.output {
+            post.seal()?;
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                post.save_binary(path)?;
+            } else {
+                let codec = self
+                    .codec
+                    .or_else(|| Codec::from_path(path))
+                    .unwrap_or(Codec::Gzip);
+                let raw = codec.compress(&serde_json::to_vec(&post)?)?;
+                ser::replace_file(path, |writer| writer.write_all(&raw))?;
+            }
+            tracing::info!(target: "cannon-cli::step-one", "Wrote the post-state at step {} to {}", post.step, path.display());
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the registers and other fields of the [WitnessState] that the step changed, one per line.
+fn print_diff(pre: &WitnessState, post: &WitnessState) {
+    let fields = [
+        ("pc", pre.pc, post.pc),
+        ("next_pc", pre.next_pc, post.next_pc),
+        ("lo", pre.lo, post.lo),
+        ("hi", pre.hi, post.hi),
+        ("heap", pre.heap, post.heap),
+        ("exit", pre.exit_code as u32, post.exit_code as u32),
+        ("exited", pre.exited as u32, post.exited as u32),
+        ("poff", pre.preimage_offset, post.preimage_offset),
+    ];
+    let registers = (0..32).map(|i| (REGISTER_NAMES[i], pre.registers[i], post.registers[i]));
+
+    for (name, before, after) in registers.chain(fields) {
+        if before != after {
+            println!("  {:<8} 0x{:08x} -> 0x{:08x}", name, before, after);
+        }
+    }
+    if pre.preimage_key != post.preimage_key {
+        println!(
+            "  {:<8} {} -> {}",
+            "pkey",
+            B256::from(pre.preimage_key),
+            B256::from(post.preimage_key)
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! The `verify-proof` subcommand for the cannon binary
+
+use super::CannonSubcommandDispatcher;
+use alloy_primitives::{Bytes, B256};
+use anyhow::Result;
+use cannon_mipsevm::{proof, Address};
+use clap::Args;
+
+/// Command line arguments for `cannon verify-proof`
+#[derive(Args, Debug)]
+#[command(author, version, about)]
+pub(crate) struct VerifyProofArgs {
+    /// The memory root that the proof must commit to.
+    #[arg(long)]
+    root: B256,
+
+    /// The 4-byte aligned address of the proven word, in hex.
+    #[arg(long, value_parser = parse_hex_word)]
+    address: Address,
+
+    /// The value of the proven word, in hex.
+    #[arg(long, value_parser = parse_hex_word)]
+    value: u32,
+
+    /// The 896 byte memory proof, in hex: the leaf followed by its 27 siblings.
+    #[arg(long)]
+    proof: Bytes,
+}
+
+/// Parses a `0x`-prefixed or bare hex word.
+fn parse_hex_word(s: &str) -> Result<u32> {
+    let s = s.trim();
+    Ok(u32::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16)?)
+}
+
+impl CannonSubcommandDispatcher for VerifyProofArgs {
+    fn dispatch(self) -> Result<()> {
+        anyhow::ensure!(
+            self.proof.len() == proof::PROOF_SIZE,
+            "The proof is {} bytes, expected {}",
+            self.proof.len(),
+            proof::PROOF_SIZE
+        );
+        anyhow::ensure!(
+            proof::verify(self.root.0, self.address, self.value, &self.proof),
+            "Invalid proof of {:08x} at address {:08x} against root {}",
+            self.value,
+            self.address,
+            self.root
+        );
+        println!(
+            "Valid proof of {:08x} at address {:08x} against root {}",
+            self.value, self.address, self.root
+        );
+        Ok(())
+    }
+}
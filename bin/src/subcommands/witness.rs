@@ -3,8 +3,7 @@
 use super::CannonSubcommandDispatcher;
 use alloy_primitives::B256;
 use anyhow::Result;
-use cannon::gz::decompress_bytes;
-use cannon_mipsevm::{State, StateWitnessHasher};
+use cannon_mipsevm::{ser::Codec, State, StateWitnessHasher};
 use clap::Args;
 use std::{fs, path::PathBuf};
 
@@ -12,11 +11,16 @@ use std::{fs, path::PathBuf};
 #[derive(Args, Debug)]
 #[command(author, version, about)]
 pub(crate) struct WitnessArgs {
-    /// The path to the input JSON state.
+    /// The path to the input JSON state. States at `.bin` paths are loaded from the binary state
+    /// file format.
     #[arg(long)]
     input: PathBuf,
 
-    /// The path to the output JSON state.
+    /// Skip verifying the checksum embedded in the state.
+    #[arg(long)]
+    skip_checksum: bool,
+
+    /// The path to write the raw state witness to. The state hash is always printed to stdout.
     #[arg(long)]
     output: Option<PathBuf>,
 }
@@ -25,9 +29,19 @@ impl CannonSubcommandDispatcher for WitnessArgs {
     fn dispatch(self) -> Result<()> {
         tracing::info!(target: "cannon-cli::witness", "Loading state JSON dump from {}", self.input.display());
 
-        let state_raw = fs::read(&self.input)?;
-        let mut state: State = serde_json::from_slice(&decompress_bytes(&state_raw)?)?;
-
+        let mut state = if self.input.extension().is_some_and(|ext| ext == "bin") {
+            // SAFETY: The state is dropped before the command returns, and the input is not
+            // written to.
+            unsafe { State::load_mmapped(&self.input)? }
+        } else {
+            let state_raw = fs::read(&self.input)?;
+            let codec = Codec::from_path(&self.input).unwrap_or_else(|| Codec::detect(&state_raw));
+            State::from_json(&codec.decompress(&state_raw)?)?
+        };
+
+        if !self.skip_checksum {
+            state.verify_checksum()?;
+        }
         tracing::info!(target: "cannon-cli::witness", "Loaded state JSON dump and deserialized the State");
 
         let witness = state.encode_witness()?;
@@ -35,17 +49,15 @@ impl CannonSubcommandDispatcher for WitnessArgs {
 
         tracing::info!(target: "cannon-cli::witness", "Encoded witness and computed witness hash: {}", B256::from(witness_hash));
 
-        match self.output {
-            Some(ref output_path) => fs::write(output_path, witness).map_err(|_| {
+        println!("{}", B256::from(witness_hash));
+
+        if let Some(ref output_path) = self.output {
+            fs::write(output_path, witness).map_err(|_| {
                 anyhow::anyhow!("Failed to write witness to {}", output_path.display())
-            }),
-            None => {
-                println!("{}", B256::from(witness_hash));
-                Ok(())
-            }
-        }?;
-
-        tracing::info!(target: "cannon-cli::witness", "Wrote witness to {}", self.output.as_ref().map_or("stdout".to_string(), |p| p.display().to_string()));
+            })?;
+            tracing::info!(target: "cannon-cli::witness", "Wrote witness to {}", output_path.display());
+        }
+
         Ok(())
     }
 }
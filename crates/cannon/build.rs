@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC bindings of the preimage oracle protocol are only generated for the `grpc` feature.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/preimage.proto");
+        tonic_build::compile_protos("proto/preimage.proto")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,360 @@
+//! This module contains the [Bisector], which locates the first step at which the native VM
+//! diverges from a reference implementation, e.g. Go Cannon.
+//!
+//! Bisection runs in three phases:
+//! 1. The native VM runs from the initial state to the final step, recording its state hash at
+//!    exponentially spaced checkpoints (`start + 1`, `start + 2`, `start + 4`, ...) as well as at
+//!    the final step. If the final hash matches the expected one, there is nothing to bisect.
+//! 2. The checkpoints are binary searched against the [StateHashReference] for the first
+//!    checkpoint whose hash diverges.
+//! 3. The steps between the last matching checkpoint and the first diverging one are binary
+//!    searched by re-running the VM from the state at the last matching step, which is cloned
+//!    whenever the search advances past it.
+//!
+//! The step that turns the last matching state into the first diverging one is then re-executed
+//! with witness generation enabled, and reported as a [Divergence].
+
+use crate::Proof;
+use alloy_primitives::B256;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{disasm::Disassembly, InstrumentedState, PreimageOracle, State};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// A [StateHashReference] provides the state hashes that a reference implementation computed at
+/// arbitrary steps. Steps are queried in no particular order.
+pub trait StateHashReference {
+    /// Returns the state hash that the reference implementation computed at the given step.
+    fn state_hash_at(&mut self, step: u64) -> Result<[u8; 32]>;
+}
+
+impl StateHashReference for crate::ShadowVerifier {
+    fn state_hash_at(&mut self, step: u64) -> Result<[u8; 32]> {
+        self.remote_state_hash(step)
+    }
+}
+
+impl StateHashReference for BTreeMap<u64, [u8; 32]> {
+    fn state_hash_at(&mut self, step: u64) -> Result<[u8; 32]> {
+        self.get(&step)
+            .copied()
+            .ok_or(anyhow!("No reference state hash at step {}", step))
+    }
+}
+
+/// A single line of a reference state hash file, as loaded by [load_reference_hashes].
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReferenceEntry {
+    step: u64,
+    #[serde(with = "cannon_mipsevm::ser::fixed_32_hex")]
+    state_hash: [u8; 32],
+}
+
+/// Loads reference state hashes from a JSONL file with one `{"step":..,"stateHash":"0x.."}`
+/// object per line, the same shape as the `cannon_stateHashAt` result of the
+/// [crate::ShadowVerifier] channel.
+///
+/// ### Takes
+/// - `path`: The path of the file.
+///
+/// ### Returns
+/// - A [Result] containing the state hashes, keyed by step.
+pub fn load_reference_hashes(path: impl AsRef<Path>) -> Result<BTreeMap<u64, [u8; 32]>> {
+    let mut hashes = BTreeMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ReferenceEntry = serde_json::from_str(&line)?;
+        hashes.insert(entry.step, entry.state_hash);
+    }
+    Ok(hashes)
+}
+
+/// The [Divergence] report of the first step at which the native VM diverges from the reference.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Divergence {
+    /// The [Proof] of the diverging step, as executed by the native VM. Its prestate hash matches
+    /// the reference, and its poststate hash does not.
+    pub proof: Proof,
+    /// The poststate hash that the reference computed for the diverging step.
+    #[serde(with = "cannon_mipsevm::ser::fixed_32_hex")]
+    pub reference_post: [u8; 32],
+    /// The program counter of the diverging instruction.
+    pub pc: u32,
+    /// The diverging instruction word.
+    pub instruction: u32,
+    /// The disassembly of the diverging instruction.
+    pub disassembly: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "first diverging step: {}", self.proof.step)?;
+        writeln!(
+            f,
+            "instruction: {:08x} ({}) at pc 0x{:08x}",
+            self.instruction, self.disassembly, self.pc
+        )?;
+        writeln!(f, "prestate hash:       {}", B256::from(self.proof.pre))?;
+        writeln!(f, "local poststate:     {}", B256::from(self.proof.post))?;
+        write!(
+            f,
+            "reference poststate: {}",
+            B256::from(self.reference_post)
+        )?;
+        if let Some(ref key) = self.proof.oracle_key {
+            write!(
+                f,
+                "\npreimage key: {} offset: {}",
+                B256::from_slice(key),
+                self.proof.oracle_offset.unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a [Bisector] run.
+#[derive(Clone)]
+pub enum Bisection {
+    /// The final state hash matches the expected hash.
+    Matched {
+        /// The final step.
+        step: u64,
+        /// The final state hash.
+        hash: [u8; 32],
+    },
+    /// The native VM diverges from the reference.
+    Diverged(Box<Divergence>),
+}
+
+/// The [Bisector] finds the first step at which the native VM diverges from a
+/// [StateHashReference].
+pub struct Bisector<O: Write, E: Write, P: PreimageOracle> {
+    /// The instrumented state of the native VM, positioned at the initial state.
+    ins_state: InstrumentedState<O, E, P>,
+    /// The step to stop at if the program does not exit before it.
+    stop_at: Option<u64>,
+}
+
+impl<O, E, P> Bisector<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    /// Creates a new [Bisector] that runs the native VM from the state of `ins_state`.
+    pub fn new(ins_state: InstrumentedState<O, E, P>) -> Self {
+        Self {
+            ins_state,
+            stop_at: None,
+        }
+    }
+
+    /// Stops the native VM at the given step rather than when the program exits.
+    pub fn with_stop_at(mut self, stop_at: u64) -> Self {
+        self.stop_at = Some(stop_at);
+        self
+    }
+
+    /// Bisects the execution of the native VM against the reference.
+    ///
+    /// ### Takes
+    /// - `reference`: The [StateHashReference] to compare intermediate state hashes against.
+    /// - `expected`: The state hash that the reference computed at the final step.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [Bisection] outcome.
+    pub fn bisect(
+        &mut self,
+        reference: &mut impl StateHashReference,
+        expected: [u8; 32],
+    ) -> Result<Bisection> {
+        let initial = self.ins_state.state.clone();
+        let start = initial.step;
+        let initial_hash = self.ins_state.state.state_hash()?;
+
+        // Phase 1: run to the final step, checkpointing hashes at exponentially spaced steps.
+        let mut checkpoints = vec![(start, initial_hash)];
+        let mut distance = 1u64;
+        while !self.ins_state.state.exited {
+            let step = self.ins_state.state.step;
+            if self.stop_at.is_some_and(|stop_at| step >= stop_at) {
+                break;
+            }
+            let target = self
+                .stop_at
+                .map_or(start + distance, |stop_at| stop_at.min(start + distance));
+            self.ins_state.step_threaded(target - step)?;
+            checkpoints.push((
+                self.ins_state.state.step,
+                self.ins_state.state.state_hash()?,
+            ));
+            distance = distance.saturating_mul(2);
+        }
+
+        let &(final_step, final_hash) = checkpoints.last().expect("Checkpoints are non-empty");
+        crate::traces::info!(target: "cannon::bisect", "Native VM reached step {} with {} checkpoints", final_step, checkpoints.len());
+        if final_hash == expected {
+            return Ok(Bisection::Matched {
+                step: final_step,
+                hash: final_hash,
+            });
+        }
+
+        // Phase 2: binary search the checkpoints for the first diverging one.
+        if reference.state_hash_at(start)? != initial_hash {
+            anyhow::bail!(
+                "The initial state at step {} already diverges from the reference",
+                start
+            );
+        }
+        if reference.state_hash_at(final_step)? == final_hash {
+            anyhow::bail!(
+                "The reference agrees with the native VM at step {}, but the expected hash differs",
+                final_step
+            );
+        }
+        let (mut good, mut bad) = (0, checkpoints.len() - 1);
+        while bad - good > 1 {
+            let mid = (good + bad) / 2;
+            let (step, hash) = checkpoints[mid];
+            if reference.state_hash_at(step)? == hash {
+                good = mid;
+            } else {
+                bad = mid;
+            }
+        }
+        let (mut good, mut bad) = (checkpoints[good].0, checkpoints[bad].0);
+        crate::traces::info!(target: "cannon::bisect", "Divergence lies between steps {} and {}", good, bad);
+
+        // Phase 3: binary search the steps between the checkpoints.
+        self.restore(initial);
+        self.ins_state.step_threaded(good - start)?;
+        let mut good_state = self.ins_state.state.clone();
+        while bad - good > 1 {
+            let mid = good + (bad - good) / 2;
+            self.ins_state
+                .step_threaded(mid - self.ins_state.state.step)?;
+            let hash = self.ins_state.state.state_hash()?;
+            if reference.state_hash_at(mid)? == hash {
+                good = mid;
+                good_state = self.ins_state.state.clone();
+            } else {
+                bad = mid;
+                self.restore(good_state.clone());
+            }
+        }
+        if self.ins_state.state.step != good {
+            self.restore(good_state);
+        }
+
+        let pc = self.ins_state.state.pc;
+        let instruction = self.ins_state.state.memory.get_memory(pc as _)?;
+        let pre = self.ins_state.state.state_hash()?;
+        let step_witness = self
+            .ins_state
+            .step(true)?
+            .ok_or(anyhow!("No step witness"))?;
+        let post = self.ins_state.state.state_hash()?;
+
+        Ok(Bisection::Diverged(Box::new(Divergence {
+            proof: Proof::new(good, pre, post, step_witness),
+            reference_post: reference.state_hash_at(bad)?,
+            pc,
+            instruction,
+            disassembly: Disassembly::new(instruction, pc as _).to_string(),
+        })))
+    }
+
+    /// Restores the native VM to the given [State].
+    fn restore(&mut self, state: State) {
+        self.ins_state.state = state;
+        self.ins_state.invalidate_block_cache();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::{counting_state, StaticOracle};
+    use std::io;
+
+    /// Computes the reference hashes of [counting_state] up to `steps`, where the reference
+    /// diverges by setting `$t2` after executing step `diverge_at`.
+    fn reference_hashes(steps: u64, diverge_at: u64) -> BTreeMap<u64, [u8; 32]> {
+        let mut ins = InstrumentedState::new(
+            counting_state(1000),
+            StaticOracle::new(Vec::new()),
+            io::sink(),
+            io::sink(),
+        );
+        let mut hashes = BTreeMap::new();
+        loop {
+            let step = ins.state.step;
+            hashes.insert(step, ins.state.state_hash().unwrap());
+            if step == steps {
+                break hashes;
+            }
+            ins.step(false).unwrap();
+            if step == diverge_at {
+                ins.state.registers[10] = 1;
+            }
+        }
+    }
+
+    #[test]
+    fn finds_first_diverging_step() {
+        for diverge_at in [0, 1, 2, 37, 63, 64, 99] {
+            let mut reference = reference_hashes(100, diverge_at);
+            let expected = reference[&100];
+
+            let ins = InstrumentedState::new(
+                counting_state(1000),
+                StaticOracle::new(Vec::new()),
+                io::sink(),
+                io::sink(),
+            );
+            let outcome = Bisector::new(ins)
+                .with_stop_at(100)
+                .bisect(&mut reference, expected)
+                .unwrap();
+
+            let Bisection::Diverged(divergence) = outcome else {
+                panic!("Expected a divergence at step {}", diverge_at);
+            };
+            assert_eq!(divergence.proof.step, diverge_at);
+            assert_eq!(divergence.proof.pre, reference[&diverge_at]);
+            assert_ne!(divergence.proof.post, divergence.reference_post);
+            assert_eq!(divergence.reference_post, reference[&(diverge_at + 1)]);
+        }
+    }
+
+    #[test]
+    fn matching_run() {
+        let mut reference = reference_hashes(100, u64::MAX);
+        let expected = reference[&100];
+
+        let ins = InstrumentedState::new(
+            counting_state(1000),
+            StaticOracle::new(Vec::new()),
+            io::sink(),
+            io::sink(),
+        );
+        let outcome = Bisector::new(ins)
+            .with_stop_at(100)
+            .bisect(&mut reference, expected)
+            .unwrap();
+        assert!(matches!(outcome, Bisection::Matched { step: 100, .. }));
+    }
+}
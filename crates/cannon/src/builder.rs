@@ -1,12 +1,22 @@
 //! The [KernelBuilder] struct is a helper for building a [Kernel] struct.
 
-use crate::{gz, ChildWithFds, Kernel, ProcessPreimageOracle};
-use anyhow::{anyhow, Result};
-use cannon_mipsevm::{InstrumentedState, State};
+use crate::{
+    prover::{Prover, ReplicaProver},
+    CachingOracle, CancellationToken, DirectoryProofWriter, GuestLogWriter, HostOracle,
+    JsonlProofWriter, JsonlTraceExporter, Kernel, LogProgressSink, Manifest, ManifestRecorder,
+    PipelinedProofWriter, ProcessPreimageOracle, ProgressSink, ProofWriter, ReplayOracle,
+    ReplayRecorder, ShadowVerifier, StepJournal, TraceExporter, DEFAULT_JOURNAL_SYNC_EVERY,
+    DEFAULT_PROOF_QUEUE_CAPACITY,
+};
+use alloy_primitives::{keccak256, B256};
+use anyhow::Result;
+use cannon_mipsevm::{ser::Codec, InstrumentedState, PageEncoding, PrecompileOracle, State};
+use preimage_oracle::ChannelOracleClient;
 use std::{
+    fmt,
     fs::{self, File},
-    io::{self, BufReader, Read, Stderr, Stdout},
-    path::PathBuf,
+    io::{self, BufReader, Read, Stderr, Stdout, Write},
+    num::NonZeroUsize,
 };
 
 /// The [KernelBuilder] struct is a helper for building a [Kernel] struct.
@@ -14,15 +24,38 @@ use std::{
 pub struct KernelBuilder {
     /// The full command to run the preimage server
     preimage_server: String,
+    /// The preimage server binary and its arguments. Takes precedence over `preimage_server`,
+    /// and allows arguments that contain spaces.
+    preimage_server_args: Vec<String>,
+    /// The path to the metadata of the program, containing its symbols.
+    meta: Option<String>,
     /// The path to the input JSON state.
     input: String,
     /// The path to the output JSON state.
     output: Option<String>,
+    /// The [Codec] of the input state, the output state and snapshots. Selected by file
+    /// extension if not specified.
+    codec: Option<Codec>,
     /// The step to generate an output proof at.
     proof_at: Option<String>,
     /// Format for proof data output file names. Proof data is written to stdout
     /// if this is not specified.
     proof_format: Option<String>,
+    /// The path of a JSONL file that all proofs are written to, instead of one file per proof.
+    proof_jsonl: Option<String>,
+    /// The path of a zstd-compressed tar archive that all proofs are bundled into, instead of
+    /// one file per proof. Takes precedence over `proof_jsonl`. Requires the `zstd` feature.
+    proof_bundle: Option<String>,
+    /// A custom sink for generated proofs. Takes precedence over `proof_format`, `proof_jsonl`
+    /// and `proof_bundle`.
+    proof_writer: Option<Custom<dyn ProofWriter + Send>>,
+    /// The number of background threads that generate and write proofs. One generates the merkle
+    /// proofs from a replica of the state, and any others write proofs to one file each in
+    /// parallel. Proofs are generated on the interpreter thread if zero, and written by a single
+    /// background thread.
+    proof_workers: usize,
+    /// Whether to record the memory accesses of every proven step in its proof.
+    proof_access_log: bool,
     /// The step pattern to generate state snapshots at.
     snapshot_at: Option<String>,
     /// Format for snapshot data output file names.
@@ -31,69 +64,290 @@ pub struct KernelBuilder {
     stop_at: Option<String>,
     /// The pattern to print information at.
     info_at: Option<String>,
+    /// The sink that progress reports are sent to. Reports are logged if not specified.
+    progress_sink: Option<Custom<dyn ProgressSink>>,
+    /// Whether or not to use the threaded execution mode for steps that do not require a proof.
+    threaded: bool,
+    /// The `host:port` address of a companion Go Cannon process to cross-verify state hashes
+    /// against.
+    shadow_rpc: Option<String>,
+    /// The step pattern to cross-verify state hashes at.
+    shadow_at: Option<String>,
+    /// The path to record every preimage served during the run to.
+    preimage_record: Option<String>,
+    /// The path of a replay log to serve preimages from, in place of the preimage server.
+    preimage_replay: Option<String>,
+    /// The client of a host in the same process to request preimages from, in place of the
+    /// preimage server.
+    preimage_channel: Option<ChannelOracleClient>,
+    /// The URL of a gRPC host to request preimages from, in place of the preimage server.
+    /// Requires the `grpc` feature.
+    preimage_grpc: Option<String>,
+    /// The path to the PEM encoded CA certificate to verify the gRPC host with.
+    preimage_grpc_ca: Option<String>,
+    /// The maximum number of bytes of preimages to cache in memory in front of the preimage
+    /// oracle. Preimages are not cached if zero.
+    preimage_cache: usize,
+    /// Whether to execute the precompiles hinted by the guest locally, rather than requesting
+    /// their results from the preimage oracle.
+    execute_precompiles: bool,
+    /// The token that interrupts the run.
+    cancellation: Option<CancellationToken>,
+    /// The path to export the execution trace to. Traces are written as Parquet if the path ends
+    /// in `.parquet` and the `parquet` feature is enabled, and as JSONL otherwise.
+    trace_out: Option<String>,
+    /// A custom sink for the execution trace. Takes precedence over `trace_out`.
+    trace_exporter: Option<Custom<dyn TraceExporter>>,
+    /// The path to write the coverage report of the guest program to. Reports at `.info` and
+    /// `.lcov` paths are written in the LCOV format, all others as a list of executed addresses.
+    coverage_out: Option<String>,
+    /// The path to write the instruction histogram of the guest program to. Reports at `.json`
+    /// paths are written as JSON, all others as text.
+    histogram_out: Option<String>,
+    /// Whether to skip verifying the checksum embedded in the input state.
+    skip_checksum: bool,
+    /// Whether to write the output state and snapshots with [PageEncoding::Compact].
+    compact_pages: bool,
+    /// Whether to forward the JSON log lines that the guest writes to stderr through `tracing`.
+    guest_logs: bool,
+    /// The prefix that the guest's structured log lines start with.
+    guest_log_prefix: Option<String>,
+    /// The path of the journal that every written state is recorded in.
+    journal: Option<String>,
+    /// The arguments of the command that started the run, recorded in a new journal.
+    journal_command: Vec<String>,
+    /// The number of journal entries between syncs. Defaults to
+    /// [DEFAULT_JOURNAL_SYNC_EVERY] if zero.
+    journal_sync_every: usize,
+    /// The path to write the chain-of-custody manifest of the run's artifacts to.
+    manifest: Option<String>,
+    /// The path of the ELF that the input state was loaded from, whose hash is listed in the
+    /// manifest.
+    manifest_elf: Option<String>,
+    /// The flags of the run, listed in the manifest.
+    manifest_flags: Vec<String>,
+    /// The names of the guest's panic handlers, whose entry marks the run as crashed. Requires
+    /// the metadata of the program.
+    panic_symbols: Vec<String>,
 }
 
 impl KernelBuilder {
     /// Builds the [Kernel] struct from the information contained within the [KernelBuilder].
+    /// Guest logs can only be forwarded by a [Kernel] built with
+    /// [KernelBuilder::build_with_guest_logs].
     ///
     /// TODO(clabby): Make the i/o streams + the preimage oracle configurable.
-    pub fn build(self) -> Result<Kernel<Stdout, Stderr, ProcessPreimageOracle>> {
-        // Read the compressed state dump from the input file, decompress it, and deserialize it.
-        let f = File::open(&self.input)?;
-        let f_sz = f.metadata()?.len();
-        let mut reader = BufReader::new(f);
-        // Give a reasonable capacity to the vector to avoid too many reallocations. The size of the file
-        // is the size of the compressed state dump, so we will still reallocate.
-        let mut raw_state = Vec::with_capacity(f_sz as usize);
-        reader.read_to_end(&mut raw_state)?;
-        let raw_state = fs::read(&self.input)?;
-        let raw_state = if self.input.ends_with("gz") {
-            gz::decompress_bytes(&raw_state)?
+    pub fn build(self) -> Result<Kernel<Stdout, Stderr, HostOracle>> {
+        anyhow::ensure!(
+            !self.guest_logs,
+            "Forwarding guest logs requires KernelBuilder::build_with_guest_logs"
+        );
+        self.build_with_stderr(io::stderr())
+    }
+
+    /// Builds the [Kernel] struct from the information contained within the [KernelBuilder], with
+    /// the guest's stderr wrapped in a [GuestLogWriter] that forwards its structured log lines
+    /// if enabled with [KernelBuilder::with_guest_logs].
+    pub fn build_with_guest_logs(
+        mut self,
+    ) -> Result<Kernel<Stdout, GuestLogWriter<Stderr>, HostOracle>> {
+        let std_err = if self.guest_logs {
+            let writer = GuestLogWriter::new(io::stderr());
+            match self.guest_log_prefix.take() {
+                Some(prefix) => writer.with_prefix(prefix),
+                None => writer,
+            }
         } else {
-            raw_state
+            GuestLogWriter::passthrough(io::stderr())
+        };
+        self.build_with_stderr(std_err)
+    }
+
+    /// Builds the [Kernel] struct with the given sink for the guest's stderr.
+    fn build_with_stderr<E: Write>(self, std_err: E) -> Result<Kernel<Stdout, E, HostOracle>> {
+        let mut state = load_state(&self.input, self.codec)?;
+        if self.skip_checksum {
+            crate::traces::warn!(target: "cannon::builder", "Skipping the checksum verification of {}", self.input);
+        } else {
+            state.verify_checksum()?;
+        }
+        if self.compact_pages {
+            state.memory.set_page_encoding(PageEncoding::Compact);
+        }
+
+        let (oracle, server_proc) = if let Some(client) = self.preimage_channel {
+            crate::traces::info!(target: "cannon::builder", "Requesting preimages from an in-process host");
+            (HostOracle::Channel(client), None)
+        } else if let Some(replay) = &self.preimage_replay {
+            crate::traces::info!(target: "cannon::builder", "Serving preimages from replay log {}", replay);
+            (HostOracle::Replay(ReplayOracle::open(replay)?), None)
+        } else if let Some(_endpoint) = &self.preimage_grpc {
+            #[cfg(feature = "grpc")]
+            {
+                crate::traces::info!(target: "cannon::builder", "Requesting preimages from gRPC host {}", _endpoint);
+                let oracle = crate::GrpcPreimageOracle::connect(
+                    _endpoint,
+                    self.preimage_grpc_ca.as_deref().map(std::path::Path::new),
+                )?;
+                (HostOracle::Grpc(oracle), None)
+            }
+            #[cfg(not(feature = "grpc"))]
+            anyhow::bail!("Serving preimages over gRPC requires the `grpc` feature");
+        } else {
+            let cmd = if self.preimage_server_args.is_empty() {
+                self.preimage_server
+                    .split(' ')
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            } else {
+                self.preimage_server_args.clone()
+            };
+            let (oracle, server_proc) = ProcessPreimageOracle::spawn(&cmd)?;
+
+            let oracle = match &self.preimage_record {
+                Some(record) => HostOracle::Recording(ReplayRecorder::create(oracle, record)?),
+                None => HostOracle::Process(oracle),
+            };
+            (oracle, server_proc)
+        };
+
+        let oracle = match NonZeroUsize::new(self.preimage_cache) {
+            Some(capacity) => HostOracle::Cached(Box::new(CachingOracle::new(oracle, capacity))),
+            None => oracle,
+        };
+        let oracle = if self.execute_precompiles {
+            HostOracle::Precompiles(Box::new(PrecompileOracle::new(oracle)))
+        } else {
+            oracle
+        };
+
+        let manifest = match &self.manifest {
+            Some(path) => {
+                let elf_hash = self
+                    .manifest_elf
+                    .as_ref()
+                    .map(|elf| fs::read(elf).map(keccak256))
+                    .transpose()?;
+                let input_state_hash = B256::from(state.state_hash()?);
+                let manifest = Manifest::new(input_state_hash, elf_hash, self.manifest_flags);
+                let mut recorder = ManifestRecorder::new(path, manifest);
+                match (&self.proof_writer, &self.proof_bundle, &self.proof_jsonl) {
+                    (Some(_), _, _) => {
+                        crate::traces::warn!(target: "cannon::builder", "Proofs written to a custom sink are not listed in the manifest");
+                    }
+                    (None, Some(path), _) => {
+                        recorder.record(path.as_str());
+                        #[cfg(feature = "zstd")]
+                        recorder.record(crate::proof_bundle_index_path(path).display().to_string());
+                    }
+                    (None, None, Some(path)) => recorder.record(path.as_str()),
+                    (None, None, None) => {
+                        recorder = recorder.with_proof_format(Some(
+                            self.proof_format
+                                .clone()
+                                .unwrap_or("%d.json.gz".to_string()),
+                        ))
+                    }
+                }
+                if let Some(ref trace_out) = self.trace_out {
+                    recorder.record(trace_out.as_str());
+                }
+                Some(recorder)
+            }
+            None => None,
+        };
+
+        let shadow = self
+            .shadow_rpc
+            .as_deref()
+            .map(ShadowVerifier::connect)
+            .transpose()?;
+
+        let proof_writer: Box<dyn ProofWriter + Send> =
+            match (self.proof_writer, self.proof_bundle, self.proof_jsonl) {
+                (Some(Custom(proof_writer)), _, _) => proof_writer,
+                (None, Some(path), _) => create_proof_bundle(&path)?,
+                (None, None, Some(path)) => Box::new(JsonlProofWriter::create(path)?),
+                (None, None, None) => {
+                    let proof_writer = DirectoryProofWriter::new(
+                        self.proof_format.unwrap_or("%d.json.gz".to_string()),
+                    );
+                    // Any workers besides the prover write the proofs in parallel.
+                    match self.proof_workers {
+                        0 | 1 => Box::new(proof_writer),
+                        workers => Box::new(PipelinedProofWriter::pool(
+                            proof_writer,
+                            workers - 1,
+                            DEFAULT_PROOF_QUEUE_CAPACITY,
+                        )),
+                    }
+                }
+            };
+
+        let trace_exporter = match (self.trace_exporter, self.trace_out) {
+            (Some(Custom(trace_exporter)), _) => Some(trace_exporter),
+            (None, Some(path)) => Some(create_trace_exporter(&path)?),
+            (None, None) => None,
+        };
+
+        let journal = match self.journal {
+            Some(path) => {
+                let sync_every = match self.journal_sync_every {
+                    0 => DEFAULT_JOURNAL_SYNC_EVERY,
+                    sync_every => sync_every,
+                };
+                Some(StepJournal::open(path, self.journal_command, sync_every)?)
+            }
+            None => None,
+        };
+
+        // TODO(clabby): Allow for the stdout to be configurable.
+        let mut instrumented = InstrumentedState::new(state, oracle, io::stdout(), std_err);
+        instrumented.set_record_access_log(self.proof_access_log);
+
+        let prover = if self.proof_workers > 0 && self.proof_at.is_some() {
+            Prover::Replica(ReplicaProver::spawn(
+                instrumented.replicate()?,
+                proof_writer,
+                DEFAULT_PROOF_QUEUE_CAPACITY,
+            ))
+        } else if self.proof_at.is_some() {
+            // The merkle proofs are generated on the interpreter thread, but proofs are still
+            // serialized and written in the background.
+            Prover::Inline(Box::new(PipelinedProofWriter::new(
+                proof_writer,
+                DEFAULT_PROOF_QUEUE_CAPACITY,
+            )))
+        } else {
+            Prover::Inline(proof_writer)
         };
-        let state: State = serde_json::from_slice(&raw_state)?;
-
-        let (hint_cl_rw, hint_oracle_rw) = preimage_oracle::create_bidirectional_channel()?;
-        let (pre_cl_rw, pre_oracle_rw) = preimage_oracle::create_bidirectional_channel()?;
-
-        let server_io = [hint_oracle_rw, pre_oracle_rw];
-
-        // TODO(clabby): Allow for the preimage server to be configurable.
-        let cmd = self
-            .preimage_server
-            .split(' ')
-            .map(String::from)
-            .collect::<Vec<_>>();
-        let (oracle, server_proc) = ProcessPreimageOracle::start(
-            PathBuf::from(
-                cmd.first()
-                    .ok_or(anyhow!("Missing preimage server binary path"))?,
-            ),
-            &cmd[1..],
-            (hint_cl_rw, pre_cl_rw),
-            &server_io,
-        )?;
-
-        let server_proc = server_proc.map(|p| ChildWithFds {
-            inner: p,
-            fds: server_io,
-        });
-
-        // TODO(clabby): Allow for the stdout / stderr to be configurable.
-        let instrumented = InstrumentedState::new(state, oracle, io::stdout(), io::stderr());
 
         Ok(Kernel::new(
             instrumented,
             server_proc,
             self.input,
             self.output,
+            self.meta,
+            self.codec,
             self.proof_at,
-            self.proof_format,
+            prover,
             self.snapshot_at,
             self.snapshot_format,
             self.stop_at,
             self.info_at,
+            self.progress_sink
+                .map(|sink| sink.0)
+                .unwrap_or_else(|| Box::new(LogProgressSink)),
+            self.threaded,
+            shadow,
+            self.shadow_at,
+            self.cancellation,
+            trace_exporter,
+            self.coverage_out,
+            self.histogram_out,
+            journal,
+            manifest,
+            self.panic_symbols,
         ))
     }
 
@@ -102,6 +356,16 @@ impl KernelBuilder {
         self
     }
 
+    pub fn with_preimage_server_args(mut self, preimage_server_args: Vec<String>) -> Self {
+        self.preimage_server_args = preimage_server_args;
+        self
+    }
+
+    pub fn with_meta(mut self, meta: Option<String>) -> Self {
+        self.meta = meta;
+        self
+    }
+
     pub fn with_input(mut self, input: String) -> Self {
         self.input = input;
         self
@@ -112,6 +376,11 @@ impl KernelBuilder {
         self
     }
 
+    pub fn with_codec(mut self, codec: Option<Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
     pub fn with_proof_at(mut self, proof_at: Option<String>) -> Self {
         self.proof_at = proof_at;
         self
@@ -122,6 +391,35 @@ impl KernelBuilder {
         self
     }
 
+    pub fn with_proof_jsonl(mut self, proof_jsonl: Option<String>) -> Self {
+        self.proof_jsonl = proof_jsonl;
+        self
+    }
+
+    /// Bundles all proofs into a zstd-compressed tar archive at the given path, with an index
+    /// for reading them back in any order. Requires the `zstd` feature.
+    pub fn with_proof_bundle(mut self, proof_bundle: Option<String>) -> Self {
+        self.proof_bundle = proof_bundle;
+        self
+    }
+
+    pub fn with_proof_writer(mut self, proof_writer: impl ProofWriter + Send + 'static) -> Self {
+        self.proof_writer = Some(Custom(Box::new(proof_writer)));
+        self
+    }
+
+    pub fn with_proof_workers(mut self, proof_workers: usize) -> Self {
+        self.proof_workers = proof_workers;
+        self
+    }
+
+    /// Records every memory access performed by the proven steps, and attaches them to their
+    /// proofs. Adds overhead to every step executed without the threaded execution mode.
+    pub fn with_proof_access_log(mut self, proof_access_log: bool) -> Self {
+        self.proof_access_log = proof_access_log;
+        self
+    }
+
     pub fn with_snapshot_at(mut self, snapshot_at: Option<String>) -> Self {
         self.snapshot_at = snapshot_at;
         self
@@ -141,4 +439,209 @@ impl KernelBuilder {
         self.info_at = info_at;
         self
     }
+
+    pub fn with_threaded(mut self, threaded: bool) -> Self {
+        self.threaded = threaded;
+        self
+    }
+
+    pub fn with_shadow_rpc(mut self, shadow_rpc: Option<String>) -> Self {
+        self.shadow_rpc = shadow_rpc;
+        self
+    }
+
+    pub fn with_shadow_at(mut self, shadow_at: Option<String>) -> Self {
+        self.shadow_at = shadow_at;
+        self
+    }
+
+    pub fn with_preimage_record(mut self, preimage_record: Option<String>) -> Self {
+        self.preimage_record = preimage_record;
+        self
+    }
+
+    pub fn with_preimage_replay(mut self, preimage_replay: Option<String>) -> Self {
+        self.preimage_replay = preimage_replay;
+        self
+    }
+
+    /// Requests preimages from a host in the same process through the given client, in place of
+    /// the preimage server. The host serves the other end of the channel, e.g. on another thread.
+    pub fn with_preimage_channel(mut self, client: ChannelOracleClient) -> Self {
+        self.preimage_channel = Some(client);
+        self
+    }
+
+    pub fn with_preimage_grpc(
+        mut self,
+        preimage_grpc: Option<String>,
+        preimage_grpc_ca: Option<String>,
+    ) -> Self {
+        self.preimage_grpc = preimage_grpc;
+        self.preimage_grpc_ca = preimage_grpc_ca;
+        self
+    }
+
+    pub fn with_preimage_cache(mut self, preimage_cache: usize) -> Self {
+        self.preimage_cache = preimage_cache;
+        self
+    }
+
+    pub fn with_execute_precompiles(mut self, execute_precompiles: bool) -> Self {
+        self.execute_precompiles = execute_precompiles;
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn with_trace_out(mut self, trace_out: Option<String>) -> Self {
+        self.trace_out = trace_out;
+        self
+    }
+
+    pub fn with_coverage_out(mut self, coverage_out: Option<String>) -> Self {
+        self.coverage_out = coverage_out;
+        self
+    }
+
+    pub fn with_histogram_out(mut self, histogram_out: Option<String>) -> Self {
+        self.histogram_out = histogram_out;
+        self
+    }
+
+    /// Skips verifying the checksum embedded in the input state when it is loaded, e.g. to
+    /// resume from a state that was modified by hand.
+    pub fn with_skip_checksum(mut self, skip_checksum: bool) -> Self {
+        self.skip_checksum = skip_checksum;
+        self
+    }
+
+    /// Writes the output state and snapshots with [PageEncoding::Compact], which elides zero
+    /// pages, rather than in the encoding that Go Cannon reads.
+    pub fn with_compact_pages(mut self, compact_pages: bool) -> Self {
+        self.compact_pages = compact_pages;
+        self
+    }
+
+    /// Forwards the JSON log lines that the guest writes to stderr through the host's `tracing`
+    /// subscriber, at the levels given by the guest. Only lines starting with `prefix` are
+    /// intercepted, if given; all other output is written to stderr unchanged. Requires the
+    /// [Kernel] to be built with [KernelBuilder::build_with_guest_logs].
+    pub fn with_guest_logs(mut self, guest_logs: bool, prefix: Option<String>) -> Self {
+        self.guest_logs = guest_logs;
+        self.guest_log_prefix = prefix;
+        self
+    }
+
+    /// Records every state written during the run in the journal at the given path, so that the
+    /// run can be resumed from its last durable snapshot after a crash. The `command` that
+    /// started the run is recorded in the header of a new journal.
+    pub fn with_journal(mut self, journal: Option<String>, command: Vec<String>) -> Self {
+        self.journal = journal;
+        self.journal_command = command;
+        self
+    }
+
+    /// Sets the number of journal entries that are appended between syncs.
+    pub fn with_journal_sync_every(mut self, sync_every: usize) -> Self {
+        self.journal_sync_every = sync_every;
+        self
+    }
+
+    /// Writes a chain-of-custody manifest of the artifacts written during the run to the given
+    /// path, listing the hash of the input state, the hash of the `elf` that it was loaded from,
+    /// if given, and the `flags` of the run.
+    pub fn with_manifest(
+        mut self,
+        manifest: Option<String>,
+        elf: Option<String>,
+        flags: Vec<String>,
+    ) -> Self {
+        self.manifest = manifest;
+        self.manifest_elf = elf;
+        self.manifest_flags = flags;
+        self
+    }
+
+    /// Detects the guest entering any of the given panic handlers, where `*` matches any sequence
+    /// of characters, so that a crash is reported as [crate::Outcome::Panicked] rather than as an
+    /// ordinary exit. See [crate::DEFAULT_PANIC_SYMBOLS] for the handlers of common runtimes.
+    pub fn with_panic_symbols(mut self, panic_symbols: Vec<String>) -> Self {
+        self.panic_symbols = panic_symbols;
+        self
+    }
+
+    /// Sets the [TraceExporter] that a record of every executed instruction is written to, in
+    /// place of the exporter selected by `trace_out`.
+    pub fn with_trace_exporter(mut self, trace_exporter: impl TraceExporter + 'static) -> Self {
+        self.trace_exporter = Some(Custom(Box::new(trace_exporter)));
+        self
+    }
+
+    /// Sets the [ProgressSink] that progress reports are sent to at the steps matching the
+    /// `info_at` pattern, in place of logging them.
+    pub fn with_progress_sink(mut self, progress_sink: impl ProgressSink + 'static) -> Self {
+        self.progress_sink = Some(Custom(Box::new(progress_sink)));
+        self
+    }
+}
+
+/// Creates the [crate::BundleProofWriter] for the given path.
+fn create_proof_bundle(path: &str) -> Result<Box<dyn ProofWriter + Send>> {
+    #[cfg(feature = "zstd")]
+    return Ok(Box::new(crate::BundleProofWriter::create(path)?));
+
+    #[cfg(not(feature = "zstd"))]
+    anyhow::bail!("Bundling proofs into {} requires the `zstd` feature", path);
+}
+
+/// A custom component of the [KernelBuilder], which is debug-printed as its type.
+struct Custom<T: ?Sized>(Box<T>);
+
+impl<T: ?Sized> fmt::Debug for Custom<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(std::any::type_name::<T>())
+    }
+}
+
+/// Creates the [TraceExporter] for the given path, selected by its extension.
+fn create_trace_exporter(path: &str) -> Result<Box<dyn TraceExporter>> {
+    if path.ends_with(".parquet") {
+        #[cfg(feature = "parquet")]
+        return Ok(Box::new(crate::ParquetTraceExporter::create(path)?));
+
+        #[cfg(not(feature = "parquet"))]
+        anyhow::bail!("Exporting Parquet traces requires the `parquet` feature");
+    }
+    Ok(Box::new(JsonlTraceExporter::create(path)?))
+}
+
+/// Loads the [State] at the given path. Binary state files are memory-mapped, so that their pages
+/// are only loaded on first access. Otherwise, the compressed state dump is read from the file,
+/// decompressed with the given [Codec] (selected by file extension or detected if not specified),
+/// and deserialized.
+///
+/// Memory-mapped states require that their file is not modified in place while they are alive.
+/// The kernel only ever replaces state files with [cannon_mipsevm::ser::replace_file].
+pub(crate) fn load_state(path: &str, codec: Option<Codec>) -> Result<State> {
+    if path.ends_with(".bin") {
+        // SAFETY: State files are only replaced by renaming over them, never modified in place.
+        return Ok(unsafe { State::load_mmapped(path)? });
+    }
+
+    let f = File::open(path)?;
+    let f_sz = f.metadata()?.len();
+    let mut reader = BufReader::new(f);
+    // Give a reasonable capacity to the vector to avoid too many reallocations. The size of the file
+    // is the size of the compressed state dump, so we will still reallocate.
+    let mut raw_state = Vec::with_capacity(f_sz as usize);
+    reader.read_to_end(&mut raw_state)?;
+    let codec = codec
+        .or_else(|| Codec::from_path(path))
+        .unwrap_or_else(|| Codec::detect(&raw_state));
+    let raw_state = codec.decompress(&raw_state)?;
+    Ok(State::from_json(&raw_state)?)
 }
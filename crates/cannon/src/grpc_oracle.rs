@@ -0,0 +1,305 @@
+//! This module contains the gRPC transport of the preimage oracle protocol, which allows hosts
+//! written in other languages or running on other machines to serve preimages to Cannon over the
+//! network. The protocol is described in `proto/preimage.proto`.
+//!
+//! The [GrpcPreimageOracle] is the client side used by the emulator, and the [GrpcPreimageService]
+//! serves any [PreimageOracle] to remote emulators.
+
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{CannonError, CannonResult, PreimageOracle};
+use preimage_oracle::Hint;
+use std::{fs, future::Future, io, net::SocketAddr, path::Path, sync::Mutex};
+use tokio::{
+    net::TcpListener,
+    runtime::{Builder, Runtime},
+};
+use tonic::{
+    transport::{
+        server::TcpIncoming, Certificate, Channel, ClientTlsConfig, Endpoint, Identity, Server,
+        ServerTlsConfig,
+    },
+    Code, Request, Response, Status,
+};
+
+/// The generated bindings of the `cannon.preimage.v1` protocol.
+pub mod proto {
+    tonic::include_proto!("cannon.preimage.v1");
+}
+
+use proto::{
+    preimage_oracle_client::PreimageOracleClient,
+    preimage_oracle_server::{PreimageOracle as PreimageOracleRpc, PreimageOracleServer},
+    GetPreimageRequest, GetPreimageResponse, HintRequest, HintResponse,
+};
+
+/// The [GrpcPreimageOracle] is a [PreimageOracle] that requests hints and preimages from a host
+/// over gRPC. Endpoints with the `https` scheme are connected to over TLS.
+///
+/// The emulator is synchronous, so the client drives its requests on a runtime of its own. The
+/// requests are spawned onto the runtime rather than blocked on, so that the oracle may be used
+/// from within another runtime, such as the one of the [crate::Kernel].
+pub struct GrpcPreimageOracle {
+    /// The client of the host.
+    client: PreimageOracleClient<Channel>,
+    /// The runtime that drives the client's requests.
+    runtime: Option<Runtime>,
+}
+
+impl GrpcPreimageOracle {
+    /// Connects to the host at the given endpoint.
+    ///
+    /// ### Takes
+    /// - `endpoint`: The URL of the host, e.g. `https://preimages.example.com:50051`.
+    /// - `ca_cert`: The path to a PEM encoded CA certificate to verify the host with. The system's
+    ///   root certificates are used if `None`. Ignored for endpoints without TLS.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the connected [GrpcPreimageOracle].
+    pub fn connect(endpoint: &str, ca_cert: Option<&Path>) -> Result<Self> {
+        let mut endpoint = Endpoint::from_shared(endpoint.to_string())?;
+        if endpoint.uri().scheme_str() == Some("https") {
+            let mut tls = ClientTlsConfig::new();
+            if let Some(host) = endpoint.uri().host() {
+                tls = tls.domain_name(host);
+            }
+            if let Some(ca_cert) = ca_cert {
+                tls = tls.ca_certificate(Certificate::from_pem(fs::read(ca_cert)?));
+            }
+            endpoint = endpoint.tls_config(tls)?;
+        }
+
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let channel = spawn_blocking_on(&runtime, async move { endpoint.connect().await })?;
+        crate::traces::info!(target: "cannon::preimage::grpc", "Connected to the gRPC preimage oracle");
+
+        Ok(Self {
+            client: PreimageOracleClient::new(channel),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Runs a request of the client to completion on its runtime.
+    fn request<T, F>(&self, request: impl FnOnce(PreimageOracleClient<Channel>) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<Response<T>, Status>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("Runtime is only taken on drop");
+        let response = spawn_blocking_on(runtime, request(self.client.clone()));
+        response
+            .map(Response::into_inner)
+            .map_err(|status| match status.code() {
+                Code::NotFound => anyhow!("Preimage not found: {}", status.message()),
+                _ => anyhow!("gRPC request failed: {}", status),
+            })
+    }
+}
+
+impl PreimageOracle for GrpcPreimageOracle {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        let hint = value.hint().to_vec();
+        self.request(|mut client| async move { client.hint(HintRequest { hint }).await })
+            .map(|_| ())
+            .map_err(CannonError::OracleIo)
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        let key = key.to_vec();
+        self.request(
+            |mut client| async move { client.get_preimage(GetPreimageRequest { key }).await },
+        )
+        .map(|response| response.preimage)
+        .map_err(CannonError::OracleIo)
+    }
+}
+
+impl Drop for GrpcPreimageOracle {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which is not allowed within another runtime.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Spawns a future onto the runtime and blocks the current thread until it completes.
+fn spawn_blocking_on<F>(runtime: &Runtime, future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    runtime.spawn(async move {
+        let _ = tx.send(future.await);
+    });
+    rx.recv().expect("The gRPC runtime stopped")
+}
+
+/// The [GrpcPreimageService] serves the hints and preimages of a [PreimageOracle] over gRPC, e.g.
+/// to share the preimages of a local host with emulators on other machines.
+pub struct GrpcPreimageService<P: PreimageOracle + Send + 'static> {
+    /// The oracle that serves the requests, one at a time.
+    oracle: Mutex<P>,
+}
+
+impl<P: PreimageOracle + Send + 'static> GrpcPreimageService<P> {
+    /// Creates a new [GrpcPreimageService] for the given oracle.
+    pub fn new(oracle: P) -> Self {
+        Self {
+            oracle: Mutex::new(oracle),
+        }
+    }
+
+    /// Serves the oracle at the given address until the server fails.
+    ///
+    /// ### Takes
+    /// - `addr`: The address to listen on.
+    /// - `tls`: The PEM encoded certificate and private key of the server, if it should only
+    ///   accept TLS connections.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the server failed.
+    pub async fn serve(self, addr: SocketAddr, tls: Option<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        self.serve_with_listener(TcpListener::bind(addr).await?, tls)
+            .await
+    }
+
+    /// Serves the oracle on a bound listener until the server fails, e.g. on a listener bound to
+    /// port 0 to let the OS pick a free port.
+    ///
+    /// ### Takes
+    /// - `listener`: The listener to accept connections from.
+    /// - `tls`: The PEM encoded certificate and private key of the server, if it should only
+    ///   accept TLS connections.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the server failed.
+    pub async fn serve_with_listener(
+        self,
+        listener: TcpListener,
+        tls: Option<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<()> {
+        let mut server = Server::builder();
+        if let Some((cert, key)) = tls {
+            server = server
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))?;
+        }
+        let incoming =
+            TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!("{}", e))?;
+        server
+            .add_service(PreimageOracleServer::new(self))
+            .serve_with_incoming(incoming)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Maps an error of the served [PreimageOracle] to the matching gRPC [Status].
+///
+/// Oracles report missing preimages as [CannonError::OracleIo] errors, so those are mapped to
+/// [Code::NotFound] unless they were caused by an I/O error of the host, which is mapped to
+/// [Code::Unavailable], or [Code::DeadlineExceeded] if it timed out. All other errors are
+/// [Code::Internal].
+fn oracle_status(err: CannonError) -> Status {
+    match err {
+        CannonError::OracleIo(e) => {
+            let kind = e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<io::Error>())
+                .map(io::Error::kind);
+            match kind {
+                None | Some(io::ErrorKind::NotFound) => Status::not_found(e.to_string()),
+                Some(io::ErrorKind::TimedOut) => Status::deadline_exceeded(e.to_string()),
+                Some(_) => Status::unavailable(e.to_string()),
+            }
+        }
+        e => Status::internal(e.to_string()),
+    }
+}
+
+#[tonic::async_trait]
+impl<P: PreimageOracle + Send + 'static> PreimageOracleRpc for GrpcPreimageService<P> {
+    async fn hint(&self, request: Request<HintRequest>) -> Result<Response<HintResponse>, Status> {
+        let hint = request.into_inner().hint;
+        self.oracle
+            .lock()
+            .map_err(|_| Status::internal("Oracle poisoned"))?
+            .hint(hint.as_slice())
+            .map_err(oracle_status)?;
+        Ok(Response::new(HintResponse {}))
+    }
+
+    async fn get_preimage(
+        &self,
+        request: Request<GetPreimageRequest>,
+    ) -> Result<Response<GetPreimageResponse>, Status> {
+        let key: [u8; 32] = request
+            .into_inner()
+            .key
+            .as_slice()
+            .try_into()
+            .map_err(|_| Status::invalid_argument("The preimage key must be 32 bytes"))?;
+        let preimage = self
+            .oracle
+            .lock()
+            .map_err(|_| Status::internal("Oracle poisoned"))?
+            .get(key)
+            .map_err(oracle_status)?;
+        Ok(Response::new(GetPreimageResponse { preimage }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::StaticOracle;
+    use preimage_oracle::{Keccak256Key, Key};
+
+    #[test]
+    fn grpc_roundtrip() {
+        let runtime = Runtime::new().unwrap();
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime.spawn(
+            GrpcPreimageService::new(StaticOracle::new(b"hello world".to_vec()))
+                .serve_with_listener(listener, None),
+        );
+
+        let mut oracle = GrpcPreimageOracle::connect(&format!("http://{}", addr), None).unwrap();
+
+        let key = (alloy_primitives::keccak256(b"hello world").0 as Keccak256Key).preimage_key();
+        oracle.hint(b"l1-block 0x01".as_slice()).unwrap();
+        assert_eq!(oracle.get(key).unwrap(), b"hello world".to_vec());
+        assert!(matches!(
+            oracle.get([0xFF; 32]),
+            Err(CannonError::OracleIo(_))
+        ));
+    }
+
+    #[test]
+    fn oracle_errors_map_to_status_codes() {
+        let io_error = |kind| CannonError::OracleIo(io::Error::new(kind, "host").into());
+        assert_eq!(
+            oracle_status(CannonError::OracleIo(anyhow!("No image for key"))).code(),
+            Code::NotFound
+        );
+        assert_eq!(
+            oracle_status(io_error(io::ErrorKind::BrokenPipe)).code(),
+            Code::Unavailable
+        );
+        assert_eq!(
+            oracle_status(io_error(io::ErrorKind::TimedOut)).code(),
+            Code::DeadlineExceeded
+        );
+        assert_eq!(
+            oracle_status(CannonError::Other(anyhow!("poisoned"))).code(),
+            Code::Internal
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! This module contains the [GuestLogWriter], which intercepts structured log lines that the guest
+//! writes to stderr and forwards them through the host's `tracing` subscriber.
+
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+/// The `tracing` target that guest logs are forwarded under.
+pub const GUEST_LOG_TARGET: &str = "cannon::guest";
+
+/// The maximum length of an incomplete line that the [GuestLogWriter] buffers. Longer lines are
+/// written to the inner sink unchanged as soon as they reach it.
+pub const MAX_GUEST_LOG_LINE: usize = 64 * 1024;
+
+/// The level of a structured guest log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestLogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl GuestLogLevel {
+    /// Parses the level of a guest log line, accepting both the `slog` (`DEBUG`, `WARN`) and the
+    /// `log15` (`dbug`, `eror`, `crit`) spellings, case-insensitively.
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.to_ascii_lowercase().as_str() {
+            "trace" | "trce" => Some(Self::Trace),
+            "debug" | "dbug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" | "eror" | "crit" | "critical" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// A structured log line written by the guest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuestLog {
+    /// The level of the line.
+    pub level: GuestLogLevel,
+    /// The message of the line.
+    pub msg: String,
+    /// The remaining key-value pairs of the line, except for its timestamp.
+    pub fields: Map<String, Value>,
+}
+
+impl GuestLog {
+    /// Parses a JSON log line, as written by `op-program` with `--log.format=json`.
+    ///
+    /// ### Takes
+    /// - `line`: The line, without its trailing newline.
+    ///
+    /// ### Returns
+    /// - `Some(log)` if the line is a JSON object with a known level and a message.
+    /// - `None` otherwise.
+    pub fn parse(line: &str) -> Option<Self> {
+        let Value::Object(mut fields) = serde_json::from_str(line.trim()).ok()? else {
+            return None;
+        };
+        let level = ["level", "lvl"]
+            .iter()
+            .find_map(|key| fields.remove(*key))?;
+        let level = GuestLogLevel::parse(level.as_str()?)?;
+        let msg = match fields.remove("msg").or_else(|| fields.remove("message"))? {
+            Value::String(msg) => msg,
+            msg => msg.to_string(),
+        };
+        // The guest's clock is not meaningful to the host.
+        fields.remove("time");
+        fields.remove("t");
+        Some(Self { level, msg, fields })
+    }
+
+    /// Returns the remaining key-value pairs of the line as a JSON object.
+    pub fn fields_json(&self) -> Value {
+        Value::Object(self.fields.clone())
+    }
+
+    /// Forwards the line to the host's `tracing` subscriber, under the [GUEST_LOG_TARGET].
+    pub fn forward(&self) {
+        match self.level {
+            GuestLogLevel::Trace => {
+                crate::traces::trace!(target: GUEST_LOG_TARGET, fields = %self.fields_json(), "{}", self.msg)
+            }
+            GuestLogLevel::Debug => {
+                crate::traces::debug!(target: GUEST_LOG_TARGET, fields = %self.fields_json(), "{}", self.msg)
+            }
+            GuestLogLevel::Info => {
+                crate::traces::info!(target: GUEST_LOG_TARGET, fields = %self.fields_json(), "{}", self.msg)
+            }
+            GuestLogLevel::Warn => {
+                crate::traces::warn!(target: GUEST_LOG_TARGET, fields = %self.fields_json(), "{}", self.msg)
+            }
+            GuestLogLevel::Error => {
+                crate::traces::error!(target: GUEST_LOG_TARGET, fields = %self.fields_json(), "{}", self.msg)
+            }
+        }
+    }
+}
+
+/// The [GuestLogWriter] wraps the sink of the guest's stderr. Lines that start with its prefix
+/// and parse as a [GuestLog] are forwarded through `tracing`; all other output is written to the
+/// inner sink unchanged.
+///
+/// Lines are only intercepted if the `tracing` feature is enabled, as they would otherwise be
+/// lost.
+pub struct GuestLogWriter<W: Write> {
+    /// The sink that unstructured output is written to.
+    inner: W,
+    /// Whether structured lines are intercepted.
+    intercept: bool,
+    /// The prefix that structured lines start with, stripped before parsing.
+    prefix: String,
+    /// The incomplete line that has been written so far, up to [MAX_GUEST_LOG_LINE] bytes.
+    line: Vec<u8>,
+}
+
+impl<W: Write> GuestLogWriter<W> {
+    /// Creates a new [GuestLogWriter] that intercepts JSON log lines.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            intercept: cfg!(feature = "tracing"),
+            prefix: String::new(),
+            line: Vec::new(),
+        }
+    }
+
+    /// Creates a new [GuestLogWriter] that writes all output to the inner sink unchanged.
+    pub fn passthrough(inner: W) -> Self {
+        Self {
+            intercept: false,
+            ..Self::new(inner)
+        }
+    }
+
+    /// Only intercepts lines that start with the given prefix, e.g. `CANNON_LOG `.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Handles a complete line, including its trailing newline.
+    fn handle_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let log = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.strip_prefix(self.prefix.as_str()))
+            .and_then(GuestLog::parse);
+        match log {
+            Some(log) => {
+                log.forward();
+                Ok(())
+            }
+            None => self.inner.write_all(line),
+        }
+    }
+}
+
+impl<W: Write> Write for GuestLogWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.intercept {
+            return self.inner.write(buf);
+        }
+
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|&b| b == b'\n') {
+            let (line, tail) = rest.split_at(pos + 1);
+            if self.line.is_empty() {
+                self.handle_line(line)?;
+            } else {
+                let mut full = std::mem::take(&mut self.line);
+                full.extend_from_slice(line);
+                self.handle_line(&full)?;
+            }
+            rest = tail;
+        }
+        self.line.extend_from_slice(rest);
+        if self.line.len() >= MAX_GUEST_LOG_LINE {
+            let line = std::mem::take(&mut self.line);
+            self.inner.write_all(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner sink. An incomplete line stays buffered until it is terminated, so that
+    /// a structured line split across several writes is still intercepted.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for GuestLogWriter<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            let _ = self.handle_line(&line);
+            let _ = self.inner.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_guest_logs() {
+        let log = GuestLog::parse(
+            r#"{"t":"2024-01-01T00:00:00Z","lvl":"dbug","msg":"Fetching block","number":12}"#,
+        )
+        .unwrap();
+        assert_eq!(log.level, GuestLogLevel::Debug);
+        assert_eq!(log.msg, "Fetching block");
+        assert_eq!(log.fields.len(), 1);
+        assert_eq!(log.fields["number"], 12);
+
+        let log = GuestLog::parse(r#"{"time":"x","level":"WARN","msg":"Slow"}"#).unwrap();
+        assert_eq!(log.level, GuestLogLevel::Warn);
+        assert!(log.fields.is_empty());
+
+        assert!(GuestLog::parse("panic: runtime error").is_none());
+        assert!(GuestLog::parse(r#"{"msg":"no level"}"#).is_none());
+        assert!(GuestLog::parse(r#"{"level":"loud","msg":"unknown level"}"#).is_none());
+    }
+
+    #[test]
+    fn passes_through_unstructured_output() {
+        let mut out = Vec::new();
+        {
+            let mut writer = GuestLogWriter::new(&mut out).with_prefix("LOG ");
+            writer
+                .write_all(b"LOG {\"lvl\":\"info\",\"msg\":\"hi\"}\nplain ")
+                .unwrap();
+            writer
+                .write_all(b"text\n{\"lvl\":\"info\",\"msg\":\"no prefix\"}\ntail")
+                .unwrap();
+            writer.flush().unwrap();
+        }
+        let intercepted = if cfg!(feature = "tracing") {
+            ""
+        } else {
+            "LOG {\"lvl\":\"info\",\"msg\":\"hi\"}\n"
+        };
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{intercepted}plain text\n{{\"lvl\":\"info\",\"msg\":\"no prefix\"}}\ntail")
+        );
+    }
+
+    #[test]
+    fn flushes_long_lines() {
+        let mut out = Vec::new();
+        let mut writer = GuestLogWriter::new(&mut out);
+        writer.intercept = true;
+
+        let chunk = vec![b'x'; MAX_GUEST_LOG_LINE / 2];
+        writer.write_all(&chunk).unwrap();
+        assert!(writer.inner.is_empty());
+        writer.write_all(&chunk).unwrap();
+        assert_eq!(writer.inner.len(), MAX_GUEST_LOG_LINE);
+        assert!(writer.line.is_empty());
+
+        writer.write_all(b"end\n").unwrap();
+        drop(writer);
+        assert_eq!(out.len(), MAX_GUEST_LOG_LINE + 4);
+    }
+}
@@ -0,0 +1,135 @@
+//! This module contains the [PanicDetector], which detects when the guest enters the panic handler
+//! of its runtime, so that a run of a program that crashed can be told apart from a run of a
+//! program that proved its claim invalid.
+//!
+//! Both end with a non-zero exit code, and the [cannon_mipsevm::VMStatus] committed to in the state
+//! hash is defined by the exit code alone. The detector only affects the [crate::Outcome] reported
+//! to the host, never the state.
+
+use cannon_mipsevm::{Address, HookAction, InstrumentedState, Metadata};
+use std::{cell::RefCell, fmt, io::Write, rc::Rc};
+
+/// The symbols of the panic handlers of the Go and Rust runtimes, which are detected by default.
+pub const DEFAULT_PANIC_SYMBOLS: &[&str] = &[
+    "runtime.fatalpanic",
+    "runtime.fatalthrow",
+    "rust_begin_unwind",
+    "rust_panic",
+];
+
+/// A [GuestPanic] records the panic handler that the guest entered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestPanic {
+    /// The name of the panic handler.
+    pub symbol: String,
+    /// The step at which the handler was entered.
+    pub step: u64,
+    /// The program counter of the handler's entry.
+    pub pc: Address,
+}
+
+impl fmt::Display for GuestPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "guest entered {} (pc: {:08x}) at step {}",
+            self.symbol, self.pc, self.step
+        )
+    }
+}
+
+/// The [PanicDetector] watches for the guest entering any of a set of panic handlers, through
+/// function-entry hooks of the [InstrumentedState]. It records the first panic handler entered;
+/// the guest keeps running, so that it exits the way it would without the detector.
+#[derive(Debug, Clone, Default)]
+pub struct PanicDetector {
+    /// The first panic handler entered by the guest, if any.
+    detected: Rc<RefCell<Option<GuestPanic>>>,
+}
+
+impl PanicDetector {
+    /// Installs a [PanicDetector] on the given [InstrumentedState].
+    ///
+    /// ### Takes
+    /// - `ins_state`: The [InstrumentedState] to watch.
+    /// - `meta`: The [Metadata] of the program, containing its symbols.
+    /// - `patterns`: The names of the panic handlers, where `*` matches any sequence of
+    ///   characters.
+    ///
+    /// ### Returns
+    /// - The installed [PanicDetector].
+    pub fn install<O: Write, E: Write, P: cannon_mipsevm::PreimageOracle>(
+        ins_state: &mut InstrumentedState<O, E, P>,
+        meta: &Metadata,
+        patterns: &[String],
+    ) -> Self {
+        let detector = Self::default();
+        for pattern in patterns {
+            let detected = Rc::clone(&detector.detected);
+            let matched = ins_state.on_enter(meta, pattern, move |state, name| {
+                let mut detected = detected.borrow_mut();
+                if detected.is_none() {
+                    crate::traces::warn!(target: "cannon::panic", "Guest entered panic handler {} at step {}", name, state.step);
+                    *detected = Some(GuestPanic {
+                        symbol: name.to_string(),
+                        step: state.step,
+                        pc: state.pc,
+                    });
+                }
+                HookAction::Continue
+            });
+            if matched == 0 {
+                crate::traces::debug!(target: "cannon::panic", "The program has no panic handler matching {}", pattern);
+            }
+        }
+        detector
+    }
+
+    /// Returns the first panic handler entered by the guest, if any.
+    pub fn detected(&self) -> Option<GuestPanic> {
+        self.detected.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::StaticOracle;
+    use std::io;
+
+    #[test]
+    fn detects_panic_handlers() {
+        let elf = include_bytes!("../../../example/bin/hello.elf");
+        let mut state = cannon_mipsevm::load_elf(elf).unwrap();
+        cannon_mipsevm::patch_go(elf, &mut state).unwrap();
+        cannon_mipsevm::patch_stack(&mut state).unwrap();
+        let meta = Metadata::from_elf(elf).unwrap();
+        let entry = meta
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == "runtime.main")
+            .map(|symbol| symbol.start)
+            .unwrap();
+
+        let mut ins_state = InstrumentedState::new(
+            state,
+            StaticOracle::new(b"hello world".to_vec()),
+            io::sink(),
+            io::sink(),
+        );
+        // `runtime.main` stands in for a panic handler, as the program never panics.
+        let detector = PanicDetector::install(
+            &mut ins_state,
+            &meta,
+            &["runtime.fatal*".to_string(), "runtime.main".to_string()],
+        );
+        while !ins_state.state.exited {
+            ins_state.step(false).unwrap();
+        }
+
+        let panic = detector.detected().unwrap();
+        assert_eq!(panic.symbol, "runtime.main");
+        assert_eq!(panic.pc, entry);
+        assert_eq!(ins_state.state.exit_code, 0);
+    }
+}
@@ -1,27 +1,17 @@
 //! This module contains utilities for compressing and decompressing serialized bytes using gzip.
 
 use anyhow::Result;
-use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
-use std::io::{Read, Write};
+use cannon_mipsevm::ser::Codec;
 
 /// Compresses a byte slice using gzip.
 #[inline(always)]
 pub fn compress_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
-    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len()), Compression::default());
-    encoder.write_all(bytes)?;
-    Ok(encoder.finish()?)
+    Ok(Codec::Gzip.compress(bytes)?)
 }
 
 /// Decompresses a byte slice using gzip.
 pub fn decompress_bytes(compressed_bytes: &[u8]) -> Result<Vec<u8>> {
-    let mut decoder = GzDecoder::new(compressed_bytes);
-
-    // Give our decompressed buffer the same capacity as the compressed buffer to reduce
-    // reallocations up to the compressed buffer's size.
-    let mut decompressed_bytes = Vec::with_capacity(compressed_bytes.len());
-    decoder.read_to_end(&mut decompressed_bytes)?;
-
-    Ok(decompressed_bytes)
+    Ok(Codec::Gzip.decompress(compressed_bytes)?)
 }
 
 #[cfg(test)]
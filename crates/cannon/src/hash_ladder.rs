@@ -0,0 +1,411 @@
+//! This module contains the hash ladder file format: the compact list of the state hashes at every
+//! Nth step of a trace, which bisection in a dispute game commits to.
+//!
+//! A ladder file starts with a header holding [HASH_LADDER_MAGIC], [HASH_LADDER_VERSION], the
+//! interval between hashes and the first step of the ladder. The hashes follow in blocks of up to
+//! [HASH_LADDER_BLOCK_SIZE], each prefixed with its length and suffixed with a checksum that
+//! chains the keccak256 hash of the previous checksum (or the header, for the first block) and
+//! the block's hashes. A block is only written once complete, so an interrupted generator leaves
+//! behind a file whose valid prefix can be resumed with [HashLadderWriter::resume].
+//!
+//! Hashes are taken at the steps that are multiples of the interval. Once the program exits, its
+//! state no longer changes, so the ladder ends with the first multiple of the interval at or
+//! after the final step, whose hash is that of the final state.
+
+use alloy_primitives::keccak256;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{InstrumentedState, PreimageOracle};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// The magic bytes at the start of a hash ladder file.
+pub const HASH_LADDER_MAGIC: [u8; 8] = *b"CNNLADDR";
+
+/// The version of the hash ladder file format.
+pub const HASH_LADDER_VERSION: u32 = 1;
+
+/// The maximum number of hashes per checksummed block of a hash ladder file.
+pub const HASH_LADDER_BLOCK_SIZE: usize = 1024;
+
+/// The size of the header of a hash ladder file.
+const HEADER_SIZE: usize = 8 + 4 + 8 + 8;
+
+/// A [HashLadder] holds the state hashes at every `interval`th step of a trace, starting at
+/// `first_step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashLadder {
+    /// The number of steps between consecutive hashes.
+    pub interval: u64,
+    /// The step of the first hash.
+    pub first_step: u64,
+    /// The state hashes, in step order.
+    pub hashes: Vec<[u8; 32]>,
+}
+
+impl HashLadder {
+    /// Loads a [HashLadder] from a file, verifying its checksums.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [HashLadder], or an error if the file is truncated or any of
+    ///   its checksums does not match.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut raw = Vec::new();
+        File::open(path)?.read_to_end(&mut raw)?;
+        let (ladder, _, valid_len) = Self::decode(&raw)?;
+        anyhow::ensure!(
+            valid_len == raw.len(),
+            "Hash ladder is corrupt or truncated after {} hashes",
+            ladder.hashes.len()
+        );
+        Ok(ladder)
+    }
+
+    /// Returns the step of the hash at the given index.
+    pub fn step_of(&self, index: usize) -> u64 {
+        self.first_step + index as u64 * self.interval
+    }
+
+    /// Returns the state hash at the given step, if the ladder holds it.
+    pub fn hash_at(&self, step: u64) -> Option<[u8; 32]> {
+        if step < self.first_step || (step - self.first_step) % self.interval != 0 {
+            return None;
+        }
+        let index = (step - self.first_step) / self.interval;
+        self.hashes.get(usize::try_from(index).ok()?).copied()
+    }
+
+    /// Encodes the header of a hash ladder file.
+    fn encode_header(interval: u64, first_step: u64) -> [u8; HEADER_SIZE] {
+        let mut header = [0u8; HEADER_SIZE];
+        header[..8].copy_from_slice(&HASH_LADDER_MAGIC);
+        header[8..12].copy_from_slice(&HASH_LADDER_VERSION.to_be_bytes());
+        header[12..20].copy_from_slice(&interval.to_be_bytes());
+        header[20..28].copy_from_slice(&first_step.to_be_bytes());
+        header
+    }
+
+    /// Decodes the valid prefix of a hash ladder file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [HashLadder] of all complete blocks with matching checksums,
+    ///   the checksum of the last of them, and the length of the valid prefix in bytes.
+    fn decode(raw: &[u8]) -> Result<(Self, [u8; 32], usize)> {
+        anyhow::ensure!(raw.len() >= HEADER_SIZE, "Hash ladder header is truncated");
+        anyhow::ensure!(raw[..8] == HASH_LADDER_MAGIC, "Not a hash ladder file");
+        let version = u32::from_be_bytes(raw[8..12].try_into()?);
+        anyhow::ensure!(
+            version == HASH_LADDER_VERSION,
+            "Unsupported hash ladder version {}",
+            version
+        );
+        let interval = u64::from_be_bytes(raw[12..20].try_into()?);
+        anyhow::ensure!(interval > 0, "Hash ladder interval must be positive");
+        let first_step = u64::from_be_bytes(raw[20..28].try_into()?);
+
+        let mut ladder = Self {
+            interval,
+            first_step,
+            hashes: Vec::new(),
+        };
+        let mut checksum = keccak256(&raw[..HEADER_SIZE]).0;
+        let mut offset = HEADER_SIZE;
+        while let Some(len) = raw.get(offset..offset + 4) {
+            let len = u32::from_be_bytes(len.try_into()?) as usize;
+            let end = offset + 4 + len * 32 + 32;
+            if len == 0 || len > HASH_LADDER_BLOCK_SIZE || end > raw.len() {
+                break;
+            }
+            let hashes = &raw[offset + 4..end - 32];
+            let expected = chain_checksum(&checksum, hashes);
+            if raw[end - 32..end] != expected {
+                break;
+            }
+            ladder.hashes.extend(
+                hashes
+                    .chunks_exact(32)
+                    .map(|hash| <[u8; 32]>::try_from(hash).expect("Chunk is 32 bytes")),
+            );
+            checksum = expected;
+            offset = end;
+        }
+        Ok((ladder, checksum, offset))
+    }
+}
+
+/// The [HashLadderWriter] appends state hashes to a hash ladder file, one checksummed block at a
+/// time.
+pub struct HashLadderWriter {
+    /// The writer of the ladder file.
+    writer: BufWriter<File>,
+    /// The number of steps between consecutive hashes.
+    interval: u64,
+    /// The step of the first hash.
+    first_step: u64,
+    /// The number of hashes written in complete blocks.
+    written: u64,
+    /// The hashes of the block being assembled.
+    pending: Vec<[u8; 32]>,
+    /// The checksum of the last complete block.
+    checksum: [u8; 32],
+    /// The last hash pushed, along with its step.
+    last: Option<(u64, [u8; 32])>,
+}
+
+impl HashLadderWriter {
+    /// Creates a new hash ladder file, truncating any existing one.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the file.
+    /// - `interval`: The number of steps between consecutive hashes.
+    /// - `first_step`: The step of the first hash, which must be a multiple of `interval`.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [HashLadderWriter].
+    pub fn create(path: impl AsRef<Path>, interval: u64, first_step: u64) -> Result<Self> {
+        anyhow::ensure!(interval > 0, "Hash ladder interval must be positive");
+        anyhow::ensure!(
+            first_step % interval == 0,
+            "The first step of the hash ladder must be a multiple of its interval"
+        );
+
+        let header = HashLadder::encode_header(interval, first_step);
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&header)?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            interval,
+            first_step,
+            written: 0,
+            pending: Vec::with_capacity(HASH_LADDER_BLOCK_SIZE),
+            checksum: keccak256(header).0,
+            last: None,
+        })
+    }
+
+    /// Opens an existing hash ladder file to append to, discarding any trailing block that was
+    /// only partially written or whose checksum does not match.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [HashLadderWriter].
+    pub fn resume(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut raw = Vec::new();
+        file.read_to_end(&mut raw)?;
+
+        let (ladder, checksum, valid_len) = HashLadder::decode(&raw)?;
+        if valid_len < raw.len() {
+            crate::traces::warn!(target: "cannon::hash_ladder", "Discarding {} bytes of incomplete hash ladder data", raw.len() - valid_len);
+            file.set_len(valid_len as u64)?;
+        }
+        file.seek(SeekFrom::Start(valid_len as u64))?;
+
+        let last = ladder
+            .hashes
+            .last()
+            .map(|hash| (ladder.step_of(ladder.hashes.len() - 1), *hash));
+        Ok(Self {
+            writer: BufWriter::new(file),
+            interval: ladder.interval,
+            first_step: ladder.first_step,
+            written: ladder.hashes.len() as u64,
+            pending: Vec::with_capacity(HASH_LADDER_BLOCK_SIZE),
+            checksum,
+            last,
+        })
+    }
+
+    /// Returns the number of steps between consecutive hashes.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Returns the step of the next hash to push.
+    pub fn next_step(&self) -> u64 {
+        self.first_step + (self.written + self.pending.len() as u64) * self.interval
+    }
+
+    /// Returns the last hash pushed, along with its step.
+    pub fn last(&self) -> Option<(u64, [u8; 32])> {
+        self.last
+    }
+
+    /// Appends the state hash at [HashLadderWriter::next_step], writing out the current block
+    /// once it is complete.
+    pub fn push(&mut self, hash: [u8; 32]) -> Result<()> {
+        self.last = Some((self.next_step(), hash));
+        self.pending.push(hash);
+        if self.pending.len() == HASH_LADDER_BLOCK_SIZE {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the incomplete block, if any, and flushes the file.
+    pub fn finish(mut self) -> Result<()> {
+        self.write_block()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the pending hashes as a block, followed by its checksum.
+    fn write_block(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let hashes = self.pending.concat();
+        self.checksum = chain_checksum(&self.checksum, &hashes);
+        self.writer
+            .write_all(&(self.pending.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&hashes)?;
+        self.writer.write_all(&self.checksum)?;
+        self.writer.flush()?;
+
+        self.written += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Runs the [InstrumentedState] and pushes the state hash at every step of the ladder to the
+/// [HashLadderWriter], until the program exits or the ladder passes `stop_at`.
+///
+/// If the ladder already holds hashes, e.g. after [HashLadderWriter::resume], the VM is first
+/// fast-forwarded to the step of the last of them, and its state hash there must match.
+///
+/// ### Takes
+/// - `ins`: The [InstrumentedState], at or before the step of the next hash of the ladder.
+/// - `writer`: The [HashLadderWriter] to push hashes to.
+/// - `stop_at`: The last step to take a hash at, if any.
+///
+/// ### Returns
+/// - A [Result] containing the number of hashes pushed.
+pub fn generate_hash_ladder<O, E, P>(
+    ins: &mut InstrumentedState<O, E, P>,
+    mut writer: HashLadderWriter,
+    stop_at: Option<u64>,
+) -> Result<u64>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    if let Some((step, hash)) = writer.last() {
+        anyhow::ensure!(
+            ins.state.step <= step,
+            "The state at step {} is past the end of the hash ladder at step {}",
+            ins.state.step,
+            step
+        );
+        ins.step_threaded(step - ins.state.step)?;
+        let local_hash = ins.state.state_hash()?;
+        anyhow::ensure!(
+            local_hash == hash,
+            "The state hash at step {} does not match the hash ladder",
+            step
+        );
+        if ins.state.exited {
+            writer.finish()?;
+            return Ok(0);
+        }
+    }
+
+    let mut pushed = 0;
+    loop {
+        let target = writer.next_step();
+        if stop_at.is_some_and(|stop_at| target > stop_at) {
+            break;
+        }
+        let step = ins.state.step;
+        let remaining = target.checked_sub(step).ok_or(anyhow!(
+            "The state at step {} is past step {}",
+            step,
+            target
+        ))?;
+        ins.step_threaded(remaining)?;
+
+        writer.push(ins.state.state_hash()?)?;
+        pushed += 1;
+        if ins.state.exited {
+            break;
+        }
+    }
+    writer.finish()?;
+    Ok(pushed)
+}
+
+/// Chains a checksum with the hashes of the next block.
+fn chain_checksum(checksum: &[u8; 32], hashes: &[u8]) -> [u8; 32] {
+    keccak256([checksum.as_slice(), hashes].concat()).0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::{counting_state, StaticOracle};
+    use std::{fs, io};
+
+    #[test]
+    fn ladder_roundtrip_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ladder.bin");
+
+        // The reference ladder, generated in one go.
+        let mut ins = InstrumentedState::new(
+            counting_state(1875),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let writer = HashLadderWriter::create(&path, 3, 0).unwrap();
+        let pushed = generate_hash_ladder(&mut ins, writer, None).unwrap();
+        assert!(ins.state.exited);
+        let ladder = HashLadder::load(&path).unwrap();
+        assert_eq!(ladder.hashes.len() as u64, pushed);
+        assert!(ladder.hashes.len() > HASH_LADDER_BLOCK_SIZE);
+        assert_eq!(
+            ladder.hash_at(ladder.step_of(ladder.hashes.len() - 1)),
+            Some(ins.state.state_hash().unwrap())
+        );
+        assert_eq!(ladder.hash_at(1), None);
+
+        // An interrupted run, whose trailing block was cut off mid-write.
+        let mut ins = InstrumentedState::new(
+            counting_state(1875),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let writer = HashLadderWriter::create(&path, 3, 0).unwrap();
+        generate_hash_ladder(&mut ins, writer, Some(3 * 1500)).unwrap();
+        let raw = fs::read(&path).unwrap();
+        fs::write(&path, &raw[..raw.len() - 40]).unwrap();
+        assert!(HashLadder::load(&path).is_err());
+
+        let mut ins = InstrumentedState::new(
+            counting_state(1875),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let writer = HashLadderWriter::resume(&path).unwrap();
+        assert_eq!(writer.next_step(), 3 * HASH_LADDER_BLOCK_SIZE as u64);
+        generate_hash_ladder(&mut ins, writer, None).unwrap();
+        assert_eq!(HashLadder::load(&path).unwrap(), ladder);
+
+        // Corrupted hashes are detected.
+        let mut raw = fs::read(&path).unwrap();
+        raw[HEADER_SIZE + 4] ^= 1;
+        fs::write(&path, &raw).unwrap();
+        assert!(HashLadder::load(&path).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! This module contains the [StepJournal], an append-only journal of the snapshots written by a
+//! run, which allows the run to be resumed from its last durable snapshot after a crash.
+//!
+//! The journal is a JSONL file. Its first line is a [JournalHeader] holding the command of the
+//! run, and every following line a [JournalEntry]. A line that was torn by a crash is ignored.
+//! Paths are recorded as absolute paths, or relative to the recorded working directory of the
+//! run, so that a run can be resumed from any directory.
+
+use crate::builder::load_state;
+use alloy_primitives::B256;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::State;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// The version of the journal format.
+pub const JOURNAL_VERSION: u32 = 1;
+
+/// The default number of entries appended to a [StepJournal] between `fsync`s.
+pub const DEFAULT_JOURNAL_SYNC_EVERY: usize = 1;
+
+/// The [JournalHeader] is the first line of a journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalHeader {
+    /// The version of the journal format.
+    pub version: u32,
+    /// The arguments of the command that started the run, which a resumed run is started with.
+    pub command: Vec<String>,
+    /// The absolute working directory of the command, which the relative paths among its
+    /// arguments are resolved against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+}
+
+/// A [JournalEntry] records a state that was written during a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The step of the state.
+    pub step: u64,
+    /// The hash of the state's witness.
+    pub state_hash: B256,
+    /// The absolute path that the state was written to.
+    pub snapshot: String,
+    /// Whether the state is the final state of the run, which cannot be resumed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub last: bool,
+}
+
+/// The [StepJournal] appends a [JournalEntry] for every state written during a run, after the
+/// state itself has been synced to disk. The journal is synced every `sync_every` entries, so an
+/// entry is only durable once a sync has followed it.
+#[derive(Debug)]
+pub struct StepJournal {
+    /// The path of the journal.
+    path: PathBuf,
+    /// The journal file, opened for appending.
+    file: File,
+    /// The number of entries between syncs.
+    sync_every: usize,
+    /// The number of entries appended since the last sync.
+    pending: usize,
+}
+
+impl StepJournal {
+    /// Opens the journal at the given path for appending, creating it with a header holding the
+    /// given command if it does not exist. A line torn by a crash is truncated.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the journal.
+    /// - `command`: The arguments of the command that started the run, recorded along with the
+    ///   current working directory. Ignored if the journal exists, in which case its original
+    ///   command is kept.
+    /// - `sync_every`: The number of entries between syncs.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [StepJournal].
+    pub fn open(path: impl AsRef<Path>, command: Vec<String>, sync_every: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let exists = path.metadata().is_ok_and(|meta| meta.len() > 0);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+
+        if exists {
+            Journal::load(&path)?;
+            let contents = fs::read(&path)?;
+            let intact = contents
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |pos| pos + 1);
+            if intact < contents.len() {
+                crate::traces::warn!(target: "cannon::journal", "Truncating a torn entry of the journal at {}", path.display());
+                file.set_len(intact as u64)?;
+            }
+        } else {
+            let header = JournalHeader {
+                version: JOURNAL_VERSION,
+                command,
+                cwd: Some(std::env::current_dir()?),
+            };
+            writeln!(file, "{}", serde_json::to_string(&header)?)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            path,
+            file,
+            sync_every: sync_every.max(1),
+            pending: 0,
+        })
+    }
+
+    /// Returns the path of the journal.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends an entry to the journal, syncing it if `sync_every` entries are pending.
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(entry)?)?;
+        self.pending += 1;
+        if self.pending >= self.sync_every {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Syncs all pending entries to disk.
+    pub fn sync(&mut self) -> Result<()> {
+        if self.pending > 0 {
+            self.file.sync_data()?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StepJournal {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+/// The [Journal] holds the contents of a journal, as read by a resumed run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journal {
+    /// The header of the journal.
+    pub header: JournalHeader,
+    /// The intact entries of the journal, in the order they were appended.
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Loads the journal at the given path, ignoring a torn last line.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: JournalHeader = serde_json::from_str(
+            &lines
+                .next()
+                .ok_or(anyhow!("The journal at {} is empty", path.display()))??,
+        )?;
+        anyhow::ensure!(
+            header.version == JOURNAL_VERSION,
+            "Unsupported journal version {}, expected {}",
+            header.version,
+            JOURNAL_VERSION
+        );
+
+        let mut entries = Vec::new();
+        let mut lines = lines.peekable();
+        while let Some(line) = lines.next() {
+            match serde_json::from_str(&line?) {
+                Ok(entry) => entries.push(entry),
+                // Only the last line can be torn by a crash.
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Self { header, entries })
+    }
+
+    /// Returns whether the run finished, i.e. whether its last entry holds its final state.
+    pub fn is_finished(&self) -> bool {
+        self.entries.last().is_some_and(|entry| entry.last)
+    }
+
+    /// Finds the latest entry whose snapshot is intact, i.e. loads with a valid checksum and
+    /// matches the hash of the entry. Snapshots that were lost or corrupted in the crash are
+    /// skipped.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the latest intact entry and its [State], or `None` if no
+    ///   snapshot is intact.
+    pub fn recover(&self) -> Result<Option<(&JournalEntry, State)>> {
+        for entry in self.entries.iter().rev().filter(|entry| !entry.last) {
+            match load_intact(entry) {
+                Ok(state) => return Ok(Some((entry, state))),
+                Err(_e) => {
+                    crate::traces::warn!(target: "cannon::journal", "Skipping the snapshot {} at step {}: {}", entry.snapshot, entry.step, _e);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Loads the snapshot of an entry, verifying its checksum and hash.
+fn load_intact(entry: &JournalEntry) -> Result<State> {
+    let mut state = load_state(&entry.snapshot, None)?;
+    state.verify_checksum()?;
+    let state_hash = B256::from(state.state_hash()?);
+    anyhow::ensure!(
+        state.step == entry.step && state_hash == entry.state_hash,
+        "The snapshot does not match the journal"
+    );
+    Ok(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Seek;
+
+    #[test]
+    fn journal_recovers_last_intact_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let journal_path = dir.join("run.journal");
+
+        let mut journal = StepJournal::open(&journal_path, vec!["--input".into()], 2).unwrap();
+        for step in [0, 10] {
+            let mut state = State {
+                step,
+                ..Default::default()
+            };
+            state.seal().unwrap();
+            let snapshot = dir.join(format!("{step}.json"));
+            fs::write(&snapshot, serde_json::to_vec(&state).unwrap()).unwrap();
+            journal
+                .append(&JournalEntry {
+                    step,
+                    state_hash: B256::from(state.state_hash().unwrap()),
+                    snapshot: snapshot.display().to_string(),
+                    last: false,
+                })
+                .unwrap();
+        }
+        // The snapshot at step 20 was lost in the crash, and the last entry torn.
+        journal
+            .append(&JournalEntry {
+                step: 20,
+                state_hash: B256::ZERO,
+                snapshot: dir.join("20.json").display().to_string(),
+                last: false,
+            })
+            .unwrap();
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&journal_path).unwrap();
+        file.seek(std::io::SeekFrom::End(0)).unwrap();
+        file.write_all(b"{\"step\":30,\"sta").unwrap();
+
+        let loaded = Journal::load(&journal_path).unwrap();
+        assert_eq!(loaded.header.command, ["--input"]);
+        assert_eq!(loaded.header.cwd, Some(std::env::current_dir().unwrap()));
+        assert_eq!(loaded.entries.len(), 3);
+        assert!(!loaded.is_finished());
+        let (entry, state) = loaded.recover().unwrap().unwrap();
+        assert_eq!((entry.step, state.step), (10, 10));
+
+        // Reopening truncates the torn line before appending.
+        let mut journal = StepJournal::open(&journal_path, Vec::new(), 1).unwrap();
+        journal
+            .append(&JournalEntry {
+                step: 40,
+                state_hash: B256::ZERO,
+                snapshot: "out.json".to_string(),
+                last: true,
+            })
+            .unwrap();
+        let loaded = Journal::load(&journal_path).unwrap();
+        assert_eq!(loaded.entries.len(), 4);
+        assert!(loaded.is_finished());
+    }
+}
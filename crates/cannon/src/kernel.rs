@@ -1,17 +1,25 @@
 //! This module contains the [Kernel] struct and its associated methods.
 
-use crate::{gz::compress_bytes, types::Proof, ChildWithFds};
-use anyhow::{anyhow, Result};
-use cannon_mipsevm::{InstrumentedState, PreimageOracle, StateWitnessHasher};
+use crate::{
+    prover::Prover, CancellationToken, ChildWithFds, JournalEntry, ManifestRecorder, Outcome,
+    PanicDetector, Progress, ProgressSink, ShadowVerifier, StateHashIndex, StepJournal, StepRecord,
+    TraceExporter,
+};
+use alloy_primitives::B256;
+use anyhow::Result;
+use cannon_mipsevm::{
+    ser::{self, Codec},
+    CannonError, CannonResult, InstrumentedState, Metadata, PreimageOracle, VMStatus,
+    UNKNOWN_SYMBOL,
+};
 use std::{
     fs::File,
     io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
 };
 use tokio::{runtime::Runtime, task::JoinHandle};
 
-#[cfg(feature = "tracing")]
-use std::time::Instant;
-
 /// The [Kernel] struct contains the configuration for a Cannon kernel as well as
 /// the [PreimageOracle] and [InstrumentedState] instances that form it.
 #[allow(dead_code)]
@@ -27,11 +35,15 @@ pub struct Kernel<O: Write, E: Write, P: PreimageOracle> {
     input: String,
     /// The path to the output JSON state.
     output: Option<String>,
+    /// The path to the metadata of the program, containing its symbols.
+    meta: Option<String>,
+    /// The [Codec] that the output state and snapshots are compressed with. Selected by file
+    /// extension if not specified, falling back to [Codec::Gzip].
+    codec: Option<Codec>,
     /// The step to generate an output proof at.
     proof_at: Option<String>,
-    /// Format for proof data output file names. Proof data is written to stdout
-    /// if this is not specified.
-    proof_format: Option<String>,
+    /// The [Prover] that generates proofs and writes them to their sink.
+    prover: Prover,
     /// The step pattern to generate state snapshots at.
     snapshot_at: Option<String>,
     /// Format for snapshot data output file names.
@@ -40,6 +52,28 @@ pub struct Kernel<O: Write, E: Write, P: PreimageOracle> {
     stop_at: Option<String>,
     /// The pattern to print information at.
     info_at: Option<String>,
+    /// The sink that [Progress] reports are sent to at the steps matching `info_at`.
+    progress_sink: Box<dyn ProgressSink>,
+    /// Whether or not to use the threaded execution mode for steps that do not require a proof.
+    threaded: bool,
+    /// The connection to a companion Go Cannon process to cross-verify state hashes against.
+    shadow: Option<ShadowVerifier>,
+    /// The step pattern to cross-verify state hashes at.
+    shadow_at: Option<String>,
+    /// The token that interrupts the run, e.g. upon receiving a termination signal.
+    cancellation: Option<CancellationToken>,
+    /// The sink that a [StepRecord] of every executed instruction is written to, if any.
+    trace_exporter: Option<Box<dyn TraceExporter>>,
+    /// The path to write the coverage report of the guest program to.
+    coverage_out: Option<String>,
+    /// The path to write the instruction histogram of the guest program to.
+    histogram_out: Option<String>,
+    /// The journal that every written state is recorded in, for resuming after a crash.
+    journal: Option<StepJournal>,
+    /// The recorder of the chain-of-custody manifest of the artifacts written by the run.
+    manifest: Option<ManifestRecorder>,
+    /// The names of the guest's panic handlers, whose entry marks the run as crashed.
+    panic_symbols: Vec<String>,
 }
 
 impl<O, E, P> Kernel<O, E, P>
@@ -54,130 +88,337 @@ where
         server_proc: Option<ChildWithFds>,
         input: String,
         output: Option<String>,
+        meta: Option<String>,
+        codec: Option<Codec>,
         proof_at: Option<String>,
-        proof_format: Option<String>,
+        prover: Prover,
         snapshot_at: Option<String>,
         snapshot_format: Option<String>,
         stop_at: Option<String>,
         info_at: Option<String>,
+        progress_sink: Box<dyn ProgressSink>,
+        threaded: bool,
+        shadow: Option<ShadowVerifier>,
+        shadow_at: Option<String>,
+        cancellation: Option<CancellationToken>,
+        trace_exporter: Option<Box<dyn TraceExporter>>,
+        coverage_out: Option<String>,
+        histogram_out: Option<String>,
+        journal: Option<StepJournal>,
+        manifest: Option<ManifestRecorder>,
+        panic_symbols: Vec<String>,
     ) -> Self {
         Self {
             ins_state,
             server_proc,
             input,
             output,
+            meta,
+            codec,
             proof_at,
-            proof_format,
+            prover,
             snapshot_at,
             snapshot_format,
             stop_at,
             info_at,
+            progress_sink,
+            threaded,
+            shadow,
+            shadow_at,
+            cancellation,
+            trace_exporter,
+            coverage_out,
+            histogram_out,
+            journal,
+            manifest,
+            panic_symbols,
+        }
+    }
+
+    /// Serializes the current state for writing to the given path, with its checksum embedded.
+    /// States written to `.bin` paths use the binary state file format, which can be resumed from
+    /// without parsing every page upfront. All other states are serialized as JSON.
+    ///
+    /// ### Returns
+    /// - The serialized state and the [Codec] to compress it with.
+    fn encode_state(&mut self, path: &str) -> Result<(Vec<u8>, Codec)> {
+        self.ins_state.state.seal()?;
+        if path.ends_with(".bin") {
+            let mut bin_state = Vec::new();
+            self.ins_state.state.write_binary(&mut bin_state)?;
+            return Ok((bin_state, Codec::None));
+        }
+
+        let codec = self
+            .codec
+            .or_else(|| Codec::from_path(path))
+            .unwrap_or(Codec::Gzip);
+        Ok((serde_json::to_vec(&self.ins_state.state)?, codec))
+    }
+
+    /// Records the state written to the given path in the journal, if any.
+    fn journal_state(&mut self, path: &str, last: bool) -> Result<()> {
+        if let Some(ref mut journal) = self.journal {
+            let entry = JournalEntry {
+                step: self.ins_state.state.step,
+                state_hash: B256::from(self.ins_state.state.state_hash()?),
+                snapshot: std::path::absolute(path)?.display().to_string(),
+                last,
+            };
+            journal.append(&entry)?;
+        }
+        Ok(())
+    }
+
+    /// Symbolizes the error of a failed step. The instruction histogram, if any, is written
+    /// before the error is returned, as the instruction that failed is often the one to implement.
+    fn check_step(&mut self, stepped: CannonResult<()>, meta: &Metadata) -> Result<()> {
+        if let Err(e) = stepped {
+            if let Err(write_err) = self.write_histogram(meta) {
+                crate::traces::warn!(target: "cannon::kernel", "Failed to write the instruction histogram: {}", write_err);
+            }
+            return Err(symbolize(e, meta));
         }
+        Ok(())
     }
 
-    pub fn run(mut self) -> Result<()> {
+    /// Writes the report of the instruction histogram to `histogram_out`, if it is enabled.
+    /// Reports at `.json` paths are written as JSON, all others as text.
+    fn write_histogram(&mut self, meta: &Metadata) -> Result<()> {
+        let (Some(path), Some(histogram)) = (
+            self.histogram_out.as_ref(),
+            self.ins_state.instruction_histogram(),
+        ) else {
+            return Ok(());
+        };
+
+        let report = histogram.report(meta);
+        crate::traces::info!(target: "cannon::kernel", "Writing histogram of {} instructions to {}", report.total, path);
+        for encounter in &report.unsupported {
+            crate::traces::warn!(target: "cannon::kernel", "Encountered unsupported {} at 0x{:08x} in {} ({} times)", encounter.detail, encounter.pc, encounter.symbol.as_deref().unwrap_or(UNKNOWN_SYMBOL), encounter.count);
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path.ends_with(".json") {
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+        } else {
+            write!(writer, "{}", report)?;
+        }
+        writer.flush()?;
+        if let Some(ref mut manifest) = self.manifest {
+            manifest.record(path.as_str());
+        }
+        Ok(())
+    }
+
+    /// Runs the program until it exits, the `stop_at` step is reached, or the run is interrupted
+    /// through its [CancellationToken].
+    ///
+    /// When interrupted, the current step is finished, and the state is written as a snapshot
+    /// that the run can be resumed from along with all proofs generated so far.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [Outcome] of the run.
+    pub fn run(mut self) -> Result<Outcome> {
         let rt = Runtime::new().unwrap();
 
         rt.block_on(async move {
-            let stop_at = create_matcher(self.stop_at.as_ref())?;
+            // A `hash:<claim>` stop pattern requires the state hash of every step.
+            let mut claim_index = StateHashIndex::from_pattern(self.stop_at.as_ref())?;
+            let (stop_at, hash_check) = match claim_index {
+                Some(_) => (Matcher::Never, Matcher::Always),
+                None => (create_matcher(self.stop_at.as_ref())?, Matcher::Never),
+            };
             let proof_at = create_matcher(self.proof_at.as_ref())?;
             let snapshot_at = create_matcher(self.snapshot_at.as_ref())?;
+            let shadow_at = create_matcher(self.shadow_at.as_ref())?;
+
+            let snapshot_fmt = self
+                .snapshot_format
+                .take()
+                .unwrap_or("%d.json.gz".to_string());
 
-            let proof_fmt = self.proof_format.unwrap_or("%d.json.gz".to_string());
-            let snapshot_fmt = self.snapshot_format.unwrap_or("%d.json.gz".to_string());
+            // The symbols of the program are used to detect when it enters functions that signal
+            // a problem with the program. In the threaded execution mode, these are only checked
+            // at the steps that require the kernel's attention.
+            let meta = self
+                .meta
+                .as_deref()
+                .map(Metadata::load)
+                .transpose()?
+                .unwrap_or_default();
+            let sleep_check = meta.symbol_matcher("runtime.notesleep");
+            let gc_check = meta.symbol_matcher("runtime.gcenable");
+            let mut warned_gc = false;
+            // Panic handlers are detected through function-entry hooks, which are checked at every
+            // step in both execution modes.
+            let panic_detector =
+                PanicDetector::install(&mut self.ins_state, &meta, &self.panic_symbols);
 
-            #[cfg(feature = "tracing")]
             let (info_at, start_step, start) = (
                 create_matcher(self.info_at.as_ref())?,
                 self.ins_state.state.step,
                 Instant::now(),
             );
+            // The remaining steps of the run can only be estimated if it stops at a known step.
+            let target_step = match stop_at {
+                Matcher::Equal(step) => Some(step),
+                _ => None,
+            };
+
+            let cancel_check = match self.cancellation {
+                Some(_) => CANCELLATION_CHECK,
+                None => Matcher::Never,
+            };
+
+            // Every instruction is recorded individually when exporting a trace, so the threaded
+            // execution mode is not used.
+            let threaded = self.threaded && self.trace_exporter.is_none();
+            if self.trace_exporter.is_some() {
+                self.ins_state.set_record_mem_access(true);
+            }
+            if self.coverage_out.is_some() {
+                self.ins_state.enable_coverage();
+            }
+            if self.histogram_out.is_some() {
+                self.ins_state.enable_instruction_histogram();
+            }
 
             let mut io_tasks: Vec<JoinHandle<Result<()>>> = Vec::default();
 
+            let mut outcome = None;
             while !self.ins_state.state.exited {
                 let step = self.ins_state.state.step;
 
-                #[cfg(feature = "tracing")]
                 if info_at.matches(step) {
-                    let delta = start.elapsed();
-                    crate::traces::info!(
-                        target: "cannon::kernel",
-                        "[ELAPSED: {}.{:03}s] step: {}, pc: {}, instruction: {:08x}, ips: {}, pages: {}, mem: {}",
-                        delta.as_secs(),
-                        delta.subsec_millis(),
-                        step,
-                        self.ins_state.state.pc,
-                        self.ins_state.state.memory.get_memory(self.ins_state.state.pc)?,
-                        (step - start_step) as f64 / delta.as_secs_f64(),
-                        self.ins_state.state.memory.page_count(),
-                        self.ins_state.state.memory.usage(),
-                    );
+                    let symbol = meta.lookup_symbol(self.ins_state.state.pc).to_string();
+                    let progress = Progress::capture(
+                        &mut self.ins_state.state,
+                        start_step,
+                        start,
+                        Some(symbol),
+                        target_step,
+                    )?;
+                    self.progress_sink.report(&progress);
                 }
 
                 if stop_at.matches(step) {
                     crate::traces::info!(target: "cannon::kernel", "Stopping at step {}", step);
+                    outcome = Some(Outcome::Stopped);
                     break;
                 }
 
+                if let Some(ref mut index) = claim_index {
+                    let state_hash = B256::from(self.ins_state.state.state_hash()?);
+                    if index.record(step, state_hash) {
+                        outcome = Some(Outcome::Stopped);
+                        break;
+                    }
+                }
+
+                if cancel_check.matches(step)
+                    && self
+                        .cancellation
+                        .as_ref()
+                        .is_some_and(CancellationToken::is_cancelled)
+                {
+                    crate::traces::info!(target: "cannon::kernel", "Interrupted at step {}", step);
+                    outcome = Some(Outcome::Cancelled);
+                    break;
+                }
+
+                // Don't loop forever when the program gets stuck.
+                if sleep_check.matches(self.ins_state.state.pc) {
+                    anyhow::bail!("Got stuck in Go sleep at step {}", step);
+                }
+                if !warned_gc && gc_check.matches(self.ins_state.state.pc) {
+                    warned_gc = true;
+                    crate::traces::warn!(target: "cannon::kernel", "Program entered runtime.gcenable at step {}; the Go runtime was not patched", step);
+                }
+
+                if let Some(ref mut shadow) = self.shadow {
+                    if shadow_at.matches(step) {
+                        let local_hash = self.ins_state.state.state_hash()?;
+                        shadow.verify(&self.ins_state.state, local_hash)?;
+                    }
+                }
+
                 if snapshot_at.matches(step) {
                     crate::traces::info!(target: "cannon::kernel", "Writing snapshot at step {}", step);
-                    let ser_state = serde_json::to_vec(&self.ins_state.state).unwrap();
                     let snap_path = snapshot_fmt.replace("%d", &format!("{}", step));
-                    io_tasks.push(tokio::task::spawn(async move {
-                        let gz_state = compress_bytes(&ser_state)?;
-                        let mut writer = BufWriter::new(File::create(snap_path)?);
-                        writer.write_all(&gz_state)?;
-                        crate::traces::info!(target: "cannon::kernel", "Wrote snapshot at step {} successfully.", step);
-
-                        Ok(())
-                    }));
+                    if let Some(ref mut manifest) = self.manifest {
+                        manifest.record(snap_path.as_str());
+                    }
+                    let (ser_state, codec) = self.encode_state(&snap_path)?;
+                    if self.journal.is_some() {
+                        // The snapshot must be durable before the journal entry pointing to it.
+                        write_durable(&snap_path, &codec.compress(&ser_state)?)?;
+                        self.journal_state(&snap_path, false)?;
+                    } else {
+                        io_tasks.push(tokio::task::spawn(async move {
+                            let compressed_state = codec.compress(&ser_state)?;
+                            let mut writer = BufWriter::new(File::create(snap_path)?);
+                            writer.write_all(&compressed_state)?;
+                            crate::traces::info!(target: "cannon::kernel", "Wrote snapshot at step {} successfully.", step);
+
+                            Ok(())
+                        }));
+                    }
                 }
 
+                let mut record = self
+                    .trace_exporter
+                    .is_some()
+                    .then(|| StepRecord::begin(&mut self.ins_state.state))
+                    .transpose()?;
+
                 if proof_at.matches(step) {
                     crate::traces::info!(target: "cannon::kernel", "Writing proof at step {}", step);
 
-                    let prestate_hash = self.ins_state.state.encode_witness()?.state_hash();
-                    let step_witness = self
-                        .ins_state
-                        .step(true)?
-                        .ok_or(anyhow!("No step witness"))?;
-                    let poststate_hash = self.ins_state.state.encode_witness()?.state_hash();
-
-                    let proof_path = proof_fmt.replace("%d", &format!("{}", step));
-                    io_tasks.push(tokio::task::spawn(async move {
-                        let proof = {
-                            let preimage_input = step_witness.encode_preimage_oracle_input();
-                            Proof {
-                                step,
-                                pre: prestate_hash,
-                                post: poststate_hash,
-                                state_data: step_witness.state,
-                                step_input: step_witness.encode_step_input().to_vec(),
-                                proof_data: step_witness.mem_proof,
-                                oracle_input: preimage_input.map(|k| k.to_vec()),
-                                oracle_key: step_witness.preimage_key.map(|k| k.to_vec()),
-                                oracle_value: step_witness.preimage_value,
-                                oracle_offset: step_witness.preimage_offset,
-                            }
-                        };
-
-                        let ser_proof = &serde_json::to_string(&proof)?;
-                        let mut writer = BufWriter::new(File::create(proof_path)?);
-                        writer.write_all(ser_proof.as_bytes())?;
+                    let stepped = self.prover.prove(&mut self.ins_state);
+                    self.check_step(stepped, &meta)?;
+                    if let Some(ref mut manifest) = self.manifest {
+                        manifest.record_proof(step);
+                    }
 
-                        crate::traces::info!(target: "cannon::kernel", "Wrote proof at step {} successfully.", step);
+                    crate::traces::info!(target: "cannon::kernel", "Wrote proof at step {} successfully.", step);
+                } else if threaded {
+                    // Run up until the next step that requires the kernel's attention in the
+                    // threaded execution mode.
+                    let next_event = [
+                        &stop_at,
+                        &hash_check,
+                        &proof_at,
+                        &snapshot_at,
+                        &shadow_at,
+                        &cancel_check,
+                        &info_at,
+                        &SERVER_CHECK,
+                    ]
+                    .into_iter()
+                    .filter_map(|m| m.next_match(step))
+                    .min()
+                    .unwrap_or(u64::MAX);
 
-                        Ok(())
-                    }));
+                    let stepped = self
+                        .ins_state
+                        .step_threaded(next_event - step)
+                        .map(|_| ());
+                    self.check_step(stepped, &meta)?;
                 } else {
-                    self.ins_state.step(false)?;
+                    let stepped = self.ins_state.step(false).map(|_| ());
+                    self.check_step(stepped, &meta)?;
+                }
+
+                if let (Some(record), Some(exporter)) = (&mut record, &mut self.trace_exporter) {
+                    record.finish(&mut self.ins_state)?;
+                    exporter.write_record(record)?;
                 }
 
                 // Periodically check if the preimage server process has exited. If it has, then
                 // we should exit as well with a failure.
                 // TODO: This may be problematic.
-                if step % 10_000_000 == 0 {
+                if SERVER_CHECK.matches(step) {
                     if let Some(ref mut proc) = self.server_proc {
                         match proc.inner.try_wait() {
                             Ok(Some(status)) => {
@@ -193,21 +434,102 @@ where
                 }
             }
 
-            // Output the final state
-            if let Some(output) = &self.output {
+            if let Some(index) = claim_index.as_mut() {
+                if outcome.is_none() {
+                    // The final state of the program is the last state that can match the claim.
+                    let step = self.ins_state.state.step;
+                    index.record(step, B256::from(self.ins_state.state.state_hash()?));
+                }
+
+                #[cfg(feature = "tracing")]
+                {
+                    match index.find(index.claim()) {
+                        Some(step) => {
+                            crate::traces::info!(target: "cannon::kernel", "Found the claimed state {} at step {}", index.claim(), step)
+                        }
+                        None => {
+                            crate::traces::warn!(target: "cannon::kernel", "The run ended at step {} without reaching the claimed state {}", self.ins_state.state.step, index.claim())
+                        }
+                    }
+                    // The states leading up to the end of the run locate the claim inside the
+                    // trace.
+                    for (step, state_hash) in index.recent() {
+                        crate::traces::debug!(target: "cannon::kernel", "State {} at step {}", state_hash, step);
+                    }
+                }
+            }
+
+            let outcome = outcome.unwrap_or_else(|| {
+                let status = self.ins_state.state.status();
+                // A program that recovers from a panic and exits with a valid status did not crash.
+                match panic_detector.detected().filter(|_| status != VMStatus::Valid) {
+                    Some(panic) => {
+                        crate::traces::error!(target: "cannon::kernel", "The program crashed: {}", panic);
+                        Outcome::Panicked(status)
+                    }
+                    None => Outcome::Exited(status),
+                }
+            });
+            if outcome == Outcome::Cancelled {
+                // Write the current state to a snapshot rather than the output, so that an
+                // interrupted run is never mistaken for a finished one.
+                let step = self.ins_state.state.step;
+                let snap_path = snapshot_fmt.replace("%d", &format!("{}", step));
+                crate::traces::info!(target: "cannon::kernel", "Writing resumable state at step {} to {}", step, snap_path);
+
+                let (ser_state, codec) = self.encode_state(&snap_path)?;
+                write_durable(&snap_path, &codec.compress(&ser_state)?)?;
+                self.journal_state(&snap_path, false)?;
+                if let Some(ref mut manifest) = self.manifest {
+                    manifest.record(snap_path);
+                }
+            } else if let Some(output) = self.output.clone() {
+                // Output the final state
                 if !output.is_empty() {
                     crate::traces::info!(target: "cannon::kernel", "Writing final state to {}", output);
-                    let mut writer = BufWriter::new(File::create(output)?);
-
-                    let ser_state = &serde_json::to_vec(&self.ins_state.state)?;
-                    let gz_state = compress_bytes(ser_state)?;
 
-                    writer.write_all(&gz_state)?;
+                    // The state is encoded before the output is replaced, as its pages may still
+                    // be mapped from the output if the run resumed from it.
+                    let (ser_state, codec) = self.encode_state(&output)?;
+                    let compressed_state = codec.compress(&ser_state)?;
+                    ser::replace_file(&output, |writer| writer.write_all(&compressed_state))?;
+                    if let Some(ref mut manifest) = self.manifest {
+                        manifest.record(output.as_str());
+                    }
                 }
+                self.journal_state(&output, true)?;
             } else {
                 println!("{:?}", &self.ins_state.state);
+                self.journal_state("", true)?;
+            }
+            if let Some(ref mut journal) = self.journal {
+                journal.sync()?;
+            }
+
+            self.prover.flush()?;
+            if let Some(ref mut exporter) = self.trace_exporter {
+                exporter.finish()?;
+            }
+            if let (Some(path), Some(coverage)) =
+                (self.coverage_out.as_ref(), self.ins_state.coverage())
+            {
+                crate::traces::info!(target: "cannon::kernel", "Writing coverage of {} instructions to {}", coverage.len(), path);
+                let mut writer = BufWriter::new(File::create(path)?);
+                // LCOV reports are symbolized with the program's metadata. All other paths
+                // receive the raw list of executed addresses.
+                if path.ends_with(".info") || path.ends_with(".lcov") {
+                    coverage.write_lcov(&meta, &mut writer)?;
+                } else {
+                    coverage.write_addresses(&mut writer)?;
+                }
+                writer.flush()?;
+                if let Some(ref mut manifest) = self.manifest {
+                    manifest.record(path.as_str());
+                }
             }
 
+            self.write_histogram(&meta)?;
+
             crate::traces::info!(target: "cannon::kernel", "Kernel exiting...");
 
             // Wait for all of the i/o tasks to finish.
@@ -215,13 +537,36 @@ where
                 task.await??;
             }
 
+            // The artifacts are hashed once all of them have been written.
+            if let Some(manifest) = self.manifest.take() {
+                manifest.finish()?;
+            }
+
             // File descriptors are closed when the kernel struct is dropped, since it owns all open IO.
-            Ok(())
+            Ok(outcome)
         })
     }
 }
 
-enum Matcher {
+/// Writes a state to the given path and syncs it to disk, so that it survives a crash. The file
+/// is replaced atomically, and its directory synced so that the rename itself is durable.
+fn write_durable(path: &str, data: &[u8]) -> Result<()> {
+    ser::replace_file(path, |writer| writer.write_all(data))?;
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// The interval at which the kernel checks whether the preimage server process is still alive.
+const SERVER_CHECK: Matcher = Matcher::MultipleOf(10_000_000);
+
+/// The interval at which the kernel checks whether the run has been interrupted.
+const CANCELLATION_CHECK: Matcher = Matcher::MultipleOf(1 << 16);
+
+pub(crate) enum Matcher {
     Never,
     Always,
     Equal(u64),
@@ -230,7 +575,7 @@ enum Matcher {
 
 impl Matcher {
     #[inline(always)]
-    fn matches(&self, value: u64) -> bool {
+    pub(crate) fn matches(&self, value: u64) -> bool {
         match self {
             Matcher::Never => false,
             Matcher::Always => true,
@@ -238,9 +583,21 @@ impl Matcher {
             Matcher::MultipleOf(steps) => value % steps == 0,
         }
     }
+
+    /// Returns the first step strictly after `value` that the [Matcher] matches, if any.
+    #[inline(always)]
+    pub(crate) fn next_match(&self, value: u64) -> Option<u64> {
+        match self {
+            Matcher::Never => None,
+            Matcher::Always => value.checked_add(1),
+            Matcher::Equal(step) => (*step > value).then_some(*step),
+            Matcher::MultipleOf(0) => None,
+            Matcher::MultipleOf(steps) => (value / steps + 1).checked_mul(*steps),
+        }
+    }
 }
 
-fn create_matcher(pattern: Option<&String>) -> Result<Matcher> {
+pub(crate) fn create_matcher(pattern: Option<&String>) -> Result<Matcher> {
     match pattern {
         None => Ok(Matcher::Never),
         Some(pattern) => match pattern.as_str() {
@@ -268,3 +625,79 @@ fn create_matcher(pattern: Option<&String>) -> Result<Matcher> {
         },
     }
 }
+
+/// Attributes a fault on an unsupported instruction to the function of the guest that contains
+/// it, if the program's symbols are known.
+///
+/// ### Takes
+/// - `err`: The error returned by the [InstrumentedState].
+/// - `meta`: The [Metadata] of the program.
+///
+/// ### Returns
+/// - The error, with the name of the faulting function attached as context.
+fn symbolize(err: CannonError, meta: &Metadata) -> anyhow::Error {
+    let pc = match err {
+        CannonError::InvalidInstruction { pc, .. }
+        | CannonError::FloatingPointInstruction { pc, .. } => pc,
+        _ => return err.into(),
+    };
+    match meta.lookup_symbol(pc) {
+        UNKNOWN_SYMBOL => err.into(),
+        symbol => {
+            let context = format!("Guest faulted in {} at pc {:08x}", symbol, pc);
+            anyhow::Error::from(err).context(context)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{prover::ReplicaProver, MemoryProofWriter};
+    use cannon_mipsevm::{
+        test_utils::{counting_state, StaticOracle},
+        Symbol,
+    };
+    use std::io;
+
+    #[test]
+    fn symbolizes_floating_point_traps_at_proof_steps() {
+        let meta = Metadata {
+            symbols: vec![Symbol {
+                name: "main.float".to_string(),
+                start: 0x1000,
+                size: 0x20,
+            }],
+        };
+
+        for replicated in [false, true] {
+            let mut state = counting_state(1);
+            // 0x1004: add.s $f0, $f0, $f0
+            state.memory.set_memory(0x1004, 0x46000000).unwrap();
+            let mut ins_state =
+                InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+
+            let writer = Box::new(MemoryProofWriter::default());
+            let mut prover = if replicated {
+                Prover::Replica(ReplicaProver::spawn(
+                    ins_state.replicate().unwrap(),
+                    writer,
+                    1,
+                ))
+            } else {
+                Prover::Inline(writer)
+            };
+
+            prover.prove(&mut ins_state).unwrap();
+            let err = prover.prove(&mut ins_state).unwrap_err();
+            assert!(matches!(
+                err,
+                CannonError::FloatingPointInstruction { pc: 0x1004, .. }
+            ));
+            assert_eq!(
+                symbolize(err, &meta).to_string(),
+                "Guest faulted in main.float at pc 00001004"
+            );
+        }
+    }
+}
@@ -1,18 +1,98 @@
 #![doc = include_str!("../README.md")]
 
+mod bisect;
+pub use bisect::{load_reference_hashes, Bisection, Bisector, Divergence, StateHashReference};
+
 mod builder;
 pub use builder::KernelBuilder;
 
 pub mod gz;
 pub use gz::{compress_bytes, decompress_bytes};
 
+mod guest_panic;
+pub use guest_panic::{GuestPanic, PanicDetector, DEFAULT_PANIC_SYMBOLS};
+
+mod guest_log;
+pub use guest_log::{
+    GuestLog, GuestLogLevel, GuestLogWriter, GUEST_LOG_TARGET, MAX_GUEST_LOG_LINE,
+};
+
+#[cfg(feature = "grpc")]
+mod grpc_oracle;
+#[cfg(feature = "grpc")]
+pub use grpc_oracle::{proto, GrpcPreimageOracle, GrpcPreimageService};
+
+mod hash_ladder;
+pub use hash_ladder::{
+    generate_hash_ladder, HashLadder, HashLadderWriter, HASH_LADDER_BLOCK_SIZE, HASH_LADDER_MAGIC,
+    HASH_LADDER_VERSION,
+};
+
+mod journal;
+pub use journal::{
+    Journal, JournalEntry, JournalHeader, StepJournal, DEFAULT_JOURNAL_SYNC_EVERY, JOURNAL_VERSION,
+};
+
 mod kernel;
 pub use kernel::Kernel;
 
+mod manifest;
+pub use manifest::{Manifest, ManifestArtifact, ManifestRecorder, MANIFEST_VERSION};
+
+mod oracle_cache;
+pub use oracle_cache::{CacheStats, CachingOracle, PREIMAGE_CACHE_WINDOW};
+
 mod proc_oracle;
 pub use proc_oracle::ProcessPreimageOracle;
 
+mod progress;
+pub use progress::{LogProgressSink, Progress, ProgressSink};
+
+#[cfg(feature = "zstd")]
+mod proof_bundle;
+#[cfg(feature = "zstd")]
+pub use proof_bundle::{
+    proof_bundle_index_path, BundleProofWriter, ProofBundle, ProofBundleEntry, ProofBundleIndex,
+    PROOF_BUNDLE_INDEX_SUFFIX, PROOF_BUNDLE_VERSION,
+};
+
+mod proof_writer;
+pub use proof_writer::{
+    DirectoryProofWriter, JsonlProofWriter, MemoryProofWriter, PipelinedProofWriter, ProofWriter,
+    DEFAULT_PROOF_QUEUE_CAPACITY,
+};
+
+mod prover;
+
+mod replay;
+pub use replay::{HostOracle, ReplayEntry, ReplayOracle, ReplayRecorder};
+
+mod run;
+pub use run::{
+    run, CancellationToken, Outcome, Program, RunConfig, RunOutcome, CANCELLATION_CHECK_INTERVAL,
+};
+
+mod shadow;
+pub use shadow::ShadowVerifier;
+
+mod state_index;
+pub use state_index::{StateHashIndex, DEFAULT_STATE_INDEX_CAPACITY, HASH_PATTERN_PREFIX};
+
+mod trace_export;
+#[cfg(feature = "parquet")]
+pub use trace_export::ParquetTraceExporter;
+pub use trace_export::{JsonlTraceExporter, StepRecord, TraceExporter};
+
+mod trace_farm;
+pub use trace_farm::{FarmOutput, TraceFarm};
+
+mod trace_provider;
+pub use trace_provider::{
+    CannonTraceProvider, Claim, Position, PreimageOracleData, StepData, TraceProvider,
+    DEFAULT_SNAPSHOT_INTERVAL,
+};
+
 mod types;
-pub use types::{ChildWithFds, Proof};
+pub use types::{ChildWithFds, Proof, PROOF_SCHEMA, PROOF_VERSION};
 
 mod traces;
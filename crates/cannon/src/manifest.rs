@@ -0,0 +1,271 @@
+//! This module contains the [Manifest], a chain-of-custody record of the artifacts produced by a
+//! run, which allows the evidence of a challenger to be audited later on.
+//!
+//! The manifest lists the hash of the input state, the hash of the ELF that the state was loaded
+//! from, the version of Cannon and the flags of the run, followed by every artifact written, e.g.
+//! proofs, snapshots and the output state. The header and the artifacts are hash-linked: each
+//! [ManifestArtifact] holds a link that commits to the link before it, starting from the digest of
+//! the header, and the manifest's `head` commits to all of them.
+
+use alloy_primitives::{keccak256, B256};
+use anyhow::{anyhow, ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The version of the manifest format.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The [Manifest] is the chain-of-custody record of a run's artifacts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The version of the manifest format.
+    pub version: u32,
+    /// The version of Cannon that produced the artifacts.
+    pub crate_version: String,
+    /// The hash of the witness of the input state.
+    pub input_state_hash: B256,
+    /// The keccak256 hash of the ELF that the input state was loaded from, if known.
+    pub elf_hash: Option<B256>,
+    /// The flags of the run.
+    pub flags: Vec<String>,
+    /// The artifacts produced by the run, in the order they were recorded.
+    pub artifacts: Vec<ManifestArtifact>,
+    /// The link of the last artifact, or the digest of the header if there are no artifacts.
+    pub head: B256,
+}
+
+/// A [ManifestArtifact] is a file listed in a [Manifest].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestArtifact {
+    /// The path of the artifact, as it was written.
+    pub path: String,
+    /// The keccak256 hash of the contents of the artifact.
+    pub hash: B256,
+    /// The hash of the previous link, the path and the hash of the artifact.
+    pub link: B256,
+}
+
+impl Manifest {
+    /// Creates a new [Manifest] without any artifacts.
+    ///
+    /// ### Takes
+    /// - `input_state_hash`: The hash of the witness of the input state.
+    /// - `elf_hash`: The keccak256 hash of the ELF that the input state was loaded from, if known.
+    /// - `flags`: The flags of the run.
+    pub fn new(input_state_hash: B256, elf_hash: Option<B256>, flags: Vec<String>) -> Self {
+        let mut manifest = Self {
+            version: MANIFEST_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            input_state_hash,
+            elf_hash,
+            flags,
+            artifacts: Vec::new(),
+            head: B256::ZERO,
+        };
+        manifest.head = manifest.header_digest();
+        manifest
+    }
+
+    /// Returns the digest of the header of the manifest, which the first link commits to.
+    pub fn header_digest(&self) -> B256 {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.version.to_be_bytes());
+        data.extend_from_slice(keccak256(&self.crate_version).as_slice());
+        data.extend_from_slice(self.input_state_hash.as_slice());
+        data.extend_from_slice(self.elf_hash.unwrap_or_default().as_slice());
+        for flag in &self.flags {
+            data.extend_from_slice(keccak256(flag).as_slice());
+        }
+        keccak256(data)
+    }
+
+    /// Appends an artifact with the given contents to the manifest, linking it to the head.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the artifact.
+    /// - `contents`: The contents of the artifact.
+    pub fn push(&mut self, path: impl Into<String>, contents: &[u8]) {
+        let path = path.into();
+        let hash = keccak256(contents);
+        let link = link(self.head, &path, hash);
+        self.artifacts.push(ManifestArtifact { path, hash, link });
+        self.head = link;
+    }
+
+    /// Loads a [Manifest] from the given path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let manifest: Self = serde_json::from_slice(&fs::read(path)?)?;
+        ensure!(
+            manifest.version == MANIFEST_VERSION,
+            "Unsupported manifest version {}",
+            manifest.version
+        );
+        Ok(manifest)
+    }
+
+    /// Writes the [Manifest] to the given path as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Verifies that the links of the manifest are intact and that every artifact matches its
+    /// hash.
+    ///
+    /// ### Takes
+    /// - `base`: The directory that relative artifact paths are resolved against.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the artifacts match the manifest.
+    pub fn verify(&self, base: impl AsRef<Path>) -> Result<()> {
+        let mut head = self.header_digest();
+        for artifact in &self.artifacts {
+            head = link(head, &artifact.path, artifact.hash);
+            ensure!(
+                artifact.link == head,
+                "The link of {} does not follow from the artifacts before it",
+                artifact.path
+            );
+
+            let path = base.as_ref().join(&artifact.path);
+            let contents =
+                fs::read(&path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let hash = keccak256(contents);
+            ensure!(
+                hash == artifact.hash,
+                "{} has hash {}, but the manifest lists {}",
+                artifact.path,
+                hash,
+                artifact.hash
+            );
+        }
+        ensure!(
+            head == self.head,
+            "The head of the manifest does not match its artifacts"
+        );
+        Ok(())
+    }
+}
+
+/// Returns the link of an artifact, committing to the previous link.
+fn link(previous: B256, path: &str, hash: B256) -> B256 {
+    let mut data = [0u8; 96];
+    data[..32].copy_from_slice(previous.as_slice());
+    data[32..64].copy_from_slice(keccak256(path).as_slice());
+    data[64..].copy_from_slice(hash.as_slice());
+    keccak256(data)
+}
+
+/// The [ManifestRecorder] collects the artifacts written by a [crate::Kernel] and writes its
+/// [Manifest] once the run has finished. Artifacts are hashed when the manifest is written, after
+/// all of them have been flushed to disk.
+#[derive(Debug)]
+pub struct ManifestRecorder {
+    /// The path to write the manifest to.
+    path: PathBuf,
+    /// The manifest, holding the header of the run.
+    manifest: Manifest,
+    /// The format of the file names of proofs, if proofs are written one file per proof.
+    proof_format: Option<String>,
+    /// The paths of the artifacts written so far, in order.
+    pending: Vec<String>,
+}
+
+impl ManifestRecorder {
+    /// Creates a new [ManifestRecorder] that writes the given manifest to `path`.
+    pub fn new(path: impl Into<PathBuf>, manifest: Manifest) -> Self {
+        Self {
+            path: path.into(),
+            manifest,
+            proof_format: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Sets the format of the file names of proofs, with `%d` replaced by the step of the proof.
+    pub fn with_proof_format(mut self, proof_format: Option<String>) -> Self {
+        self.proof_format = proof_format;
+        self
+    }
+
+    /// Records an artifact written to the given path. An artifact recorded more than once, e.g. a
+    /// JSONL file appended to, is listed once, at its first position.
+    pub fn record(&mut self, path: impl Into<String>) {
+        let path = path.into();
+        if !path.is_empty() && !self.pending.contains(&path) {
+            self.pending.push(path);
+        }
+    }
+
+    /// Records the proof generated at the given step, if proofs are written one file per proof.
+    pub fn record_proof(&mut self, step: u64) {
+        if let Some(path) = self
+            .proof_format
+            .as_ref()
+            .map(|format| format.replace("%d", &step.to_string()))
+        {
+            self.record(path);
+        }
+    }
+
+    /// Hashes the recorded artifacts and writes the [Manifest].
+    ///
+    /// ### Returns
+    /// - A [Result] containing the written [Manifest].
+    pub fn finish(mut self) -> Result<Manifest> {
+        for path in std::mem::take(&mut self.pending) {
+            let contents =
+                fs::read(&path).map_err(|e| anyhow!("Failed to read artifact {}: {}", path, e))?;
+            self.manifest.push(path, &contents);
+        }
+        self.manifest.save(&self.path)?;
+        crate::traces::info!(target: "cannon::manifest", "Wrote manifest of {} artifacts to {}", self.manifest.artifacts.len(), self.path.display());
+        Ok(self.manifest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manifest_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (proof, output) = (dir.join("1.json"), dir.join("out.json"));
+        fs::write(&proof, b"proof").unwrap();
+        fs::write(&output, b"output").unwrap();
+
+        let manifest = Manifest::new(
+            B256::repeat_byte(0xAA),
+            Some(B256::repeat_byte(0xBB)),
+            vec!["--proof-at".to_string(), "=1".to_string()],
+        );
+        let mut recorder = ManifestRecorder::new(dir.join("manifest.json"), manifest)
+            .with_proof_format(Some(dir.join("%d.json").display().to_string()));
+        recorder.record_proof(1);
+        recorder.record(output.display().to_string());
+        recorder.record(output.display().to_string());
+        let manifest = recorder.finish().unwrap();
+
+        let loaded = Manifest::load(dir.join("manifest.json")).unwrap();
+        assert_eq!(loaded, manifest);
+        assert_eq!(loaded.artifacts.len(), 2);
+        assert_eq!(loaded.artifacts[0].hash, keccak256(b"proof"));
+        loaded.verify(".").unwrap();
+
+        // Tampering with an artifact or with the manifest is detected.
+        fs::write(&output, b"tampered").unwrap();
+        assert!(loaded.verify(".").is_err());
+        fs::write(&output, b"output").unwrap();
+        let mut tampered = loaded.clone();
+        tampered.flags.clear();
+        assert!(tampered.verify(".").is_err());
+        let mut tampered = loaded;
+        tampered.artifacts.remove(0);
+        assert!(tampered.verify(".").is_err());
+    }
+}
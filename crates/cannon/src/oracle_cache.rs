@@ -0,0 +1,290 @@
+//! This module contains the [CachingOracle], a decorator that caches the preimages served by any
+//! [PreimageOracle] in memory.
+//!
+//! The VM only remembers the preimage it read last, so a guest that alternates between preimages,
+//! e.g. while walking a trie, requests each of them from the host again. Preimages are immutable
+//! for the duration of a run, so they can be served from a cache without a round-trip to the host.
+//! The cache is split into windows of the preimages, so that the parts that the guest reads stay
+//! cached the longest, and is bounded by bytes, so that a few large preimages cannot exhaust the
+//! memory of the host.
+
+use cannon_mipsevm::{CannonResult, PreimageOracle};
+use lru::LruCache;
+use preimage_oracle::{Hint, PrecompileKey};
+use std::{collections::HashMap, fmt, num::NonZeroUsize};
+
+/// The size of the windows that a [CachingOracle] splits preimages into.
+pub const PREIMAGE_CACHE_WINDOW: usize = 4096;
+
+/// The [CacheStats] count the requests served by a [CachingOracle].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of preimages served from the cache.
+    pub hits: u64,
+    /// The number of preimages requested from the wrapped oracle.
+    pub misses: u64,
+    /// The number of preimage windows evicted from the cache.
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Returns the fraction of requests that were served from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions
+        )
+    }
+}
+
+/// The [CachingOracle] wraps a [PreimageOracle] and holds the preimages that it served, split into
+/// windows of [PREIMAGE_CACHE_WINDOW] bytes keyed by the preimage key and the window's offset. The
+/// windows that the guest reads are kept the longest, and the least recently used window is
+/// evicted once the cached windows exceed the byte budget. A preimage is served from the cache
+/// while all of its windows are cached. Hints are always forwarded.
+pub struct CachingOracle<P: PreimageOracle> {
+    /// The wrapped [PreimageOracle].
+    inner: P,
+    /// The cached windows, by preimage key and window index.
+    cache: LruCache<([u8; 32], u32), Vec<u8>>,
+    /// The length of each preimage with cached windows, and the number of its cached windows.
+    preimages: HashMap<[u8; 32], (usize, usize)>,
+    /// The number of bytes of the cached windows.
+    bytes: usize,
+    /// The maximum number of bytes of the cached windows.
+    max_bytes: usize,
+    /// The counters of the requests served.
+    stats: CacheStats,
+}
+
+impl<P: PreimageOracle> CachingOracle<P> {
+    /// Creates a new [CachingOracle] that holds at most `max_bytes` bytes of the preimages of
+    /// `inner`.
+    pub fn new(inner: P, max_bytes: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            cache: LruCache::unbounded(),
+            preimages: HashMap::default(),
+            bytes: 0,
+            max_bytes: max_bytes.get(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the counters of the requests served so far.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns the number of bytes of the cached preimage windows.
+    pub fn cached_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the wrapped [PreimageOracle].
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Reassembles a preimage from its cached windows.
+    ///
+    /// ### Returns
+    /// - The preimage, or `None` if any of its windows is not cached.
+    fn lookup(&mut self, key: [u8; 32]) -> Option<Vec<u8>> {
+        let &(length, cached) = self.preimages.get(&key)?;
+        let windows = window_count(length);
+        if cached != windows {
+            return None;
+        }
+
+        let mut preimage = Vec::with_capacity(length);
+        for window in 0..windows as u32 {
+            preimage.extend_from_slice(self.cache.get(&(key, window))?);
+        }
+        Some(preimage)
+    }
+
+    /// Caches the windows of a preimage, evicting the least recently used windows beyond the byte
+    /// budget. Preimages larger than the budget are not cached.
+    fn insert(&mut self, key: [u8; 32], preimage: &[u8]) {
+        if preimage.len() > self.max_bytes {
+            return;
+        }
+
+        let windows = window_count(preimage.len());
+        for window in 0..windows {
+            let data = &preimage[(window * PREIMAGE_CACHE_WINDOW).min(preimage.len())
+                ..((window + 1) * PREIMAGE_CACHE_WINDOW).min(preimage.len())];
+            match self.cache.push((key, window as u32), data.to_vec()) {
+                // The window is still cached from an earlier request.
+                Some((_, old)) => self.bytes -= old.len(),
+                None => self.preimages.entry(key).or_insert((preimage.len(), 0)).1 += 1,
+            }
+            self.bytes += data.len();
+        }
+
+        while self.bytes > self.max_bytes {
+            match self.cache.pop_lru() {
+                Some(window) => self.evict(window),
+                None => break,
+            }
+        }
+    }
+
+    /// Accounts for a window that was removed from the cache.
+    fn evict(&mut self, ((key, _), data): (([u8; 32], u32), Vec<u8>)) {
+        self.stats.evictions += 1;
+        self.bytes -= data.len();
+        if let Some(entry) = self.preimages.get_mut(&key) {
+            entry.1 -= 1;
+            if entry.1 == 0 {
+                self.preimages.remove(&key);
+            }
+        }
+    }
+}
+
+impl<P: PreimageOracle> PreimageOracle for CachingOracle<P> {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        self.inner.hint(value)
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        if let Some(preimage) = self.lookup(key) {
+            self.stats.hits += 1;
+            return Ok(preimage);
+        }
+
+        self.stats.misses += 1;
+        let preimage = self.inner.get(key)?;
+        self.insert(key, &preimage);
+        Ok(preimage)
+    }
+
+    fn precompile_call(&self, key: [u8; 32]) -> Option<PrecompileKey> {
+        self.inner.precompile_call(key)
+    }
+
+    fn on_read(
+        &mut self,
+        key: [u8; 32],
+        length: u64,
+        offset: u32,
+        part: &[u8],
+    ) -> CannonResult<()> {
+        // The offset is into the length-prefixed preimage.
+        let window = offset.saturating_sub(8) / PREIMAGE_CACHE_WINDOW as u32;
+        self.cache.promote(&(key, window));
+        self.inner.on_read(key, length, offset, part)
+    }
+}
+
+/// Returns the number of windows of a preimage of the given length. Empty preimages are cached as
+/// a single empty window.
+fn window_count(length: usize) -> usize {
+    length.div_ceil(PREIMAGE_CACHE_WINDOW).max(1)
+}
+
+impl<P: PreimageOracle> Drop for CachingOracle<P> {
+    fn drop(&mut self) {
+        crate::traces::info!(target: "cannon::preimage::cache", "Preimage cache: {}", self.stats);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A [PreimageOracle] that serves the key's first byte repeated `length` times as the
+    /// preimage, counting the requests it served.
+    struct CountingOracle {
+        length: usize,
+        requests: usize,
+    }
+
+    impl CountingOracle {
+        fn new(length: usize) -> Self {
+            Self {
+                length,
+                requests: 0,
+            }
+        }
+    }
+
+    impl PreimageOracle for CountingOracle {
+        fn hint(&mut self, _: impl Hint) -> CannonResult<()> {
+            Ok(())
+        }
+
+        fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+            self.requests += 1;
+            Ok(vec![key[0]; self.length])
+        }
+    }
+
+    #[test]
+    fn caches_preimages() {
+        let mut oracle = CachingOracle::new(CountingOracle::new(4), NonZeroUsize::new(8).unwrap());
+        for key in [1, 2, 2, 1, 3, 1] {
+            assert_eq!(oracle.get([key; 32]).unwrap(), vec![key; 4]);
+        }
+
+        // `3` evicts `2`, the least recently used preimage, so `1` is still cached.
+        assert_eq!(
+            oracle.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 3,
+                evictions: 1
+            }
+        );
+        assert_eq!(oracle.stats().hit_rate(), 0.5);
+        assert_eq!(oracle.inner().requests, 3);
+        assert_eq!(oracle.cached_bytes(), 8);
+    }
+
+    #[test]
+    fn evicts_windows_by_bytes() {
+        // Each preimage spans three windows, and the budget holds four.
+        let length = 2 * PREIMAGE_CACHE_WINDOW + 1;
+        let mut oracle = CachingOracle::new(
+            CountingOracle::new(length),
+            NonZeroUsize::new(4 * PREIMAGE_CACHE_WINDOW).unwrap(),
+        );
+        oracle.get([1; 32]).unwrap();
+        oracle.on_read([1; 32], length as u64, 8, &[1; 4]).unwrap();
+        oracle.get([2; 32]).unwrap();
+
+        // The second window of `1` is evicted, as the guest read its first window last.
+        assert_eq!(oracle.stats().evictions, 1);
+        assert_eq!(oracle.cached_bytes(), 2 * length - PREIMAGE_CACHE_WINDOW);
+        assert_eq!(oracle.get([2; 32]).unwrap(), vec![2; length]);
+        assert_eq!(oracle.get([1; 32]).unwrap(), vec![1; length]);
+        assert_eq!(oracle.stats().hits, 1);
+        assert_eq!(oracle.inner().requests, 3);
+        assert!(oracle.cached_bytes() <= 4 * PREIMAGE_CACHE_WINDOW);
+
+        // Preimages larger than the budget are never cached.
+        let mut oracle = CachingOracle::new(
+            CountingOracle::new(length),
+            NonZeroUsize::new(length - 1).unwrap(),
+        );
+        oracle.get([1; 32]).unwrap();
+        oracle.get([1; 32]).unwrap();
+        assert_eq!(oracle.inner().requests, 2);
+        assert_eq!(oracle.cached_bytes(), 0);
+    }
+}
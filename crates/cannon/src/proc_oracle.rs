@@ -1,7 +1,8 @@
 //! This module contains the [PreimageServer] struct and its associated methods.
 
-use anyhow::Result;
-use cannon_mipsevm::PreimageOracle;
+use crate::ChildWithFds;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{CannonError, CannonResult, PreimageOracle};
 use command_fds::{CommandFdExt, FdMapping};
 use preimage_oracle::{Hint, HintWriter, Hinter, Oracle, OracleClient, RawKey, ReadWritePair};
 use std::{
@@ -32,7 +33,7 @@ impl ProcessPreimageOracle {
     ) -> Result<(Self, Option<Child>)> {
         let cmd_str = cmd.display().to_string();
         let child = (!cmd_str.is_empty()).then(|| {
-            crate::info!(
+            crate::traces::info!(
                 "Starting preimage server process: {} {:?}",
                 cmd.display(),
                 args
@@ -76,15 +77,48 @@ impl ProcessPreimageOracle {
             child.transpose()?,
         ))
     }
+
+    /// Creates the hint and preimage channels and starts the preimage server process on them.
+    ///
+    /// ### Takes
+    /// - `cmd`: The preimage server binary, followed by its arguments. No process is started if
+    ///   the binary path is empty.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [ProcessPreimageOracle] and the server process, along with
+    ///   the server's side of the channels, which must outlive the oracle.
+    pub fn spawn(cmd: &[String]) -> Result<(Self, Option<ChildWithFds>)> {
+        let (hint_cl_rw, hint_oracle_rw) = preimage_oracle::create_bidirectional_channel()?;
+        let (pre_cl_rw, pre_oracle_rw) = preimage_oracle::create_bidirectional_channel()?;
+
+        let server_io = [hint_oracle_rw, pre_oracle_rw];
+        let (oracle, server_proc) = Self::start(
+            PathBuf::from(
+                cmd.first()
+                    .ok_or(anyhow!("Missing preimage server binary path"))?,
+            ),
+            &cmd[1..],
+            (hint_cl_rw, pre_cl_rw),
+            &server_io,
+        )?;
+
+        let server_proc = server_proc.map(|p| ChildWithFds {
+            inner: p,
+            fds: server_io,
+        });
+        Ok((oracle, server_proc))
+    }
 }
 
 impl PreimageOracle for ProcessPreimageOracle {
-    fn hint(&mut self, value: impl Hint) -> Result<()> {
-        self.hint_writer_client.hint(value)
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        self.hint_writer_client
+            .hint(value)
+            .map_err(CannonError::OracleIo)
     }
 
-    fn get(&mut self, key: [u8; 32]) -> anyhow::Result<Vec<u8>> {
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
         let key = RawKey(key);
-        self.preimage_client.get(key)
+        self.preimage_client.get(key).map_err(CannonError::OracleIo)
     }
 }
@@ -0,0 +1,173 @@
+//! This module contains the [Progress] reports that are periodically emitted during a run, and
+//! the [ProgressSink] trait describing where they are sent.
+
+use anyhow::Result;
+use cannon_mipsevm::{disasm::Disassembly, Page, State};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// A [Progress] report is a snapshot of the state of a run, emitted at the steps matching the
+/// `info_at` pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// The current step.
+    pub step: u64,
+    /// The current program counter.
+    pub pc: u32,
+    /// The instruction at the current program counter.
+    pub instruction: u32,
+    /// The name of the function containing the current program counter, if the program's
+    /// metadata is known.
+    pub symbol: Option<String>,
+    /// The wall-clock time elapsed since the run started.
+    pub elapsed: Duration,
+    /// The average number of steps executed per second since the run started.
+    pub steps_per_sec: f64,
+    /// The number of allocated pages.
+    pub pages: usize,
+    /// The size of the allocated pages, in bytes.
+    pub memory_bytes: u64,
+    /// The step that the run is expected to end at, if known, e.g. from a `=<step>` stop pattern
+    /// or a step limit.
+    pub target_step: Option<u64>,
+}
+
+impl Progress {
+    /// Captures a [Progress] report of the given [State].
+    ///
+    /// ### Takes
+    /// - `state`: The current [State] of the run.
+    /// - `start_step`: The step that the run started at.
+    /// - `start`: The time that the run started at.
+    /// - `symbol`: The name of the function containing the current program counter, if known.
+    /// - `target_step`: The step that the run is expected to end at, if known.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [Progress] report.
+    pub(crate) fn capture(
+        state: &mut State,
+        start_step: u64,
+        start: Instant,
+        symbol: Option<String>,
+        target_step: Option<u64>,
+    ) -> Result<Self> {
+        let elapsed = start.elapsed();
+        let pages = state.memory.page_count();
+        Ok(Self {
+            step: state.step,
+            pc: state.pc,
+            instruction: state.memory.get_memory(state.pc)?,
+            symbol,
+            elapsed,
+            steps_per_sec: (state.step - start_step) as f64 / elapsed.as_secs_f64(),
+            pages,
+            memory_bytes: (pages * std::mem::size_of::<Page>()) as u64,
+            target_step,
+        })
+    }
+
+    /// Returns the number of steps remaining until the target step, if it is known.
+    pub fn remaining_steps(&self) -> Option<u64> {
+        self.target_step
+            .map(|target| target.saturating_sub(self.step))
+    }
+
+    /// Returns the estimated wall-clock time remaining until the target step is reached, based on
+    /// the average speed of the run so far.
+    ///
+    /// ### Returns
+    /// - `Some(eta)` if the target step is known and the speed of the run could be measured.
+    /// - `None` otherwise.
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.remaining_steps()?;
+        if !self.steps_per_sec.is_finite() || self.steps_per_sec <= 0.0 {
+            return None;
+        }
+        Duration::try_from_secs_f64(remaining as f64 / self.steps_per_sec).ok()
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[ELAPSED: {}.{:03}s] step: {}, pc: {:08x}, ",
+            self.elapsed.as_secs(),
+            self.elapsed.subsec_millis(),
+            self.step,
+            self.pc
+        )?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, "symbol: {}, ", symbol)?;
+        }
+        write!(
+            f,
+            "instruction: {:08x} ({}), ips: {:.0}, pages: {}, mem: {} KiB",
+            self.instruction,
+            Disassembly::new(self.instruction, self.pc),
+            self.steps_per_sec,
+            self.pages,
+            self.memory_bytes / 1024
+        )?;
+        if let (Some(remaining), Some(eta)) = (self.remaining_steps(), self.eta()) {
+            write!(f, ", remaining: {}, eta: {}s", remaining, eta.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+/// The [ProgressSink] trait describes a receiver of the [Progress] reports emitted during a run.
+/// Closures taking a `&Progress` are [ProgressSink]s.
+pub trait ProgressSink {
+    /// Receives a [Progress] report.
+    fn report(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressSink for F {
+    fn report(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// The [LogProgressSink] logs [Progress] reports at the info level, if the `tracing` feature is
+/// enabled. It is the default [ProgressSink] of the [crate::Kernel].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogProgressSink;
+
+impl ProgressSink for LogProgressSink {
+    fn report(&mut self, _progress: &Progress) {
+        crate::traces::info!(target: "cannon::kernel", step = _progress.step, "{}", _progress);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn eta() {
+        let mut progress = Progress {
+            step: 1_000,
+            pc: 0x1000,
+            instruction: 0,
+            symbol: None,
+            elapsed: Duration::from_secs(1),
+            steps_per_sec: 1_000.0,
+            pages: 2,
+            memory_bytes: 8192,
+            target_step: None,
+        };
+        assert_eq!(progress.eta(), None);
+        assert!(!progress.to_string().contains("eta"));
+
+        progress.target_step = Some(11_000);
+        assert_eq!(progress.remaining_steps(), Some(10_000));
+        assert_eq!(progress.eta(), Some(Duration::from_secs(10)));
+        assert!(progress.to_string().ends_with("remaining: 10000, eta: 10s"));
+
+        progress.steps_per_sec = 0.0;
+        assert_eq!(progress.eta(), None);
+    }
+}
@@ -0,0 +1,298 @@
+//! This module contains the [BundleProofWriter], which bundles the [Proof]s of a run into a single
+//! zstd-compressed tar archive rather than one file per proof, and the [ProofBundle], which reads
+//! them back.
+//!
+//! Every proof is stored as a `<step>.json` entry of the archive, and every entry is compressed as
+//! its own zstd frame. The frames concatenate into a regular `.tar.zst` file, which can be
+//! extracted with standard tools, while a proof can be read by decompressing only its own frame.
+//! The offset of each frame is listed in an index file next to the bundle, at the path of the
+//! bundle followed by [PROOF_BUNDLE_INDEX_SUFFIX]. The index is written when the bundle is
+//! flushed, or dropped, so that a failed run still leaves a readable bundle of the proofs written
+//! before it failed.
+
+use crate::{Proof, ProofWriter};
+use anyhow::{anyhow, ensure, Result};
+use cannon_mipsevm::ser::Codec;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// The version of the proof bundle index format.
+pub const PROOF_BUNDLE_VERSION: u32 = 1;
+
+/// The suffix appended to the path of a proof bundle to form the path of its index.
+pub const PROOF_BUNDLE_INDEX_SUFFIX: &str = ".index.json";
+
+/// The size of a tar block, which entries are padded to.
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// The [ProofBundleIndex] maps the steps of the proofs in a bundle to their frames.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBundleIndex {
+    /// The version of the index format.
+    pub version: u32,
+    /// The frames of the proofs, in the order they were written.
+    pub entries: Vec<ProofBundleEntry>,
+}
+
+/// A [ProofBundleEntry] locates the zstd frame holding the proof at a given step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBundleEntry {
+    /// The step of the proof.
+    pub step: u64,
+    /// The offset of the frame within the bundle, in bytes.
+    pub offset: u64,
+    /// The compressed length of the frame, in bytes.
+    pub len: u64,
+}
+
+/// Returns the path of the index of the proof bundle at the given path.
+pub fn proof_bundle_index_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut index = path.as_ref().as_os_str().to_owned();
+    index.push(PROOF_BUNDLE_INDEX_SUFFIX);
+    index.into()
+}
+
+/// The [BundleProofWriter] appends every [Proof] to a zstd-compressed tar archive, and writes
+/// the archive's index once flushed or dropped.
+pub struct BundleProofWriter {
+    /// The path of the bundle.
+    path: PathBuf,
+    /// The buffered bundle file.
+    writer: BufWriter<File>,
+    /// The offset of the next frame.
+    offset: u64,
+    /// The index of the frames written so far.
+    index: ProofBundleIndex,
+    /// Whether the end of the archive has been written.
+    finished: bool,
+}
+
+impl BundleProofWriter {
+    /// Creates a new [BundleProofWriter] that writes to the file at the given path, truncating it
+    /// if it already exists.
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        Ok(Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            offset: 0,
+            index: ProofBundleIndex {
+                version: PROOF_BUNDLE_VERSION,
+                entries: Vec::new(),
+            },
+            finished: false,
+        })
+    }
+
+    /// Compresses the given bytes into a frame and appends it to the bundle.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the offset and the length of the frame.
+    fn write_frame(&mut self, bytes: &[u8]) -> Result<(u64, u64)> {
+        let frame = Codec::Zstd.compress(bytes)?;
+        self.writer.write_all(&frame)?;
+        let offset = self.offset;
+        self.offset += frame.len() as u64;
+        Ok((offset, frame.len() as u64))
+    }
+}
+
+impl ProofWriter for BundleProofWriter {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        ensure!(!self.finished, "The proof bundle has already been flushed");
+
+        let data = serde_json::to_vec(proof)?;
+        let mut header = tar::Header::new_ustar();
+        header.set_path(format!("{}.json", proof.step))?;
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let mut entry = Vec::with_capacity(TAR_BLOCK_SIZE * 2 + data.len());
+        entry.extend_from_slice(header.as_bytes());
+        entry.extend_from_slice(&data);
+        entry.resize(entry.len().next_multiple_of(TAR_BLOCK_SIZE), 0);
+
+        let (offset, len) = self.write_frame(&entry)?;
+        self.index.entries.push(ProofBundleEntry {
+            step: proof.step,
+            offset,
+            len,
+        });
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        // A tar archive ends with two zeroed blocks.
+        self.write_frame(&[0u8; TAR_BLOCK_SIZE * 2])?;
+        self.writer.flush()?;
+        self.finished = true;
+
+        let index_path = proof_bundle_index_path(&self.path);
+        fs::write(&index_path, serde_json::to_vec(&self.index)?)?;
+        crate::traces::info!(target: "cannon::proof_bundle", "Bundled {} proofs into {}", self.index.entries.len(), self.path.display());
+        Ok(())
+    }
+}
+
+impl Drop for BundleProofWriter {
+    fn drop(&mut self) {
+        if let Err(_e) = self.flush() {
+            crate::traces::error!(target: "cannon::proof_bundle", "Failed to finish the proof bundle {}: {}", self.path.display(), _e);
+        }
+    }
+}
+
+/// The [ProofBundle] reads the [Proof]s of a bundle written by a [BundleProofWriter] in any
+/// order, decompressing only the frames of the proofs that are read.
+#[derive(Debug)]
+pub struct ProofBundle {
+    /// The bundle file.
+    file: File,
+    /// The offset and the length of the frame of each proof, by step.
+    frames: BTreeMap<u64, (u64, u64)>,
+}
+
+impl ProofBundle {
+    /// Opens the proof bundle at the given path, along with its index.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let index_path = proof_bundle_index_path(&path);
+        let index: ProofBundleIndex = serde_json::from_slice(
+            &fs::read(&index_path)
+                .map_err(|e| anyhow!("Failed to read {}: {}", index_path.display(), e))?,
+        )?;
+        ensure!(
+            index.version == PROOF_BUNDLE_VERSION,
+            "Unsupported proof bundle version {}",
+            index.version
+        );
+
+        Ok(Self {
+            file: File::open(path)?,
+            frames: index
+                .entries
+                .into_iter()
+                .map(|entry| (entry.step, (entry.offset, entry.len)))
+                .collect(),
+        })
+    }
+
+    /// Returns the steps of the proofs in the bundle, in ascending order.
+    pub fn steps(&self) -> impl Iterator<Item = u64> + '_ {
+        self.frames.keys().copied()
+    }
+
+    /// Returns the number of proofs in the bundle.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns `true` if the bundle holds no proofs.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Reads the [Proof] at the given step from the bundle.
+    ///
+    /// ### Takes
+    /// - `step`: The step of the proof.
+    ///
+    /// ### Returns
+    /// - `Ok(Some(proof))` if the bundle holds a proof at the step.
+    /// - `Ok(None)` if it does not.
+    /// - `Err(_)` if the proof could not be read.
+    pub fn read_proof(&mut self, step: u64) -> Result<Option<Proof>> {
+        let Some(&(offset, len)) = self.frames.get(&step) else {
+            return Ok(None);
+        };
+
+        let mut frame = vec![0u8; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut frame)?;
+        let entry = Codec::Zstd.decompress(&frame)?;
+
+        ensure!(
+            entry.len() >= TAR_BLOCK_SIZE,
+            "The frame of the proof at step {} is truncated",
+            step
+        );
+        let header = tar::Header::from_byte_slice(&entry[..TAR_BLOCK_SIZE]);
+        let size = header.entry_size()? as usize;
+        let data = entry
+            .get(TAR_BLOCK_SIZE..TAR_BLOCK_SIZE + size)
+            .ok_or_else(|| anyhow!("The proof at step {} is truncated", step))?;
+        Ok(Some(Proof::from_json(data).map_err(|e| anyhow!("{}", e))?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::StepWitness;
+
+    #[test]
+    fn bundle_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proofs.tar.zst");
+
+        let proofs = (1..=3)
+            .map(|step| {
+                Proof::new(
+                    step * 10,
+                    [step as u8; 32],
+                    [step as u8 + 1; 32],
+                    StepWitness {
+                        mem_proof: vec![step as u8; 28 * 32],
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut writer = BundleProofWriter::create(&path).unwrap();
+        for proof in &proofs {
+            writer.write_proof(proof).unwrap();
+        }
+        writer.flush().unwrap();
+        writer.flush().unwrap();
+
+        // Proofs are read back in any order.
+        let mut bundle = ProofBundle::open(&path).unwrap();
+        assert_eq!(bundle.steps().collect::<Vec<_>>(), [10, 20, 30]);
+        assert!(bundle.read_proof(30).unwrap() == Some(proofs[2].clone()));
+        assert!(bundle.read_proof(10).unwrap() == Some(proofs[0].clone()));
+        assert!(bundle.read_proof(15).unwrap().is_none());
+
+        // The bundle is a regular zstd-compressed tar archive.
+        let archive = Codec::Zstd.decompress(&fs::read(&path).unwrap()).unwrap();
+        let names = tar::Archive::new(archive.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["10.json", "20.json", "30.json"]);
+    }
+
+    #[test]
+    fn bundle_is_indexed_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proofs.tar.zst");
+
+        let proof = Proof::new(7, [1; 32], [2; 32], StepWitness::default());
+        let mut writer = BundleProofWriter::create(&path).unwrap();
+        writer.write_proof(&proof).unwrap();
+        // The run fails before the writer is flushed.
+        drop(writer);
+
+        let mut bundle = ProofBundle::open(&path).unwrap();
+        assert_eq!(bundle.steps().collect::<Vec<_>>(), [7]);
+        assert!(bundle.read_proof(7).unwrap() == Some(proof));
+    }
+}
@@ -0,0 +1,352 @@
+//! This module contains the [ProofWriter] trait and its implementations, which determine where
+//! the [Proof]s generated by the [crate::Kernel] are written to.
+
+use crate::Proof;
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// The default number of proofs that may be queued for a [PipelinedProofWriter]'s workers before
+/// the [crate::Kernel] blocks on emitting another proof.
+pub const DEFAULT_PROOF_QUEUE_CAPACITY: usize = 64;
+
+/// The [ProofWriter] trait describes a sink for the [Proof]s generated by the [crate::Kernel].
+pub trait ProofWriter {
+    /// Writes a [Proof] to the sink.
+    ///
+    /// ### Takes
+    /// - `proof`: The [Proof] to write.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the proof was written successfully.
+    fn write_proof(&mut self, proof: &Proof) -> Result<()>;
+
+    /// Flushes any buffered proofs to the sink. Called once the [crate::Kernel] has finished
+    /// running.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: ProofWriter + ?Sized> ProofWriter for Box<W> {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        (**self).write_proof(proof)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// The [DirectoryProofWriter] writes each [Proof] to its own JSON file. The file name is derived
+/// from a format string, where `%d` is replaced with the step of the proof.
+#[derive(Debug, Clone)]
+pub struct DirectoryProofWriter {
+    /// Format for proof data output file names.
+    format: String,
+}
+
+impl DirectoryProofWriter {
+    /// Creates a new [DirectoryProofWriter] from the given file name format.
+    pub fn new(format: impl Into<String>) -> Self {
+        Self {
+            format: format.into(),
+        }
+    }
+}
+
+impl ProofWriter for DirectoryProofWriter {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        let proof_path = self.format.replace("%d", &format!("{}", proof.step));
+        let mut writer = BufWriter::new(File::create(proof_path)?);
+        serde_json::to_writer(&mut writer, proof)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The [JsonlProofWriter] writes all [Proof]s to a single stream, one JSON object per line.
+pub struct JsonlProofWriter<W: Write> {
+    /// The buffered stream that proofs are written to.
+    writer: BufWriter<W>,
+}
+
+impl JsonlProofWriter<File> {
+    /// Creates a new [JsonlProofWriter] that writes to the file at the given path, truncating it
+    /// if it already exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> JsonlProofWriter<W> {
+    /// Creates a new [JsonlProofWriter] that writes to the given stream.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write> ProofWriter for JsonlProofWriter<W> {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, proof)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// The [MemoryProofWriter] collects all [Proof]s in memory. Clones of a [MemoryProofWriter] share
+/// the same collection, so a library user may keep a clone to read the proofs back after the
+/// [crate::Kernel] has consumed the writer.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryProofWriter {
+    /// The proofs collected so far.
+    proofs: Arc<Mutex<Vec<Proof>>>,
+}
+
+impl MemoryProofWriter {
+    /// Returns a copy of the [Proof]s collected so far.
+    pub fn proofs(&self) -> Vec<Proof> {
+        self.proofs
+            .lock()
+            .expect("Proof collection poisoned")
+            .clone()
+    }
+}
+
+impl ProofWriter for MemoryProofWriter {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        self.proofs
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Proof collection poisoned"))?
+            .push(proof.clone());
+        Ok(())
+    }
+}
+
+/// The [PipelinedProofWriter] hands [Proof]s off to background worker threads over a bounded
+/// queue, so that serializing and writing proofs does not stall the thread that generates them.
+///
+/// Errors raised by the workers surface from the next call to [ProofWriter::write_proof] or from
+/// [ProofWriter::flush], which waits for all queued proofs to be written.
+pub struct PipelinedProofWriter {
+    /// The sending half of the proof queue. `None` once the writer has been flushed.
+    sender: Option<SyncSender<Proof>>,
+    /// The worker threads.
+    workers: Vec<JoinHandle<Result<()>>>,
+}
+
+impl PipelinedProofWriter {
+    /// Creates a new [PipelinedProofWriter] with a single worker, which writes proofs to `inner`
+    /// in the order that they were emitted.
+    ///
+    /// ### Takes
+    /// - `inner`: The [ProofWriter] that the worker writes proofs to.
+    /// - `capacity`: The maximum number of queued proofs.
+    pub fn new(inner: impl ProofWriter + Send + 'static, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        Self {
+            sender: Some(sender),
+            workers: vec![Self::spawn_worker(inner, receiver)],
+        }
+    }
+
+    /// Creates a new [PipelinedProofWriter] with a pool of workers, each writing proofs to its own
+    /// clone of `inner`. Proofs may be written out of order, so this is only suitable for writers
+    /// that write each proof independently, such as the [DirectoryProofWriter].
+    ///
+    /// ### Takes
+    /// - `inner`: The [ProofWriter] that is cloned into each worker.
+    /// - `workers`: The number of workers.
+    /// - `capacity`: The maximum number of queued proofs.
+    pub fn pool<W>(inner: W, workers: usize, capacity: usize) -> Self
+    where
+        W: ProofWriter + Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        Self {
+            sender: Some(sender),
+            workers: (0..workers.max(1))
+                .map(|_| Self::spawn_worker(inner.clone(), Arc::clone(&receiver)))
+                .collect(),
+        }
+    }
+
+    /// Spawns a worker that writes the proofs received from the queue to `inner` until the queue
+    /// is closed.
+    fn spawn_worker(
+        mut inner: impl ProofWriter + Send + 'static,
+        receiver: Arc<Mutex<Receiver<Proof>>>,
+    ) -> JoinHandle<Result<()>> {
+        thread::spawn(move || {
+            loop {
+                let proof = match receiver
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("Proof queue poisoned"))?
+                    .recv()
+                {
+                    Ok(proof) => proof,
+                    Err(_) => break,
+                };
+                inner.write_proof(&proof)?;
+            }
+            inner.flush()
+        })
+    }
+
+    /// Closes the proof queue and waits for the workers to finish writing the queued proofs.
+    ///
+    /// ### Returns
+    /// - The first error raised by any worker, if any.
+    fn join(&mut self) -> Result<()> {
+        self.sender = None;
+        let mut result = Ok(());
+        for worker in self.workers.drain(..) {
+            let worker_result = worker
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Proof writer worker panicked")));
+            if result.is_ok() {
+                result = worker_result;
+            }
+        }
+        result
+    }
+}
+
+impl ProofWriter for PipelinedProofWriter {
+    fn write_proof(&mut self, proof: &Proof) -> Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Proof writer is closed"))?;
+        if sender.send(proof.clone()).is_err() {
+            // All workers have exited, which only happens if they failed.
+            self.join()?;
+            anyhow::bail!("Proof writer workers exited unexpectedly");
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.join()
+    }
+}
+
+impl Drop for PipelinedProofWriter {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn proof(step: u64) -> Proof {
+        Proof {
+            version: crate::PROOF_VERSION,
+            step,
+            pre: [1u8; 32],
+            post: [2u8; 32],
+            state_data: [3u8; cannon_mipsevm::STATE_WITNESS_SIZE],
+            proof_data: vec![4u8; 28 * 32],
+            step_input: vec![5u8; 8],
+            oracle_key: None,
+            oracle_value: None,
+            oracle_offset: None,
+            oracle_input: None,
+            mem_accesses: None,
+        }
+    }
+
+    #[test]
+    fn jsonl_writer() {
+        let mut out = Vec::new();
+        {
+            let mut writer = JsonlProofWriter::new(&mut out);
+            writer.write_proof(&proof(1)).unwrap();
+            writer.write_proof(&proof(2)).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let proofs = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| Proof::from_json(line.as_bytes()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(proofs == vec![proof(1), proof(2)]);
+    }
+
+    #[test]
+    fn memory_writer() {
+        let collector = MemoryProofWriter::default();
+        let mut writer: Box<dyn ProofWriter> = Box::new(collector.clone());
+        writer.write_proof(&proof(1)).unwrap();
+        writer.write_proof(&proof(2)).unwrap();
+        assert!(collector.proofs() == vec![proof(1), proof(2)]);
+    }
+
+    #[test]
+    fn pipelined_writer() {
+        let collector = MemoryProofWriter::default();
+        let mut writer = PipelinedProofWriter::new(collector.clone(), 2);
+        for step in 0..16 {
+            writer.write_proof(&proof(step)).unwrap();
+        }
+        writer.flush().unwrap();
+        assert!(collector.proofs() == (0..16).map(proof).collect::<Vec<_>>());
+        assert!(writer.write_proof(&proof(16)).is_err());
+    }
+
+    #[test]
+    fn pipelined_pool() {
+        let collector = MemoryProofWriter::default();
+        let mut writer = PipelinedProofWriter::pool(collector.clone(), 4, 2);
+        for step in 0..16 {
+            writer.write_proof(&proof(step)).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let mut steps = collector
+            .proofs()
+            .iter()
+            .map(|p| p.step)
+            .collect::<Vec<_>>();
+        steps.sort_unstable();
+        assert_eq!(steps, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pipelined_error() {
+        struct FailingWriter;
+
+        impl ProofWriter for FailingWriter {
+            fn write_proof(&mut self, _: &Proof) -> Result<()> {
+                anyhow::bail!("disk full")
+            }
+        }
+
+        let mut writer = PipelinedProofWriter::new(FailingWriter, 1);
+        let err = (0..4)
+            .map(|step| writer.write_proof(&proof(step)))
+            .find_map(Result::err)
+            .or_else(|| writer.flush().err())
+            .unwrap();
+        assert!(err.to_string().contains("disk full"));
+    }
+}
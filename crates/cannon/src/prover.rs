@@ -0,0 +1,185 @@
+//! This module contains the [Prover], which generates the [Proof]s of the [crate::Kernel] either on
+//! the interpreter thread, or from a [StateReplica] on a background thread.
+
+use crate::{Proof, ProofWriter};
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{
+    CannonError, CannonResult, InstrumentedState, PreimageOracle, ReplicaSeed, ReplicatedStep,
+    StateReplica,
+};
+use std::{
+    io::Write,
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+/// The [Prover] generates a [Proof] at each step that requires one, and writes it to a
+/// [ProofWriter].
+pub(crate) enum Prover {
+    /// Generates the merkle proofs of each [Proof] on the interpreter thread.
+    Inline(Box<dyn ProofWriter>),
+    /// Generates the merkle proofs of each [Proof] on a background thread.
+    Replica(ReplicaProver),
+}
+
+impl Prover {
+    /// Executes the next step of the [InstrumentedState] and generates its [Proof].
+    ///
+    /// ### Takes
+    /// - `ins_state`: The [InstrumentedState] to step.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the step was executed and its proof generated, or
+    ///   queued for generation, successfully. Faults of the step are returned as raised by the
+    ///   [InstrumentedState], so that they can be symbolized as those of any other step.
+    pub(crate) fn prove<O, E, P>(
+        &mut self,
+        ins_state: &mut InstrumentedState<O, E, P>,
+    ) -> CannonResult<()>
+    where
+        O: Write,
+        E: Write,
+        P: PreimageOracle,
+    {
+        match self {
+            Self::Inline(proof_writer) => {
+                let step = ins_state.state.step;
+                let prestate_hash = ins_state.state.state_hash()?;
+                let step_witness = ins_state
+                    .step(true)?
+                    .ok_or_else(|| CannonError::Other(anyhow!("No step witness")))?;
+                let poststate_hash = ins_state.state.state_hash()?;
+
+                let proof = Proof::new(step, prestate_hash, poststate_hash, step_witness);
+                proof_writer.write_proof(&proof).map_err(CannonError::from)
+            }
+            Self::Replica(prover) => prover
+                .prove(ins_state.step_replicated()?)
+                .map_err(CannonError::from),
+        }
+    }
+
+    /// Waits for all queued proofs to be generated and flushes them to the [ProofWriter].
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Inline(proof_writer) => proof_writer.flush(),
+            Self::Replica(prover) => prover.join(),
+        }
+    }
+}
+
+/// The [ReplicaProver] generates [Proof]s from a [StateReplica] on a background thread, so that
+/// merkleizing the memory for each proof does not stall the [crate::Kernel]'s interpreter loop.
+/// The interpreter only transfers the pages written since the last proof to the thread.
+///
+/// Errors raised by the thread surface from the next call to [ReplicaProver::prove] or from
+/// [ReplicaProver::join].
+pub(crate) struct ReplicaProver {
+    /// The sending half of the queue of steps to prove. `None` once the prover has been joined.
+    sender: Option<SyncSender<ReplicatedStep>>,
+    /// The thread that generates and writes the proofs.
+    worker: Option<JoinHandle<Result<()>>>,
+}
+
+impl ReplicaProver {
+    /// Spawns the thread of a new [ReplicaProver].
+    ///
+    /// ### Takes
+    /// - `seed`: The [ReplicaSeed] of the [InstrumentedState] whose steps are proven.
+    /// - `proof_writer`: The [ProofWriter] that the thread writes proofs to.
+    /// - `capacity`: The maximum number of queued steps.
+    pub(crate) fn spawn(
+        seed: ReplicaSeed,
+        mut proof_writer: Box<dyn ProofWriter + Send>,
+        capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<ReplicatedStep>(capacity);
+        let worker = thread::spawn(move || {
+            let mut replica = StateReplica::new(seed)?;
+            for replicated in receiver {
+                let step = replicated.step();
+                let (prestate_hash, step_witness, poststate_hash) = replica.prove(replicated)?;
+                let proof = Proof::new(step, prestate_hash, poststate_hash, step_witness);
+                proof_writer.write_proof(&proof)?;
+            }
+            proof_writer.flush()
+        });
+
+        Self {
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues a [ReplicatedStep] to be proven.
+    fn prove(&mut self, replicated: ReplicatedStep) -> Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| anyhow!("Prover is closed"))?;
+        if sender.send(replicated).is_err() {
+            // The thread only exits early if it failed.
+            self.join()?;
+            anyhow::bail!("Prover thread exited unexpectedly");
+        }
+        Ok(())
+    }
+
+    /// Closes the queue and waits for the thread to prove the queued steps.
+    ///
+    /// ### Returns
+    /// - The error raised by the thread, if any.
+    fn join(&mut self) -> Result<()> {
+        self.sender = None;
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .unwrap_or_else(|_| Err(anyhow!("Prover thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryProofWriter;
+    use cannon_mipsevm::test_utils::{counting_state, StaticOracle};
+    use std::io;
+
+    #[test]
+    fn replica_proofs_match_inline_proofs() {
+        let prove_all = |replicated: bool| {
+            let writer = MemoryProofWriter::default();
+            let mut ins_state = InstrumentedState::new(
+                counting_state(16),
+                StaticOracle::default(),
+                io::sink(),
+                io::sink(),
+            );
+            let mut prover = if replicated {
+                Prover::Replica(ReplicaProver::spawn(
+                    ins_state.replicate().unwrap(),
+                    Box::new(writer.clone()),
+                    4,
+                ))
+            } else {
+                Prover::Inline(Box::new(writer.clone()))
+            };
+
+            while !ins_state.state.exited {
+                if ins_state.state.step % 5 == 0 {
+                    prover.prove(&mut ins_state).unwrap();
+                } else {
+                    ins_state.step(false).unwrap();
+                }
+            }
+            prover.flush().unwrap();
+            writer.proofs()
+        };
+
+        let proofs = prove_all(true);
+        assert_eq!(proofs.len(), 13);
+        assert!(proofs == prove_all(false));
+    }
+}
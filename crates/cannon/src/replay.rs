@@ -0,0 +1,353 @@
+//! This module contains the types used to record the preimages served during a run into a replay
+//! log, and to re-run the VM using only that log as its [PreimageOracle].
+//!
+//! A replay log is a JSONL file with one [ReplayEntry] per distinct part of a preimage that the
+//! guest read. Only the parts that were read are recorded, and the rest of each preimage is
+//! replayed as zeros, which the guest never observes: every read that the guest performed is
+//! served again byte-for-byte without the original host.
+
+use crate::ProcessPreimageOracle;
+use anyhow::Result;
+use cannon_mipsevm::{CannonError, CannonResult, PreimageOracle};
+use preimage_oracle::{Hint, Hinter, LocalInputs, Oracle, PrecompileKey, RawKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// A [ReplayEntry] is a part of a preimage that the guest read during a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    /// The key of the preimage.
+    #[serde(with = "cannon_mipsevm::ser::fixed_32_hex")]
+    pub key: [u8; 32],
+    /// The length of the preimage, as served in the length prefix of a preimage read.
+    pub length: u64,
+    /// The offset of the part into the length-prefixed preimage.
+    pub offset: u32,
+    /// The part of the length-prefixed preimage that was read at the offset.
+    #[serde(with = "cannon_mipsevm::ser::vec_u8_hex")]
+    pub data: Vec<u8>,
+}
+
+/// The [ReplayRecorder] wraps a [PreimageOracle] and appends every distinct part of a preimage
+/// that the guest reads to a replay log.
+pub struct ReplayRecorder<P: PreimageOracle> {
+    /// The wrapped [PreimageOracle].
+    inner: P,
+    /// The replay log that parts are written to.
+    writer: BufWriter<File>,
+    /// The keys and offsets of the parts that have already been written to the replay log.
+    recorded: HashSet<([u8; 32], u32)>,
+}
+
+impl<P: PreimageOracle> ReplayRecorder<P> {
+    /// Creates a new [ReplayRecorder] that records the preimages served by `inner` into the file at
+    /// the given path, truncating it if it already exists.
+    pub fn create(inner: P, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            inner,
+            writer: BufWriter::new(File::create(path)?),
+            recorded: HashSet::default(),
+        })
+    }
+
+    /// Appends a [ReplayEntry] to the replay log. Entries are flushed immediately so that the log
+    /// is complete even if the run is aborted.
+    fn record(&mut self, entry: &ReplayEntry) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, entry)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<P: PreimageOracle> PreimageOracle for ReplayRecorder<P> {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        self.inner.hint(value)
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        self.inner.get(key)
+    }
+
+    fn precompile_call(&self, key: [u8; 32]) -> Option<PrecompileKey> {
+        self.inner.precompile_call(key)
+    }
+
+    fn on_read(
+        &mut self,
+        key: [u8; 32],
+        length: u64,
+        offset: u32,
+        part: &[u8],
+    ) -> CannonResult<()> {
+        self.inner.on_read(key, length, offset, part)?;
+        if self.recorded.insert((key, offset)) {
+            let entry = ReplayEntry {
+                key,
+                length,
+                offset,
+                data: part.to_vec(),
+            };
+            self.record(&entry).map_err(CannonError::OracleIo)?;
+        }
+        Ok(())
+    }
+}
+
+/// The [ReplayOracle] is a [PreimageOracle] that serves preimages exclusively from a replay log.
+/// Hints are ignored, as there is no host to act upon them.
+///
+/// As only the parts that were read are replayed, the preimages in the
+/// [cannon_mipsevm::StepWitness]es of a replayed run cannot be loaded into the `PreimageOracle`
+/// contract by their keccak256 hash.
+#[derive(Debug, Default)]
+pub struct ReplayOracle {
+    /// The preimages contained within the replay log, with the bytes that were not read zeroed.
+    preimages: HashMap<[u8; 32], Vec<u8>>,
+}
+
+impl ReplayOracle {
+    /// Loads a [ReplayOracle] from the replay log at the given path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut preimages = HashMap::default();
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let entry: ReplayEntry = serde_json::from_str(&line)?;
+            Self::apply(&mut preimages, &entry).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid replay entry for key {} at offset {}: {}",
+                    alloy_primitives::B256::from(entry.key),
+                    entry.offset,
+                    e
+                )
+            })?;
+        }
+        Ok(Self { preimages })
+    }
+
+    /// Inserts a preimage that is not in the replay log, such as a local preimage of a standalone
+    /// run.
+    ///
+    /// ### Takes
+    /// - `key`: The key of the preimage.
+    /// - `value`: The preimage, without its length prefix.
+    pub fn insert_preimage(&mut self, key: [u8; 32], value: Vec<u8>) {
+        self.preimages.insert(key, value);
+    }
+
+    /// Inserts the local preimages of the [LocalInputs] of a standalone run.
+    ///
+    /// ### Takes
+    /// - `inputs`: The [LocalInputs] of the run.
+    pub fn insert_local_inputs(&mut self, inputs: &LocalInputs) {
+        self.preimages.extend(inputs.preimages());
+    }
+
+    /// Writes the part of a [ReplayEntry] into its preimage, checking it against the length of
+    /// the preimage.
+    fn apply(preimages: &mut HashMap<[u8; 32], Vec<u8>>, entry: &ReplayEntry) -> Result<()> {
+        let prefixed_len = entry.length.saturating_add(8);
+        anyhow::ensure!(
+            entry.data.len() <= 32 && entry.offset as u64 + entry.data.len() as u64 <= prefixed_len,
+            "the part of {} bytes lies past the end of the preimage of length {}",
+            entry.data.len(),
+            entry.length
+        );
+
+        let preimage = match preimages.entry(entry.key) {
+            Entry::Occupied(preimage) => {
+                anyhow::ensure!(
+                    preimage.get().len() as u64 == entry.length,
+                    "the length {} does not match the length {} of another part",
+                    entry.length,
+                    preimage.get().len()
+                );
+                preimage.into_mut()
+            }
+            Entry::Vacant(preimage) => preimage.insert(vec![0; entry.length as usize]),
+        };
+
+        let length_prefix = entry.length.to_be_bytes();
+        for (i, &byte) in entry.data.iter().enumerate() {
+            match entry.offset as usize + i {
+                index @ 0..=7 => anyhow::ensure!(
+                    length_prefix[index] == byte,
+                    "the part does not match the length prefix"
+                ),
+                index => preimage[index - 8] = byte,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PreimageOracle for ReplayOracle {
+    fn hint(&mut self, _: impl Hint) -> CannonResult<()> {
+        Ok(())
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        self.preimages.get(&key).cloned().ok_or_else(|| {
+            CannonError::OracleIo(anyhow::anyhow!(
+                "Preimage for key {} is not in the replay log",
+                alloy_primitives::B256::from(key)
+            ))
+        })
+    }
+}
+
+/// The [HostOracle] is the [PreimageOracle] used by a [crate::Kernel] built by the
+/// [crate::KernelBuilder].
+pub enum HostOracle {
+    /// Preimages are served by a preimage server process.
+    Process(ProcessPreimageOracle),
+    /// Preimages are served by a preimage server process and recorded into a replay log.
+    Recording(ReplayRecorder<ProcessPreimageOracle>),
+    /// Preimages are served from a replay log.
+    Replay(ReplayOracle),
+    /// Preimages are served from an in-memory cache in front of another [HostOracle].
+    Cached(Box<crate::CachingOracle<HostOracle>>),
+    /// Precompile results are executed locally, in front of another [HostOracle].
+    Precompiles(Box<cannon_mipsevm::PrecompileOracle<HostOracle>>),
+    /// Preimages are served by a host in the same process over in-process channels.
+    Channel(preimage_oracle::ChannelOracleClient),
+    /// Preimages are served by a host over gRPC.
+    #[cfg(feature = "grpc")]
+    Grpc(crate::GrpcPreimageOracle),
+}
+
+impl PreimageOracle for HostOracle {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        match self {
+            HostOracle::Process(oracle) => oracle.hint(value),
+            HostOracle::Recording(oracle) => oracle.hint(value),
+            HostOracle::Replay(oracle) => oracle.hint(value),
+            HostOracle::Cached(oracle) => oracle.hint(value),
+            HostOracle::Precompiles(oracle) => oracle.hint(value),
+            HostOracle::Channel(oracle) => {
+                Hinter::hint(oracle, value).map_err(CannonError::OracleIo)
+            }
+            #[cfg(feature = "grpc")]
+            HostOracle::Grpc(oracle) => oracle.hint(value),
+        }
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        match self {
+            HostOracle::Process(oracle) => oracle.get(key),
+            HostOracle::Recording(oracle) => oracle.get(key),
+            HostOracle::Replay(oracle) => oracle.get(key),
+            HostOracle::Cached(oracle) => oracle.get(key),
+            HostOracle::Precompiles(oracle) => oracle.get(key),
+            HostOracle::Channel(oracle) => {
+                Oracle::get(oracle, RawKey(key)).map_err(CannonError::OracleIo)
+            }
+            #[cfg(feature = "grpc")]
+            HostOracle::Grpc(oracle) => oracle.get(key),
+        }
+    }
+
+    fn precompile_call(&self, key: [u8; 32]) -> Option<PrecompileKey> {
+        match self {
+            HostOracle::Cached(oracle) => oracle.precompile_call(key),
+            HostOracle::Precompiles(oracle) => oracle.precompile_call(key),
+            _ => None,
+        }
+    }
+
+    fn on_read(
+        &mut self,
+        key: [u8; 32],
+        length: u64,
+        offset: u32,
+        part: &[u8],
+    ) -> CannonResult<()> {
+        match self {
+            HostOracle::Recording(oracle) => oracle.on_read(key, length, offset, part),
+            HostOracle::Cached(oracle) => oracle.on_read(key, length, offset, part),
+            HostOracle::Precompiles(oracle) => oracle.on_read(key, length, offset, part),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::preimage_part;
+
+    /// A [PreimageOracle] that serves the key's first byte repeated as the preimage.
+    struct EchoOracle;
+
+    impl PreimageOracle for EchoOracle {
+        fn hint(&mut self, _: impl Hint) -> CannonResult<()> {
+            Ok(())
+        }
+
+        fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+            Ok(vec![key[0]; key[0] as usize])
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.jsonl");
+        // The guest reads the start of the first preimage twice, and two parts of the second.
+        let reads = [
+            ([7u8; 32], 0),
+            ([100u8; 32], 0),
+            ([7u8; 32], 0),
+            ([100u8; 32], 72),
+        ];
+
+        let mut recorder = ReplayRecorder::create(EchoOracle, &path).unwrap();
+        for (key, offset) in reads {
+            let preimage = recorder.get(key).unwrap();
+            let (part, length) = preimage_part(&preimage, offset).unwrap();
+            recorder
+                .on_read(key, preimage.len() as u64, offset, &part[..length as usize])
+                .unwrap();
+        }
+        drop(recorder);
+
+        // Duplicate reads are only recorded once.
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        let mut replay = ReplayOracle::open(&path).unwrap();
+        assert_eq!(replay.get([7u8; 32]).unwrap(), [7; 7]);
+
+        // Only the parts that were read are served; the rest of the preimage is zeroed.
+        let replayed = replay.get([100u8; 32]).unwrap();
+        assert_eq!(replayed.len(), 100);
+        assert_eq!(replayed[..24], [100; 24]);
+        assert_eq!(replayed[24..64], [0; 40]);
+        assert_eq!(replayed[64..96], [100; 32]);
+        assert_eq!(replayed[96..], [0; 4]);
+
+        assert!(matches!(
+            replay.get([2u8; 32]),
+            Err(CannonError::OracleIo(_))
+        ));
+
+        // Local preimages of a standalone run are served alongside the replay log.
+        replay.insert_local_inputs(&LocalInputs {
+            l2_chain_id: 10,
+            ..Default::default()
+        });
+        let mut key = [0u8; 32];
+        key[0] = 1;
+        key[31] = 5;
+        assert_eq!(replay.get(key).unwrap(), 10u64.to_be_bytes());
+    }
+}
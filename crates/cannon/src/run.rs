@@ -0,0 +1,487 @@
+//! This module contains [run], a high-level entry point that runs a program end-to-end in one call.
+
+use crate::{
+    kernel::{create_matcher, Matcher},
+    Progress, ProgressSink, Proof, StateHashIndex,
+};
+use alloy_primitives::B256;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{
+    load_elf, patch_go, patch_stack, InstrumentedState, PreimageOracle, State, VMStatus,
+};
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of steps between checks of the [CancellationToken] and the wall-clock timeout.
+pub const CANCELLATION_CHECK_INTERVAL: u64 = 1 << 10;
+
+/// The [Program] that [run] executes.
+pub enum Program {
+    /// A 32-bit big-endian MIPS ELF file, which is loaded into a fresh [State].
+    Elf {
+        /// The raw ELF file.
+        raw: Vec<u8>,
+        /// Whether or not to patch the Go runtime of the program.
+        patch_go: bool,
+        /// Whether or not to set up the initial stack of the program.
+        patch_stack: bool,
+    },
+    /// An existing [State], such as a prestate or a snapshot.
+    State(Box<State>),
+}
+
+impl Program {
+    /// Creates a [Program] from an ELF file compiled from Go, with all patches applied.
+    pub fn go_elf(raw: Vec<u8>) -> Self {
+        Program::Elf {
+            raw,
+            patch_go: true,
+            patch_stack: true,
+        }
+    }
+}
+
+/// The [CancellationToken] allows a run to be cancelled cooperatively from another thread. Clones
+/// of a [CancellationToken] share the same cancellation flag.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Requests cancellation of all runs observing this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The [RunConfig] describes a single end-to-end run of a [Program].
+pub struct RunConfig<P: PreimageOracle> {
+    /// The program to run.
+    program: Program,
+    /// The [PreimageOracle] that serves preimages to the program.
+    oracle: P,
+    /// The step pattern to generate proofs at.
+    proof_at: Option<String>,
+    /// The step pattern to stop running at.
+    stop_at: Option<String>,
+    /// The token that cancels the run.
+    cancellation: Option<CancellationToken>,
+    /// The maximum wall-clock time of the run.
+    timeout: Option<Duration>,
+    /// The maximum number of steps of the run.
+    max_steps: Option<u64>,
+    /// The step pattern to report progress at, and the sink that reports are sent to.
+    progress: Option<(String, Box<dyn ProgressSink>)>,
+    /// The sink for the program's stdout.
+    stdout: Box<dyn Write>,
+    /// The sink for the program's stderr.
+    stderr: Box<dyn Write>,
+}
+
+impl<P: PreimageOracle> RunConfig<P> {
+    /// Creates a new [RunConfig] that runs the given [Program] until it exits, without generating
+    /// any proofs. The program's output is discarded.
+    pub fn new(program: Program, oracle: P) -> Self {
+        Self {
+            program,
+            oracle,
+            proof_at: None,
+            stop_at: None,
+            cancellation: None,
+            timeout: None,
+            max_steps: None,
+            progress: None,
+            stdout: Box::new(io::sink()),
+            stderr: Box::new(io::sink()),
+        }
+    }
+
+    /// Sets the step pattern (`never`, `always`, `=<step>` or `%<steps>`) to generate proofs at.
+    pub fn with_proof_at(mut self, proof_at: impl Into<String>) -> Self {
+        self.proof_at = Some(proof_at.into());
+        self
+    }
+
+    /// Sets the step pattern (`never`, `always`, `=<step>` or `%<steps>`) to stop running at, or
+    /// a `hash:<claim>` pattern to stop at the first state whose hash equals the claim.
+    pub fn with_stop_at(mut self, stop_at: impl Into<String>) -> Self {
+        self.stop_at = Some(stop_at.into());
+        self
+    }
+
+    /// Sets the [CancellationToken] that cancels the run. The token is checked every
+    /// [CANCELLATION_CHECK_INTERVAL] steps.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the maximum wall-clock time of the run. The clock is checked every
+    /// [CANCELLATION_CHECK_INTERVAL] steps.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of steps to execute in the run, counted from the initial step of
+    /// the [Program].
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets the step pattern (`never`, `always`, `=<step>` or `%<steps>`) to send [Progress]
+    /// reports to the given [ProgressSink] at. The remaining steps are estimated from the stop
+    /// pattern if it is of the form `=<step>`, or from the step limit.
+    pub fn with_progress(
+        mut self,
+        info_at: impl Into<String>,
+        sink: impl ProgressSink + 'static,
+    ) -> Self {
+        self.progress = Some((info_at.into(), Box::new(sink)));
+        self
+    }
+
+    /// Sets the sinks for the program's stdout and stderr.
+    pub fn with_output(
+        mut self,
+        stdout: impl Write + 'static,
+        stderr: impl Write + 'static,
+    ) -> Self {
+        self.stdout = Box::new(stdout);
+        self.stderr = Box::new(stderr);
+        self
+    }
+}
+
+/// The [Outcome] describes why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program exited with the given [VMStatus].
+    Exited(VMStatus),
+    /// The program exited with the given [VMStatus], other than [VMStatus::Valid], after entering
+    /// a panic handler, as detected by a [crate::PanicDetector].
+    Panicked(VMStatus),
+    /// The run reached the `stop_at` step pattern.
+    Stopped,
+    /// The run was cancelled through its [CancellationToken].
+    Cancelled,
+    /// The run exceeded its wall-clock timeout or step limit.
+    TimedOut,
+}
+
+/// The [RunOutcome] is the result of [run].
+pub struct RunOutcome {
+    /// Why the run ended.
+    pub outcome: Outcome,
+    /// The final [State] of the program.
+    pub state: State,
+    /// The state hash of the final [State].
+    pub state_hash: [u8; 32],
+    /// The proofs generated during the run, in step order.
+    pub proofs: Vec<Proof>,
+    /// The rolling index of the most recent state hashes, if the run stopped at a `hash:<claim>`
+    /// pattern. The final state is recorded as well if the program exited.
+    pub state_index: Option<StateHashIndex>,
+}
+
+/// Runs a [Program] end-to-end, loading it, wiring up the [PreimageOracle], and stepping it until
+/// it exits, a stopping condition is met, or the run is cancelled or times out. In all cases the
+/// last [State] is returned in the [RunOutcome].
+///
+/// ### Takes
+/// - `config`: The [RunConfig] describing the run.
+///
+/// ### Returns
+/// - A [Result] containing the [RunOutcome] of the run.
+pub fn run<P: PreimageOracle>(config: RunConfig<P>) -> Result<RunOutcome> {
+    let state = match config.program {
+        Program::Elf {
+            raw,
+            patch_go: go,
+            patch_stack: stack,
+        } => {
+            let mut state = load_elf(&raw)?;
+            if go {
+                patch_go(&raw, &mut state)?;
+            }
+            if stack {
+                patch_stack(&mut state)?;
+            }
+            state
+        }
+        Program::State(state) => *state,
+    };
+
+    let proof_at = create_matcher(config.proof_at.as_ref())?;
+    let mut claim_index = StateHashIndex::from_pattern(config.stop_at.as_ref())?;
+    let stop_at = match claim_index {
+        Some(_) => Matcher::Never,
+        None => create_matcher(config.stop_at.as_ref())?,
+    };
+    let (info_at, mut progress_sink) = match config.progress {
+        Some((info_at, sink)) => (create_matcher(Some(&info_at))?, Some(sink)),
+        None => (Matcher::Never, None),
+    };
+
+    let start = Instant::now();
+    let start_step = state.step;
+    let target_step = [
+        match stop_at {
+            Matcher::Equal(step) => Some(step),
+            _ => None,
+        },
+        config.max_steps.map(|max| start_step.saturating_add(max)),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    let mut ins_state = InstrumentedState::new(state, config.oracle, config.stdout, config.stderr);
+    let mut proofs = Vec::default();
+    let outcome = loop {
+        if ins_state.state.exited {
+            break Outcome::Exited(ins_state.state.status());
+        }
+
+        let step = ins_state.state.step;
+        if stop_at.matches(step) {
+            break Outcome::Stopped;
+        }
+        if let Some(ref mut index) = claim_index {
+            let state_hash = B256::from(ins_state.state.state_hash()?);
+            if index.record(step, state_hash) {
+                break Outcome::Stopped;
+            }
+        }
+
+        let executed = step - start_step;
+        if config.max_steps.is_some_and(|max| executed >= max) {
+            break Outcome::TimedOut;
+        }
+        if executed % CANCELLATION_CHECK_INTERVAL == 0 {
+            if config
+                .cancellation
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+            {
+                break Outcome::Cancelled;
+            }
+            if config
+                .timeout
+                .is_some_and(|timeout| start.elapsed() >= timeout)
+            {
+                break Outcome::TimedOut;
+            }
+        }
+
+        if info_at.matches(step) {
+            if let Some(sink) = progress_sink.as_mut() {
+                let progress =
+                    Progress::capture(&mut ins_state.state, start_step, start, None, target_step)?;
+                sink.report(&progress);
+            }
+        }
+
+        if proof_at.matches(step) {
+            let pre = ins_state.state.state_hash()?;
+            let step_witness = ins_state.step(true)?.ok_or(anyhow!("No step witness"))?;
+            let post = ins_state.state.state_hash()?;
+            proofs.push(Proof::new(step, pre, post, step_witness));
+        } else {
+            ins_state.step(false)?;
+        }
+    };
+
+    let mut state = ins_state.state;
+    let state_hash = state.state_hash()?;
+    if let (Some(index), Outcome::Exited(_)) = (claim_index.as_mut(), outcome) {
+        // The final state of the program is the last state that can match the claim.
+        index.record(state.step, B256::from(state_hash));
+    }
+    Ok(RunOutcome {
+        outcome,
+        state,
+        state_hash,
+        proofs,
+        state_index: claim_index,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::HostOracle;
+    use cannon_mipsevm::test_utils::{ClaimTestOracle, StaticOracle};
+    use std::{cell::RefCell, rc::Rc};
+
+    /// A [Write] sink that can be read back after it has been moved into the [RunConfig].
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn run_hello() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let stdout = SharedBuf::default();
+        let config = RunConfig::new(
+            Program::go_elf(elf),
+            StaticOracle::new(b"hello world".to_vec()),
+        )
+        .with_proof_at("=10")
+        .with_output(stdout.clone(), io::sink());
+
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::Exited(VMStatus::Valid));
+        assert!(outcome.state.exited);
+        assert_eq!(outcome.state.exit_code, 0);
+        assert_eq!(outcome.proofs.len(), 1);
+        assert_eq!(outcome.proofs[0].step, 10);
+        assert_eq!(stdout.0.borrow().as_slice(), b"hello world!\n");
+    }
+
+    #[test]
+    fn run_stops_at() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&reports);
+        let config = RunConfig::new(Program::go_elf(elf), StaticOracle::default())
+            .with_stop_at("=1000")
+            .with_progress("%250", move |progress: &Progress| {
+                log.borrow_mut()
+                    .push((progress.step, progress.remaining_steps()))
+            });
+
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::Stopped);
+        assert!(!outcome.state.exited);
+        assert_eq!(outcome.state.step, 1000);
+        assert_eq!(
+            *reports.borrow(),
+            vec![
+                (0, Some(1000)),
+                (250, Some(750)),
+                (500, Some(500)),
+                (750, Some(250))
+            ]
+        );
+    }
+
+    #[test]
+    fn run_stops_at_claimed_hash() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let claimed = run(
+            RunConfig::new(Program::go_elf(elf.clone()), StaticOracle::default())
+                .with_stop_at("=500"),
+        )
+        .unwrap();
+
+        let outcome = run(
+            RunConfig::new(Program::go_elf(elf), StaticOracle::default())
+                .with_stop_at(format!("hash:{}", B256::from(claimed.state_hash))),
+        )
+        .unwrap();
+        assert_eq!(outcome.outcome, Outcome::Stopped);
+        assert_eq!(outcome.state.step, 500);
+        assert_eq!(outcome.state_hash, claimed.state_hash);
+        let index = outcome.state_index.unwrap();
+        assert_eq!(index.find(B256::from(claimed.state_hash)), Some(500));
+        assert_eq!(index.recent().last().map(|(step, _)| *step), Some(500));
+    }
+
+    #[test]
+    fn run_checks_exited_state_against_claim() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let exited = run(RunConfig::new(
+            Program::go_elf(elf.clone()),
+            StaticOracle::default(),
+        ))
+        .unwrap();
+        assert!(matches!(exited.outcome, Outcome::Exited(_)));
+
+        let outcome = run(
+            RunConfig::new(Program::go_elf(elf), StaticOracle::default())
+                .with_stop_at(format!("hash:{}", B256::from(exited.state_hash))),
+        )
+        .unwrap();
+        assert_eq!(outcome.outcome, exited.outcome);
+        let index = outcome.state_index.unwrap();
+        assert_eq!(
+            index.find(B256::from(exited.state_hash)),
+            Some(exited.state.step)
+        );
+    }
+
+    #[test]
+    fn run_with_in_process_host() {
+        let elf = include_bytes!("../../../example/bin/claim.elf").to_vec();
+        let (client, mut server) = preimage_oracle::create_oracle_channel();
+        let host = std::thread::spawn(move || {
+            let oracle = RefCell::new(ClaimTestOracle::default());
+            server.serve(
+                |key: [u8; 32]| oracle.borrow_mut().get(key).map_err(|e| anyhow!("{}", e)),
+                |hint: &[u8]| oracle.borrow_mut().hint(hint).map_err(|e| anyhow!("{}", e)),
+            )
+        });
+
+        let stdout = SharedBuf::default();
+        let config = RunConfig::new(Program::go_elf(elf), HostOracle::Channel(client))
+            .with_output(stdout.clone(), io::sink());
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::Exited(VMStatus::Valid));
+        assert!(String::from_utf8(stdout.0.borrow().clone())
+            .unwrap()
+            .ends_with("is good!\n"));
+
+        // The host stops serving once the run drops its client.
+        host.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn run_cancelled() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let token = CancellationToken::default();
+        token.cancel();
+        let config = RunConfig::new(Program::go_elf(elf), StaticOracle::default())
+            .with_cancellation(token.clone());
+
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::Cancelled);
+        assert_eq!(outcome.state.step, 0);
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn run_times_out() {
+        let elf = include_bytes!("../../../example/bin/hello.elf").to_vec();
+        let config = RunConfig::new(Program::go_elf(elf.clone()), StaticOracle::default())
+            .with_max_steps(1500);
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::TimedOut);
+        assert_eq!(outcome.state.step, 1500);
+
+        let config = RunConfig::new(Program::go_elf(elf), StaticOracle::default())
+            .with_timeout(Duration::ZERO);
+        let outcome = run(config).unwrap();
+        assert_eq!(outcome.outcome, Outcome::TimedOut);
+        assert_eq!(outcome.state.step, 0);
+    }
+}
@@ -0,0 +1,247 @@
+//! This module contains the [ShadowVerifier], which cross-verifies state hashes against a
+//! companion Go Cannon process over a simple JSON-RPC channel.
+//!
+//! The channel is a TCP stream carrying newline-delimited JSON-RPC 2.0 messages. For every
+//! verified step, the verifier sends a `cannon_stateHashAt` request with the step number as its
+//! only parameter, and expects the companion process to run its own VM up to that step and respond
+//! with the state hash it computed:
+//!
+//! ```text
+//! --> {"jsonrpc":"2.0","id":1,"method":"cannon_stateHashAt","params":[1000]}
+//! <-- {"jsonrpc":"2.0","id":1,"result":{"step":1000,"stateHash":"0x03..."}}
+//! ```
+
+use alloy_primitives::B256;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::State;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, BufWriter, Write},
+    net::TcpStream,
+};
+
+/// The [ShadowVerifier] sends state hashes computed by the native VM to a companion Go Cannon
+/// process and compares them against the hashes that it computed for the same step.
+pub struct ShadowVerifier {
+    /// The buffered reading half of the RPC connection.
+    reader: BufReader<TcpStream>,
+    /// The buffered writing half of the RPC connection.
+    writer: BufWriter<TcpStream>,
+    /// The ID of the next request to send.
+    next_id: u64,
+}
+
+#[derive(Serialize)]
+struct RpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: [u64; 1],
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    id: u64,
+    result: Option<StateHashResult>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StateHashResult {
+    step: u64,
+    #[serde(with = "cannon_mipsevm::ser::fixed_32_hex")]
+    state_hash: [u8; 32],
+}
+
+impl ShadowVerifier {
+    /// Connects to the companion Go Cannon process listening at the given address.
+    ///
+    /// ### Takes
+    /// - `addr`: The `host:port` address of the companion process.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the connected [ShadowVerifier].
+    pub fn connect(addr: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr)
+            .map_err(|e| anyhow!("Failed to connect to shadow Cannon at {}: {}", addr, e))?;
+        stream.set_nodelay(true)?;
+
+        crate::traces::info!(target: "cannon::shadow", "Connected to shadow Cannon at {}", addr);
+
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            next_id: 1,
+        })
+    }
+
+    /// Fetches the state hash that the companion process computed at the given step.
+    ///
+    /// ### Takes
+    /// - `step`: The step to fetch the state hash at.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the remote state hash.
+    pub fn remote_state_hash(&mut self, step: u64) -> Result<[u8; 32]> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = RpcRequest {
+            jsonrpc: "2.0",
+            id,
+            method: "cannon_stateHashAt",
+            params: [step],
+        };
+        serde_json::to_writer(&mut self.writer, &request)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("Shadow Cannon closed the connection");
+        }
+
+        let response: RpcResponse = serde_json::from_str(&line)?;
+        if response.id != id {
+            anyhow::bail!(
+                "Shadow Cannon responded to request {} while awaiting request {}",
+                response.id,
+                id
+            );
+        }
+        if let Some(err) = response.error {
+            anyhow::bail!("Shadow Cannon returned error {}: {}", err.code, err.message);
+        }
+
+        let result = response
+            .result
+            .ok_or(anyhow!("Shadow Cannon returned an empty result"))?;
+        if result.step != step {
+            anyhow::bail!(
+                "Shadow Cannon returned the state hash at step {}, expected step {}",
+                result.step,
+                step
+            );
+        }
+        Ok(result.state_hash)
+    }
+
+    /// Verifies that the state hash of the given [State] matches the state hash computed by the
+    /// companion process at the same step.
+    ///
+    /// ### Takes
+    /// - `state`: The [State] of the native VM.
+    /// - `local_hash`: The state hash of `state`.
+    ///
+    /// ### Returns
+    /// - `Ok(())` if the hashes match.
+    /// - `Err(_)` containing a divergence dump if the hashes do not match, or if the companion
+    ///   process could not be reached.
+    pub fn verify(&mut self, state: &State, local_hash: [u8; 32]) -> Result<()> {
+        let remote_hash = self.remote_state_hash(state.step)?;
+        if remote_hash == local_hash {
+            crate::traces::debug!(target: "cannon::shadow", "State hash at step {} matches shadow Cannon", state.step);
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "State hash divergence from shadow Cannon\n{}",
+            divergence_dump(state, local_hash, remote_hash)
+        )
+    }
+}
+
+/// Renders a human-readable dump of the native VM's [State] at the point of divergence.
+fn divergence_dump(state: &State, local_hash: [u8; 32], remote_hash: [u8; 32]) -> String {
+    let mut dump = String::new();
+    let _ = writeln!(dump, "step:        {}", state.step);
+    let _ = writeln!(dump, "local hash:  {}", B256::from(local_hash));
+    let _ = writeln!(dump, "remote hash: {}", B256::from(remote_hash));
+    let _ = writeln!(
+        dump,
+        "pc: 0x{:08x} next_pc: 0x{:08x} lo: 0x{:08x} hi: 0x{:08x} heap: 0x{:08x}",
+        state.pc, state.next_pc, state.lo, state.hi, state.heap
+    );
+    let _ = writeln!(
+        dump,
+        "exited: {} exit_code: {} preimage_key: {} preimage_offset: {}",
+        state.exited,
+        state.exit_code,
+        B256::from(state.preimage_key),
+        state.preimage_offset
+    );
+    for (i, chunk) in state.registers.chunks(8).enumerate() {
+        let _ = write!(dump, "r{:02}-r{:02}:", i * 8, i * 8 + 7);
+        for r in chunk {
+            let _ = write!(dump, " {:08x}", r);
+        }
+        let _ = writeln!(dump);
+    }
+    let _ = write!(dump, "pages: {}", state.memory.page_count());
+    dump
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::counting_state;
+    use serde_json::{json, Value};
+    use std::{net::TcpListener, thread};
+
+    /// Serves `cannon_stateHashAt` requests on a loopback listener, answering every request with
+    /// the hash of `hash_at(step)`, until the verifier disconnects.
+    fn shadow_server(hash_at: impl Fn(u64) -> [u8; 32] + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                assert_eq!(request["method"], "cannon_stateHashAt");
+                let step = request["params"][0].as_u64().unwrap();
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": { "step": step, "stateHash": B256::from(hash_at(step)).to_string() },
+                });
+                writeln!(writer, "{}", response).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn verify_matching_and_diverging_hashes() {
+        let mut state = counting_state(10);
+        let hash = state.state_hash().unwrap();
+        let addr = shadow_server(move |step| if step == 0 { hash } else { [0xAB; 32] });
+
+        let mut verifier = ShadowVerifier::connect(&addr).unwrap();
+        verifier.verify(&state, hash).unwrap();
+
+        state.step = 7;
+        let err = verifier.verify(&state, hash).unwrap_err().to_string();
+        assert!(err.contains("State hash divergence"), "{}", err);
+        assert!(err.contains("step:        7"), "{}", err);
+        assert!(
+            err.contains(&format!("local hash:  {}", B256::from(hash))),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains(&format!("remote hash: {}", B256::from([0xAB; 32]))),
+            "{}",
+            err
+        );
+        assert!(err.contains("pc: 0x00001000"), "{}", err);
+    }
+}
@@ -0,0 +1,132 @@
+//! This module contains the [StateHashIndex], which backs the `hash:<claim>` stop pattern that
+//! halts a run once it reaches a claimed state hash.
+
+use alloy_primitives::B256;
+use anyhow::Result;
+use std::collections::VecDeque;
+
+/// The prefix of a stop pattern that halts at a claimed state hash.
+pub const HASH_PATTERN_PREFIX: &str = "hash:";
+
+/// The default number of recent state hashes kept by a [StateHashIndex].
+pub const DEFAULT_STATE_INDEX_CAPACITY: usize = 1024;
+
+/// The [StateHashIndex] holds a claimed state hash and a rolling index of the most recent state
+/// hashes of a run, so that a challenger can locate the step of a claimed state inside a trace
+/// and see the states leading up to it.
+#[derive(Debug, Clone)]
+pub struct StateHashIndex {
+    /// The claimed state hash to halt at.
+    claim: B256,
+    /// The most recent steps and their state hashes, oldest first.
+    recent: VecDeque<(u64, B256)>,
+    /// The maximum number of recent state hashes to keep.
+    capacity: usize,
+}
+
+impl StateHashIndex {
+    /// Creates a new [StateHashIndex] for the given claim, keeping the
+    /// [DEFAULT_STATE_INDEX_CAPACITY] most recent state hashes.
+    pub fn new(claim: B256) -> Self {
+        Self {
+            claim,
+            recent: VecDeque::with_capacity(DEFAULT_STATE_INDEX_CAPACITY),
+            capacity: DEFAULT_STATE_INDEX_CAPACITY,
+        }
+    }
+
+    /// Sets the maximum number of recent state hashes to keep.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        while self.recent.len() > self.capacity {
+            self.recent.pop_front();
+        }
+        self
+    }
+
+    /// Parses a stop pattern of the form `hash:<claim>`, with the claim as a hex string.
+    ///
+    /// ### Takes
+    /// - `pattern`: The stop pattern.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [StateHashIndex] for the claim, or `None` if the pattern is
+    ///   not a hash pattern.
+    pub fn from_pattern(pattern: Option<&String>) -> Result<Option<Self>> {
+        match pattern.and_then(|pattern| pattern.strip_prefix(HASH_PATTERN_PREFIX)) {
+            Some(claim) => {
+                let claim = claim
+                    .parse::<B256>()
+                    .map_err(|e| anyhow::anyhow!("Invalid claimed state hash {}: {}", claim, e))?;
+                Ok(Some(Self::new(claim)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the claimed state hash.
+    pub fn claim(&self) -> B256 {
+        self.claim
+    }
+
+    /// Records the state hash of a step, evicting the oldest recorded hash if the index is full.
+    ///
+    /// ### Takes
+    /// - `step`: The step of the state.
+    /// - `state_hash`: The hash of the state's witness.
+    ///
+    /// ### Returns
+    /// - `true` if the state hash equals the claim.
+    pub fn record(&mut self, step: u64, state_hash: B256) -> bool {
+        if self.recent.len() == self.capacity {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((step, state_hash));
+        state_hash == self.claim
+    }
+
+    /// Returns the step of the most recent recorded state with the given hash, if it is still in
+    /// the index.
+    pub fn find(&self, state_hash: B256) -> Option<u64> {
+        self.recent
+            .iter()
+            .rev()
+            .find_map(|(step, hash)| (*hash == state_hash).then_some(*step))
+    }
+
+    /// Returns the recorded steps and their state hashes, oldest first.
+    pub fn recent(&self) -> impl Iterator<Item = &(u64, B256)> {
+        self.recent.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rolling_state_hash_index() {
+        let claim = B256::repeat_byte(0xCC);
+        let pattern = format!("hash:{}", claim);
+        let mut index = StateHashIndex::from_pattern(Some(&pattern))
+            .unwrap()
+            .unwrap()
+            .with_capacity(2);
+        assert_eq!(index.claim(), claim);
+        assert!(StateHashIndex::from_pattern(Some(&"=10".to_string()))
+            .unwrap()
+            .is_none());
+        assert!(StateHashIndex::from_pattern(Some(&"hash:0x12".to_string())).is_err());
+
+        assert!(!index.record(0, B256::repeat_byte(0xAA)));
+        assert!(!index.record(1, B256::repeat_byte(0xBB)));
+        assert!(index.record(2, claim));
+        // The oldest hash was evicted.
+        assert_eq!(index.find(B256::repeat_byte(0xAA)), None);
+        assert_eq!(index.find(claim), Some(2));
+        assert_eq!(
+            index.recent().map(|(step, _)| *step).collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+}
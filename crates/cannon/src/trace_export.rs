@@ -0,0 +1,370 @@
+//! This module contains the [StepRecord]s that describe the effects of each executed instruction,
+//! and the [TraceExporter] trait and its implementations, which write them out so that zk proving
+//! pipelines can ingest Cannon traces without instrumenting the interpreter themselves.
+
+use anyhow::Result;
+use cannon_mipsevm::{InstrumentedState, PreimageOracle, State};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// A [StepRecord] describes the execution of a single instruction: the instruction word, the
+/// registers before and after it, and the memory word it accessed, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepRecord {
+    /// The step of the instruction.
+    pub step: u64,
+    /// The program counter of the instruction.
+    pub pc: u32,
+    /// The instruction word.
+    pub instruction: u32,
+    /// The general purpose registers before the instruction.
+    pub pre_registers: [u32; 32],
+    /// The general purpose registers after the instruction.
+    pub post_registers: [u32; 32],
+    /// The `lo` register before the instruction.
+    pub pre_lo: u32,
+    /// The `hi` register before the instruction.
+    pub pre_hi: u32,
+    /// The `lo` register after the instruction.
+    pub post_lo: u32,
+    /// The `hi` register after the instruction.
+    pub post_hi: u32,
+    /// The address of the memory word read or written by the instruction, other than its own
+    /// instruction fetch.
+    pub mem_address: Option<u32>,
+    /// The value of the accessed memory word before the instruction.
+    pub mem_pre: Option<u32>,
+    /// The value of the accessed memory word after the instruction. Differs from `mem_pre` only
+    /// if the instruction wrote to it.
+    pub mem_post: Option<u32>,
+}
+
+impl StepRecord {
+    /// Begins a [StepRecord] of the instruction that is about to be executed from the given
+    /// [State].
+    ///
+    /// ### Takes
+    /// - `state`: The [State] before the instruction.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the partial [StepRecord], to be completed with
+    ///   [StepRecord::finish] once the instruction was executed.
+    pub fn begin(state: &mut State) -> Result<Self> {
+        Ok(Self {
+            step: state.step,
+            pc: state.pc,
+            instruction: state.memory.get_memory(state.pc)?,
+            pre_registers: state.registers,
+            post_registers: state.registers,
+            pre_lo: state.lo,
+            pre_hi: state.hi,
+            post_lo: state.lo,
+            post_hi: state.hi,
+            mem_address: None,
+            mem_pre: None,
+            mem_post: None,
+        })
+    }
+
+    /// Completes the [StepRecord] after the instruction was executed with
+    /// [InstrumentedState::step]. Memory access recording must be enabled with
+    /// [InstrumentedState::set_record_mem_access].
+    ///
+    /// ### Takes
+    /// - `ins_state`: The [InstrumentedState] that executed the instruction.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the record was completed successfully.
+    pub fn finish<O, E, P>(&mut self, ins_state: &mut InstrumentedState<O, E, P>) -> Result<()>
+    where
+        O: Write,
+        E: Write,
+        P: PreimageOracle,
+    {
+        let state = &mut ins_state.state;
+        self.post_registers = state.registers;
+        self.post_lo = state.lo;
+        self.post_hi = state.hi;
+        if let Some((address, value)) = ins_state.mem_access() {
+            self.mem_address = Some(address);
+            self.mem_pre = Some(value);
+            self.mem_post = Some(state.memory.get_memory(address)?);
+        }
+        Ok(())
+    }
+}
+
+/// The [TraceExporter] trait describes a sink for the [StepRecord]s of a run.
+pub trait TraceExporter {
+    /// Writes a [StepRecord] to the sink.
+    ///
+    /// ### Takes
+    /// - `record`: The [StepRecord] to write.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the record was written successfully.
+    fn write_record(&mut self, record: &StepRecord) -> Result<()>;
+
+    /// Flushes any buffered records and finalizes the sink. Called once the [crate::Kernel] has
+    /// finished running.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: TraceExporter + ?Sized> TraceExporter for Box<T> {
+    fn write_record(&mut self, record: &StepRecord) -> Result<()> {
+        (**self).write_record(record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        (**self).finish()
+    }
+}
+
+/// The [JsonlTraceExporter] writes each [StepRecord] as a JSON object on its own line. It needs
+/// no additional dependencies, but is far larger than the columnar formats.
+pub struct JsonlTraceExporter<W: Write> {
+    /// The buffered stream that records are written to.
+    writer: BufWriter<W>,
+}
+
+impl JsonlTraceExporter<File> {
+    /// Creates a new [JsonlTraceExporter] that writes to the file at the given path, truncating it
+    /// if it already exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> JsonlTraceExporter<W> {
+    /// Creates a new [JsonlTraceExporter] that writes to the given stream.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write> TraceExporter for JsonlTraceExporter<W> {
+    fn write_record(&mut self, record: &StepRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub use self::parquet_export::ParquetTraceExporter;
+
+#[cfg(feature = "parquet")]
+mod parquet_export {
+    use super::{StepRecord, TraceExporter};
+    use anyhow::Result;
+    use arrow::{
+        array::{ArrayRef, FixedSizeListBuilder, UInt32Builder, UInt64Builder},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    };
+    use parquet::arrow::ArrowWriter;
+    use std::{fs::File, path::Path, sync::Arc};
+
+    /// The number of records buffered before they are written out as a row group.
+    const BATCH_SIZE: usize = 1 << 16;
+
+    /// The [ParquetTraceExporter] writes [StepRecord]s to a Parquet file, with one column per
+    /// field. Registers are stored as fixed size lists of 32 values, and the memory access
+    /// columns are null for instructions that do not access memory.
+    pub struct ParquetTraceExporter {
+        /// The Arrow schema of the records.
+        schema: SchemaRef,
+        /// The Parquet writer. `None` once the exporter has been finished.
+        writer: Option<ArrowWriter<File>>,
+        /// The builders of the columns of the current batch.
+        step: UInt64Builder,
+        pc: UInt32Builder,
+        instruction: UInt32Builder,
+        pre_registers: FixedSizeListBuilder<UInt32Builder>,
+        post_registers: FixedSizeListBuilder<UInt32Builder>,
+        pre_lo: UInt32Builder,
+        pre_hi: UInt32Builder,
+        post_lo: UInt32Builder,
+        post_hi: UInt32Builder,
+        mem_address: UInt32Builder,
+        mem_pre: UInt32Builder,
+        mem_post: UInt32Builder,
+        /// The number of records in the current batch.
+        buffered: usize,
+    }
+
+    impl ParquetTraceExporter {
+        /// Creates a new [ParquetTraceExporter] that writes to the file at the given path,
+        /// truncating it if it already exists.
+        pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+            let registers =
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt32, true)), 32);
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("step", DataType::UInt64, false),
+                Field::new("pc", DataType::UInt32, false),
+                Field::new("instruction", DataType::UInt32, false),
+                Field::new("pre_registers", registers.clone(), false),
+                Field::new("post_registers", registers, false),
+                Field::new("pre_lo", DataType::UInt32, false),
+                Field::new("pre_hi", DataType::UInt32, false),
+                Field::new("post_lo", DataType::UInt32, false),
+                Field::new("post_hi", DataType::UInt32, false),
+                Field::new("mem_address", DataType::UInt32, true),
+                Field::new("mem_pre", DataType::UInt32, true),
+                Field::new("mem_post", DataType::UInt32, true),
+            ]));
+            let writer = ArrowWriter::try_new(File::create(path)?, Arc::clone(&schema), None)?;
+
+            Ok(Self {
+                schema,
+                writer: Some(writer),
+                step: UInt64Builder::new(),
+                pc: UInt32Builder::new(),
+                instruction: UInt32Builder::new(),
+                pre_registers: FixedSizeListBuilder::new(UInt32Builder::new(), 32),
+                post_registers: FixedSizeListBuilder::new(UInt32Builder::new(), 32),
+                pre_lo: UInt32Builder::new(),
+                pre_hi: UInt32Builder::new(),
+                post_lo: UInt32Builder::new(),
+                post_hi: UInt32Builder::new(),
+                mem_address: UInt32Builder::new(),
+                mem_pre: UInt32Builder::new(),
+                mem_post: UInt32Builder::new(),
+                buffered: 0,
+            })
+        }
+
+        /// Writes the buffered records out as a row group.
+        fn flush_batch(&mut self) -> Result<()> {
+            if self.buffered == 0 {
+                return Ok(());
+            }
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(self.step.finish()),
+                Arc::new(self.pc.finish()),
+                Arc::new(self.instruction.finish()),
+                Arc::new(self.pre_registers.finish()),
+                Arc::new(self.post_registers.finish()),
+                Arc::new(self.pre_lo.finish()),
+                Arc::new(self.pre_hi.finish()),
+                Arc::new(self.post_lo.finish()),
+                Arc::new(self.post_hi.finish()),
+                Arc::new(self.mem_address.finish()),
+                Arc::new(self.mem_pre.finish()),
+                Arc::new(self.mem_post.finish()),
+            ];
+            let batch = RecordBatch::try_new(Arc::clone(&self.schema), columns)?;
+            self.writer
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("Trace exporter is finished"))?
+                .write(&batch)?;
+            self.buffered = 0;
+            Ok(())
+        }
+    }
+
+    impl TraceExporter for ParquetTraceExporter {
+        fn write_record(&mut self, record: &StepRecord) -> Result<()> {
+            self.step.append_value(record.step);
+            self.pc.append_value(record.pc);
+            self.instruction.append_value(record.instruction);
+            self.pre_registers
+                .values()
+                .append_slice(&record.pre_registers);
+            self.pre_registers.append(true);
+            self.post_registers
+                .values()
+                .append_slice(&record.post_registers);
+            self.post_registers.append(true);
+            self.pre_lo.append_value(record.pre_lo);
+            self.pre_hi.append_value(record.pre_hi);
+            self.post_lo.append_value(record.post_lo);
+            self.post_hi.append_value(record.post_hi);
+            self.mem_address.append_option(record.mem_address);
+            self.mem_pre.append_option(record.mem_pre);
+            self.mem_post.append_option(record.mem_post);
+
+            self.buffered += 1;
+            if self.buffered == BATCH_SIZE {
+                self.flush_batch()?;
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            self.flush_batch()?;
+            if let Some(writer) = self.writer.take() {
+                writer.close()?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::StaticOracle;
+    use std::io;
+
+    #[test]
+    fn records_steps() {
+        // 0x1000: addiu $t0, $zero, 42
+        // 0x1004: sw $t0, 0x100($zero)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0x2408002A).unwrap();
+        state.memory.set_memory(0x1004, 0xAC080100).unwrap();
+        state.memory.set_memory(0x100, 7).unwrap();
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::new(Vec::new()), io::sink(), io::sink());
+        ins.set_record_mem_access(true);
+
+        let mut out = Vec::new();
+        {
+            let mut exporter = JsonlTraceExporter::new(&mut out);
+            for _ in 0..2 {
+                let mut record = StepRecord::begin(&mut ins.state).unwrap();
+                ins.step(false).unwrap();
+                record.finish(&mut ins).unwrap();
+                exporter.write_record(&record).unwrap();
+            }
+            exporter.finish().unwrap();
+        }
+
+        let records = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<StepRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].pc, 0x1000);
+        assert_eq!(records[0].instruction, 0x2408002A);
+        assert_eq!(records[0].pre_registers[8], 0);
+        assert_eq!(records[0].post_registers[8], 42);
+        assert_eq!(records[0].mem_address, None);
+
+        assert_eq!(records[1].step, 1);
+        assert_eq!(records[1].mem_address, Some(0x100));
+        assert_eq!(records[1].mem_pre, Some(7));
+        assert_eq!(records[1].mem_post, Some(42));
+    }
+}
@@ -0,0 +1,340 @@
+//! This module contains the [TraceFarm], which generates the hash ladder and proofs of a trace in
+//! parallel, by sharding the trace at the snapshots written by an earlier run.
+
+use crate::{builder::load_state, HashLadder, HashLadderWriter, Proof};
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{InstrumentedState, PreimageOracle};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// The [TraceFarm] shards a trace at the snapshots of its [crate::State] and runs the shards on
+/// a pool of worker threads. Each worker resumes from the snapshot at the start of its shard,
+/// takes the state hashes of the ladder and the requested proofs within it, and stops at the
+/// next snapshot. The results of all shards are merged in step order.
+///
+/// The snapshots must be states of the same trace, e.g. those written by a sequential run with
+/// `--snapshot-at`. Shards start at the snapshots, so the farm can only be as parallel as the
+/// number of snapshots allows.
+#[derive(Debug, Clone)]
+pub struct TraceFarm {
+    /// The paths of the snapshots, keyed by their step.
+    snapshots: BTreeMap<u64, PathBuf>,
+    /// The number of steps between consecutive hashes of the ladder.
+    interval: u64,
+    /// The number of worker threads.
+    workers: usize,
+    /// The last step to take a hash or proof at. The trace extends until the program exits if not
+    /// specified.
+    stop_at: Option<u64>,
+    /// The steps to generate proofs at.
+    proof_at: BTreeSet<u64>,
+}
+
+/// The [FarmOutput] holds the merged results of a [TraceFarm] run.
+#[derive(Debug, Clone)]
+pub struct FarmOutput {
+    /// The hash ladder of the trace, starting at the first multiple of the interval at or after
+    /// the first snapshot.
+    pub ladder: HashLadder,
+    /// The generated proofs, in step order. Proofs at steps at or after the program exited are
+    /// not generated.
+    pub proofs: Vec<Proof>,
+}
+
+impl FarmOutput {
+    /// Writes the hash ladder to a new hash ladder file.
+    pub fn write_hash_ladder(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer =
+            HashLadderWriter::create(path, self.ladder.interval, self.ladder.first_step)?;
+        for hash in &self.ladder.hashes {
+            writer.push(*hash)?;
+        }
+        writer.finish()
+    }
+}
+
+/// The results of a single shard of a [TraceFarm] run.
+struct ShardOutput {
+    /// The state hashes of the ladder within the shard.
+    hashes: Vec<[u8; 32]>,
+    /// The proofs within the shard.
+    proofs: Vec<Proof>,
+    /// Whether the program exited within the shard.
+    exited: bool,
+}
+
+impl TraceFarm {
+    /// Creates a new [TraceFarm] that takes a state hash at every `interval`th step, running on
+    /// as many worker threads as there are available CPUs.
+    pub fn new(interval: u64) -> Self {
+        Self {
+            snapshots: BTreeMap::new(),
+            interval: interval.max(1),
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            stop_at: None,
+            proof_at: BTreeSet::new(),
+        }
+    }
+
+    /// Adds the snapshot of the state at the given step.
+    pub fn with_snapshot(mut self, step: u64, path: impl Into<PathBuf>) -> Self {
+        self.snapshots.insert(step, path.into());
+        self
+    }
+
+    /// Adds all snapshots whose paths match the given format, in which `%d` stands for the step,
+    /// as with `--snapshot-format`.
+    ///
+    /// ### Takes
+    /// - `format`: The format of the snapshot paths, e.g. `snapshots/%d.json.gz`.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [TraceFarm] with the matching snapshots added.
+    pub fn with_snapshot_format(mut self, format: &str) -> Result<Self> {
+        let (prefix, suffix) = format.split_once("%d").ok_or(anyhow!(
+            "The snapshot format {} does not contain %d",
+            format
+        ))?;
+        // The step may only appear in the file name; the directory is scanned for matching files.
+        let (dir, file_prefix) = match prefix.rfind(std::path::MAIN_SEPARATOR) {
+            Some(pos) => (&prefix[..=pos], &prefix[pos + 1..]),
+            None => (".", prefix),
+        };
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(step) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(file_prefix))
+                .and_then(|name| name.strip_suffix(suffix))
+                .and_then(|step| step.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            self.snapshots.insert(step, entry.path());
+        }
+        Ok(self)
+    }
+
+    /// Sets the number of worker threads.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Sets the last step to take a hash or proof at.
+    pub fn with_stop_at(mut self, stop_at: u64) -> Self {
+        self.stop_at = Some(stop_at);
+        self
+    }
+
+    /// Adds the steps to generate proofs at.
+    pub fn with_proofs_at(mut self, steps: impl IntoIterator<Item = u64>) -> Self {
+        self.proof_at.extend(steps);
+        self
+    }
+
+    /// Returns the steps of the snapshots that the trace is sharded at.
+    pub fn shard_starts(&self) -> Vec<u64> {
+        self.snapshots
+            .keys()
+            .copied()
+            .take_while(|step| self.stop_at.map_or(true, |stop_at| *step <= stop_at))
+            .collect()
+    }
+
+    /// Runs all shards of the trace on the worker threads and merges their results.
+    ///
+    /// ### Takes
+    /// - `oracle`: Creates the [PreimageOracle] of a shard. Called once per shard, on the worker
+    ///   thread running it.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the merged [FarmOutput], or the error of the first failing shard.
+    pub fn run<P, F>(&self, oracle: F) -> Result<FarmOutput>
+    where
+        P: PreimageOracle,
+        F: Fn() -> Result<P> + Sync,
+    {
+        let starts = self.shard_starts();
+        anyhow::ensure!(
+            !starts.is_empty(),
+            "The trace farm has no snapshots to start from"
+        );
+
+        let next_shard = AtomicUsize::new(0);
+        let outputs = Mutex::new(BTreeMap::new());
+        let error = Mutex::new(None);
+        thread::scope(|scope| {
+            for _ in 0..self.workers.min(starts.len()) {
+                scope.spawn(|| loop {
+                    let index = next_shard.fetch_add(1, Ordering::Relaxed);
+                    if index >= starts.len() || error.lock().expect("Lock poisoned").is_some() {
+                        break;
+                    }
+                    let end = starts.get(index + 1).copied();
+                    match self.run_shard(starts[index], end, &oracle) {
+                        Ok(output) => {
+                            outputs.lock().expect("Lock poisoned").insert(index, output);
+                        }
+                        Err(e) => {
+                            error.lock().expect("Lock poisoned").get_or_insert(e);
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(e) = error.into_inner().expect("Lock poisoned") {
+            return Err(e);
+        }
+
+        // Merge the shards in step order, up to the one that the program exited in.
+        let mut ladder = HashLadder {
+            interval: self.interval,
+            first_step: starts[0].div_ceil(self.interval) * self.interval,
+            hashes: Vec::new(),
+        };
+        let mut proofs = Vec::new();
+        for (_, output) in outputs.into_inner().expect("Lock poisoned") {
+            ladder.hashes.extend(output.hashes);
+            proofs.extend(output.proofs);
+            if output.exited {
+                break;
+            }
+        }
+        Ok(FarmOutput { ladder, proofs })
+    }
+
+    /// Runs the shard from the snapshot at `start` up to the snapshot at `end`, if any.
+    fn run_shard<P, F>(&self, start: u64, end: Option<u64>, oracle: &F) -> Result<ShardOutput>
+    where
+        P: PreimageOracle,
+        F: Fn() -> Result<P>,
+    {
+        let path = &self.snapshots[&start];
+        crate::traces::info!(target: "cannon::trace_farm", "Running shard from step {} ({})", start, path.display());
+        let mut state = load_state(&path.to_string_lossy(), None)?;
+        state.verify_checksum()?;
+        anyhow::ensure!(
+            state.step == start,
+            "The snapshot {} is at step {}, expected step {}",
+            path.display(),
+            state.step,
+            start
+        );
+
+        // The shard ends before the next snapshot, whose shard takes over from there.
+        let end = match (end, self.stop_at) {
+            (Some(end), _) => end,
+            (None, Some(stop_at)) => stop_at.saturating_add(1),
+            (None, None) => u64::MAX,
+        };
+        let mut ins = InstrumentedState::new(state, oracle()?, io::sink(), io::sink());
+        let mut proof_steps = self.proof_at.range(start..end).copied().peekable();
+        let mut next_hash = start.div_ceil(self.interval) * self.interval;
+        let mut output = ShardOutput {
+            hashes: Vec::new(),
+            proofs: Vec::new(),
+            exited: false,
+        };
+        loop {
+            let target = proof_steps
+                .peek()
+                .map_or(next_hash, |proof_step| next_hash.min(*proof_step));
+            if target >= end {
+                break;
+            }
+            ins.step_threaded(target - ins.state.step)?;
+
+            if ins.state.exited {
+                // The ladder ends with the first hash at or after the final step.
+                if next_hash < end {
+                    output.hashes.push(ins.state.state_hash()?);
+                }
+                output.exited = true;
+                break;
+            }
+            if target == next_hash {
+                output.hashes.push(ins.state.state_hash()?);
+                next_hash += self.interval;
+            }
+            if proof_steps.next_if_eq(&target).is_some() {
+                let pre = ins.state.state_hash()?;
+                let step_witness = ins.step(true)?.ok_or(anyhow!("No step witness"))?;
+                let post = ins.state.state_hash()?;
+                output
+                    .proofs
+                    .push(Proof::new(target, pre, post, step_witness));
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generate_hash_ladder;
+    use cannon_mipsevm::test_utils::{counting_state, StaticOracle};
+
+    #[test]
+    fn farm_matches_sequential_run() {
+        let dir = std::env::temp_dir().join("cannon_trace_farm");
+        fs::create_dir_all(&dir).unwrap();
+
+        // Write snapshots every 1000 steps of the 7501-step run, and the reference ladder in one go.
+        let mut ins = InstrumentedState::new(
+            counting_state(1875),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        while !ins.state.exited {
+            let path = dir.join(format!("{}.json", ins.state.step));
+            fs::write(path, serde_json::to_vec(&ins.state).unwrap()).unwrap();
+            ins.step_threaded(1000).unwrap();
+        }
+        let reference_path = dir.join("reference.ladder");
+        let mut ins = InstrumentedState::new(
+            counting_state(1875),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let writer = HashLadderWriter::create(&reference_path, 7, 0).unwrap();
+        generate_hash_ladder(&mut ins, writer, None).unwrap();
+        let reference = HashLadder::load(&reference_path).unwrap();
+
+        let farm = TraceFarm::new(7)
+            .with_workers(3)
+            .with_snapshot_format(&format!("{}/%d.json", dir.display()))
+            .unwrap()
+            .with_proofs_at([999, 1000, 4321]);
+        assert_eq!(farm.shard_starts().len(), 8);
+        let output = farm.run(|| Ok(StaticOracle::default())).unwrap();
+        assert_eq!(output.ladder, reference);
+        assert_eq!(
+            output
+                .proofs
+                .iter()
+                .map(|proof| proof.step)
+                .collect::<Vec<_>>(),
+            [999, 1000, 4321]
+        );
+
+        let farm_path = dir.join("farm.ladder");
+        output.write_hash_ladder(&farm_path).unwrap();
+        assert_eq!(HashLadder::load(&farm_path).unwrap(), reference);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,421 @@
+//! This module contains the [TraceProvider] trait, which serves the claims of the
+//! `FaultDisputeGame` bisection model, and the [CannonTraceProvider], which backs it with the
+//! native VM.
+//!
+//! The game bisects over a binary tree of depth `max_depth`, whose leaves are the instruction
+//! steps of the program. A [Position] in the tree commits to the trace index of the rightmost leaf
+//! beneath it, and the claim at a trace index `i` is the state hash after executing the
+//! instruction at step `i`, i.e. the poststate of the [Proof] at step `i`. Trace indices beyond
+//! the exit of the program commit to the final state.
+
+use crate::Proof;
+use anyhow::{anyhow, Result};
+use cannon_mipsevm::{
+    InstrumentedState, PreimageOracle, State, StateWitness, StateWitnessHasher, StepWitness,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+/// A [Claim] is the state hash that a [Position] in the game tree commits to.
+pub type Claim = [u8; 32];
+
+/// A [Position] is a node in the game tree, identified by its generalized index
+/// $2^{\text{depth}} + \text{index}$, as in the `FaultDisputeGame` contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position(u128);
+
+impl Position {
+    /// The root of the game tree.
+    pub const ROOT: Position = Position(1);
+
+    /// Creates a new [Position] at the given depth and index within that depth.
+    pub fn new(depth: u8, index_at_depth: u64) -> Self {
+        Self((1u128 << depth) | index_at_depth as u128)
+    }
+
+    /// Creates a new [Position] from its generalized index.
+    pub fn from_generalized_index(gindex: u128) -> Self {
+        Self(gindex)
+    }
+
+    /// Returns the generalized index of the [Position].
+    pub fn generalized_index(&self) -> u128 {
+        self.0
+    }
+
+    /// Returns the depth of the [Position] in the game tree.
+    pub fn depth(&self) -> u8 {
+        (127 - self.0.leading_zeros()) as u8
+    }
+
+    /// Returns the index of the [Position] within its depth.
+    pub fn index_at_depth(&self) -> u64 {
+        (self.0 ^ (1u128 << self.depth())) as u64
+    }
+
+    /// Returns the left child of the [Position], which attacks it.
+    pub fn attack(&self) -> Self {
+        Self(self.0 << 1)
+    }
+
+    /// Returns the left child of the right child of the [Position]'s parent, which defends it.
+    pub fn defend(&self) -> Self {
+        Self((self.0 | 1) << 1)
+    }
+
+    /// Returns the trace index that the [Position] commits to.
+    ///
+    /// ### Takes
+    /// - `max_depth`: The maximum depth of the game tree.
+    ///
+    /// ### Returns
+    /// - The index of the rightmost leaf beneath the [Position].
+    pub fn trace_index(&self, max_depth: u8) -> u64 {
+        let remaining = max_depth - self.depth();
+        (((self.index_at_depth() as u128 + 1) << remaining) - 1) as u64
+    }
+}
+
+/// The [StepData] required to counter a leaf claim with a `step` of the `MIPS` contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepData {
+    /// The encoded prestate of the step.
+    pub prestate: StateWitness,
+    /// The memory proof of the step.
+    pub proof: Vec<u8>,
+    /// The preimage that the step reads, which must be loaded into the `PreimageOracle` first.
+    pub oracle_data: Option<PreimageOracleData>,
+}
+
+/// The [PreimageOracleData] of a step that reads a preimage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreimageOracleData {
+    /// The key of the preimage.
+    pub key: Vec<u8>,
+    /// The preimage, including its 8 byte length prefix.
+    pub value: Vec<u8>,
+    /// The offset that the step reads from.
+    pub offset: u32,
+    /// The calldata of the `PreimageOracle` call that loads the preimage part.
+    pub input: Option<Vec<u8>>,
+}
+
+/// A [TraceProvider] serves the claims and step data of the `FaultDisputeGame` bisection model.
+pub trait TraceProvider {
+    /// Returns the [Claim] that the given [Position] commits to.
+    fn get(&mut self, pos: Position) -> Result<Claim>;
+
+    /// Returns the [StepData] required to step from the trace index that the given [Position]
+    /// commits to.
+    fn get_step_data(&mut self, pos: Position) -> Result<StepData>;
+
+    /// Returns the encoded absolute prestate of the trace.
+    fn absolute_prestate(&mut self) -> Result<StateWitness>;
+}
+
+/// The default number of steps between the cached snapshots of a [CannonTraceProvider].
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1 << 24;
+
+/// The [CannonTraceProvider] is a [TraceProvider] that executes the native VM from the absolute
+/// prestate. It caches snapshots of the [State] at a fixed interval of steps, so that a claim at
+/// any trace index can be produced by re-executing at most one interval, as well as every [Proof]
+/// it has produced.
+pub struct CannonTraceProvider<O: Write, E: Write, P: PreimageOracle> {
+    /// The instrumented state of the native VM.
+    ins_state: InstrumentedState<O, E, P>,
+    /// The maximum depth of the game tree.
+    max_depth: u8,
+    /// The step of the absolute prestate, which trace indices are relative to.
+    start: u64,
+    /// The number of steps between cached snapshots.
+    snapshot_interval: u64,
+    /// The cached snapshots, keyed by step, which do not share memory pages with the native VM.
+    snapshots: BTreeMap<u64, State>,
+    /// The cached proofs, keyed by step.
+    proofs: HashMap<u64, Proof>,
+    /// The final state witness and its hash, once the program is known to have exited.
+    exited: Option<(u64, StateWitness, [u8; 32])>,
+}
+
+impl<O, E, P> CannonTraceProvider<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    /// Creates a new [CannonTraceProvider].
+    ///
+    /// ### Takes
+    /// - `ins_state`: The instrumented state of the native VM, at the absolute prestate.
+    /// - `max_depth`: The maximum depth of the game tree.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [CannonTraceProvider].
+    pub fn new(ins_state: InstrumentedState<O, E, P>, max_depth: u8) -> Result<Self> {
+        if max_depth > 64 {
+            anyhow::bail!("Maximum depth {} exceeds the 64 bit trace index", max_depth);
+        }
+
+        let start = ins_state.state.step;
+        Ok(Self {
+            snapshots: BTreeMap::from([(start, ins_state.state.clone())]),
+            ins_state,
+            max_depth,
+            start,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            proofs: HashMap::default(),
+            exited: None,
+        })
+    }
+
+    /// Sets the number of steps between cached snapshots.
+    pub fn with_snapshot_interval(mut self, snapshot_interval: u64) -> Self {
+        self.snapshot_interval = snapshot_interval.max(1);
+        self
+    }
+
+    /// Returns the [Proof] of the instruction at the given trace index. If the program exits
+    /// before the trace index, the proof steps from the final state, which the `MIPS` contract
+    /// treats as a no-op.
+    pub fn proof_at(&mut self, trace_index: u64) -> Result<Proof> {
+        let step = self
+            .start
+            .checked_add(trace_index)
+            .ok_or(anyhow!("Trace index {} overflows the step", trace_index))?;
+        if let Some(proof) = self.proofs.get(&step) {
+            return Ok(proof.clone());
+        }
+        if let Some(proof) = self.exited_proof(step) {
+            return Ok(proof);
+        }
+
+        self.seek(step)?;
+        if self.ins_state.state.exited {
+            return self
+                .exited_proof(step)
+                .ok_or(anyhow!("Program exited without recording its final state"));
+        }
+
+        let pre = self.ins_state.state.state_hash()?;
+        let step_witness = self
+            .ins_state
+            .step(true)?
+            .ok_or(anyhow!("No step witness"))?;
+        let post = self.ins_state.state.state_hash()?;
+
+        let proof = Proof::new(step, pre, post, step_witness);
+        self.proofs.insert(step, proof.clone());
+        Ok(proof)
+    }
+
+    /// Positions the native VM at the given step, restoring the closest snapshot at or before it
+    /// if the VM is past it, and caching snapshots on the way. Stops early if the program exits.
+    fn seek(&mut self, step: u64) -> Result<()> {
+        let current = self.ins_state.state.step;
+        let (&snapshot_step, snapshot) = self
+            .snapshots
+            .range(..=step)
+            .next_back()
+            .expect("The absolute prestate is always cached");
+        if current > step || current < snapshot_step {
+            self.ins_state.state = snapshot.clone();
+            self.ins_state.invalidate_block_cache();
+        }
+
+        while self.ins_state.state.step < step && !self.ins_state.state.exited {
+            let current = self.ins_state.state.step;
+            let next_snapshot = (current / self.snapshot_interval + 1) * self.snapshot_interval;
+            self.ins_state
+                .step_threaded(next_snapshot.min(step) - current)?;
+
+            let current = self.ins_state.state.step;
+            if current % self.snapshot_interval == 0 && !self.snapshots.contains_key(&current) {
+                crate::traces::debug!(target: "cannon::trace_provider", "Caching snapshot at step {}", current);
+                self.snapshots.insert(current, self.ins_state.state.clone());
+            }
+        }
+
+        if self.ins_state.state.exited && self.exited.is_none() {
+            let witness = self.ins_state.state.encode_witness()?;
+            self.exited = Some((self.ins_state.state.step, witness, witness.state_hash()));
+        }
+        Ok(())
+    }
+
+    /// Returns the no-op [Proof] at the given step, if the program exited at or before it.
+    fn exited_proof(&self, step: u64) -> Option<Proof> {
+        let (exit_step, witness, hash) = self.exited?;
+        (step >= exit_step).then(|| {
+            Proof::new(
+                step,
+                hash,
+                hash,
+                StepWitness {
+                    state: witness,
+                    mem_proof: Vec::new(),
+                    ..Default::default()
+                },
+            )
+        })
+    }
+
+    /// Returns the trace index that the given [Position] commits to.
+    fn trace_index(&self, pos: Position) -> Result<u64> {
+        if pos.depth() > self.max_depth {
+            anyhow::bail!(
+                "Position at depth {} exceeds the maximum depth {}",
+                pos.depth(),
+                self.max_depth
+            );
+        }
+        Ok(pos.trace_index(self.max_depth))
+    }
+}
+
+impl<O, E, P> TraceProvider for CannonTraceProvider<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    fn get(&mut self, pos: Position) -> Result<Claim> {
+        let trace_index = self.trace_index(pos)?;
+        Ok(self.proof_at(trace_index)?.post)
+    }
+
+    fn get_step_data(&mut self, pos: Position) -> Result<StepData> {
+        let trace_index = self.trace_index(pos)?;
+        let proof = self.proof_at(trace_index)?;
+
+        let oracle_data = match (proof.oracle_key, proof.oracle_value, proof.oracle_offset) {
+            (Some(key), Some(value), Some(offset)) => Some(PreimageOracleData {
+                key,
+                value,
+                offset,
+                input: proof.oracle_input,
+            }),
+            _ => None,
+        };
+        Ok(StepData {
+            prestate: proof.state_data,
+            proof: proof.proof_data,
+            oracle_data,
+        })
+    }
+
+    fn absolute_prestate(&mut self) -> Result<StateWitness> {
+        let prestate = self
+            .snapshots
+            .get_mut(&self.start)
+            .expect("The absolute prestate is always cached");
+        Ok(prestate.encode_witness()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cannon_mipsevm::test_utils::{counting_state, StaticOracle};
+    use std::io;
+
+    #[test]
+    fn positions() {
+        assert_eq!(Position::ROOT.depth(), 0);
+        assert_eq!(Position::ROOT.trace_index(4), 15);
+        assert_eq!(Position::ROOT.attack(), Position::new(1, 0));
+        assert_eq!(Position::new(1, 0).trace_index(4), 7);
+        assert_eq!(Position::new(1, 0).defend(), Position::new(2, 2));
+        assert_eq!(Position::new(2, 1).defend(), Position::new(3, 2));
+        assert_eq!(Position::new(4, 5).trace_index(4), 5);
+        assert_eq!(Position::new(4, 5).generalized_index(), 21);
+        assert_eq!(Position::from_generalized_index(21).index_at_depth(), 5);
+    }
+
+    #[test]
+    fn claims_match_execution() {
+        // 0x1000: addiu $t0, $t0, 1
+        // 0x1004: addiu $v0, $zero, 4246 (exit_group)
+        // 0x1008: syscall
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0x25080001).unwrap();
+        state.memory.set_memory(0x1004, 0x24021096).unwrap();
+        state.memory.set_memory(0x1008, 0x0000000C).unwrap();
+
+        let mut expected = Vec::new();
+        let mut ins = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::new(Vec::new()),
+            io::sink(),
+            io::sink(),
+        );
+        while !ins.state.exited {
+            ins.step(false).unwrap();
+            expected.push(ins.state.state_hash().unwrap());
+        }
+        assert_eq!(expected.len(), 3);
+
+        let mut provider = CannonTraceProvider::new(
+            InstrumentedState::new(state, StaticOracle::new(Vec::new()), io::sink(), io::sink()),
+            3,
+        )
+        .unwrap()
+        .with_snapshot_interval(2);
+
+        // Query out of order to exercise the snapshot cache.
+        for trace_index in [2, 0, 7, 1, 5] {
+            let claim = provider.get(Position::new(3, trace_index)).unwrap();
+            let expected = expected[(trace_index as usize).min(2)];
+            assert_eq!(claim, expected, "trace index {}", trace_index);
+        }
+        assert_eq!(provider.get(Position::ROOT).unwrap(), expected[2]);
+
+        let step_data = provider.get_step_data(Position::new(3, 1)).unwrap();
+        assert_eq!(step_data.proof.len(), 28 * 32 * 2);
+        assert!(step_data.oracle_data.is_none());
+
+        let step_data = provider.get_step_data(Position::new(3, 6)).unwrap();
+        assert!(step_data.proof.is_empty());
+        assert_eq!(step_data.prestate.state_hash(), expected[2]);
+    }
+
+    #[test]
+    fn seeks_backwards_over_stores() {
+        let mut expected = Vec::new();
+        let mut ins = InstrumentedState::new(
+            counting_state(20),
+            StaticOracle::new(Vec::new()),
+            io::sink(),
+            io::sink(),
+        );
+        while !ins.state.exited {
+            ins.step(false).unwrap();
+            expected.push(ins.state.state_hash().unwrap());
+        }
+        assert_eq!(expected.len(), 81);
+
+        let mut provider = CannonTraceProvider::new(
+            InstrumentedState::new(
+                counting_state(20),
+                StaticOracle::new(Vec::new()),
+                io::sink(),
+                io::sink(),
+            ),
+            7,
+        )
+        .unwrap()
+        .with_snapshot_interval(8);
+
+        // Every backwards seek restores a snapshot that the later stores must not have modified.
+        for trace_index in [79, 10, 80, 3, 40, 100, 17, 0, 65] {
+            let claim = provider.get(Position::new(7, trace_index)).unwrap();
+            let expected = expected[(trace_index as usize).min(80)];
+            assert_eq!(claim, expected, "trace index {}", trace_index);
+        }
+    }
+}
@@ -1,14 +1,29 @@
 //! This module contains the types for the `cannon` interface.
 
-use cannon_mipsevm::StateWitness;
-use preimage_oracle::ReadWritePair;
+use alloy_primitives::B256;
+use cannon_mipsevm::{
+    CannonResult, MemAccess, Migration, Schema, StateWitness, StepWitness, Versioned,
+};
+use preimage_oracle::{KeyType, ReadWritePair};
 use serde::{Deserialize, Serialize};
 use std::process::Child;
 
+/// The current version of the serialized [Proof] format.
+pub const PROOF_VERSION: u32 = 1;
+
+/// The [Schema] of the serialized [Proof] format.
+pub const PROOF_SCHEMA: Schema = Schema {
+    kind: "proof",
+    current: PROOF_VERSION,
+    migrations: &[Migration::Compatible],
+};
+
 /// The [Proof] struct contains the data for a Cannon proof at a given instruction.
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Proof {
+    #[serde(default)]
+    pub version: u32,
     pub step: u64,
     pub pre: [u8; 32],
     pub post: [u8; 32],
@@ -20,6 +35,86 @@ pub struct Proof {
     pub oracle_value: Option<Vec<u8>>,
     pub oracle_offset: Option<u32>,
     pub oracle_input: Option<Vec<u8>>,
+    /// The memory accesses performed by the step, if they were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_accesses: Option<Vec<MemAccess>>,
+}
+
+impl Versioned for Proof {
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+}
+
+impl Proof {
+    /// Creates a new [Proof] for the instruction at the given step.
+    ///
+    /// ### Takes
+    /// - `step`: The step of the proven instruction.
+    /// - `pre`: The state hash prior to the instruction.
+    /// - `post`: The state hash after the instruction.
+    /// - `step_witness`: The [StepWitness] of the instruction.
+    pub fn new(step: u64, pre: [u8; 32], post: [u8; 32], step_witness: StepWitness) -> Self {
+        let preimage_input = step_witness.encode_preimage_oracle_input();
+        Self {
+            version: PROOF_VERSION,
+            step,
+            pre,
+            post,
+            state_data: step_witness.state,
+            step_input: step_witness.encode_step_input().to_vec(),
+            proof_data: step_witness.mem_proof,
+            oracle_input: preimage_input.map(|k| k.to_vec()),
+            oracle_key: step_witness.preimage_key.map(|k| k.to_vec()),
+            oracle_value: step_witness.preimage_value,
+            oracle_offset: step_witness.preimage_offset,
+            mem_accesses: step_witness.mem_accesses,
+        }
+    }
+
+    /// Returns the input to the preimage oracle of contract versions that take the local context
+    /// as an argument, if the step reads a preimage. Local preimages are re-encoded to be loaded
+    /// under the key localized with the local context; the input of all other preimages is the
+    /// [Proof]'s `oracle_input`.
+    ///
+    /// ### Takes
+    /// - `local_context`: The local context of the dispute game.
+    pub fn oracle_input_with_local_context(&self, local_context: B256) -> Option<Vec<u8>> {
+        let is_local = self
+            .oracle_key
+            .as_ref()
+            .is_some_and(|key| key.first() == Some(&(KeyType::Local as u8)));
+        if !is_local {
+            return self.oracle_input.clone();
+        }
+        self.step_witness()
+            .encode_preimage_oracle_input_with_local_context(local_context)
+            .map(|input| input.to_vec())
+    }
+
+    /// Reconstructs the [StepWitness] that the [Proof] was created from, e.g. to re-encode its step
+    /// input for a different version of the `MIPS` contract. The precompile call of a precompile
+    /// preimage is only kept in the [Proof]'s `oracle_input`, and is left empty.
+    pub fn step_witness(&self) -> StepWitness {
+        StepWitness {
+            state: self.state_data,
+            mem_proof: self.proof_data.clone(),
+            preimage_key: self
+                .oracle_key
+                .as_ref()
+                .and_then(|key| key.as_slice().try_into().ok()),
+            preimage_value: self.oracle_value.clone(),
+            preimage_offset: self.oracle_offset,
+            precompile_call: None,
+            mem_accesses: self.mem_accesses.clone(),
+        }
+    }
+
+    /// Deserializes a [Proof] from its JSON representation, upgrading proofs written by older
+    /// versions of this crate to the current [PROOF_VERSION].
+    pub fn from_json(raw: &[u8]) -> CannonResult<Self> {
+        PROOF_SCHEMA.from_slice(raw)
+    }
 }
 
 /// A [Child] process that was given file descriptors. This struct couples
@@ -1,11 +1,51 @@
 use cannon_mipsevm::{
     load_elf, patch_go, patch_stack,
     test_utils::{ClaimTestOracle, StaticOracle},
-    InstrumentedState, PreimageOracle,
+    InstrumentedState, PreimageOracle, State,
 };
-use criterion::{criterion_group, criterion_main, Bencher, Criterion};
+use criterion::{criterion_group, criterion_main, Bencher, Criterion, Throughput};
 use pprof::criterion::{Output, PProfProfiler};
-use std::io::BufWriter;
+use std::io::{self, BufWriter};
+
+/// The number of instructions executed per iteration of the guest loop benchmarks.
+const LOOP_STEPS: u64 = 100_000;
+
+/// A tight loop of ALU instructions.
+const ALU_LOOP: &[u32] = &[
+    0x25080001, // addiu $t0, $t0, 1
+    0x01284826, // xor $t1, $t1, $t0
+    0x08000400, // j 0x1000
+    0x00000000, // nop
+];
+
+/// A loop that increments each word of a 64 KiB buffer at `$t2` in turn.
+const MEMORY_LOOP: &[u32] = &[
+    0x01485821, // addu $t3, $t2, $t0
+    0x8D690000, // lw $t1, 0($t3)
+    0x25290001, // addiu $t1, $t1, 1
+    0xAD690000, // sw $t1, 0($t3)
+    0x25080004, // addiu $t0, $t0, 4
+    0x3108FFFC, // andi $t0, $t0, 0xFFFC
+    0x08000400, // j 0x1000
+    0x00000000, // nop
+];
+
+/// Creates an [InstrumentedState] that runs the given guest loop, placed at `0x1000`, forever.
+fn guest_loop(program: &[u32]) -> InstrumentedState<io::Sink, io::Sink, StaticOracle> {
+    let mut state = State {
+        pc: 0x1000,
+        next_pc: 0x1004,
+        ..Default::default()
+    };
+    state.registers[10] = 0x100000;
+    for (i, instruction) in program.iter().enumerate() {
+        state
+            .memory
+            .set_memory(0x1000 + i as u32 * 4, *instruction)
+            .unwrap();
+    }
+    InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink())
+}
 
 #[inline(always)]
 fn bench_exec(
@@ -30,6 +70,21 @@ fn bench_exec(
     })
 }
 
+/// Executes the given ELF to completion in the threaded execution mode, which runs blocks of
+/// pre-decoded instructions from the block cache.
+#[inline(always)]
+fn bench_exec_threaded(elf_bytes: &[u8], oracle: impl PreimageOracle, b: &mut Bencher) {
+    let mut state = load_elf(elf_bytes).unwrap();
+    patch_go(elf_bytes, &mut state).unwrap();
+    patch_stack(&mut state).unwrap();
+
+    let out = BufWriter::new(Vec::default());
+    let err = BufWriter::new(Vec::default());
+    let mut ins = InstrumentedState::new(state, oracle, out, err);
+
+    b.iter(|| ins.step_threaded(u64::MAX).unwrap())
+}
+
 fn execution(c: &mut Criterion) {
     let mut g = c.benchmark_group("execution");
     g.sample_size(10);
@@ -39,6 +94,11 @@ fn execution(c: &mut Criterion) {
         bench_exec(elf_bytes, StaticOracle::default(), false, b);
     });
 
+    g.bench_function("[Threaded] Execution (hello.elf)", |b| {
+        let elf_bytes = include_bytes!("../../../example/bin/hello.elf");
+        bench_exec_threaded(elf_bytes, StaticOracle::default(), b);
+    });
+
     g.bench_function("[Witness] Execution (hello.elf)", |b| {
         let elf_bytes = include_bytes!("../../../example/bin/hello.elf");
         bench_exec(elf_bytes, StaticOracle::default(), true, b);
@@ -49,15 +109,52 @@ fn execution(c: &mut Criterion) {
         bench_exec(elf_bytes, ClaimTestOracle::default(), false, b);
     });
 
+    g.bench_function("[Threaded] Execution (claim.elf)", |b| {
+        let elf_bytes = include_bytes!("../../../example/bin/claim.elf");
+        bench_exec_threaded(elf_bytes, ClaimTestOracle::default(), b);
+    });
+
     g.bench_function("[Witness] Execution (claim.elf)", |b| {
         let elf_bytes = include_bytes!("../../../example/bin/claim.elf");
         bench_exec(elf_bytes, ClaimTestOracle::default(), true, b);
     });
 }
 
+/// Measures the instructions per second of representative guest loops, in each execution mode.
+fn stepping(c: &mut Criterion) {
+    let mut g = c.benchmark_group("stepping");
+    g.sample_size(10);
+    g.throughput(Throughput::Elements(LOOP_STEPS));
+
+    for (name, program) in [("ALU loop", ALU_LOOP), ("Memory loop", MEMORY_LOOP)] {
+        g.bench_function(format!("[No Witness] Stepping ({name})"), |b| {
+            let mut ins = guest_loop(program);
+            b.iter(|| {
+                for _ in 0..LOOP_STEPS {
+                    ins.step(false).unwrap();
+                }
+            })
+        });
+
+        g.bench_function(format!("[Threaded] Stepping ({name})"), |b| {
+            let mut ins = guest_loop(program);
+            b.iter(|| ins.step_threaded(LOOP_STEPS).unwrap())
+        });
+
+        g.bench_function(format!("[Witness] Stepping ({name})"), |b| {
+            let mut ins = guest_loop(program);
+            b.iter(|| {
+                for _ in 0..LOOP_STEPS {
+                    ins.step(true).unwrap();
+                }
+            })
+        });
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = execution
+    targets = execution, stepping
 }
 criterion_main!(benches);
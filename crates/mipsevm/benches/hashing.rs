@@ -0,0 +1,57 @@
+use cannon_mipsevm::{Memory, MerkleHasher, State, StateWitnessHasher};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use pprof::criterion::{Output, PProfProfiler};
+use rand::RngCore;
+
+/// Measures the hashes that dominate proof-heavy runs. Run with and without the `asm-keccak`
+/// feature to compare the keccak256 implementations.
+fn keccak(c: &mut Criterion) {
+    let mut g = c.benchmark_group("hashing");
+
+    let mut data = [0u8; 64];
+    rand::thread_rng().fill_bytes(&mut data);
+    let (left, right) = (
+        data[..32].try_into().unwrap(),
+        data[32..].try_into().unwrap(),
+    );
+
+    g.throughput(Throughput::Elements(1));
+    g.bench_function("Merkle Leaves (64 bytes)", |b| {
+        b.iter(|| MerkleHasher::Keccak256.hash_leaves(&data));
+    });
+    g.bench_function("Merkle Pair (2 x 32 bytes)", |b| {
+        b.iter(|| MerkleHasher::Keccak256.hash_pair(left, right));
+    });
+
+    let witness = State::default().encode_witness().unwrap();
+    g.bench_function("State Witness", |b| {
+        b.iter(|| witness.state_hash());
+    });
+}
+
+/// Measures the merkleization of a 25 MB memory from scratch, in which every node is rehashed.
+fn merkleize(c: &mut Criterion) {
+    let mut g = c.benchmark_group("hashing");
+    g.sample_size(10);
+
+    let mut memory = Memory::default();
+    let mut data = vec![0u8; 25_000_000];
+    rand::thread_rng().fill_bytes(&mut data[..]);
+    memory
+        .set_memory_range(0, &data[..])
+        .expect("Should not error");
+
+    g.bench_function("Merkleize (memory size = 25 MB)", |b| {
+        b.iter(|| {
+            memory.invalidate_all();
+            memory.merkle_root().unwrap()
+        });
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = keccak, merkleize
+}
+criterion_main!(benches);
@@ -1,7 +1,7 @@
 use cannon_mipsevm::Memory;
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use pprof::criterion::{Output, PProfProfiler};
-use rand::RngCore;
+use rand::{Rng, RngCore};
 
 fn merkle_root(c: &mut Criterion) {
     let mut g = c.benchmark_group("memory");
@@ -56,9 +56,70 @@ fn merkle_root(c: &mut Criterion) {
     });
 }
 
+/// Measures the recomputation of the merkle root of a 25 MB memory after writing to `k` of its
+/// pages, which is the work done for each proof after a stretch of execution.
+fn merkle_root_after_writes(c: &mut Criterion) {
+    let mut g = c.benchmark_group("memory");
+    g.sample_size(10);
+
+    let mut memory = Memory::default();
+    let mut data = vec![0u8; 25_000_000];
+    rand::thread_rng().fill_bytes(&mut data[..]);
+    memory
+        .set_memory_range(0, &data[..])
+        .expect("Should not error");
+    memory.merkle_root().unwrap();
+    let pages = (data.len() / 4096) as u32;
+
+    for k in [1u32, 16, 256, 4096] {
+        g.throughput(Throughput::Elements(k as u64));
+        g.bench_function(format!("Merkle Root (after {k} page writes)"), |b| {
+            let mut rng = rand::thread_rng();
+            b.iter(|| {
+                for _ in 0..k {
+                    let address = rng.gen_range(0..pages) * 4096 + rng.gen_range(0..1024) * 4;
+                    memory.set_memory(address, rng.gen()).unwrap();
+                }
+                memory.merkle_root().unwrap()
+            });
+        });
+    }
+}
+
+/// Compares the throughput of memory accesses spread over a working set of pages. Run with and
+/// without the `tlb` feature to compare the page caches.
+fn page_access(c: &mut Criterion) {
+    let mut g = c.benchmark_group("memory");
+    g.sample_size(10);
+
+    for pages in [2u32, 16, 64] {
+        g.bench_function(
+            format!("Random Access (1M words, working set = {pages} pages)"),
+            |b| {
+                let mut memory = Memory::default();
+                let mut data = vec![0u8; pages as usize * 4096];
+                rand::thread_rng().fill_bytes(&mut data[..]);
+                memory
+                    .set_memory_range(0, &data[..])
+                    .expect("Should not error");
+
+                let mut rng = rand::thread_rng();
+                let addresses = (0..1_000_000)
+                    .map(|_| rng.gen_range(0..pages * 1024) * 4)
+                    .collect::<Vec<_>>();
+                b.iter(|| {
+                    addresses
+                        .iter()
+                        .fold(0u32, |acc, &a| acc ^ memory.get_memory(a).unwrap())
+                });
+            },
+        );
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = merkle_root
+    targets = merkle_root, merkle_root_after_writes, page_access
 }
 criterion_main!(benches);
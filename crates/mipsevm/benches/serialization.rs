@@ -0,0 +1,93 @@
+use cannon_mipsevm::{ser::Codec, State};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use pprof::criterion::{Output, PProfProfiler};
+use rand::RngCore;
+use std::{fs, io::BufWriter};
+
+/// Creates a [State] with `size` bytes of random memory.
+fn random_state(size: usize) -> State {
+    let mut state = State::default();
+    let mut data = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut data[..]);
+    state
+        .memory
+        .set_memory_range(0, &data[..])
+        .expect("Should not error");
+    state
+}
+
+/// Measures the throughput of serializing and deserializing a [State] as JSON, with and without
+/// compression.
+fn json(c: &mut Criterion) {
+    let mut g = c.benchmark_group("serialization");
+    g.sample_size(10);
+
+    let state = random_state(8_000_000);
+    let json = serde_json::to_vec(&state).unwrap();
+    g.throughput(Throughput::Bytes(json.len() as u64));
+
+    g.bench_function("Serialize JSON (memory size = 8 MB)", |b| {
+        b.iter(|| serde_json::to_vec(&state).unwrap());
+    });
+
+    g.bench_function("Deserialize JSON (memory size = 8 MB)", |b| {
+        b.iter(|| State::from_json(&json).unwrap());
+    });
+
+    for codec in [Codec::Zlib, Codec::Gzip] {
+        let compressed = codec.compress(&json).unwrap();
+
+        g.bench_function(
+            format!("Compress JSON ({codec:?}, memory size = 8 MB)"),
+            |b| {
+                b.iter(|| codec.compress(&json).unwrap());
+            },
+        );
+
+        g.bench_function(
+            format!("Decompress JSON ({codec:?}, memory size = 8 MB)"),
+            |b| {
+                b.iter(|| codec.decompress(&compressed).unwrap());
+            },
+        );
+    }
+}
+
+/// Measures the throughput of writing a [State] in the binary state file format, and of loading it
+/// back. Pages of a loaded state are only read from the file on first access, so the load is
+/// measured together with the merkleization that touches every page.
+fn binary(c: &mut Criterion) {
+    let mut g = c.benchmark_group("serialization");
+    g.sample_size(10);
+
+    let mut state = random_state(8_000_000);
+    let mut raw = Vec::new();
+    state.write_binary(&mut raw).unwrap();
+    g.throughput(Throughput::Bytes(raw.len() as u64));
+
+    g.bench_function("Write Binary (memory size = 8 MB)", |b| {
+        b.iter_batched_ref(
+            || Vec::with_capacity(raw.len()),
+            |out| state.write_binary(BufWriter::new(out)).unwrap(),
+            BatchSize::LargeInput,
+        );
+    });
+
+    let path = std::env::temp_dir().join("cannon-bench-state.bin");
+    fs::write(&path, &raw).unwrap();
+    g.bench_function("Load Binary + Merkle Root (memory size = 8 MB)", |b| {
+        b.iter(|| {
+            // SAFETY: The file is not modified until the benchmark finishes.
+            let mut state = unsafe { State::load_mmapped(&path).unwrap() };
+            state.memory.merkle_root().unwrap()
+        });
+    });
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = json, binary
+}
+criterion_main!(benches);
@@ -2,23 +2,54 @@
 
 # This script is used to generate the bindings for the MIPS contracts in the
 # Optimism monorepo.
+#
+# The contracts are compiled at the commit pinned by the `optimism` submodule,
+# or at `$OPTIMISM_REV` if it is set. The hashes of the generated bytecode and
+# the commit are recorded in `manifest.json`, which the embedded bytecode is
+# checked against at runtime.
+
+set -euo pipefail
 
 # The current directory relative to the script.
 DIR="$( cd "$( dirname "${BASH_SOURCE[0]}" )" >/dev/null 2>&1 && pwd )"
 
+# The commit that the `optimism` submodule is pinned to, if any.
+PINNED=$(git -C "$DIR" ls-files --stage optimism | awk '{ print $2 }')
+if [ -z "${OPTIMISM_REV:-}" ] && [ -z "$PINNED" ]; then
+    echo "Error: The optimism submodule is not pinned to a commit. Set OPTIMISM_REV to the commit to compile at."
+    exit 1
+fi
+
 # Check if a folder with relative path `../optimism` exists and is not empty.
-# If it doesn't exist, install the submodules.
+# If it doesn't exist, install the submodule, or clone the monorepo if it is not pinned yet.
 if [ ! -d "$DIR/optimism"  ] || [ -z "$(ls -A $DIR/optimism)" ]; then
-    echo "Error: Optimism monorepo not present. Initializing submodules..."
-    git submodule update --init --recursive
+    if [ -n "$PINNED" ]; then
+        echo "Optimism monorepo not present. Initializing submodules..."
+        git -C "$DIR" submodule update --init --recursive optimism
+    else
+        echo "Optimism monorepo not present. Cloning..."
+        git clone --quiet https://github.com/ethereum-optimism/optimism "$DIR/optimism"
+    fi
 fi
 
-# Check if `forge` is installed
-if ! command -v forge &> /dev/null
-then
-    echo "Error: forge not found. Please install forge and try again."
-    exit
+# Check if `forge`, `cast` and `jq` are installed
+for tool in forge cast jq; do
+    if ! command -v $tool &> /dev/null
+    then
+        echo "Error: $tool not found. Please install $tool and try again."
+        exit 1
+    fi
+done
+
+# Check out the pinned commit of the Optimism monorepo.
+if [ -n "${OPTIMISM_REV:-}" ]; then
+    echo "Checking out Optimism monorepo at $OPTIMISM_REV..."
+    git -C "$DIR/optimism" fetch --quiet origin "$OPTIMISM_REV"
+    git -C "$DIR/optimism" checkout --quiet "$OPTIMISM_REV"
+    git -C "$DIR/optimism" submodule update --init --recursive
 fi
+REV=$(git -C "$DIR/optimism" rev-parse HEAD)
+echo "Compiling contracts at $REV..."
 
 CTB="$DIR/optimism/packages/contracts-bedrock"
 
@@ -33,10 +64,20 @@ MIPS_BIN=$(cat $MIPS_ARTIFACT | jq -r '.bytecode.object')
 PREIMAGE_DEPLOYED_BIN=$(cat $PREIMAGE_ARTIFACT | jq -r '.deployedBytecode.object')
 
 echo "Removing old bindings..."
-rm $DIR/*.bin
+rm -f $DIR/*.bin
 echo "Old bindings removed."
 
 echo -n "${MIPS_BIN:2}" > $DIR/mips_creation.bin
-echo -n "${PREIMAGE_DEPLOYED_BIN:2}" >> $DIR/preimage_oracle_deployed.bin
+echo -n "${PREIMAGE_DEPLOYED_BIN:2}" > $DIR/preimage_oracle_deployed.bin
+
+jq -n \
+    --arg rev "$REV" \
+    --arg mips "$(cast keccak "$MIPS_BIN")" \
+    --arg preimage "$(cast keccak "$PREIMAGE_DEPLOYED_BIN")" \
+    '{optimismRev: $rev, mipsCreationHash: $mips, preimageOracleDeployedHash: $preimage}' \
+    > $DIR/manifest.json
 
 echo "Bindings generated successfully."
+if [ "$REV" != "$PINNED" ]; then
+    echo "Pin the optimism submodule to $REV with \`git add optimism\` when committing the bindings."
+fi
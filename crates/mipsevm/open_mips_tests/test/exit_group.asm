@@ -4,6 +4,7 @@
     .global test
     .ent    test
 
+# expect: exit 1
 test:
   li $a0, 1
   li $v0, 4246
@@ -0,0 +1,340 @@
+//! This module contains the [AddressSpace], which tracks the mapped regions of the guest's address
+//! space to emulate the `mmap`, `munmap` and `brk` syscalls with Linux semantics.
+//!
+//! The `MIPS` contract only implements a monotonically growing heap pointer for `mmap`, and a
+//! fixed program break for `brk`. The [AddressSpace] is therefore an opt-in
+//! [extension](crate::State#extensions), enabled with [crate::State::enable_address_space].
+
+use crate::{
+    mips::instrumented::{MIPS_EINVAL, MIPS_ENOMEM},
+    page, Address,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `MAP_FIXED` flag for `mmap` on MIPS.
+pub(crate) const MAP_FIXED: u32 = 0x10;
+
+/// The initial program break. Matches the constant program break reported by the `MIPS` contract.
+pub(crate) const BRK_BASE: Address = 0x40000000;
+/// The end of the user portion of the address space (`kuseg`).
+pub(crate) const USER_END: u64 = 0x80000000;
+/// The size of the stack reservation below the stack pointer, matching Linux's default
+/// `RLIMIT_STACK`.
+pub(crate) const STACK_RESERVE: u64 = 8 << 20;
+
+/// Rounds the given value up to the next page boundary.
+#[inline(always)]
+fn page_align_up(value: u64) -> u64 {
+    (value + page::PAGE_ADDRESS_MASK as u64) & !(page::PAGE_ADDRESS_MASK as u64)
+}
+
+/// The [AddressSpace] is an interval map of the page-aligned regions mapped within the guest's
+/// address space, along with the guest's program break.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressSpace {
+    /// Map of region start addresses to their (exclusive) end addresses. Regions never overlap,
+    /// and adjacent regions are merged.
+    regions: BTreeMap<u64, u64>,
+    /// The lowest address that `mmap` places mappings at when no usable hint is given.
+    mmap_base: Address,
+    /// The current program break.
+    brk: Address,
+}
+
+impl AddressSpace {
+    /// Creates a new, empty [AddressSpace].
+    ///
+    /// ### Takes
+    /// - `mmap_base`: The lowest address that `mmap` places mappings at.
+    pub fn new(mmap_base: Address) -> Self {
+        Self {
+            regions: BTreeMap::default(),
+            mmap_base,
+            brk: BRK_BASE,
+        }
+    }
+
+    /// Returns the current program break.
+    pub fn brk(&self) -> Address {
+        self.brk
+    }
+
+    /// Returns an iterator over the mapped regions, as `(start, end)` pairs with an exclusive end.
+    pub fn regions(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.regions.iter().map(|(start, end)| (*start, *end))
+    }
+
+    /// Returns `true` if the given address lies within a mapped region.
+    pub fn is_mapped(&self, address: Address) -> bool {
+        !self.is_free(address as u64, address as u64 + 1)
+    }
+
+    /// Marks the given range as mapped. The range is expanded to page boundaries.
+    ///
+    /// ### Takes
+    /// - `start`: The start of the range.
+    /// - `end`: The exclusive end of the range.
+    pub fn reserve(&mut self, start: u64, end: u64) {
+        let start = start & !(page::PAGE_ADDRESS_MASK as u64);
+        let end = page_align_up(end).min(USER_END);
+        if start >= end {
+            return;
+        }
+
+        // Absorb all regions that overlap or touch the new region.
+        let (mut start, mut end) = (start, end);
+        let absorbed = self
+            .regions
+            .range(..=end)
+            .filter(|(_, e)| **e >= start)
+            .map(|(s, e)| (*s, *e))
+            .collect::<Vec<_>>();
+        for (s, e) in absorbed {
+            self.regions.remove(&s);
+            start = start.min(s);
+            end = end.max(e);
+        }
+        self.regions.insert(start, end);
+    }
+
+    /// Marks the given page-aligned range as unmapped, splitting any regions that straddle it.
+    ///
+    /// ### Takes
+    /// - `start`: The start of the range.
+    /// - `end`: The exclusive end of the range.
+    pub fn release(&mut self, start: u64, end: u64) {
+        let overlapping = self
+            .regions
+            .range(..end)
+            .filter(|(_, e)| **e > start)
+            .map(|(s, e)| (*s, *e))
+            .collect::<Vec<_>>();
+        for (s, e) in overlapping {
+            self.regions.remove(&s);
+            if s < start {
+                self.regions.insert(s, start);
+            }
+            if e > end {
+                self.regions.insert(end, e);
+            }
+        }
+    }
+
+    /// Emulates the `mmap` syscall for an anonymous mapping.
+    ///
+    /// ### Takes
+    /// - `hint`: The requested address of the mapping.
+    /// - `len`: The length of the mapping in bytes.
+    /// - `flags`: The `mmap` flags.
+    ///
+    /// ### Returns
+    /// - `Ok((addr, len))` containing the address and page-aligned length of the new mapping. The
+    ///   contents of the mapping must be zeroed by the caller.
+    /// - `Err(errno)` if the mapping could not be created.
+    pub fn mmap(&mut self, hint: Address, len: u32, flags: u32) -> Result<(Address, u32), u32> {
+        if len == 0 {
+            return Err(MIPS_EINVAL);
+        }
+        let len = page_align_up(len as u64);
+
+        let addr = if flags & MAP_FIXED != 0 {
+            if hint as usize & page::PAGE_ADDRESS_MASK != 0 {
+                return Err(MIPS_EINVAL);
+            }
+            if hint as u64 + len > USER_END {
+                return Err(MIPS_ENOMEM);
+            }
+            hint as u64
+        } else {
+            let hint = hint as u64 & !(page::PAGE_ADDRESS_MASK as u64);
+            if hint != 0 && hint + len <= USER_END && self.is_free(hint, hint + len) {
+                hint
+            } else {
+                self.find_gap(len, self.mmap_base as u64, BRK_BASE as u64)
+                    .ok_or(MIPS_ENOMEM)?
+            }
+        };
+
+        self.reserve(addr, addr + len);
+        Ok((addr as Address, len as u32))
+    }
+
+    /// Emulates the `munmap` syscall.
+    ///
+    /// ### Takes
+    /// - `addr`: The start of the range to unmap.
+    /// - `len`: The length of the range to unmap in bytes.
+    ///
+    /// ### Returns
+    /// - `Ok((addr, len))` containing the page-aligned range that was unmapped. The pages within
+    ///   it should be released by the caller.
+    /// - `Err(errno)` if the arguments are invalid.
+    pub fn munmap(&mut self, addr: Address, len: u32) -> Result<(Address, u32), u32> {
+        if addr as usize & page::PAGE_ADDRESS_MASK != 0 || len == 0 {
+            return Err(MIPS_EINVAL);
+        }
+        let len = page_align_up(len as u64);
+        if addr as u64 + len > USER_END {
+            return Err(MIPS_EINVAL);
+        }
+
+        self.release(addr as u64, addr as u64 + len);
+        Ok((addr, len as u32))
+    }
+
+    /// Emulates the `brk` syscall. As on Linux, a failed request leaves the program break
+    /// unchanged, and the current program break is always returned.
+    ///
+    /// ### Takes
+    /// - `addr`: The requested program break.
+    ///
+    /// ### Returns
+    /// - The new program break, and the page-aligned range that was released by shrinking the
+    ///   program break, if any.
+    pub fn set_brk(&mut self, addr: Address) -> (Address, Option<(Address, u32)>) {
+        if addr < BRK_BASE {
+            return (self.brk, None);
+        }
+
+        let old_end = page_align_up(self.brk as u64);
+        let new_end = page_align_up(addr as u64);
+        let mut released = None;
+        if new_end > old_end {
+            if new_end > USER_END || !self.is_free(old_end, new_end) {
+                return (self.brk, None);
+            }
+            self.reserve(old_end, new_end);
+        } else if new_end < old_end {
+            self.release(new_end, old_end);
+            released = Some((new_end as Address, (old_end - new_end) as u32));
+        }
+
+        self.brk = addr;
+        (self.brk, released)
+    }
+
+    /// Returns `true` if no mapped region overlaps the given range.
+    fn is_free(&self, start: u64, end: u64) -> bool {
+        self.regions
+            .range(..end)
+            .next_back()
+            .map_or(true, |(_, e)| *e <= start)
+    }
+
+    /// Finds the lowest gap of at least `len` bytes between `from` and `limit`.
+    fn find_gap(&self, len: u64, from: u64, limit: u64) -> Option<u64> {
+        let mut cursor = from;
+        for (start, end) in self.regions() {
+            if end <= cursor {
+                continue;
+            }
+            if start >= cursor + len {
+                break;
+            }
+            cursor = end;
+        }
+        (cursor + len <= limit).then_some(cursor)
+    }
+}
+
+/// Returns the range of the stack reservation for the given stack pointer.
+pub(crate) fn stack_reservation(sp: Address) -> (u64, u64) {
+    let top = page_align_up(sp as u64 + 1).min(USER_END);
+    (top.saturating_sub(STACK_RESERVE), USER_END)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PAGE: u32 = page::PAGE_SIZE as u32;
+
+    #[test]
+    fn mmap_places_mappings_in_gaps() {
+        let mut space = AddressSpace::new(0x20000000);
+
+        assert_eq!(space.mmap(0, 1, 0), Ok((0x20000000, PAGE)));
+        assert_eq!(
+            space.mmap(0, PAGE * 2, 0),
+            Ok((0x20000000 + PAGE, PAGE * 2))
+        );
+
+        // Unmapping the first mapping leaves a gap that is reused.
+        assert_eq!(space.munmap(0x20000000, PAGE), Ok((0x20000000, PAGE)));
+        assert!(!space.is_mapped(0x20000000));
+        assert_eq!(space.mmap(0, PAGE, 0), Ok((0x20000000, PAGE)));
+
+        // Gaps that are too small are skipped.
+        space.munmap(0x20000000, PAGE).unwrap();
+        assert_eq!(
+            space.mmap(0, PAGE * 2, 0),
+            Ok((0x20000000 + PAGE * 3, PAGE * 2))
+        );
+    }
+
+    #[test]
+    fn mmap_honors_hints_and_fixed() {
+        let mut space = AddressSpace::new(0x20000000);
+
+        assert_eq!(space.mmap(0x30000000, PAGE, 0), Ok((0x30000000, PAGE)));
+        // An occupied hint falls back to the first gap.
+        assert_eq!(space.mmap(0x30000000, PAGE, 0), Ok((0x20000000, PAGE)));
+        // MAP_FIXED replaces the existing mapping.
+        assert_eq!(
+            space.mmap(0x30000000, PAGE * 2, MAP_FIXED),
+            Ok((0x30000000, PAGE * 2))
+        );
+        assert_eq!(
+            space.regions().collect::<Vec<_>>(),
+            vec![(0x20000000, 0x20001000), (0x30000000, 0x30002000)]
+        );
+    }
+
+    #[test]
+    fn mmap_errors() {
+        let mut space = AddressSpace::new(0x20000000);
+
+        assert_eq!(space.mmap(0, 0, 0), Err(MIPS_EINVAL));
+        assert_eq!(space.mmap(0x30000004, PAGE, MAP_FIXED), Err(MIPS_EINVAL));
+        assert_eq!(
+            space.mmap(0x7FFFF000, PAGE * 2, MAP_FIXED),
+            Err(MIPS_ENOMEM)
+        );
+        assert_eq!(space.mmap(0, BRK_BASE, 0), Err(MIPS_ENOMEM));
+        assert_eq!(space.munmap(0x20000004, PAGE), Err(MIPS_EINVAL));
+    }
+
+    #[test]
+    fn munmap_splits_regions() {
+        let mut space = AddressSpace::new(0x20000000);
+        space.mmap(0, PAGE * 3, 0).unwrap();
+        space.munmap(0x20000000 + PAGE, PAGE).unwrap();
+        assert_eq!(
+            space.regions().collect::<Vec<_>>(),
+            vec![(0x20000000, 0x20001000), (0x20002000, 0x20003000)]
+        );
+    }
+
+    #[test]
+    fn brk_grows_and_shrinks() {
+        let mut space = AddressSpace::new(0x20000000);
+
+        assert_eq!(space.set_brk(0), (BRK_BASE, None));
+        assert_eq!(space.set_brk(BRK_BASE + 10), (BRK_BASE + 10, None));
+        assert!(space.is_mapped(BRK_BASE));
+        assert_eq!(
+            space.set_brk(BRK_BASE + PAGE * 2),
+            (BRK_BASE + PAGE * 2, None)
+        );
+        assert_eq!(
+            space.set_brk(BRK_BASE),
+            (BRK_BASE, Some((BRK_BASE, PAGE * 2)))
+        );
+
+        // Growing into an existing mapping fails, leaving the break unchanged.
+        space.mmap(BRK_BASE + PAGE, PAGE, MAP_FIXED).unwrap();
+        assert_eq!(space.set_brk(BRK_BASE + PAGE * 4), (BRK_BASE, None));
+    }
+}
@@ -0,0 +1,182 @@
+//! This module contains the [AuxVector], the ELF auxiliary vector that is passed to the guest on
+//! its initial stack by [crate::patch_stack_with_args].
+//!
+//! The Go and Rust runtimes read the auxiliary vector at startup, e.g. to locate the program
+//! headers or to seed their hash maps. A realistic vector lets unpatched runtimes start without
+//! falling back to defaults.
+
+use crate::page;
+use alloy_primitives::keccak256;
+use anyhow::Result;
+use elf::{
+    abi::{PT_LOAD, PT_PHDR},
+    endian::AnyEndian,
+    ElfBytes,
+};
+
+/// The end of the auxiliary vector.
+pub const AT_NULL: u32 = 0;
+/// The address of the program headers.
+pub const AT_PHDR: u32 = 3;
+/// The size of a program header.
+pub const AT_PHENT: u32 = 4;
+/// The number of program headers.
+pub const AT_PHNUM: u32 = 5;
+/// The page size.
+pub const AT_PAGESZ: u32 = 6;
+/// The base address of the interpreter; zero for static executables.
+pub const AT_BASE: u32 = 7;
+/// The flags of the interpreter.
+pub const AT_FLAGS: u32 = 8;
+/// The entry point of the program.
+pub const AT_ENTRY: u32 = 9;
+/// The real user ID.
+pub const AT_UID: u32 = 11;
+/// The effective user ID.
+pub const AT_EUID: u32 = 12;
+/// The real group ID.
+pub const AT_GID: u32 = 13;
+/// The effective group ID.
+pub const AT_EGID: u32 = 14;
+/// The hardware capabilities of the CPU.
+pub const AT_HWCAP: u32 = 16;
+/// The frequency of `times`.
+pub const AT_CLKTCK: u32 = 17;
+/// Whether the program runs in secure mode.
+pub const AT_SECURE: u32 = 23;
+/// The address of 16 random bytes.
+pub const AT_RANDOM: u32 = 25;
+
+/// The random bytes that the stack patch has always passed to the guest.
+const DEFAULT_RANDOM: [u8; 16] = *b"4;byfairdiceroll";
+
+/// The [AuxVector] holds the entries of the ELF auxiliary vector and the bytes that `AT_RANDOM`
+/// points to. The `AT_RANDOM` and `AT_NULL` entries are appended when the vector is laid out on
+/// the stack, as only then is the address of the random bytes known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuxVector {
+    /// The key-value pairs of the vector, in order.
+    pub entries: Vec<(u32, u32)>,
+    /// The 16 bytes that `AT_RANDOM` points to.
+    pub random: [u8; 16],
+}
+
+impl Default for AuxVector {
+    /// The minimal vector of the stack patch, only holding the page size and the random bytes.
+    fn default() -> Self {
+        Self {
+            entries: vec![(AT_PAGESZ, page::PAGE_SIZE as u32)],
+            random: DEFAULT_RANDOM,
+        }
+    }
+}
+
+impl AuxVector {
+    /// Creates a realistic [AuxVector] for a static executable, as the Linux kernel would pass it.
+    /// The random bytes are derived from the ELF file, so that the guest sees the same bytes on
+    /// every load.
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [AuxVector], or an error if the ELF file cannot be parsed.
+    pub fn from_elf(raw: &[u8]) -> Result<Self> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let headers = elf
+            .segments()
+            .ok_or(anyhow::anyhow!("Failed to load section headers"))?;
+
+        // The program headers are mapped either by their own segment or as part of the first
+        // loaded segment, which usually starts at the beginning of the file.
+        let phoff = elf.ehdr.e_phoff;
+        let phdr = headers
+            .iter()
+            .find(|header| header.p_type == PT_PHDR)
+            .map(|header| header.p_vaddr)
+            .or_else(|| {
+                headers
+                    .iter()
+                    .find(|header| {
+                        header.p_type == PT_LOAD
+                            && (header.p_offset..header.p_offset + header.p_filesz).contains(&phoff)
+                    })
+                    .map(|header| header.p_vaddr + phoff - header.p_offset)
+            });
+
+        let mut entries = Vec::with_capacity(16);
+        if let Some(phdr) = phdr {
+            entries.push((AT_PHDR, phdr as u32));
+        }
+        entries.extend([
+            (AT_PHENT, elf.ehdr.e_phentsize as u32),
+            (AT_PHNUM, elf.ehdr.e_phnum as u32),
+            (AT_PAGESZ, page::PAGE_SIZE as u32),
+            (AT_BASE, 0),
+            (AT_FLAGS, 0),
+            (AT_ENTRY, elf.ehdr.e_entry as u32),
+            (AT_UID, 0),
+            (AT_EUID, 0),
+            (AT_GID, 0),
+            (AT_EGID, 0),
+            (AT_HWCAP, 0),
+            (AT_CLKTCK, 100),
+            (AT_SECURE, 0),
+        ]);
+
+        let mut random = [0u8; 16];
+        random.copy_from_slice(&keccak256(raw)[..16]);
+        Ok(Self { entries, random })
+    }
+
+    /// Sets the 16 bytes that `AT_RANDOM` points to.
+    pub fn with_random(mut self, random: [u8; 16]) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Returns the value of the entry with the given key, if present.
+    pub fn get(&self, key: u32) -> Option<u32> {
+        self.entries
+            .iter()
+            .find_map(|(k, v)| (*k == key).then_some(*v))
+    }
+
+    /// Returns the number of words that the vector takes on the stack, including the `AT_RANDOM`
+    /// and `AT_NULL` entries.
+    pub fn stack_words(&self) -> usize {
+        2 * (self.entries.len() + 2)
+    }
+
+    /// Returns the words of the vector as laid out on the stack, with `AT_RANDOM` pointing to the
+    /// given address.
+    pub fn to_words(&self, random_ptr: u32) -> Vec<u32> {
+        self.entries
+            .iter()
+            .chain([&(AT_RANDOM, random_ptr), &(AT_NULL, 0)])
+            .flat_map(|(key, value)| [*key, *value])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn auxv_from_elf() {
+        let raw = include_bytes!("../../../example/bin/hello.elf");
+        let auxv = AuxVector::from_elf(raw).unwrap();
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw).unwrap();
+
+        assert_eq!(auxv.get(AT_ENTRY), Some(elf.ehdr.e_entry as u32));
+        assert_eq!(auxv.get(AT_PHNUM), Some(elf.ehdr.e_phnum as u32));
+        assert_eq!(auxv.get(AT_PAGESZ), Some(page::PAGE_SIZE as u32));
+        assert!(auxv.get(AT_PHDR).is_some());
+        assert_ne!(auxv.random, DEFAULT_RANDOM);
+
+        let words = auxv.to_words(0x1234);
+        assert_eq!(words.len(), auxv.stack_words());
+        assert_eq!(words[words.len() - 4..], [AT_RANDOM, 0x1234, AT_NULL, 0]);
+    }
+}
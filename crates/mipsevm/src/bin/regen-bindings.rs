@@ -0,0 +1,27 @@
+//! Regenerates the contract bindings in `bindings/` by running `bindings/bindings.sh`, which
+//! compiles the contracts at the commit pinned by the `optimism` submodule, or at `OPTIMISM_REV`
+//! if it is set, and records the commit in `bindings/manifest.json`.
+//!
+//! Run with `cargo run -p cannon-mipsevm --bin regen-bindings`.
+
+use std::{
+    path::Path,
+    process::{Command, ExitCode},
+};
+
+fn main() -> ExitCode {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("bindings")
+        .join("bindings.sh");
+    match Command::new("bash").arg(&script).status() {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(status) => {
+            eprintln!("{} failed with {}", script.display(), status);
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("Failed to run {}: {}", script.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
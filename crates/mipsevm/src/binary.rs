@@ -0,0 +1,221 @@
+//! This module contains the binary state file format, which lays out the pages of a [State] so that
+//! the file can be memory-mapped and its pages materialized lazily on first access.
+//!
+//! The file starts with [BINARY_STATE_MAGIC] and the big-endian format version, followed by the
+//! length-prefixed JSON of the [State] without its memory, the number of pages, and the index of
+//! each page. The page data follows, in the same order, starting at the next page-aligned offset.
+
+use crate::{page, PageIndex, State, STATE_SCHEMA};
+use anyhow::{bail, Result};
+use memmap2::Mmap;
+use rustc_hash::FxHashMap;
+use std::{fs::File, io::Write, path::Path, rc::Rc};
+
+/// The magic bytes at the start of a binary state file.
+pub const BINARY_STATE_MAGIC: [u8; 4] = *b"CNST";
+
+/// The current version of the binary state file format.
+pub const BINARY_STATE_VERSION: u32 = 1;
+
+/// Writes a [State] in the binary state file format.
+///
+/// ### Takes
+/// - `state`: The [State] to write.
+/// - `writer`: The writer to write the binary state file to.
+///
+/// ### Returns
+/// - A [Result] indicating whether the state was written successfully.
+pub(crate) fn write<W: Write>(state: &mut State, mut writer: W) -> Result<()> {
+    // Serialize the state without its memory, which is written page by page below.
+    let memory = std::mem::take(&mut state.memory);
+    let header = serde_json::to_vec(state);
+    state.memory = memory;
+    let header = header?;
+
+    let mut indices = state.memory.page_indices().collect::<Vec<_>>();
+    indices.sort_unstable();
+
+    writer.write_all(&BINARY_STATE_MAGIC)?;
+    writer.write_all(&BINARY_STATE_VERSION.to_be_bytes())?;
+    writer.write_all(&(header.len() as u64).to_be_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(&(indices.len() as u64).to_be_bytes())?;
+    for index in &indices {
+        writer.write_all(&index.to_be_bytes())?;
+    }
+
+    let table_end = 24 + header.len() + indices.len() * 8;
+    writer.write_all(&vec![0u8; data_start(table_end) - table_end])?;
+    state.memory.visit_pages(|_, data| writer.write_all(data))?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Loads a [State] from a binary state file by memory-mapping it. Only the [State]'s header is
+/// parsed upfront; pages are copied out of the file on first access.
+///
+/// ### Safety
+/// The file must not be truncated or modified in place while the returned [State] is alive. See
+/// [State::load_mmapped].
+///
+/// ### Takes
+/// - `path`: The path of the binary state file.
+///
+/// ### Returns
+/// - A [Result] containing the loaded [State].
+pub(crate) unsafe fn load_mmapped(path: impl AsRef<Path>) -> Result<State> {
+    let file = File::open(path)?;
+    // SAFETY: The mapping is read-only, and the caller guarantees that the file is not modified
+    // while it is mapped.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    let read_u64 = |offset: usize| -> Result<u64> {
+        match mmap.get(offset..offset + 8) {
+            Some(bytes) => Ok(u64::from_be_bytes(bytes.try_into()?)),
+            None => bail!("Binary state file truncated at offset {}", offset),
+        }
+    };
+
+    if mmap.get(..4) != Some(BINARY_STATE_MAGIC.as_slice()) {
+        bail!("Not a binary state file");
+    }
+    let version = u32::from_be_bytes(mmap.get(4..8).unwrap_or_default().try_into()?);
+    if version != BINARY_STATE_VERSION {
+        bail!(
+            "Unsupported binary state file version {}, supported versions: {}",
+            version,
+            BINARY_STATE_VERSION
+        );
+    }
+
+    let header_len = read_u64(8)? as usize;
+    let Some(header) = mmap.get(16..16 + header_len) else {
+        bail!("Binary state file truncated in header");
+    };
+    let mut state: State = STATE_SCHEMA.from_slice(header)?;
+
+    let table_start = 24 + header_len;
+    let page_count = read_u64(16 + header_len)? as usize;
+    if page_count > mmap.len() / page::PAGE_SIZE {
+        bail!(
+            "Binary state file truncated: expected {} pages of data",
+            page_count
+        );
+    }
+    let pages_start = data_start(table_start + page_count * 8);
+    if mmap.len() < pages_start + page_count * page::PAGE_SIZE {
+        bail!(
+            "Binary state file truncated: expected {} pages of data",
+            page_count
+        );
+    }
+
+    let mut offsets = FxHashMap::default();
+    for i in 0..page_count {
+        let index: PageIndex = read_u64(table_start + i * 8)?;
+        if offsets
+            .insert(index, pages_start + i * page::PAGE_SIZE)
+            .is_some()
+        {
+            bail!(
+                "cannot load duplicate page, entry {}, page index {}",
+                i,
+                index
+            );
+        }
+    }
+    state.memory.register_lazy_pages(Rc::new(mmap), offsets);
+
+    Ok(state)
+}
+
+/// Returns the page-aligned offset that page data starts at, given the end of the page table.
+fn data_start(table_end: usize) -> usize {
+    table_end.next_multiple_of(page::PAGE_SIZE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn binary_roundtrip() {
+        let mut state = State {
+            pc: 0x1000,
+            step: 42,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+        state.memory.set_memory(0x7FFF_0000, 0xCAFEBABE).unwrap();
+        state.memory.alloc_page(0x2000).unwrap();
+        let witness = state.encode_witness().unwrap();
+
+        let path = std::env::temp_dir().join("cannon_binary_roundtrip.bin");
+        state.save_binary(&path).unwrap();
+
+        // SAFETY: The file is not modified while the state is alive.
+        let mut loaded = unsafe { State::load_mmapped(&path).unwrap() };
+        assert_eq!(loaded.memory.page_count(), 3);
+        assert!(loaded.memory.pages.is_empty());
+
+        // Pages are materialized on first access.
+        assert_eq!(loaded.memory.get_memory(0x1000).unwrap(), 0xDEADBEEF);
+        assert_eq!(loaded.memory.pages.len(), 1);
+        assert_eq!(loaded.memory.page_count(), 3);
+
+        assert_eq!(loaded.encode_witness().unwrap(), witness);
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&state).unwrap()
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_truncated() {
+        let mut state = State::default();
+        state.memory.set_memory(0x1000, 1).unwrap();
+        let mut raw = Vec::new();
+        write(&mut state, &mut raw).unwrap();
+
+        let path = std::env::temp_dir().join("cannon_binary_truncated.bin");
+        // SAFETY: The loads fail, so no state outlives the writes to the file.
+        std::fs::write(&path, &raw[..raw.len() - 1]).unwrap();
+        assert!(unsafe { State::load_mmapped(&path) }.is_err());
+        std::fs::write(&path, b"{}").unwrap();
+        assert!(unsafe { State::load_mmapped(&path) }.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn save_over_mmapped_source() {
+        let mut state = State::default();
+        for page in 0..4 {
+            state
+                .memory
+                .set_memory(page << 12, page as u32 + 1)
+                .unwrap();
+        }
+        let witness = state.encode_witness().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.bin");
+        state.save_binary(&path).unwrap();
+
+        // Writing a lazily loaded state back to the file it was mapped from replaces the file
+        // rather than truncating the mapping, so the pages that were not yet materialized can
+        // still be read.
+        // SAFETY: The file is only ever replaced by renaming over it.
+        let mut loaded = unsafe { State::load_mmapped(&path).unwrap() };
+        assert!(loaded.memory.pages.is_empty());
+        loaded.save_binary(&path).unwrap();
+        assert_eq!(loaded.encode_witness().unwrap(), witness);
+
+        // SAFETY: As above.
+        let mut reloaded = unsafe { State::load_mmapped(&path).unwrap() };
+        assert_eq!(reloaded.encode_witness().unwrap(), witness);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! This module contains the [Clock], which serves the `clock_gettime` and `gettimeofday` syscalls
+//! from the step counter so that guests using timeouts and timers execute deterministically.
+//!
+//! The `MIPS` contract does not implement the clock syscalls, so the [Clock] is an opt-in
+//! [extension](crate::State#extensions), enabled with [crate::State::enable_clock].
+
+use serde::{Deserialize, Serialize};
+
+/// The default number of nanoseconds of guest time that pass per step.
+pub const DEFAULT_NANOS_PER_STEP: u64 = 10;
+
+/// `CLOCK_REALTIME`
+const CLOCK_REALTIME: u32 = 0;
+/// `CLOCK_MONOTONIC`
+const CLOCK_MONOTONIC: u32 = 1;
+/// `CLOCK_PROCESS_CPUTIME_ID`
+const CLOCK_PROCESS_CPUTIME_ID: u32 = 2;
+/// `CLOCK_THREAD_CPUTIME_ID`
+const CLOCK_THREAD_CPUTIME_ID: u32 = 3;
+/// `CLOCK_MONOTONIC_RAW`
+const CLOCK_MONOTONIC_RAW: u32 = 4;
+/// `CLOCK_REALTIME_COARSE`
+const CLOCK_REALTIME_COARSE: u32 = 5;
+/// `CLOCK_MONOTONIC_COARSE`
+const CLOCK_MONOTONIC_COARSE: u32 = 6;
+/// `CLOCK_BOOTTIME`
+const CLOCK_BOOTTIME: u32 = 7;
+
+/// The [Clock] derives the guest's time from the step counter: every step advances all clocks by
+/// a fixed number of nanoseconds. The monotonic clocks start at zero, while the real-time clocks
+/// start at a fixed epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clock {
+    /// The number of nanoseconds of guest time that pass per step.
+    pub nanos_per_step: u64,
+    /// The real time at step zero, in nanoseconds since the Unix epoch.
+    pub epoch_nanos: u64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(DEFAULT_NANOS_PER_STEP)
+    }
+}
+
+impl Clock {
+    /// Creates a new [Clock] that advances by `nanos_per_step` nanoseconds per step, with its
+    /// real-time clocks starting at the Unix epoch.
+    pub fn new(nanos_per_step: u64) -> Self {
+        Self {
+            nanos_per_step,
+            epoch_nanos: 0,
+        }
+    }
+
+    /// Sets the real time at step zero, in seconds since the Unix epoch.
+    pub fn with_epoch(mut self, epoch_secs: u64) -> Self {
+        self.epoch_nanos = epoch_secs.saturating_mul(1_000_000_000);
+        self
+    }
+
+    /// Returns the time of the given clock at the given step, in nanoseconds.
+    ///
+    /// ### Takes
+    /// - `clock_id`: The `clockid_t` of the clock to read.
+    /// - `step`: The current step of the emulator.
+    ///
+    /// ### Returns
+    /// - `Some(nanos)` containing the time of the clock.
+    /// - `None` if the clock is not supported.
+    pub fn now(&self, clock_id: u32, step: u64) -> Option<u64> {
+        let elapsed = step.saturating_mul(self.nanos_per_step);
+        match clock_id {
+            CLOCK_REALTIME | CLOCK_REALTIME_COARSE => {
+                Some(self.epoch_nanos.saturating_add(elapsed))
+            }
+            CLOCK_MONOTONIC
+            | CLOCK_PROCESS_CPUTIME_ID
+            | CLOCK_THREAD_CPUTIME_ID
+            | CLOCK_MONOTONIC_RAW
+            | CLOCK_MONOTONIC_COARSE
+            | CLOCK_BOOTTIME => Some(elapsed),
+            _ => None,
+        }
+    }
+
+    /// Encodes the given time as a 32-bit `struct timespec`.
+    pub(crate) fn timespec(nanos: u64) -> [u8; 8] {
+        let mut out = [0u8; 8];
+        out[..4].copy_from_slice(&((nanos / 1_000_000_000) as u32).to_be_bytes());
+        out[4..].copy_from_slice(&((nanos % 1_000_000_000) as u32).to_be_bytes());
+        out
+    }
+
+    /// Encodes the given time as a `struct __kernel_timespec`, with 64-bit fields.
+    pub(crate) fn timespec64(nanos: u64) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        out[..8].copy_from_slice(&(nanos / 1_000_000_000).to_be_bytes());
+        out[8..].copy_from_slice(&(nanos % 1_000_000_000).to_be_bytes());
+        out
+    }
+
+    /// Encodes the given time as a 32-bit `struct timeval`.
+    pub(crate) fn timeval(nanos: u64) -> [u8; 8] {
+        let mut out = [0u8; 8];
+        out[..4].copy_from_slice(&((nanos / 1_000_000_000) as u32).to_be_bytes());
+        out[4..].copy_from_slice(&((nanos % 1_000_000_000 / 1_000) as u32).to_be_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::StaticOracle, InstrumentedState, State};
+    use std::io;
+
+    #[test]
+    fn clocks() {
+        let clock = Clock::new(250).with_epoch(1_700_000_000);
+        assert_eq!(clock.now(CLOCK_MONOTONIC, 4_000_001), Some(1_000_000_250));
+        assert_eq!(
+            clock.now(CLOCK_REALTIME, 4),
+            Some(1_700_000_000_000_001_000)
+        );
+        assert_eq!(clock.now(12, 4), None);
+
+        assert_eq!(Clock::timespec(1_000_000_250), [0, 0, 0, 1, 0, 0, 0, 250]);
+        assert_eq!(Clock::timeval(1_000_002_250), [0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(Clock::timespec64(1_000_000_250)[..8], 1u64.to_be_bytes());
+    }
+
+    #[test]
+    fn clock_syscalls() {
+        // 0x1000: syscall; 0x1004: syscall
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            step: 99,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0x0000000C).unwrap();
+        state.memory.set_memory(0x1004, 0x0000000C).unwrap();
+        state.enable_clock(Clock::new(1_000));
+
+        // clock_gettime(CLOCK_MONOTONIC, 0x2000)
+        state.registers[2] = 4263;
+        state.registers[4] = CLOCK_MONOTONIC;
+        state.registers[5] = 0x2000;
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        ins.step(false).unwrap();
+        assert_eq!(ins.state.registers[2], 0);
+        assert_eq!(ins.state.memory.get_memory(0x2000).unwrap(), 0);
+        assert_eq!(ins.state.memory.get_memory(0x2004).unwrap(), 100_000);
+
+        // Unsupported clocks fail with EINVAL.
+        ins.state.registers[2] = 4263;
+        ins.state.registers[4] = 12;
+        ins.step(false).unwrap();
+        assert_eq!(ins.state.registers[2], 0xFFFFFFFF);
+        assert_eq!(ins.state.registers[7], 0x16);
+    }
+}
@@ -0,0 +1,330 @@
+//! This module contains the [CompatReport], a best-effort static scan of an ELF file for features
+//! that the emulator does not support, so that incompatible programs are caught before they are
+//! run rather than billions of steps in.
+//!
+//! The scan is conservative in what it reports: every word of the executable sections is decoded,
+//! including data embedded in them and code that is never reached, so findings may be false
+//! positives. The syscall numbers are only resolved where they are loaded as a constant right
+//! before the `syscall` instruction.
+
+use crate::{
+    disasm::{describe_fields, is_floating_point, mnemonic},
+    types::Syscall,
+    Address, Metadata,
+};
+use anyhow::Result;
+use elf::{
+    abi::{EM_MIPS, PT_DYNAMIC, PT_INTERP, PT_LOAD, PT_TLS, SHT_PROGBITS},
+    endian::AnyEndian,
+    file::Class,
+    ElfBytes,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+/// The `p_flags` bit of an ELF segment that marks it as executable.
+const PF_X: u32 = 0x1;
+/// The `sh_flags` bit of an ELF section that marks it as containing instructions.
+const SHF_EXECINSTR: u64 = 0x4;
+
+/// The mnemonics of the MIPS32r2 instructions, which are only executed by states with
+/// [crate::State::enable_mips32r2].
+pub(crate) const MIPS32R2_MNEMONICS: [&str; 7] =
+    ["seb", "seh", "wsbh", "ext", "ins", "rotr", "rotrv"];
+
+/// The number of instructions before a `syscall` that are searched for the load of its number.
+const SYSCALL_LOOKBEHIND: usize = 4;
+
+/// The maximum number of findings per category that are listed by the [fmt::Display] of a
+/// [CompatReport].
+const MAX_LISTED: usize = 10;
+
+/// A [Finding] of the scan, locating an instruction in the program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// The address of the instruction.
+    pub address: Address,
+    /// The instruction word.
+    pub instruction: u32,
+    /// The name of the symbol containing the instruction.
+    pub symbol: String,
+    /// A description of the finding.
+    pub detail: String,
+}
+
+/// The [CompatReport] of an ELF file, listing the features that the emulator does not support.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CompatReport {
+    /// Problems with the ELF file itself that prevent it from being loaded or run, such as a
+    /// wrong architecture or dynamic linking.
+    pub errors: Vec<String>,
+    /// Problems that may or may not surface at runtime, such as thread-local storage.
+    pub warnings: Vec<String>,
+    /// The number of instruction words that were scanned.
+    pub instructions: usize,
+    /// Instructions that the emulator does not support.
+    pub unsupported: Vec<Finding>,
+    /// Floating-point instructions, which the emulator does not support.
+    pub floating_point: Vec<Finding>,
+    /// MIPS32r2 instructions, which are only supported with `--mips32r2`.
+    pub mips32r2: Vec<Finding>,
+    /// The resolved syscall numbers, with the number of call sites each.
+    pub syscalls: BTreeMap<u32, usize>,
+    /// Call sites of syscalls that the emulator ignores, returning `0` without effect.
+    pub unknown_syscalls: Vec<Finding>,
+    /// The number of call sites whose syscall number could not be resolved statically.
+    pub dynamic_syscalls: usize,
+}
+
+impl CompatReport {
+    /// Scans an ELF file for features that the emulator does not support.
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [CompatReport], or an error if the ELF file cannot be parsed.
+    pub fn from_elf(raw: &[u8]) -> Result<Self> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let mut report = Self::default();
+
+        if elf.ehdr.class != Class::ELF32 {
+            report.errors.push("Not a 32-bit ELF file".to_string());
+        }
+        if elf.ehdr.e_machine != EM_MIPS {
+            report.errors.push(format!(
+                "Not a MIPS ELF file (e_machine = {})",
+                elf.ehdr.e_machine
+            ));
+        }
+        if !matches!(elf.ehdr.endianness, AnyEndian::Big) {
+            report.errors.push("Not a big-endian ELF file".to_string());
+        }
+        if !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        let segments = elf
+            .segments()
+            .ok_or(anyhow::anyhow!("Failed to load section headers"))?;
+        if segments
+            .iter()
+            .any(|segment| matches!(segment.p_type, PT_INTERP | PT_DYNAMIC))
+        {
+            report
+                .errors
+                .push("Dynamically linked; only static executables can be loaded".to_string());
+        }
+        if segments.iter().any(|segment| segment.p_type == PT_TLS) {
+            report.warnings.push(
+                "Uses thread-local storage (PT_TLS); accessing it with `rdhwr` is not supported"
+                    .to_string(),
+            );
+        }
+
+        let entry = elf.ehdr.e_entry;
+        let code = segments
+            .iter()
+            .filter(|segment| segment.p_type == PT_LOAD && segment.p_flags & PF_X != 0)
+            .collect::<Vec<_>>();
+        if !code
+            .iter()
+            .any(|segment| (segment.p_vaddr..segment.p_vaddr + segment.p_memsz).contains(&entry))
+        {
+            report.errors.push(format!(
+                "The entry point 0x{:08x} is not in an executable segment",
+                entry
+            ));
+        }
+
+        // Prefer the executable sections, which exclude the headers and read-only data that share
+        // the executable segment, and fall back to the segments for stripped files.
+        let mut regions = Vec::new();
+        if let Some(sections) = elf.section_headers() {
+            for section in sections.iter().filter(|section| {
+                section.sh_type == SHT_PROGBITS && section.sh_flags & SHF_EXECINSTR != 0
+            }) {
+                regions.push((section.sh_addr, elf.section_data(&section)?.0));
+            }
+        }
+        if regions.is_empty() {
+            for segment in code {
+                let data = &elf.segment_data(&segment)?[..segment.p_filesz as usize];
+                regions.push((segment.p_vaddr, data));
+            }
+        }
+
+        let metadata = Metadata::from_elf(raw).unwrap_or_default();
+        for (address, data) in regions {
+            let words = data
+                .chunks_exact(4)
+                .map(|word| u32::from_be_bytes(word.try_into().expect("Word is 4 bytes")))
+                .collect::<Vec<_>>();
+            report.scan(&words, address as Address, &metadata);
+        }
+
+        if !report.floating_point.is_empty() {
+            report.warnings.push(
+                "Contains floating-point instructions; build Go guests with GOMIPS=softfloat"
+                    .to_string(),
+            );
+        }
+        Ok(report)
+    }
+
+    /// Scans the instruction words of an executable region starting at the given address.
+    fn scan(&mut self, words: &[u32], base: Address, metadata: &Metadata) {
+        let finding = |i: usize, detail: String| {
+            let address = base + 4 * i as Address;
+            Finding {
+                address,
+                instruction: words[i],
+                symbol: metadata.lookup_symbol(address).to_string(),
+                detail,
+            }
+        };
+
+        self.instructions += words.len();
+        for (i, &instruction) in words.iter().enumerate() {
+            if is_floating_point(instruction) {
+                self.floating_point
+                    .push(finding(i, describe_fields(instruction)));
+                continue;
+            }
+            match mnemonic(instruction) {
+                None => self
+                    .unsupported
+                    .push(finding(i, describe_fields(instruction))),
+                Some(name) if MIPS32R2_MNEMONICS.contains(&name) => {
+                    self.mips32r2.push(finding(i, name.to_string()))
+                }
+                Some("syscall") => match syscall_number(&words[..i]) {
+                    Some(number) if Syscall::try_from(number).is_ok() => {
+                        *self.syscalls.entry(number).or_default() += 1;
+                    }
+                    Some(number) => {
+                        *self.syscalls.entry(number).or_default() += 1;
+                        self.unknown_syscalls
+                            .push(finding(i, format!("syscall {}", number)));
+                    }
+                    None => self.dynamic_syscalls += 1,
+                },
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Returns whether the program is expected to run, i.e. whether there are no errors and no
+    /// unsupported instructions.
+    ///
+    /// ### Takes
+    /// - `mips32r2`: Whether the MIPS32r2 instructions are supported.
+    pub fn is_compatible(&self, mips32r2: bool) -> bool {
+        self.errors.is_empty()
+            && self.unsupported.is_empty()
+            && self.floating_point.is_empty()
+            && (mips32r2 || self.mips32r2.is_empty())
+    }
+}
+
+/// Resolves the number of a syscall from the instructions preceding it, if `$v0` is loaded with
+/// a constant (`addiu $v0, $zero, n` or `ori $v0, $zero, n`) right before it.
+fn syscall_number(preceding: &[u32]) -> Option<u32> {
+    for &instruction in preceding.iter().rev().take(SYSCALL_LOOKBEHIND) {
+        let opcode = instruction >> 26;
+        let rs = (instruction >> 21) & 0x1F;
+        let rt = (instruction >> 16) & 0x1F;
+        let rd = (instruction >> 11) & 0x1F;
+        match opcode {
+            0x09 if rt == 2 && rs == 0 => return Some(instruction as i16 as i32 as u32),
+            0x0D if rt == 2 && rs == 0 => return Some(instruction & 0xFFFF),
+            // Any other write to `$v0` makes the number dynamic.
+            0x00 if rd == 2 => return None,
+            0x01..=0x07 => return None,
+            _ if opcode != 0x00 && opcode < 0x28 && rt == 2 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list(f: &mut fmt::Formatter<'_>, title: &str, findings: &[Finding]) -> fmt::Result {
+            if findings.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{} ({}):", title, findings.len())?;
+            for finding in findings.iter().take(MAX_LISTED) {
+                writeln!(
+                    f,
+                    "  0x{:08x}  {:08x}  {:<24} in {}",
+                    finding.address, finding.instruction, finding.detail, finding.symbol
+                )?;
+            }
+            if findings.len() > MAX_LISTED {
+                writeln!(f, "  ... and {} more", findings.len() - MAX_LISTED)?;
+            }
+            Ok(())
+        }
+
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        writeln!(f, "Scanned {} instruction words", self.instructions)?;
+        list(f, "Unsupported instructions", &self.unsupported)?;
+        list(f, "Floating-point instructions", &self.floating_point)?;
+        list(
+            f,
+            "MIPS32r2 instructions (require --mips32r2)",
+            &self.mips32r2,
+        )?;
+        list(f, "Ignored syscalls", &self.unknown_syscalls)?;
+        if !self.syscalls.is_empty() {
+            let syscalls = self
+                .syscalls
+                .iter()
+                .map(|(number, count)| format!("{} (x{})", number, count))
+                .collect::<Vec<_>>();
+            writeln!(f, "Syscalls: {}", syscalls.join(", "))?;
+        }
+        if self.dynamic_syscalls > 0 {
+            writeln!(
+                f,
+                "{} syscall sites with numbers unknown until runtime",
+                self.dynamic_syscalls
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hello_is_compatible() {
+        let report =
+            CompatReport::from_elf(include_bytes!("../../../example/bin/hello.elf")).unwrap();
+        assert!(report.is_compatible(false), "{report}");
+        assert!(report.errors.is_empty(), "{report}");
+        assert!(report.instructions > 0);
+        assert!(report.floating_point.is_empty(), "{report}");
+        assert!(report.syscalls.contains_key(&4246) || report.dynamic_syscalls > 0);
+    }
+
+    #[test]
+    fn resolves_syscall_numbers() {
+        // addiu $v0, $zero, 4004; addiu $a0, $zero, 1
+        assert_eq!(syscall_number(&[0x24020FA4, 0x24040001]), Some(4004));
+        // ori $v0, $zero, 4999
+        assert_eq!(syscall_number(&[0x34021387]), Some(4999));
+        // lw $v0, 4($sp)
+        assert_eq!(syscall_number(&[0x8FA20004]), None);
+        assert_eq!(syscall_number(&[]), None);
+    }
+}
@@ -0,0 +1,212 @@
+//! This module contains the [Coverage] bitmap, which records the guest program counters executed
+//! by the [crate::InstrumentedState], and its exporters.
+
+use crate::{page, Address, Metadata};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use std::io::Write;
+
+/// The number of instructions within a page.
+const INSTRUCTIONS_PER_PAGE: usize = page::PAGE_SIZE / 4;
+
+/// The number of 64 bit words in the bitmap of a page.
+const WORDS_PER_PAGE: usize = INSTRUCTIONS_PER_PAGE / 64;
+
+/// The [Coverage] bitmap records which guest instructions were executed, with one bit per
+/// instruction word. Bitmaps are allocated per page of code, so that only pages that were executed
+/// from take up space.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// The bitmaps of the pages that were executed from, keyed by page index.
+    pages: FxHashMap<Address, [u64; WORDS_PER_PAGE]>,
+}
+
+impl Coverage {
+    /// Records that the instruction at `pc` was executed.
+    #[inline(always)]
+    pub fn record(&mut self, pc: Address) {
+        let bit = (pc as usize & (page::PAGE_SIZE - 1)) >> 2;
+        let words = self
+            .pages
+            .entry(pc >> page::PAGE_ADDRESS_SIZE)
+            .or_insert([0; WORDS_PER_PAGE]);
+        words[bit / 64] |= 1 << (bit % 64);
+    }
+
+    /// Returns whether the instruction at `pc` was executed.
+    pub fn is_hit(&self, pc: Address) -> bool {
+        let bit = (pc as usize & (page::PAGE_SIZE - 1)) >> 2;
+        self.pages
+            .get(&(pc >> page::PAGE_ADDRESS_SIZE))
+            .is_some_and(|words| words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Returns the number of distinct instructions that were executed.
+    pub fn len(&self) -> usize {
+        self.pages
+            .values()
+            .flat_map(|words| words.iter())
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns `true` if no instructions were executed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Merges the executed instructions of another [Coverage] bitmap into this one, e.g. to
+    /// combine the coverage of several runs.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&page_index, other_words) in other.pages.iter() {
+            let words = self.pages.entry(page_index).or_insert([0; WORDS_PER_PAGE]);
+            for (word, other) in words.iter_mut().zip(other_words.iter()) {
+                *word |= other;
+            }
+        }
+    }
+
+    /// Returns the addresses of all executed instructions, in ascending order.
+    pub fn addresses(&self) -> Vec<Address> {
+        let mut page_indices = self.pages.keys().copied().collect::<Vec<_>>();
+        page_indices.sort_unstable();
+
+        let mut addresses = Vec::with_capacity(self.len());
+        for page_index in page_indices {
+            let base = page_index << page::PAGE_ADDRESS_SIZE;
+            for (i, &word) in self.pages[&page_index].iter().enumerate() {
+                let mut word = word;
+                while word != 0 {
+                    let bit = word.trailing_zeros() as usize;
+                    addresses.push(base + ((i * 64 + bit) << 2) as Address);
+                    word &= word - 1;
+                }
+            }
+        }
+        addresses
+    }
+
+    /// Writes the addresses of all executed instructions as `0x`-prefixed hex, one per line, in
+    /// ascending order. The output can be piped into `addr2line -e <program>` to map the executed
+    /// instructions to source lines.
+    ///
+    /// ### Takes
+    /// - `writer`: The writer to write the addresses to.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the addresses were written successfully.
+    pub fn write_addresses(&self, mut writer: impl Write) -> Result<()> {
+        for address in self.addresses() {
+            writeln!(writer, "0x{:08x}", address)?;
+        }
+        Ok(())
+    }
+
+    /// Writes an LCOV report of the coverage, symbolized with the program's [Metadata].
+    ///
+    /// ELF symbols carry no source locations, so each symbol is reported as its own source file
+    /// named after the symbol, with one line per instruction word: line `n` of the symbol is the
+    /// instruction at offset `4 * (n - 1)` from its start. Symbols without a size are skipped.
+    ///
+    /// ### Takes
+    /// - `metadata`: The [Metadata] of the program, containing its symbols.
+    /// - `writer`: The writer to write the report to.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the report was written successfully.
+    pub fn write_lcov(&self, metadata: &Metadata, mut writer: impl Write) -> Result<()> {
+        writeln!(writer, "TN:cannon")?;
+        for symbol in metadata.symbols.iter().filter(|symbol| symbol.size > 0) {
+            let lines = symbol.size.div_ceil(4);
+            let hits = (0..lines)
+                .filter(|i| self.is_hit(symbol.start.wrapping_add(i * 4)))
+                .count();
+
+            writeln!(writer, "SF:{}", symbol.name)?;
+            writeln!(writer, "FN:1,{}", symbol.name)?;
+            writeln!(writer, "FNDA:{},{}", (hits > 0) as u8, symbol.name)?;
+            writeln!(writer, "FNF:1")?;
+            writeln!(writer, "FNH:{}", (hits > 0) as u8)?;
+            for i in 0..lines {
+                let hit = self.is_hit(symbol.start.wrapping_add(i * 4));
+                writeln!(writer, "DA:{},{}", i + 1, hit as u8)?;
+            }
+            writeln!(writer, "LF:{}", lines)?;
+            writeln!(writer, "LH:{}", hits)?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Symbol;
+
+    #[test]
+    fn record_and_report() {
+        let mut coverage = Coverage::default();
+        assert!(coverage.is_empty());
+
+        for pc in [0x1000, 0x1004, 0x1004, 0x1ffc, 0x7000_0000] {
+            coverage.record(pc);
+        }
+        assert_eq!(coverage.len(), 4);
+        assert!(coverage.is_hit(0x1004));
+        assert!(!coverage.is_hit(0x1008));
+        assert_eq!(
+            coverage.addresses(),
+            vec![0x1000, 0x1004, 0x1ffc, 0x7000_0000]
+        );
+
+        let mut other = Coverage::default();
+        other.record(0x1008);
+        coverage.merge(&other);
+        assert_eq!(coverage.len(), 5);
+
+        let mut addresses = Vec::new();
+        coverage.write_addresses(&mut addresses).unwrap();
+        assert!(String::from_utf8(addresses)
+            .unwrap()
+            .starts_with("0x00001000\n0x00001004\n0x00001008\n"));
+    }
+
+    #[test]
+    fn lcov() {
+        let mut coverage = Coverage::default();
+        coverage.record(0x1000);
+        coverage.record(0x1008);
+
+        let metadata = Metadata {
+            symbols: vec![
+                Symbol {
+                    name: "main.main".to_string(),
+                    start: 0x1000,
+                    size: 12,
+                },
+                Symbol {
+                    name: "main.unused".to_string(),
+                    start: 0x100c,
+                    size: 4,
+                },
+                Symbol {
+                    name: "main.label".to_string(),
+                    start: 0x1010,
+                    size: 0,
+                },
+            ],
+        };
+        let mut report = Vec::new();
+        coverage.write_lcov(&metadata, &mut report).unwrap();
+
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "TN:cannon\n\
+             SF:main.main\nFN:1,main.main\nFNDA:1,main.main\nFNF:1\nFNH:1\n\
+             DA:1,1\nDA:2,0\nDA:3,1\nLF:3\nLH:2\nend_of_record\n\
+             SF:main.unused\nFN:1,main.unused\nFNDA:0,main.unused\nFNF:1\nFNH:0\n\
+             DA:1,0\nLF:1\nLH:0\nend_of_record\n"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! This module contains a disassembler for the subset of the MIPS32 instruction set that the
+//! emulator supports, for use in traces, debuggers and error messages.
+//!
+//! Instructions are rendered in the style of `objdump`, with registers referred to by their
+//! conventional names, e.g. `addiu $sp, $sp, -32` or `lw $ra, 28($sp)`. Branch and jump targets are
+//! resolved to absolute addresses, which requires the address of the instruction.
+
+use crate::Address;
+use std::fmt;
+
+/// The conventional names of the general purpose registers, by index.
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+/// The [Disassembly] of an instruction, which renders it with [fmt::Display]. Instructions that
+/// the emulator does not support are rendered as a raw `.word`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The instruction word.
+    pub instruction: u32,
+    /// The address of the instruction.
+    pub pc: Address,
+}
+
+impl Disassembly {
+    /// Creates a new [Disassembly] of the instruction at the given address.
+    pub fn new(instruction: u32, pc: Address) -> Self {
+        Self { instruction, pc }
+    }
+
+    /// Returns whether the emulator supports the instruction.
+    pub fn is_supported(&self) -> bool {
+        mnemonic(self.instruction).is_some()
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match disassemble(self.instruction, self.pc) {
+            Some(text) => f.write_str(&text),
+            None => write!(f, ".word 0x{:08x}", self.instruction),
+        }
+    }
+}
+
+/// Returns the mnemonic of an instruction.
+///
+/// ### Takes
+/// - `instruction`: The instruction word.
+///
+/// ### Returns
+/// - `Some(mnemonic)` if the emulator supports the instruction. The MIPS32r2 instructions are
+///   only executed by states with [crate::State::enable_mips32r2].
+/// - `None` otherwise.
+pub fn mnemonic(instruction: u32) -> Option<&'static str> {
+    let opcode = instruction >> 26;
+    let mnemonic = match opcode {
+        0x00 => match instruction & 0x3F {
+            0x00 if instruction == 0 => "nop",
+            0x00 => "sll",
+            0x02 if (instruction >> 21) & 0x1 == 1 => "rotr",
+            0x02 => "srl",
+            0x03 => "sra",
+            0x04 => "sllv",
+            0x06 if (instruction >> 6) & 0x1 == 1 => "rotrv",
+            0x06 => "srlv",
+            0x07 => "srav",
+            0x08 => "jr",
+            0x09 => "jalr",
+            0x0A => "movz",
+            0x0B => "movn",
+            0x0C => "syscall",
+            0x0F => "sync",
+            0x10 => "mfhi",
+            0x11 => "mthi",
+            0x12 => "mflo",
+            0x13 => "mtlo",
+            0x18 => "mult",
+            0x19 => "multu",
+            0x1A => "div",
+            0x1B => "divu",
+            0x20 => "add",
+            0x21 => "addu",
+            0x22 => "sub",
+            0x23 => "subu",
+            0x24 => "and",
+            0x25 => "or",
+            0x26 => "xor",
+            0x27 => "nor",
+            0x2A => "slt",
+            0x2B => "sltu",
+            _ => return None,
+        },
+        0x01 => match (instruction >> 16) & 0x1F {
+            0x00 => "bltz",
+            0x01 => "bgez",
+            _ => return None,
+        },
+        0x02 => "j",
+        0x03 => "jal",
+        0x04 => "beq",
+        0x05 => "bne",
+        0x06 => "blez",
+        0x07 => "bgtz",
+        0x08 => "addi",
+        0x09 => "addiu",
+        0x0A => "slti",
+        0x0B => "sltiu",
+        0x0C => "andi",
+        0x0D => "ori",
+        0x0E => "xori",
+        0x0F => "lui",
+        0x1C => match instruction & 0x3F {
+            0x02 => "mul",
+            0x20 => "clz",
+            0x21 => "clo",
+            _ => return None,
+        },
+        0x1F => match instruction & 0x3F {
+            0x00 => "ext",
+            0x04 => "ins",
+            0x20 => match (instruction >> 6) & 0x1F {
+                0x02 => "wsbh",
+                0x10 => "seb",
+                0x18 => "seh",
+                _ => return None,
+            },
+            _ => return None,
+        },
+        0x20 => "lb",
+        0x21 => "lh",
+        0x22 => "lwl",
+        0x23 => "lw",
+        0x24 => "lbu",
+        0x25 => "lhu",
+        0x26 => "lwr",
+        0x28 => "sb",
+        0x29 => "sh",
+        0x2A => "swl",
+        0x2B => "sw",
+        0x2E => "swr",
+        0x30 => "ll",
+        0x38 => "sc",
+        _ => return None,
+    };
+    Some(mnemonic)
+}
+
+/// Disassembles an instruction into its mnemonic and operands.
+///
+/// ### Takes
+/// - `instruction`: The instruction word.
+/// - `pc`: The address of the instruction, used to resolve branch and jump targets.
+///
+/// ### Returns
+/// - `Some(text)` if the emulator supports the instruction.
+/// - `None` otherwise.
+pub fn disassemble(instruction: u32, pc: Address) -> Option<String> {
+    let mnemonic = mnemonic(instruction)?;
+    let opcode = instruction >> 26;
+    let rs = reg(instruction >> 21);
+    let rt = reg(instruction >> 16);
+    let rd = reg(instruction >> 11);
+    let shamt = (instruction >> 6) & 0x1F;
+    let imm = instruction as u16;
+    let simm = imm as i16;
+    let branch_target = pc.wrapping_add(4).wrapping_add(((simm as i32) << 2) as u32);
+
+    let text = match (opcode, mnemonic) {
+        (_, "nop" | "syscall" | "sync") => mnemonic.to_string(),
+        (0x00, "sll" | "srl" | "sra" | "rotr") => format!("{mnemonic} {rd}, {rt}, {shamt}"),
+        (0x00, "sllv" | "srlv" | "srav" | "rotrv") => format!("{mnemonic} {rd}, {rt}, {rs}"),
+        (0x00, "jr" | "mthi" | "mtlo") => format!("{mnemonic} {rs}"),
+        (0x00, "jalr") if (instruction >> 11) & 0x1F == 31 => format!("{mnemonic} {rs}"),
+        (0x00, "jalr") => format!("{mnemonic} {rd}, {rs}"),
+        (0x00, "mfhi" | "mflo") => format!("{mnemonic} {rd}"),
+        (0x00, "mult" | "multu" | "div" | "divu") => format!("{mnemonic} {rs}, {rt}"),
+        (0x00 | 0x1C, "clz" | "clo") => format!("{mnemonic} {rd}, {rs}"),
+        (0x00 | 0x1C, _) => format!("{mnemonic} {rd}, {rs}, {rt}"),
+        (0x1F, "ext") => {
+            let size = ((instruction >> 11) & 0x1F) + 1;
+            format!("{mnemonic} {rt}, {rs}, {shamt}, {size}")
+        }
+        (0x1F, "ins") => {
+            let size = ((instruction >> 11) & 0x1F)
+                .wrapping_sub(shamt)
+                .wrapping_add(1);
+            format!("{mnemonic} {rt}, {rs}, {shamt}, {size}")
+        }
+        (0x1F, _) => format!("{mnemonic} {rd}, {rt}"),
+        (0x01 | 0x06 | 0x07, _) => format!("{mnemonic} {rs}, 0x{branch_target:x}"),
+        (0x02 | 0x03, _) => {
+            let target = (pc.wrapping_add(4) & 0xF000_0000) | ((instruction & 0x03FF_FFFF) << 2);
+            format!("{mnemonic} 0x{target:x}")
+        }
+        (0x04 | 0x05, _) => format!("{mnemonic} {rs}, {rt}, 0x{branch_target:x}"),
+        (0x0C..=0x0E, _) => format!("{mnemonic} {rt}, {rs}, 0x{imm:x}"),
+        (0x0F, _) => format!("{mnemonic} {rt}, 0x{imm:x}"),
+        (0x08..=0x0B, _) => format!("{mnemonic} {rt}, {rs}, {simm}"),
+        _ => format!("{mnemonic} {rt}, {simm}({rs})"),
+    };
+    Some(text)
+}
+
+/// Returns whether an instruction belongs to the floating-point unit (`COP1` and `COP1X`, and the
+/// `lwc1`, `ldc1`, `swc1` and `sdc1` loads and stores), which the emulator does not support.
+///
+/// ### Takes
+/// - `instruction`: The instruction word.
+pub fn is_floating_point(instruction: u32) -> bool {
+    matches!(instruction >> 26, 0x11 | 0x13 | 0x31 | 0x35 | 0x39 | 0x3D)
+}
+
+/// Describes the fields that determine the operation of an instruction, for reporting
+/// instructions that the emulator does not support.
+///
+/// ### Takes
+/// - `instruction`: The instruction word.
+///
+/// ### Returns
+/// - The opcode of the instruction, followed by its function or `rt` field where the opcode
+///   selects an instruction by them.
+pub fn describe_fields(instruction: u32) -> String {
+    let opcode = instruction >> 26;
+    match opcode {
+        0x00 | 0x1C => format!(
+            "opcode 0x{:02x}, funct 0x{:02x}",
+            opcode,
+            instruction & 0x3F
+        ),
+        0x01 => format!("opcode 0x01, rt 0x{:02x}", (instruction >> 16) & 0x1F),
+        0x11 => format!(
+            "opcode 0x11, fmt 0x{:02x}, funct 0x{:02x}",
+            (instruction >> 21) & 0x1F,
+            instruction & 0x3F
+        ),
+        _ => format!("opcode 0x{:02x}", opcode),
+    }
+}
+
+/// Returns the `$`-prefixed name of the register in the low 5 bits of `index`.
+fn reg(index: u32) -> String {
+    format!("${}", REGISTER_NAMES[(index & 0x1F) as usize])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassemble_instructions() {
+        let cases: &[(u32, &str)] = &[
+            (0x00000000, "nop"),
+            (0x27BDFFE0, "addiu $sp, $sp, -32"),
+            (0x8FBF001C, "lw $ra, 28($sp)"),
+            (0xAFA40004, "sw $a0, 4($sp)"),
+            (0x3C011234, "lui $at, 0x1234"),
+            (0x3421FFFF, "ori $at, $at, 0xffff"),
+            (0x00851021, "addu $v0, $a0, $a1"),
+            (0x00041080, "sll $v0, $a0, 2"),
+            (0x03E00008, "jr $ra"),
+            (0x0320F809, "jalr $t9"),
+            (0x00604809, "jalr $t1, $v1"),
+            (0x0000000C, "syscall"),
+            (0x00850018, "mult $a0, $a1"),
+            (0x00001010, "mfhi $v0"),
+            (0x70851002, "mul $v0, $a0, $a1"),
+            (0x70801020, "clz $v0, $a0"),
+            (0x10850003, "beq $a0, $a1, 0x1010"),
+            (0x1C80FFFF, "bgtz $a0, 0x1000"),
+            (0x04810002, "bgez $a0, 0x100c"),
+            (0x0C000400, "jal 0x1000"),
+            (0xC3A40000, "ll $a0, 0($sp)"),
+            (0xE3A40000, "sc $a0, 0($sp)"),
+            (0x00241202, "rotr $v0, $a0, 8"),
+            (0x00A41046, "rotrv $v0, $a0, $a1"),
+            (0x7C041420, "seb $v0, $a0"),
+            (0x7C041620, "seh $v0, $a0"),
+            (0x7C0410A0, "wsbh $v0, $a0"),
+            (0x7C823900, "ext $v0, $a0, 4, 8"),
+            (0x7C827A04, "ins $v0, $a0, 8, 8"),
+        ];
+        for &(instruction, expected) in cases {
+            assert_eq!(
+                disassemble(instruction, 0x1000).as_deref(),
+                Some(expected),
+                "{instruction:08x}"
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_instructions() {
+        // rdhwr $3, $29
+        let rdhwr = 0x7C03E83B;
+        assert_eq!(mnemonic(rdhwr), None);
+        assert!(!Disassembly::new(rdhwr, 0).is_supported());
+        assert_eq!(Disassembly::new(rdhwr, 0).to_string(), ".word 0x7c03e83b");
+        assert_eq!(describe_fields(rdhwr), "opcode 0x1f");
+
+        // break
+        assert_eq!(mnemonic(0x0000000D), None);
+        assert_eq!(describe_fields(0x0000000D), "opcode 0x00, funct 0x0d");
+        // bltzal
+        assert_eq!(mnemonic(0x04900001), None);
+
+        // add.s $f0, $f2, $f4
+        let add_s = 0x46041000;
+        assert!(is_floating_point(add_s));
+        assert_eq!(describe_fields(add_s), "opcode 0x11, fmt 0x10, funct 0x00");
+        assert!(!is_floating_point(rdhwr));
+    }
+}
@@ -0,0 +1,171 @@
+//! This module contains the [EntropySource], which serves the bytes returned by the `getrandom`
+//! syscall so that guests requiring entropy still execute deterministically across hosts.
+//!
+//! The `MIPS` contract does not implement `getrandom`, so the [EntropySource] is an opt-in
+//! [extension](crate::State#extensions), enabled with [crate::State::enable_entropy].
+
+use crate::utils::keccak256;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The maximum number of bytes served by a single `getrandom` syscall. Guests retry short reads, so
+/// larger requests are served over several steps.
+pub(crate) const MAX_GETRANDOM_LEN: u32 = 256;
+
+/// The [EntropySource] determines the bytes returned by the `getrandom` syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum EntropySource {
+    /// Bytes are derived from the state hash and the step at the time of the syscall, as
+    /// `keccak256(stateHash ++ step ++ block)` for each 32-byte block.
+    StateHash,
+    /// Bytes are read from a stream derived from a seed, as `keccak256(seed ++ counter)` for each
+    /// 32-byte block.
+    Seed {
+        /// The seed of the stream.
+        #[serde(with = "crate::ser::fixed_32_hex")]
+        seed: [u8; 32],
+        /// The number of 32-byte blocks consumed from the stream so far.
+        counter: u64,
+    },
+}
+
+impl EntropySource {
+    /// Creates a new [EntropySource::Seed] stream from the given seed.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        EntropySource::Seed { seed, counter: 0 }
+    }
+
+    /// Reads bytes from the [EntropySource].
+    ///
+    /// ### Takes
+    /// - `len`: The number of bytes to read.
+    /// - `step`: The current step of the emulator.
+    /// - `state_hash`: Computes the current state hash. Only called for
+    ///   [EntropySource::StateHash].
+    ///
+    /// ### Returns
+    /// - A [Result] containing the bytes read.
+    pub(crate) fn read(
+        &mut self,
+        len: usize,
+        step: u64,
+        state_hash: impl FnOnce() -> Result<[u8; 32]>,
+    ) -> Result<Vec<u8>> {
+        let blocks = len.div_ceil(32) as u64;
+        let mut out = Vec::with_capacity(blocks as usize * 32);
+        match self {
+            EntropySource::StateHash => {
+                let mut preimage = [0u8; 48];
+                preimage[..32].copy_from_slice(&state_hash()?);
+                preimage[32..40].copy_from_slice(&step.to_be_bytes());
+                for block in 0..blocks {
+                    preimage[40..].copy_from_slice(&block.to_be_bytes());
+                    out.extend_from_slice(keccak256(preimage).as_slice());
+                }
+            }
+            EntropySource::Seed { seed, counter } => {
+                let mut preimage = [0u8; 40];
+                preimage[..32].copy_from_slice(seed.as_slice());
+                for _ in 0..blocks {
+                    preimage[32..].copy_from_slice(&counter.to_be_bytes());
+                    out.extend_from_slice(keccak256(preimage).as_slice());
+                    *counter += 1;
+                }
+            }
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+}
+
+impl FromStr for EntropySource {
+    type Err = anyhow::Error;
+
+    /// Parses `state-hash` as [EntropySource::StateHash], or a 32-byte hex seed as an
+    /// [EntropySource::Seed] stream.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "state-hash" {
+            return Ok(EntropySource::StateHash);
+        }
+
+        let seed = crate::ser::decode_hex(s)
+            .ok()
+            .and_then(|seed| <[u8; 32]>::try_from(seed).ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid entropy source: {}; expected `state-hash` or a 32-byte hex seed",
+                    s
+                )
+            })?;
+        Ok(EntropySource::from_seed(seed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seed_stream() {
+        let mut a = EntropySource::from_seed([7u8; 32]);
+        let first = a.read(40, 0, || unreachable!()).unwrap();
+        let second = a.read(32, 0, || unreachable!()).unwrap();
+        assert_eq!(first.len(), 40);
+        assert_eq!(
+            a,
+            EntropySource::Seed {
+                seed: [7u8; 32],
+                counter: 3
+            }
+        );
+
+        // The stream is independent of the step, and continues after partially consumed blocks.
+        let mut b = EntropySource::from_seed([7u8; 32]);
+        let all = b.read(96, 100, || unreachable!()).unwrap();
+        assert_eq!(all[..40], first[..]);
+        assert_eq!(all[64..], second[..]);
+
+        let mut c = EntropySource::from_seed([8u8; 32]);
+        assert_ne!(c.read(40, 0, || unreachable!()).unwrap(), first);
+    }
+
+    #[test]
+    fn state_hash() {
+        let mut source = EntropySource::StateHash;
+        let a = source.read(64, 1, || Ok([1u8; 32])).unwrap();
+        assert_eq!(a, source.read(64, 1, || Ok([1u8; 32])).unwrap());
+        assert_ne!(a, source.read(64, 2, || Ok([1u8; 32])).unwrap());
+        assert_ne!(a, source.read(64, 1, || Ok([2u8; 32])).unwrap());
+        assert_ne!(a[..32], a[32..]);
+        assert!(source.read(1, 1, || anyhow::bail!("no hash")).is_err());
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "state-hash".parse::<EntropySource>().unwrap(),
+            EntropySource::StateHash
+        );
+        assert_eq!(
+            format!("0x{}", "01".repeat(32))
+                .parse::<EntropySource>()
+                .unwrap(),
+            EntropySource::from_seed([1u8; 32])
+        );
+        assert!("0x0101".parse::<EntropySource>().is_err());
+        assert!("random".parse::<EntropySource>().is_err());
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        for source in [
+            EntropySource::StateHash,
+            EntropySource::from_seed([3u8; 32]),
+        ] {
+            let ser = serde_json::to_string(&source).unwrap();
+            assert_eq!(serde_json::from_str::<EntropySource>(&ser).unwrap(), source);
+        }
+    }
+}
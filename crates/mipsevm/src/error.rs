@@ -0,0 +1,397 @@
+//! This module contains the [CannonError] type returned from the public API of this crate.
+
+use crate::{Access, Address, MemoryRegions, Permissions};
+use alloy_sol_types::{sol, SolError};
+use std::fmt;
+
+/// A [Result] type alias where the error is a [CannonError].
+pub type CannonResult<T> = Result<T, CannonError>;
+
+/// The [CannonError] enum describes the kinds of failures that may surface from the MIPS emulator,
+/// its [crate::PreimageOracle], and the [crate::test_utils::evm::MipsEVM].
+#[derive(Debug)]
+pub enum CannonError {
+    /// The instruction at `pc` could not be decoded, or is not supported.
+    InvalidInstruction {
+        /// The address of the instruction.
+        pc: Address,
+        /// The raw instruction word.
+        instruction: u32,
+    },
+    /// The instruction at `pc` is a floating-point instruction, and no [crate::FpTrapHandler]
+    /// emulated it.
+    FloatingPointInstruction {
+        /// The address of the instruction.
+        pc: Address,
+        /// The raw instruction word.
+        instruction: u32,
+    },
+    /// A branch or jump was encountered within the delay slot of another branch or jump.
+    InvalidDelaySlot {
+        /// The address of the offending instruction.
+        pc: Address,
+    },
+    /// A word-sized memory access was not aligned to 4 bytes.
+    UnalignedAccess(Address),
+    /// The [crate::PreimageOracle] failed to serve a hint or a preimage.
+    OracleIo(anyhow::Error),
+    /// The guest read a preimage at an offset past the end of the length-prefixed preimage.
+    PreimageOutOfBounds {
+        /// The key of the preimage.
+        key: [u8; 32],
+        /// The offset of the read.
+        offset: u32,
+        /// The length of the preimage, including its 8-byte length prefix.
+        length: usize,
+    },
+    /// A call to one of the contracts within the [crate::test_utils::evm::MipsEVM] reverted.
+    EvmRevert {
+        /// The raw revert data.
+        data: Vec<u8>,
+    },
+    /// A call to one of the contracts within the [crate::test_utils::evm::MipsEVM] failed for a
+    /// reason other than a revert.
+    EvmFailure(String),
+    /// The state hash returned by the `MIPS` contract does not match the state witness it emitted.
+    WitnessMismatch {
+        /// The state hash returned by the contract.
+        expected: [u8; 32],
+        /// The hash of the state witness emitted by the contract.
+        actual: [u8; 32],
+    },
+    /// A function-entry hook aborted the run.
+    HookAborted {
+        /// The name of the entered function.
+        symbol: String,
+        /// The entry address of the function.
+        pc: Address,
+    },
+    /// A [crate::PreStepHook] or [crate::PostStepHook] aborted the run.
+    StepAborted {
+        /// The program counter when the run was aborted.
+        pc: Address,
+        /// The step when the run was aborted.
+        step: u64,
+    },
+    /// A guest access violated the permissions of a page, with [crate::PageProtection] enabled.
+    ProtectionFault {
+        /// The accessed address.
+        address: Address,
+        /// The kind of access.
+        access: Access,
+        /// The permissions of the accessed page.
+        permissions: Permissions,
+        /// The program counter of the accessing instruction.
+        pc: Address,
+        /// The name of the [crate::MemoryRegion] containing the accessed address, if annotated.
+        region: Option<String>,
+    },
+    /// A guest access touched the page guarding the stack, with a stack guard enabled in the
+    /// [crate::MemoryRegions].
+    StackOverflow {
+        /// The accessed address.
+        address: Address,
+        /// The program counter of the accessing instruction.
+        pc: Address,
+        /// The name of the [crate::MemoryRegion] containing the accessed address, if annotated.
+        region: Option<String>,
+    },
+    /// A serialized document is of a version that this version of the crate cannot load.
+    UnsupportedVersion {
+        /// The kind of document.
+        kind: &'static str,
+        /// The version of the document.
+        version: u32,
+        /// The versions of the document that can be loaded.
+        supported: Vec<u32>,
+    },
+    /// A loaded state does not match the checksum that was embedded when it was written.
+    ChecksumMismatch {
+        /// The checksum embedded in the state.
+        expected: [u8; 32],
+        /// The checksum of the loaded state.
+        actual: [u8; 32],
+    },
+    /// Any other error.
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for CannonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CannonError::InvalidInstruction { pc, instruction } => {
+                write!(
+                    f,
+                    "Invalid instruction {:08x} ({}) at pc {:08x}",
+                    instruction,
+                    crate::disasm::describe_fields(*instruction),
+                    pc
+                )
+            }
+            CannonError::FloatingPointInstruction { pc, instruction } => {
+                write!(
+                    f,
+                    "Unsupported floating-point instruction {:08x} ({}) at pc {:08x}; the guest \
+                     must be built for soft-float",
+                    instruction,
+                    crate::disasm::describe_fields(*instruction),
+                    pc
+                )
+            }
+            CannonError::InvalidDelaySlot { pc } => {
+                write!(f, "Unexpected branch or jump in delay slot at {:08x}", pc)
+            }
+            CannonError::UnalignedAccess(address) => {
+                write!(f, "Unaligned memory access: {:x}", address)
+            }
+            CannonError::OracleIo(e) => write!(f, "Preimage oracle error: {}", e),
+            CannonError::PreimageOutOfBounds {
+                key,
+                offset,
+                length,
+            } => {
+                write!(
+                    f,
+                    "Preimage read at offset {} is out of bounds of the {} byte length-prefixed \
+                     preimage 0x",
+                    offset, length
+                )?;
+                key.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            CannonError::EvmRevert { data } => match decode_revert_reason(data) {
+                Some(reason) => write!(f, "EVM call reverted: {}", reason),
+                None => {
+                    write!(f, "EVM call reverted with data 0x")?;
+                    data.iter().try_for_each(|b| write!(f, "{:02x}", b))
+                }
+            },
+            CannonError::EvmFailure(reason) => write!(f, "EVM call failed: {}", reason),
+            CannonError::WitnessMismatch { expected, actual } => {
+                write!(f, "Post-state hash does not match state hash in log: 0x")?;
+                expected.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+                write!(f, " != 0x")?;
+                actual.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            CannonError::HookAborted { symbol, pc } => {
+                write!(f, "Run aborted on entering {} at {:08x}", symbol, pc)
+            }
+            CannonError::StepAborted { pc, step } => {
+                write!(
+                    f,
+                    "Run aborted by a step hook at step {} (pc {:08x})",
+                    step, pc
+                )
+            }
+            CannonError::ProtectionFault {
+                address,
+                access,
+                permissions,
+                pc,
+                region,
+            } => write!(
+                f,
+                "{} page with permissions {} at {:08x}{} (pc {:08x})",
+                access,
+                permissions,
+                address,
+                InRegion(region),
+                pc
+            ),
+            CannonError::StackOverflow {
+                address,
+                pc,
+                region,
+            } => write!(
+                f,
+                "Guest stack overflow: access to guard page at {:08x}{} (pc {:08x})",
+                address,
+                InRegion(region),
+                pc
+            ),
+            CannonError::UnsupportedVersion {
+                kind,
+                version,
+                supported,
+            } => {
+                write!(
+                    f,
+                    "Unsupported {} version {}, supported versions: ",
+                    kind, version
+                )?;
+                let supported = supported.iter().map(u32::to_string).collect::<Vec<_>>();
+                write!(f, "{}", supported.join(", "))
+            }
+            CannonError::ChecksumMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "State checksum mismatch, the state file is truncated or corrupted: 0x"
+                )?;
+                expected.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+                write!(f, " != 0x")?;
+                actual.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            CannonError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl CannonError {
+    /// Attaches the name of the [crate::MemoryRegion] containing the accessed address to a
+    /// [CannonError::ProtectionFault] or a [CannonError::StackOverflow]. Other errors are
+    /// returned unchanged.
+    ///
+    /// ### Takes
+    /// - `regions`: The [MemoryRegions] of the state, if any.
+    pub(crate) fn with_region(mut self, regions: Option<&MemoryRegions>) -> Self {
+        if let CannonError::ProtectionFault {
+            address,
+            ref mut region,
+            ..
+        }
+        | CannonError::StackOverflow {
+            address,
+            ref mut region,
+            ..
+        } = self
+        {
+            *region = regions
+                .and_then(|regions| regions.region_of(address))
+                .map(|found| found.name.clone());
+        }
+        self
+    }
+
+    /// Returns the decoded revert reason if the error is a [CannonError::EvmRevert] with revert
+    /// data that could be decoded.
+    ///
+    /// ### Returns
+    /// - `Some(reason)` if the revert data is an `Error(string)`, a `Panic(uint256)`, or one of
+    ///   the custom errors thrown by the `MIPS` and `PreimageOracle` contracts.
+    /// - `None` otherwise.
+    pub fn revert_reason(&self) -> Option<String> {
+        match self {
+            CannonError::EvmRevert { data } => decode_revert_reason(data),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the region of a fault, if any, as ` in <region>`.
+struct InRegion<'a>(&'a Option<String>);
+
+impl fmt::Display for InRegion<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(region) => write!(f, " in {}", region),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for CannonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CannonError::OracleIo(e) | CannonError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for CannonError {
+    /// Recovers a [CannonError] that was propagated through an [anyhow::Error], falling back to
+    /// [CannonError::Other] for all other errors.
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<CannonError>() {
+            Ok(err) => err,
+            Err(err) => CannonError::Other(err),
+        }
+    }
+}
+
+sol! {
+    /// Thrown by the `PreimageOracle` when the input to a preimage part is not of the expected size.
+    error InvalidInputSize();
+
+    /// Thrown by the `PreimageOracle` when the requested part offset is out of bounds.
+    error PartOffsetOOB();
+
+    /// Thrown by the `PreimageOracle` when a merkle proof is invalid.
+    error InvalidProof();
+
+    /// Thrown by the `PreimageOracle` when a preimage is invalid.
+    error InvalidPreimage();
+
+    /// Thrown by the `MIPS` contract when a memory proof is invalid.
+    error InvalidMemoryProof();
+}
+
+/// Decodes the revert data returned by the `MIPS` or `PreimageOracle` contracts.
+///
+/// ### Takes
+/// - `data`: The raw revert data.
+///
+/// ### Returns
+/// - `Some(reason)` if the data could be decoded, otherwise `None`.
+fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let selector: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    let custom = [
+        (InvalidInputSize::SELECTOR, InvalidInputSize::SIGNATURE),
+        (PartOffsetOOB::SELECTOR, PartOffsetOOB::SIGNATURE),
+        (InvalidProof::SELECTOR, InvalidProof::SIGNATURE),
+        (InvalidPreimage::SELECTOR, InvalidPreimage::SIGNATURE),
+        (InvalidMemoryProof::SELECTOR, InvalidMemoryProof::SIGNATURE),
+    ];
+    if let Some((_, signature)) = custom.iter().find(|(s, _)| *s == selector) {
+        return Some(signature.to_string());
+    }
+
+    // Fall back to the standard `Error(string)` and `Panic(uint256)` errors.
+    alloy_sol_types::decode_revert_reason(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_primitives::U256;
+    use alloy_sol_types::{Panic, Revert};
+
+    #[test]
+    fn decode_error_string() {
+        let err = CannonError::EvmRevert {
+            data: Revert {
+                reason: "invalid instruction".to_string(),
+            }
+            .abi_encode(),
+        };
+        assert!(err.revert_reason().unwrap().contains("invalid instruction"));
+        assert!(err.to_string().contains("invalid instruction"));
+    }
+
+    #[test]
+    fn decode_panic() {
+        let err = CannonError::EvmRevert {
+            data: Panic {
+                code: U256::from(0x11),
+            }
+            .abi_encode(),
+        };
+        assert!(err.revert_reason().is_some());
+    }
+
+    #[test]
+    fn decode_custom_error() {
+        let err = CannonError::EvmRevert {
+            data: PartOffsetOOB {}.abi_encode(),
+        };
+        assert_eq!(err.revert_reason().as_deref(), Some("PartOffsetOOB()"));
+    }
+
+    #[test]
+    fn undecodable_revert() {
+        let err = CannonError::EvmRevert {
+            data: vec![0xde, 0xad],
+        };
+        assert_eq!(err.revert_reason(), None);
+        assert_eq!(err.to_string(), "EVM call reverted with data 0xdead");
+    }
+}
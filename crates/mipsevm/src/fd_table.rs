@@ -0,0 +1,231 @@
+//! This module contains the [FdTable], which backs a small set of whitelisted, read-only virtual
+//! files that guests may `open`, `read`, `lseek` and `close`.
+//!
+//! The `MIPS` contract does not implement these syscalls, so the [FdTable] is an opt-in
+//! [extension](crate::State#extensions), enabled with [crate::State::enable_virtual_files].
+
+use crate::mips::instrumented::{MIPS_EBADF, MIPS_EINVAL, MIPS_ENOENT, MIPS_EROFS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The first file descriptor handed out for virtual files. Lower descriptors are reserved for the
+/// standard streams and the preimage oracle channels.
+pub(crate) const FIRST_VIRTUAL_FD: u32 = 7;
+
+/// The access mode mask of the `open` flags.
+pub(crate) const O_ACCMODE: u32 = 0x3;
+
+/// The default set of virtual files, with deterministic contents.
+pub const DEFAULT_VIRTUAL_FILES: &[(&str, &[u8])] = &[
+    ("/etc/hosts", b"127.0.0.1\tlocalhost\n::1\tlocalhost\n"),
+    ("/etc/resolv.conf", b""),
+    ("/etc/passwd", b"root:x:0:0:root:/root:/bin/sh\n"),
+    ("/proc/self/maps", b""),
+];
+
+/// An [OpenFile] is an open file description of a virtual file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenFile {
+    /// The path of the virtual file.
+    pub path: String,
+    /// The current read offset into the file.
+    pub offset: u64,
+}
+
+/// The [FdTable] holds the contents of the whitelisted virtual files along with the file
+/// descriptors that the guest has opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FdTable {
+    /// Map of whitelisted paths to their contents.
+    #[serde(with = "files_hex")]
+    files: BTreeMap<String, Vec<u8>>,
+    /// Map of open file descriptors to their [OpenFile]s.
+    open: BTreeMap<u32, OpenFile>,
+}
+
+impl FdTable {
+    /// Creates a new [FdTable] containing the [DEFAULT_VIRTUAL_FILES].
+    pub fn new() -> Self {
+        let mut table = Self::default();
+        for (path, contents) in DEFAULT_VIRTUAL_FILES {
+            table.insert_file(*path, contents.to_vec());
+        }
+        table
+    }
+
+    /// Adds a virtual file to the whitelist, replacing the contents of an existing file at the same
+    /// path.
+    ///
+    /// ### Takes
+    /// - `path`: The absolute path of the file.
+    /// - `contents`: The contents of the file.
+    pub fn insert_file(&mut self, path: impl Into<String>, contents: Vec<u8>) {
+        self.files.insert(path.into(), contents);
+    }
+
+    /// Returns `true` if the given file descriptor refers to an open virtual file.
+    pub fn is_open(&self, fd: u32) -> bool {
+        self.open.contains_key(&fd)
+    }
+
+    /// Emulates the `open` syscall.
+    ///
+    /// ### Takes
+    /// - `path`: The path to open.
+    /// - `flags`: The `open` flags.
+    ///
+    /// ### Returns
+    /// - `Ok(fd)` containing the lowest free file descriptor.
+    /// - `Err(errno)` if the path is not whitelisted or is not opened read-only.
+    pub fn open(&mut self, path: &str, flags: u32) -> Result<u32, u32> {
+        if !self.files.contains_key(path) {
+            return Err(MIPS_ENOENT);
+        }
+        if flags & O_ACCMODE != 0 {
+            return Err(MIPS_EROFS);
+        }
+
+        let fd = (FIRST_VIRTUAL_FD..)
+            .find(|fd| !self.open.contains_key(fd))
+            .ok_or(MIPS_EINVAL)?;
+        self.open.insert(
+            fd,
+            OpenFile {
+                path: path.to_string(),
+                offset: 0,
+            },
+        );
+        Ok(fd)
+    }
+
+    /// Emulates the `read` syscall.
+    ///
+    /// ### Takes
+    /// - `fd`: The file descriptor to read from.
+    /// - `count`: The maximum number of bytes to read.
+    ///
+    /// ### Returns
+    /// - `Ok(data)` containing the bytes read, which is empty at the end of the file.
+    /// - `Err(errno)` if the file descriptor is not open.
+    pub fn read(&mut self, fd: u32, count: u32) -> Result<&[u8], u32> {
+        let file = self.open.get_mut(&fd).ok_or(MIPS_EBADF)?;
+        let contents = self.files.get(&file.path).ok_or(MIPS_EBADF)?;
+
+        let start = (file.offset as usize).min(contents.len());
+        let end = start.saturating_add(count as usize).min(contents.len());
+        file.offset = end as u64;
+        Ok(&contents[start..end])
+    }
+
+    /// Emulates the `lseek` syscall.
+    ///
+    /// ### Takes
+    /// - `fd`: The file descriptor to seek.
+    /// - `offset`: The signed offset to seek by.
+    /// - `whence`: `SEEK_SET` (0), `SEEK_CUR` (1) or `SEEK_END` (2).
+    ///
+    /// ### Returns
+    /// - `Ok(offset)` containing the resulting offset from the start of the file.
+    /// - `Err(errno)` if the file descriptor is not open or the arguments are invalid.
+    pub fn lseek(&mut self, fd: u32, offset: i32, whence: u32) -> Result<u32, u32> {
+        let file = self.open.get_mut(&fd).ok_or(MIPS_EBADF)?;
+        let len = self.files.get(&file.path).map_or(0, |f| f.len()) as i64;
+
+        let base = match whence {
+            0 => 0,
+            1 => file.offset as i64,
+            2 => len,
+            _ => return Err(MIPS_EINVAL),
+        };
+        let new_offset = base + offset as i64;
+        if !(0..=u32::MAX as i64).contains(&new_offset) {
+            return Err(MIPS_EINVAL);
+        }
+
+        file.offset = new_offset as u64;
+        Ok(new_offset as u32)
+    }
+
+    /// Emulates the `close` syscall.
+    ///
+    /// ### Takes
+    /// - `fd`: The file descriptor to close.
+    ///
+    /// ### Returns
+    /// - `Ok(())` if the file descriptor was open.
+    /// - `Err(errno)` otherwise.
+    pub fn close(&mut self, fd: u32) -> Result<(), u32> {
+        self.open.remove(&fd).map(|_| ()).ok_or(MIPS_EBADF)
+    }
+}
+
+/// Serializes the contents of the virtual files as hex strings.
+mod files_hex {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize)]
+    struct Hex(#[serde(with = "crate::ser::vec_u8_hex")] Vec<u8>);
+
+    pub fn serialize<S>(files: &BTreeMap<String, Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        files
+            .iter()
+            .map(|(path, contents)| (path, Hex(contents.clone())))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BTreeMap<String, Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let files = BTreeMap::<String, Hex>::deserialize(deserializer)?;
+        Ok(files.into_iter().map(|(path, hex)| (path, hex.0)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn open_read_close() {
+        let mut table = FdTable::new();
+
+        let fd = table.open("/etc/hosts", 0).unwrap();
+        assert_eq!(fd, FIRST_VIRTUAL_FD);
+        assert_eq!(table.open("/etc/hosts", 0), Ok(FIRST_VIRTUAL_FD + 1));
+
+        assert_eq!(table.read(fd, 9).unwrap(), b"127.0.0.1");
+        assert_eq!(table.lseek(fd, -2, 1), Ok(7));
+        assert_eq!(table.read(fd, 2).unwrap(), b".1");
+        assert_eq!(table.lseek(fd, 0, 2), Ok(34));
+        assert!(table.read(fd, 2).unwrap().is_empty());
+
+        assert_eq!(table.close(fd), Ok(()));
+        assert_eq!(table.close(fd), Err(MIPS_EBADF));
+        assert_eq!(table.read(fd, 1), Err(MIPS_EBADF));
+
+        // The lowest free descriptor is reused.
+        assert_eq!(table.open("/proc/self/maps", 0), Ok(FIRST_VIRTUAL_FD));
+    }
+
+    #[test]
+    fn open_errors() {
+        let mut table = FdTable::new();
+        assert_eq!(table.open("/etc/shadow", 0), Err(MIPS_ENOENT));
+        assert_eq!(table.open("/etc/hosts", 1), Err(MIPS_EROFS));
+        assert_eq!(table.lseek(3, 0, 0), Err(MIPS_EBADF));
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut table = FdTable::new();
+        table.open("/etc/hosts", 0).unwrap();
+        let ser = serde_json::to_string(&table).unwrap();
+        assert_eq!(serde_json::from_str::<FdTable>(&ser).unwrap(), table);
+    }
+}
@@ -0,0 +1,94 @@
+//! This module contains the degenerate semantics of the `futex` syscall for single-threaded
+//! guests, which newer Go runtimes call on startup.
+//!
+//! With a single thread, no other thread can ever change a futex word or wake a waiter: a wait
+//! either fails because the word does not hold the expected value, times out, or returns
+//! immediately as a spurious wakeup, which callers must tolerate. A wake never finds a waiter.
+//!
+//! The `MIPS` contract does not implement `futex`, so these semantics are an opt-in
+//! [extension](crate::State#extensions), enabled with [crate::State::enable_futex].
+
+use crate::mips::instrumented::{MIPS_EAGAIN, MIPS_EINVAL, MIPS_ENOSYS, MIPS_ETIMEDOUT};
+
+/// `FUTEX_WAIT`
+const FUTEX_WAIT: u32 = 0;
+/// `FUTEX_WAKE`
+const FUTEX_WAKE: u32 = 1;
+/// `FUTEX_WAIT_BITSET`
+const FUTEX_WAIT_BITSET: u32 = 9;
+/// `FUTEX_WAKE_BITSET`
+const FUTEX_WAKE_BITSET: u32 = 10;
+/// `FUTEX_PRIVATE_FLAG`, which has no effect within a single process.
+const FUTEX_PRIVATE_FLAG: u32 = 128;
+/// `FUTEX_CLOCK_REALTIME`, which has no effect as waits never block.
+const FUTEX_CLOCK_REALTIME: u32 = 256;
+
+/// Emulates a `futex` call of a single-threaded guest.
+///
+/// ### Takes
+/// - `op`: The futex operation, including its flags.
+/// - `word`: The current value of the futex word, or [None] if its address is misaligned.
+/// - `val`: The value that a wait expects the futex word to hold.
+/// - `timeout`: The address of the timeout of a wait, or zero to wait indefinitely.
+///
+/// ### Returns
+/// - `Ok(v0)` with the return value of the syscall.
+/// - `Err(errno)` with the error number of the syscall.
+pub(crate) fn futex(op: u32, word: Option<u32>, val: u32, timeout: u32) -> Result<u32, u32> {
+    let word = word.ok_or(MIPS_EINVAL)?;
+    match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT | FUTEX_WAIT_BITSET if word != val => Err(MIPS_EAGAIN),
+        // No other thread can wake the waiter, so a bounded wait runs into its timeout.
+        FUTEX_WAIT | FUTEX_WAIT_BITSET if timeout != 0 => Err(MIPS_ETIMEDOUT),
+        // An unbounded wait would never return; report a spurious wakeup instead.
+        FUTEX_WAIT | FUTEX_WAIT_BITSET => Ok(0),
+        // There are no waiters to wake.
+        FUTEX_WAKE | FUTEX_WAKE_BITSET => Ok(0),
+        _ => Err(MIPS_ENOSYS),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::StaticOracle, InstrumentedState, State};
+    use std::io;
+
+    #[test]
+    fn futex_syscalls() {
+        // 0x1000: syscall; 0x1004: syscall; ...
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        for i in 0..5 {
+            state.memory.set_memory(0x1000 + 4 * i, 0x0000000C).unwrap();
+        }
+        state.memory.set_memory(0x2000, 7).unwrap();
+        state.enable_futex();
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+
+        let mut call = |uaddr: u32, op: u32, val: u32, timeout: u32| {
+            ins.state.registers[2] = 4238;
+            ins.state.registers[4] = uaddr;
+            ins.state.registers[5] = op;
+            ins.state.registers[6] = val;
+            ins.state.registers[7] = timeout;
+            ins.step(false).unwrap();
+            (ins.state.registers[2], ins.state.registers[7])
+        };
+
+        // FUTEX_WAIT_PRIVATE on a word that changed.
+        assert_eq!(call(0x2000, 128, 6, 0), (0xFFFFFFFF, MIPS_EAGAIN));
+        // FUTEX_WAIT_PRIVATE with a timeout.
+        assert_eq!(call(0x2000, 128, 7, 0x3000), (0xFFFFFFFF, MIPS_ETIMEDOUT));
+        // FUTEX_WAIT_PRIVATE without a timeout wakes up spuriously.
+        assert_eq!(call(0x2000, 128, 7, 0), (0, 0));
+        // FUTEX_WAKE_PRIVATE finds no waiters.
+        assert_eq!(call(0x2000, 129, 1, 0), (0, 0));
+        // Misaligned futex words are rejected.
+        assert_eq!(call(0x2002, 128, 7, 0), (0xFFFFFFFF, MIPS_EINVAL));
+    }
+}
@@ -0,0 +1,261 @@
+//! This module contains the [InstructionHistogram], which counts the instructions executed by the
+//! [crate::InstrumentedState] and records every encounter with an instruction or syscall that the
+//! emulator does not support, to help prioritize which of them to implement next.
+//!
+//! Unlike the [crate::CompatReport], which statically scans every word of a program, the histogram
+//! only reflects what a run actually executed.
+
+use crate::{
+    compat::MIPS32R2_MNEMONICS,
+    disasm::{describe_fields, is_floating_point, mnemonic},
+    types::Syscall,
+    Address, Metadata, State, UNKNOWN_SYMBOL,
+};
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+/// The number of instructions that are listed by the [fmt::Display] of a [HistogramReport].
+const MAX_LISTED: usize = 30;
+
+/// The `syscall` instruction.
+const SYSCALL: u32 = 0x0000000C;
+
+/// An [Encounter] with an unsupported instruction or syscall at a single address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Encounter {
+    /// The address of the instruction.
+    pub pc: Address,
+    /// The instruction word.
+    pub instruction: u32,
+    /// The name of the symbol containing the instruction, if symbolized.
+    pub symbol: Option<String>,
+    /// A description of what is unsupported.
+    pub detail: String,
+    /// The step at which the instruction was first encountered.
+    pub first_step: u64,
+    /// The number of times the instruction was encountered.
+    pub count: u64,
+}
+
+/// The [InstructionHistogram] counts the executed instructions by mnemonic, and records the
+/// instructions and syscalls that the emulator does not support, with the addresses at which they
+/// were encountered.
+///
+/// Floating-point instructions are recorded as unsupported even if a
+/// [crate::FpTrapHandler] emulated them, as are MIPS32r2 instructions executed without
+/// [State::enable_mips32r2] and syscalls that the emulator does not know.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InstructionHistogram {
+    /// The number of executed instructions, keyed by mnemonic or, for unsupported instructions,
+    /// by the fields that select their operation.
+    counts: FxHashMap<Cow<'static, str>, u64>,
+    /// The encounters with unsupported instructions and syscalls, keyed by address and detail.
+    unsupported: BTreeMap<(Address, String), Encounter>,
+}
+
+impl InstructionHistogram {
+    /// Records the instruction that the [State] is about to execute.
+    ///
+    /// ### Takes
+    /// - `state`: The [State] before the instruction is executed.
+    /// - `instruction`: The instruction word at the current program counter.
+    pub fn record(&mut self, state: &State, instruction: u32) {
+        let name = mnemonic(instruction);
+        let key = match name {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(describe_fields(instruction)),
+        };
+
+        let detail = if is_floating_point(instruction) {
+            Some(format!("floating-point ({})", key))
+        } else if name.is_none() {
+            Some(key.to_string())
+        } else if name.is_some_and(|name| MIPS32R2_MNEMONICS.contains(&name)) && !state.mips32r2 {
+            Some(format!("{} (requires MIPS32r2)", key))
+        } else if instruction == SYSCALL && Syscall::try_from(state.registers[2]).is_err() {
+            Some(format!("syscall {}", state.registers[2]))
+        } else {
+            None
+        };
+
+        match self.counts.get_mut(key.as_ref()) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(key, 1);
+            }
+        }
+        if let Some(detail) = detail {
+            self.unsupported
+                .entry((state.pc, detail.clone()))
+                .or_insert_with(|| Encounter {
+                    pc: state.pc,
+                    instruction,
+                    symbol: None,
+                    detail,
+                    first_step: state.step,
+                    count: 0,
+                })
+                .count += 1;
+        }
+    }
+
+    /// Returns the total number of recorded instructions.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns the number of times the instruction with the given mnemonic was executed.
+    pub fn count(&self, mnemonic: &str) -> u64 {
+        self.counts.get(mnemonic).copied().unwrap_or_default()
+    }
+
+    /// Returns the encounters with unsupported instructions and syscalls, ordered by address.
+    pub fn unsupported(&self) -> impl Iterator<Item = &Encounter> {
+        self.unsupported.values()
+    }
+
+    /// Creates the [HistogramReport] of the recorded instructions.
+    ///
+    /// ### Takes
+    /// - `metadata`: The [Metadata] of the program, used to symbolize the encounters with
+    ///   unsupported instructions.
+    ///
+    /// ### Returns
+    /// - The [HistogramReport], with the instructions ordered from most to least executed.
+    pub fn report(&self, metadata: &Metadata) -> HistogramReport {
+        let mut instructions = self
+            .counts
+            .iter()
+            .map(|(name, &count)| (name.to_string(), count))
+            .collect::<Vec<_>>();
+        instructions.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let unsupported = self
+            .unsupported
+            .values()
+            .map(|encounter| Encounter {
+                symbol: Some(metadata.lookup_symbol(encounter.pc).to_string()),
+                ..encounter.clone()
+            })
+            .collect();
+
+        HistogramReport {
+            total: self.total(),
+            instructions,
+            unsupported,
+        }
+    }
+}
+
+/// The [HistogramReport] of an [InstructionHistogram].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramReport {
+    /// The total number of executed instructions.
+    pub total: u64,
+    /// The executed instructions with their counts, from most to least executed.
+    pub instructions: Vec<(String, u64)>,
+    /// The encounters with unsupported instructions and syscalls, ordered by address.
+    pub unsupported: Vec<Encounter>,
+}
+
+impl fmt::Display for HistogramReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Executed {} instructions", self.total)?;
+        for (name, count) in self.instructions.iter().take(MAX_LISTED) {
+            let share = *count as f64 * 100.0 / self.total.max(1) as f64;
+            writeln!(f, "  {:<32} {:>16} {:>6.2}%", name, count, share)?;
+        }
+        if self.instructions.len() > MAX_LISTED {
+            writeln!(f, "  ... and {} more", self.instructions.len() - MAX_LISTED)?;
+        }
+
+        if !self.unsupported.is_empty() {
+            writeln!(f, "Unsupported encounters ({}):", self.unsupported.len())?;
+            for encounter in &self.unsupported {
+                writeln!(
+                    f,
+                    "  0x{:08x}  {:08x}  {:<32} x{} (first at step {}) in {}",
+                    encounter.pc,
+                    encounter.instruction,
+                    encounter.detail,
+                    encounter.count,
+                    encounter.first_step,
+                    encounter.symbol.as_deref().unwrap_or(UNKNOWN_SYMBOL)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_utils::StaticOracle, InstrumentedState, Metadata, State, Symbol};
+    use std::io;
+
+    #[test]
+    fn histogram() {
+        // 0x1000: addiu $t0, $t0, 1
+        // 0x1004: addiu $t0, $t0, 1
+        // 0x1008: rotr $t0, $t1, 4
+        // 0x100c: syscall (with $v0 = 9999)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        for (i, instruction) in [0x25080001, 0x25080001, 0x00294102, 0x0000000C]
+            .into_iter()
+            .enumerate()
+        {
+            state
+                .memory
+                .set_memory(0x1000 + i as u32 * 4, instruction)
+                .unwrap();
+        }
+        state.registers[2] = 9999;
+
+        let mut stepped = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        assert!(stepped.instruction_histogram().is_none());
+        stepped.enable_instruction_histogram();
+        for _ in 0..4 {
+            stepped.step(false).unwrap();
+        }
+
+        let mut threaded =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        threaded.enable_instruction_histogram();
+        threaded.step_threaded(4).unwrap();
+
+        let histogram = stepped.take_instruction_histogram().unwrap();
+        assert_eq!(threaded.instruction_histogram(), Some(&histogram));
+        assert_eq!(histogram.total(), 4);
+        assert_eq!(histogram.count("addiu"), 2);
+        assert_eq!(histogram.count("rotr"), 1);
+
+        let metadata = Metadata {
+            symbols: vec![Symbol {
+                name: "main.main".to_string(),
+                start: 0x1000,
+                size: 16,
+            }],
+        };
+        let report = histogram.report(&metadata);
+        assert_eq!(report.instructions[0], ("addiu".to_string(), 2));
+        assert_eq!(report.unsupported.len(), 2);
+        assert_eq!(report.unsupported[0].pc, 0x1008);
+        assert_eq!(report.unsupported[0].detail, "rotr (requires MIPS32r2)");
+        assert_eq!(report.unsupported[1].detail, "syscall 9999");
+        assert_eq!(report.unsupported[1].first_step, 3);
+        assert_eq!(report.unsupported[1].symbol.as_deref(), Some("main.main"));
+        assert!(report.to_string().contains("syscall 9999"));
+    }
+}
@@ -4,31 +4,128 @@
 
 pub(crate) mod traces;
 
+mod address_space;
+pub use self::address_space::AddressSpace;
+
+pub mod auxv;
+pub use self::auxv::AuxVector;
+
+mod binary;
+pub use self::binary::{BINARY_STATE_MAGIC, BINARY_STATE_VERSION};
+
+mod clock;
+pub use self::clock::{Clock, DEFAULT_NANOS_PER_STEP};
+
+mod compat;
+pub use self::compat::{CompatReport, Finding};
+
+mod coverage;
+pub use self::coverage::Coverage;
+
+mod entropy;
+pub use self::entropy::EntropySource;
+
+mod error;
+pub use self::error::{CannonError, CannonResult};
+
+mod futex;
+
+mod histogram;
+pub use self::histogram::{Encounter, HistogramReport, InstructionHistogram};
+
+mod fd_table;
+pub use self::fd_table::{FdTable, OpenFile, DEFAULT_VIRTUAL_FILES};
+
+mod metadata;
+pub use self::metadata::{Metadata, Symbol, SymbolMatcher, UNKNOWN_SYMBOL};
+
+mod migrate;
+pub use self::migrate::{Migration, Schema, Versioned, STATE_SCHEMA, STATE_VERSION};
+
 mod memory;
-pub use self::memory::Memory;
+pub use self::memory::{Memory, MemoryOf, PageEncoding};
+
+mod memory_layout;
+pub use self::memory_layout::{MemoryLayout, KERNEL_REGION};
+
+mod merkle;
+pub use self::merkle::MerkleHasher;
 
 mod page;
-pub use self::page::CachedPage;
+pub use self::page::{CachedPage, CachedPageOf};
+
+mod pool;
+pub use self::pool::{PagePool, PagePoolOf, PagePoolStats};
+
+mod precompile;
+pub use self::precompile::{execute_precompile, PrecompileOracle, PRECOMPILE_HINT};
+
+mod preimage_part;
+pub use self::preimage_part::{
+    encode_keccak256_preimage_part, encode_precompile_preimage_part, keccak256_preimage_key,
+    preimage_part,
+};
+
+mod protection;
+pub use self::protection::{Access, PageProtection, Permissions};
+
+mod regions;
+pub use self::regions::{
+    MemoryRegion, MemoryRegions, HEAP_REGION, ORACLE_SCRATCH_REGION, PROGRAM_REGION, STACK_REGION,
+};
+
+mod replica;
+pub use self::replica::{ReplicaSeed, ReplicatedStep, StateDelta, StateReplica};
+
+mod scheduler;
+pub use self::scheduler::{RoundRobin, SchedulerPolicy, SeededRandom, ThreadId};
 
 mod state;
-pub use self::state::State;
+pub use self::state::{State, StateHashCache};
+
+#[cfg(feature = "tlb")]
+mod tlb;
 
 mod traits;
-pub use self::traits::{PreimageOracle, StateWitnessHasher};
+pub use self::traits::{PreimageOracle, StateWitnessFields, Stepper};
+
+mod verify;
+pub use verify::verify_step;
 
 mod witness;
-pub use witness::{StepWitness, STATE_WITNESS_SIZE};
+pub use witness::{
+    layout as witness_layout, Keccak256Hasher, MemAccess, MemAccessKind, StateWitnessDisplay,
+    StepWitness, STATE_WITNESS_SIZE,
+};
+
+pub use cannon_witness::{StateWitnessHasher, WitnessHasher, WitnessState};
 
 mod utils;
 
 mod types;
-pub use types::{Address, Fd, Gindex, Page, PageIndex, StateWitness, VMStatus};
+pub use types::{
+    Address, Fd, Gindex, Page, PageIndex, PageOf, SharedCachedPage, SharedCachedPageOf,
+    StateWitness, VMStatus,
+};
 
 mod mips;
-pub use mips::InstrumentedState;
+pub use mips::{
+    EntryCallback, FpTrapHandler, HookAction, InstrumentedState, PostStepHook, PreStepHook,
+};
 
 mod patch;
-pub use patch::{load_elf, patch_go, patch_stack, MultiReader};
+pub use patch::{
+    load_elf, load_elf_with_layout, patch_go, patch_stack, patch_stack_with_args, MultiReader,
+};
+
+mod patch_set;
+pub use patch_set::{PatchSet, Replacement, SymbolPatch};
+
+pub mod disasm;
+
+pub mod mem_access;
+
+pub mod proof;
 
 pub mod ser;
 
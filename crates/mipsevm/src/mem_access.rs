@@ -0,0 +1,214 @@
+//! This module contains helpers for accessing the big-endian [Memory] of the emulator at byte,
+//! halfword and word granularity, including the merging semantics of MIPS' unaligned word
+//! instructions (`lwl`, `lwr`, `swl` and `swr`).
+//!
+//! The word-level functions operate on the 32 bit word containing an address, and are what the
+//! emulator itself uses to execute loads and stores. The [Memory]-level functions build on them,
+//! so that tools pre-populating memory images do not need to reimplement the endianness logic.
+
+use crate::{Address, CannonError, CannonResult, Memory};
+
+/// Returns the byte at `address` within the word containing it.
+#[inline(always)]
+pub fn load_byte(word: u32, address: Address) -> u8 {
+    (word >> (24 - ((address & 0x3) << 3))) as u8
+}
+
+/// Returns the halfword at `address` within the word containing it. The lowest bit of the address
+/// is ignored.
+#[inline(always)]
+pub fn load_half(word: u32, address: Address) -> u16 {
+    (word >> (16 - ((address & 0x2) << 3))) as u16
+}
+
+/// Returns the word containing `address` with the byte at `address` replaced by `value`.
+#[inline(always)]
+pub fn store_byte(word: u32, address: Address, value: u8) -> u32 {
+    let sl = 24 - ((address & 0x3) << 3);
+    let mask = 0xFFFFFFFF ^ (0xFF << sl);
+    (word & mask) | ((value as u32) << sl)
+}
+
+/// Returns the word containing `address` with the halfword at `address` replaced by `value`. The
+/// lowest bit of the address is ignored.
+#[inline(always)]
+pub fn store_half(word: u32, address: Address, value: u16) -> u32 {
+    let sl = 16 - ((address & 0x2) << 3);
+    let mask = 0xFFFFFFFF ^ (0xFFFF << sl);
+    (word & mask) | ((value as u32) << sl)
+}
+
+/// Load word left: merges the bytes of the word from `address` up to the end of the word into the
+/// most significant bytes of `reg`.
+#[inline(always)]
+pub fn lwl(reg: u32, word: u32, address: Address) -> u32 {
+    let sl = (address & 0x3) << 3;
+    let mask = 0xFFFFFFFF << sl;
+    (reg & !mask) | (word << sl)
+}
+
+/// Load word right: merges the bytes of the word from its start up to and including `address`
+/// into the least significant bytes of `reg`.
+#[inline(always)]
+pub fn lwr(reg: u32, word: u32, address: Address) -> u32 {
+    let sr = 24 - ((address & 0x3) << 3);
+    let mask = 0xFFFFFFFFu32 >> sr;
+    (reg & !mask) | (word >> sr)
+}
+
+/// Store word left: merges the most significant bytes of `reg` into the word from `address` up
+/// to the end of the word.
+#[inline(always)]
+pub fn swl(word: u32, reg: u32, address: Address) -> u32 {
+    let sr = (address & 0x3) << 3;
+    let mask = 0xFFFFFFFFu32 >> sr;
+    (word & !mask) | (reg >> sr)
+}
+
+/// Store word right: merges the least significant bytes of `reg` into the word from its start up
+/// to and including `address`.
+#[inline(always)]
+pub fn swr(word: u32, reg: u32, address: Address) -> u32 {
+    let sl = 24 - ((address & 0x3) << 3);
+    let mask = 0xFFFFFFFF << sl;
+    (word & !mask) | (reg << sl)
+}
+
+/// Reads the byte at `address` from the [Memory].
+pub fn read_u8(memory: &mut Memory, address: Address) -> CannonResult<u8> {
+    Ok(load_byte(memory.get_memory(address & !0x3)?, address))
+}
+
+/// Reads the halfword at the 2-byte aligned `address` from the [Memory].
+pub fn read_u16(memory: &mut Memory, address: Address) -> CannonResult<u16> {
+    if address & 0x1 != 0 {
+        return Err(CannonError::UnalignedAccess(address));
+    }
+    Ok(load_half(memory.get_memory(address & !0x3)?, address))
+}
+
+/// Reads the word at the 4-byte aligned `address` from the [Memory].
+pub fn read_u32(memory: &mut Memory, address: Address) -> CannonResult<u32> {
+    memory.get_memory(address)
+}
+
+/// Reads the halfword at any `address` from the [Memory].
+pub fn read_u16_unaligned(memory: &mut Memory, address: Address) -> CannonResult<u16> {
+    let hi = read_u8(memory, address)?;
+    let lo = read_u8(memory, address.wrapping_add(1))?;
+    Ok(u16::from_be_bytes([hi, lo]))
+}
+
+/// Reads the word at any `address` from the [Memory], as the `lwl` / `lwr` instruction pair
+/// would.
+pub fn read_u32_unaligned(memory: &mut Memory, address: Address) -> CannonResult<u32> {
+    let last = address.wrapping_add(3);
+    let value = lwl(0, memory.get_memory(address & !0x3)?, address);
+    Ok(lwr(value, memory.get_memory(last & !0x3)?, last))
+}
+
+/// Writes the byte at `address` to the [Memory].
+pub fn write_u8(memory: &mut Memory, address: Address, value: u8) -> CannonResult<()> {
+    let word_address = address & !0x3;
+    let word = memory.get_memory(word_address)?;
+    memory.set_memory(word_address, store_byte(word, address, value))
+}
+
+/// Writes the halfword at the 2-byte aligned `address` to the [Memory].
+pub fn write_u16(memory: &mut Memory, address: Address, value: u16) -> CannonResult<()> {
+    if address & 0x1 != 0 {
+        return Err(CannonError::UnalignedAccess(address));
+    }
+    let word_address = address & !0x3;
+    let word = memory.get_memory(word_address)?;
+    memory.set_memory(word_address, store_half(word, address, value))
+}
+
+/// Writes the word at the 4-byte aligned `address` to the [Memory].
+pub fn write_u32(memory: &mut Memory, address: Address, value: u32) -> CannonResult<()> {
+    memory.set_memory(address, value)
+}
+
+/// Writes the halfword at any `address` to the [Memory].
+pub fn write_u16_unaligned(memory: &mut Memory, address: Address, value: u16) -> CannonResult<()> {
+    let [hi, lo] = value.to_be_bytes();
+    write_u8(memory, address, hi)?;
+    write_u8(memory, address.wrapping_add(1), lo)
+}
+
+/// Writes the word at any `address` to the [Memory], as the `swl` / `swr` instruction pair
+/// would.
+pub fn write_u32_unaligned(memory: &mut Memory, address: Address, value: u32) -> CannonResult<()> {
+    let first = address & !0x3;
+    let word = memory.get_memory(first)?;
+    memory.set_memory(first, swl(word, value, address))?;
+
+    let last = address.wrapping_add(3);
+    let word = memory.get_memory(last & !0x3)?;
+    memory.set_memory(last & !0x3, swr(word, value, last))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn word_bytes() {
+        let word = 0x11223344;
+        assert_eq!(
+            (0..4).map(|a| load_byte(word, a)).collect::<Vec<_>>(),
+            [0x11, 0x22, 0x33, 0x44]
+        );
+        assert_eq!(load_half(word, 0), 0x1122);
+        assert_eq!(load_half(word, 2), 0x3344);
+        assert_eq!(store_byte(word, 2, 0xAA), 0x1122AA44);
+        assert_eq!(store_half(word, 0, 0xAABB), 0xAABB3344);
+        assert_eq!(store_half(word, 2, 0xAABB), 0x1122AABB);
+    }
+
+    #[test]
+    fn unaligned_merging() {
+        let (reg, word) = (0xAABBCCDD, 0x11223344);
+        assert_eq!(lwl(reg, word, 0), 0x11223344);
+        assert_eq!(lwl(reg, word, 1), 0x223344DD);
+        assert_eq!(lwl(reg, word, 3), 0x44BBCCDD);
+        assert_eq!(lwr(reg, word, 0), 0xAABBCC11);
+        assert_eq!(lwr(reg, word, 2), 0xAA112233);
+        assert_eq!(lwr(reg, word, 3), 0x11223344);
+        assert_eq!(swl(word, reg, 0), 0xAABBCCDD);
+        assert_eq!(swl(word, reg, 1), 0x11AABBCC);
+        assert_eq!(swl(word, reg, 3), 0x112233AA);
+        assert_eq!(swr(word, reg, 0), 0xDD223344);
+        assert_eq!(swr(word, reg, 2), 0xBBCCDD44);
+        assert_eq!(swr(word, reg, 3), 0xAABBCCDD);
+    }
+
+    #[test]
+    fn memory_access() {
+        let mut memory = Memory::default();
+
+        // Unaligned words spanning a page boundary.
+        write_u32_unaligned(&mut memory, 0xFFE, 0x11223344).unwrap();
+        assert_eq!(read_u32(&mut memory, 0xFFC).unwrap(), 0x00001122);
+        assert_eq!(read_u32(&mut memory, 0x1000).unwrap(), 0x33440000);
+        assert_eq!(read_u32_unaligned(&mut memory, 0xFFE).unwrap(), 0x11223344);
+        assert_eq!(read_u16_unaligned(&mut memory, 0xFFF).unwrap(), 0x2233);
+
+        write_u16(&mut memory, 0x2002, 0xBEEF).unwrap();
+        write_u8(&mut memory, 0x2000, 0xAA).unwrap();
+        assert_eq!(read_u32(&mut memory, 0x2000).unwrap(), 0xAA00BEEF);
+        assert_eq!(read_u16(&mut memory, 0x2002).unwrap(), 0xBEEF);
+        assert_eq!(read_u8(&mut memory, 0x2003).unwrap(), 0xEF);
+
+        write_u16_unaligned(&mut memory, 0x2001, 0x1234).unwrap();
+        assert_eq!(read_u32(&mut memory, 0x2000).unwrap(), 0xAA1234EF);
+
+        // Aligned words round-trip through the unaligned helpers.
+        write_u32_unaligned(&mut memory, 0x3000, 0xCAFEBABE).unwrap();
+        assert_eq!(read_u32(&mut memory, 0x3000).unwrap(), 0xCAFEBABE);
+
+        assert!(read_u16(&mut memory, 0x2001).is_err());
+        assert!(write_u16(&mut memory, 0x2001, 0).is_err());
+        assert!(read_u32(&mut memory, 0x2001).is_err());
+    }
+}
@@ -1,49 +1,473 @@
 //! The memory module contains the [Memory] data structure and its functionality for the emulator.
 
+#[cfg(feature = "tlb")]
+use crate::tlb::PageTlb;
 use crate::{
-    page::{self},
-    types::SharedCachedPage,
-    utils::keccak_concat_hashes,
-    Address, Gindex, Page, PageIndex,
+    page::{self, PAGE_ADDRESS_SIZE},
+    pool::PagePoolOf,
+    types::{PageOf, SharedCachedPageOf},
+    Address, CannonError, CannonResult, Gindex, MerkleHasher, Page, PageIndex, PagePoolStats,
 };
-use anyhow::Result;
-use rustc_hash::FxHashMap;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use memmap2::Mmap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
-use std::{io::Read, rc::Rc};
+use std::{
+    cell::RefCell,
+    io::Read,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-/// The [Memory] struct represents the MIPS emulator's memory.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Memory {
+/// The [MemoryOf] struct represents a MIPS emulator's memory with pages of `2^P` bytes. The
+/// merkle tree over the 32-bit address space has the same depth for every page size; `P` only
+/// determines how many of its levels are stored within pages rather than in the node map.
+///
+/// Pages are shared through reference counting within a memory, but a clone copies every page, so
+/// that a clone is a snapshot that is not affected by later writes to the original.
+#[derive(Debug, Eq, PartialEq)]
+pub struct MemoryOf<const P: usize>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
     /// Map of generalized index -> the merkle root of each index. None if invalidated.
     pub nodes: FxHashMap<Gindex, Option<[u8; 32]>>,
     /// Map of page indices to [CachedPage]s.
-    pub pages: FxHashMap<PageIndex, SharedCachedPage>,
+    pub pages: FxHashMap<PageIndex, SharedCachedPageOf<P>>,
     /// We store two caches upfront; we often read instructions from one page and reserve another
     /// for scratch memory. This prevents map lookups for each instruction.
-    pub last_page: [(PageIndex, Option<SharedCachedPage>); 2],
+    pub last_page: [(PageIndex, Option<SharedCachedPageOf<P>>); 2],
+    /// The pool that pages are allocated from and released to.
+    pub(crate) pool: PagePoolOf<P>,
+    /// Pages that are backed by a memory-mapped state file, or were deserialized as zero pages,
+    /// and are not yet materialized.
+    pub(crate) lazy: LazyPages,
+    /// How the pages of the memory are serialized.
+    pub(crate) page_encoding: PageEncoding,
+    /// The hash function that the memory is merkleized with. Not serialized.
+    pub(crate) hasher: MerkleHasher,
+    /// The cache of recently used pages, consulted in place of `last_page`.
+    #[cfg(feature = "tlb")]
+    pub(crate) tlb: PageTlb<P>,
+    /// The generation of the memory's contents. See [MemoryOf::generation].
+    pub(crate) generation: Generation,
+    /// The indices of the pages written, allocated or freed since the last call to
+    /// [MemoryOf::take_dirty_pages], if tracked. See [MemoryOf::track_dirty_pages].
+    pub(crate) dirty: DirtyPages,
+}
+
+/// The [Memory] struct represents the MIPS emulator's memory.
+pub type Memory = MemoryOf<PAGE_ADDRESS_SIZE>;
+
+/// The source of [Generation]s, shared by all memories so that a generation is never reused.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// The [Generation] of a [MemoryOf] identifies its contents. It is drawn afresh whenever the
+/// contents change, and is only shared with clones of the memory that have not been changed
+/// since. A generation reflects the history of a memory rather than its contents, so it is not
+/// compared.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Generation(u64);
+
+impl Generation {
+    /// Draws a new, unique [Generation].
+    fn next() -> Self {
+        Self(NEXT_GENERATION.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self::next()
+    }
+}
+
+impl PartialEq for Generation {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Generation {}
+
+/// The [DirtyPages] of a [MemoryOf] are the indices of the pages that changed since they were last
+/// collected, if tracked. Like a [Generation], they reflect the history of a memory rather than its
+/// contents, so they are not compared.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DirtyPages(Option<FxHashSet<PageIndex>>);
+
+impl PartialEq for DirtyPages {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DirtyPages {}
+
+/// The [PageEncoding] enum describes how the pages of a [Memory] are serialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PageEncoding {
+    /// Every page is serialized with its zlib-compressed data, as Go Cannon serializes pages.
+    #[default]
+    Go,
+    /// Pages that are entirely zero are serialized by their index alone, and the data of all
+    /// others is prefixed with a [crate::ser::BlobHeader]. This shrinks the serialized states of
+    /// sparse address spaces considerably, but cannot be read by Go Cannon.
+    Compact,
+}
+
+/// The [LazyPages] struct holds the pages of a memory-mapped state file, and the zero pages of a
+/// deserialized state, that have not been accessed yet. Pages are copied into the [Memory] on
+/// first access.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LazyPages {
+    /// The memory-mapped state file.
+    backing: Option<Rc<Mmap>>,
+    /// Map of page indices to the offsets of their data within the backing file.
+    offsets: FxHashMap<PageIndex, usize>,
+    /// The indices of zero pages, which need no backing.
+    zeroed: FxHashSet<PageIndex>,
+}
+
+impl PartialEq for LazyPages {
+    fn eq(&self, other: &Self) -> bool {
+        let same_backing = match (&self.backing, &other.backing) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_backing && self.offsets == other.offsets && self.zeroed == other.zeroed
+    }
 }
 
-impl Default for Memory {
+impl Eq for LazyPages {}
+
+impl<const P: usize> Clone for MemoryOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// Copies every page into the clone rather than sharing it, so that writes to either memory
+    /// are not visible in the other. The page lookup caches start out empty.
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            pages: self
+                .pages
+                .iter()
+                .map(|(&index, page)| (index, Rc::new(RefCell::new(*page.borrow()))))
+                .collect(),
+            last_page: [(!0u64, None), (!0u64, None)],
+            pool: self.pool.clone(),
+            lazy: self.lazy.clone(),
+            page_encoding: self.page_encoding,
+            hasher: self.hasher,
+            #[cfg(feature = "tlb")]
+            tlb: PageTlb::default(),
+            generation: self.generation,
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+impl<const P: usize> Default for MemoryOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
     fn default() -> Self {
         Self {
             nodes: FxHashMap::default(),
             pages: FxHashMap::default(),
             last_page: [(!0u64, None), (!0u64, None)],
+            pool: PagePoolOf::default(),
+            lazy: LazyPages::default(),
+            page_encoding: PageEncoding::default(),
+            hasher: MerkleHasher::default(),
+            #[cfg(feature = "tlb")]
+            tlb: PageTlb::default(),
+            generation: Generation::default(),
+            dirty: DirtyPages::default(),
         }
     }
 }
 
-impl Memory {
-    /// Returns the number of allocated pages in memory.
+impl<const P: usize> MemoryOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The number of address bits within a page.
+    pub const PAGE_ADDRESS_SIZE: usize = P;
+    /// The number of address bits that select a page.
+    pub const PAGE_KEY_SIZE: usize = 32 - P;
+    /// The size of a page, in bytes.
+    pub const PAGE_SIZE: usize = 1 << P;
+    /// The mask of the address bits within a page.
+    pub const PAGE_ADDRESS_MASK: usize = Self::PAGE_SIZE - 1;
+    /// The mask of the page index bits of a generalized index.
+    pub const PAGE_KEY_MASK: usize = (1 << Self::PAGE_KEY_SIZE) - 1;
+
+    /// Creates an empty [Memory] that is merkleized with the given [MerkleHasher].
+    ///
+    /// ### Takes
+    /// - `hasher`: The [MerkleHasher] to merkleize the memory with.
+    ///
+    /// ### Returns
+    /// - The empty [Memory].
+    pub fn with_hasher(hasher: MerkleHasher) -> Self {
+        Self {
+            hasher,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the [MerkleHasher] that the [Memory] is merkleized with.
+    pub fn hasher(&self) -> MerkleHasher {
+        self.hasher
+    }
+
+    /// Returns the generation of the [Memory]'s contents, which changes with every write,
+    /// allocation or release of a page, and is unique across all memories. A memory whose
+    /// generation has not changed still has the same merkle root.
+    ///
+    /// Writes made directly to the data of a page, bypassing the [Memory], must be followed by
+    /// [MemoryOf::invalidate] to be reflected in both the merkle root and the generation.
+    pub fn generation(&self) -> u64 {
+        self.generation.0
+    }
+
+    /// Returns the [PageEncoding] that the [Memory]'s pages are serialized with.
+    pub fn page_encoding(&self) -> PageEncoding {
+        self.page_encoding
+    }
+
+    /// Sets the [PageEncoding] that the [Memory]'s pages are serialized with. Either encoding is
+    /// accepted when deserializing.
+    ///
+    /// ### Takes
+    /// - `page_encoding`: The [PageEncoding] to serialize pages with.
+    pub fn set_page_encoding(&mut self, page_encoding: PageEncoding) {
+        self.page_encoding = page_encoding;
+    }
+
+    /// Switches the [MerkleHasher] that the [Memory] is merkleized with, e.g. after loading a
+    /// state that was serialized without it. All cached nodes are invalidated.
+    ///
+    /// ### Takes
+    /// - `hasher`: The [MerkleHasher] to merkleize the memory with.
+    pub fn set_hasher(&mut self, hasher: MerkleHasher) {
+        if hasher == self.hasher {
+            return;
+        }
+        self.hasher = hasher;
+        self.generation = Generation::next();
+        for page in self.pages.values() {
+            page.borrow_mut().invalidate_full();
+        }
+        self.nodes.values_mut().for_each(|node| *node = None);
+    }
+
+    /// Invalidates the merkle cache of every page and node of the [Memory], e.g. after the data of
+    /// pages was written directly, bypassing the [Memory].
+    pub fn invalidate_all(&mut self) {
+        self.generation = Generation::next();
+        for (&page_index, page) in self.pages.iter() {
+            page.borrow_mut().invalidate_full();
+            if let Some(ref mut dirty) = self.dirty.0 {
+                dirty.insert(page_index);
+            }
+        }
+        self.nodes.values_mut().for_each(|node| *node = None);
+    }
+
+    /// Starts tracking the pages that are written, allocated or freed, to be collected with
+    /// [MemoryOf::take_dirty_pages]. This allows a copy of the [Memory] to be kept in sync by
+    /// transferring only the pages that changed.
+    pub fn track_dirty_pages(&mut self) {
+        self.dirty.0.get_or_insert_with(FxHashSet::default);
+    }
+
+    /// Returns the pages that were written, allocated or freed since dirty pages are tracked or
+    /// since the last call, and resets the tracked set.
+    ///
+    /// ### Returns
+    /// - The index of each dirty page, with a copy of its data, or `None` if the page was freed.
+    ///   Empty if dirty pages are not tracked.
+    pub fn take_dirty_pages(&mut self) -> Vec<(PageIndex, Option<Box<PageOf<P>>>)> {
+        let Some(dirty) = self.dirty.0.as_mut() else {
+            return Vec::new();
+        };
+        let mut indices = dirty.drain().collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .map(|page_index| {
+                let data = self
+                    .pages
+                    .get(&page_index)
+                    .map(|page| Box::new(page.borrow().data));
+                (page_index, data)
+            })
+            .collect()
+    }
+
+    /// Records a write to, or the allocation or release of, the page at the given index, if
+    /// dirty pages are tracked.
+    #[inline(always)]
+    fn mark_dirty(&mut self, page_index: PageIndex) {
+        if let Some(ref mut dirty) = self.dirty.0 {
+            dirty.insert(page_index);
+        }
+    }
+
+    /// Takes a zeroed page from the [PagePoolOf]. Pooled pages carry the merkle cache of a zero page
+    /// for the default [MerkleHasher], which is invalidated for any other hasher.
+    fn acquire_page(&mut self) -> SharedCachedPageOf<P> {
+        let page = self.pool.acquire();
+        if self.hasher != MerkleHasher::default() {
+            page.borrow_mut().invalidate_full();
+        }
+        page
+    }
+
+    /// Returns the number of allocated pages in memory, including pages that are not yet
+    /// materialized.
     pub fn page_count(&self) -> usize {
-        self.pages.len()
+        self.pages.len() + self.lazy.offsets.len() + self.lazy.zeroed.len()
+    }
+
+    /// Returns the indices of all allocated pages in memory, in no particular order.
+    pub fn page_indices(&self) -> impl Iterator<Item = PageIndex> + '_ {
+        self.pages
+            .keys()
+            .chain(self.lazy.offsets.keys())
+            .chain(self.lazy.zeroed.iter())
+            .copied()
+    }
+
+    /// Registers pages that are backed by a memory-mapped state file. The pages are copied into
+    /// the [Memory] on first access.
+    ///
+    /// ### Takes
+    /// - `backing`: The memory-mapped state file.
+    /// - `offsets`: Map of page indices to the offsets of their data within `backing`.
+    pub(crate) fn register_lazy_pages(
+        &mut self,
+        backing: Rc<Mmap>,
+        offsets: FxHashMap<PageIndex, usize>,
+    ) {
+        self.generation = Generation::next();
+        for &page_index in offsets.keys() {
+            let mut key = (1 << Self::PAGE_KEY_SIZE) | page_index;
+            while key > 0 {
+                self.nodes.insert(key, None);
+                key >>= 1;
+            }
+        }
+        self.lazy.backing = Some(backing);
+        self.lazy.offsets = offsets;
+    }
+
+    /// Registers a zero page that is allocated on first access. Zero pages do not contribute to
+    /// the merkle tree, so none of its nodes are invalidated.
+    ///
+    /// ### Takes
+    /// - `page_index`: The page index of the zero page.
+    fn register_zero_page(&mut self, page_index: PageIndex) {
+        self.generation = Generation::next();
+        self.lazy.zeroed.insert(page_index);
+    }
+
+    /// Copies a page from the memory-mapped state file into the [Memory], or allocates a
+    /// registered zero page, if it is not yet materialized.
+    ///
+    /// ### Takes
+    /// - `page_index`: The page index of the page to materialize.
+    ///
+    /// ### Returns
+    /// - The materialized [CachedPage], or `None` if the page is not lazily allocated.
+    fn materialize(&mut self, page_index: PageIndex) -> Option<SharedCachedPageOf<P>> {
+        if self.lazy.zeroed.remove(&page_index) {
+            let page = self.acquire_page();
+            self.pages.insert(page_index, Rc::clone(&page));
+            return Some(page);
+        }
+
+        let offset = self.lazy.offsets.remove(&page_index)?;
+        let backing = Rc::clone(self.lazy.backing.as_ref()?);
+
+        let page = self.pool.acquire();
+        {
+            let mut page = page.borrow_mut();
+            page.data
+                .copy_from_slice(&backing[offset..offset + Self::PAGE_SIZE]);
+            page.invalidate_full();
+        }
+        self.pages.insert(page_index, Rc::clone(&page));
+        Some(page)
+    }
+
+    /// Copies all pages that are not yet materialized from the memory-mapped state file into the
+    /// [Memory], and allocates all registered zero pages.
+    pub fn materialize_all(&mut self) {
+        let indices = self
+            .lazy
+            .offsets
+            .keys()
+            .chain(self.lazy.zeroed.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        for page_index in indices {
+            self.materialize(page_index);
+        }
+    }
+
+    /// Visits the data of all allocated pages in ascending page index order, without
+    /// materializing any pages.
+    ///
+    /// ### Takes
+    /// - `f`: A function that takes a [PageIndex] and the [Page] data.
+    pub(crate) fn visit_pages<E>(
+        &self,
+        mut f: impl FnMut(PageIndex, &PageOf<P>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let zero_page: PageOf<P> = [0u8; 1 << P];
+        let mut indices = self.page_indices().collect::<Vec<_>>();
+        indices.sort_unstable();
+        for page_index in indices {
+            match self.pages.get(&page_index) {
+                Some(page) => f(page_index, &page.borrow().data)?,
+                None if self.lazy.zeroed.contains(&page_index) => f(page_index, &zero_page)?,
+                None => {
+                    let backing = self
+                        .lazy
+                        .backing
+                        .as_ref()
+                        .expect("Lazy pages have a backing");
+                    let offset = self.lazy.offsets[&page_index];
+                    let data: &PageOf<P> = backing[offset..offset + Self::PAGE_SIZE]
+                        .try_into()
+                        .expect("Slice is page-sized");
+                    f(page_index, data)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the usage counters of the [PagePoolOf] that pages are allocated from.
+    pub fn pool_stats(&self) -> PagePoolStats {
+        self.pool.stats()
     }
 
     /// Performs an operation on all pages in the memory.
     ///
     /// ### Takes
     /// - `f`: A function that takes a [PageIndex] and a shared reference to a [CachedPage].
-    pub fn for_each_page(&mut self, mut f: impl FnMut(PageIndex, SharedCachedPage)) {
+    pub fn for_each_page(&mut self, mut f: impl FnMut(PageIndex, SharedCachedPageOf<P>)) {
+        self.materialize_all();
         self.pages.iter().for_each(|(key, page)| {
             f(*key, Rc::clone(page));
         });
@@ -55,20 +479,24 @@ impl Memory {
     /// - `address`: The address to invalidate.
     ///
     /// ### Returns
-    /// - A [Result] indicating if the operation was successful.
-    pub fn invalidate(&mut self, address: Address) -> Result<()> {
+    /// - A [CannonResult] indicating if the operation was successful, or a
+    ///   [CannonError::UnalignedAccess] if the address is not aligned to 4 bytes.
+    pub fn invalidate(&mut self, address: Address) -> CannonResult<()> {
         if address & 0x3 != 0 {
-            panic!("Unaligned memory access: {:x}", address);
+            return Err(CannonError::UnalignedAccess(address));
         }
+        self.generation = Generation::next();
 
         // Find the page and invalidate the address within it.
-        match self.page_lookup(address as u64 >> page::PAGE_ADDRESS_SIZE) {
+        let page_index = address as u64 >> Self::PAGE_ADDRESS_SIZE;
+        match self.page_lookup(page_index) {
             Some(page) => {
+                self.mark_dirty(page_index);
                 let mut page = page.borrow_mut();
                 let prev_valid = !page.valid[1];
 
                 // Invalidate the address within the page.
-                page.invalidate(address & page::PAGE_ADDRESS_MASK as u32)?;
+                page.invalidate(address & Self::PAGE_ADDRESS_MASK as u32)?;
 
                 // If the page was already invalid before, then nodes to the memory
                 // root will also still be invalid.
@@ -83,7 +511,7 @@ impl Memory {
         }
 
         // Find the generalized index of the first page covering the address
-        let mut g_index = ((1u64 << 32) | address as u64) >> page::PAGE_ADDRESS_SIZE;
+        let mut g_index = ((1u64 << 32) | address as u64) >> Self::PAGE_ADDRESS_SIZE;
         // Invalidate all nodes in the branch
         while g_index > 0 {
             self.nodes.insert(g_index, None);
@@ -101,54 +529,94 @@ impl Memory {
     ///
     /// ### Returns
     /// - A reference to the [CachedPage] if it exists.
-    pub fn page_lookup(&mut self, page_index: PageIndex) -> Option<SharedCachedPage> {
+    pub fn page_lookup(&mut self, page_index: PageIndex) -> Option<SharedCachedPageOf<P>> {
         // Check caches before maps
+        #[cfg(feature = "tlb")]
+        if let Some(page) = self.tlb.lookup(page_index) {
+            return Some(page);
+        }
+        #[cfg(not(feature = "tlb"))]
         if let Some((_, Some(page))) = self.last_page.iter().find(|(key, _)| *key == page_index) {
-            Some(Rc::clone(page))
-        } else if let Some(page) = self.pages.get(&page_index) {
-            // Cache the page
+            return Some(Rc::clone(page));
+        }
+
+        let page = match self.pages.get(&page_index) {
+            Some(page) => Rc::clone(page),
+            None => self.materialize(page_index)?,
+        };
+
+        // Cache the page
+        #[cfg(feature = "tlb")]
+        self.tlb.insert(page_index, &page);
+        #[cfg(not(feature = "tlb"))]
+        {
             self.last_page[1] = self.last_page[0].clone();
             self.last_page[0] = (page_index, Some(page.clone()));
-
-            Some(Rc::clone(page))
-        } else {
-            None
         }
+
+        Some(page)
     }
 
-    pub fn merkleize_subtree(&mut self, g_index: Gindex) -> Result<[u8; 32]> {
+    /// Removes a page from the page caches, so that a replaced or freed page is never served
+    /// from them.
+    ///
+    /// ### Takes
+    /// - `page_index`: The page index of the page to remove.
+    fn uncache_page(&mut self, page_index: PageIndex) {
+        self.last_page
+            .iter_mut()
+            .filter(|(key, _)| *key == page_index)
+            .for_each(|entry| *entry = (!0u64, None));
+        #[cfg(feature = "tlb")]
+        self.tlb.evict(page_index);
+    }
+
+    pub fn merkleize_subtree(&mut self, g_index: Gindex) -> CannonResult<[u8; 32]> {
         // Fetch the amount of bits required to represent the generalized index
         let bits = 64 - g_index.leading_zeros();
-        if bits > 28 {
-            anyhow::bail!("Gindex is too deep")
+        if bits as usize > page::MEMORY_TREE_DEPTH + 1 {
+            return Err(CannonError::Other(anyhow::anyhow!("Gindex is too deep")));
         }
 
-        if bits > page::PAGE_KEY_SIZE as u32 {
-            let depth_into_page = bits - 1 - page::PAGE_KEY_SIZE as u32;
-            let page_index = (g_index >> depth_into_page) & page::PAGE_KEY_MASK as u64;
-            return self.pages.get(&page_index).map_or(
-                Ok(page::ZERO_HASHES[28 - bits as usize]),
+        if bits > Self::PAGE_KEY_SIZE as u32 {
+            let depth_into_page = bits - 1 - Self::PAGE_KEY_SIZE as u32;
+            let page_index = (g_index >> depth_into_page) & Self::PAGE_KEY_MASK as u64;
+            // Zero pages that are not yet materialized hash like absent pages.
+            let page = match self.pages.get(&page_index) {
+                Some(page) => Some(Rc::clone(page)),
+                None if self.lazy.zeroed.contains(&page_index) => None,
+                None => self.materialize(page_index),
+            };
+            let hasher = self.hasher;
+            return page.map_or(
+                Ok(hasher.zero_hashes()[page::MEMORY_TREE_DEPTH + 1 - bits as usize]),
                 |page| {
                     let page_g_index =
                         (1 << depth_into_page) | (g_index & ((1 << depth_into_page) - 1));
-                    page.borrow_mut().merkleize_subtree(page_g_index)
+                    Ok(page
+                        .borrow_mut()
+                        .merkleize_subtree_with(page_g_index, hasher)?)
                 },
             );
         }
 
-        if bits > page::PAGE_KEY_SIZE as u32 + 1 {
-            anyhow::bail!("Cannot jump into intermediate node of page")
+        if bits > Self::PAGE_KEY_SIZE as u32 + 1 {
+            return Err(CannonError::Other(anyhow::anyhow!(
+                "Cannot jump into intermediate node of page"
+            )));
         }
 
         match self.nodes.get(&g_index) {
             Some(Some(node)) => return Ok(*node),
-            None => return Ok(page::ZERO_HASHES[28 - bits as usize]),
+            None => {
+                return Ok(self.hasher.zero_hashes()[page::MEMORY_TREE_DEPTH + 1 - bits as usize])
+            }
             _ => { /* noop */ }
         }
 
         let left = self.merkleize_subtree(g_index << 1)?;
         let right = self.merkleize_subtree((g_index << 1) | 1)?;
-        let result = *keccak_concat_hashes(left, right);
+        let result = self.hasher.hash_pair(left, right);
 
         self.nodes.insert(g_index, Some(result));
 
@@ -159,7 +627,8 @@ impl Memory {
     ///
     /// ### Returns
     /// - The 32 byte merkle root hash of the [Memory].
-    pub fn merkle_root(&mut self) -> Result<[u8; 32]> {
+    pub fn merkle_root(&mut self) -> CannonResult<[u8; 32]> {
+        let _span = crate::traces::debug_span!(target: "mipsevm::memory", "merkle_root");
         self.merkleize_subtree(1)
     }
 
@@ -169,8 +638,11 @@ impl Memory {
     /// - `address`: The address to compute the merkle proof for.
     ///
     /// ### Returns
-    /// - The 896 bit merkle proof for the given address.
-    pub fn merkle_proof(&mut self, address: Address) -> Result<[u8; 28 * 32]> {
+    /// - The 896 byte merkle proof for the given address.
+    pub fn merkle_proof(
+        &mut self,
+        address: Address,
+    ) -> CannonResult<[u8; (page::MEMORY_TREE_DEPTH + 1) * 32]> {
         let proof = self.traverse_branch(1, address, 0)?;
 
         proof
@@ -178,7 +650,9 @@ impl Memory {
             .flatten()
             .collect::<Vec<u8>>()
             .try_into()
-            .map_err(|_| anyhow::anyhow!("Failed to convert proof to fixed array"))
+            .map_err(|_| {
+                CannonError::Other(anyhow::anyhow!("Failed to convert proof to fixed array"))
+            })
     }
 
     /// Traverse a branch of the merkle tree, generating a proof for the given address.
@@ -195,15 +669,15 @@ impl Memory {
         parent: Gindex,
         address: Address,
         depth: u8,
-    ) -> Result<Vec<[u8; 32]>> {
-        if depth == 32 - 5 {
-            let mut proof = Vec::with_capacity(32 - 5 + 1);
+    ) -> CannonResult<Vec<[u8; 32]>> {
+        if depth as usize == page::MEMORY_TREE_DEPTH {
+            let mut proof = Vec::with_capacity(page::MEMORY_TREE_DEPTH + 1);
             proof.push(self.merkleize_subtree(parent)?);
             return Ok(proof);
         }
 
-        if depth > 32 - 5 {
-            anyhow::bail!("Traversed too deep")
+        if depth as usize > page::MEMORY_TREE_DEPTH {
+            return Err(CannonError::Other(anyhow::anyhow!("Traversed too deep")));
         }
 
         let mut local = parent << 1;
@@ -227,16 +701,16 @@ impl Memory {
     /// - `value`: The 32 bit value to set.
     ///
     /// ### Returns
-    /// - A [Result] indicating if the operation was successful.
+    /// - A [CannonResult] indicating if the operation was successful.
     #[inline(always)]
-    pub fn set_memory(&mut self, address: Address, value: u32) -> Result<()> {
+    pub fn set_memory(&mut self, address: Address, value: u32) -> CannonResult<()> {
         // Address must be aligned to 4 bytes
         if address & 0x3 != 0 {
-            anyhow::bail!("Unaligned memory access: {:x}", address);
+            return Err(CannonError::UnalignedAccess(address));
         }
 
-        let page_index = address as PageIndex >> page::PAGE_ADDRESS_SIZE as u64;
-        let page_address = address as usize & page::PAGE_ADDRESS_MASK;
+        let page_index = address as PageIndex >> Self::PAGE_ADDRESS_SIZE as u64;
+        let page_address = address as usize & Self::PAGE_ADDRESS_MASK;
 
         // Attempt to look up the page.
         // - If it does exist, invalidate it before changing it.
@@ -246,7 +720,7 @@ impl Memory {
             .map(|page| {
                 // If the page exists, invalidate it - the value will change.
                 self.invalidate(address)?;
-                Ok::<_, anyhow::Error>(page)
+                Ok::<_, CannonError>(page)
             })
             .unwrap_or_else(|| {
                 let page = self.alloc_page(page_index)?;
@@ -269,18 +743,18 @@ impl Memory {
     /// ### Returns
     /// - The 32 bit value at the given address.
     #[inline(always)]
-    pub fn get_memory(&mut self, address: Address) -> Result<u32> {
+    pub fn get_memory(&mut self, address: Address) -> CannonResult<u32> {
         // Address must be aligned to 4 bytes
         if address & 0x3 != 0 {
-            anyhow::bail!("Unaligned memory access: {:x}", address);
+            return Err(CannonError::UnalignedAccess(address));
         }
 
-        match self.page_lookup(address as u64 >> page::PAGE_ADDRESS_SIZE as u64) {
+        match self.page_lookup(address as u64 >> Self::PAGE_ADDRESS_SIZE as u64) {
             Some(page) => {
-                let page_address = address as usize & page::PAGE_ADDRESS_MASK;
-                Ok(u32::from_be_bytes(
-                    page.borrow().data[page_address..page_address + 4].try_into()?,
-                ))
+                let page_address = address as usize & Self::PAGE_ADDRESS_MASK;
+                let mut word = [0u8; 4];
+                word.copy_from_slice(&page.borrow().data[page_address..page_address + 4]);
+                Ok(u32::from_be_bytes(word))
             }
             None => Ok(0),
         }
@@ -293,11 +767,17 @@ impl Memory {
     ///
     /// ### Returns
     /// - A reference to the allocated [CachedPage].
-    pub fn alloc_page(&mut self, page_index: PageIndex) -> Result<SharedCachedPage> {
-        let page = SharedCachedPage::default();
+    pub fn alloc_page(&mut self, page_index: PageIndex) -> CannonResult<SharedCachedPageOf<P>> {
+        crate::traces::trace!(target: "mipsevm::memory", page_index, "Allocating page");
+        self.lazy.offsets.remove(&page_index);
+        self.lazy.zeroed.remove(&page_index);
+        self.uncache_page(page_index);
+        let page = self.acquire_page();
         self.pages.insert(page_index, page.clone());
+        self.generation = Generation::next();
+        self.mark_dirty(page_index);
 
-        let mut key = (1 << page::PAGE_KEY_SIZE) | page_index;
+        let mut key = (1 << Self::PAGE_KEY_SIZE) | page_index;
         while key > 0 {
             self.nodes.insert(key, None);
             key >>= 1;
@@ -305,6 +785,37 @@ impl Memory {
         Ok(page)
     }
 
+    /// Free the page at the given page index, returning it to the [PagePoolOf]. The memory covered by
+    /// the page reads as zero afterwards.
+    ///
+    /// ### Takes
+    /// - `page_index`: The page index of the page to free.
+    ///
+    /// ### Returns
+    /// - `true` if a page was allocated at the index, otherwise `false`.
+    pub fn free_page(&mut self, page_index: PageIndex) -> bool {
+        self.materialize(page_index);
+        let Some(page) = self.pages.remove(&page_index) else {
+            return false;
+        };
+        crate::traces::trace!(target: "mipsevm::memory", page_index, "Freeing page");
+        self.uncache_page(page_index);
+        self.generation = Generation::next();
+        self.mark_dirty(page_index);
+
+        // The page's subtree is now empty, and all of its ancestors must be recomputed.
+        let mut key = (1 << Self::PAGE_KEY_SIZE) | page_index;
+        self.nodes.remove(&key);
+        key >>= 1;
+        while key > 0 {
+            self.nodes.insert(key, None);
+            key >>= 1;
+        }
+
+        self.pool.release(page);
+        true
+    }
+
     /// Set a range of memory in the [Memory] at a given address.
     ///
     /// ### Takes
@@ -312,30 +823,135 @@ impl Memory {
     /// - `data`: The data to set.
     ///
     /// ### Returns
-    /// - A [Result] indicating if the operation was successful.
-    pub fn set_memory_range<T: Read>(&mut self, address: Address, data: T) -> Result<()> {
-        let mut address = address;
+    /// - A [CannonResult] indicating if the operation was successful.
+    pub fn set_memory_range<T: Read>(&mut self, address: Address, data: T) -> CannonResult<()> {
         let mut data = data;
-        loop {
-            let page_index = address as PageIndex >> page::PAGE_ADDRESS_SIZE as u64;
-            let page_address = address as usize & page::PAGE_ADDRESS_MASK;
-
-            let page = self
-                .page_lookup(page_index)
-                .map(Ok)
-                .unwrap_or_else(|| self.alloc_page(page_index))?;
-            page.borrow_mut().invalidate_full();
+        let mut buf = Vec::default();
+        data.read_to_end(&mut buf).map_err(anyhow::Error::from)?;
+        self.set_range(address, &buf)
+    }
+
+    /// Writes a contiguous range of bytes to the [Memory], spanning as many pages as necessary.
+    /// Missing pages are allocated upfront, each page is copied into with a single write, and the
+    /// merkle tree is invalidated once per page rather than once per word.
+    ///
+    /// ### Takes
+    /// - `address`: The address to write the data at.
+    /// - `data`: The data to write.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating if the operation was successful.
+    pub fn set_range(&mut self, address: Address, data: &[u8]) -> CannonResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let end = address as u64 + data.len() as u64;
+        if end > 1 << 32 {
+            return Err(CannonError::Other(anyhow::anyhow!(
+                "Memory range {:08x} - {:x} is out of the 32-bit address space",
+                address,
+                end
+            )));
+        }
+
+        let first_page = address as PageIndex >> Self::PAGE_ADDRESS_SIZE;
+        let last_page = (end - 1) >> Self::PAGE_ADDRESS_SIZE;
+        crate::traces::debug!(
+            target: "mipsevm::memory",
+            address,
+            len = data.len(),
+            pages = last_page - first_page + 1,
+            "Writing memory range"
+        );
+
+        self.generation = Generation::next();
+
+        // Allocate all missing pages at once, without touching the merkle tree.
+        for page_index in first_page..=last_page {
+            if !self.pages.contains_key(&page_index) && self.materialize(page_index).is_none() {
+                self.uncache_page(page_index);
+                let page = self.acquire_page();
+                self.pages.insert(page_index, page);
+            }
+        }
 
-            match data.read(&mut page.borrow_mut().data[page_address..]) {
-                Ok(n) => {
-                    if n == 0 {
-                        return Ok(());
-                    }
-                    address += n as u32;
+        let mut offset = 0;
+        for page_index in first_page..=last_page {
+            let page_start = page_index << Self::PAGE_ADDRESS_SIZE;
+            let start = (address as u64).max(page_start) - page_start;
+            let len = (Self::PAGE_SIZE as u64 - start).min(end - page_start - start) as usize;
+
+            let mut page = self.pages[&page_index].borrow_mut();
+            page.data[start as usize..start as usize + len]
+                .copy_from_slice(&data[offset..offset + len]);
+            page.invalidate_full();
+            offset += len;
+            if let Some(ref mut dirty) = self.dirty.0 {
+                dirty.insert(page_index);
+            }
+        }
+
+        // Invalidate the branches of all written pages. Ancestors of an invalid node are invalid
+        // as well, so each branch is only walked until it joins an already invalidated one.
+        for page_index in first_page..=last_page {
+            let mut key = (1 << Self::PAGE_KEY_SIZE) | page_index;
+            while key > 0 {
+                if matches!(self.nodes.insert(key, None), Some(None)) {
+                    break;
                 }
-                Err(e) => return Err(e.into()),
-            };
+                key >>= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Exports the [Memory] as a sparse image, containing the data of every allocated page in
+    /// ascending address order. Pages that are not yet materialized from a memory-mapped state
+    /// file are read from the file without being materialized.
+    ///
+    /// ### Returns
+    /// - A list of the page-aligned base address and data of each allocated page.
+    pub fn dump_sparse(&self) -> Vec<(Address, PageOf<P>)> {
+        let mut image = Vec::with_capacity(self.page_count());
+        self.visit_pages(|page_index, data| {
+            image.push(((page_index << Self::PAGE_ADDRESS_SIZE) as Address, *data));
+            Ok::<_, std::convert::Infallible>(())
+        })
+        .expect("Visiting pages is infallible");
+        image
+    }
+
+    /// Imports a sparse image, as exported by [Memory::dump_sparse], into the [Memory]. Each page
+    /// of the image replaces the page at its address in full; pages that are not part of the image
+    /// are left untouched.
+    ///
+    /// ### Takes
+    /// - `image`: A list of page-aligned base addresses and the data of the page at each.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating if the operation was successful.
+    pub fn restore_sparse(
+        &mut self,
+        image: impl IntoIterator<Item = (Address, PageOf<P>)>,
+    ) -> CannonResult<()> {
+        for (address, data) in image {
+            if address as usize & Self::PAGE_ADDRESS_MASK != 0 {
+                return Err(CannonError::Other(anyhow::anyhow!(
+                    "Sparse image page address {:08x} is not page-aligned",
+                    address
+                )));
+            }
+
+            let page = self.alloc_page(address as PageIndex >> Self::PAGE_ADDRESS_SIZE)?;
+            if data.iter().any(|&b| b != 0) {
+                let mut page = page.borrow_mut();
+                page.data = data;
+                page.invalidate_full();
+            }
         }
+        Ok(())
     }
 
     /// Returns a human-readable string describing the size of the [Memory].
@@ -344,7 +960,7 @@ impl Memory {
     /// - A human-readable string describing the size of the [Memory] in B, KiB,
     ///   MiB, GiB, TiB, PiB, or EiB.
     pub fn usage(&self) -> String {
-        let total = (self.pages.len() * page::PAGE_SIZE) as u64;
+        let total = (self.page_count() * Self::PAGE_SIZE) as u64;
         const UNIT: u64 = 1024;
         if total < UNIT {
             return format!("{} B", total);
@@ -365,19 +981,35 @@ impl Memory {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A serialized page of [Memory]. Zero pages serialized with [PageEncoding::Compact] have no
+/// data.
+#[derive(Serialize, Deserialize, Debug, Default)]
 struct PageEntry {
     index: PageIndex,
-    #[serde(with = "crate::ser::page_base64")]
-    data: Page,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<PageData>,
 }
 
-impl Default for PageEntry {
-    fn default() -> Self {
-        Self {
-            index: Default::default(),
-            data: [0u8; page::PAGE_SIZE],
-        }
+/// The contents of a page, as a base64 string of its compressed data.
+#[derive(Serialize, Deserialize, Debug)]
+struct PageData(String);
+
+impl PageData {
+    /// Encodes the data of a page with the given [PageEncoding].
+    fn encode(data: &Page, page_encoding: PageEncoding) -> std::io::Result<Self> {
+        let compressed = match page_encoding {
+            PageEncoding::Go => crate::ser::compress_bytes(data)?,
+            PageEncoding::Compact => crate::ser::compress_blob(crate::ser::Codec::Zlib, data)?,
+        };
+        Ok(Self(BASE64_STANDARD.encode(compressed)))
+    }
+
+    /// Decodes the data of a page serialized with either [PageEncoding].
+    fn decode(&self) -> Result<Page, String> {
+        let compressed = BASE64_STANDARD.decode(&self.0).map_err(|e| e.to_string())?;
+        let data = crate::ser::decompress_blob(&compressed).map_err(|e| e.to_string())?;
+        data.try_into()
+            .map_err(|data: Vec<u8>| format!("Invalid page length: {} bytes", data.len()))
     }
 }
 
@@ -386,16 +1018,20 @@ impl Serialize for Memory {
     where
         S: serde::Serializer,
     {
-        let mut page_entries: Vec<PageEntry> = self
-            .pages
-            .iter()
-            .map(|(&k, p)| PageEntry {
-                index: k,
-                data: p.borrow().data,
-            })
-            .collect();
+        let mut page_entries: Vec<PageEntry> = Vec::with_capacity(self.page_count());
+        self.visit_pages(|index, data| {
+            let elide = self.page_encoding == PageEncoding::Compact && data.iter().all(|&b| b == 0);
+            let data = match elide {
+                true => None,
+                false => Some(
+                    PageData::encode(data, self.page_encoding)
+                        .map_err(serde::ser::Error::custom)?,
+                ),
+            };
+            page_entries.push(PageEntry { index, data });
+            Ok::<_, S::Error>(())
+        })?;
 
-        page_entries.sort_by(|a, b| a.index.cmp(&b.index));
         page_entries.serialize(serializer)
     }
 }
@@ -409,18 +1045,25 @@ impl<'de> Deserialize<'de> for Memory {
 
         let mut memory = Memory::default();
 
-        for (i, p) in page_entries.iter().enumerate() {
-            if memory.pages.contains_key(&p.index) {
+        for (i, p) in page_entries.into_iter().enumerate() {
+            if memory.pages.contains_key(&p.index) || memory.lazy.zeroed.contains(&p.index) {
                 return Err(serde::de::Error::custom(format!(
                     "cannot load duplicate page, entry {}, page index {}",
                     i, p.index
                 )));
             }
+
+            // Elided zero pages are only allocated once they are accessed.
+            let Some(data) = p.data else {
+                memory.register_zero_page(p.index);
+                continue;
+            };
+            let data = data.decode().map_err(serde::de::Error::custom)?;
             let page = memory.alloc_page(p.index).map_err(|_| {
                 serde::de::Error::custom("Failed to allocate page in deserialization")
             })?;
             let mut page = page.borrow_mut();
-            page.data = p.data;
+            page.data = data;
             page.invalidate_full();
         }
 
@@ -477,6 +1120,7 @@ impl<'a> Read for MemoryReader<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::keccak_concat_hashes;
 
     mod merkle_proof {
         use super::*;
@@ -633,6 +1277,63 @@ mod test {
                 "Zero again"
             );
         }
+
+        #[test]
+        fn free_page() {
+            let mut memory = Memory::default();
+            memory.set_memory(0xF004, 1).unwrap();
+            memory.set_memory(0x1F004, 2).unwrap();
+            let mut expected = Memory::default();
+            expected.set_memory(0x1F004, 2).unwrap();
+
+            assert!(memory.free_page(0xF));
+            assert!(!memory.free_page(0xF));
+            assert_eq!(memory.get_memory(0xF004).unwrap(), 0);
+            assert_eq!(
+                expected.merkle_root().unwrap(),
+                memory.merkle_root().unwrap(),
+                "Freed page reads as zero"
+            );
+
+            // The freed page is recycled, zeroed, for the next allocation.
+            memory.set_memory(0xF000, 0).unwrap();
+            assert_eq!(memory.get_memory(0xF004).unwrap(), 0);
+            assert_eq!(memory.pool_stats().released, 1);
+            assert_eq!(memory.pool_stats().reused, 1);
+        }
+
+        #[test]
+        fn page_sizes() {
+            fn build<const P: usize>() -> MemoryOf<P>
+            where
+                [(); 1 << P]:,
+                [(); (1 << P) >> 5]:,
+            {
+                let mut memory = MemoryOf::<P>::default();
+                memory.set_memory(0x10000, 0xaabbccdd).unwrap();
+                memory.set_range(0x1FFC, &[0x42; 9000]).unwrap();
+                memory.set_memory(0x13370000, 123).unwrap();
+                assert!(memory.free_page(0x13370000 >> P));
+                memory
+            }
+
+            let mut memory = build::<{ page::PAGE_ADDRESS_SIZE }>();
+            let mut small = build::<8>();
+            let mut large = build::<16>();
+            assert_eq!(memory.page_count(), 5);
+            assert_eq!(small.page_count(), 38);
+            assert_eq!(large.page_count(), 2);
+
+            // The merkle tree does not depend on how it is split into pages.
+            let root = memory.merkle_root().unwrap();
+            assert_eq!(small.merkle_root().unwrap(), root);
+            assert_eq!(large.merkle_root().unwrap(), root);
+            for address in [0x10000, 0x3000, 0x13370000] {
+                let proof = memory.merkle_proof(address).unwrap();
+                assert_eq!(small.merkle_proof(address).unwrap(), proof);
+                assert_eq!(large.merkle_proof(address).unwrap(), proof);
+            }
+        }
     }
 
     mod read_write {
@@ -674,6 +1375,103 @@ mod test {
             assert_eq!([0u8; 10], buf[buf.len() - 10..], "empty end");
         }
 
+        #[test]
+        fn set_range() {
+            let mut data = vec![0u8; 3 * page::PAGE_SIZE];
+            rand::thread_rng().fill_bytes(&mut data[..]);
+            let address = 0x1FFC;
+
+            // Write over a page whose merkle root is already cached.
+            let mut memory = Memory::default();
+            memory.set_memory(0x2000, 0xFFFFFFFF).unwrap();
+            memory.merkle_root().unwrap();
+            memory.set_range(address, &data).unwrap();
+
+            let mut expected = Memory::default();
+            for (i, word) in data.chunks(4).enumerate() {
+                expected
+                    .set_memory(
+                        address + i as u32 * 4,
+                        u32::from_be_bytes(word.try_into().unwrap()),
+                    )
+                    .unwrap();
+            }
+            assert_eq!(memory.page_count(), 4);
+            assert_eq!(
+                memory.merkle_root().unwrap(),
+                expected.merkle_root().unwrap()
+            );
+
+            assert!(memory.set_range(0xFFFFFFFE, &[0u8; 4]).is_err());
+        }
+
+        #[test]
+        fn sparse_roundtrip() {
+            let mut memory = Memory::default();
+            memory.set_memory(0x10000004, 0xaabbccdd).unwrap();
+            memory.set_memory(0x3000, 0x11223344).unwrap();
+            memory.alloc_page(0x7FFFF).unwrap();
+
+            let image = memory.dump_sparse();
+            assert_eq!(
+                image.iter().map(|(addr, _)| *addr).collect::<Vec<_>>(),
+                vec![0x3000, 0x10000000, 0x7FFFF000]
+            );
+
+            let mut restored = Memory::default();
+            restored.set_memory(0x3008, 0xFFFFFFFF).unwrap();
+            restored.merkle_root().unwrap();
+            restored.restore_sparse(image).unwrap();
+            assert_eq!(restored.get_memory(0x3008).unwrap(), 0);
+            assert_eq!(
+                restored.merkle_root().unwrap(),
+                memory.merkle_root().unwrap()
+            );
+
+            assert!(restored
+                .restore_sparse([(0x1004, [0u8; page::PAGE_SIZE])])
+                .is_err());
+        }
+
+        #[test]
+        fn dirty_pages() {
+            let mut memory = Memory::default();
+            memory.set_memory(0x1000, 1).unwrap();
+            assert!(memory.take_dirty_pages().is_empty());
+
+            memory.track_dirty_pages();
+            memory.set_memory(0x3000, 0x11223344).unwrap();
+            memory.set_memory(0x1004, 2).unwrap();
+            memory.set_range(0x4FFC, &[0xFF; 8]).unwrap();
+            memory.alloc_page(0x7).unwrap();
+            memory.free_page(0x7);
+
+            let dirty = memory.take_dirty_pages();
+            assert_eq!(
+                dirty.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+                vec![0x1, 0x3, 0x4, 0x5, 0x7]
+            );
+            assert_eq!(
+                dirty[1].1.as_ref().unwrap()[..4],
+                0x11223344u32.to_be_bytes()
+            );
+            assert!(dirty[4].1.is_none());
+
+            // Applying the dirty pages to a copy of the memory brings it in sync.
+            let mut copy = Memory::default();
+            copy.set_memory(0x1000, 1).unwrap();
+            for (page_index, data) in dirty {
+                match data {
+                    Some(data) => copy
+                        .restore_sparse([((page_index << page::PAGE_ADDRESS_SIZE) as u32, *data)])
+                        .unwrap(),
+                    None => assert!(!copy.free_page(page_index)),
+                }
+            }
+            assert_eq!(copy.merkle_root().unwrap(), memory.merkle_root().unwrap());
+            assert!(memory.take_dirty_pages().is_empty());
+        }
+
         #[test]
         fn read_write() {
             let mut memory = Memory::default();
@@ -696,6 +1494,17 @@ mod test {
             assert_eq!(0xaabbccdd, memory.get_memory(12).unwrap());
         }
 
+        #[test]
+        fn unaligned_invalidate() {
+            let mut memory = Memory::default();
+            memory.set_memory(12, 0xaabbccdd).unwrap();
+            assert!(matches!(
+                memory.invalidate(13),
+                Err(CannonError::UnalignedAccess(13))
+            ));
+            memory.invalidate(12).unwrap();
+        }
+
         #[test]
         fn unaligned_write() {
             let mut memory = Memory::default();
@@ -745,11 +1554,68 @@ mod test {
                         nodes: nodes.into_iter().collect::<FxHashMap<_, _>>(),
                         pages: pages.into_iter().collect::<FxHashMap<_, _>>(),
                         last_page: [lp_a, lp_b],
+                        pool: PagePoolOf::default(),
+                        lazy: LazyPages::default(),
+                        hasher: MerkleHasher::default(),
+                        #[cfg(feature = "tlb")]
+                        tlb: Default::default(),
+                        generation: Generation::default(),
                     })
                     .boxed()
             }
         }
 
+        #[test]
+        fn zero_page_elision() {
+            let mut memory = Memory::default();
+            memory.alloc_page(0).unwrap();
+            memory.alloc_page(1 << 10).unwrap();
+            memory.set_memory(0x8000_0000, 0xaabbccdd).unwrap();
+            let root = memory.merkle_root().unwrap();
+
+            // Pages are serialized as Go Cannon serializes them by default.
+            let serialized = serde_json::to_value(&memory).unwrap();
+            let entries = serialized.as_array().unwrap();
+            assert_eq!(entries.len(), 3);
+            for entry in entries {
+                let data = BASE64_STANDARD
+                    .decode(entry["data"].as_str().unwrap())
+                    .unwrap();
+                assert!(crate::ser::BlobHeader::decode(&data).unwrap().is_none());
+                assert_eq!(
+                    crate::ser::decompress_bytes(&data).unwrap().len(),
+                    page::PAGE_SIZE
+                );
+            }
+
+            memory.set_page_encoding(PageEncoding::Compact);
+            let serialized = serde_json::to_value(&memory).unwrap();
+            let entries = serialized.as_array().unwrap();
+            assert_eq!(entries.len(), 3);
+            assert_eq!(
+                entries.iter().filter(|e| e.get("data").is_some()).count(),
+                1
+            );
+
+            // Elided zero pages are only allocated once they are accessed.
+            let mut deserialized: Memory = serde_json::from_value(serialized).unwrap();
+            assert_eq!(deserialized.page_count(), 3);
+            assert_eq!(deserialized.pages.len(), 1);
+            assert_eq!(deserialized.merkle_root().unwrap(), root);
+            assert_eq!(deserialized.pages.len(), 1);
+            assert_eq!(deserialized.get_memory(0x8000_0000).unwrap(), 0xaabbccdd);
+            assert_eq!(deserialized.get_memory(0x0000_0004).unwrap(), 0);
+            deserialized.set_memory(0x0040_0000, 1).unwrap();
+            assert_eq!(deserialized.pages.len(), 3);
+            assert_eq!(deserialized.page_count(), 3);
+
+            memory.set_memory(0x0040_0000, 1).unwrap();
+            assert_eq!(
+                deserialized.merkle_root().unwrap(),
+                memory.merkle_root().unwrap()
+            );
+        }
+
         proptest! {
             #[test]
             fn test_serialize_roundtrip(mut memory: Memory) {
@@ -0,0 +1,176 @@
+//! This module contains the [MemoryLayout], which describes where the program, the heap and the
+//! stack of a guest are placed in its address space.
+//!
+//! Unlike Linux, Cannon never randomizes the placement of these regions: the `MIPS` contract
+//! assumes the fixed [MemoryLayout::CANONICAL] layout, e.g. it hands out `mmap` regions from the
+//! canonical heap start and reports a constant program break. [crate::load_elf] therefore asserts
+//! that the program fits the canonical layout, and fails loudly if any of its segments collides
+//! with a region reserved for the heap, the stack or the kernel, rather than producing a state
+//! that the contract would execute differently.
+
+use crate::{
+    address_space::{self, BRK_BASE, USER_END},
+    page, Address, MemoryRegion, HEAP_REGION, STACK_REGION,
+};
+use anyhow::{bail, ensure, Result};
+
+/// The name of the region above the user portion of the address space (`kseg0` and above).
+pub const KERNEL_REGION: &str = "kernel";
+
+/// The [MemoryLayout] of a guest's address space. The program is loaded below `heap_start`, the
+/// heap grows from `heap_start` up to the stack reservation, and the stack grows down from
+/// `stack_pointer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// The initial heap pointer, from which `mmap` hands out memory.
+    pub heap_start: Address,
+    /// The program break reported by `brk`.
+    pub brk: Address,
+    /// The initial stack pointer.
+    pub stack_pointer: Address,
+    /// The end of the user portion of the address space.
+    pub user_end: u64,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self::CANONICAL
+    }
+}
+
+impl MemoryLayout {
+    /// The canonical Cannon layout, which the `MIPS` contract assumes.
+    pub const CANONICAL: Self = Self {
+        heap_start: 0x2000_0000,
+        brk: BRK_BASE,
+        stack_pointer: 0x7FFF_D000,
+        user_end: USER_END,
+    };
+
+    /// Returns `true` if the layout is the [MemoryLayout::CANONICAL] layout.
+    pub fn is_canonical(&self) -> bool {
+        *self == Self::CANONICAL
+    }
+
+    /// Returns the start of the stack reservation below the initial stack pointer.
+    pub fn stack_start(&self) -> u64 {
+        address_space::stack_reservation(self.stack_pointer).0
+    }
+
+    /// Returns the regions reserved for the heap, the stack and the kernel, which the segments
+    /// of the program must not overlap.
+    pub fn reserved_regions(&self) -> [MemoryRegion; 3] {
+        let stack_start = self.stack_start();
+        [
+            MemoryRegion {
+                name: HEAP_REGION.to_string(),
+                start: self.heap_start as u64,
+                end: stack_start,
+            },
+            MemoryRegion {
+                name: STACK_REGION.to_string(),
+                start: stack_start,
+                end: self.user_end,
+            },
+            MemoryRegion {
+                name: KERNEL_REGION.to_string(),
+                start: self.user_end,
+                end: 1 << 32,
+            },
+        ]
+    }
+
+    /// Checks that the layout itself is consistent: its addresses are page-aligned, and the
+    /// heap, the program break and the stack are ordered within the user portion of the address
+    /// space.
+    ///
+    /// ### Returns
+    /// - `Err(_)` if the layout is inconsistent.
+    pub fn validate(&self) -> Result<()> {
+        for (name, address) in [
+            ("heap start", self.heap_start as u64),
+            ("program break", self.brk as u64),
+            ("stack pointer", self.stack_pointer as u64),
+            ("user end", self.user_end),
+        ] {
+            ensure!(
+                address & page::PAGE_ADDRESS_MASK as u64 == 0,
+                "The {} {:08x} of the memory layout is not page-aligned",
+                name,
+                address
+            );
+        }
+        ensure!(
+            self.user_end <= 1 << 32,
+            "The user end {:08x} of the memory layout exceeds the 32-bit address space",
+            self.user_end
+        );
+        ensure!(
+            (self.heap_start as u64) < self.stack_start()
+                && (self.heap_start..=self.stack_pointer).contains(&self.brk)
+                && (self.stack_pointer as u64) < self.user_end,
+            "The memory layout {:?} does not order the heap, the program break and the stack within user memory",
+            self
+        );
+        Ok(())
+    }
+
+    /// Checks that a segment of the program does not collide with any of the
+    /// [MemoryLayout::reserved_regions].
+    ///
+    /// ### Takes
+    /// - `index`: The index of the segment in the ELF file.
+    /// - `start`: The start address of the segment.
+    /// - `end`: The exclusive end address of the segment.
+    ///
+    /// ### Returns
+    /// - `Err(_)` if the segment overlaps a reserved region.
+    pub fn check_segment(&self, index: usize, start: u64, end: u64) -> Result<()> {
+        if start == end {
+            return Ok(());
+        }
+        for region in self.reserved_regions() {
+            if start < region.end && region.start < end {
+                bail!(
+                    "Program segment {} [{:08x}, {:08x}) collides with the reserved {} region of the memory layout",
+                    index,
+                    start,
+                    end,
+                    region
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonical_layout() {
+        let layout = MemoryLayout::default();
+        assert!(layout.is_canonical());
+        layout.validate().unwrap();
+
+        // Segments below the heap fit, anything reaching into a reserved region does not.
+        layout.check_segment(0, 0x10000, 0x8ffb4).unwrap();
+        layout.check_segment(1, 0x2000_0000, 0x2000_0000).unwrap();
+        for (start, end, region) in [
+            (0x1fff_f000, 0x2000_1000, HEAP_REGION),
+            (0x7FFF_0000, 0x7FFF_1000, STACK_REGION),
+            (0x8000_0000, 0x8000_1000, KERNEL_REGION),
+        ] {
+            let err = layout.check_segment(2, start, end).unwrap_err().to_string();
+            assert!(err.contains(region), "{}", err);
+        }
+
+        let misaligned = MemoryLayout {
+            heap_start: 0x2000_0004,
+            ..MemoryLayout::CANONICAL
+        };
+        assert!(!misaligned.is_canonical());
+        assert!(misaligned.validate().is_err());
+    }
+}
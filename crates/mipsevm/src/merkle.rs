@@ -0,0 +1,286 @@
+//! This module contains the [MerkleHasher], which selects the hash function that the [Memory] is
+//! merkleized with.
+//!
+//! The `MIPS` contract verifies memory proofs against keccak256 roots, so
+//! [MerkleHasher::Keccak256] is the default. With the `poseidon` feature enabled, memory may
+//! instead be merkleized with Poseidon over the BN254 scalar field, which is far cheaper to prove
+//! inside of a zkVM or SNARK circuit that verifies Cannon traces.
+//!
+//! [Memory]: crate::Memory
+
+use crate::utils::keccak_concat_hashes;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "simd-keccak"))]
+use crate::utils::keccak256;
+
+/// The [MerkleHasher] enum describes the hash functions that the [crate::Memory] can be merkleized
+/// with.
+///
+/// The leaves of the memory tree are 32 byte words, which are not hashed. The parents of the
+/// leaves are hashed from the 64 bytes of raw memory they cover with [MerkleHasher::hash_leaves],
+/// and all other nodes from their two children with [MerkleHasher::hash_pair].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MerkleHasher {
+    /// keccak256 of the concatenated children, as verified by the `MIPS` contract.
+    #[default]
+    Keccak256,
+    /// The circom-compatible Poseidon permutation over the BN254 scalar field. Raw memory is split
+    /// into 16 byte limbs so that it always fits into a field element, and the limbs of both
+    /// leaves are hashed with a 4-ary Poseidon instance. Internal nodes are hashed with a 2-ary
+    /// instance. Digests are the big-endian encodings of the resulting field elements.
+    #[cfg(feature = "poseidon")]
+    Poseidon,
+}
+
+impl MerkleHasher {
+    /// Hashes the parent of two leaves from the 64 bytes of raw memory it covers.
+    ///
+    /// ### Takes
+    /// - `data`: The 64 bytes of memory covered by the node.
+    ///
+    /// ### Returns
+    /// - The 32 byte hash of the node.
+    #[inline(always)]
+    pub fn hash_leaves(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            MerkleHasher::Keccak256 => {
+                #[cfg(feature = "simd-keccak")]
+                {
+                    let mut out = [0u8; 32];
+                    keccak256_aarch64_simd::simd_keccak256_64b_single(data, &mut out);
+                    out
+                }
+
+                #[cfg(not(feature = "simd-keccak"))]
+                *keccak256(data)
+            }
+            #[cfg(feature = "poseidon")]
+            MerkleHasher::Poseidon => poseidon::hash_leaves(data),
+        }
+    }
+
+    /// Hashes an internal node from the hashes of its two children.
+    ///
+    /// ### Takes
+    /// - `left`: The hash of the left child.
+    /// - `right`: The hash of the right child.
+    ///
+    /// ### Returns
+    /// - The 32 byte hash of the node.
+    #[inline(always)]
+    pub fn hash_pair(&self, left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        match self {
+            MerkleHasher::Keccak256 => *keccak_concat_hashes(left, right),
+            #[cfg(feature = "poseidon")]
+            MerkleHasher::Poseidon => poseidon::hash_pair(left, right),
+        }
+    }
+
+    /// Returns the precomputed hashes of full-zero subtrees, indexed by their height.
+    pub fn zero_hashes(&self) -> &'static [[u8; 32]; 256] {
+        match self {
+            MerkleHasher::Keccak256 => &crate::page::ZERO_HASHES,
+            #[cfg(feature = "poseidon")]
+            MerkleHasher::Poseidon => &poseidon::ZERO_HASHES,
+        }
+    }
+
+    /// Computes the root of the memory tree from a merkle proof, as produced by
+    /// [crate::Memory::merkle_proof].
+    ///
+    /// ### Takes
+    /// - `address`: The address that the proof was generated for.
+    /// - `proof`: The merkle proof; the leaf followed by its 27 siblings, from the bottom up.
+    ///
+    /// ### Returns
+    /// - The 32 byte merkle root that the proof commits to.
+    pub fn proof_root(&self, address: u32, proof: &[u8; 28 * 32]) -> [u8; 32] {
+        let word = |i: usize| -> [u8; 32] {
+            proof[i * 32..(i + 1) * 32]
+                .try_into()
+                .expect("Proof word is 32 bytes")
+        };
+
+        let mut path = address >> 5;
+        let (leaf, sibling) = (word(0), word(1));
+        let mut data = [0u8; 64];
+        if path & 1 != 0 {
+            data[..32].copy_from_slice(&sibling);
+            data[32..].copy_from_slice(&leaf);
+        } else {
+            data[..32].copy_from_slice(&leaf);
+            data[32..].copy_from_slice(&sibling);
+        }
+        let mut node = self.hash_leaves(&data);
+        path >>= 1;
+
+        for i in 2..28 {
+            node = if path & 1 != 0 {
+                self.hash_pair(word(i), node)
+            } else {
+                self.hash_pair(node, word(i))
+            };
+            path >>= 1;
+        }
+        node
+    }
+
+    /// Returns all [MerkleHasher]s that are enabled.
+    pub fn all() -> &'static [MerkleHasher] {
+        &[
+            MerkleHasher::Keccak256,
+            #[cfg(feature = "poseidon")]
+            MerkleHasher::Poseidon,
+        ]
+    }
+}
+
+/// Builds the table of full-zero subtree hashes for a [MerkleHasher].
+pub(crate) fn build_zero_hashes(hasher: MerkleHasher) -> [[u8; 32]; 256] {
+    let mut out = [[0u8; 32]; 256];
+    out[1] = hasher.hash_leaves(&[0u8; 64]);
+    for i in 2..256 {
+        out[i] = hasher.hash_pair(out[i - 1], out[i - 1]);
+    }
+    out
+}
+
+#[cfg(feature = "poseidon")]
+mod poseidon {
+    use super::{build_zero_hashes, MerkleHasher};
+    use ark_bn254::Fr;
+    use ark_ff::{BigInteger, PrimeField};
+    use light_poseidon::{Poseidon, PoseidonHasher};
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+
+    /// The size of the limbs that raw memory is split into.
+    const LIMB_SIZE: usize = 16;
+
+    /// Precomputed hashes of each full-zero subtree level.
+    pub(super) static ZERO_HASHES: Lazy<[[u8; 32]; 256]> =
+        Lazy::new(|| build_zero_hashes(MerkleHasher::Poseidon));
+
+    thread_local! {
+        /// The 2-ary Poseidon instance, for internal nodes.
+        static PAIR: RefCell<Poseidon<Fr>> =
+            RefCell::new(Poseidon::new_circom(2).expect("Valid Poseidon parameters"));
+        /// The 4-ary Poseidon instance, for the parents of leaves.
+        static LEAVES: RefCell<Poseidon<Fr>> = RefCell::new(
+            Poseidon::new_circom(64 / LIMB_SIZE).expect("Valid Poseidon parameters"),
+        );
+    }
+
+    /// Hashes the parent of two leaves from the 64 bytes of raw memory it covers.
+    pub(super) fn hash_leaves(data: &[u8]) -> [u8; 32] {
+        let limbs = data
+            .chunks_exact(LIMB_SIZE)
+            .map(Fr::from_be_bytes_mod_order)
+            .collect::<Vec<_>>();
+        LEAVES.with(|poseidon| to_bytes(poseidon.borrow_mut().hash(&limbs)))
+    }
+
+    /// Hashes an internal node from the hashes of its two children.
+    pub(super) fn hash_pair(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        let inputs = [
+            Fr::from_be_bytes_mod_order(&left),
+            Fr::from_be_bytes_mod_order(&right),
+        ];
+        PAIR.with(|poseidon| to_bytes(poseidon.borrow_mut().hash(&inputs)))
+    }
+
+    /// Encodes a Poseidon digest as 32 big-endian bytes.
+    fn to_bytes(digest: Result<Fr, light_poseidon::PoseidonError>) -> [u8; 32] {
+        let bytes = digest
+            .expect("Input count matches the Poseidon instance")
+            .into_bigint()
+            .to_bytes_be();
+        let mut out = [0u8; 32];
+        out[32 - bytes.len()..].copy_from_slice(&bytes);
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Memory;
+
+    #[test]
+    fn zero_hashes() {
+        for &hasher in MerkleHasher::all() {
+            assert_eq!(
+                &build_zero_hashes(hasher)[..],
+                &hasher.zero_hashes()[..],
+                "{hasher:?}"
+            );
+            let mut memory = Memory::with_hasher(hasher);
+            assert_eq!(
+                memory.merkle_root().unwrap(),
+                hasher.zero_hashes()[32 - 5],
+                "{hasher:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn proofs_verify() {
+        for &hasher in MerkleHasher::all() {
+            let mut memory = Memory::with_hasher(hasher);
+            memory.set_memory(0x10000, 0xaabbccdd).unwrap();
+            memory.set_memory(0x80004, 42).unwrap();
+            memory.set_memory(0x13370000, 123).unwrap();
+            let root = memory.merkle_root().unwrap();
+
+            for address in [0x10000, 0x80004, 0x13370000, 0x7fff_fffc] {
+                let proof = memory.merkle_proof(address).unwrap();
+                assert_eq!(hasher.proof_root(address, &proof), root, "{hasher:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_matches_fresh() {
+        for &hasher in MerkleHasher::all() {
+            let mut memory = Memory::with_hasher(hasher);
+            memory
+                .set_memory_range(0x1000, &[0xAB; 0x3000][..])
+                .unwrap();
+            memory.merkle_root().unwrap();
+            memory.set_memory(0x2004, 7).unwrap();
+            memory.free_page(3);
+
+            let mut fresh = Memory::with_hasher(hasher);
+            fresh.set_memory_range(0x1000, &[0xAB; 0x2000][..]).unwrap();
+            fresh.set_memory(0x2004, 7).unwrap();
+
+            assert_eq!(
+                memory.merkle_root().unwrap(),
+                fresh.merkle_root().unwrap(),
+                "{hasher:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rehash() {
+        let mut memory = Memory::default();
+        memory.set_memory(0x10000, 0xaabbccdd).unwrap();
+        let keccak_root = memory.merkle_root().unwrap();
+
+        for &hasher in MerkleHasher::all() {
+            memory.set_hasher(hasher);
+            let mut expected = Memory::with_hasher(hasher);
+            expected.set_memory(0x10000, 0xaabbccdd).unwrap();
+            assert_eq!(
+                memory.merkle_root().unwrap(),
+                expected.merkle_root().unwrap()
+            );
+        }
+
+        memory.set_hasher(MerkleHasher::Keccak256);
+        assert_eq!(memory.merkle_root().unwrap(), keccak_root);
+    }
+}
@@ -0,0 +1,184 @@
+//! This module contains the [Metadata] of a program, which maps the symbols of its ELF file to
+//! address ranges. It is generated when the ELF file is loaded and written to a `meta.json` file,
+//! matching the format of the Go implementation of Cannon.
+
+use crate::Address;
+use anyhow::Result;
+use elf::{endian::AnyEndian, ElfBytes};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// The name returned by [Metadata::lookup_symbol] for addresses outside of any known symbol.
+pub const UNKNOWN_SYMBOL: &str = "!unknown";
+
+/// A [Symbol] of a program's ELF file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// The address that the symbol starts at.
+    pub start: Address,
+    /// The size of the symbol, in bytes.
+    pub size: u32,
+}
+
+/// The [Metadata] of a program, containing its symbols sorted by start address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The symbols of the program, sorted by start address.
+    pub symbols: Vec<Symbol>,
+}
+
+impl Metadata {
+    /// Creates the [Metadata] of a program from the symbol table of its ELF file.
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [Metadata] of the program.
+    pub fn from_elf(raw: &[u8]) -> Result<Self> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let (symbol_table, string_table) = elf
+            .symbol_table()?
+            .ok_or(anyhow::anyhow!("Failed to load ELF symbol table"))?;
+
+        let mut symbols = symbol_table
+            .iter()
+            .map(|symbol| {
+                Ok(Symbol {
+                    name: string_table.get(symbol.st_name as usize)?.to_string(),
+                    start: symbol.st_value as Address,
+                    size: symbol.st_size as u32,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        symbols.sort_by_key(|symbol| symbol.start);
+
+        Ok(Self { symbols })
+    }
+
+    /// Loads [Metadata] from a `meta.json` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Writes the [Metadata] to a `meta.json` file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(fs::write(path, serde_json::to_vec(self)?)?)
+    }
+
+    /// Returns the name of the symbol that contains the given address.
+    ///
+    /// ### Takes
+    /// - `address`: The address to look up.
+    ///
+    /// ### Returns
+    /// - The name of the symbol, or [UNKNOWN_SYMBOL] if no symbol contains the address.
+    pub fn lookup_symbol(&self, address: Address) -> &str {
+        // Find the last symbol that starts at or before the address.
+        let i = self
+            .symbols
+            .partition_point(|symbol| symbol.start <= address);
+        match i.checked_sub(1).map(|i| &self.symbols[i]) {
+            Some(symbol) if address - symbol.start < symbol.size.max(1) => &symbol.name,
+            _ => UNKNOWN_SYMBOL,
+        }
+    }
+
+    /// Creates a [SymbolMatcher] for the symbol with the given name.
+    ///
+    /// ### Takes
+    /// - `name`: The name of the symbol.
+    ///
+    /// ### Returns
+    /// - A [SymbolMatcher] that matches addresses within the symbol, or that never matches if the
+    ///   program has no symbol with the given name.
+    pub fn symbol_matcher(&self, name: &str) -> SymbolMatcher {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| SymbolMatcher {
+                start: symbol.start,
+                end: symbol.start.saturating_add(symbol.size.max(1)),
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// A [SymbolMatcher] detects when an address lies within a symbol, such as when the program
+/// counter enters a function.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SymbolMatcher {
+    /// The first address of the symbol.
+    start: Address,
+    /// The address after the last address of the symbol.
+    end: Address,
+}
+
+impl SymbolMatcher {
+    /// Returns `true` if the address lies within the symbol.
+    #[inline(always)]
+    pub fn matches(&self, address: Address) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        Metadata {
+            symbols: vec![
+                Symbol {
+                    name: "main.main".to_string(),
+                    start: 0x1000,
+                    size: 0x100,
+                },
+                Symbol {
+                    name: "runtime.notesleep".to_string(),
+                    start: 0x2000,
+                    size: 0x40,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn lookup_symbol() {
+        let meta = metadata();
+        assert_eq!(meta.lookup_symbol(0x1000), "main.main");
+        assert_eq!(meta.lookup_symbol(0x10FC), "main.main");
+        assert_eq!(meta.lookup_symbol(0x1100), UNKNOWN_SYMBOL);
+        assert_eq!(meta.lookup_symbol(0x0FFC), UNKNOWN_SYMBOL);
+        assert_eq!(meta.lookup_symbol(0x2020), "runtime.notesleep");
+    }
+
+    #[test]
+    fn symbol_matcher() {
+        let meta = metadata();
+        let sleep = meta.symbol_matcher("runtime.notesleep");
+        assert!(sleep.matches(0x2000));
+        assert!(!sleep.matches(0x2040));
+        assert!(!meta.symbol_matcher("runtime.gcenable").matches(0));
+    }
+
+    #[test]
+    fn from_elf() {
+        let meta = Metadata::from_elf(include_bytes!("../../../example/bin/hello.elf")).unwrap();
+        assert!(meta
+            .symbols
+            .windows(2)
+            .all(|pair| pair[0].start <= pair[1].start));
+        let gcenable = meta.symbol_matcher("runtime.gcenable");
+        assert_ne!(gcenable, SymbolMatcher::default());
+        let start = meta
+            .symbols
+            .iter()
+            .find(|s| s.name == "runtime.gcenable")
+            .unwrap()
+            .start;
+        assert_eq!(meta.lookup_symbol(start), "runtime.gcenable");
+    }
+}
@@ -0,0 +1,241 @@
+//! This module contains the [Schema] type, which versions the serialized JSON formats of this
+//! crate and upgrades documents written by older versions of the crate on load.
+//!
+//! Every versioned document carries a top-level `version` field. Documents written before the
+//! field was introduced have no `version` field, and are treated as version `0`.
+//!
+//! Documents that only need [Migration::Compatible] migrations are deserialized directly from
+//! their raw bytes, like documents at the current version. Only documents that need a
+//! [Migration::Transform] are parsed into an intermediate [Value] first.
+
+use crate::{CannonError, CannonResult};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+
+/// The current version of the serialized [crate::State] format.
+pub const STATE_VERSION: u32 = 1;
+
+/// The [Schema] of the serialized [crate::State] format.
+pub const STATE_SCHEMA: Schema = Schema {
+    kind: "state",
+    current: STATE_VERSION,
+    migrations: &[Migration::Compatible],
+};
+
+/// The `version` field of a JSON document, deserialized while skipping over the rest of it.
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: Option<u64>,
+}
+
+/// A [Migration] upgrades a JSON document by exactly one version.
+#[derive(Debug, Clone, Copy)]
+pub enum Migration {
+    /// The version only added fields with defaults, such as the `version` field itself, so
+    /// documents of the previous version deserialize as they are.
+    Compatible,
+    /// Upgrades the document in place.
+    Transform(fn(&mut Map<String, Value>) -> CannonResult<()>),
+}
+
+/// A document of a versioned format, which is stamped with the current version once loaded.
+pub trait Versioned {
+    /// Sets the version of the document.
+    fn set_version(&mut self, version: u32);
+}
+
+/// A [Schema] describes a versioned JSON format and the [Migration]s that upgrade older documents
+/// to the current version.
+#[derive(Debug, Clone, Copy)]
+pub struct Schema {
+    /// The name of the format, used in error messages.
+    pub kind: &'static str,
+    /// The current version of the format.
+    pub current: u32,
+    /// The migrations of the format. The migration at index `i` upgrades a document from version
+    /// `i` to version `i + 1`, so there must be exactly `current` migrations.
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    /// Returns the versions of the format that can be loaded.
+    pub fn supported_versions(&self) -> Vec<u32> {
+        (0..=self.current).collect()
+    }
+
+    /// Checks that a version of the format can be loaded.
+    fn check_version(&self, version: u32) -> CannonResult<()> {
+        if version > self.current {
+            return Err(CannonError::UnsupportedVersion {
+                kind: self.kind,
+                version,
+                supported: self.supported_versions(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Upgrades a JSON document to the current version of the format.
+    ///
+    /// ### Takes
+    /// - `value`: The JSON document, at any supported version.
+    ///
+    /// ### Returns
+    /// - `Ok(value)` containing the document at the current version.
+    /// - `Err(CannonError::UnsupportedVersion)` if the document is of an unknown version.
+    pub fn migrate(&self, mut value: Value) -> CannonResult<Value> {
+        let object = value.as_object_mut().ok_or_else(|| {
+            CannonError::Other(anyhow::anyhow!("Expected a JSON object for {}", self.kind))
+        })?;
+
+        let version = match object.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| {
+                    CannonError::Other(anyhow::anyhow!(
+                        "Invalid {} version: {}",
+                        self.kind,
+                        version
+                    ))
+                })?,
+        };
+        self.check_version(version)?;
+
+        for migration in &self.migrations[version as usize..self.current as usize] {
+            if let Migration::Transform(transform) = migration {
+                transform(object)?;
+            }
+        }
+        object.insert("version".to_string(), self.current.into());
+        Ok(value)
+    }
+
+    /// Deserializes a JSON document of any supported version, upgrading it to the current version
+    /// first. Documents that only need [Migration::Compatible] migrations are deserialized
+    /// directly, without building an intermediate [Value].
+    ///
+    /// ### Takes
+    /// - `raw`: The raw JSON document.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the deserialized document.
+    pub fn from_slice<T: DeserializeOwned + Versioned>(&self, raw: &[u8]) -> CannonResult<T> {
+        if let Ok(VersionProbe { version }) = serde_json::from_slice(raw) {
+            if let Ok(version) = u32::try_from(version.unwrap_or_default()) {
+                self.check_version(version)?;
+                let compatible = self.migrations[version as usize..self.current as usize]
+                    .iter()
+                    .all(|migration| matches!(migration, Migration::Compatible));
+                if compatible {
+                    let mut document: T =
+                        serde_json::from_slice(raw).map_err(anyhow::Error::from)?;
+                    document.set_version(self.current);
+                    return Ok(document);
+                }
+            }
+        }
+
+        let value = serde_json::from_slice(raw).map_err(anyhow::Error::from)?;
+        let value = self.migrate(value)?;
+        Ok(serde_json::from_value(value).map_err(anyhow::Error::from)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::State;
+
+    #[test]
+    fn load_legacy_state() {
+        let mut state = State {
+            pc: 0x1000,
+            step: 42,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+
+        let mut legacy = serde_json::to_value(&state).unwrap();
+        legacy.as_object_mut().unwrap().remove("version");
+
+        let mut loaded: State = STATE_SCHEMA
+            .from_slice(&serde_json::to_vec(&legacy).unwrap())
+            .unwrap();
+        assert_eq!(loaded.version, STATE_VERSION);
+        assert_eq!(
+            loaded.encode_witness().unwrap(),
+            state.encode_witness().unwrap()
+        );
+    }
+
+    #[test]
+    fn load_current_state() {
+        let mut state = State {
+            pc: 0x1000,
+            step: 42,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+
+        let mut loaded: State = STATE_SCHEMA
+            .from_slice(&serde_json::to_vec(&state).unwrap())
+            .unwrap();
+        assert_eq!(loaded.version, STATE_VERSION);
+        assert_eq!(
+            loaded.encode_witness().unwrap(),
+            state.encode_witness().unwrap()
+        );
+    }
+
+    #[test]
+    fn transform_legacy_document() {
+        #[derive(Deserialize)]
+        struct Renamed {
+            version: u32,
+            name: String,
+        }
+
+        impl Versioned for Renamed {
+            fn set_version(&mut self, version: u32) {
+                self.version = version;
+            }
+        }
+
+        fn rename_label(object: &mut Map<String, Value>) -> CannonResult<()> {
+            let name = object.remove("label").unwrap_or_default();
+            object.insert("name".to_string(), name);
+            Ok(())
+        }
+
+        const SCHEMA: Schema = Schema {
+            kind: "renamed",
+            current: 2,
+            migrations: &[Migration::Compatible, Migration::Transform(rename_label)],
+        };
+
+        let loaded: Renamed = SCHEMA.from_slice(br#"{"label":"legacy"}"#).unwrap();
+        assert_eq!((loaded.version, loaded.name.as_str()), (2, "legacy"));
+        let loaded: Renamed = SCHEMA
+            .from_slice(br#"{"version":2,"name":"current"}"#)
+            .unwrap();
+        assert_eq!((loaded.version, loaded.name.as_str()), (2, "current"));
+    }
+
+    #[test]
+    fn reject_future_version() {
+        let mut value = serde_json::to_value(State::default()).unwrap();
+        value["version"] = (STATE_VERSION + 1).into();
+
+        let err = STATE_SCHEMA
+            .from_slice::<State>(&serde_json::to_vec(&value).unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::UnsupportedVersion { version, .. } if version == STATE_VERSION + 1
+        ));
+        assert!(err.to_string().contains("supported versions: 0, 1"));
+    }
+}
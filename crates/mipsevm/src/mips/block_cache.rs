@@ -0,0 +1,171 @@
+//! This module contains the [BlockCache], which backs the threaded execution mode of the
+//! [crate::InstrumentedState].
+
+use super::mips_vm::DecodedInstruction;
+use crate::{page, Address, Memory, PageIndex};
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::rc::Rc;
+
+/// The maximum number of instructions that are translated into a single [Block].
+pub(crate) const MAX_BLOCK_SIZE: usize = 64;
+
+/// A [CachedInstruction] is a pre-fetched and pre-decoded instruction, paired with the address
+/// that it was fetched from.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CachedInstruction {
+    /// The address of the instruction.
+    pub(crate) pc: Address,
+    /// The decoded instruction.
+    pub(crate) decoded: DecodedInstruction,
+}
+
+/// A [Block] is a run of consecutive instructions, starting at a given program counter and ending
+/// after the delay slot of the first control transfer instruction, the first syscall, or the end
+/// of the page that the block starts in.
+pub(crate) type Block = Rc<[CachedInstruction]>;
+
+/// The [BlockCache] holds translated [Block]s keyed by their starting program counter.
+///
+/// Blocks are only valid as long as the code they were translated from is unchanged. Any write to
+/// a page that contains translated code flushes the entire cache.
+#[derive(Debug, Default)]
+pub(crate) struct BlockCache {
+    /// Map of starting program counters to their translated [Block]s.
+    blocks: FxHashMap<Address, Block>,
+    /// The set of page indices that contain translated code.
+    code_pages: FxHashSet<PageIndex>,
+    /// Incremented every time that the cache is flushed.
+    pub(crate) generation: u64,
+}
+
+impl BlockCache {
+    /// Fetch the [Block] starting at `pc`, translating it from [Memory] if it is not cached.
+    ///
+    /// ### Takes
+    /// - `pc`: The program counter that the block starts at.
+    /// - `memory`: The [Memory] to translate the block from.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [Block] or an error if the instructions could not be fetched.
+    #[inline(always)]
+    pub(crate) fn get_or_translate(&mut self, pc: Address, memory: &mut Memory) -> Result<Block> {
+        if let Some(block) = self.blocks.get(&pc) {
+            return Ok(Rc::clone(block));
+        }
+
+        let block = translate(pc, memory)?;
+        self.code_pages
+            .insert(pc as PageIndex >> page::PAGE_ADDRESS_SIZE);
+        self.blocks.insert(pc, Rc::clone(&block));
+        Ok(block)
+    }
+
+    /// Signal a write to [Memory] at the given [Address]. If the address lies within a page that
+    /// contains translated code, the cache is flushed.
+    ///
+    /// ### Takes
+    /// - `address`: The address that was written to.
+    #[inline(always)]
+    pub(crate) fn invalidate(&mut self, address: Address) {
+        if !self.code_pages.is_empty()
+            && self
+                .code_pages
+                .contains(&(address as PageIndex >> page::PAGE_ADDRESS_SIZE))
+        {
+            self.clear();
+        }
+    }
+
+    /// Flush all translated [Block]s from the cache.
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+        self.code_pages.clear();
+        self.generation += 1;
+    }
+}
+
+/// Translate the [Block] starting at the given program counter.
+///
+/// ### Takes
+/// - `pc`: The program counter that the block starts at.
+/// - `memory`: The [Memory] to fetch the instructions from.
+///
+/// ### Returns
+/// - A [Result] containing the translated [Block].
+fn translate(pc: Address, memory: &mut Memory) -> Result<Block> {
+    let mut block = Vec::with_capacity(MAX_BLOCK_SIZE);
+    let mut pc = pc;
+    let mut in_delay_slot = false;
+
+    loop {
+        let decoded = DecodedInstruction::new(memory.get_memory(pc)?);
+        block.push(CachedInstruction { pc, decoded });
+
+        if in_delay_slot || block.len() == MAX_BLOCK_SIZE || is_syscall(&decoded) {
+            break;
+        }
+        in_delay_slot = is_control_transfer(&decoded);
+
+        // Blocks never span multiple pages, so that a write to a page only ever needs to
+        // invalidate the blocks that start within it.
+        pc = pc.wrapping_add(4);
+        if pc as usize & page::PAGE_ADDRESS_MASK == 0 {
+            break;
+        }
+    }
+
+    Ok(block.into())
+}
+
+/// Returns `true` if the instruction is a jump or a branch, which has a delay slot.
+#[inline(always)]
+fn is_control_transfer(decoded: &DecodedInstruction) -> bool {
+    matches!(decoded.opcode, 1..=7) || (decoded.opcode == 0 && matches!(decoded.fun, 8 | 9))
+}
+
+/// Returns `true` if the instruction is a syscall.
+#[inline(always)]
+fn is_syscall(decoded: &DecodedInstruction) -> bool {
+    decoded.opcode == 0 && decoded.fun == 0x0C
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn translates_decoded_blocks() {
+        let mut memory = Memory::default();
+        // 0x1000: addiu $t0, $t0, -1
+        // 0x1004: bne $t0, $zero, 0x1000
+        // 0x1008: nop
+        // 0x100c: syscall
+        for (i, instruction) in [0x2508FFFF, 0x1500FFFE, 0, 0x0000000C]
+            .into_iter()
+            .enumerate()
+        {
+            memory
+                .set_memory(0x1000 + i as u32 * 4, instruction)
+                .unwrap();
+        }
+
+        let mut cache = BlockCache::default();
+        let block = cache.get_or_translate(0x1000, &mut memory).unwrap();
+        assert_eq!(
+            block.iter().map(|cached| cached.pc).collect::<Vec<_>>(),
+            vec![0x1000, 0x1004, 0x1008]
+        );
+        assert_eq!(block[0].decoded, DecodedInstruction::new(0x2508FFFF));
+        assert_eq!(block[0].decoded.rt_reg, 8);
+        assert_eq!(block[0].decoded.sign_ext_imm, 0xFFFFFFFF);
+
+        let block = cache.get_or_translate(0x100c, &mut memory).unwrap();
+        assert_eq!(block.len(), 1);
+
+        // A write to translated code flushes the cache.
+        cache.invalidate(0x1008);
+        assert_eq!(cache.generation, 1);
+        assert!(cache.blocks.is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! This module contains the [EntryHooks] of the [crate::InstrumentedState], which invoke callbacks
+//! when the guest enters functions of interest, the [StepHooks], which are invoked around every
+//! instruction, and the [FpTrapHandler], which is invoked for floating-point instructions.
+
+use crate::{Address, CannonError, CannonResult, Metadata, State};
+use rustc_hash::FxHashMap;
+
+/// The [HookAction] returned by a function-entry callback decides how the emulator proceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Continue executing the function.
+    Continue,
+    /// Abort the run with a [CannonError::HookAborted] before the function's first instruction
+    /// is executed.
+    Abort,
+}
+
+/// A function-entry callback. Receives the [State] prior to executing the function's first
+/// instruction and the name of the entered function.
+pub type EntryCallback = Box<dyn FnMut(&State, &str) -> HookAction>;
+
+/// A [PreStepHook] is invoked before every instruction executed by the
+/// [crate::InstrumentedState], and may veto the instruction by aborting the run. Closures taking
+/// a `&mut State` and returning a [HookAction] are [PreStepHook]s.
+///
+/// Hooks may modify the [State]; like the [extensions](State#extensions) of a [State], doing so
+/// diverges from the `MIPS` contract. Hooks that modify code must not be combined with
+/// [crate::InstrumentedState::step_threaded], which does not observe such writes.
+pub trait PreStepHook {
+    /// Invoked before the instruction at the current program counter is executed.
+    ///
+    /// ### Takes
+    /// - `state`: The [State] prior to the instruction.
+    ///
+    /// ### Returns
+    /// - [HookAction::Abort] to abort the run with a [CannonError::StepAborted] without executing
+    ///   the instruction.
+    fn pre_step(&mut self, state: &mut State) -> HookAction;
+}
+
+impl<F: FnMut(&mut State) -> HookAction> PreStepHook for F {
+    fn pre_step(&mut self, state: &mut State) -> HookAction {
+        self(state)
+    }
+}
+
+/// A [PostStepHook] is invoked after every instruction executed by the
+/// [crate::InstrumentedState]. Closures taking a `&mut State` and returning a [HookAction] are
+/// [PostStepHook]s. The same caveats as for [PreStepHook]s apply to modifying the [State].
+pub trait PostStepHook {
+    /// Invoked after an instruction has been executed.
+    ///
+    /// ### Takes
+    /// - `state`: The [State] after the instruction.
+    ///
+    /// ### Returns
+    /// - [HookAction::Abort] to abort the run with a [CannonError::StepAborted].
+    fn post_step(&mut self, state: &mut State) -> HookAction;
+}
+
+impl<F: FnMut(&mut State) -> HookAction> PostStepHook for F {
+    fn post_step(&mut self, state: &mut State) -> HookAction {
+        self(state)
+    }
+}
+
+/// An [FpTrapHandler] is invoked by the [crate::InstrumentedState] in place of executing a
+/// floating-point instruction, which the emulator does not support. Closures taking a
+/// `&mut State` and the instruction word, and returning a [HookAction], are [FpTrapHandler]s.
+///
+/// The same caveats as for [PreStepHook]s apply to modifying the [State].
+pub trait FpTrapHandler {
+    /// Invoked for the floating-point instruction at the current program counter.
+    ///
+    /// ### Takes
+    /// - `state`: The [State] prior to the instruction.
+    /// - `instruction`: The floating-point instruction word.
+    ///
+    /// ### Returns
+    /// - [HookAction::Continue] if the handler emulated the instruction. The emulator then
+    ///   advances to the next instruction, so branches on floating-point conditions cannot be
+    ///   emulated.
+    /// - [HookAction::Abort] to abort the run with a [CannonError::FloatingPointInstruction].
+    fn trap(&mut self, state: &mut State, instruction: u32) -> HookAction;
+}
+
+impl<F: FnMut(&mut State, u32) -> HookAction> FpTrapHandler for F {
+    fn trap(&mut self, state: &mut State, instruction: u32) -> HookAction {
+        self(state, instruction)
+    }
+}
+
+/// The [StepHooks] hold the [PreStepHook]s and [PostStepHook]s registered with the
+/// [crate::InstrumentedState].
+#[derive(Default)]
+pub(crate) struct StepHooks {
+    /// The hooks invoked before each instruction.
+    pre: Vec<Box<dyn PreStepHook>>,
+    /// The hooks invoked after each instruction.
+    post: Vec<Box<dyn PostStepHook>>,
+}
+
+impl StepHooks {
+    /// Returns `true` if no hooks are registered.
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty()
+    }
+
+    /// Registers a [PreStepHook].
+    pub(crate) fn add_pre(&mut self, hook: Box<dyn PreStepHook>) {
+        self.pre.push(hook);
+    }
+
+    /// Registers a [PostStepHook].
+    pub(crate) fn add_post(&mut self, hook: Box<dyn PostStepHook>) {
+        self.post.push(hook);
+    }
+
+    /// Invokes all [PreStepHook]s in the order that they were registered.
+    ///
+    /// ### Returns
+    /// - `Err(CannonError::StepAborted)` if any hook returned [HookAction::Abort].
+    pub(crate) fn pre_step(&mut self, state: &mut State) -> CannonResult<()> {
+        let mut abort = false;
+        for hook in self.pre.iter_mut() {
+            abort |= hook.pre_step(state) == HookAction::Abort;
+        }
+        Self::check(abort, state)
+    }
+
+    /// Invokes all [PostStepHook]s in the order that they were registered.
+    ///
+    /// ### Returns
+    /// - `Err(CannonError::StepAborted)` if any hook returned [HookAction::Abort].
+    pub(crate) fn post_step(&mut self, state: &mut State) -> CannonResult<()> {
+        let mut abort = false;
+        for hook in self.post.iter_mut() {
+            abort |= hook.post_step(state) == HookAction::Abort;
+        }
+        Self::check(abort, state)
+    }
+
+    /// Converts the combined [HookAction] of the hooks into a result.
+    fn check(abort: bool, state: &State) -> CannonResult<()> {
+        if abort {
+            return Err(CannonError::StepAborted {
+                pc: state.pc,
+                step: state.step,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The [EntryHooks] hold the function-entry callbacks registered with
+/// [crate::InstrumentedState::on_enter], keyed by the entry addresses of the matched functions.
+#[derive(Default)]
+pub(crate) struct EntryHooks {
+    /// The registered callbacks.
+    callbacks: Vec<EntryCallback>,
+    /// Map of function entry addresses to the function's name and the indices of its callbacks.
+    entries: FxHashMap<Address, (String, Vec<usize>)>,
+}
+
+impl EntryHooks {
+    /// Returns `true` if no hooks are registered.
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Registers a callback for all symbols of the [Metadata] that match the pattern.
+    ///
+    /// ### Returns
+    /// - The number of symbols that matched the pattern.
+    pub(crate) fn register(
+        &mut self,
+        metadata: &Metadata,
+        symbol_pattern: &str,
+        callback: EntryCallback,
+    ) -> usize {
+        let index = self.callbacks.len();
+        let mut matched = 0;
+        for symbol in metadata
+            .symbols
+            .iter()
+            .filter(|symbol| glob_match(symbol_pattern, &symbol.name))
+        {
+            self.entries
+                .entry(symbol.start)
+                .or_insert_with(|| (symbol.name.clone(), Vec::new()))
+                .1
+                .push(index);
+            matched += 1;
+        }
+        self.callbacks.push(callback);
+        matched
+    }
+
+    /// Invokes the callbacks registered for the function starting at the current program counter,
+    /// if any.
+    ///
+    /// ### Returns
+    /// - `Err(CannonError::HookAborted)` if any callback returned [HookAction::Abort].
+    pub(crate) fn dispatch(&mut self, state: &State) -> CannonResult<()> {
+        let Some((name, indices)) = self.entries.get(&state.pc) else {
+            return Ok(());
+        };
+
+        let mut abort = false;
+        for &i in indices {
+            abort |= (self.callbacks[i])(state, name) == HookAction::Abort;
+        }
+        if abort {
+            return Err(CannonError::HookAborted {
+                symbol: name.clone(),
+                pc: state.pc,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Matches a symbol name against a pattern, where `*` matches any sequence of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard; the pattern must match exactly.
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::StaticOracle, InstrumentedState, Symbol};
+    use std::{cell::RefCell, io, rc::Rc};
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("runtime.gcenable", "runtime.gcenable"));
+        assert!(!glob_match("runtime.gc", "runtime.gcenable"));
+        assert!(glob_match("runtime.gc*", "runtime.gcenable"));
+        assert!(glob_match("*.init", "flag.init"));
+        assert!(glob_match(
+            "github.com/*/prometheus.*",
+            "github.com/x/prometheus.init"
+        ));
+        assert!(!glob_match("*.init", "flag.init.0"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn on_enter() {
+        // 0x00: j 0x10
+        // 0x04: nop
+        // 0x10: nop
+        let mut state = State {
+            next_pc: 4,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x00, 0x08_00_00_04).unwrap();
+        let metadata = Metadata {
+            symbols: vec![Symbol {
+                name: "main.target".to_string(),
+                start: 0x10,
+                size: 0x10,
+            }],
+        };
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::new(Vec::new()), io::sink(), io::sink());
+        let entered = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&entered);
+        let matched = ins.on_enter(&metadata, "main.*", move |state, name| {
+            log.borrow_mut().push((state.step, name.to_string()));
+            HookAction::Abort
+        });
+        assert_eq!(matched, 1);
+
+        ins.step(false).unwrap();
+        ins.step(false).unwrap();
+        let err = ins.step(false).unwrap_err();
+        assert!(matches!(err, CannonError::HookAborted { pc: 0x10, .. }));
+        assert_eq!(*entered.borrow(), vec![(2, "main.target".to_string())]);
+        assert_eq!(ins.state.step, 2);
+    }
+
+    #[test]
+    fn fp_trap() {
+        // 0x1000: lwc1 $f0, 0($sp)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xC7A00000).unwrap();
+
+        let mut ins = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let err = ins.step(false).unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::FloatingPointInstruction {
+                pc: 0x1000,
+                instruction: 0xC7A00000
+            }
+        ));
+        assert!(err.to_string().contains("soft-float"), "{err}");
+
+        // An emulating handler advances the guest past the instruction.
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        ins.set_fp_trap_handler(|state: &mut State, instruction: u32| {
+            state.registers[2] = instruction;
+            HookAction::Continue
+        });
+        ins.step(false).unwrap();
+        assert_eq!(ins.state.registers[2], 0xC7A00000);
+        assert_eq!((ins.state.pc, ins.state.next_pc), (0x1004, 0x1008));
+    }
+
+    #[test]
+    fn step_hooks() {
+        // 0x00: addiu $t0, $t0, 1 (repeated)
+        let mut state = State {
+            next_pc: 4,
+            ..Default::default()
+        };
+        for pc in (0..0x40).step_by(4) {
+            state.memory.set_memory(pc, 0x25_08_00_01).unwrap();
+        }
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&seen);
+        ins.add_pre_step_hook(move |state: &mut State| {
+            log.borrow_mut().push(state.pc);
+            if state.step == 3 {
+                HookAction::Abort
+            } else {
+                HookAction::Continue
+            }
+        });
+        // Double the counter after every instruction.
+        ins.add_post_step_hook(|state: &mut State| {
+            state.registers[8] *= 2;
+            HookAction::Continue
+        });
+
+        ins.step(false).unwrap();
+        assert_eq!(ins.step_threaded(2).unwrap(), 2);
+        let err = ins.step_threaded(8).unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::StepAborted { pc: 0x0c, step: 3 }
+        ));
+
+        // The vetoed instruction was not executed.
+        assert_eq!(*seen.borrow(), vec![0x00, 0x04, 0x08, 0x0c]);
+        assert_eq!(ins.state.step, 3);
+        assert_eq!(ins.state.registers[8], 14);
+    }
+}
@@ -1,11 +1,27 @@
 //! This module contains the [InstrumentedState] definition.
 
-use crate::{traits::PreimageOracle, Address, State, StepWitness};
-use anyhow::Result;
+use super::{
+    block_cache::BlockCache,
+    hooks::{EntryHooks, HookAction, StepHooks},
+    FpTrapHandler, PostStepHook, PreStepHook,
+};
+use crate::{
+    traits::{PreimageOracle, Stepper},
+    Address, CannonResult, Coverage, InstructionHistogram, MemAccess, Metadata, State,
+    StateWitnessHasher, StepWitness,
+};
+use alloy_primitives::B256;
+use preimage_oracle::KeyType;
 use std::io::{BufWriter, Write};
 
+pub(crate) const MIPS_ENOENT: u32 = 0x2;
 pub(crate) const MIPS_EBADF: u32 = 0x9;
+pub(crate) const MIPS_EAGAIN: u32 = 0xB;
+pub(crate) const MIPS_ENOMEM: u32 = 0xC;
 pub(crate) const MIPS_EINVAL: u32 = 0x16;
+pub(crate) const MIPS_EROFS: u32 = 0x1E;
+pub(crate) const MIPS_ENOSYS: u32 = 0x59;
+pub(crate) const MIPS_ETIMEDOUT: u32 = 0x91;
 
 /// The [InstrumentedState] is a wrapper around [State] that contains cached machine state,
 /// the input and output buffers, and an implementation of the MIPS VM.
@@ -26,6 +42,18 @@ pub struct InstrumentedState<O: Write, E: Write, P: PreimageOracle> {
     pub(crate) mem_proof_enabled: bool,
     /// The memory proof, if it is enabled.
     pub(crate) mem_proof: [u8; 28 * 32],
+    /// Whether or not the memory word accessed by each step is recorded.
+    pub(crate) record_mem_access: bool,
+    /// The memory word accessed by the last step and its value before the step, if recorded.
+    pub(crate) mem_access: Option<(Address, u32)>,
+    /// Whether or not every memory access performed by each step is recorded.
+    pub(crate) record_access_log: bool,
+    /// The memory accesses performed by the last step, in order, if recorded.
+    pub(crate) access_log: Vec<MemAccess>,
+    /// The [Coverage] bitmap of the executed instructions, if coverage collection is enabled.
+    pub(crate) coverage: Option<Coverage>,
+    /// The [InstructionHistogram] of the executed instructions, if it is enabled.
+    pub(crate) histogram: Option<InstructionHistogram>,
     /// The [PreimageOracle] used to fetch preimages.
     pub(crate) preimage_oracle: P,
     /// Cached pre-image data, including 8 byte length prefix
@@ -35,6 +63,15 @@ pub struct InstrumentedState<O: Write, E: Write, P: PreimageOracle> {
     /// The offset we last read from, or max u32 if nothing is read at
     /// the current step.
     pub(crate) last_preimage_offset: u32,
+    /// The cache of translated instruction blocks used by the threaded execution mode.
+    pub(crate) block_cache: BlockCache,
+    /// The function-entry hooks registered with [InstrumentedState::on_enter].
+    pub(crate) entry_hooks: EntryHooks,
+    /// The hooks registered with [InstrumentedState::add_pre_step_hook] and
+    /// [InstrumentedState::add_post_step_hook].
+    pub(crate) step_hooks: StepHooks,
+    /// The handler registered with [InstrumentedState::set_fp_trap_handler], if any.
+    pub(crate) fp_trap: Option<Box<dyn FpTrapHandler>>,
 }
 
 impl<O, E, P> InstrumentedState<O, E, P>
@@ -51,23 +88,174 @@ where
             last_mem_access: 0,
             mem_proof_enabled: false,
             mem_proof: [0u8; 28 * 32],
+            record_mem_access: false,
+            mem_access: None,
+            record_access_log: false,
+            access_log: Vec::default(),
+            coverage: None,
+            histogram: None,
             preimage_oracle: oracle,
             last_preimage: Vec::default(),
             last_preimage_key: [0u8; 32],
             last_preimage_offset: 0,
+            block_cache: BlockCache::default(),
+            entry_hooks: EntryHooks::default(),
+            step_hooks: StepHooks::default(),
+            fp_trap: None,
         }
     }
 
+    /// Registers a callback that is invoked whenever the guest enters a function whose name
+    /// matches the pattern, before the function's first instruction is executed. Returning
+    /// [HookAction::Abort] from the callback aborts the step with a
+    /// [crate::CannonError::HookAborted].
+    ///
+    /// ### Takes
+    /// - `metadata`: The [Metadata] of the program, containing its symbols.
+    /// - `symbol_pattern`: The name of the function, where `*` matches any sequence of
+    ///   characters.
+    /// - `callback`: The callback, which receives the [State] and the name of the entered function.
+    ///
+    /// ### Returns
+    /// - The number of functions that matched the pattern.
+    pub fn on_enter(
+        &mut self,
+        metadata: &Metadata,
+        symbol_pattern: &str,
+        callback: impl FnMut(&State, &str) -> HookAction + 'static,
+    ) -> usize {
+        self.entry_hooks
+            .register(metadata, symbol_pattern, Box::new(callback))
+    }
+
+    /// Registers a [PreStepHook], which is invoked before every instruction and may abort the run
+    /// before the instruction is executed. Hooks are invoked in the order that they were
+    /// registered, after any function-entry hooks.
+    ///
+    /// ### Takes
+    /// - `hook`: The [PreStepHook] to register.
+    pub fn add_pre_step_hook(&mut self, hook: impl PreStepHook + 'static) {
+        self.step_hooks.add_pre(Box::new(hook));
+    }
+
+    /// Registers a [PostStepHook], which is invoked after every instruction and may abort the
+    /// run. Hooks are invoked in the order that they were registered.
+    ///
+    /// ### Takes
+    /// - `hook`: The [PostStepHook] to register.
+    pub fn add_post_step_hook(&mut self, hook: impl PostStepHook + 'static) {
+        self.step_hooks.add_post(Box::new(hook));
+    }
+
+    /// Sets the [FpTrapHandler], which is invoked in place of executing floating-point
+    /// instructions. Without a handler, floating-point instructions abort the run with a
+    /// [crate::CannonError::FloatingPointInstruction].
+    ///
+    /// ### Takes
+    /// - `handler`: The [FpTrapHandler] to set, replacing any previous one.
+    pub fn set_fp_trap_handler(&mut self, handler: impl FpTrapHandler + 'static) {
+        self.fp_trap = Some(Box::new(handler));
+    }
+
+    /// Enables or disables recording of the memory word accessed by each step, which is then
+    /// available from [InstrumentedState::mem_access]. Only [InstrumentedState::step] records
+    /// accesses; the threaded execution mode does not.
+    ///
+    /// ### Takes
+    /// - `enabled`: Whether or not to record memory accesses.
+    pub fn set_record_mem_access(&mut self, enabled: bool) {
+        self.record_mem_access = enabled;
+        self.mem_access = None;
+    }
+
+    /// Returns the memory word accessed by the last step, along with its value before the step,
+    /// if recording is enabled with [InstrumentedState::set_record_mem_access] and the step
+    /// accessed memory other than fetching its instruction.
+    pub fn mem_access(&self) -> Option<(Address, u32)> {
+        self.mem_access
+    }
+
+    /// Enables or disables recording of every memory access performed by each step, including
+    /// the instruction fetch and the accesses of syscalls. The accesses of the last step are
+    /// available from [InstrumentedState::access_log], and are attached to its [StepWitness] if
+    /// a proof is requested. Only [InstrumentedState::step] records accesses; the threaded
+    /// execution mode does not.
+    ///
+    /// ### Takes
+    /// - `enabled`: Whether or not to record memory accesses.
+    pub fn set_record_access_log(&mut self, enabled: bool) {
+        self.record_access_log = enabled;
+        self.access_log.clear();
+    }
+
+    /// Returns the memory accesses performed by the last step, in order, if recording is enabled
+    /// with [InstrumentedState::set_record_access_log].
+    pub fn access_log(&self) -> &[MemAccess] {
+        &self.access_log
+    }
+
+    /// Enables collection of the [Coverage] of the guest program, recording the program counter of
+    /// every instruction executed from now on by both [InstrumentedState::step] and
+    /// [InstrumentedState::step_threaded]. Coverage that was already collected is kept.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Coverage::default);
+    }
+
+    /// Returns the [Coverage] collected so far, if coverage collection is enabled.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Disables coverage collection, returning the [Coverage] collected so far.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Enables collection of the [InstructionHistogram] of the guest program, recording every
+    /// instruction executed from now on by both [InstrumentedState::step] and
+    /// [InstrumentedState::step_threaded]. Instructions that were already recorded are kept.
+    pub fn enable_instruction_histogram(&mut self) {
+        self.histogram
+            .get_or_insert_with(InstructionHistogram::default);
+    }
+
+    /// Returns the [InstructionHistogram] collected so far, if it is enabled.
+    pub fn instruction_histogram(&self) -> Option<&InstructionHistogram> {
+        self.histogram.as_ref()
+    }
+
+    /// Disables the [InstructionHistogram], returning the one collected so far.
+    pub fn take_instruction_histogram(&mut self) -> Option<InstructionHistogram> {
+        self.histogram.take()
+    }
+
     /// Step the MIPS emulator forward one instruction.
     ///
     /// ### Returns
     /// - Ok(Some(witness)): The [StepWitness] for the current
     /// - Err(_): An error occurred while processing the instruction step in the MIPS emulator.
     #[inline(always)]
-    pub fn step(&mut self, proof: bool) -> Result<Option<StepWitness>> {
+    pub fn step(&mut self, proof: bool) -> CannonResult<Option<StepWitness>> {
+        crate::traces::trace!(
+            target: "mipsevm::stepper",
+            step = self.state.step,
+            pc = self.state.pc,
+            proof,
+            "Stepping"
+        );
+
+        if !self.entry_hooks.is_empty() {
+            self.entry_hooks.dispatch(&self.state)?;
+        }
+        if !self.step_hooks.is_empty() {
+            self.step_hooks.pre_step(&mut self.state)?;
+        }
+
         self.mem_proof_enabled = proof;
         self.last_mem_access = !0u32 as Address;
         self.last_preimage_offset = !0u32;
+        self.mem_access = None;
+        self.access_log.clear();
 
         let mut witness = None;
         if proof {
@@ -82,8 +270,24 @@ where
             })
         }
 
+        if let Some(ref mut coverage) = self.coverage {
+            if !self.state.exited {
+                coverage.record(self.state.pc);
+            }
+        }
+        if self.histogram.is_some() && !self.state.exited {
+            let instruction = self.state.memory.get_memory(self.state.pc)?;
+            if let Some(ref mut histogram) = self.histogram {
+                histogram.record(&self.state, instruction);
+            }
+        }
+
         self.inner_step()?;
 
+        if !self.step_hooks.is_empty() {
+            self.step_hooks.post_step(&mut self.state)?;
+        }
+
         if proof {
             witness = witness.map(|mut wit| {
                 wit.mem_proof[28 * 32..].copy_from_slice(self.mem_proof.as_slice());
@@ -91,6 +295,15 @@ where
                     wit.preimage_key = Some(self.last_preimage_key);
                     wit.preimage_value = Some(self.last_preimage.clone());
                     wit.preimage_offset = Some(self.last_preimage_offset);
+                    if self.last_preimage_key[0] == KeyType::Precompile as u8 {
+                        wit.precompile_call = self
+                            .preimage_oracle
+                            .precompile_call(self.last_preimage_key)
+                            .map(|call| [&call.address[..], &call.input].concat());
+                    }
+                }
+                if self.record_access_log {
+                    wit.mem_accesses = Some(self.access_log.clone());
                 }
                 wit
             })
@@ -99,6 +312,118 @@ where
         Ok(witness)
     }
 
+    /// Step the MIPS emulator forward `n` instructions, returning the state hash after each of
+    /// them. Neither a [StepWitness] nor the encoded [crate::StateWitness] is materialized; the
+    /// hash is streamed from the fields of the state, which makes this suitable for computing the
+    /// ladder of state hashes that a bisection game commits to.
+    ///
+    /// ### Takes
+    /// - `n`: The number of instructions to execute.
+    ///
+    /// ### Returns
+    /// - Ok(hashes): The state hash after each executed instruction. Fewer than `n` hashes are
+    ///   returned only if the program exited.
+    /// - Err(_): An error occurred while processing an instruction step in the MIPS emulator.
+    pub fn step_n(&mut self, n: u64) -> CannonResult<Vec<B256>> {
+        let mut hashes = Vec::with_capacity(n.min(1 << 20) as usize);
+        for _ in 0..n {
+            if self.state.exited {
+                break;
+            }
+            self.step(false)?;
+            hashes.push(B256::from(self.state.witness_state()?.state_hash()));
+        }
+        Ok(hashes)
+    }
+
+    /// Step the MIPS emulator forward up to `max_steps` instructions in the threaded execution
+    /// mode.
+    ///
+    /// The threaded execution mode fetches instructions from a cache of pre-translated blocks
+    /// rather than from [crate::Memory], and skips all bookkeeping required for witness
+    /// generation. It is intended for the stretches of a run where no proof is requested; the
+    /// resulting state is identical to stepping with [InstrumentedState::step].
+    ///
+    /// ### Takes
+    /// - `max_steps`: The maximum number of instructions to execute.
+    ///
+    /// ### Returns
+    /// - Ok(n): The number of instructions executed. This is less than `max_steps` only if the
+    ///   program exited.
+    /// - Err(_): An error occurred while processing an instruction step in the MIPS emulator.
+    pub fn step_threaded(&mut self, max_steps: u64) -> CannonResult<u64> {
+        let _span = crate::traces::debug_span!(
+            target: "mipsevm::stepper",
+            "step_threaded",
+            step = self.state.step,
+            max_steps
+        );
+        self.mem_proof_enabled = false;
+
+        let mut executed = 0;
+        while executed < max_steps && !self.state.exited {
+            crate::traces::trace!(
+                target: "mipsevm::stepper",
+                step = self.state.step,
+                pc = self.state.pc,
+                "Entering block"
+            );
+            let block = self
+                .block_cache
+                .get_or_translate(self.state.pc, &mut self.state.memory)?;
+            let generation = self.block_cache.generation;
+
+            for cached in block.iter() {
+                if executed == max_steps || self.state.exited || self.state.pc != cached.pc {
+                    break;
+                }
+
+                if !self.entry_hooks.is_empty() {
+                    self.entry_hooks.dispatch(&self.state)?;
+                }
+                if !self.step_hooks.is_empty() {
+                    self.step_hooks.pre_step(&mut self.state)?;
+                    // The hook may have redirected control flow away from the translated block.
+                    if self.state.exited || self.state.pc != cached.pc {
+                        break;
+                    }
+                }
+
+                if let Some(ref mut coverage) = self.coverage {
+                    coverage.record(cached.pc);
+                }
+                if let Some(ref mut histogram) = self.histogram {
+                    histogram.record(&self.state, cached.decoded.instruction);
+                }
+
+                self.state.step += 1;
+                self.step_decoded(&cached.decoded)?;
+                executed += 1;
+                if self.record_access_log {
+                    self.access_log.clear();
+                }
+
+                if !self.step_hooks.is_empty() {
+                    self.step_hooks.post_step(&mut self.state)?;
+                }
+
+                // If the instruction wrote to translated code, the remainder of the block is stale.
+                if self.block_cache.generation != generation {
+                    break;
+                }
+            }
+        }
+
+        Ok(executed)
+    }
+
+    /// Flushes the cache of translated instruction blocks used by
+    /// [InstrumentedState::step_threaded]. This must be called if the code within
+    /// [InstrumentedState::state] is modified outside of the emulator.
+    pub fn invalidate_block_cache(&mut self) {
+        self.block_cache.clear();
+    }
+
     /// Returns the stdout buffer.
     pub fn std_out(&self) -> &[u8] {
         self.std_out.buffer()
@@ -110,90 +435,38 @@ where
     }
 }
 
+impl<O, E, P> Stepper for InstrumentedState<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn step(&mut self, proof: bool) -> CannonResult<Option<StepWitness>> {
+        InstrumentedState::step(self, proof)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use alloy_primitives::keccak256;
 
-    use crate::test_utils::{ClaimTestOracle, BASE_ADDR_END, END_ADDR};
+    use crate::test_utils::{open_mips, ClaimTestOracle};
     use crate::witness::STATE_WITNESS_SIZE;
     use crate::{load_elf, patch, StateWitnessHasher};
-    use crate::{test_utils::StaticOracle, Address, InstrumentedState, Memory, State};
-    use std::io::BufWriter;
-    use std::{
-        fs,
-        io::{self, BufReader},
-        path::PathBuf,
-    };
-
-    mod open_mips {
-        use super::*;
-
-        #[test]
-        fn open_mips_tests() {
-            let tests_path = PathBuf::from(std::env::current_dir().unwrap())
-                .join("open_mips_tests")
-                .join("test")
-                .join("bin");
-            let test_files = fs::read_dir(tests_path).unwrap();
-
-            for f in test_files.into_iter() {
-                if let Ok(f) = f {
-                    let file_name = String::from(f.file_name().to_str().unwrap());
-
-                    // Short circuit early for `exit_group.bin`
-                    let exit_group = file_name == "exit_group.bin";
-
-                    let program_mem = fs::read(f.path()).unwrap();
-
-                    let mut state = {
-                        let mut state = State::default();
-                        state.pc = 0;
-                        state.next_pc = 4;
-                        state.memory = Memory::default();
-                        state
-                    };
-                    state
-                        .memory
-                        .set_memory_range(0, BufReader::new(program_mem.as_slice()))
-                        .unwrap();
-
-                    // Set the return address ($ra) to jump into when the test completes.
-                    state.registers[31] = END_ADDR;
-
-                    let mut ins = InstrumentedState::new(
-                        state,
-                        StaticOracle::new(b"hello world".to_vec()),
-                        io::stdout(),
-                        io::stderr(),
-                    );
-
-                    for _ in 0..1000 {
-                        if ins.state.pc == END_ADDR {
-                            break;
-                        }
-                        if exit_group && ins.state.exited {
-                            break;
-                        }
-                        ins.step(false).unwrap();
-                    }
+    use crate::{test_utils::StaticOracle, InstrumentedState, State};
+    use std::io::{self, BufWriter};
 
-                    if exit_group {
-                        assert_ne!(END_ADDR, ins.state.pc, "must not reach end");
-                        assert!(ins.state.exited, "must exit");
-                        assert_eq!(1, ins.state.exit_code, "must exit with 1");
-                    } else {
-                        assert_eq!(END_ADDR, ins.state.pc, "must reach end");
-                        let mut state = ins.state.memory;
-                        let (done, result) = (
-                            state.get_memory((BASE_ADDR_END + 4) as Address).unwrap(),
-                            state.get_memory((BASE_ADDR_END + 8) as Address).unwrap(),
-                        );
-                        assert_eq!(done, 1, "must set done to 1");
-                        assert_eq!(result, 1, "must have success result {:?}", f.file_name());
-                    }
-                }
-            }
-        }
+    #[test]
+    fn open_mips_tests() {
+        open_mips::run_all(|ins| {
+            ins.step(false)?;
+            Ok(())
+        })
+        .unwrap();
     }
 
     #[test]
@@ -237,6 +510,55 @@ mod test {
         }
     }
 
+    #[test]
+    fn access_log() {
+        use crate::{MemAccess, MemAccessKind};
+
+        // 0x1000: sw $t0, 0($t1); 0x1004: lw $t2, 0($t1)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xAD280000).unwrap();
+        state.memory.set_memory(0x1004, 0x8D2A0000).unwrap();
+        state.memory.set_memory(0x2000, 0x11111111).unwrap();
+        state.registers[8] = 0xCAFEBABE;
+        state.registers[9] = 0x2000;
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+
+        // Accesses are not recorded unless enabled.
+        assert!(ins.step(true).unwrap().unwrap().mem_accesses.is_none());
+        ins.state.pc = 0x1000;
+        ins.state.next_pc = 0x1004;
+        ins.state.memory.set_memory(0x2000, 0x11111111).unwrap();
+        ins.set_record_access_log(true);
+
+        let access = |address, old, new, kind| MemAccess {
+            address,
+            old,
+            new,
+            kind,
+        };
+        let witness = ins.step(true).unwrap().unwrap();
+        assert_eq!(
+            witness.mem_accesses.unwrap(),
+            [
+                access(0x1000, 0xAD280000, 0xAD280000, MemAccessKind::Fetch),
+                access(0x2000, 0x11111111, 0xCAFEBABE, MemAccessKind::Write),
+            ]
+        );
+        ins.step(false).unwrap();
+        assert_eq!(
+            ins.access_log(),
+            [
+                access(0x1004, 0x8D2A0000, 0x8D2A0000, MemAccessKind::Fetch),
+                access(0x2000, 0xCAFEBABE, 0xCAFEBABE, MemAccessKind::Read),
+            ]
+        );
+    }
+
     #[test]
     fn test_hello() {
         let elf_bytes = include_bytes!("../../../../example/bin/hello.elf");
@@ -269,6 +591,53 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_hello_threaded() {
+        let elf_bytes = include_bytes!("../../../../example/bin/hello.elf");
+        let mut state = load_elf(elf_bytes).unwrap();
+        patch::patch_go(elf_bytes, &mut state).unwrap();
+        patch::patch_stack(&mut state).unwrap();
+
+        let mut precise = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::new(b"hello world".to_vec()),
+            BufWriter::new(Vec::default()),
+            BufWriter::new(Vec::default()),
+        );
+        let mut threaded = InstrumentedState::new(
+            state,
+            StaticOracle::new(b"hello world".to_vec()),
+            BufWriter::new(Vec::default()),
+            BufWriter::new(Vec::default()),
+        );
+
+        // Step both emulators in lockstep chunks, comparing the state after each chunk.
+        for _ in 0..400 {
+            if precise.state.exited {
+                break;
+            }
+            for _ in 0..1_000 {
+                precise.step(false).unwrap();
+            }
+            threaded.step_threaded(1_000).unwrap();
+
+            assert_eq!(precise.state.step, threaded.state.step);
+            assert_eq!(
+                precise.state.encode_witness().unwrap(),
+                threaded.state.encode_witness().unwrap(),
+                "threaded execution must not diverge at step {}",
+                precise.state.step
+            );
+        }
+
+        assert!(threaded.state.exited, "must exit");
+        assert_eq!(threaded.state.exit_code, 0, "must exit with 0");
+        assert_eq!(
+            String::from_utf8(threaded.std_out.buffer().to_vec()).unwrap(),
+            "hello world!\n"
+        );
+    }
+
     #[test]
     fn test_claim() {
         let elf_bytes = include_bytes!("../../../../example/bin/claim.elf");
@@ -305,4 +674,199 @@ mod test {
             "started!"
         );
     }
+
+    #[test]
+    fn mips32r2() {
+        // $a0 = 0x12345687, $a1 = 4, $v0 = 0xAAAAAAAA
+        let cases = [
+            ("seb $v0, $a0", 0x7C041420, 0xFFFFFF87),
+            ("seh $v0, $a0", 0x7C041620, 0x00005687),
+            ("wsbh $v0, $a0", 0x7C0410A0, 0x34128756),
+            ("ext $v0, $a0, 4, 8", 0x7C823900, 0x00000068),
+            ("ins $v0, $a0, 8, 8", 0x7C827A04, 0xAAAA87AA),
+            ("rotr $v0, $a0, 8", 0x00241202, 0x87123456),
+            ("rotrv $v0, $a0, $a1", 0x00A41046, 0x71234568),
+        ];
+
+        for (name, instruction, expected) in cases {
+            let mut state = State {
+                pc: 0x1000,
+                next_pc: 0x1004,
+                ..Default::default()
+            };
+            state.memory.set_memory(0x1000, instruction).unwrap();
+            state.registers[2] = 0xAAAAAAAA;
+            state.registers[4] = 0x12345687;
+            state.registers[5] = 4;
+
+            // Without MIPS32r2, SPECIAL3 is invalid and the rotations execute as srl and srlv.
+            let mut ins = InstrumentedState::new(
+                state.clone(),
+                StaticOracle::default(),
+                io::sink(),
+                io::sink(),
+            );
+            if instruction >> 26 == 0x1F {
+                assert!(ins.step(false).is_err(), "{name}");
+            } else {
+                ins.step(false).unwrap();
+                assert_ne!(ins.state.registers[2], expected, "{name}");
+            }
+
+            state.enable_mips32r2();
+            let mut ins =
+                InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+            ins.step(false).unwrap();
+            assert_eq!(ins.state.registers[2], expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn step_n() {
+        // 0x1000: addiu $t0, $t0, 1 (repeated)
+        // 0x1010: syscall (exit_group)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        for pc in (0x1000..0x1010).step_by(4) {
+            state.memory.set_memory(pc, 0x25080001).unwrap();
+        }
+        state.memory.set_memory(0x1010, 0x0000000C).unwrap();
+        state.registers[2] = 4246;
+
+        let mut stepped = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let mut expected = Vec::new();
+        for _ in 0..3 {
+            stepped.step(false).unwrap();
+            expected.push(stepped.state.state_hash().unwrap());
+        }
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        let hashes = ins.step_n(3).unwrap();
+        assert_eq!(
+            hashes.iter().map(|hash| hash.0).collect::<Vec<_>>(),
+            expected
+        );
+
+        // The ladder ends at the step that exits the program.
+        let hashes = ins.step_n(10).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert!(ins.state.exited);
+        assert_eq!(hashes[1].0, ins.state.state_hash().unwrap());
+    }
+
+    #[test]
+    fn record_mem_access() {
+        // 0x1000: lw $t0, 0x100($zero)
+        // 0x1004: sw $t0, 0x104($zero)
+        // 0x1008: addiu $t0, $t0, 1
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        for (i, instruction) in [0x8C080100, 0xAC080104, 0x25080001].into_iter().enumerate() {
+            state
+                .memory
+                .set_memory(0x1000 + i as u32 * 4, instruction)
+                .unwrap();
+        }
+        state.memory.set_memory(0x100, 42).unwrap();
+        state.memory.set_memory(0x104, 7).unwrap();
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        ins.step(false).unwrap();
+        assert_eq!(ins.mem_access(), None, "Recording is disabled by default");
+
+        ins.set_record_mem_access(true);
+        ins.step(false).unwrap();
+        assert_eq!(ins.mem_access(), Some((0x104, 7)));
+        assert_eq!(ins.state.memory.get_memory(0x104).unwrap(), 42);
+
+        ins.step(false).unwrap();
+        assert_eq!(ins.mem_access(), None);
+    }
+
+    #[test]
+    fn coverage() {
+        // 0x1000: beq $zero, $zero, 0x100c
+        // 0x1004: nop
+        // 0x1008: addiu $t0, $t0, 1
+        // 0x100c: addiu $t0, $t0, 1
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        for (i, instruction) in [0x10000002, 0, 0x25080001, 0x25080001]
+            .into_iter()
+            .enumerate()
+        {
+            state
+                .memory
+                .set_memory(0x1000 + i as u32 * 4, instruction)
+                .unwrap();
+        }
+
+        let mut stepped = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        assert!(stepped.coverage().is_none());
+        stepped.enable_coverage();
+        for _ in 0..3 {
+            stepped.step(false).unwrap();
+        }
+
+        let mut threaded =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        threaded.enable_coverage();
+        threaded.step_threaded(3).unwrap();
+
+        let coverage = stepped.take_coverage().unwrap();
+        assert_eq!(coverage.addresses(), vec![0x1000, 0x1004, 0x100c]);
+        assert_eq!(threaded.coverage(), Some(&coverage));
+        assert!(stepped.coverage().is_none());
+    }
+
+    #[test]
+    fn preimage_read_bounds() {
+        use crate::CannonError;
+        use preimage_oracle::{Keccak256Key, Key};
+
+        let key = (keccak256(b"hello world").0 as Keccak256Key).preimage_key();
+        let mut ins = InstrumentedState::new(
+            State::default(),
+            StaticOracle::new(b"hello world".to_vec()),
+            io::sink(),
+            io::sink(),
+        );
+
+        // The end of the length-prefixed preimage reads nothing.
+        let (_, len) = ins.read_preimage(key, 8 + 11).unwrap();
+        assert_eq!(len, 0);
+        let (data, len) = ins.read_preimage(key, 8).unwrap();
+        assert_eq!(&data[..len], b"hello world");
+
+        let err = ins.read_preimage(key, 8 + 12).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CannonError>(),
+            Some(CannonError::PreimageOutOfBounds {
+                offset: 20,
+                length: 19,
+                ..
+            })
+        ));
+    }
 }
@@ -1,11 +1,16 @@
 //! This module contains the MIPS VM implementation for the [InstrumentedState].
 
 use crate::{
+    clock::Clock,
+    disasm,
+    entropy::MAX_GETRANDOM_LEN,
+    futex, mem_access,
     memory::MemoryReader,
-    mips::instrumented::{MIPS_EBADF, MIPS_EINVAL},
+    mips::instrumented::{MIPS_EBADF, MIPS_EINVAL, MIPS_ENOENT},
     page,
     types::Syscall,
-    Address, Fd, InstrumentedState, PreimageOracle,
+    Access, Address, CannonError, Fd, HookAction, InstrumentedState, MemAccess, MemAccessKind,
+    PreimageOracle,
 };
 use anyhow::Result;
 use std::io::{self, BufReader, Read, Write};
@@ -32,6 +37,12 @@ where
         offset: u32,
     ) -> Result<([u8; 32], usize)> {
         if key != self.last_preimage_key {
+            crate::traces::debug!(
+                target: "mipsevm::oracle",
+                step = self.state.step,
+                "Fetching preimage for key {}",
+                alloy_primitives::B256::from(key)
+            );
             let data = self.preimage_oracle.get(key)?;
             self.last_preimage_key = key;
 
@@ -42,11 +53,27 @@ where
             self.last_preimage[8..].copy_from_slice(&data);
         }
 
+        // As in `MIPS.sol`, the offset may point at most to the end of the length-prefixed
+        // preimage, where the guest reads zero bytes.
+        if offset as usize > self.last_preimage.len() {
+            return Err(CannonError::PreimageOutOfBounds {
+                key,
+                offset,
+                length: self.last_preimage.len(),
+            }
+            .into());
+        }
         self.last_preimage_offset = offset;
 
         let mut data = [0u8; 32];
         let data_len =
             BufReader::new(&self.last_preimage[offset as usize..]).read(data.as_mut_slice())?;
+        self.preimage_oracle.on_read(
+            key,
+            self.last_preimage.len().saturating_sub(8) as u64,
+            offset,
+            &data[..data_len],
+        )?;
         Ok((data, data_len))
     }
 
@@ -59,6 +86,10 @@ where
     /// - A [Result] indicating if the operation was successful.
     #[inline(always)]
     pub(crate) fn track_mem_access(&mut self, effective_address: Address) -> Result<()> {
+        if self.record_mem_access && self.mem_access.is_none() {
+            let value = self.state.memory.get_memory(effective_address)?;
+            self.mem_access = Some((effective_address, value));
+        }
         if self.mem_proof_enabled && self.last_mem_access != effective_address {
             if self.last_mem_access != Address::MAX {
                 anyhow::bail!("Unexpected diffrent memory access at {:x}, already have access at {:x} buffered", effective_address, self.last_mem_access);
@@ -70,6 +101,81 @@ where
         Ok(())
     }
 
+    /// Appends an access to the memory access log of the step, if recording is enabled with
+    /// [InstrumentedState::set_record_access_log].
+    ///
+    /// ### Takes
+    /// - `address`: The address of the accessed word.
+    /// - `old`: The value of the word before the access.
+    /// - `new`: The value of the word after the access.
+    /// - `kind`: The kind of the access.
+    #[inline(always)]
+    pub(crate) fn log_access(&mut self, address: Address, old: u32, new: u32, kind: MemAccessKind) {
+        if self.record_access_log {
+            self.access_log.push(MemAccess {
+                address,
+                old,
+                new,
+                kind,
+            });
+        }
+    }
+
+    /// Appends reads of the words covering `[address, address + len)` to the memory access log
+    /// of the step, if recording is enabled, for syscalls that read memory in bulk.
+    ///
+    /// ### Takes
+    /// - `address`: The start of the read range.
+    /// - `len`: The length of the read range in bytes.
+    fn log_range_read(&mut self, address: Address, len: u32) -> Result<()> {
+        if !self.record_access_log || len == 0 {
+            return Ok(());
+        }
+        let end = address as u64 + len as u64;
+        let mut word_address = (address & 0xFFFFFFFC) as u64;
+        while word_address < end {
+            let value = self.state.memory.get_memory(word_address as Address)?;
+            self.log_access(word_address as Address, value, value, MemAccessKind::Read);
+            word_address += 4;
+        }
+        Ok(())
+    }
+
+    /// Checks an access against the [crate::PageProtection] of the state, if strict page
+    /// protection is enabled.
+    ///
+    /// ### Takes
+    /// - `address`: The accessed address.
+    /// - `access`: The kind of access.
+    ///
+    /// ### Returns
+    /// - A [Result] containing a [CannonError::ProtectionFault] if the access is not permitted.
+    #[inline(always)]
+    pub(crate) fn check_access(&self, address: Address, access: Access) -> Result<()> {
+        if let Some(protection) = &self.state.protection {
+            protection
+                .check(address, access, self.state.pc)
+                .map_err(|e| e.with_region(self.state.regions.as_ref()))?;
+        }
+        self.check_stack_guard(address)
+    }
+
+    /// Checks that an access does not touch the page guarding the stack, if a stack guard is
+    /// enabled in the [crate::MemoryRegions] of the state.
+    ///
+    /// ### Takes
+    /// - `address`: The accessed address.
+    ///
+    /// ### Returns
+    /// - A [Result] containing a [CannonError::StackOverflow] if the address is guarded.
+    #[inline(always)]
+    pub(crate) fn check_stack_guard(&self, address: Address) -> Result<()> {
+        if let Some(regions) = &self.state.regions {
+            regions.check(address, self.state.pc)?;
+        }
+        Ok(())
+    }
+
     /// Performs a single step of the MIPS thread context emulation.
     ///
     /// ### Returns
@@ -84,7 +190,43 @@ where
 
         // Fetch the instruction
         let instruction = self.state.memory.get_memory(self.state.pc as Address)?;
-        let opcode = instruction >> 26;
+        self.log_access(
+            self.state.pc,
+            instruction,
+            instruction,
+            MemAccessKind::Fetch,
+        );
+        self.step_instruction(instruction)
+    }
+
+    /// Executes a single, already fetched instruction within the MIPS thread context emulation.
+    ///
+    /// ### Takes
+    /// - `instruction`: The instruction located at the current program counter.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating if the step was successful.
+    #[inline(always)]
+    pub(crate) fn step_instruction(&mut self, instruction: u32) -> Result<()> {
+        self.step_decoded(&DecodedInstruction::new(instruction))
+    }
+
+    /// Executes a single, already decoded instruction within the MIPS thread context emulation.
+    ///
+    /// ### Takes
+    /// - `decoded`: The [DecodedInstruction] located at the current program counter.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating if the step was successful.
+    #[inline(always)]
+    pub(crate) fn step_decoded(&mut self, decoded: &DecodedInstruction) -> Result<()> {
+        self.check_access(self.state.pc, Access::Execute)?;
+        let instruction = decoded.instruction;
+        let opcode = decoded.opcode;
+
+        if decoded.is_floating_point {
+            return self.handle_fp_trap(instruction);
+        }
 
         // j-type j/jal
         if (2..=3).contains(&opcode) {
@@ -96,25 +238,31 @@ where
         }
 
         // Register fetch
-        let mut rs = self.state.registers[((instruction >> 21) & 0x1F) as usize]; // source register 1 value
+        let mut rs = self.state.registers[decoded.rs_reg as usize]; // source register 1 value
         let mut rt = 0; // source register 2 / temp value
-        let rt_reg = (instruction >> 16) & 0x1F;
+        let rt_reg = decoded.rt_reg;
 
         // R-type or I-type (stores rt)
         let mut rd_reg = rt_reg;
         if [0, 0x1c].contains(&opcode) {
             // R-type (stores rd)
             rt = self.state.registers[rt_reg as usize];
-            rd_reg = (instruction >> 11) & 0x1F;
+            rd_reg = decoded.rd_reg;
         } else if opcode < 20 {
             // rt is SignExtImm
             // Don't sign extend for andi, ori, xori
             if (0x0c..=0x0e).contains(&opcode) {
                 // ZeroExtImm
-                rt = instruction & 0xFFFF;
+                rt = decoded.imm;
             } else {
                 // SignExtImm
-                rt = sign_extend(instruction & 0xFFFF, 16);
+                rt = decoded.sign_ext_imm;
+            }
+        } else if opcode == 0x1F && self.state.mips32r2 {
+            // SPECIAL3: ext and ins store rt, while the BSHFL instructions store rd
+            rt = self.state.registers[rt_reg as usize];
+            if decoded.fun == 0x20 {
+                rd_reg = decoded.rd_reg;
             }
         } else if opcode >= 0x28 || [0x22, 0x26].contains(&opcode) {
             // Store rt value with store
@@ -134,8 +282,9 @@ where
         // We also do the load for stores
         if opcode >= 0x20 {
             // M[R[rs]+SignExtImm]
-            rs += sign_extend(instruction & 0xFFFF, 16);
+            rs += decoded.sign_ext_imm;
             let address = rs & 0xFFFFFFFC;
+            self.check_stack_guard(address as Address)?;
             self.track_mem_access(address as Address)?;
 
             mem = self.state.memory.get_memory(address as Address)?;
@@ -144,13 +293,15 @@ where
                 store_address = address;
                 // Store opcodes don't write back to a register
                 rd_reg = 0;
+            } else {
+                self.log_access(address, mem, mem, MemAccessKind::Read);
             }
         }
 
         // ALU
         let val = self.execute(instruction, rs, rt, mem)?;
 
-        let fun = instruction & 0x3F;
+        let fun = decoded.fun;
         if opcode == 0 && (8..0x1c).contains(&fun) {
             match fun {
                 (8..=9) => {
@@ -178,22 +329,52 @@ where
             }
         }
 
+        // sc: the emulator runs a single thread without interrupts, so nothing can clear the
+        // LLbit set by a preceding ll and the conditional store always succeeds.
         if opcode == 0x38 && rt_reg != 0 {
             self.state.registers[rt_reg as usize] = 1;
         }
 
         // Write memory
         if store_address != 0xFFFFFFFF {
+            self.check_access(store_address, Access::Write)?;
             self.track_mem_access(store_address as Address)?;
             self.state
                 .memory
                 .set_memory(store_address as Address, val)?;
+            self.block_cache.invalidate(store_address as Address);
+            self.log_access(store_address, mem, val, MemAccessKind::Write);
         }
 
         // Write back the value to the destination register
         self.handle_rd(rd_reg, val, true)
     }
 
+    /// Handles a floating-point instruction by passing it to the [crate::FpTrapHandler], if any.
+    ///
+    /// ### Takes
+    /// - `instruction`: The floating-point instruction at the current program counter.
+    ///
+    /// ### Returns
+    /// - A [Result] containing a [CannonError::FloatingPointInstruction] if no handler emulated
+    ///   the instruction.
+    fn handle_fp_trap(&mut self, instruction: u32) -> Result<()> {
+        let emulated = self.fp_trap.as_mut().is_some_and(|handler| {
+            handler.trap(&mut self.state, instruction) == HookAction::Continue
+        });
+        if !emulated {
+            return Err(CannonError::FloatingPointInstruction {
+                pc: self.state.pc,
+                instruction,
+            }
+            .into());
+        }
+
+        self.state.pc = self.state.next_pc;
+        self.state.next_pc += 4;
+        Ok(())
+    }
+
     /// Handles a syscall within the MIPS thread context emulation.
     ///
     /// ### Returns
@@ -209,8 +390,49 @@ where
             self.state.registers[6],
         );
 
+        crate::traces::trace!(
+            target: "mipsevm::stepper",
+            step = self.state.step,
+            pc = self.state.pc,
+            syscall = self.state.registers[2],
+            "Handling syscall"
+        );
+
         if let Ok(syscall) = Syscall::try_from(self.state.registers[2]) {
             match syscall {
+                Syscall::Mmap if self.state.address_space.is_some() => {
+                    let flags = self.state.registers[7];
+                    let space = self.state.address_space.as_mut().expect("Checked above");
+                    match space.mmap(a0, a1, flags) {
+                        Ok((addr, len)) => {
+                            // New anonymous mappings are zero-filled.
+                            self.release_pages(addr, len);
+                            v0 = addr;
+                        }
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Munmap if self.state.address_space.is_some() => {
+                    let space = self.state.address_space.as_mut().expect("Checked above");
+                    match space.munmap(a0, a1) {
+                        Ok((addr, len)) => self.release_pages(addr, len),
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Brk if self.state.address_space.is_some() => {
+                    let space = self.state.address_space.as_mut().expect("Checked above");
+                    let (brk, released) = space.set_brk(a0);
+                    if let Some((addr, len)) = released {
+                        self.release_pages(addr, len);
+                    }
+                    v0 = brk;
+                }
                 Syscall::Mmap => {
                     let mut sz = a1;
 
@@ -228,6 +450,133 @@ where
                         v0 = a0;
                     }
                 }
+                Syscall::Munmap => {
+                    // munmap is a no-op without address space tracking.
+                }
+                Syscall::Open | Syscall::Openat if self.state.virtual_files.is_some() => {
+                    // openat resolves absolute paths only, so the directory fd is ignored.
+                    let (path_ptr, flags) = if matches!(syscall, Syscall::Open) {
+                        (a0, a1)
+                    } else {
+                        (a1, a2)
+                    };
+                    let path = self.read_c_string(path_ptr)?;
+                    let table = self.state.virtual_files.as_mut().expect("Checked above");
+                    match path.map_or(Err(MIPS_ENOENT), |path| table.open(&path, flags)) {
+                        Ok(fd) => v0 = fd,
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Read if self.is_virtual_fd(a0) => {
+                    let table = self.state.virtual_files.as_mut().expect("Checked above");
+                    match table.read(a0, a2) {
+                        Ok(data) => {
+                            let data = data.to_vec();
+                            self.write_bytes(a1, &data)?;
+                            v0 = data.len() as u32;
+                        }
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Lseek if self.is_virtual_fd(a0) => {
+                    let table = self.state.virtual_files.as_mut().expect("Checked above");
+                    match table.lseek(a0, a1 as i32, a2) {
+                        Ok(offset) => v0 = offset,
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Close if self.is_virtual_fd(a0) => {
+                    let table = self.state.virtual_files.as_mut().expect("Checked above");
+                    if let Err(errno) = table.close(a0) {
+                        v0 = 0xFFFFFFFF;
+                        v1 = errno;
+                    }
+                }
+                Syscall::Fcntl if self.is_virtual_fd(a0) => {
+                    // F_GETFD: no flags set, F_GETFL: O_RDONLY.
+                    if a1 != 1 && a1 != 3 {
+                        v0 = 0xFFFFFFFF;
+                        v1 = MIPS_EINVAL;
+                    }
+                }
+                Syscall::Open | Syscall::Openat | Syscall::Lseek | Syscall::Close => {
+                    // Not supported without virtual files; treated like any other unknown syscall.
+                }
+                Syscall::Getrandom if self.state.entropy.is_some() => {
+                    // The source is taken out of the state while reading, as the state hash is
+                    // computed from the rest of the state.
+                    let len = a1.min(MAX_GETRANDOM_LEN);
+                    let step = self.state.step;
+                    let mut entropy = self.state.entropy.take().expect("Checked above");
+                    let data = entropy.read(len as usize, step, || self.state.state_hash());
+                    self.state.entropy = Some(entropy);
+
+                    self.write_bytes(a0, &data?)?;
+                    v0 = len;
+                }
+                Syscall::Getrandom => {
+                    // Not supported without an entropy source; treated like any other unknown
+                    // syscall.
+                }
+                Syscall::ClockGettime | Syscall::ClockGettime64 if self.state.clock.is_some() => {
+                    let clock = self.state.clock.expect("Checked above");
+                    match clock.now(a0, self.state.step) {
+                        Some(nanos) if matches!(syscall, Syscall::ClockGettime) => {
+                            self.write_bytes(a1, &Clock::timespec(nanos))?;
+                        }
+                        Some(nanos) => self.write_bytes(a1, &Clock::timespec64(nanos))?,
+                        None => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = MIPS_EINVAL;
+                        }
+                    }
+                }
+                Syscall::Gettimeofday if self.state.clock.is_some() => {
+                    let clock = self.state.clock.expect("Checked above");
+                    let nanos = clock
+                        .now(0, self.state.step)
+                        .expect("Real time is supported");
+                    if a0 != 0 {
+                        self.write_bytes(a0, &Clock::timeval(nanos))?;
+                    }
+                    // The timezone, if requested, is UTC.
+                    if a1 != 0 {
+                        self.write_bytes(a1, &[0u8; 8])?;
+                    }
+                }
+                Syscall::ClockGettime | Syscall::ClockGettime64 | Syscall::Gettimeofday => {
+                    // Not supported without a clock; treated like any other unknown syscall.
+                }
+                Syscall::Futex if self.state.futex => {
+                    let word = match a0 & 0x3 {
+                        0 => {
+                            let word = self.state.memory.get_memory(a0)?;
+                            self.log_access(a0, word, word, MemAccessKind::Read);
+                            Some(word)
+                        }
+                        _ => None,
+                    };
+                    match futex::futex(a1, word, a2, self.state.registers[7]) {
+                        Ok(value) => v0 = value,
+                        Err(errno) => {
+                            v0 = 0xFFFFFFFF;
+                            v1 = errno;
+                        }
+                    }
+                }
+                Syscall::Futex => {
+                    // Not supported without futex semantics; treated like any other unknown
+                    // syscall.
+                }
                 Syscall::Brk => {
                     v0 = 0x40000000;
                 }
@@ -247,6 +596,7 @@ where
                     Ok(Fd::PreimageRead) => {
                         let effective_address = (a1 & 0xFFFFFFFC) as Address;
 
+                        self.check_access(effective_address, Access::Write)?;
                         self.track_mem_access(effective_address)?;
                         let memory = self.state.memory.get_memory(effective_address)?;
 
@@ -264,11 +614,16 @@ where
 
                         let mut out_mem = memory.to_be_bytes();
                         out_mem[alignment..alignment + data_len].copy_from_slice(&data[..data_len]);
-                        self.state
-                            .memory
-                            .set_memory(effective_address, u32::from_be_bytes(out_mem))?;
+                        let out_mem = u32::from_be_bytes(out_mem);
+                        self.state.memory.set_memory(effective_address, out_mem)?;
+                        self.block_cache.invalidate(effective_address);
+                        self.log_access(effective_address, memory, out_mem, MemAccessKind::Write);
                         self.state.preimage_offset += data_len as u32;
                         v0 = data_len as u32;
+                        if let (Some(regions), true) = (&mut self.state.regions, data_len > 0) {
+                            let start = effective_address as u64 + alignment as u64;
+                            regions.annotate_oracle_scratch(start, start + data_len as u64);
+                        }
                     }
                     Ok(Fd::HintRead) => {
                         // Don't actually read anything into memory, just say we read it. The
@@ -290,6 +645,7 @@ where
                             &mut self.std_err
                         };
                         io::copy(&mut reader, writer)?;
+                        self.log_range_read(a1, a2)?;
                         v0 = a2;
                     }
                     Ok(Fd::HintWrite) => {
@@ -297,6 +653,7 @@ where
                             MemoryReader::new(&mut self.state.memory, a1 as Address, a2);
                         let mut hint_data = Vec::with_capacity(a2 as usize);
                         reader.read_to_end(&mut hint_data)?;
+                        self.log_range_read(a1, a2)?;
                         self.state.last_hint.extend(hint_data);
 
                         // Continue processing while there is enough data to check if there are any
@@ -322,6 +679,7 @@ where
                         self.track_mem_access(effective_address as Address)?;
 
                         let memory = self.state.memory.get_memory(effective_address as Address)?;
+                        self.log_access(effective_address, memory, memory, MemAccessKind::Read);
                         let mut key = self.state.preimage_key;
                         let alignment = a1 & 0x3;
                         let space = 4 - alignment;
@@ -400,6 +758,80 @@ where
         Ok(())
     }
 
+    /// Returns `true` if the given file descriptor refers to an open virtual file.
+    #[inline(always)]
+    fn is_virtual_fd(&self, fd: u32) -> bool {
+        self.state
+            .virtual_files
+            .as_ref()
+            .map_or(false, |table| table.is_open(fd))
+    }
+
+    /// Reads a NUL-terminated string of at most `PATH_MAX` bytes from memory.
+    ///
+    /// ### Takes
+    /// - `address`: The address of the string.
+    ///
+    /// ### Returns
+    /// - `Ok(Some(string))` if a valid UTF-8 string was read.
+    /// - `Ok(None)` if the string is unterminated or is not valid UTF-8.
+    fn read_c_string(&mut self, address: Address) -> Result<Option<String>> {
+        const PATH_MAX: u32 = 4096;
+
+        let mut bytes = Vec::default();
+        for i in 0..PATH_MAX {
+            let address = address.wrapping_add(i);
+            let word = self.state.memory.get_memory(address & 0xFFFFFFFC)?;
+            if i == 0 || address & 0x3 == 0 {
+                self.log_access(address & 0xFFFFFFFC, word, word, MemAccessKind::Read);
+            }
+            let byte = word.to_be_bytes()[(address & 0x3) as usize];
+            if byte == 0 {
+                return Ok(String::from_utf8(bytes).ok());
+            }
+            bytes.push(byte);
+        }
+        Ok(None)
+    }
+
+    /// Writes the given bytes into memory at an arbitrarily aligned address.
+    ///
+    /// ### Takes
+    /// - `address`: The address to write to.
+    /// - `data`: The bytes to write.
+    fn write_bytes(&mut self, address: Address, data: &[u8]) -> Result<()> {
+        for (i, byte) in data.iter().enumerate() {
+            let address = address.wrapping_add(i as u32);
+            let word_address = address & 0xFFFFFFFC;
+            self.check_access(word_address, Access::Write)?;
+            let old = self.state.memory.get_memory(word_address)?;
+            let mut word = old.to_be_bytes();
+            word[(address & 0x3) as usize] = *byte;
+            let new = u32::from_be_bytes(word);
+            self.state.memory.set_memory(word_address, new)?;
+            self.block_cache.invalidate(word_address);
+            self.log_access(word_address, old, new, MemAccessKind::Write);
+        }
+        Ok(())
+    }
+
+    /// Releases the pages covering the given page-aligned range back to the page pool, so that the
+    /// range reads as zero.
+    ///
+    /// ### Takes
+    /// - `addr`: The start of the range.
+    /// - `len`: The length of the range in bytes.
+    fn release_pages(&mut self, addr: Address, len: u32) {
+        let start = addr as u64 >> page::PAGE_ADDRESS_SIZE;
+        let end = (addr as u64 + len as u64) >> page::PAGE_ADDRESS_SIZE;
+        for page_index in start..end {
+            if self.state.memory.free_page(page_index) {
+                self.block_cache
+                    .invalidate((page_index << page::PAGE_ADDRESS_SIZE) as Address);
+            }
+        }
+    }
+
     /// Handles a branch within the MIPS thread context emulation.
     ///
     /// ### Takes
@@ -419,7 +851,7 @@ where
         rs: u32,
     ) -> Result<()> {
         if self.state.next_pc != self.state.pc + 4 {
-            anyhow::bail!("Unexpected branch in delay slot at {:x}", self.state.pc,);
+            return Err(CannonError::InvalidDelaySlot { pc: self.state.pc }.into());
         }
 
         let should_branch = match opcode {
@@ -549,7 +981,7 @@ where
     #[inline(always)]
     pub(crate) fn handle_jump(&mut self, link_reg: u32, dest: u32) -> Result<()> {
         if self.state.next_pc != self.state.pc + 4 {
-            anyhow::bail!("Unexpected jump in delay slot at {:x}", self.state.pc);
+            return Err(CannonError::InvalidDelaySlot { pc: self.state.pc }.into());
         }
 
         let prev_pc = self.state.pc;
@@ -624,6 +1056,10 @@ where
             match fun {
                 // sll
                 0 => Ok(rt << ((instruction >> 6) & 0x1F)),
+                // rotr (MIPS32r2)
+                2 if self.state.mips32r2 && (instruction >> 21) & 0x1 == 1 => {
+                    Ok(rt.rotate_right((instruction >> 6) & 0x1F))
+                }
                 // srl
                 2 => Ok(rt >> ((instruction >> 6) & 0x1F)),
                 // sra
@@ -633,6 +1069,10 @@ where
                 }
                 // sslv
                 4 => Ok(rt << (rs & 0x1F)),
+                // rotrv (MIPS32r2)
+                6 if self.state.mips32r2 && (instruction >> 6) & 0x1 == 1 => {
+                    Ok(rt.rotate_right(rs & 0x1F))
+                }
                 // srlv
                 6 => Ok(rt >> (rs & 0x1F)),
                 7 => Ok(sign_extend(rt >> rs, 32 - rs)),
@@ -661,7 +1101,11 @@ where
                 0x2a => Ok(((rs as i32) < (rt as i32)) as u32),
                 // sltiu
                 0x2b => Ok((rs < rt) as u32),
-                _ => anyhow::bail!("Invalid function code {:x}", fun),
+                _ => Err(CannonError::InvalidInstruction {
+                    pc: self.state.pc,
+                    instruction,
+                }
+                .into()),
             }
         } else {
             match opcode {
@@ -686,73 +1130,97 @@ where
                             }
                             Ok(i)
                         }
-                        _ => anyhow::bail!("Invalid function code {:x}", fun),
+                        _ => Err(CannonError::InvalidInstruction {
+                            pc: self.state.pc,
+                            instruction,
+                        }
+                        .into()),
                     }
                 }
+                // SPECIAL3 (MIPS32r2)
+                0x1F if self.state.mips32r2 => self.execute_special3(instruction, rs, rt),
                 // lui
                 0x0F => Ok(rt << 16),
                 // lb
-                0x20 => Ok(sign_extend((mem >> (24 - ((rs & 0x3) << 3))) & 0xFF, 8)),
+                0x20 => Ok(sign_extend(mem_access::load_byte(mem, rs) as u32, 8)),
                 // lh
-                0x21 => Ok(sign_extend((mem >> (16 - ((rs & 0x2) << 3))) & 0xFFFF, 16)),
+                0x21 => Ok(sign_extend(mem_access::load_half(mem, rs) as u32, 16)),
                 // lwl
-                0x22 => {
-                    let sl = (rs & 0x3) << 3;
-                    let val = mem << sl;
-                    let mask = 0xFFFFFFFF << sl;
-                    Ok((rt & !mask) | val)
-                }
+                0x22 => Ok(mem_access::lwl(rt, mem, rs)),
                 // lw
                 0x23 => Ok(mem),
                 // lbu
-                0x24 => Ok((mem >> (24 - ((rs & 0x3) << 3))) & 0xFF),
+                0x24 => Ok(mem_access::load_byte(mem, rs) as u32),
                 // lhu
-                0x25 => Ok((mem >> (16 - ((rs & 0x2) << 3))) & 0xFFFF),
+                0x25 => Ok(mem_access::load_half(mem, rs) as u32),
                 // lwr
-                0x26 => {
-                    let sr = 24 - ((rs & 0x3) << 3);
-                    let val = mem >> sr;
-                    let mask = 0xFFFFFFFFu32 >> sr;
-                    Ok((rt & !mask) | val)
-                }
+                0x26 => Ok(mem_access::lwr(rt, mem, rs)),
                 // sb
-                0x28 => {
-                    let sl = 24 - ((rs & 0x3) << 3);
-                    let val = (rt & 0xFF) << sl;
-                    let mask = 0xFFFFFFFF ^ (0xFF << sl);
-                    Ok((mem & mask) | val)
-                }
+                0x28 => Ok(mem_access::store_byte(mem, rs, rt as u8)),
                 // sh
-                0x29 => {
-                    let sl = 16 - ((rs & 0x2) << 3);
-                    let val = (rt & 0xFFFF) << sl;
-                    let mask = 0xFFFFFFFF ^ (0xFFFF << sl);
-                    Ok((mem & mask) | val)
-                }
+                0x29 => Ok(mem_access::store_half(mem, rs, rt as u16)),
                 // swl
-                0x2a => {
-                    let sr = (rs & 0x3) << 3;
-                    let val = rt >> sr;
-                    let mask = 0xFFFFFFFFu32 >> sr;
-                    Ok((mem & !mask) | val)
-                }
+                0x2a => Ok(mem_access::swl(mem, rt, rs)),
                 // sw
                 0x2b => Ok(rt),
                 // swr
-                0x2e => {
-                    let sl = 24 - ((rs & 0x3) << 3);
-                    let val = rt << sl;
-                    let mask = 0xFFFFFFFF << sl;
-                    Ok((mem & !mask) | val)
-                }
+                0x2e => Ok(mem_access::swr(mem, rt, rs)),
                 // ll
                 0x30 => Ok(mem),
                 // sc
                 0x38 => Ok(rt),
-                _ => anyhow::bail!("Invalid opcode {:x}", opcode),
+                _ => Err(CannonError::InvalidInstruction {
+                    pc: self.state.pc,
+                    instruction,
+                }
+                .into()),
             }
         }
     }
+
+    /// Executes a MIPS32r2 `SPECIAL3` instruction: `ext`, `ins`, or one of the `BSHFL`
+    /// instructions `wsbh`, `seb` and `seh`.
+    ///
+    /// ### Takes
+    /// - `instruction`: The instruction to execute.
+    /// - `rs`: The value of the rs register.
+    /// - `rt`: The value of the rt register.
+    ///
+    /// ### Returns
+    /// - `Ok(n)` - The value to write back to rt (`ext` and `ins`) or rd (`BSHFL`).
+    /// - `Err(_)`: The instruction is not a supported `SPECIAL3` instruction.
+    #[inline(always)]
+    fn execute_special3(&self, instruction: u32, rs: u32, rt: u32) -> Result<u32> {
+        let lsb = (instruction >> 6) & 0x1F;
+        let msb = (instruction >> 11) & 0x1F;
+        let value = match instruction & 0x3F {
+            // ext: the rd field holds the size of the field minus one
+            0x00 if lsb + msb < 32 => Some((rs >> lsb) & (u32::MAX >> (31 - msb))),
+            // ins
+            0x04 if msb >= lsb => {
+                let mask = (u32::MAX >> (31 - (msb - lsb))) << lsb;
+                Some((rt & !mask) | ((rs << lsb) & mask))
+            }
+            // BSHFL: the sa field selects the operation
+            0x20 => match lsb {
+                // wsbh
+                0x02 => Some(((rt & 0x00FF00FF) << 8) | ((rt >> 8) & 0x00FF00FF)),
+                // seb
+                0x10 => Some(sign_extend(rt & 0xFF, 8)),
+                // seh
+                0x18 => Some(sign_extend(rt & 0xFFFF, 16)),
+                _ => None,
+            },
+            _ => None,
+        };
+        value.ok_or_else(|| {
+            CannonError::InvalidInstruction {
+                pc: self.state.pc,
+                instruction,
+            }
+            .into()
+        })
+    }
 }
 
 /// Perform a sign extension of a value embedded in the lower bits of `data` up to
@@ -763,6 +1231,50 @@ where
 /// - `index`: The index of the bit to sign extend to.
 ///
 /// ### Returns
+
+/// A [DecodedInstruction] holds the fields of an instruction word, extracted once so that
+/// instructions that are executed repeatedly, such as those in the [super::block_cache::BlockCache],
+/// are not decoded on every execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DecodedInstruction {
+    /// The raw instruction word.
+    pub(crate) instruction: u32,
+    /// The opcode, in bits 26-31.
+    pub(crate) opcode: u32,
+    /// The function field of R-type instructions, in bits 0-5.
+    pub(crate) fun: u32,
+    /// The index of the `rs` register, in bits 21-25.
+    pub(crate) rs_reg: u32,
+    /// The index of the `rt` register, in bits 16-20.
+    pub(crate) rt_reg: u32,
+    /// The index of the `rd` register of R-type instructions, in bits 11-15.
+    pub(crate) rd_reg: u32,
+    /// The zero-extended immediate of I-type instructions, in bits 0-15.
+    pub(crate) imm: u32,
+    /// The sign-extended immediate of I-type instructions.
+    pub(crate) sign_ext_imm: u32,
+    /// Whether the instruction is a floating-point instruction, which traps.
+    pub(crate) is_floating_point: bool,
+}
+
+impl DecodedInstruction {
+    /// Decodes the given instruction word.
+    #[inline(always)]
+    pub(crate) fn new(instruction: u32) -> Self {
+        Self {
+            instruction,
+            opcode: instruction >> 26,
+            fun: instruction & 0x3F,
+            rs_reg: (instruction >> 21) & 0x1F,
+            rt_reg: (instruction >> 16) & 0x1F,
+            rd_reg: (instruction >> 11) & 0x1F,
+            imm: instruction & 0xFFFF,
+            sign_ext_imm: sign_extend(instruction & 0xFFFF, 16),
+            is_floating_point: disasm::is_floating_point(instruction),
+        }
+    }
+}
+
 /// - The sign extended value.
 #[inline(always)]
 pub(crate) fn sign_extend(data: u32, index: u32) -> u32 {
@@ -1,6 +1,11 @@
 //! The MIPS module contains the implementation of the [InstrumentedState] and the MIPS emulator.
 
-mod instrumented;
+mod block_cache;
+
+mod hooks;
+pub use self::hooks::{EntryCallback, FpTrapHandler, HookAction, PostStepHook, PreStepHook};
+
+pub(crate) mod instrumented;
 pub use self::instrumented::InstrumentedState;
 
 mod mips_vm;
@@ -1,11 +1,11 @@
-//! This module contains the data structure for a [Page] within the MIPS emulator's [Memory].
+//! This module contains the data structure for a [crate::Page] within the MIPS emulator's [Memory].
 
-use crate::{utils::keccak_concat_hashes, Address, Gindex, Page};
+use crate::{merkle::build_zero_hashes, types::PageOf, Address, Gindex, MerkleHasher};
 use anyhow::Result;
 use once_cell::sync::Lazy;
 
-#[cfg(not(feature = "simd-keccak"))]
-use crate::utils::keccak256;
+#[cfg(test)]
+use crate::utils::keccak_concat_hashes;
 
 pub(crate) const PAGE_ADDRESS_SIZE: usize = 12;
 pub(crate) const PAGE_KEY_SIZE: usize = 32 - PAGE_ADDRESS_SIZE;
@@ -14,63 +14,83 @@ pub(crate) const PAGE_SIZE_WORDS: usize = PAGE_SIZE >> 5;
 pub(crate) const PAGE_ADDRESS_MASK: usize = PAGE_SIZE - 1;
 pub(crate) const MAX_PAGE_COUNT: usize = 1 << PAGE_KEY_SIZE;
 pub(crate) const PAGE_KEY_MASK: usize = MAX_PAGE_COUNT - 1;
+/// The depth of the memory's merkle tree, down to its 32 byte leaves. This is independent of the
+/// page size, which only determines where the tree is split between the memory and its pages.
+pub(crate) const MEMORY_TREE_DEPTH: usize = 32 - 5;
 
-/// Precomputed hashes of each full-zero range sub-tree level.
-pub(crate) static ZERO_HASHES: Lazy<[[u8; 32]; 256]> = Lazy::new(|| {
-    let mut out = [[0u8; 32]; 256];
-    for i in 1..256 {
-        out[i] = *keccak_concat_hashes(out[i - 1], out[i - 1])
-    }
-    out
-});
-
-/// Precomputed cache of a merkleized page with all zero data.
-pub(crate) static DEFAULT_CACHE: Lazy<[[u8; 32]; PAGE_SIZE_WORDS]> = Lazy::new(|| {
-    let mut page = CachedPage {
-        data: [0; PAGE_SIZE],
-        cache: [[0; 32]; PAGE_SIZE_WORDS],
-        valid: [false; PAGE_SIZE / 32],
-    };
-    page.merkle_root().unwrap();
-    page.cache
-});
-
-/// A [CachedPage] is a [Page] with an in-memory cache of intermediate nodes.
+/// Precomputed hashes of each full-zero range sub-tree level, for the default
+/// [MerkleHasher::Keccak256].
+pub(crate) static ZERO_HASHES: Lazy<[[u8; 32]; 256]> =
+    Lazy::new(|| build_zero_hashes(MerkleHasher::Keccak256));
+
+/// A [CachedPageOf] is a [crate::types::PageOf] with an in-memory cache of intermediate nodes.
+/// It is generic over the number of address bits within the page, `P`, for experimental memory
+/// layouts; the emulator uses [CachedPage], with pages of [PAGE_SIZE] bytes.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub struct CachedPage {
-    pub data: Page,
+pub struct CachedPageOf<const P: usize>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    pub data: PageOf<P>,
     /// Storage for intermediate nodes
-    pub cache: [[u8; 32]; PAGE_SIZE_WORDS],
-    /// Bitmap for 128 nodes. 1 if valid, 0 if invalid.
-    pub valid: [bool; PAGE_SIZE / 32],
+    pub cache: [[u8; 32]; (1 << P) >> 5],
+    /// Bitmap for the intermediate nodes. 1 if valid, 0 if invalid.
+    pub valid: [bool; (1 << P) >> 5],
 }
 
-impl Default for CachedPage {
+/// A [CachedPage] is a [crate::Page] with an in-memory cache of intermediate nodes.
+pub type CachedPage = CachedPageOf<PAGE_ADDRESS_SIZE>;
+
+impl<const P: usize> Default for CachedPageOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
     fn default() -> Self {
         Self {
-            data: [0; PAGE_SIZE],
-            cache: *DEFAULT_CACHE,
-            valid: [true; PAGE_SIZE / 32],
+            data: [0; 1 << P],
+            cache: Self::zero_cache(),
+            valid: [true; (1 << P) >> 5],
         }
     }
 }
 
-impl CachedPage {
-    /// Invalidate a given address within the [Page].
+impl<const P: usize> CachedPageOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The size of the page, in bytes.
+    pub const SIZE: usize = 1 << P;
+    /// The number of 32 byte words in the page.
+    pub const SIZE_WORDS: usize = Self::SIZE >> 5;
+    /// The mask of the address bits within the page.
+    pub const ADDRESS_MASK: usize = Self::SIZE - 1;
+
+    /// Returns the cache of a merkleized page with all zero data, for the default
+    /// [MerkleHasher::Keccak256]. The node at generalized index `g` is the root of an all zero
+    /// subtree of height `P - 5 - log2(g)`.
+    fn zero_cache() -> [[u8; 32]; (1 << P) >> 5] {
+        let zero_hashes = &*ZERO_HASHES;
+        std::array::from_fn(|g_index| zero_hashes[P - 5 - g_index.max(1).ilog2() as usize])
+    }
+
+    /// Invalidate a given address within the page.
     ///
     /// ### Takes
-    /// - `page_addr`: The [Address] to invalidate within the [Page].
+    /// - `page_addr`: The [Address] to invalidate within the page.
     ///
     /// ### Returns
     /// - A [Result] indicating if the operation was successful.
     #[inline(always)]
     pub fn invalidate(&mut self, page_addr: Address) -> Result<()> {
-        if page_addr >= PAGE_SIZE as Address {
+        if page_addr >= Self::SIZE as Address {
             anyhow::bail!("Invalid page address: {}", page_addr);
         }
 
         // The first cache layer caches nodes that have two 32 byte leaf nodes.
-        let key = ((1 << PAGE_ADDRESS_SIZE) | page_addr) >> 6;
+        let key = ((1 << P) | page_addr) >> 6;
 
         // Invalidate the key and all subsequent keys using slicing
         self.valid[..=key as usize].fill(false);
@@ -78,24 +98,25 @@ impl CachedPage {
         Ok(())
     }
 
-    /// Invalidate the entire [Page].
+    /// Invalidate the entire page.
     ///
     /// This is equivalent to calling `invalidate` on every address in the page.
     #[inline(always)]
     pub fn invalidate_full(&mut self) {
-        self.valid = [false; PAGE_SIZE / 32];
+        self.valid = [false; (1 << P) >> 5];
     }
 
-    /// Compute the merkle root of the [Page].
+    /// Compute the merkle root of the page.
     ///
     /// ## Returns
-    /// - The 32 byte merkle root hash of the [Page].
+    /// - The 32 byte merkle root hash of the page.
     #[inline(always)]
     pub fn merkle_root(&mut self) -> Result<[u8; 32]> {
         self.merkleize_subtree(1)
     }
 
-    /// Compute the merkle root for the subtree rooted at the given generalized index.
+    /// Compute the merkle root for the subtree rooted at the given generalized index, with the
+    /// default [MerkleHasher::Keccak256].
     ///
     /// ### Takes
     /// - `g_index`: The generalized index of the subtree to merkleize.
@@ -105,42 +126,51 @@ impl CachedPage {
     ///  generalized index is too deep.
     #[inline(always)]
     pub fn merkleize_subtree(&mut self, g_index: Gindex) -> Result<[u8; 32]> {
+        self.merkleize_subtree_with(g_index, MerkleHasher::Keccak256)
+    }
+
+    /// Compute the merkle root for the subtree rooted at the given generalized index with the
+    /// given [MerkleHasher]. The cache of the [CachedPage] must have been computed with the same
+    /// hasher, or be invalidated.
+    ///
+    /// ### Takes
+    /// - `g_index`: The generalized index of the subtree to merkleize.
+    /// - `hasher`: The [MerkleHasher] to hash nodes with.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the 32 byte merkle root hash of the subtree or an error if the
+    ///  generalized index is too deep.
+    #[inline(always)]
+    pub fn merkleize_subtree_with(
+        &mut self,
+        g_index: Gindex,
+        hasher: MerkleHasher,
+    ) -> Result<[u8; 32]> {
         // Cast to usize to avoid `as usize` everywhere.
         let g_index = g_index as usize;
 
-        if (PAGE_SIZE_WORDS..PAGE_SIZE_WORDS * 2).contains(&g_index) {
-            let node_index = (g_index & (PAGE_ADDRESS_MASK >> 5)) << 5;
+        if (Self::SIZE_WORDS..Self::SIZE_WORDS * 2).contains(&g_index) {
+            let node_index = (g_index & (Self::ADDRESS_MASK >> 5)) << 5;
             return Ok(self.data[node_index..node_index + 32].try_into()?);
-        } else if g_index >= PAGE_SIZE_WORDS * 2 {
+        } else if g_index >= Self::SIZE_WORDS * 2 {
             anyhow::bail!("Generalized index is too deep: {}", g_index);
         } else if self.valid[g_index] {
             return Ok(self.cache[g_index]);
         }
 
-        let hash = if g_index >= PAGE_SIZE_WORDS >> 1 {
+        let hash = if g_index >= Self::SIZE_WORDS >> 1 {
             // This is a leaf node.
-            let data_idx = (g_index - (PAGE_SIZE_WORDS >> 1)) << 6;
-            #[cfg(feature = "simd-keccak")]
-            {
-                let mut out = [0u8; 32];
-                keccak256_aarch64_simd::simd_keccak256_64b_single(
-                    &self.data[data_idx..data_idx + 64],
-                    &mut out,
-                );
-                out
-            }
-
-            #[cfg(not(feature = "simd-keccak"))]
-            *keccak256(&self.data[data_idx..data_idx + 64])
+            let data_idx = (g_index - (Self::SIZE_WORDS >> 1)) << 6;
+            hasher.hash_leaves(&self.data[data_idx..data_idx + 64])
         } else {
             // This is an internal node.
             let left_child = g_index << 1;
             let right_child = left_child + 1;
 
             // Ensure children are hashed.
-            *keccak_concat_hashes(
-                self.merkleize_subtree(left_child as Gindex)?,
-                self.merkleize_subtree(right_child as Gindex)?,
+            hasher.hash_pair(
+                self.merkleize_subtree_with(left_child as Gindex, hasher)?,
+                self.merkleize_subtree_with(right_child as Gindex, hasher)?,
             )
         };
         self.valid[g_index] = true;
@@ -212,4 +242,24 @@ mod test {
             "Full invalidation should always change the root."
         );
     }
+
+    #[test]
+    fn zero_cache_sizes() {
+        fn check<const P: usize>()
+        where
+            [(); 1 << P]:,
+            [(); (1 << P) >> 5]:,
+        {
+            let mut page = CachedPageOf::<P>::default();
+            let root = page.merkle_root().unwrap();
+            page.invalidate_full();
+            assert_eq!(page.merkle_root().unwrap(), root);
+            assert_eq!(page.cache, CachedPageOf::<P>::default().cache);
+            assert_eq!(root, ZERO_HASHES[P - 5]);
+        }
+
+        check::<PAGE_ADDRESS_SIZE>();
+        check::<6>();
+        check::<16>();
+    }
 }
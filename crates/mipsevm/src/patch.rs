@@ -1,9 +1,9 @@
 //! This module contains utilities for loading ELF files into [State] objects.
 
-use crate::{page, Address, State};
+use crate::{page, Address, AuxVector, MemoryLayout, PatchSet, State};
 use anyhow::Result;
 use elf::{abi::PT_LOAD, endian::AnyEndian, ElfBytes};
-use std::io::{self, Cursor, Read};
+use std::io::{self, Read};
 
 /// Symbols that indicate there is a patch to be made on an ELF file that was compiled from Go.
 pub(crate) const GO_SYMBOLS: [&str; 14] = [
@@ -23,21 +23,56 @@ pub(crate) const GO_SYMBOLS: [&str; 14] = [
     "runtime.check", // We need to patch this out, we don't pass float64nan because we don't support floats
 ];
 
-/// Load a raw ELF file into a [State] object.
+/// Load a raw ELF file into a [State] object, asserting that it fits the canonical
+/// [MemoryLayout].
 ///
 /// ### Takes
 /// - `raw`: The raw contents of the ELF file to load.
 ///
 /// ### Returns
 /// - `Ok(state)` if the ELF file was loaded successfully
-/// - `Err(_)` if the ELF file could not be loaded
+/// - `Err(_)` if the ELF file could not be loaded, e.g. if one of its segments collides with the
+///   heap, the stack or the kernel region of the canonical layout
 pub fn load_elf(raw: &[u8]) -> Result<State> {
+    load_elf_with_layout(raw, &MemoryLayout::CANONICAL)
+}
+
+/// Load a raw ELF file into a [State] object, placing the heap per the given [MemoryLayout].
+///
+/// Only the heap can be moved: the program break is reported by the emulator, and the stack is
+/// placed by [patch_stack], at their canonical addresses. Layouts with any other program break or
+/// stack pointer are rejected rather than silently ignored. States loaded with any layout other
+/// than [MemoryLayout::CANONICAL] violate the assumptions of the `MIPS` contract, like the
+/// [extensions](State#extensions) of a [State].
+///
+/// ### Takes
+/// - `raw`: The raw contents of the ELF file to load.
+/// - `layout`: The [MemoryLayout] to load the ELF file into.
+///
+/// ### Returns
+/// - `Ok(state)` if the ELF file was loaded successfully
+/// - `Err(_)` if the layout is inconsistent or moves the program break or the stack, or if the ELF
+///   file could not be loaded into it
+pub fn load_elf_with_layout(raw: &[u8], layout: &MemoryLayout) -> Result<State> {
+    layout.validate()?;
+    anyhow::ensure!(
+        layout.brk == MemoryLayout::CANONICAL.brk
+            && layout.stack_pointer == MemoryLayout::CANONICAL.stack_pointer,
+        "The memory layout {:?} moves the program break or the stack pointer, which are fixed at {:08x} and {:08x}",
+        layout,
+        MemoryLayout::CANONICAL.brk,
+        MemoryLayout::CANONICAL.stack_pointer
+    );
+    if !layout.is_canonical() {
+        crate::traces::warn!(target: "mipsevm::patch", "Loading the ELF file into a non-canonical memory layout {:?}, which the MIPS contract does not support", layout);
+    }
+
     let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
 
     let mut state = State {
         pc: elf.ehdr.e_entry as u32,
         next_pc: elf.ehdr.e_entry as u32 + 4,
-        heap: 0x20000000,
+        heap: layout.heap_start,
         ..Default::default()
     };
 
@@ -51,16 +86,10 @@ pub fn load_elf(raw: &[u8]) -> Result<State> {
         }
 
         let section_data = &elf.segment_data(&header)?[..header.p_filesz as usize];
-        let mut reader: Box<dyn Read> = Box::new(section_data);
 
         if header.p_filesz != header.p_memsz {
             if header.p_type == PT_LOAD {
-                if header.p_filesz < header.p_memsz {
-                    reader = Box::new(MultiReader(
-                        reader,
-                        Cursor::new(vec![0; (header.p_memsz - header.p_filesz) as usize]),
-                    ));
-                } else {
+                if header.p_filesz > header.p_memsz {
                     anyhow::bail!(
                         "Invalid PT_LOAD program segment {}, file size ({}) > mem size ({})",
                         i,
@@ -87,16 +116,18 @@ pub fn load_elf(raw: &[u8]) -> Result<State> {
                 header.p_memsz
             );
         }
+        layout.check_segment(i, header.p_vaddr, header.p_vaddr + header.p_memsz)?;
 
-        state
-            .memory
-            .set_memory_range(header.p_vaddr as u32, reader)?;
+        // The remainder of the segment beyond the file data is zero-filled.
+        let mut segment = section_data.to_vec();
+        segment.resize(header.p_memsz as usize, 0);
+        state.memory.set_range(header.p_vaddr as u32, &segment)?;
     }
 
     Ok(state)
 }
 
-/// Patch a Go ELF file to work with mipsevm.
+/// Patch a Go ELF file to work with mipsevm, applying the builtin [PatchSet::go].
 ///
 /// ### Takes
 /// - `elf`: The ELF file to patch
@@ -106,28 +137,7 @@ pub fn load_elf(raw: &[u8]) -> Result<State> {
 /// - `Ok(())` if the patch was successful
 /// - `Err(_)` if the patch failed
 pub fn patch_go(raw: &[u8], state: &mut State) -> Result<()> {
-    let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
-    let (parsing_table, string_table) = elf
-        .symbol_table()?
-        .ok_or(anyhow::anyhow!("Failed to load ELF symbol table"))?;
-
-    for symbol in parsing_table {
-        let symbol_idx = symbol.st_name;
-        let name = string_table.get(symbol_idx as usize)?;
-
-        if GO_SYMBOLS.contains(&name) {
-            // MIPS32 patch: ret (pseudo instruction)
-            // 03e00008 = jr $ra = ret (pseudo instruction)
-            // 00000000 = nop (executes with delay-slot, but does nothing)
-            state.memory.set_memory_range(
-                symbol.st_value as u32,
-                [0x03, 0xe0, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00].as_slice(),
-            )?;
-        } else if name == "runtime.MemProfileRate" {
-            // disable mem profiling, to avoid a lot of unnecessary floating point ops
-            state.memory.set_memory(symbol.st_value as u32, 0)?;
-        }
-    }
+    PatchSet::go().apply(raw, state)?;
     Ok(())
 }
 
@@ -141,7 +151,7 @@ pub fn patch_go(raw: &[u8], state: &mut State) -> Result<()> {
 /// - `Err(_)` if the patch failed
 pub fn patch_stack(state: &mut State) -> Result<()> {
     // Setup stack pointer
-    let ptr = 0x7F_FF_D0_00_u32;
+    let ptr = MemoryLayout::CANONICAL.stack_pointer;
 
     // Allocate 1 page for the initial stack data, and 16KB = 4 pages for the stack to grow.
     state.memory.set_memory_range(
@@ -152,7 +162,7 @@ pub fn patch_stack(state: &mut State) -> Result<()> {
 
     #[inline(always)]
     fn store_mem(st: &mut State, address: Address, value: u32) -> Result<()> {
-        st.memory.set_memory(address, value)
+        Ok(st.memory.set_memory(address, value)?)
     }
 
     // init argc, argv, aux on stack
@@ -173,6 +183,72 @@ pub fn patch_stack(state: &mut State) -> Result<()> {
     Ok(())
 }
 
+/// Patches the stack like [patch_stack], but passes the given arguments and environment variables
+/// to the guest. The initial stack is laid out per the MIPS Linux ABI: `$sp` points to `argc`,
+/// followed by the null-terminated `argv` and `envp` pointer arrays and the auxiliary vector. The
+/// strings and the random bytes of the [AuxVector] are placed above them.
+///
+/// ### Takes
+/// - `state`: The state to patch the stack for
+/// - `args`: The arguments of the guest, including the program name as `argv[0]` by convention.
+/// - `env`: The environment variables of the guest, as `KEY=VALUE` pairs.
+/// - `auxv`: The auxiliary vector of the guest, e.g. [AuxVector::default] or
+///   [AuxVector::from_elf].
+///
+/// ### Returns
+/// - `Ok(())` if the patch was successful
+/// - `Err(_)` if the patch failed, e.g. if the arguments do not fit below the top of user memory
+pub fn patch_stack_with_args(
+    state: &mut State,
+    args: &[String],
+    env: &[String],
+    auxv: &AuxVector,
+) -> Result<()> {
+    let ptr = MemoryLayout::CANONICAL.stack_pointer;
+
+    // argc, the argv and envp arrays with their terminators, and the auxv pairs.
+    let table_size = 4 * (1 + args.len() + 1 + env.len() + 1 + auxv.stack_words());
+    let strings_size = args.iter().chain(env).map(|s| s.len() + 1).sum::<usize>();
+    let size = table_size + 16 + strings_size;
+    let init_pages = size.div_ceil(page::PAGE_SIZE).max(1);
+    if ptr as u64 + (init_pages * page::PAGE_SIZE) as u64 > MemoryLayout::CANONICAL.user_end {
+        anyhow::bail!(
+            "The arguments and environment of the guest take {} bytes, which exceeds the initial stack",
+            size
+        );
+    }
+
+    // Allocate the pages for the initial stack data, and 16KB = 4 pages for the stack to grow.
+    state.memory.set_range(
+        ptr - 4 * page::PAGE_SIZE as u32,
+        &vec![0u8; page::PAGE_SIZE * (4 + init_pages)],
+    )?;
+    state.registers[29] = ptr;
+
+    let random = ptr + table_size as u32;
+    state.memory.set_range(random, &auxv.random)?;
+
+    let mut table = Vec::with_capacity(table_size / 4);
+    table.push(args.len() as u32);
+    let mut string_ptr = random + 16;
+    for strings in [args, env] {
+        for s in strings {
+            let mut bytes = s.as_bytes().to_vec();
+            bytes.push(0);
+            state.memory.set_range(string_ptr, &bytes)?;
+            table.push(string_ptr);
+            string_ptr += bytes.len() as u32;
+        }
+        table.push(0);
+    }
+    table.extend(auxv.to_words(random));
+
+    for (i, value) in table.into_iter().enumerate() {
+        state.memory.set_memory(ptr + 4 * i as u32, value)?;
+    }
+    Ok(())
+}
+
 /// A multi reader is a reader that reads from the first reader until it returns 0, then reads from the second reader.
 pub struct MultiReader<R1: Read, R2: Read>(R1, R2);
 
@@ -185,3 +261,105 @@ impl<R1: Read, R2: Read> Read for MultiReader<R1, R2> {
         Ok(read_first)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auxv::{AT_NULL, AT_PAGESZ, AT_RANDOM};
+
+    /// Reads the null-terminated string at the given address.
+    fn read_string(state: &mut State, mut address: Address) -> String {
+        let mut bytes = Vec::new();
+        loop {
+            let word = state.memory.get_memory(address & !3).unwrap().to_be_bytes();
+            let byte = word[(address & 3) as usize];
+            if byte == 0 {
+                return String::from_utf8(bytes).unwrap();
+            }
+            bytes.push(byte);
+            address += 1;
+        }
+    }
+
+    #[test]
+    fn stack_with_args() {
+        let mut state = State::default();
+        let args = ["prog".to_string(), "--verbose".to_string()];
+        let env = ["HOME=/".to_string()];
+        patch_stack_with_args(&mut state, &args, &env, &AuxVector::default()).unwrap();
+
+        let sp = state.registers[29];
+        let mut word = |i: u32| state.memory.get_memory(sp + 4 * i).unwrap();
+        assert_eq!(word(0), 2);
+        let (argv0, argv1, envp0) = (word(1), word(2), word(4));
+        assert_eq!((word(3), word(5)), (0, 0));
+        assert_eq!(
+            [word(6), word(7), word(8)],
+            [AT_PAGESZ, page::PAGE_SIZE as u32, AT_RANDOM]
+        );
+        assert_eq!([word(10), word(11)], [AT_NULL, 0]);
+
+        assert_eq!(read_string(&mut state, argv0), "prog");
+        assert_eq!(read_string(&mut state, argv1), "--verbose");
+        assert_eq!(read_string(&mut state, envp0), "HOME=/");
+
+        let too_long = ["x".repeat(16 * 1024)];
+        assert!(patch_stack_with_args(
+            &mut State::default(),
+            &too_long,
+            &[],
+            &AuxVector::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn load_elf_asserts_layout() {
+        let elf = include_bytes!("../../../example/bin/hello.elf");
+        let state = load_elf(elf).unwrap();
+        assert_eq!(state.heap, MemoryLayout::CANONICAL.heap_start);
+
+        // Move the last `PT_LOAD` segment into the heap.
+        let parsed = ElfBytes::<AnyEndian>::minimal_parse(elf).unwrap();
+        let (index, _) = parsed
+            .segments()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| header.p_type == PT_LOAD)
+            .last()
+            .unwrap();
+        let vaddr = parsed.ehdr.e_phoff as usize + index * parsed.ehdr.e_phentsize as usize + 8;
+        let mut colliding = elf.to_vec();
+        colliding[vaddr..vaddr + 4].copy_from_slice(&0x2000_0000u32.to_be_bytes());
+
+        let err = load_elf(&colliding).unwrap_err().to_string();
+        assert!(err.contains("heap"), "{}", err);
+    }
+
+    #[test]
+    fn load_elf_rejects_non_canonical_brk_and_stack() {
+        let elf = include_bytes!("../../../example/bin/hello.elf");
+        let layout = MemoryLayout {
+            heap_start: 0x3000_0000,
+            ..MemoryLayout::CANONICAL
+        };
+        assert_eq!(
+            load_elf_with_layout(elf, &layout).unwrap().heap,
+            0x3000_0000
+        );
+
+        for layout in [
+            MemoryLayout {
+                brk: MemoryLayout::CANONICAL.brk + page::PAGE_SIZE as Address,
+                ..MemoryLayout::CANONICAL
+            },
+            MemoryLayout {
+                stack_pointer: MemoryLayout::CANONICAL.stack_pointer - page::PAGE_SIZE as Address,
+                ..MemoryLayout::CANONICAL
+            },
+        ] {
+            assert!(load_elf_with_layout(elf, &layout).is_err());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! This module contains the [PatchSet], a data-driven set of rules that patch the symbols of a
+//! guest's runtime that mipsevm cannot execute, such as the Go garbage collector.
+//!
+//! A [PatchSet] is either one of the builtin sets (see [PatchSet::builtin]) or loaded from a TOML
+//! file:
+//!
+//! ```toml
+//! name = "go1.22"
+//!
+//! [[patch]]
+//! symbol = "runtime.gcenable"
+//! action = "ret"
+//!
+//! [[patch]]
+//! symbol = "runtime.MemProfileRate"
+//! action = "word"
+//! value = 0
+//!
+//! [[patch]]
+//! symbol = "runtime.check"
+//! action = "bytes"
+//! value = "0x03e0000800000000"
+//! ```
+
+use crate::{patch::GO_SYMBOLS, State};
+use alloy_primitives::Bytes;
+use anyhow::Result;
+use elf::{endian::AnyEndian, ElfBytes};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// `jr $ra` followed by a `nop` in its delay slot, which returns from a function immediately.
+const RET: [u8; 8] = [0x03, 0xe0, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00];
+
+/// The [Replacement] that a [SymbolPatch] writes at the address of its symbol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "value", rename_all = "snake_case")]
+pub enum Replacement {
+    /// Return from the function immediately.
+    Ret,
+    /// Overwrite the word at the symbol, e.g. to set a variable.
+    Word(u32),
+    /// Overwrite the bytes at the symbol.
+    Bytes(Bytes),
+}
+
+impl Replacement {
+    /// Returns the bytes written at the address of the symbol.
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ret => RET.to_vec(),
+            Self::Word(word) => word.to_be_bytes().to_vec(),
+            Self::Bytes(bytes) => bytes.to_vec(),
+        }
+    }
+}
+
+/// A [SymbolPatch] replaces the code or data at the address of a symbol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolPatch {
+    /// The name of the symbol, as in the ELF symbol table.
+    pub symbol: String,
+    /// The replacement written at the address of the symbol.
+    #[serde(flatten)]
+    pub replacement: Replacement,
+}
+
+/// The [PatchSet] holds the [SymbolPatch]es that are applied to an ELF file for a given language
+/// or runtime. Patches of symbols that are not in the ELF file are skipped, so that a set can
+/// cover several versions of a runtime.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchSet {
+    /// The name of the set.
+    #[serde(default)]
+    pub name: String,
+    /// The patches of the set.
+    #[serde(default, rename = "patch")]
+    pub patches: Vec<SymbolPatch>,
+}
+
+impl PatchSet {
+    /// The names of the builtin [PatchSet]s.
+    pub const BUILTIN: [&'static str; 2] = ["go", "none"];
+
+    /// Returns the builtin [PatchSet] for Go guests, which disables the garbage collector, the
+    /// background threads of the runtime and memory profiling.
+    pub fn go() -> Self {
+        let mut patches = GO_SYMBOLS
+            .iter()
+            .map(|symbol| SymbolPatch {
+                symbol: symbol.to_string(),
+                replacement: Replacement::Ret,
+            })
+            .collect::<Vec<_>>();
+        // Disable memory profiling, to avoid a lot of unnecessary floating point ops.
+        patches.push(SymbolPatch {
+            symbol: "runtime.MemProfileRate".to_string(),
+            replacement: Replacement::Word(0),
+        });
+        Self {
+            name: "go".to_string(),
+            patches,
+        }
+    }
+
+    /// Returns the builtin [PatchSet] with the given name, or `None` if there is none.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "go" => Some(Self::go()),
+            "none" => Some(Self {
+                name: "none".to_string(),
+                patches: Vec::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parses a [PatchSet] from its TOML representation.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Loads a [PatchSet] from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Resolves a [PatchSet] from either the name of a builtin set or the path to a TOML file.
+    pub fn resolve(name_or_path: &str) -> Result<Self> {
+        match Self::builtin(name_or_path) {
+            Some(set) => Ok(set),
+            None => Self::load(name_or_path).map_err(|e| {
+                anyhow::anyhow!(
+                    "{} is neither a builtin patch set ({}) nor a valid patch set file: {}",
+                    name_or_path,
+                    Self::BUILTIN.join(", "),
+                    e
+                )
+            }),
+        }
+    }
+
+    /// Applies the patches to the symbols of an ELF file that was loaded into the state.
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    /// - `state`: The state that the ELF file was loaded into.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the number of patches applied.
+    pub fn apply(&self, raw: &[u8], state: &mut State) -> Result<usize> {
+        if self.patches.is_empty() {
+            return Ok(0);
+        }
+
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let (parsing_table, string_table) = elf
+            .symbol_table()?
+            .ok_or(anyhow::anyhow!("Failed to load ELF symbol table"))?;
+
+        let mut applied = 0;
+        for symbol in parsing_table {
+            let name = string_table.get(symbol.st_name as usize)?;
+            for patch in self.patches.iter().filter(|patch| patch.symbol == name) {
+                state
+                    .memory
+                    .set_range(symbol.st_value as u32, &patch.replacement.bytes())?;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{load_elf, Metadata};
+
+    #[test]
+    fn go_patch_set_from_toml() {
+        let toml = toml::to_string(&PatchSet::go()).unwrap();
+        assert_eq!(PatchSet::from_toml(&toml).unwrap(), PatchSet::go());
+
+        let set = PatchSet::from_toml(
+            r#"
+            name = "custom"
+
+            [[patch]]
+            symbol = "runtime.gcenable"
+            action = "ret"
+
+            [[patch]]
+            symbol = "runtime.MemProfileRate"
+            action = "word"
+            value = 0
+
+            [[patch]]
+            symbol = "runtime.check"
+            action = "bytes"
+            value = "0x03e0000800000000"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(set.patches.len(), 3);
+        assert_eq!(set.patches[2].replacement.bytes(), RET);
+
+        let raw = include_bytes!("../../../example/bin/hello.elf");
+        let mut state = load_elf(raw).unwrap();
+        assert!(set.apply(raw, &mut state).unwrap() >= 2);
+        let gcenable = Metadata::from_elf(raw)
+            .unwrap()
+            .symbols
+            .into_iter()
+            .find(|symbol| symbol.name == "runtime.gcenable")
+            .unwrap();
+        assert_eq!(state.memory.get_memory(gcenable.start).unwrap(), 0x03e00008);
+        assert_eq!(state.memory.get_memory(gcenable.start + 4).unwrap(), 0);
+
+        let mut unpatched = load_elf(raw).unwrap();
+        assert_eq!(
+            PatchSet::resolve("none")
+                .unwrap()
+                .apply(raw, &mut unpatched)
+                .unwrap(),
+            0
+        );
+        assert!(PatchSet::resolve("does-not-exist.toml").is_err());
+    }
+}
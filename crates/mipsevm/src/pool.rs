@@ -0,0 +1,208 @@
+//! This module contains the [PagePool], which recycles the [crate::CachedPage]s released by the
+//! [crate::Memory].
+
+use crate::{
+    page::{CachedPageOf, PAGE_ADDRESS_SIZE},
+    types::SharedCachedPageOf,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// The number of pages that are allocated in a batch when the [PagePool] runs dry.
+pub(crate) const REFILL_BATCH_SIZE: usize = 16;
+
+/// The default maximum number of free pages that the [PagePool] holds on to. Released pages beyond
+/// this limit are returned to the allocator.
+pub(crate) const DEFAULT_MAX_FREE_PAGES: usize = 1024;
+
+/// Counters describing the behavior of a [PagePool], for tuning its parameters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PagePoolStats {
+    /// The number of pages that were allocated from the system allocator.
+    pub allocated: u64,
+    /// The number of times that the pool was refilled with a batch of new pages.
+    pub refills: u64,
+    /// The number of pages handed out by [PagePool::acquire].
+    pub acquired: u64,
+    /// The number of pages handed out by [PagePool::acquire] that were recycled.
+    pub reused: u64,
+    /// The number of pages returned to the pool by [PagePool::release].
+    pub released: u64,
+    /// The number of pages that were dropped by [PagePool::release], either because they were
+    /// still shared or because the pool was full.
+    pub dropped: u64,
+    /// The number of free pages currently held by the pool.
+    pub free: usize,
+}
+
+/// The [PagePoolOf] hands out zeroed [CachedPageOf]s, recycling the pages that are released back to
+/// it. When the pool runs dry, it is refilled with a batch of [REFILL_BATCH_SIZE] pages, to reduce
+/// allocator churn when guests map and unmap memory heavily. Each page of a batch is allocated
+/// individually. Like the [CachedPageOf]s it hands
+/// out, it is generic over the number of address bits within a page.
+#[derive(Debug)]
+pub struct PagePoolOf<const P: usize>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The free pages, ready to be handed out.
+    free: Vec<SharedCachedPageOf<P>>,
+    /// The number of pages at the bottom of `free` that come from a refill and were never handed
+    /// out. The pool is only refilled when `free` is empty, so released pages always lie above
+    /// them.
+    fresh: usize,
+    /// The maximum number of free pages to hold on to.
+    max_free: usize,
+    /// Usage counters.
+    stats: PagePoolStats,
+}
+
+/// The [PagePoolOf] of the emulator's [crate::CachedPage]s.
+pub type PagePool = PagePoolOf<PAGE_ADDRESS_SIZE>;
+
+impl<const P: usize> Default for PagePoolOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FREE_PAGES)
+    }
+}
+
+impl<const P: usize> Clone for PagePoolOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// Pages in the pool are uniquely owned, so a clone starts out empty with the same limits.
+    fn clone(&self) -> Self {
+        Self::new(self.max_free)
+    }
+}
+
+impl<const P: usize> PartialEq for PagePoolOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The pool is an allocation detail that is not part of the observable state of the
+    /// [crate::Memory], so all pools compare equal.
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<const P: usize> Eq for PagePoolOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+}
+
+impl<const P: usize> PagePoolOf<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// Creates a new, empty [PagePool] that holds on to at most `max_free` free pages.
+    pub fn new(max_free: usize) -> Self {
+        Self {
+            free: Vec::default(),
+            fresh: 0,
+            max_free,
+            stats: PagePoolStats::default(),
+        }
+    }
+
+    /// Returns the usage counters of the [PagePool].
+    pub fn stats(&self) -> PagePoolStats {
+        PagePoolStats {
+            free: self.free.len(),
+            ..self.stats
+        }
+    }
+
+    /// Takes a zeroed page from the pool, refilling the pool if it is empty.
+    ///
+    /// ### Returns
+    /// - A uniquely owned, zeroed [CachedPageOf].
+    pub fn acquire(&mut self) -> SharedCachedPageOf<P> {
+        self.stats.acquired += 1;
+        if self.free.is_empty() {
+            self.refill();
+        }
+        if self.free.len() > self.fresh {
+            self.stats.reused += 1;
+        } else {
+            self.fresh -= 1;
+        }
+        self.free.pop().expect("Free list is not empty")
+    }
+
+    /// Returns a page to the pool. The page is only recycled if no other references to it exist
+    /// and the pool is not full.
+    ///
+    /// ### Takes
+    /// - `page`: The page to release.
+    pub fn release(&mut self, page: SharedCachedPageOf<P>) {
+        if Rc::strong_count(&page) != 1 || self.free.len() >= self.max_free {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        *page.borrow_mut() = CachedPageOf::default();
+        self.free.push(page);
+        self.stats.released += 1;
+    }
+
+    /// Refills the free list with a batch of [REFILL_BATCH_SIZE] newly allocated, zeroed pages.
+    fn refill(&mut self) {
+        self.free.reserve(REFILL_BATCH_SIZE);
+        self.free
+            .extend((0..REFILL_BATCH_SIZE).map(|_| Rc::new(RefCell::new(CachedPageOf::default()))));
+        self.fresh = REFILL_BATCH_SIZE;
+        self.stats.allocated += REFILL_BATCH_SIZE as u64;
+        self.stats.refills += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recycles_released_pages() {
+        let mut pool = PagePool::default();
+
+        let page = pool.acquire();
+        assert_eq!(pool.stats().refills, 1);
+        assert_eq!(pool.stats().free, REFILL_BATCH_SIZE - 1);
+
+        page.borrow_mut().data[0] = 0xFF;
+        pool.release(page);
+        assert_eq!(pool.stats().released, 1);
+
+        // Recycled pages are handed out zeroed.
+        for _ in 0..REFILL_BATCH_SIZE {
+            assert_eq!(*pool.acquire().borrow(), CachedPageOf::default());
+        }
+        assert_eq!(pool.stats().refills, 1);
+        assert_eq!(pool.stats().reused, 1);
+    }
+
+    #[test]
+    fn drops_shared_and_excess_pages() {
+        let mut pool = PagePool::new(0);
+
+        let page = pool.acquire();
+        let shared = Rc::clone(&page);
+        pool.release(page);
+        drop(shared);
+        let page = pool.acquire();
+        pool.release(page);
+
+        assert_eq!(pool.stats().released, 0);
+        assert_eq!(pool.stats().dropped, 2);
+    }
+}
@@ -0,0 +1,302 @@
+//! This module contains the [PrecompileOracle], which serves the results of precompile calls
+//! (preimage key type 6) by executing the precompiles natively with revm.
+//!
+//! Guests such as `op-program` accelerate expensive precompiles, e.g. `ecrecover` or the KZG point
+//! evaluation, by hinting the precompile's address and input with an `l1-precompile` hint and then
+//! reading the result as a [PrecompileKey] preimage, rather than executing the precompile in MIPS.
+
+use crate::{CannonError, CannonResult, PreimageOracle};
+use alloy_primitives::hex;
+use preimage_oracle::{Hint, Key, KeyType, PrecompileKey};
+use revm::{
+    precompile::{Precompile, Precompiles},
+    primitives::{Address, Env},
+};
+use rustc_hash::FxHashMap;
+
+/// The type of the hint that announces the address and input of a precompile call.
+pub const PRECOMPILE_HINT: &str = "l1-precompile";
+
+/// Executes a precompile as the `PreimageOracle` contract does with a `staticcall`, returning the
+/// preimage of its [PrecompileKey]: a status byte, `1` if the call succeeded and `0` otherwise,
+/// followed by the output of the call. A call to an address without a precompile succeeds with
+/// an empty output, as a call to an account without code does.
+///
+/// ### Takes
+/// - `address`: The address of the precompile.
+/// - `input`: The input of the call.
+///
+/// ### Returns
+/// - The status byte followed by the output of the call.
+pub fn execute_precompile(address: [u8; 20], input: &[u8]) -> Vec<u8> {
+    let result = match Precompiles::latest().get(&Address::from(address)) {
+        Some(Precompile::Standard(precompile)) => precompile(input, u64::MAX),
+        Some(Precompile::Env(precompile)) => precompile(input, u64::MAX, &Env::default()),
+        None => Ok((0, Vec::new())),
+    };
+    match result {
+        Ok((_, output)) => [&[1u8][..], &output].concat(),
+        Err(_) => vec![0],
+    }
+}
+
+/// The [PrecompileOracle] wraps a [PreimageOracle] and serves the preimages of [PrecompileKey]s
+/// by executing the precompiles that were hinted with [PRECOMPILE_HINT]. All other hints and
+/// preimages are forwarded to the wrapped oracle, as are precompile keys that were not hinted.
+pub struct PrecompileOracle<P: PreimageOracle> {
+    /// The wrapped [PreimageOracle].
+    inner: P,
+    /// The hinted precompile calls, by their preimage key.
+    calls: FxHashMap<[u8; 32], PrecompileKey>,
+}
+
+impl<P: PreimageOracle> PrecompileOracle<P> {
+    /// Creates a new [PrecompileOracle] in front of `inner`.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            calls: FxHashMap::default(),
+        }
+    }
+
+    /// Parses a [PRECOMPILE_HINT], whose data is the hex encoded address of the precompile
+    /// followed by its input.
+    fn parse_hint(hint: &[u8]) -> Option<PrecompileKey> {
+        let data = std::str::from_utf8(hint)
+            .ok()?
+            .strip_prefix(PRECOMPILE_HINT)?
+            .strip_prefix(' ')?;
+        let data = hex::decode(data.trim()).ok()?;
+        (data.len() >= 20).then(|| PrecompileKey {
+            address: data[..20].try_into().expect("Slice is 20 bytes"),
+            input: data[20..].to_vec(),
+        })
+    }
+}
+
+impl<P: PreimageOracle> PreimageOracle for PrecompileOracle<P> {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
+        if let Some(call) = Self::parse_hint(value.hint()) {
+            self.calls.insert(call.clone().preimage_key(), call);
+        }
+        self.inner.hint(value)
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        if key[0] != KeyType::Precompile as u8 {
+            return self.inner.get(key);
+        }
+        match self.calls.get(&key) {
+            Some(call) => {
+                crate::traces::debug!(target: "mipsevm::precompile", "Executing precompile {}", Address::from(call.address));
+                Ok(execute_precompile(call.address, &call.input))
+            }
+            None => self.inner.get(key).map_err(|e| {
+                CannonError::OracleIo(anyhow::anyhow!(
+                    "Precompile preimage was not hinted with {}: {}",
+                    PRECOMPILE_HINT,
+                    e
+                ))
+            }),
+        }
+    }
+
+    fn precompile_call(&self, key: [u8; 32]) -> Option<PrecompileKey> {
+        self.calls
+            .get(&key)
+            .cloned()
+            .or_else(|| self.inner.precompile_call(key))
+    }
+
+    fn on_read(
+        &mut self,
+        key: [u8; 32],
+        length: u64,
+        offset: u32,
+        part: &[u8],
+    ) -> CannonResult<()> {
+        self.inner.on_read(key, length, offset, part)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        test_utils::{
+            evm::{MipsEVM, OracleConfig},
+            StaticOracle,
+        },
+        InstrumentedState, State, StepWitness,
+    };
+    use alloy_primitives::{hex, keccak256};
+    use std::io;
+
+    /// `ecrecover` of a signature by `0xceaccac640adf55b2028469bd36ba501f28b699d`, from the
+    /// precompile test vectors of go-ethereum.
+    const ECRECOVER_INPUT: [u8; 128] = hex!("38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e000000000000000000000000000000000000000000000000000000000000001b38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02");
+
+    fn precompile_address(n: u8) -> [u8; 20] {
+        let mut address = [0u8; 20];
+        address[19] = n;
+        address
+    }
+
+    #[test]
+    fn precompile_key() {
+        let key = PrecompileKey {
+            address: precompile_address(1),
+            input: ECRECOVER_INPUT.to_vec(),
+        }
+        .preimage_key();
+        let expected = keccak256([&precompile_address(1)[..], &ECRECOVER_INPUT].concat());
+        assert_eq!(key[0], 6);
+        assert_eq!(key[1..], expected[1..]);
+    }
+
+    #[test]
+    fn executes_precompiles() {
+        assert_eq!(
+            execute_precompile(precompile_address(1), &ECRECOVER_INPUT)[13..],
+            hex!("ceaccac640adf55b2028469bd36ba501f28b699d")
+        );
+        assert_eq!(
+            execute_precompile(precompile_address(4), b"identity"),
+            b"\x01identity"
+        );
+        assert_eq!(execute_precompile(precompile_address(6), &[0xFF; 128]), [0]);
+        assert_eq!(execute_precompile(precompile_address(0xAA), b"input"), [1]);
+    }
+
+    /// Precompile calls with successful, failing and empty results.
+    fn calls() -> Vec<PrecompileKey> {
+        [
+            (precompile_address(1), ECRECOVER_INPUT.to_vec()),
+            (precompile_address(2), b"hello world".to_vec()),
+            (precompile_address(4), b"identity".to_vec()),
+            // Invalid input to the `bn256Add` precompile.
+            (precompile_address(6), vec![0xFF; 128]),
+            // Not a precompile.
+            (precompile_address(0xAA), b"input".to_vec()),
+        ]
+        .into_iter()
+        .map(|(address, input)| PrecompileKey { address, input })
+        .collect()
+    }
+
+    /// Steps a `read` of the precompile preimage at the given offset, served by a
+    /// [PrecompileOracle] that the call was hinted to.
+    fn step_precompile_read(call: &PrecompileKey, offset: u32) -> (StepWitness, State) {
+        // syscall: read(PreimageRead, 0x2000, 4)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            preimage_key: call.clone().preimage_key(),
+            preimage_offset: offset,
+            ..Default::default()
+        };
+        state.registers[2] = 4003;
+        state.registers[4] = 5;
+        state.registers[5] = 0x2000;
+        state.registers[6] = 4;
+        state.memory.set_memory(0x1000, 0x0000000C).unwrap();
+        state.memory.set_memory(0x2000, 0).unwrap();
+
+        let mut oracle = PrecompileOracle::new(StaticOracle::default());
+        let hint = format!(
+            "{} 0x{}{}",
+            PRECOMPILE_HINT,
+            hex::encode(call.address),
+            hex::encode(&call.input)
+        );
+        oracle.hint(hint.as_bytes()).unwrap();
+
+        let mut instrumented = InstrumentedState::new(state, oracle, io::sink(), io::sink());
+        let witness = instrumented.step(true).unwrap().unwrap();
+        (witness, instrumented.state)
+    }
+
+    #[test]
+    fn precompile_witness() {
+        let call = &calls()[0];
+        let (witness, _) = step_precompile_read(call, 8);
+        assert_eq!(
+            witness.precompile_call,
+            Some([&call.address[..], &call.input].concat())
+        );
+        assert_eq!(
+            witness.encode_preimage_oracle_input().unwrap(),
+            crate::encode_precompile_preimage_part(call.address, &call.input, 8)
+        );
+
+        // Without the call, the preimage cannot be loaded into the `PreimageOracle`.
+        let witness = StepWitness {
+            precompile_call: None,
+            ..witness
+        };
+        assert!(witness.encode_preimage_oracle_input().is_none());
+    }
+
+    #[test]
+    #[ignore = "the bundled PreimageOracle predates precompile preimages; set CANNON_PREIMAGE_ORACLE_ARTIFACT"]
+    fn precompiles_match_oracle_contract() {
+        let mut evm = MipsEVM::new();
+        evm.try_init_with_config(OracleConfig::from_env().unwrap())
+            .unwrap();
+
+        for call in calls() {
+            let address = Address::from(call.address);
+            let key = call.clone().preimage_key();
+            let result = execute_precompile(call.address, &call.input);
+            for offset in [0, 8, result.len() as u32 + 7] {
+                // The contract executes the precompile itself when the part is loaded.
+                evm.load_preimage_part(crate::encode_precompile_preimage_part(
+                    call.address,
+                    &call.input,
+                    offset,
+                ))
+                .unwrap();
+                assert_eq!(
+                    evm.read_preimage(key, offset).unwrap(),
+                    crate::preimage_part(&result, offset).unwrap(),
+                    "{} at offset {}",
+                    address,
+                    offset
+                );
+            }
+
+            // Steps that read the result load it from the witness, and match the `MIPS` contract.
+            let (witness, post) = step_precompile_read(&call, 4);
+            assert_eq!(
+                evm.step(witness).unwrap(),
+                post.encode_witness().unwrap(),
+                "{}",
+                address
+            );
+        }
+    }
+
+    #[test]
+    fn serves_hinted_precompiles() {
+        let mut oracle = PrecompileOracle::new(StaticOracle::new(b"hello world".to_vec()));
+        let call = PrecompileKey {
+            address: precompile_address(4),
+            input: b"identity".to_vec(),
+        };
+        let key = call.clone().preimage_key();
+        assert!(oracle.get(key).is_err());
+
+        let hint = format!(
+            "{} 0x{}{}",
+            PRECOMPILE_HINT,
+            hex::encode(call.address),
+            hex::encode(&call.input)
+        );
+        oracle.hint(hint.as_bytes()).unwrap();
+        assert_eq!(oracle.get(key).unwrap(), b"\x01identity");
+
+        // Other keys are served by the wrapped oracle.
+        let key = (keccak256(b"hello world").0 as preimage_oracle::Keccak256Key).preimage_key();
+        assert_eq!(oracle.get(key).unwrap(), b"hello world");
+    }
+}
@@ -0,0 +1,136 @@
+//! This module contains helpers to compute the parts of keccak256 preimages that the
+//! `PreimageOracle` contract serves, and the calldata that loads them, so that hosts can pre-load
+//! preimage parts on chain ahead of a `step`.
+//!
+//! The `PreimageOracle` serves a preimage in 32 byte parts of the preimage prefixed with its 8 byte
+//! big-endian length, at any offset within the prefixed preimage. Parts that extend past the end of
+//! the prefixed preimage are zero padded.
+
+use crate::witness::{loadKeccak256PreimagePartCall, loadPrecompilePreimagePartCall};
+use alloy_primitives::{keccak256, U256};
+use alloy_sol_types::SolCall;
+use preimage_oracle::{Keccak256Key, Key};
+use revm::primitives::Bytes;
+
+/// Returns the type-prefixed keccak256 preimage key of a preimage.
+pub fn keccak256_preimage_key(preimage: &[u8]) -> [u8; 32] {
+    (keccak256(preimage).0 as Keccak256Key).preimage_key()
+}
+
+/// Computes the part of a preimage that the `PreimageOracle` serves at the given offset.
+///
+/// ### Takes
+/// - `preimage`: The full preimage, without its length prefix.
+/// - `offset`: The offset into the length-prefixed preimage.
+///
+/// ### Returns
+/// - `Some((part, length))` containing the zero padded part and the number of bytes of it that lie
+///   within the length-prefixed preimage.
+/// - `None` if the offset lies beyond the end of the length-prefixed preimage, which the
+///   `PreimageOracle` rejects.
+pub fn preimage_part(preimage: &[u8], offset: u32) -> Option<([u8; 32], u32)> {
+    let offset = offset as usize;
+    let prefixed_len = preimage.len() + 8;
+    if offset >= prefixed_len {
+        return None;
+    }
+
+    let length_prefix = (preimage.len() as u64).to_be_bytes();
+    let mut part = [0u8; 32];
+    let length = (prefixed_len - offset).min(32);
+    for (i, byte) in part.iter_mut().take(length).enumerate() {
+        let index = offset + i;
+        *byte = if index < 8 {
+            length_prefix[index]
+        } else {
+            preimage[index - 8]
+        };
+    }
+    Some((part, length as u32))
+}
+
+/// ABI encodes the `PreimageOracle.loadKeccak256PreimagePart` call that loads the part of a
+/// preimage at the given offset.
+///
+/// ### Takes
+/// - `preimage`: The full preimage, without its length prefix.
+/// - `offset`: The offset into the length-prefixed preimage.
+///
+/// ### Returns
+/// - The ABI encoded calldata of the call.
+pub fn encode_keccak256_preimage_part(preimage: &[u8], offset: u32) -> Bytes {
+    let call = loadKeccak256PreimagePartCall {
+        _0: U256::from(offset),
+        _1: preimage.to_vec(),
+    };
+    call.abi_encode().into()
+}
+
+/// ABI encodes the `PreimageOracle.loadPrecompilePreimagePart` call that loads the part of the
+/// result of a precompile call at the given offset. The contract executes the precompile itself,
+/// so only the call is passed rather than its result.
+///
+/// ### Takes
+/// - `address`: The address of the precompile.
+/// - `input`: The input of the call.
+/// - `offset`: The offset into the length-prefixed result.
+///
+/// ### Returns
+/// - The ABI encoded calldata of the call.
+pub fn encode_precompile_preimage_part(address: [u8; 20], input: &[u8], offset: u32) -> Bytes {
+    let call = loadPrecompilePreimagePartCall {
+        _0: U256::from(offset),
+        _1: address.into(),
+        _2: input.to_vec(),
+    };
+    call.abi_encode().into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::evm::MipsEVM, CannonError};
+    use proptest::{prelude::*, proptest};
+
+    #[test]
+    fn parts() {
+        let preimage = b"hello world";
+        let (part, length) = preimage_part(preimage, 0).unwrap();
+        assert_eq!(length, 19);
+        assert_eq!(&part[..8], &11u64.to_be_bytes());
+        assert_eq!(&part[8..19], preimage);
+        assert_eq!(&part[19..], &[0u8; 13]);
+
+        let (part, length) = preimage_part(preimage, 18).unwrap();
+        assert_eq!(length, 1);
+        assert_eq!(part[0], b'd');
+        assert!(preimage_part(preimage, 19).is_none());
+
+        let key = keccak256_preimage_key(preimage);
+        assert_eq!(key[0], 2);
+        assert_eq!(key[1..], keccak256(preimage)[1..]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn parts_match_contract(
+            preimage in proptest::collection::vec(any::<u8>(), 0..200),
+            offset in 0u32..216,
+        ) {
+            let mut evm = MipsEVM::new();
+            evm.try_init().unwrap();
+
+            let key = keccak256_preimage_key(&preimage);
+            let result = evm.load_preimage_part(encode_keccak256_preimage_part(&preimage, offset));
+            match preimage_part(&preimage, offset) {
+                Some(expected) => {
+                    result.unwrap();
+                    prop_assert_eq!(evm.read_preimage(key, offset).unwrap(), expected);
+                }
+                None => prop_assert!(matches!(result, Err(CannonError::EvmRevert { .. }))),
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! This module contains utilities to verify the memory proofs emitted by the VM, e.g. in
+//! [crate::StepWitness::mem_proof], against a memory root, without re-implementing the layout of
+//! the memory tree.
+
+use crate::{Address, MerkleHasher};
+
+/// The size of a single memory proof, in bytes: the 32 byte leaf followed by its 27 siblings.
+pub const PROOF_SIZE: usize = 28 * 32;
+
+/// Verifies a keccak256 memory proof, as verified by the `MIPS` contract.
+///
+/// ### Takes
+/// - `root`: The memory root that the proof must commit to.
+/// - `addr`: The 4-byte aligned address of the word.
+/// - `value_word`: The value of the word at `addr`.
+/// - `proof`: The memory proof for `addr`, as produced by [crate::Memory::merkle_proof].
+///
+/// ### Returns
+/// - `true` if the proof is well-formed, its leaf holds `value_word` at `addr`, and it commits
+///   to `root`.
+/// - `false` otherwise.
+pub fn verify(root: [u8; 32], addr: Address, value_word: u32, proof: &[u8]) -> bool {
+    verify_with(MerkleHasher::Keccak256, root, addr, value_word, proof)
+}
+
+/// Verifies a memory proof of a tree merkleized with the given [MerkleHasher]. See [verify].
+pub fn verify_with(
+    hasher: MerkleHasher,
+    root: [u8; 32],
+    addr: Address,
+    value_word: u32,
+    proof: &[u8],
+) -> bool {
+    let Ok(proof) = <&[u8; PROOF_SIZE]>::try_from(proof) else {
+        return false;
+    };
+    leaf_word(proof, addr) == Some(value_word) && hasher.proof_root(addr, proof) == root
+}
+
+/// Returns the word at the given address within the leaf of a memory proof.
+///
+/// ### Takes
+/// - `proof`: The memory proof for `addr`.
+/// - `addr`: The address of the word.
+///
+/// ### Returns
+/// - `Some(word)` if `addr` is 4-byte aligned.
+/// - `None` otherwise.
+pub fn leaf_word(proof: &[u8; PROOF_SIZE], addr: Address) -> Option<u32> {
+    if addr & 3 != 0 {
+        return None;
+    }
+    let offset = (addr & 31) as usize;
+    let word = proof[offset..offset + 4]
+        .try_into()
+        .expect("Word is 4 bytes");
+    Some(u32::from_be_bytes(word))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Memory;
+
+    #[test]
+    fn verify_memory_proofs() {
+        let mut memory = Memory::default();
+        memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+        memory.set_memory(0x7FFF_FFFC, 0x12345678).unwrap();
+        let root = memory.merkle_root().unwrap();
+
+        for (addr, value) in [(0x1000, 0xDEADBEEF), (0x7FFF_FFFC, 0x12345678), (0x2000, 0)] {
+            let proof = memory.merkle_proof(addr).unwrap();
+            assert!(verify(root, addr, value, &proof));
+            assert!(!verify(root, addr, value ^ 1, &proof));
+            assert!(!verify(root, addr + 1, value, &proof));
+            assert!(!verify(root, addr, value, &proof[..PROOF_SIZE - 1]));
+
+            let mut tampered = proof;
+            tampered[PROOF_SIZE - 1] ^= 1;
+            assert!(!verify(root, addr, value, &tampered));
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! This module contains the [PageProtection] table, which tracks the permissions of the pages
+//! loaded from the segments of an ELF file and faults on writes to read-only pages and on
+//! execution from non-executable pages.
+//!
+//! The `MIPS` contract does not enforce page permissions, so the [PageProtection] is opt-in (see
+//! [crate::State::enable_protection]) and not part of the [crate::StateWitness]. It is intended to
+//! catch guest miscompiles early rather than to be proven on-chain.
+
+use crate::{page, Address, CannonError, CannonResult, PageIndex};
+use anyhow::Result;
+use elf::{abi::PT_LOAD, endian::AnyEndian, ElfBytes};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// The `p_flags` bit of an ELF segment that marks it as executable.
+const PF_X: u32 = 0x1;
+/// The `p_flags` bit of an ELF segment that marks it as writable.
+const PF_W: u32 = 0x2;
+/// The `p_flags` bit of an ELF segment that marks it as readable.
+const PF_R: u32 = 0x4;
+
+/// The [Permissions] of a page, as a bitmask of the ELF segment flags `PF_X`, `PF_W` and `PF_R`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions(u8);
+
+impl Permissions {
+    /// Pages that may be executed.
+    pub const EXECUTE: Self = Self(PF_X as u8);
+    /// Pages that may be written to.
+    pub const WRITE: Self = Self(PF_W as u8);
+    /// Pages that may be read from.
+    pub const READ: Self = Self(PF_R as u8);
+
+    /// Creates [Permissions] from the `p_flags` of an ELF program header.
+    pub fn from_elf_flags(flags: u32) -> Self {
+        Self((flags & (PF_X | PF_W | PF_R)) as u8)
+    }
+
+    /// Returns `true` if all permissions in `other` are granted.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the union of both sets of permissions.
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |perm: Self, c: char| if self.contains(perm) { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(Self::READ, 'r'),
+            flag(Self::WRITE, 'w'),
+            flag(Self::EXECUTE, 'x')
+        )
+    }
+}
+
+/// The kind of memory access that violated the permissions of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// A store, or a syscall writing to guest memory.
+    Write,
+    /// An instruction fetch.
+    Execute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Write => write!(f, "Write to"),
+            Access::Execute => write!(f, "Execution from"),
+        }
+    }
+}
+
+/// The [PageProtection] table maps the pages covered by the loadable segments of an ELF file to
+/// their [Permissions]. Pages outside of the segments, such as the heap, the stack and `mmap`ed
+/// regions, are readable and writable but not executable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageProtection {
+    /// Map of page indices to the [Permissions] of the pages loaded from the ELF file.
+    pages: BTreeMap<PageIndex, Permissions>,
+}
+
+impl PageProtection {
+    /// Builds the [PageProtection] table from the `PT_LOAD` segments of an ELF file. Pages that
+    /// are shared by multiple segments are granted the permissions of all of them.
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [PageProtection] table.
+    pub fn from_elf(raw: &[u8]) -> Result<Self> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let headers = elf
+            .segments()
+            .ok_or(anyhow::anyhow!("Failed to load section headers"))?;
+
+        let mut protection = Self::default();
+        for header in headers.iter() {
+            if header.p_type != PT_LOAD || header.p_memsz == 0 {
+                continue;
+            }
+            protection.protect(
+                header.p_vaddr,
+                header.p_vaddr + header.p_memsz,
+                Permissions::from_elf_flags(header.p_flags),
+            );
+        }
+        Ok(protection)
+    }
+
+    /// Grants the given [Permissions] to all pages overlapping the range `[start, end)`.
+    ///
+    /// ### Takes
+    /// - `start`: The start address of the range.
+    /// - `end`: The end address of the range.
+    /// - `permissions`: The [Permissions] to grant.
+    pub fn protect(&mut self, start: u64, end: u64, permissions: Permissions) {
+        let first_page = start >> page::PAGE_ADDRESS_SIZE;
+        let last_page = (end.max(start + 1) - 1) >> page::PAGE_ADDRESS_SIZE;
+        for page_index in first_page..=last_page {
+            let entry = self.pages.entry(page_index).or_default();
+            *entry = entry.union(permissions);
+        }
+    }
+
+    /// Returns the [Permissions] of the page containing the given address.
+    pub fn permissions(&self, address: Address) -> Permissions {
+        self.pages
+            .get(&(address as PageIndex >> page::PAGE_ADDRESS_SIZE))
+            .copied()
+            .unwrap_or(Permissions::READ.union(Permissions::WRITE))
+    }
+
+    /// Checks that the page containing `address` permits the given [Access].
+    ///
+    /// ### Takes
+    /// - `address`: The accessed address.
+    /// - `access`: The kind of access.
+    /// - `pc`: The program counter of the accessing instruction.
+    ///
+    /// ### Returns
+    /// - `Err(CannonError::ProtectionFault)` if the access is not permitted.
+    #[inline(always)]
+    pub fn check(&self, address: Address, access: Access, pc: Address) -> CannonResult<()> {
+        let permissions = self.permissions(address);
+        let required = match access {
+            Access::Write => Permissions::WRITE,
+            Access::Execute => Permissions::EXECUTE,
+        };
+        if permissions.contains(required) {
+            return Ok(());
+        }
+        Err(CannonError::ProtectionFault {
+            address,
+            access,
+            permissions,
+            pc,
+            region: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::StaticOracle, InstrumentedState, State};
+    use std::io;
+
+    #[test]
+    fn merges_segment_permissions() {
+        let mut protection = PageProtection::default();
+        let rx = Permissions::READ.union(Permissions::EXECUTE);
+        let rw = Permissions::READ.union(Permissions::WRITE);
+        protection.protect(0x1000, 0x2800, rx);
+        protection.protect(0x2800, 0x4000, rw);
+
+        assert_eq!(protection.permissions(0x1ffc), rx);
+        assert_eq!(protection.permissions(0x2000).to_string(), "rwx");
+        assert_eq!(protection.permissions(0x3000), rw);
+        assert_eq!(protection.permissions(0x8000), rw);
+
+        assert!(protection.check(0x1000, Access::Execute, 0).is_ok());
+        assert!(protection.check(0x2000, Access::Write, 0).is_ok());
+        assert!(matches!(
+            protection.check(0x1004, Access::Write, 0x1000),
+            Err(CannonError::ProtectionFault {
+                address: 0x1004,
+                access: Access::Write,
+                pc: 0x1000,
+                ..
+            })
+        ));
+        let err = protection
+            .check(0x3000, Access::Execute, 0x3000)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Execution from page with permissions rw- at 00003000 (pc 00003000)"
+        );
+    }
+
+    #[test]
+    fn strict_mode_faults() {
+        // 0x1000: sw $zero, 0x1000($zero)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xAC001000).unwrap();
+        let mut protection = PageProtection::default();
+        protection.protect(
+            0x1000,
+            0x2000,
+            Permissions::READ.union(Permissions::EXECUTE),
+        );
+        state.enable_protection(protection);
+
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        let err = ins.step(false).unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::ProtectionFault {
+                address: 0x1000,
+                access: Access::Write,
+                pc: 0x1000,
+                ..
+            }
+        ));
+        assert_eq!(ins.state.memory.get_memory(0x1000).unwrap(), 0xAC001000);
+
+        // Execution from pages outside of the program's segments faults as well.
+        ins.state.pc = 0x2000;
+        ins.state.next_pc = 0x2004;
+        let err = ins.step(false).unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::ProtectionFault {
+                access: Access::Execute,
+                ..
+            }
+        ));
+    }
+}
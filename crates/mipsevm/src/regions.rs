@@ -0,0 +1,266 @@
+//! This module contains the [MemoryRegions] of a [crate::State], which name the ranges of the
+//! guest's address space (its program, heap and stack) and optionally guard the page below the
+//! stack, so that guest crashes can be attributed to the region they occurred in.
+//!
+//! Region names are annotations only and do not change the semantics of the emulator. The stack
+//! guard aborts the run on any access to the guarded page, where the `MIPS` contract would let
+//! the stack silently grow into it, so it is opt-in (see [crate::State::enable_stack_guard]) and
+//! not part of the [crate::StateWitness].
+
+use crate::{address_space, page, Address, CannonError, CannonResult, PageIndex};
+use anyhow::Result;
+use elf::{abi::PT_LOAD, endian::AnyEndian, ElfBytes};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The name of the regions loaded from the segments of the program's ELF file.
+pub const PROGRAM_REGION: &str = "program";
+/// The name of the region that the heap grows into.
+pub const HEAP_REGION: &str = "heap";
+/// The name of the stack reservation below the initial stack pointer.
+pub const STACK_REGION: &str = "stack";
+/// The name of buffers that the guest reads pre-images into.
+pub const ORACLE_SCRATCH_REGION: &str = "oracle scratch";
+
+/// A named [MemoryRegion] of the guest's address space.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    /// The name of the region.
+    pub name: String,
+    /// The start address of the region.
+    pub start: u64,
+    /// The exclusive end address of the region.
+    pub end: u64,
+}
+
+impl MemoryRegion {
+    /// Returns `true` if the given address lies within the region.
+    pub fn contains(&self, address: Address) -> bool {
+        (self.start..self.end).contains(&(address as u64))
+    }
+}
+
+impl fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{:08x}, {:08x})", self.name, self.start, self.end)
+    }
+}
+
+/// The [MemoryRegions] hold the named [MemoryRegion]s of the guest's address space and the page
+/// guarding the stack, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryRegions {
+    /// The annotated regions, in the order they were annotated in.
+    regions: Vec<MemoryRegion>,
+    /// The index of the page guarding the stack. Any access to it aborts the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stack_guard: Option<PageIndex>,
+}
+
+impl MemoryRegions {
+    /// Annotates the standard regions of a program loaded with [crate::load_elf] and patched with
+    /// [crate::patch_stack]: each `PT_LOAD` segment of the ELF file as [PROGRAM_REGION], the
+    /// range from the heap pointer up to the stack as [HEAP_REGION], and the stack reservation
+    /// below the stack pointer as [STACK_REGION].
+    ///
+    /// ### Takes
+    /// - `raw`: The raw contents of the ELF file.
+    /// - `heap`: The initial heap pointer.
+    /// - `sp`: The initial stack pointer.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the annotated [MemoryRegions].
+    pub fn from_elf(raw: &[u8], heap: Address, sp: Address) -> Result<Self> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(raw)?;
+        let headers = elf
+            .segments()
+            .ok_or(anyhow::anyhow!("Failed to load section headers"))?;
+
+        let mut regions = Self::default();
+        for header in headers.iter() {
+            if header.p_type == PT_LOAD && header.p_memsz != 0 {
+                regions.annotate(
+                    PROGRAM_REGION,
+                    header.p_vaddr,
+                    header.p_vaddr + header.p_memsz,
+                );
+            }
+        }
+        let (stack_start, stack_end) = address_space::stack_reservation(sp);
+        regions.annotate(HEAP_REGION, heap as u64, stack_start);
+        regions.annotate(STACK_REGION, stack_start, stack_end);
+        Ok(regions)
+    }
+
+    /// Names the range `[start, end)`. Where annotations overlap, the most recent one takes
+    /// precedence.
+    ///
+    /// ### Takes
+    /// - `name`: The name of the region.
+    /// - `start`: The start address of the region.
+    /// - `end`: The exclusive end address of the region.
+    pub fn annotate(&mut self, name: impl Into<String>, start: u64, end: u64) {
+        self.regions.push(MemoryRegion {
+            name: name.into(),
+            start,
+            end,
+        });
+    }
+
+    /// Names the range `[start, end)` that the guest read a pre-image into as
+    /// [ORACLE_SCRATCH_REGION]. The most recently annotated region is extended instead if it is
+    /// an oracle scratch region that the range overlaps or adjoins, so that a buffer filled by
+    /// consecutive reads is annotated as a single region.
+    ///
+    /// ### Takes
+    /// - `start`: The start address of the range.
+    /// - `end`: The exclusive end address of the range.
+    pub fn annotate_oracle_scratch(&mut self, start: u64, end: u64) {
+        if let Some(last) = self.regions.last_mut() {
+            if last.name == ORACLE_SCRATCH_REGION && start <= last.end && end >= last.start {
+                last.start = last.start.min(start);
+                last.end = last.end.max(end);
+                return;
+            }
+        }
+        self.annotate(ORACLE_SCRATCH_REGION, start, end);
+    }
+
+    /// Returns an iterator over the annotated regions, in the order they were annotated in.
+    pub fn regions(&self) -> impl Iterator<Item = &MemoryRegion> {
+        self.regions.iter()
+    }
+
+    /// Returns the most recently annotated region containing the given address, if any.
+    pub fn region_of(&self, address: Address) -> Option<&MemoryRegion> {
+        self.regions
+            .iter()
+            .rev()
+            .find(|region| region.contains(address))
+    }
+
+    /// Guards the page containing the given address, so that any guest access to it aborts the
+    /// run with a [CannonError::StackOverflow].
+    ///
+    /// ### Takes
+    /// - `address`: An address within the page to guard.
+    pub fn set_stack_guard(&mut self, address: Address) {
+        self.stack_guard = Some(address as PageIndex >> page::PAGE_ADDRESS_SIZE);
+    }
+
+    /// Returns the base address of the page guarding the stack, if any.
+    pub fn stack_guard(&self) -> Option<Address> {
+        self.stack_guard
+            .map(|page_index| (page_index << page::PAGE_ADDRESS_SIZE) as Address)
+    }
+
+    /// Checks that a guest access does not touch the page guarding the stack.
+    ///
+    /// ### Takes
+    /// - `address`: The accessed address.
+    /// - `pc`: The program counter of the accessing instruction.
+    ///
+    /// ### Returns
+    /// - `Err(CannonError::StackOverflow)` if the address lies within the guard page.
+    #[inline(always)]
+    pub fn check(&self, address: Address, pc: Address) -> CannonResult<()> {
+        match self.stack_guard {
+            Some(page_index) if address as PageIndex >> page::PAGE_ADDRESS_SIZE == page_index => {
+                Err(CannonError::StackOverflow {
+                    address,
+                    pc,
+                    region: None,
+                }
+                .with_region(Some(self)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::StaticOracle, InstrumentedState, State};
+    use std::io;
+
+    #[test]
+    fn region_lookup() {
+        let mut regions = MemoryRegions::default();
+        regions.annotate(HEAP_REGION, 0x2000_0000, 0x7000_0000);
+        regions.annotate(ORACLE_SCRATCH_REGION, 0x2000_1000, 0x2000_2000);
+
+        assert_eq!(regions.region_of(0x2000_0ffc).unwrap().name, HEAP_REGION);
+        assert_eq!(
+            regions.region_of(0x2000_1000).unwrap().name,
+            ORACLE_SCRATCH_REGION
+        );
+        assert!(regions.region_of(0x7000_0000).is_none());
+        assert_eq!(
+            regions.region_of(0x2000_2000).unwrap().to_string(),
+            "heap [20000000, 70000000)"
+        );
+
+        // Consecutive pre-image reads into a buffer extend a single scratch region.
+        regions.annotate_oracle_scratch(0x3000_0000, 0x3000_0004);
+        regions.annotate_oracle_scratch(0x3000_0004, 0x3000_0008);
+        regions.annotate_oracle_scratch(0x3000_0006, 0x3000_0007);
+        assert_eq!(regions.regions().count(), 3);
+        assert_eq!(
+            regions.region_of(0x3000_0004).unwrap().to_string(),
+            "oracle scratch [30000000, 30000008)"
+        );
+    }
+
+    #[test]
+    fn stack_guard_aborts() {
+        // addiu $sp, $sp, -16; sw $zero, 0($sp)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0x27BDFFF0).unwrap();
+        state.memory.set_memory(0x1004, 0xAFA00000).unwrap();
+        state.registers[29] = 0x7F00_0008;
+        state.enable_stack_guard();
+        let guard = state.regions.as_ref().unwrap().stack_guard().unwrap();
+        assert_eq!(
+            guard as u64 + page::PAGE_SIZE as u64,
+            0x7F00_1000 - (8 << 20)
+        );
+
+        // Accesses within the stack reservation are unaffected.
+        let mut ins = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        ins.step(false).unwrap();
+        ins.step(false).unwrap();
+
+        state.registers[29] = guard + 0x10;
+        state
+            .regions
+            .as_mut()
+            .unwrap()
+            .annotate("stack", guard as u64, 0x7F00_1000);
+        let mut ins =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        ins.step(false).unwrap();
+        let err = ins.step(false).unwrap_err();
+        assert!(matches!(
+            err,
+            CannonError::StackOverflow { address, pc: 0x1004, .. } if address == guard
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Guest stack overflow: access to guard page at {:08x} in stack (pc 00001004)",
+                guard
+            )
+        );
+    }
+}
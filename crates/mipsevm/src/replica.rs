@@ -0,0 +1,305 @@
+//! This module contains the [StateReplica], a copy of an [InstrumentedState]'s [State] that is kept
+//! in sync through [StateDelta]s, so that the [StepWitness]es of an emulator's steps, and the
+//! merkle proofs within them, can be generated on another thread than the one running the
+//! emulator.
+//!
+//! The emulator starts replicating its state with [InstrumentedState::replicate], and executes the
+//! steps that require a proof with [InstrumentedState::step_replicated], which only transfers the
+//! pages written since the last proven step. The [StateReplica] applies the changes and executes
+//! the step again, with the preimage that the emulator read during the step, to generate its
+//! witness.
+
+use crate::{
+    traits::PreimageOracle, CannonError, CannonResult, InstrumentedState, Memory, MerkleHasher,
+    Page, PageIndex, State, StepWitness,
+};
+use preimage_oracle::{Hint, KeyType, PrecompileKey};
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+/// The [StateDelta] holds the changes of an [InstrumentedState]'s [State] since the last
+/// [StateDelta] was taken from it.
+#[derive(Debug)]
+pub struct StateDelta {
+    /// The [State] without its [Memory], serialized as JSON.
+    fields: Vec<u8>,
+    /// The pages that were written, allocated or freed, with their data, or `None` if freed.
+    pages: Vec<(PageIndex, Option<Box<Page>>)>,
+}
+
+/// The [ReplicaSeed] holds the full [State] of an [InstrumentedState] at the time that it started
+/// replicating, from which a [StateReplica] is created.
+#[derive(Debug)]
+pub struct ReplicaSeed {
+    /// The full [State], with all of its pages.
+    delta: StateDelta,
+    /// The [MerkleHasher] of the [State]'s [Memory].
+    hasher: MerkleHasher,
+    /// Whether the memory accesses of each step are recorded in its [StepWitness].
+    record_access_log: bool,
+}
+
+/// The [ReplicatedStep] holds the changes of an [InstrumentedState]'s [State] up to a step that
+/// requires a proof, along with the preimage that the step read, if any.
+#[derive(Debug)]
+pub struct ReplicatedStep {
+    /// The changes of the [State] before the step.
+    delta: StateDelta,
+    /// The step that was executed.
+    step: u64,
+    /// The preimage that was read during the step, if any.
+    preimage: Option<ReplicatedPreimage>,
+}
+
+impl ReplicatedStep {
+    /// Returns the step that was executed.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+}
+
+/// A preimage that was read during a [ReplicatedStep].
+#[derive(Debug, Clone)]
+struct ReplicatedPreimage {
+    /// The key of the preimage.
+    key: [u8; 32],
+    /// The preimage, without its length prefix.
+    data: Vec<u8>,
+    /// The precompile call that the preimage was derived from, for precompile keys.
+    precompile_call: Option<PrecompileKey>,
+}
+
+impl<O, E, P> InstrumentedState<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    /// Starts replicating the [State], tracking the pages that are written from here on.
+    ///
+    /// ### Returns
+    /// - The [ReplicaSeed] to create the [StateReplica] from, holding a copy of the full [State].
+    pub fn replicate(&mut self) -> CannonResult<ReplicaSeed> {
+        self.state.memory.track_dirty_pages();
+        self.state.memory.take_dirty_pages();
+        let pages = self
+            .state
+            .memory
+            .dump_sparse()
+            .into_iter()
+            .map(|(address, data)| {
+                (
+                    address as PageIndex >> Memory::PAGE_ADDRESS_SIZE,
+                    Some(Box::new(data)),
+                )
+            })
+            .collect();
+
+        Ok(ReplicaSeed {
+            delta: StateDelta {
+                fields: self.serialize_fields()?,
+                pages,
+            },
+            hasher: self.state.memory.hasher(),
+            record_access_log: self.record_access_log,
+        })
+    }
+
+    /// Steps the MIPS emulator forward one instruction without generating a [StepWitness],
+    /// capturing what a [StateReplica] needs to generate it instead. The [State] must be
+    /// replicated with [InstrumentedState::replicate] first.
+    ///
+    /// Step hooks are not executed by the [StateReplica], so they must not change the [State].
+    ///
+    /// ### Returns
+    /// - Ok(step): The [ReplicatedStep] to generate the [StepWitness] from.
+    /// - Err(_): An error occurred while processing the instruction step in the MIPS emulator.
+    pub fn step_replicated(&mut self) -> CannonResult<ReplicatedStep> {
+        let step = self.state.step;
+        let delta = StateDelta {
+            fields: self.serialize_fields()?,
+            pages: self.state.memory.take_dirty_pages(),
+        };
+
+        self.step(false)?;
+
+        let preimage = (self.last_preimage_offset != u32::MAX).then(|| {
+            let key = self.last_preimage_key;
+            ReplicatedPreimage {
+                key,
+                data: self.last_preimage[8..].to_vec(),
+                precompile_call: (key[0] == KeyType::Precompile as u8)
+                    .then(|| self.preimage_oracle.precompile_call(key))
+                    .flatten(),
+            }
+        });
+
+        Ok(ReplicatedStep {
+            delta,
+            step,
+            preimage,
+        })
+    }
+
+    /// Serializes the [State] without its [Memory].
+    fn serialize_fields(&mut self) -> CannonResult<Vec<u8>> {
+        let memory = mem::take(&mut self.state.memory);
+        let fields = serde_json::to_vec(&self.state);
+        self.state.memory = memory;
+        Ok(fields.map_err(anyhow::Error::from)?)
+    }
+}
+
+/// The [StateReplica] is a copy of an [InstrumentedState]'s [State], which generates the
+/// [StepWitness]es of the steps replicated with [InstrumentedState::step_replicated].
+pub struct StateReplica {
+    /// The replicated state, which serves the preimages read by the replicated steps.
+    ins_state: InstrumentedState<io::Sink, io::Sink, ReplicaOracle>,
+}
+
+impl StateReplica {
+    /// Creates a new [StateReplica] from a [ReplicaSeed].
+    ///
+    /// ### Takes
+    /// - `seed`: The [ReplicaSeed] taken with [InstrumentedState::replicate].
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the [StateReplica].
+    pub fn new(seed: ReplicaSeed) -> CannonResult<Self> {
+        let state = State {
+            memory: Memory::with_hasher(seed.hasher),
+            ..Default::default()
+        };
+        let mut ins_state =
+            InstrumentedState::new(state, ReplicaOracle::default(), io::sink(), io::sink());
+        ins_state.set_record_access_log(seed.record_access_log);
+
+        let mut replica = Self { ins_state };
+        replica.apply(seed.delta)?;
+        Ok(replica)
+    }
+
+    /// Brings the [StateReplica] up to date with the pre-state of a [ReplicatedStep], and executes
+    /// the step to generate its [StepWitness].
+    ///
+    /// ### Takes
+    /// - `step`: The [ReplicatedStep] taken with [InstrumentedState::step_replicated].
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the hash of the pre-state, the [StepWitness] and the hash of
+    ///   the post-state.
+    pub fn prove(
+        &mut self,
+        step: ReplicatedStep,
+    ) -> CannonResult<([u8; 32], StepWitness, [u8; 32])> {
+        self.apply(step.delta)?;
+        if self.ins_state.state.step != step.step {
+            return Err(CannonError::Other(anyhow::anyhow!(
+                "Replicated step {} does not match the replica at step {}",
+                step.step,
+                self.ins_state.state.step
+            )));
+        }
+
+        self.ins_state.preimage_oracle.preimage = step.preimage;
+        let prestate_hash = self.ins_state.state.state_hash()?;
+        let witness = self
+            .ins_state
+            .step(true)?
+            .ok_or_else(|| CannonError::Other(anyhow::anyhow!("No step witness")))?;
+        let poststate_hash = self.ins_state.state.state_hash()?;
+        Ok((prestate_hash, witness, poststate_hash))
+    }
+
+    /// Applies a [StateDelta] to the replicated [State].
+    fn apply(&mut self, delta: StateDelta) -> CannonResult<()> {
+        let mut state =
+            serde_json::from_slice::<State>(&delta.fields).map_err(anyhow::Error::from)?;
+        state.memory = mem::take(&mut self.ins_state.state.memory);
+        self.ins_state.state = state;
+
+        let memory = &mut self.ins_state.state.memory;
+        for (page_index, data) in delta.pages {
+            match data {
+                Some(data) => memory
+                    .restore_sparse([((page_index << Memory::PAGE_ADDRESS_SIZE) as u32, *data)])?,
+                None => {
+                    memory.free_page(page_index);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The [ReplicaOracle] serves the preimage read by a [ReplicatedStep] to the [StateReplica].
+#[derive(Default)]
+struct ReplicaOracle {
+    /// The preimage read by the step that is being replicated, if any.
+    preimage: Option<ReplicatedPreimage>,
+}
+
+impl PreimageOracle for ReplicaOracle {
+    fn hint(&mut self, _value: impl Hint) -> CannonResult<()> {
+        Ok(())
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        match self.preimage {
+            Some(ref preimage) if preimage.key == key => Ok(preimage.data.clone()),
+            _ => Err(CannonError::Other(anyhow::anyhow!(
+                "Preimage {} was not read by the replicated step",
+                alloy_primitives::hex::encode(key)
+            ))),
+        }
+    }
+
+    fn precompile_call(&self, key: [u8; 32]) -> Option<PrecompileKey> {
+        self.preimage
+            .as_ref()
+            .filter(|preimage| preimage.key == key)
+            .and_then(|preimage| preimage.precompile_call.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{open_mips, StaticOracle};
+
+    #[test]
+    fn replica_matches_emulator() {
+        let mut replica = None;
+        open_mips::run_all(|ins| {
+            if ins.state.step == 0 {
+                replica = Some(StateReplica::new(ins.replicate()?)?);
+            }
+            let replica = replica.as_mut().expect("Replica is created at step 0");
+
+            // Prove every third step, so that the changes of the steps in between are replicated
+            // as well.
+            if ins.state.step % 3 != 0 {
+                ins.step(false)?;
+                return Ok(());
+            }
+
+            let mut expected = InstrumentedState::new(
+                ins.state.clone(),
+                StaticOracle::new(open_mips::VECTOR_PREIMAGE.to_vec()),
+                io::sink(),
+                io::sink(),
+            );
+            let expected_pre = expected.state.state_hash()?;
+            let expected_witness = expected.step(true)?.expect("Witness must be generated");
+
+            let (pre, witness, post) = replica.prove(ins.step_replicated()?)?;
+            anyhow::ensure!(pre == expected_pre, "pre-state mismatch");
+            anyhow::ensure!(witness == expected_witness, "witness mismatch");
+            anyhow::ensure!(post == ins.state.state_hash()?, "post-state mismatch");
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+//! This module contains the [SchedulerPolicy] trait, which decides the thread that a
+//! multithreaded emulator steps next, along with the [RoundRobin] and [SeededRandom] policies.
+//!
+//! The emulator itself is single-threaded; the policies exist so that a multithreaded stepper can
+//! explore interleavings of guest threads deterministically, replaying any schedule from its
+//! policy and seed.
+
+use crate::utils::keccak256;
+
+/// The identifier of a guest thread.
+pub type ThreadId = u32;
+
+/// A [SchedulerPolicy] decides which of the runnable guest threads is stepped next.
+///
+/// Policies must be deterministic: given the same sequence of calls, they must pick the same
+/// threads, so that a schedule that exposes a concurrency bug can be replayed.
+pub trait SchedulerPolicy {
+    /// Picks the thread to step next.
+    ///
+    /// ### Takes
+    /// - `step`: The step that the picked thread will execute.
+    /// - `runnable`: The runnable threads, in ascending order. Never empty.
+    ///
+    /// ### Returns
+    /// - The picked thread, which is one of `runnable`.
+    fn next_thread(&mut self, step: u64, runnable: &[ThreadId]) -> ThreadId;
+}
+
+/// A [SchedulerPolicy] that steps the runnable threads in turn, in ascending order of their
+/// [ThreadId].
+#[derive(Debug, Default, Clone)]
+pub struct RoundRobin {
+    /// The thread that was picked last, if any.
+    last: Option<ThreadId>,
+}
+
+impl SchedulerPolicy for RoundRobin {
+    fn next_thread(&mut self, _: u64, runnable: &[ThreadId]) -> ThreadId {
+        let next = self
+            .last
+            .and_then(|last| runnable.iter().find(|&&id| id > last))
+            .unwrap_or(&runnable[0]);
+        self.last = Some(*next);
+        *next
+    }
+}
+
+/// A [SchedulerPolicy] that picks a runnable thread at random, from a stream derived from a seed
+/// as `keccak256(seed ++ step)`. The picked thread only depends on the seed, the step and the
+/// runnable threads, so any schedule can be replayed from its seed.
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    /// The seed of the stream.
+    seed: [u8; 32],
+}
+
+impl SeededRandom {
+    /// Creates a new [SeededRandom] policy from the given seed.
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self { seed }
+    }
+}
+
+impl SchedulerPolicy for SeededRandom {
+    fn next_thread(&mut self, step: u64, runnable: &[ThreadId]) -> ThreadId {
+        let mut preimage = [0u8; 40];
+        preimage[..32].copy_from_slice(&self.seed);
+        preimage[32..].copy_from_slice(&step.to_be_bytes());
+        let hash = keccak256(preimage);
+        let sample = u64::from_be_bytes(hash[..8].try_into().expect("hash is 32 bytes"));
+        runnable[(sample % runnable.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_robin() {
+        let mut policy = RoundRobin::default();
+        let picks = (0..5)
+            .map(|step| policy.next_thread(step, &[1, 2, 4]))
+            .collect::<Vec<_>>();
+        assert_eq!(picks, [1, 2, 4, 1, 2]);
+
+        // Threads that exit or block are skipped, and threads that spawn are picked in turn.
+        assert_eq!(policy.next_thread(5, &[1, 4]), 4);
+        assert_eq!(policy.next_thread(6, &[1, 3, 4]), 1);
+        assert_eq!(policy.next_thread(7, &[1, 3, 4]), 3);
+    }
+
+    #[test]
+    fn seeded_random() {
+        let runnable = [1, 2, 3, 4];
+        let schedule = |seed| {
+            let mut policy = SeededRandom::new(seed);
+            (0..64)
+                .map(|step| policy.next_thread(step, &runnable))
+                .collect::<Vec<_>>()
+        };
+
+        let a = schedule([1u8; 32]);
+        assert_eq!(a, schedule([1u8; 32]));
+        assert_ne!(a, schedule([2u8; 32]));
+        assert!(a.iter().all(|id| runnable.contains(id)));
+        assert!(runnable.iter().all(|id| a.contains(id)));
+    }
+}
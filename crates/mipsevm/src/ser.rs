@@ -1,9 +1,24 @@
 //! Serialization utilities for the `cannon-mipsevm` crate.
 
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use std::io::{Error, Read, Write};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::{Compression, Crc};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufWriter, Error, ErrorKind, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Decodes a hex string, with or without a `0x` prefix.
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>, alloy_primitives::hex::FromHexError> {
+    let s = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    alloy_primitives::hex::decode(s)
+}
 
 /// Generates a hex string serialization module for a fixed-size byte array.
 macro_rules! fixed_hex_ser {
@@ -24,13 +39,13 @@ macro_rules! fixed_hex_ser {
                 D: Deserializer<'de>,
             {
                 let s = String::deserialize(deserializer)?;
-                hex::decode(s)
-                    .map_err(serde::de::Error::custom)
-                    .map(|bytes| {
-                        let mut array = [0u8; $size];
-                        array.copy_from_slice(&bytes);
-                        array
-                    })
+                let bytes = crate::ser::decode_hex(&s).map_err(serde::de::Error::custom)?;
+                bytes.try_into().map_err(|bytes: Vec<u8>| {
+                    serde::de::Error::invalid_length(
+                        bytes.len(),
+                        &format!("{} bytes", $size).as_str(),
+                    )
+                })
             }
         }
     };
@@ -40,6 +55,32 @@ fixed_hex_ser!(fixed_32_hex, 32);
 fixed_hex_ser!(page_hex, crate::page::PAGE_SIZE);
 fixed_hex_ser!(state_witness_hex, crate::witness::STATE_WITNESS_SIZE);
 
+/// Hex string serialization for an optional 32 byte array, as in [fixed_32_hex].
+pub mod option_fixed_32_hex {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    /// A 32 byte array, deserialized with [super::fixed_32_hex].
+    #[derive(Deserialize)]
+    struct Fixed32(#[serde(with = "super::fixed_32_hex")] [u8; 32]);
+
+    pub fn serialize<S>(bytes: &Option<[u8; 32]>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match bytes {
+            Some(bytes) => super::fixed_32_hex::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<[u8; 32]>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Fixed32>::deserialize(deserializer)?.map(|Fixed32(bytes)| bytes))
+    }
+}
+
 pub mod vec_u8_hex {
     use alloy_primitives::hex;
     use serde::{self, Deserialize, Deserializer, Serializer};
@@ -56,7 +97,33 @@ pub mod vec_u8_hex {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        hex::decode(s).map_err(serde::de::Error::custom)
+        crate::ser::decode_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hex string serialization for an optional byte vector, as in [vec_u8_hex].
+pub mod option_vec_u8_hex {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    /// A byte vector, deserialized with [super::vec_u8_hex].
+    #[derive(Deserialize)]
+    struct Bytes(#[serde(with = "super::vec_u8_hex")] Vec<u8>);
+
+    pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match bytes {
+            Some(bytes) => super::vec_u8_hex::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Bytes>::deserialize(deserializer)?.map(|Bytes(bytes)| bytes))
     }
 }
 
@@ -67,18 +134,20 @@ macro_rules! fixed_base64_ser {
             use base64::Engine;
             use serde::{self, Deserialize, Deserializer, Serializer};
 
-            use crate::ser::{compress_bytes, decompress_bytes};
+            use crate::ser::{compress_bytes, decompress_blob};
 
+            /// Serializes the bytes as zlib-compressed base64, without a [crate::ser::BlobHeader],
+            /// as Go Cannon serializes them.
             pub fn serialize<S>(bytes: &[u8; $size], serializer: S) -> Result<S::Ok, S::Error>
             where
                 S: Serializer,
             {
-                let encoded = BASE64_STANDARD.encode(bytes);
-                let encoded =
-                    compress_bytes(encoded.as_bytes()).map_err(serde::ser::Error::custom)?;
-                serializer.serialize_bytes(&encoded)
+                let compressed = compress_bytes(bytes).map_err(serde::ser::Error::custom)?;
+                serializer.serialize_str(&BASE64_STANDARD.encode(compressed))
             }
 
+            /// Deserializes the bytes from compressed base64, with or without a
+            /// [crate::ser::BlobHeader].
             pub fn deserialize<'de, D>(deserializer: D) -> Result<[u8; $size], D::Error>
             where
                 D: Deserializer<'de>,
@@ -87,13 +156,13 @@ macro_rules! fixed_base64_ser {
                 let decoded = BASE64_STANDARD
                     .decode(s)
                     .map_err(serde::de::Error::custom)?;
-                decompress_bytes(&decoded)
-                    .map_err(serde::de::Error::custom)
-                    .map(|bytes| {
-                        let mut array = [0u8; $size];
-                        array.copy_from_slice(&bytes);
-                        array
-                    })
+                let bytes = decompress_blob(&decoded).map_err(serde::de::Error::custom)?;
+                bytes.try_into().map_err(|bytes: Vec<u8>| {
+                    serde::de::Error::invalid_length(
+                        bytes.len(),
+                        &format!("{} bytes", $size).as_str(),
+                    )
+                })
             }
         }
     };
@@ -103,16 +172,424 @@ fixed_base64_ser!(fixed_32_base64, 32);
 fixed_base64_ser!(page_base64, crate::page::PAGE_SIZE);
 fixed_base64_ser!(state_witness_base64, crate::witness::STATE_WITNESS_SIZE);
 
+/// Decompresses a byte slice that was compressed with [compress_bytes].
 pub fn decompress_bytes(compressed_bytes: &[u8]) -> Result<Vec<u8>, Error> {
-    let mut decoder = ZlibDecoder::new(compressed_bytes);
-    let mut decompressed_bytes = Vec::with_capacity(compressed_bytes.len());
-    decoder.read_to_end(&mut decompressed_bytes)?;
-
-    Ok(decompressed_bytes)
+    Codec::Zlib.decompress(compressed_bytes)
 }
 
+/// Compresses a byte slice with the [Codec::Zlib] codec.
 pub fn compress_bytes(decompressed_bytes: &[u8]) -> Result<Vec<u8>, Error> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
-    encoder.write_all(decompressed_bytes)?;
-    encoder.finish()
+    Codec::Zlib.compress(decompressed_bytes)
+}
+
+/// The magic bytes that prefix a blob compressed with [compress_blob].
+pub const BLOB_MAGIC: [u8; 4] = *b"CNB\x01";
+
+/// The size of a [BlobHeader], in bytes.
+pub const BLOB_HEADER_SIZE: usize = 13;
+
+/// The [BlobHeader] describes a compressed blob, so that corrupted blobs can be rejected before
+/// their contents are used. Go Cannon does not read blobs with a header, so they are only written
+/// for pages serialized with [crate::PageEncoding::Compact].
+///
+/// Encoded as [BLOB_MAGIC], followed by the [Codec::id], the uncompressed length and the CRC-32
+/// checksum of the uncompressed data, both as big-endian `u32`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobHeader {
+    /// The [Codec] that the blob is compressed with.
+    pub codec: Codec,
+    /// The length of the uncompressed data.
+    pub length: u32,
+    /// The CRC-32 checksum of the uncompressed data.
+    pub checksum: u32,
+}
+
+impl BlobHeader {
+    /// Encodes the [BlobHeader].
+    pub fn encode(&self) -> [u8; BLOB_HEADER_SIZE] {
+        let mut header = [0u8; BLOB_HEADER_SIZE];
+        header[..4].copy_from_slice(&BLOB_MAGIC);
+        header[4] = self.codec.id();
+        header[5..9].copy_from_slice(&self.length.to_be_bytes());
+        header[9..13].copy_from_slice(&self.checksum.to_be_bytes());
+        header
+    }
+
+    /// Decodes the [BlobHeader] at the start of a blob.
+    ///
+    /// ### Takes
+    /// - `blob`: The blob.
+    ///
+    /// ### Returns
+    /// - `Ok(Some((header, payload)))` if the blob starts with a valid header.
+    /// - `Ok(None)` if the blob does not start with [BLOB_MAGIC].
+    /// - `Err(_)` if the blob starts with [BLOB_MAGIC], but the header is invalid.
+    pub fn decode(blob: &[u8]) -> Result<Option<(Self, &[u8])>, Error> {
+        if !blob.starts_with(&BLOB_MAGIC) {
+            return Ok(None);
+        }
+        if blob.len() < BLOB_HEADER_SIZE {
+            return Err(invalid_data(format!(
+                "Truncated blob header: {} bytes",
+                blob.len()
+            )));
+        }
+
+        let codec = Codec::from_id(blob[4])
+            .ok_or_else(|| invalid_data(format!("Unknown codec id in blob header: {}", blob[4])))?;
+        let header = Self {
+            codec,
+            length: u32::from_be_bytes(blob[5..9].try_into().expect("Slice is 4 bytes")),
+            checksum: u32::from_be_bytes(blob[9..13].try_into().expect("Slice is 4 bytes")),
+        };
+        Ok(Some((header, &blob[BLOB_HEADER_SIZE..])))
+    }
+}
+
+/// Compresses a byte slice with the given [Codec], prefixed with a [BlobHeader].
+pub fn compress_blob(codec: Codec, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let length = u32::try_from(bytes.len())
+        .map_err(|_| invalid_data(format!("Blob too large: {} bytes", bytes.len())))?;
+    let header = BlobHeader {
+        codec,
+        length,
+        checksum: checksum(bytes),
+    };
+
+    let mut blob = header.encode().to_vec();
+    blob.extend(codec.compress(bytes)?);
+    Ok(blob)
+}
+
+/// Decompresses a blob produced by [compress_blob], validating its [BlobHeader]. Blobs without a
+/// header are decompressed with [decompress_bytes], for compatibility with states serialized
+/// before the header was introduced.
+pub fn decompress_blob(blob: &[u8]) -> Result<Vec<u8>, Error> {
+    let Some((header, payload)) = BlobHeader::decode(blob)? else {
+        return decompress_bytes(blob);
+    };
+
+    let bytes = header.codec.decompress(payload)?;
+    if bytes.len() != header.length as usize {
+        return Err(invalid_data(format!(
+            "Blob length mismatch: header says {} bytes, got {}",
+            header.length,
+            bytes.len()
+        )));
+    }
+    let actual = checksum(&bytes);
+    if actual != header.checksum {
+        return Err(invalid_data(format!(
+            "Blob checksum mismatch: header says {:08x}, got {:08x}",
+            header.checksum, actual
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Computes the CRC-32 checksum of a byte slice.
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Creates an [Error] of kind [ErrorKind::InvalidData].
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// The [Codec] enum describes the compression codecs that serialized states may be encoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// No compression.
+    None,
+    /// zlib, at the fastest compression level.
+    Zlib,
+    /// gzip, at the default compression level.
+    Gzip,
+    /// Zstandard, at the default compression level. Considerably faster than zlib and gzip for
+    /// large state dumps.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Codec {
+    /// Selects a [Codec] from the extension of a file path.
+    ///
+    /// ### Takes
+    /// - `path`: The file path.
+    ///
+    /// ### Returns
+    /// - `Some(codec)` if the extension is `.json`, `.zz`/`.zlib`, `.gz`/`.gzip` or
+    ///   `.zst`/`.zstd`.
+    /// - `None` if the extension is unknown.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(Codec::None),
+            "zz" | "zlib" => Some(Codec::Zlib),
+            "gz" | "gzip" => Some(Codec::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" | "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Detects the [Codec] that a byte slice was compressed with from its magic bytes, falling
+    /// back to [Codec::None].
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0x1F, 0x8B, ..] => Codec::Gzip,
+            #[cfg(feature = "zstd")]
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Codec::Zstd,
+            [0x78, flags, ..] if u16::from_be_bytes([0x78, *flags]) % 31 == 0 => Codec::Zlib,
+            _ => Codec::None,
+        }
+    }
+
+    /// Returns the identifier of the [Codec] within a [BlobHeader].
+    pub fn id(&self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Zlib => 1,
+            Codec::Gzip => 2,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => 3,
+        }
+    }
+
+    /// Returns the [Codec] with the given [Codec::id], if it is known.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Codec::None),
+            1 => Some(Codec::Zlib),
+            2 => Some(Codec::Gzip),
+            #[cfg(feature = "zstd")]
+            3 => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compresses a byte slice with the [Codec].
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Codec::None => Ok(bytes.to_vec()),
+            Codec::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Codec::Gzip => {
+                let mut encoder =
+                    GzEncoder::new(Vec::with_capacity(bytes.len()), Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::encode_all(bytes, 0),
+        }
+    }
+
+    /// Decompresses a byte slice that was compressed with the [Codec].
+    pub fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        // Give the decompressed buffer the same capacity as the compressed buffer to reduce
+        // reallocations up to the compressed buffer's size.
+        let mut decompressed_bytes = Vec::with_capacity(bytes.len());
+        match self {
+            Codec::None => return Ok(bytes.to_vec()),
+            Codec::Zlib => ZlibDecoder::new(bytes).read_to_end(&mut decompressed_bytes)?,
+            Codec::Gzip => GzDecoder::new(bytes).read_to_end(&mut decompressed_bytes)?,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => {
+                zstd::stream::Decoder::new(bytes)?.read_to_end(&mut decompressed_bytes)?
+            }
+        };
+        Ok(decompressed_bytes)
+    }
+}
+
+impl FromStr for Codec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Codec::None),
+            "zlib" => Ok(Codec::Zlib),
+            "gzip" => Ok(Codec::Gzip),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(Codec::Zstd),
+            #[cfg(not(feature = "zstd"))]
+            "zstd" => anyhow::bail!("zstd support requires the `zstd` feature"),
+            _ => anyhow::bail!("Invalid codec: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::None => write!(f, "none"),
+            Codec::Zlib => write!(f, "zlib"),
+            Codec::Gzip => write!(f, "gzip"),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Replaces the file at the given path with the bytes written by `write`.
+///
+/// The bytes are written to a temporary file in the same directory, which is synced to disk and
+/// renamed over the path once complete. The previous file is never truncated, so a crash does not
+/// leave a partially written file behind, and a [crate::State] loaded from the previous file with
+/// [crate::State::load_mmapped] stays valid even if it is written back to the same path.
+///
+/// ### Takes
+/// - `path`: The path of the file to replace.
+/// - `write`: Writes the new contents of the file.
+///
+/// ### Returns
+/// - A [Result] indicating whether the file was replaced.
+pub fn replace_file<E>(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), E>,
+) -> Result<(), E>
+where
+    E: From<Error>,
+{
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.tmp{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = File::create(&tmp_path)
+        .map_err(E::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            let file = writer.into_inner().map_err(|e| E::from(e.into_error()))?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|_| Ok(fs::rename(&tmp_path, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use base64::{prelude::BASE64_STANDARD, Engine};
+
+    fn codecs() -> Vec<Codec> {
+        vec![
+            Codec::None,
+            Codec::Zlib,
+            Codec::Gzip,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd,
+        ]
+    }
+
+    #[test]
+    fn codec_roundtrip() {
+        let bytes = br#"{"memory":[],"preimageKey":"0x00"}"#.repeat(64);
+        for codec in codecs() {
+            let compressed = codec.compress(&bytes).unwrap();
+            assert_eq!(Codec::detect(&compressed), codec);
+            assert_eq!(codec.decompress(&compressed).unwrap(), bytes);
+            assert_eq!(codec.to_string().parse::<Codec>().unwrap(), codec);
+        }
+    }
+
+    #[test]
+    fn codec_from_path() {
+        assert_eq!(Codec::from_path("state.json"), Some(Codec::None));
+        assert_eq!(Codec::from_path("state.json.gz"), Some(Codec::Gzip));
+        assert_eq!(Codec::from_path("/tmp/state.zz"), Some(Codec::Zlib));
+        assert_eq!(Codec::from_path("state"), None);
+        assert_eq!(Codec::from_path("state.bin"), None);
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Wrapper(#[serde(with = "page_base64")] crate::Page);
+
+    #[test]
+    fn blob_roundtrip() {
+        let mut page = [0u8; crate::page::PAGE_SIZE];
+        page[..4].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        let ser = serde_json::to_string(&Wrapper(page)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Wrapper>(&ser).unwrap(),
+            Wrapper(page)
+        );
+
+        for codec in codecs() {
+            let blob = compress_blob(codec, &page).unwrap();
+            let (header, _) = BlobHeader::decode(&blob).unwrap().unwrap();
+            assert_eq!(header.codec, codec);
+            assert_eq!(header.length as usize, page.len());
+            assert_eq!(decompress_blob(&blob).unwrap(), page);
+        }
+    }
+
+    #[test]
+    fn blob_legacy() {
+        let blob = compress_bytes(&[0xAB; 32]).unwrap();
+        assert_eq!(decompress_blob(&blob).unwrap(), [0xAB; 32]);
+    }
+
+    #[test]
+    fn blob_corrupted() {
+        let mut blob = compress_blob(Codec::None, &[0xAB; 32]).unwrap();
+        *blob.last_mut().unwrap() ^= 1;
+        let err = decompress_blob(&blob).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+
+        let blob = compress_blob(Codec::Zlib, &[0xAB; 32]).unwrap();
+        assert!(decompress_blob(&blob[..BLOB_HEADER_SIZE - 1]).is_err());
+        assert!(decompress_blob(&blob[..BLOB_HEADER_SIZE + 2]).is_err());
+
+        // A blob of the wrong size fails to deserialize rather than panicking.
+        let ser = format!(
+            "\"{}\"",
+            BASE64_STANDARD.encode(compress_blob(Codec::Zlib, &[0u8; 32]).unwrap())
+        );
+        assert!(serde_json::from_str::<Wrapper>(&ser).is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Key(#[serde(with = "fixed_32_hex")] [u8; 32]);
+
+    #[test]
+    fn fixed_hex_prefix() {
+        let hex = "ab".repeat(32);
+        for s in [
+            format!("\"0x{hex}\""),
+            format!("\"0X{hex}\""),
+            format!("\"{hex}\""),
+        ] {
+            assert_eq!(serde_json::from_str::<Key>(&s).unwrap(), Key([0xAB; 32]));
+        }
+    }
+
+    #[test]
+    fn fixed_hex_length() {
+        for len in [0, 31, 33] {
+            let s = format!("\"0x{}\"", "ab".repeat(len));
+            let err = serde_json::from_str::<Key>(&s).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("invalid length {len}, expected 32 bytes")),
+                "{err}"
+            );
+        }
+    }
 }
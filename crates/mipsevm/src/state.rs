@@ -1,16 +1,33 @@
 //! This module contains the data structure for the state of the MIPS emulator.
 
-use crate::{witness::STATE_WITNESS_SIZE, Memory, StateWitness, VMStatus};
+use crate::{
+    address_space::{self, AddressSpace},
+    binary, page, Address, CannonError, CannonResult, Clock, EntropySource, FdTable, Memory,
+    MemoryRegion, MemoryRegions, PageProtection, StateWitness, StateWitnessHasher, VMStatus,
+    Versioned, WitnessState, STATE_SCHEMA, STATE_VERSION,
+};
+use alloy_primitives::keccak256;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::{io::Write, path::Path};
 
 /// The [State] struct contains the internal model of the MIPS emulator state.
 ///
 /// The [State] by itself does not contain functionality for performing instruction steps
 /// or executing the MIPS emulator. For this, use the [crate::InstrumentedState] struct.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+///
+/// ## Extensions
+///
+/// The `enable_*` methods opt into behavior that the `MIPS` contract does not implement, such as
+/// additional syscalls or instructions. That behavior is not part of the [StateWitness], and the
+/// contract executes the affected steps differently, so states that enable any extension diverge
+/// from the semantics of the `MIPS` contract and cannot be proven on-chain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct State {
+    /// The version of the serialized [State] format. See [crate::Schema].
+    #[serde(default)]
+    pub version: u32,
     /// The [Memory] of the emulated MIPS thread context.
     pub memory: Memory,
     /// The preimage key for the given state.
@@ -42,43 +59,491 @@ pub struct State {
     #[serde(with = "crate::ser::vec_u8_hex")]
     #[serde(default)]
     pub last_hint: Vec<u8>,
+    /// The tracked address space of the guest, if Linux `mmap`, `munmap` and `brk` semantics are
+    /// enabled. Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_space: Option<AddressSpace>,
+    /// The table of open virtual files, if the `open`, `close` and `lseek` syscalls are enabled
+    /// for whitelisted virtual files. Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_files: Option<FdTable>,
+    /// The source of the bytes returned by the `getrandom` syscall, if it is enabled. Not part of
+    /// the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy: Option<EntropySource>,
+    /// The [Clock] serving the `clock_gettime` and `gettimeofday` syscalls, if they are enabled.
+    /// Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<Clock>,
+    /// Whether the MIPS32r2 bit-manipulation instructions (`seb`, `seh`, `wsbh`, `ext`, `ins`,
+    /// `rotr` and `rotrv`) are enabled. Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mips32r2: bool,
+    /// Whether the `futex` syscall is served with single-threaded semantics. Not part of the
+    /// [StateWitness].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub futex: bool,
+    /// The permissions of the pages loaded from the program, if writes to read-only pages and
+    /// execution from non-executable pages fault. Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protection: Option<PageProtection>,
+    /// The named regions of the guest's address space and the page guarding its stack, if any
+    /// were annotated. Not part of the [StateWitness].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<MemoryRegions>,
+    /// The merkle root of the [Memory] of a state that was pruned with [State::prune], whose
+    /// pages were discarded. Takes the place of the memory's own root in the [StateWitness].
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::ser::option_fixed_32_hex"
+    )]
+    pub pruned_memory_root: Option<[u8; 32]>,
+    /// The keccak256 digest of the [StateWitness] at the time the state was written, embedded by
+    /// [State::seal] so that truncated or corrupted state files are rejected by
+    /// [State::verify_checksum] when they are loaded. Not part of the [StateWitness].
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::ser::option_fixed_32_hex"
+    )]
+    pub checksum: Option<[u8; 32]>,
+    /// The memory root and state hash of the [State] as of the last time they were computed,
+    /// reused for as long as the state is unchanged. Not serialized.
+    #[serde(skip)]
+    pub hash_cache: StateHashCache,
+}
+
+/// The [StateHashCache] holds the merkle root of a [State]'s [Memory] and the [State]'s hash as of
+/// the last time they were computed. The root is tied to the [Memory::generation] that it was
+/// computed at, and the hash to the [StateWitness] that it was computed from, which covers the
+/// registers, so neither is recomputed while the state is unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct StateHashCache {
+    /// The generation of the memory and its merkle root.
+    memory_root: Option<(u64, [u8; 32])>,
+    /// The encoded witness and its state hash.
+    state_hash: Option<(StateWitness, [u8; 32])>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            memory: Memory::default(),
+            preimage_key: [0u8; 32],
+            preimage_offset: 0,
+            pc: 0,
+            next_pc: 0,
+            lo: 0,
+            hi: 0,
+            heap: 0,
+            exit_code: 0,
+            exited: false,
+            step: 0,
+            registers: [0u32; 32],
+            last_hint: Vec::default(),
+            address_space: None,
+            virtual_files: None,
+            entropy: None,
+            clock: None,
+            mips32r2: false,
+            futex: false,
+            protection: None,
+            regions: None,
+            pruned_memory_root: None,
+            checksum: None,
+            hash_cache: StateHashCache::default(),
+        }
+    }
+}
+
+impl Versioned for State {
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 impl State {
+    /// Deserializes a [State] from its JSON representation, upgrading states written by older
+    /// versions of this crate to the current [STATE_VERSION].
+    ///
+    /// ### Takes
+    /// - `raw`: The raw JSON state.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the deserialized [State].
+    pub fn from_json(raw: &[u8]) -> CannonResult<Self> {
+        STATE_SCHEMA.from_slice(raw)
+    }
+
+    /// Writes the [State] to a writer in the binary state file format, which can be loaded with
+    /// [State::load_mmapped].
+    ///
+    /// ### Takes
+    /// - `writer`: The writer to write the binary state file to.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the state was written successfully.
+    pub fn write_binary(&mut self, writer: impl Write) -> Result<()> {
+        binary::write(self, writer)
+    }
+
+    /// Saves the [State] to a binary state file at the given path. See [State::write_binary].
+    ///
+    /// The file is replaced with [crate::ser::replace_file] rather than overwritten in place, so
+    /// a [State] may be saved to the file it was loaded from with [State::load_mmapped].
+    pub fn save_binary(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        crate::ser::replace_file(path, |writer| self.write_binary(writer))
+    }
+
+    /// Loads a [State] from a binary state file by memory-mapping it. Only the registers and
+    /// metadata are parsed upfront; pages are copied out of the file on first access, so resuming
+    /// from a large snapshot is near-instant.
+    ///
+    /// ### Safety
+    /// The file must not be truncated or modified in place while the returned [State], or any
+    /// clone of it, is alive; doing so is undefined behavior, and typically terminates the process
+    /// with `SIGBUS` on the next page access. Replacing the file by renaming another one over it,
+    /// as [State::save_binary] and [crate::ser::replace_file] do, is safe.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the binary state file.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the loaded [State].
+    pub unsafe fn load_mmapped(path: impl AsRef<Path>) -> Result<Self> {
+        binary::load_mmapped(path)
+    }
+
     /// Encode the current [State] into a [StateWitness].
     ///
     /// ### Returns
     /// - A [Result] containing the encoded [StateWitness] or an error if the encoding failed.
     pub fn encode_witness(&mut self) -> Result<StateWitness> {
-        let mut witness: StateWitness = [0u8; STATE_WITNESS_SIZE];
-        witness[..32].copy_from_slice(self.memory.merkle_root()?.as_slice());
-        witness[32..64].copy_from_slice(self.preimage_key.as_slice());
-        witness[64..68].copy_from_slice(&self.preimage_offset.to_be_bytes());
-        witness[68..72].copy_from_slice(&self.pc.to_be_bytes());
-        witness[72..76].copy_from_slice(&self.next_pc.to_be_bytes());
-        witness[76..80].copy_from_slice(&self.lo.to_be_bytes());
-        witness[80..84].copy_from_slice(&self.hi.to_be_bytes());
-        witness[84..88].copy_from_slice(&self.heap.to_be_bytes());
-        witness[88] = self.exit_code;
-        witness[89] = self.exited as u8;
-        witness[90..98].copy_from_slice(&self.step.to_be_bytes());
-        for (i, r) in self.registers.iter().enumerate() {
-            let start = 98 + i * 4;
-            witness[start..start + 4].copy_from_slice(&r.to_be_bytes());
+        Ok(self.witness_state()?.encode())
+    }
+
+    /// Collects the fields of the [State] that its [StateWitness] commits to, without encoding
+    /// them.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the [WitnessState] or an error if the memory root could not be
+    ///   computed.
+    pub fn witness_state(&mut self) -> Result<WitnessState> {
+        Ok(WitnessState {
+            memory_root: self.memory_root()?,
+            preimage_key: self.preimage_key,
+            preimage_offset: self.preimage_offset,
+            pc: self.pc,
+            next_pc: self.next_pc,
+            lo: self.lo,
+            hi: self.hi,
+            heap: self.heap,
+            exit_code: self.exit_code,
+            exited: self.exited,
+            step: self.step,
+            registers: self.registers,
+        })
+    }
+
+    /// Embeds the keccak256 checksum of the [State]'s [StateWitness] into the [State], to be
+    /// verified with [State::verify_checksum] when the written state is loaded again.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the checksum was computed successfully.
+    pub fn seal(&mut self) -> Result<()> {
+        self.checksum = Some(*keccak256(self.encode_witness()?));
+        Ok(())
+    }
+
+    /// Verifies the checksum embedded by [State::seal], if any, against the [State]'s
+    /// [StateWitness]. This merkleizes the whole [Memory], which loads every page of a
+    /// memory-mapped state.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing a [CannonError::ChecksumMismatch] if the state does not
+    ///   match its checksum.
+    pub fn verify_checksum(&mut self) -> CannonResult<()> {
+        let Some(expected) = self.checksum else {
+            return Ok(());
+        };
+        let actual = *keccak256(self.encode_witness()?);
+        if actual != expected {
+            return Err(CannonError::ChecksumMismatch { expected, actual });
         }
-        Ok(witness)
+        Ok(())
     }
 
-    /// Return the [VMStatus] given `exited` and `exit_code` statuses.
-    pub fn vm_status(exited: bool, exit_code: u8) -> VMStatus {
-        if !exited {
-            return VMStatus::Unfinished;
+    /// Returns the merkle root of the [Memory], or the root that was kept when the [State] was
+    /// pruned with [State::prune].
+    pub fn memory_root(&mut self) -> Result<[u8; 32]> {
+        if let Some(root) = self.pruned_memory_root {
+            return Ok(root);
+        }
+
+        let generation = self.memory.generation();
+        match self.hash_cache.memory_root {
+            Some((cached, root)) if cached == generation => Ok(root),
+            _ => {
+                let root = self.memory.merkle_root()?;
+                self.hash_cache.memory_root = Some((generation, root));
+                Ok(root)
+            }
+        }
+    }
+
+    /// Returns the hash of the [State]'s [StateWitness]. The hash is cached, and only recomputed
+    /// once the memory or any field of the witness has changed.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the state hash.
+    pub fn state_hash(&mut self) -> Result<[u8; 32]> {
+        let witness = self.encode_witness()?;
+        match self.hash_cache.state_hash {
+            Some((ref cached, hash)) if *cached == witness => Ok(hash),
+            _ => {
+                let hash = witness.state_hash();
+                self.hash_cache.state_hash = Some((witness, hash));
+                Ok(hash)
+            }
+        }
+    }
+
+    /// Discards the cached memory root and state hash, and invalidates the merkle cache of the
+    /// [Memory]. Only needed after writing to the data of pages directly; writes through the
+    /// [Memory] and changes of the other fields of the [State] are detected on their own.
+    pub fn invalidate(&mut self) {
+        self.hash_cache = StateHashCache::default();
+        self.memory.invalidate_all();
+    }
+
+    /// Returns whether the pages of the [State]'s [Memory] were discarded by [State::prune].
+    pub fn is_pruned(&self) -> bool {
+        self.pruned_memory_root.is_some()
+    }
+
+    /// Prunes the [State] of an exited guest by discarding every page of its [Memory], keeping
+    /// only the memory's merkle root. The pruned [State] encodes the same [StateWitness] as
+    /// before, which is all that is needed to reproduce the final claim, but its memory can no
+    /// longer be read or proven.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the state was pruned, or an error if the guest has not
+    ///   exited.
+    pub fn prune(&mut self) -> Result<()> {
+        if !self.exited {
+            anyhow::bail!("Cannot prune the state of a guest that has not exited");
         }
 
-        match exit_code {
-            0 => VMStatus::Valid,
-            1 => VMStatus::Invalid,
-            _ => VMStatus::Panic,
+        let root = self.memory_root()?;
+        self.memory = Memory::with_hasher(self.memory.hasher());
+        self.pruned_memory_root = Some(root);
+        Ok(())
+    }
+
+    /// Enables emulation of the `mmap`, `munmap` and `brk` syscalls with Linux semantics, backed
+    /// by an [AddressSpace]. All currently allocated pages, the region between the heap start and
+    /// the heap pointer, and a stack reservation below the stack pointer are marked as mapped.
+    ///
+    /// This is an [extension](State#extensions).
+    ///
+    /// ### Takes
+    /// - `heap_start`: The address that the heap started at, and that `mmap` places new mappings
+    ///   from.
+    pub fn enable_address_space(&mut self, heap_start: u32) {
+        let mut space = AddressSpace::new(heap_start);
+        for page_index in self.memory.page_indices() {
+            let start = page_index << page::PAGE_ADDRESS_SIZE;
+            space.reserve(start, start + page::PAGE_SIZE as u64);
         }
+        space.reserve(heap_start as u64, self.heap as u64);
+        let (stack_start, stack_end) = address_space::stack_reservation(self.registers[29]);
+        space.reserve(stack_start, stack_end);
+        self.address_space = Some(space);
+    }
+
+    /// Enables the `open`, `openat`, `read`, `lseek` and `close` syscalls for the whitelisted
+    /// [crate::DEFAULT_VIRTUAL_FILES], backed by an [FdTable]. Further files may be
+    /// whitelisted with [FdTable::insert_file].
+    ///
+    /// This is an [extension](State#extensions).
+    pub fn enable_virtual_files(&mut self) -> &mut FdTable {
+        self.virtual_files.insert(FdTable::new())
+    }
+
+    /// Enables the `getrandom` syscall, serving deterministic bytes from the given
+    /// [EntropySource].
+    ///
+    /// This is an [extension](State#extensions).
+    pub fn enable_entropy(&mut self, source: EntropySource) {
+        self.entropy = Some(source);
+    }
+
+    /// Enables the `clock_gettime` and `gettimeofday` syscalls, serving a time derived from the
+    /// step counter by the given [Clock].
+    ///
+    /// This is an [extension](State#extensions).
+    pub fn enable_clock(&mut self, clock: Clock) {
+        self.clock = Some(clock);
+    }
+
+    /// Enables the MIPS32r2 bit-manipulation instructions that LLVM emits for Rust guests:
+    /// `seb`, `seh`, `wsbh`, `ext`, `ins`, `rotr` and `rotrv`.
+    ///
+    /// The `MIPS` contract rejects the `SPECIAL3` instructions and executes `rotr` and `rotrv` as
+    /// `srl` and `srlv`, so this is an [extension](State#extensions).
+    pub fn enable_mips32r2(&mut self) {
+        self.mips32r2 = true;
+    }
+
+    /// Enables the `futex` syscall with the degenerate semantics of a single-threaded guest:
+    /// waits fail with `EAGAIN` if the futex word changed, time out if bounded, and otherwise
+    /// return immediately, while wakes find no waiters.
+    ///
+    /// This is an [extension](State#extensions).
+    pub fn enable_futex(&mut self) {
+        self.futex = true;
+    }
+
+    /// Enables strict page protection: guest stores and syscalls that write to a page without
+    /// write permission, and instruction fetches from a page without execute permission, fault
+    /// with a [crate::CannonError::ProtectionFault] instead of silently succeeding.
+    ///
+    /// Writes performed outside of the emulator, such as by [crate::patch_go], are not checked.
+    ///
+    /// ### Takes
+    /// - `protection`: The [PageProtection] table, usually built with
+    ///   [PageProtection::from_elf].
+    pub fn enable_protection(&mut self, protection: PageProtection) {
+        self.protection = Some(protection);
+    }
+
+    /// Names the range `[start, end)` of the guest's address space in the [MemoryRegions] of the
+    /// [State], e.g. to attribute faults to the [crate::STACK_REGION] or [crate::HEAP_REGION].
+    ///
+    /// ### Takes
+    /// - `name`: The name of the region.
+    /// - `start`: The start address of the region.
+    /// - `end`: The exclusive end address of the region.
+    pub fn annotate_region(&mut self, name: impl Into<String>, start: u64, end: u64) {
+        self.regions
+            .get_or_insert_with(MemoryRegions::default)
+            .annotate(name, start, end);
+    }
+
+    /// Returns the most recently annotated [MemoryRegion] containing the given address, if any.
+    pub fn region_of(&self, address: Address) -> Option<&MemoryRegion> {
+        self.regions.as_ref()?.region_of(address)
+    }
+
+    /// Guards the page directly below the stack reservation of the current stack pointer: any
+    /// guest access to it aborts the run with a [crate::CannonError::StackOverflow] instead of
+    /// silently growing the stack into whatever lies below.
+    ///
+    /// Runs that never touch the guard page are unaffected, but states with a stack guard
+    /// enabled abort where the `MIPS` contract would continue.
+    pub fn enable_stack_guard(&mut self) {
+        let (stack_start, _) = address_space::stack_reservation(self.registers[29]);
+        let guard = stack_start.saturating_sub(page::PAGE_SIZE as u64) as Address;
+        self.regions
+            .get_or_insert_with(MemoryRegions::default)
+            .set_stack_guard(guard);
+    }
+
+    /// Returns the [VMStatus] of the [State], as encoded in the first byte of its state hash.
+    pub fn status(&self) -> VMStatus {
+        Self::vm_status(self.exited, self.exit_code)
+    }
+
+    /// Return the [VMStatus] given `exited` and `exit_code` statuses.
+    pub fn vm_status(exited: bool, exit_code: u8) -> VMStatus {
+        VMStatus::from_exit(exited, exit_code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prune_exited() {
+        let mut state = State::default();
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+        state.memory.set_memory(0x7FFF_0000, 0xCAFEBABE).unwrap();
+        assert!(state.prune().is_err());
+
+        state.exited = true;
+        state.exit_code = 1;
+        let witness = state.encode_witness().unwrap();
+        state.prune().unwrap();
+        assert!(state.is_pruned());
+        assert_eq!(state.memory.page_count(), 0);
+        assert_eq!(state.encode_witness().unwrap(), witness);
+
+        // The kept root survives serialization, and pruning again is a no-op.
+        let mut loaded: State =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(loaded.encode_witness().unwrap(), witness);
+        loaded.prune().unwrap();
+        assert_eq!(loaded.encode_witness().unwrap(), witness);
+    }
+
+    #[test]
+    fn cached_state_hash() {
+        let mut state = State::default();
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+        let hash = state.state_hash().unwrap();
+        let generation = state.memory.generation();
+        assert_eq!(state.state_hash().unwrap(), hash);
+        assert_eq!(state.memory.generation(), generation);
+
+        // Changes of the registers and of the memory are picked up.
+        state.registers[2] = 1;
+        let with_register = state.state_hash().unwrap();
+        assert_ne!(with_register, hash);
+        state.memory.set_memory(0x1000, 0).unwrap();
+        assert_ne!(state.memory.generation(), generation);
+        assert_ne!(state.state_hash().unwrap(), with_register);
+        assert_eq!(
+            state.state_hash().unwrap(),
+            state.encode_witness().unwrap().state_hash()
+        );
+
+        // Writes that bypass the memory are picked up once the state is invalidated.
+        let hash = state.state_hash().unwrap();
+        let page = state.memory.page_lookup(1).unwrap();
+        page.borrow_mut().data[0] = 0xFF;
+        assert_eq!(state.state_hash().unwrap(), hash);
+        state.invalidate();
+        let mut expected = State::default();
+        expected.registers[2] = 1;
+        expected.memory.set_memory(0x1000, 0xFF000000).unwrap();
+        assert_eq!(state.state_hash().unwrap(), expected.state_hash().unwrap());
+    }
+
+    #[test]
+    fn checksum() {
+        let mut state = State {
+            pc: 0x1000,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0xDEADBEEF).unwrap();
+        state.verify_checksum().unwrap();
+
+        state.seal().unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        State::from_json(json.as_bytes())
+            .unwrap()
+            .verify_checksum()
+            .unwrap();
+
+        // A state that was modified after it was sealed is rejected.
+        let mut corrupted =
+            State::from_json(json.replace("\"pc\":4096", "\"pc\":4100").as_bytes()).unwrap();
+        assert!(matches!(
+            corrupted.verify_checksum(),
+            Err(CannonError::ChecksumMismatch { .. })
+        ));
     }
 }
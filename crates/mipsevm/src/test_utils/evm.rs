@@ -1,15 +1,30 @@
 //! This module contains a wrapper around a [revm] inspector with an in-memory backend
 //! that has the MIPS & PreimageOracle smart contracts deployed at deterministic addresses.
 
-use crate::{StateWitness, StateWitnessHasher, StepWitness};
+use super::lpp::{
+    addLeavesLPPCall, challengePeriodCall, initLPPCall, squeezeLPPCall, LargePreimageProposal,
+    MIN_BOND_SIZECall, KECCAK_RATE,
+};
+use crate::{CannonError, CannonResult, StateWitness, StateWitnessHasher, StepWitness};
+use alloy_sol_types::{sol, SolCall};
 use anyhow::Result;
+use preimage_oracle::KeyType;
 use revm::{
     db::{CacheDB, EmptyDB},
+    inspectors::TracerEip3155,
     primitives::{
-        hex, AccountInfo, Address, Bytecode, Bytes, CreateScheme, Output, ResultAndState,
-        TransactTo, TxEnv, B256, U256,
+        hex, Account, AccountInfo, Address, Bytecode, Bytes, CreateScheme, ExecutionResult, Output,
+        ResultAndState, SpecId, TransactTo, TxEnv, B256, KECCAK_EMPTY, U256,
     },
-    Database, EVM,
+    Database, DatabaseCommit, EVM,
+};
+use rustc_hash::FxHashSet;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 /// The address of the deployed MIPS VM on the in-memory EVM.
@@ -22,12 +37,163 @@ pub const MIPS_CREATION_CODE: &str = include_str!("../../bindings/mips_creation.
 /// The deployed EVM bytecode of the PreimageOracle contract.
 pub const PREIMAGE_ORACLE_DEPLOYED_CODE: &str =
     include_str!("../../bindings/preimage_oracle_deployed.bin");
+/// The manifest of the embedded bytecode, written by `bindings/bindings.sh` along with it.
+pub const BINDINGS_MANIFEST: &str = include_str!("../../bindings/manifest.json");
+
+/// The [BindingsManifest] records the keccak256 hashes of the embedded bytecode of the MIPS and
+/// `PreimageOracle` contracts, and the Optimism monorepo commit that it was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingsManifest {
+    /// The commit of the Optimism monorepo that the bytecode was compiled from, if recorded.
+    pub optimism_rev: Option<String>,
+    /// The keccak256 hash of [MIPS_CREATION_CODE].
+    pub mips_creation_hash: B256,
+    /// The keccak256 hash of [PREIMAGE_ORACLE_DEPLOYED_CODE].
+    pub preimage_oracle_deployed_hash: B256,
+}
+
+/// Checks that the embedded bytecode of the MIPS and `PreimageOracle` contracts matches the
+/// hashes in the [BINDINGS_MANIFEST], so that bytecode which drifted from the manifest, e.g. by
+/// being regenerated or edited without `bindings/bindings.sh`, is detected.
+///
+/// ### Returns
+/// - `Ok(manifest)` if the embedded bytecode matches the manifest.
+/// - `Err(_)` if the manifest is malformed, or the bytecode does not match it.
+pub fn verify_bindings() -> Result<BindingsManifest> {
+    let manifest: BindingsManifest = serde_json::from_str(BINDINGS_MANIFEST)?;
+    for (name, code, expected) in [
+        (
+            "mips_creation.bin",
+            MIPS_CREATION_CODE,
+            manifest.mips_creation_hash,
+        ),
+        (
+            "preimage_oracle_deployed.bin",
+            PREIMAGE_ORACLE_DEPLOYED_CODE,
+            manifest.preimage_oracle_deployed_hash,
+        ),
+    ] {
+        let actual = revm::primitives::keccak256(hex::decode(code.trim())?);
+        anyhow::ensure!(
+            actual == expected,
+            "The embedded {} has hash {}, but the bindings manifest expects {}; regenerate the bindings with `bindings/bindings.sh`",
+            name,
+            actual,
+            expected
+        );
+    }
+    Ok(manifest)
+}
+
+/// The default [SpecId] of the in-memory EVM, matching the hardfork that the MIPS & PreimageOracle
+/// contracts are deployed under on mainnet.
+pub const DEFAULT_SPEC_ID: SpecId = SpecId::CANCUN;
+
+sol! {
+    /// `PreimageOracle` readPreimage function.
+    function readPreimage(bytes32,uint256) external view returns (bytes32,uint256);
+}
+
+/// The minimum size of a large preimage proposal, in bytes, of the `PreimageOracle` on mainnet.
+pub const DEFAULT_MIN_PROPOSAL_SIZE: u64 = 126_000;
+/// The challenge period of large preimage proposals, in seconds, of the `PreimageOracle` on
+/// mainnet.
+pub const DEFAULT_CHALLENGE_PERIOD: u64 = 86_400;
+
+/// The environment variable holding the path of a `PreimageOracle.json` artifact, for tests of
+/// `PreimageOracle` features that the bundled bytecode predates. Such tests are ignored by default,
+/// and run with `cargo test -- --ignored` once the variable is set.
+pub const ORACLE_ARTIFACT_ENV: &str = "CANNON_PREIMAGE_ORACLE_ARTIFACT";
+
+/// The [OracleConfig] describes a variant of the `PreimageOracle` contract to deploy with
+/// [MipsEVM::try_init_with_config], along with its constructor parameters.
+#[derive(Debug, Clone)]
+pub struct OracleConfig {
+    /// The creation bytecode of the `PreimageOracle`, without constructor arguments.
+    pub creation_code: Bytes,
+    /// The minimum size of a large preimage proposal, in bytes.
+    pub min_proposal_size: U256,
+    /// The challenge period of large preimage proposals, in seconds.
+    pub challenge_period: U256,
+}
+
+impl OracleConfig {
+    /// Creates a new [OracleConfig] for the given creation bytecode, with the constructor
+    /// parameters of the mainnet deployment.
+    pub fn new(creation_code: impl Into<Bytes>) -> Self {
+        Self {
+            creation_code: creation_code.into(),
+            min_proposal_size: U256::from(DEFAULT_MIN_PROPOSAL_SIZE),
+            challenge_period: U256::from(DEFAULT_CHALLENGE_PERIOD),
+        }
+    }
+
+    /// Reads the creation bytecode from a `PreimageOracle.json` artifact produced by `forge
+    /// build`, with the constructor parameters of the mainnet deployment.
+    ///
+    /// ### Takes
+    /// - `path`: The path of the artifact.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the [OracleConfig].
+    pub fn from_artifact(path: impl AsRef<Path>) -> CannonResult<Self> {
+        let artifact: serde_json::Value =
+            serde_json::from_slice(&fs::read(path).map_err(anyhow::Error::from)?)
+                .map_err(anyhow::Error::from)?;
+        let object = artifact["bytecode"]["object"]
+            .as_str()
+            .ok_or(anyhow::anyhow!("Missing `bytecode.object` in artifact"))?;
+        let code = hex::decode(object).map_err(anyhow::Error::from)?;
+        Ok(Self::new(code))
+    }
+
+    /// Reads the creation bytecode from the artifact at the path in [ORACLE_ARTIFACT_ENV]. See
+    /// [OracleConfig::from_artifact].
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the [OracleConfig], or an error if the variable is not set.
+    pub fn from_env() -> CannonResult<Self> {
+        let path = std::env::var(ORACLE_ARTIFACT_ENV).map_err(|_| {
+            anyhow::anyhow!(
+                "{} must point at a PreimageOracle.json artifact",
+                ORACLE_ARTIFACT_ENV
+            )
+        })?;
+        Self::from_artifact(path)
+    }
+
+    /// Sets the minimum size of a large preimage proposal, in bytes.
+    pub fn with_min_proposal_size(mut self, min_proposal_size: u64) -> Self {
+        self.min_proposal_size = U256::from(min_proposal_size);
+        self
+    }
+
+    /// Sets the challenge period of large preimage proposals, in seconds.
+    pub fn with_challenge_period(mut self, challenge_period: u64) -> Self {
+        self.challenge_period = U256::from(challenge_period);
+        self
+    }
+
+    /// Returns the creation bytecode followed by the ABI encoded constructor arguments.
+    fn encode_creation_input(&self) -> Bytes {
+        let mut input = self.creation_code.to_vec();
+        input.extend_from_slice(&self.min_proposal_size.to_be_bytes::<32>());
+        input.extend_from_slice(&self.challenge_period.to_be_bytes::<32>());
+        input.into()
+    }
+}
 
 /// A wrapper around a [revm] inspector with an in-memory backend that has the MIPS & PreimageOracle
 /// smart contracts deployed at deterministic addresses. This is used for differential testing the
 /// implementation of the MIPS VM in this crate against the smart contract implementations.
+///
+/// The backend defaults to an in-memory [CacheDB], but any [Database] that supports
+/// [DatabaseCommit] can be supplied through [MipsEVM::with_database].
 pub struct MipsEVM<DB: Database> {
     pub inner: EVM<DB>,
+    /// The path to write an EIP-3155 trace of a failing step to, if any.
+    pub trace_path: Option<PathBuf>,
 }
 
 impl Default for MipsEVM<CacheDB<EmptyDB>> {
@@ -37,67 +203,160 @@ impl Default for MipsEVM<CacheDB<EmptyDB>> {
 }
 
 impl MipsEVM<CacheDB<EmptyDB>> {
-    /// Creates a new MIPS EVM with an in-memory backend.
+    /// Creates a new MIPS EVM with an in-memory backend, running under [DEFAULT_SPEC_ID].
     pub fn new() -> Self {
-        let mut evm = EVM::default();
-        evm.database(CacheDB::default());
+        Self::with_database(CacheDB::default())
+    }
+}
 
-        Self { inner: evm }
+impl<DB> MipsEVM<DB>
+where
+    DB: Database + DatabaseCommit,
+    DB::Error: fmt::Debug,
+{
+    /// Creates a new MIPS EVM backed by the given database, running under [DEFAULT_SPEC_ID], e.g.
+    /// to step against a forked, instrumented or persistent database. The contracts are deployed
+    /// into the database by [MipsEVM::try_init].
+    ///
+    /// ### Takes
+    /// - `db`: The database backend of the EVM.
+    ///
+    /// ### Returns
+    /// - The [MipsEVM] backed by the database.
+    pub fn with_database(db: DB) -> Self {
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = DEFAULT_SPEC_ID;
+
+        Self {
+            inner: evm,
+            trace_path: None,
+        }
+    }
+
+    /// Sets the [SpecId] that the in-memory EVM executes under.
+    ///
+    /// ### Takes
+    /// - `spec_id`: The [SpecId] of the hardfork to execute under.
+    ///
+    /// ### Returns
+    /// - The [MipsEVM] with the spec set.
+    pub fn with_spec_id(mut self, spec_id: SpecId) -> Self {
+        self.inner.env.cfg.spec_id = spec_id;
+        self
+    }
+
+    /// Returns the [SpecId] that the in-memory EVM executes under.
+    pub fn spec_id(&self) -> SpecId {
+        self.inner.env.cfg.spec_id
+    }
+
+    /// Sets the path that an EIP-3155 opcode trace of the `MIPS` contract is written to when a
+    /// call to [MipsEVM::step] fails.
+    ///
+    /// ### Takes
+    /// - `trace_path`: The path of the trace file.
+    ///
+    /// ### Returns
+    /// - The [MipsEVM] with the trace path set.
+    pub fn with_trace_path(mut self, trace_path: impl Into<PathBuf>) -> Self {
+        self.trace_path = Some(trace_path.into());
+        self
     }
 
     /// Initializes the EVM with the MIPS contracts deployed.
     ///
     /// ### Returns
-    /// - A [Result] indicating whether the initialization was successful.
-    pub fn try_init(&mut self) -> Result<()> {
-        let db = self.inner.db().ok_or(anyhow::anyhow!("Missing database"))?;
+    /// - A [CannonResult] indicating whether the initialization was successful.
+    pub fn try_init(&mut self) -> CannonResult<()> {
+        verify_bindings()?;
+        self.fund_caller()?;
+
+        // Deploy the PreimageOracle contract.
+        self.deploy_contract(
+            Address::from_slice(PREIMAGE_ORACLE_ADDR.as_slice()),
+            Bytes::from(hex::decode(PREIMAGE_ORACLE_DEPLOYED_CODE).map_err(anyhow::Error::from)?),
+        )?;
+
+        self.deploy_mips()
+    }
+
+    /// Initializes the EVM with the MIPS contract and a variant of the `PreimageOracle` deployed,
+    /// created with the constructor parameters of the given [OracleConfig], e.g. to match the
+    /// minimum proposal size and challenge period of a specific network's deployment.
+    ///
+    /// ### Takes
+    /// - `config`: The [OracleConfig] of the `PreimageOracle` variant to deploy.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the initialization was successful.
+    pub fn try_init_with_config(&mut self, config: OracleConfig) -> CannonResult<()> {
+        verify_bindings()?;
+        self.fund_caller()?;
+
+        // As with the MIPS contract, run the creation code so that the constructor fills in the
+        // immutable parameters, and then deploy the resulting code to the test address.
+        let code = self.create(
+            config.encode_creation_input(),
+            "Failed to deploy PreimageOracle contract",
+        )?;
+        self.deploy_contract(Address::from_slice(PREIMAGE_ORACLE_ADDR.as_slice()), code)?;
+
+        self.deploy_mips()
+    }
 
-        // Fund the zero address.
-        db.insert_account_info(
+    /// Funds the zero address, which all transactions are sent from.
+    fn fund_caller(&mut self) -> CannonResult<()> {
+        self.commit_account(
             Address::ZERO,
             AccountInfo {
                 balance: U256::from(u128::MAX),
                 nonce: 0,
-                code_hash: B256::ZERO,
+                code_hash: KECCAK_EMPTY,
                 code: None,
             },
-        );
-
-        // Deploy the PreimageOracle contract.
-        self.deploy_contract(
-            Address::from_slice(PREIMAGE_ORACLE_ADDR.as_slice()),
-            Bytes::from(hex::decode(PREIMAGE_ORACLE_DEPLOYED_CODE)?),
         )?;
+        Ok(())
+    }
 
+    /// Deploys the MIPS contract, pointed at the `PreimageOracle` at [PREIMAGE_ORACLE_ADDR].
+    fn deploy_mips(&mut self) -> CannonResult<()> {
         // Deploy the MIPS contract prior to deploying it manually. This contract has an immutable
         // variable, so we let the creation code fill this in for us, and then deploy it to the
         // test address.
         let encoded_preimage_addr =
             Address::from_slice(PREIMAGE_ORACLE_ADDR.as_slice()).into_word();
-        let mips_creation_heap = hex::decode(MIPS_CREATION_CODE)?
+        let mips_creation_heap = hex::decode(MIPS_CREATION_CODE)
+            .map_err(anyhow::Error::from)?
             .into_iter()
             .chain(encoded_preimage_addr)
             .collect::<Vec<_>>();
-        self.fill_tx_env(
-            TransactTo::Create(CreateScheme::Create),
-            mips_creation_heap.into(),
-        );
-        if let Ok(ResultAndState {
-            result:
-                revm::primitives::ExecutionResult::Success {
-                    reason: _,
-                    gas_used: _,
-                    gas_refunded: _,
-                    logs: _,
-                    output: Output::Create(code, _),
-                },
-            state: _,
-        }) = self.inner.transact_ref()
-        {
-            // Deploy the MIPS contract manually.
-            self.deploy_contract(Address::from_slice(MIPS_ADDR.as_slice()), code)
-        } else {
-            anyhow::bail!("Failed to deploy MIPS contract");
+        let code = self.create(mips_creation_heap.into(), "Failed to deploy MIPS contract")?;
+
+        // Deploy the MIPS contract manually.
+        Ok(self.deploy_contract(Address::from_slice(MIPS_ADDR.as_slice()), code)?)
+    }
+
+    /// Executes the given creation code without committing it, returning the deployed code.
+    ///
+    /// ### Takes
+    /// - `creation_code`: The creation code, followed by the ABI encoded constructor arguments.
+    /// - `context`: A description of the deployment, for errors.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the code returned by the creation code.
+    fn create(&mut self, creation_code: Bytes, context: &str) -> CannonResult<Bytes> {
+        self.fill_tx_env(TransactTo::Create(CreateScheme::Create), creation_code);
+        let ResultAndState { result, state: _ } = self
+            .inner
+            .transact()
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        match result {
+            ExecutionResult::Success {
+                output: Output::Create(code, _),
+                ..
+            } => Ok(code),
+            result => Err(execution_error(result, context)),
         }
     }
 
@@ -108,17 +367,156 @@ impl MipsEVM<CacheDB<EmptyDB>> {
     /// - `witness`: The [StepWitness] containing the VM state to step.
     ///
     /// ### Returns
-    /// - A [Result] containing the post-state hash of the MIPS VM or an error returned during
-    /// execution.
-    pub fn step(&mut self, witness: StepWitness) -> Result<StateWitness> {
-        if witness.has_preimage() {
-            crate::debug!(
-                target: "mipsevm::evm",
-                "Reading preimage key {:x} at offset {:?}",
-                B256::from(witness.preimage_key.ok_or(anyhow::anyhow!("Missing preimage key"))?),
-                witness.preimage_offset
-            );
+    /// - A [CannonResult] containing the post-state hash of the MIPS VM or an error returned
+    /// during execution.
+    pub fn step(&mut self, witness: StepWitness) -> CannonResult<StateWitness> {
+        let _span = crate::traces::debug_span!(
+            target: "mipsevm::evm",
+            "evm_step",
+            step = crate::StateWitnessFields::step(&witness.state)
+        );
+
+        self.load_witness_preimage(&witness)?;
 
+        crate::traces::debug!(target: "mipsevm::evm", "Performing EVM step");
+
+        let step_input = witness.encode_step_input();
+        self.fill_tx_env(TransactTo::Call(MIPS_ADDR.into()), step_input);
+
+        let result = self.transact_step();
+        if result.is_err() {
+            self.write_failure_trace()?;
+        }
+        result
+    }
+
+    /// Performs the instruction steps of a batch of [StepWitness]es on the MIPS smart contract,
+    /// in order. The contracts are only deployed if the EVM was not initialized yet, the
+    /// transaction environment of the step calls is built once, and every preimage part is only
+    /// loaded into the `PreimageOracle` once, which makes differential tests of long traces
+    /// considerably faster than calling [MipsEVM::step] per witness.
+    ///
+    /// ### Takes
+    /// - `witnesses`: The [StepWitness]es to step, in execution order.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the [EvmBatchReport] of the batch, or the error of the first
+    ///   failing step.
+    pub fn step_batch(
+        &mut self,
+        witnesses: impl IntoIterator<Item = StepWitness>,
+    ) -> CannonResult<EvmBatchReport> {
+        if !self.is_initialized() {
+            self.try_init()?;
+        }
+
+        let mut report = EvmBatchReport::default();
+        let mut loaded = FxHashSet::default();
+        let mut tx_env_stale = true;
+        for witness in witnesses {
+            if let (Some(key), Some(offset)) = (witness.preimage_key, witness.preimage_offset) {
+                if loaded.insert((key, offset)) {
+                    let start = Instant::now();
+                    self.load_witness_preimage(&witness)?;
+                    report.oracle_time += start.elapsed();
+                    report.preimages_loaded += 1;
+                    tx_env_stale = true;
+                }
+            }
+
+            if tx_env_stale {
+                self.fill_tx_env(
+                    TransactTo::Call(MIPS_ADDR.into()),
+                    witness.encode_step_input(),
+                );
+                tx_env_stale = false;
+            } else {
+                self.inner.env.tx.data = witness.encode_step_input();
+            }
+
+            let start = Instant::now();
+            let post_state = match self.transact_step() {
+                Ok(post_state) => post_state,
+                Err(e) => {
+                    crate::traces::warn!(target: "mipsevm::evm", "EVM step {} of the batch failed", report.post_states.len());
+                    self.write_failure_trace()?;
+                    return Err(e);
+                }
+            };
+            report.step_time += start.elapsed();
+            report.post_states.push(post_state);
+        }
+
+        crate::traces::debug!(target: "mipsevm::evm", "{}", report);
+        Ok(report)
+    }
+
+    /// Writes the opcode trace of the last, failed step call to the [MipsEVM::trace_path], if set.
+    fn write_failure_trace(&mut self) -> CannonResult<()> {
+        if let Some(trace_path) = self.trace_path.clone() {
+            let file = File::create(&trace_path).map_err(anyhow::Error::from)?;
+            self.trace_last_call(file)?;
+            crate::traces::warn!(target: "mipsevm::evm", "EVM step failed, wrote opcode trace to {}", trace_path.display());
+        }
+        Ok(())
+    }
+
+    /// Returns whether the MIPS contract is deployed, i.e. [MipsEVM::try_init] succeeded.
+    pub fn is_initialized(&mut self) -> bool {
+        self.inner
+            .db()
+            .and_then(|db| db.basic(Address::from(MIPS_ADDR)).ok().flatten())
+            .is_some_and(|info| info.code_hash != KECCAK_EMPTY && info.code_hash != B256::ZERO)
+    }
+
+    /// Loads the preimage part read by the step of the [StepWitness] into the `PreimageOracle`
+    /// contract, if any.
+    ///
+    /// ### Takes
+    /// - `witness`: The [StepWitness] of the step.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the part was loaded successfully.
+    fn load_witness_preimage(&mut self, witness: &StepWitness) -> CannonResult<()> {
+        if !witness.has_preimage() {
+            return Ok(());
+        }
+
+        crate::traces::debug!(
+            target: "mipsevm::evm",
+            "Reading preimage key {:x} at offset {:?}",
+            B256::from(witness.preimage_key.ok_or(anyhow::anyhow!("Missing preimage key"))?),
+            witness.preimage_offset
+        );
+
+        let key = witness
+            .preimage_key
+            .ok_or(anyhow::anyhow!("Missing preimage key"))?;
+        let offset = witness
+            .preimage_offset
+            .ok_or(anyhow::anyhow!("Missing preimage offset"))?;
+        let large_preimage = key[0] == KeyType::GlobalKeccak as u8
+            && witness
+                .preimage_value
+                .as_ref()
+                .is_some_and(|value| value.len() > KECCAK_RATE + 8);
+
+        let proposals_supported = large_preimage && self.large_preimage_bond()?.is_some();
+        if large_preimage && !proposals_supported {
+            crate::traces::warn!(target: "mipsevm::evm", "The deployed PreimageOracle does not support large preimage proposals; loading the part of preimage {:x} directly", B256::from(key));
+        }
+
+        if proposals_supported {
+            // Parts of large preimages are loaded through the large preimage proposal flow, once
+            // per offset.
+            if self.read_preimage(key, offset).is_err() {
+                let value = witness
+                    .preimage_value
+                    .as_ref()
+                    .ok_or(anyhow::anyhow!("Missing preimage value"))?;
+                self.load_large_preimage_part(&value[8..], offset)?;
+            }
+        } else {
             let preimage_oracle_input =
                 witness
                     .encode_preimage_oracle_input()
@@ -129,67 +527,284 @@ impl MipsEVM<CacheDB<EmptyDB>> {
                 TransactTo::Call(PREIMAGE_ORACLE_ADDR.into()),
                 preimage_oracle_input,
             );
-            self.inner.transact_commit().map_err(|_| {
-                anyhow::anyhow!("Failed to commit preimage to PreimageOracle contract")
+            let result = self.inner.transact_commit().map_err(|e| {
+                CannonError::EvmFailure(format!(
+                    "Failed to commit preimage to PreimageOracle contract: {:?}",
+                    e
+                ))
             })?;
+            if !result.is_success() {
+                return Err(execution_error(
+                    result,
+                    "Failed to commit preimage to PreimageOracle contract",
+                ));
+            }
         }
+        Ok(())
+    }
+
+    /// Commits a call to the `PreimageOracle` contract that loads a preimage part, e.g. the
+    /// calldata produced by [crate::encode_keccak256_preimage_part].
+    ///
+    /// ### Takes
+    /// - `input`: The ABI encoded calldata of the call.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the part was loaded successfully.
+    pub fn load_preimage_part(&mut self, input: Bytes) -> CannonResult<()> {
+        self.commit_oracle_call(input, U256::ZERO, "Failed to load preimage part")
+    }
 
-        crate::debug!(target: "mipsevm::evm", "Performing EVM step");
+    /// Reads a preimage part that was loaded into the `PreimageOracle` contract.
+    ///
+    /// ### Takes
+    /// - `key`: The type-prefixed preimage key.
+    /// - `offset`: The offset into the length-prefixed preimage.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the part and the number of bytes of it that lie within the
+    ///   length-prefixed preimage.
+    pub fn read_preimage(&mut self, key: [u8; 32], offset: u32) -> CannonResult<([u8; 32], u32)> {
+        let call = readPreimageCall {
+            _0: key.into(),
+            _1: alloy_primitives::U256::from(offset),
+        };
+        let output = self.oracle_view_call(call.abi_encode(), "Failed to read preimage part")?;
+        let returns = readPreimageCall::abi_decode_returns(&output, true)
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        Ok((returns._0.0, returns._1.to::<u32>()))
+    }
 
-        let step_input = witness.encode_step_input();
-        self.fill_tx_env(TransactTo::Call(MIPS_ADDR.into()), step_input);
-        if let Ok(ResultAndState {
-            result:
-                revm::primitives::ExecutionResult::Success {
-                    reason: _,
-                    gas_used: _,
-                    gas_refunded: _,
-                    logs,
-                    output: Output::Call(output),
-                },
-            state: _,
-        }) = self.inner.transact_ref()
-        {
-            let output = B256::from_slice(&output);
-
-            crate::debug!(target: "mipsevm::evm", "EVM step successful with resulting post-state hash: {:x}", output);
-
-            if logs.len() != 1 {
-                anyhow::bail!("Expected 1 log, got {}", logs.len());
+    /// Returns the bond required to propose a large preimage, if the deployed `PreimageOracle`
+    /// supports the large preimage proposal flow.
+    pub fn large_preimage_bond(&mut self) -> CannonResult<Option<U256>> {
+        match self.oracle_view_call(MIN_BOND_SIZECall {}.abi_encode(), "MIN_BOND_SIZE") {
+            Ok(output) => {
+                let bond = MIN_BOND_SIZECall::abi_decode_returns(&output, true)
+                    .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?
+                    ._0;
+                Ok(Some(U256::from_be_bytes(bond.to_be_bytes::<32>())))
             }
+            Err(CannonError::EvmRevert { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 
-            let post_state: StateWitness = logs[0].data.to_vec().as_slice().try_into()?;
+    /// Loads the part of a keccak256 preimage at the given offset into the `PreimageOracle`
+    /// through the large preimage proposal flow: the proposal is initialized, all of its leaves
+    /// are added in one call, and it is squeezed once the challenge period has elapsed.
+    ///
+    /// ### Takes
+    /// - `preimage`: The full preimage, without its length prefix.
+    /// - `offset`: The offset into the length-prefixed preimage.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the part was loaded successfully.
+    pub fn load_large_preimage_part(&mut self, preimage: &[u8], offset: u32) -> CannonResult<()> {
+        let bond = self.large_preimage_bond()?.ok_or(anyhow::anyhow!(
+            "The deployed PreimageOracle does not support large preimage proposals"
+        ))?;
+
+        let proposal = LargePreimageProposal::new(preimage);
+        let uuid = alloy_primitives::U256::from_be_bytes(proposal.digest())
+            ^ alloy_primitives::U256::from(offset);
+
+        let init = initLPPCall {
+            _0: uuid,
+            _1: offset,
+            _2: preimage.len() as u32,
+        };
+        self.commit_oracle_call(init.abi_encode().into(), bond, "Failed to initialize LPP")?;
+
+        let add_leaves = addLeavesLPPCall {
+            _0: uuid,
+            _1: alloy_primitives::U256::ZERO,
+            _2: preimage.to_vec(),
+            _3: proposal.state_commitments(),
+            _4: true,
+        };
+        self.commit_oracle_call(
+            add_leaves.abi_encode().into(),
+            U256::ZERO,
+            "Failed to add LPP leaves",
+        )?;
 
-            if post_state.state_hash().as_slice() != output.as_slice() {
-                anyhow::bail!(
-                    "Post-state hash does not match state hash in log: {:x} != {:x}",
-                    output,
-                    B256::from(post_state.state_hash())
-                );
-            }
+        let output = self.oracle_view_call(
+            challengePeriodCall {}.abi_encode(),
+            "Failed to read the challenge period",
+        )?;
+        let challenge_period = challengePeriodCall::abi_decode_returns(&output, true)
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?
+            ._0;
+        self.inner.env.block.timestamp +=
+            U256::from_be_bytes(challenge_period.to_be_bytes::<32>()) + U256::from(1);
+
+        let (state_matrix, pre_state, pre_proof, post_state, post_proof) = proposal.squeeze_args();
+        let squeeze = squeezeLPPCall {
+            _0: alloy_primitives::Address::ZERO,
+            _1: uuid,
+            _2: state_matrix,
+            _3: pre_state,
+            _4: pre_proof,
+            _5: post_state,
+            _6: post_proof,
+        };
+        self.commit_oracle_call(
+            squeeze.abi_encode().into(),
+            U256::ZERO,
+            "Failed to squeeze LPP",
+        )
+    }
 
-            Ok(post_state)
-        } else {
-            anyhow::bail!("Failed to step MIPS contract");
+    /// Commits a call to the `PreimageOracle` contract.
+    fn commit_oracle_call(&mut self, input: Bytes, value: U256, context: &str) -> CannonResult<()> {
+        self.fill_tx_env(TransactTo::Call(PREIMAGE_ORACLE_ADDR.into()), input);
+        self.inner.env.tx.value = value;
+        let result = self
+            .inner
+            .transact_commit()
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        if !result.is_success() {
+            return Err(execution_error(result, context));
+        }
+        Ok(())
+    }
+
+    /// Executes a call to the `PreimageOracle` contract without committing it, returning its
+    /// output.
+    fn oracle_view_call(&mut self, input: Vec<u8>, context: &str) -> CannonResult<Bytes> {
+        self.fill_tx_env(TransactTo::Call(PREIMAGE_ORACLE_ADDR.into()), input.into());
+        let ResultAndState { result, state: _ } = self
+            .inner
+            .transact()
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        match result {
+            ExecutionResult::Success {
+                output: Output::Call(output),
+                ..
+            } => Ok(output),
+            result => Err(execution_error(result, context)),
+        }
+    }
+
+    /// Executes a call to the given address without committing it, as a `staticcall` from a
+    /// contract would.
+    ///
+    /// ### Takes
+    /// - `address`: The address to call.
+    /// - `input`: The calldata of the call.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing whether the call succeeded and its output.
+    pub fn static_call(&mut self, address: [u8; 20], input: &[u8]) -> CannonResult<(bool, Bytes)> {
+        self.fill_tx_env(
+            TransactTo::Call(address.into()),
+            Bytes::copy_from_slice(input),
+        );
+        let ResultAndState { result, state: _ } = self
+            .inner
+            .transact()
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        match result {
+            ExecutionResult::Success { output, .. } => Ok((true, output.into_data())),
+            ExecutionResult::Revert { output, .. } => Ok((false, output)),
+            ExecutionResult::Halt { .. } => Ok((false, Bytes::new())),
+        }
+    }
+
+    /// Re-executes the last call made to the in-memory EVM with an inspector attached, writing
+    /// its opcode trace in the [EIP-3155](https://eips.ethereum.org/EIPS/eip-3155) format. The
+    /// state of the EVM is not modified.
+    ///
+    /// ### Takes
+    /// - `out`: The writer to write the trace to.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] indicating whether the trace was written successfully.
+    pub fn trace_last_call(&mut self, out: impl Write + 'static) -> CannonResult<()> {
+        let tracer = TracerEip3155::new(Box::new(out), true, true);
+        self.inner
+            .inspect(tracer)
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        Ok(())
+    }
+
+    /// Executes the `MIPS` step call that is currently filled in the transaction environment.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the post-state [StateWitness] emitted by the contract.
+    fn transact_step(&mut self) -> CannonResult<StateWitness> {
+        let ResultAndState { result, state: _ } = self
+            .inner
+            .transact()
+            .map_err(|e| CannonError::EvmFailure(format!("{:?}", e)))?;
+        let ExecutionResult::Success {
+            logs,
+            output: Output::Call(output),
+            ..
+        } = result
+        else {
+            return Err(execution_error(result, "Failed to step MIPS contract"));
+        };
+
+        let output = B256::from_slice(&output);
+
+        crate::traces::debug!(target: "mipsevm::evm", "EVM step successful with resulting post-state hash: {:x}", output);
+
+        if logs.len() != 1 {
+            return Err(CannonError::EvmFailure(format!(
+                "Expected 1 log, got {}",
+                logs.len()
+            )));
+        }
+
+        let post_state: StateWitness = logs[0]
+            .data
+            .to_vec()
+            .as_slice()
+            .try_into()
+            .map_err(anyhow::Error::from)?;
+
+        if post_state.state_hash().as_slice() != output.as_slice() {
+            return Err(CannonError::WitnessMismatch {
+                expected: output.0,
+                actual: post_state.state_hash(),
+            });
         }
+
+        Ok(post_state)
     }
 
     /// Deploys a contract with the given code at the given address.
     ///
     /// ### Takes
-    /// - `db`: The database backend of the MIPS EVM.
     /// - `addr`: The address to deploy the contract to.
     /// - `code`: The code of the contract to deploy.
     pub(crate) fn deploy_contract(&mut self, addr: Address, code: Bytes) -> Result<()> {
-        let mut acc_info = AccountInfo {
-            balance: U256::ZERO,
-            nonce: 0,
-            code_hash: B256::ZERO,
-            code: Some(Bytecode::new_raw(code)),
-        };
+        let code = Bytecode::new_raw(code);
+        self.commit_account(
+            addr,
+            AccountInfo {
+                balance: U256::ZERO,
+                nonce: 0,
+                code_hash: code.hash_slow(),
+                code: Some(code),
+            },
+        )
+    }
+
+    /// Writes an account to the database through [DatabaseCommit], replacing any existing
+    /// account at the address.
+    ///
+    /// ### Takes
+    /// - `addr`: The address of the account.
+    /// - `info`: The [AccountInfo] of the account, including its code, if any.
+    fn commit_account(&mut self, addr: Address, info: AccountInfo) -> Result<()> {
         let db = self.inner.db().ok_or(anyhow::anyhow!("Missing database"))?;
-        db.insert_contract(&mut acc_info);
-        db.insert_account_info(addr, acc_info);
+        let mut account = Account::from(info);
+        account.mark_touch();
+        account.mark_created();
+        db.commit([(addr, account)].into_iter().collect());
         Ok(())
     }
 
@@ -217,21 +832,82 @@ impl MipsEVM<CacheDB<EmptyDB>> {
     }
 }
 
+/// The [EvmBatchReport] summarizes a batch of steps performed by [MipsEVM::step_batch].
+#[derive(Debug, Default, Clone)]
+pub struct EvmBatchReport {
+    /// The post-state emitted by the `MIPS` contract for each step, in order.
+    pub post_states: Vec<StateWitness>,
+    /// The number of preimage parts loaded into the `PreimageOracle`.
+    pub preimages_loaded: usize,
+    /// The time spent loading preimage parts.
+    pub oracle_time: Duration,
+    /// The time spent executing the step calls.
+    pub step_time: Duration,
+}
+
+impl EvmBatchReport {
+    /// Returns the average number of steps executed per second, excluding the time spent loading
+    /// preimage parts.
+    pub fn steps_per_sec(&self) -> f64 {
+        self.post_states.len() as f64 / self.step_time.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for EvmBatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} EVM steps in {:.2?} ({:.0} steps/s), {} preimage parts loaded in {:.2?}",
+            self.post_states.len(),
+            self.step_time,
+            self.steps_per_sec(),
+            self.preimages_loaded,
+            self.oracle_time
+        )
+    }
+}
+
+/// Converts an unsuccessful [ExecutionResult] into a [CannonError].
+///
+/// ### Takes
+/// - `result`: The [ExecutionResult] of the failed call.
+/// - `context`: A description of the call that failed.
+///
+/// ### Returns
+/// - [CannonError::EvmRevert] if the call reverted, otherwise [CannonError::EvmFailure].
+fn execution_error(result: ExecutionResult, context: &str) -> CannonError {
+    match result {
+        ExecutionResult::Revert { output, .. } => CannonError::EvmRevert {
+            data: output.to_vec(),
+        },
+        ExecutionResult::Halt { reason, .. } => {
+            CannonError::EvmFailure(format!("{}: halted with {:?}", context, reason))
+        }
+        ExecutionResult::Success { .. } => {
+            CannonError::EvmFailure(format!("{}: unexpected output", context))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::{
         patch,
-        test_utils::{ClaimTestOracle, StaticOracle, BASE_ADDR_END, END_ADDR},
-        Address, InstrumentedState, Memory, State,
+        test_utils::{open_mips, ClaimTestOracle, StaticOracle, END_ADDR},
+        Address, InstrumentedState, State,
     };
-    use revm::primitives::ExecutionResult;
+    use proptest::{prelude::*, proptest};
     use std::{
         fs,
-        io::{self, BufReader, BufWriter},
-        path::PathBuf,
+        io::{self, BufWriter},
     };
 
+    #[test]
+    fn bindings_match_manifest() {
+        verify_bindings().unwrap();
+    }
+
     #[test]
     fn sanity_evm_execution() {
         const SAMPLE: [u8; 2180] = hex!("f8e0cb960000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000016000000000000000000000000000000000000000000000000000000000000000e22306a30adb7e99858491484b0d6627fe00efea43ec78488033a797a499e22ad6000000000000000000000000000000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000007000e000002000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5b4c11951957c6f8f642c4af61cd6b24640fec6dc7fc607ee8206a99e92410d3021ddb9a356815c3fac1026b6dec5df3124afbadb485c9ba5a3e3398a04b7ba85e58769b32a1beaf1ea27375a44095a0d1fb664ce2dd358e7fcbfb78c26a193440eb01ebfc9ed27500cd4dfc979272d1f0913cc9f66540d7e8005811109e1cf2d887c22bd8750d34016ac3c66b5ff102dacdd73f6b014e710b51e8022af9a1968ffd70157e48063fc33c97a050f7f640233bf646cc98d9524c6b92bcf3ab56f839867cc5f7f196b93bae1e27e6320742445d290f2263827498b54fec539f756afcefad4e508c098b9a7e1d8feb19955fb02ba9675585078710969d3440f5054e0f9dc3e7fe016e050eff260334f18a5d4fe391d82092319f5964f2e2eb7c1c3a5f8b13a49e282f609c317a833fb8d976d11517c571d1221a265d25af778ecf8923490c6ceeb450aecdc82e28293031d10c7d73bf85e57bf041a97360aa2c5d99cc1df82d9c4b87413eae2ef048f94b4d3554cea73d92b0f7af96e0271c691e2bb5c67add7c6caf302256adedf7ab114da0acfe870d449a3a489f781d659e8beccda7bce9f4e8618b6bd2f4132ce798cdc7a60e7e1460a7299e3c6342a579626d22733e50f526ec2fa19a22b31e8ed50f23cd1fdf94c9154ed3a7609a2f1ff981fe1d3b5c807b281e4683cc6d6315cf95b9ade8641defcb32372f1c126e398ef7a5a2dce0a8a7f68bb74560f8f71837c2c2ebbcbf7fffb42ae1896f13f7c7479a0b46a28b6f55540f89444f63de0378e3d121be09e06cc9ded1c20e65876d36aa0c65e9645644786b620e2dd2ad648ddfcbf4a7e5b1a3a4ecfe7f64667a3f0b7e2f4418588ed35a2458cffeb39b93d26f18d2ab13bdce6aee58e7b99359ec2dfd95a9c16dc00d6ef18b7933a6f8dc65ccb55667138776f7dea101070dc8796e3774df84f40ae0c8229d0d6069e5c8f39a7c299677a09d367fc7b05e3bc380ee652cdc72595f74c7b1043d0e1ffbab734648c838dfb0527d971b602bc216c9619ef0abf5ac974a1ed57f4050aa510dd9c74f508277b39d7973bb2dfccc5eeb0618db8cd74046ff337f0a7bf2c8e03e10f642c1886798d71806ab1e888d9e5ee87d00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
@@ -267,96 +943,103 @@ mod test {
     }
 
     #[test]
-    fn evm() {
+    fn oracle_constructor_args() {
+        // A creation code that deploys its own constructor arguments as the contract's code:
+        // CODECOPY(0, CODESIZE - 64, 64); RETURN(0, 64)
+        let creation_code = hex!("604080380360003960406000f3");
+        let config = OracleConfig::new(creation_code.to_vec())
+            .with_min_proposal_size(10_000)
+            .with_challenge_period(120);
+
         let mut mips_evm = MipsEVM::new();
+        mips_evm.try_init_with_config(config).unwrap();
+        assert!(mips_evm.is_initialized());
+
+        let db = mips_evm.inner.db().unwrap();
+        let oracle = &db.accounts[&revm::primitives::Address::from(PREIMAGE_ORACLE_ADDR)].info;
+        let code = oracle.code.as_ref().unwrap().original_bytes();
+        assert_eq!(code[..32], U256::from(10_000).to_be_bytes::<32>());
+        assert_eq!(code[32..64], U256::from(120).to_be_bytes::<32>());
+    }
+
+    #[test]
+    fn default_spec_id() {
+        assert_eq!(MipsEVM::new().spec_id(), DEFAULT_SPEC_ID);
+        assert_eq!(
+            MipsEVM::new().with_spec_id(SpecId::SHANGHAI).spec_id(),
+            SpecId::SHANGHAI
+        );
+    }
+
+    #[test]
+    fn evm_custom_database() {
+        // A layered cache over a shared base, as a forked database would be.
+        let base = CacheDB::new(EmptyDB::default());
+        let mut mips_evm = MipsEVM::with_database(CacheDB::new(base));
+        assert!(!mips_evm.is_initialized());
         mips_evm.try_init().unwrap();
+        assert!(mips_evm.is_initialized());
 
-        let tests_path = PathBuf::from(std::env::current_dir().unwrap())
-            .join("open_mips_tests")
-            .join("test")
-            .join("bin");
-        let test_files = fs::read_dir(tests_path).unwrap();
-
-        for f in test_files.into_iter() {
-            if let Ok(f) = f {
-                let file_name = String::from(f.file_name().to_str().unwrap());
-                println!(" -> Running test: {file_name}");
-
-                // Short circuit early for `exit_group.bin`
-                let exit_group = file_name == "exit_group.bin";
-
-                let program_mem = fs::read(f.path()).unwrap();
-
-                let mut state = {
-                    let mut state = State::default();
-                    state.pc = 0;
-                    state.next_pc = 4;
-                    state.memory = Memory::default();
-                    state
-                };
-                state
-                    .memory
-                    .set_memory_range(0, BufReader::new(program_mem.as_slice()))
-                    .unwrap();
+        let mut state = State::default();
+        state.next_pc = 4;
+        // addiu $t0, $zero, 0x2a
+        state.memory.set_memory(0, 0x24_08_00_2a).unwrap();
 
-                // Set the return address ($ra) to jump into when the test completes.
-                state.registers[31] = END_ADDR;
+        let mut instrumented =
+            InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+        let step_witness = instrumented.step(true).unwrap().unwrap();
+        let evm_post = mips_evm.step(step_witness).unwrap();
+        assert_eq!(evm_post, instrumented.state.encode_witness().unwrap());
+    }
 
-                let mut instrumented = InstrumentedState::new(
-                    state,
-                    StaticOracle::new(b"hello world".to_vec()),
-                    io::stdout(),
-                    io::stderr(),
-                );
+    #[test]
+    fn evm_across_specs() {
+        for spec_id in [SpecId::SHANGHAI, SpecId::CANCUN, SpecId::LATEST] {
+            let mut mips_evm = MipsEVM::new().with_spec_id(spec_id);
+            mips_evm.try_init().unwrap();
 
-                for _ in 0..1000 {
-                    if instrumented.state.pc == END_ADDR {
-                        break;
-                    }
-                    if exit_group && instrumented.state.exited {
-                        break;
-                    }
-
-                    let instruction = instrumented
-                        .state
-                        .memory
-                        .get_memory(instrumented.state.pc as Address)
-                        .unwrap();
-                    println!(
-                        "{}",
-                        format!(
-                            "step: {} pc: 0x{:08x} insn: 0x{:08x}",
-                            instrumented.state.step, instrumented.state.pc, instruction
-                        )
-                    );
-
-                    let step_witness = instrumented.step(true).unwrap().unwrap();
-
-                    // Verify that the post state matches
-                    let evm_post = mips_evm.step(step_witness).unwrap();
-                    let rust_post = instrumented.state.encode_witness().unwrap();
-
-                    assert_eq!(evm_post, rust_post);
-                }
+            let mut state = State::default();
+            state.next_pc = 4;
+            // addiu $t0, $zero, 0x2a
+            state.memory.set_memory(0, 0x24_08_00_2a).unwrap();
 
-                if exit_group {
-                    assert_ne!(END_ADDR, instrumented.state.pc, "must not reach end");
-                    assert!(instrumented.state.exited, "must exit");
-                    assert_eq!(1, instrumented.state.exit_code, "must exit with 1");
-                } else {
-                    assert_eq!(END_ADDR, instrumented.state.pc, "must reach end");
-                    let mut state = instrumented.state.memory;
-                    let (done, result) = (
-                        state.get_memory((BASE_ADDR_END + 4) as Address).unwrap(),
-                        state.get_memory((BASE_ADDR_END + 8) as Address).unwrap(),
-                    );
-                    assert_eq!(done, 1, "must set done to 1");
-                    assert_eq!(result, 1, "must have success result {:?}", f.file_name());
-                }
-            }
+            let mut instrumented = InstrumentedState::new(
+                state,
+                StaticOracle::new(b"hello world".to_vec()),
+                io::stdout(),
+                io::stderr(),
+            );
+            let step_witness = instrumented.step(true).unwrap().unwrap();
+
+            let evm_post = mips_evm.step(step_witness).unwrap();
+            let rust_post = instrumented.state.encode_witness().unwrap();
+
+            assert_eq!(evm_post, rust_post, "{spec_id:?}");
         }
     }
 
+    #[test]
+    fn evm() {
+        let mut mips_evm = MipsEVM::new();
+        mips_evm.try_init().unwrap();
+
+        open_mips::run_all(|ins| {
+            let step_witness = ins.step(true)?.expect("Witness must be generated");
+
+            // Verify that the post state matches
+            let evm_post = mips_evm.step(step_witness)?;
+            let rust_post = ins.state.encode_witness()?;
+            anyhow::ensure!(
+                evm_post == rust_post,
+                "post state mismatch at step {} (pc {:08x})",
+                ins.state.step,
+                ins.state.pc
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
     #[test]
     fn evm_single_step() {
         let mut mips_evm = MipsEVM::new();
@@ -380,8 +1063,6 @@ mod test {
         ];
 
         for (name, pc, next_pc, instruction) in cases {
-            println!(" -> Running test: {name}");
-
             let mut state = State::default();
             state.pc = pc;
             state.next_pc = next_pc;
@@ -398,7 +1079,238 @@ mod test {
             let evm_post = mips_evm.step(step_witness).unwrap();
             let rust_post = instrumented.state.encode_witness().unwrap();
 
-            assert_eq!(evm_post, rust_post);
+            assert_eq!(evm_post, rust_post, "{name}");
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// The unaligned word and atomic instructions (`lwl`, `lwr`, `swl`, `swr`, `ll` and `sc`)
+        /// match the `MIPS` contract for every byte offset, register value and memory word.
+        #[test]
+        fn evm_unaligned_and_atomic(
+            opcode in prop::sample::select(vec![0x22u32, 0x26, 0x2A, 0x2E, 0x30, 0x38]),
+            rs_reg in 1u32..32,
+            rt_reg in 0u32..32,
+            base in 0x0001_0000u32..0x7FFF_0000,
+            imm in any::<i16>(),
+            reg in any::<u32>(),
+            word in any::<u32>(),
+        ) {
+            prop_assume!(rs_reg != rt_reg);
+
+            let mut mips_evm = MipsEVM::new();
+            mips_evm.try_init().unwrap();
+
+            let mut state = State {
+                pc: 0x1000,
+                next_pc: 0x1004,
+                ..Default::default()
+            };
+            let instruction = opcode << 26 | rs_reg << 21 | rt_reg << 16 | imm as u16 as u32;
+            state.memory.set_memory(0x1000, instruction).unwrap();
+            state.registers[rs_reg as usize] = base;
+            if rt_reg != 0 {
+                state.registers[rt_reg as usize] = reg;
+            }
+            let address = base.wrapping_add(imm as i32 as u32);
+            state.memory.set_memory(address & !0x3, word).unwrap();
+
+            let mut instrumented =
+                InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+            let step_witness = instrumented.step(true).unwrap().unwrap();
+
+            let evm_post = mips_evm.step(step_witness).unwrap();
+            let rust_post = instrumented.state.encode_witness().unwrap();
+            prop_assert_eq!(evm_post, rust_post, "instruction {:08x}", instruction);
+        }
+    }
+
+    /// Steps a `read` of 4 bytes of the given keccak256 preimage at the given offset, returning
+    /// the [StepWitness] of the step and the post state.
+    fn step_keccak256_read(preimage: &[u8], offset: u32) -> (StepWitness, State) {
+        // syscall: read(PreimageRead, 0x2000, 4)
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            preimage_key: crate::keccak256_preimage_key(preimage),
+            preimage_offset: offset,
+            ..Default::default()
+        };
+        state.registers[2] = 4003;
+        state.registers[4] = 5;
+        state.registers[5] = 0x2000;
+        state.registers[6] = 4;
+        state.memory.set_memory(0x1000, 0x0000000C).unwrap();
+        state.memory.set_memory(0x2000, 0).unwrap();
+
+        let mut instrumented = InstrumentedState::new(
+            state,
+            StaticOracle::new(preimage.to_vec()),
+            io::sink(),
+            io::sink(),
+        );
+        let step_witness = instrumented.step(true).unwrap().unwrap();
+        assert!(step_witness.has_preimage());
+        (step_witness, instrumented.state)
+    }
+
+    #[test]
+    fn evm_large_preimage() {
+        let mut mips_evm = MipsEVM::new();
+        mips_evm.try_init().unwrap();
+
+        // Parts of preimages larger than a keccak256 block are loaded through the large preimage
+        // proposal flow if the deployed PreimageOracle supports it, and directly otherwise.
+        let preimage = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        for offset in [0, 4, 200, 304] {
+            let (step_witness, post) = step_keccak256_read(&preimage, offset);
+            let evm_post = mips_evm.step(step_witness).unwrap();
+            assert_eq!(
+                evm_post,
+                post.encode_witness().unwrap(),
+                "offset {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "the bundled PreimageOracle predates large preimage proposals; set CANNON_PREIMAGE_ORACLE_ARTIFACT"]
+    fn evm_large_preimage_proposal() {
+        let mut mips_evm = MipsEVM::new();
+        mips_evm
+            .try_init_with_config(
+                OracleConfig::from_env()
+                    .unwrap()
+                    .with_min_proposal_size(0)
+                    .with_challenge_period(0),
+            )
+            .unwrap();
+        assert!(mips_evm.large_preimage_bond().unwrap().is_some());
+
+        // Preimages spanning several keccak256 blocks, ending exactly on and within a block.
+        for len in [KECCAK_RATE * 2, 300, 1000] {
+            let preimage = (0..len).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+            let key = crate::keccak256_preimage_key(&preimage);
+
+            // The part is only readable once the proposal has been squeezed.
+            mips_evm.load_large_preimage_part(&preimage, 8).unwrap();
+            assert_eq!(
+                mips_evm.read_preimage(key, 8).unwrap(),
+                crate::preimage_part(&preimage, 8).unwrap()
+            );
+
+            // Steps that read other parts propose them in turn, and match the `MIPS` contract.
+            for offset in [0, 100, len as u32 + 4] {
+                assert!(mips_evm.read_preimage(key, offset).is_err());
+                let (step_witness, post) = step_keccak256_read(&preimage, offset);
+                let evm_post = mips_evm.step(step_witness).unwrap();
+                assert_eq!(
+                    evm_post,
+                    post.encode_witness().unwrap(),
+                    "len {}, offset {}",
+                    len,
+                    offset
+                );
+                assert_eq!(
+                    mips_evm.read_preimage(key, offset).unwrap(),
+                    crate::preimage_part(&preimage, offset).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn evm_mips32r2() {
+        let mut mips_evm = MipsEVM::new();
+        mips_evm.try_init().unwrap();
+
+        // The contract executes rotr and rotrv as srl and srlv, which the emulator matches unless
+        // MIPS32r2 is enabled.
+        for instruction in [0x00241202, 0x00A41046] {
+            let mut state = State {
+                pc: 0x1000,
+                next_pc: 0x1004,
+                ..Default::default()
+            };
+            state.memory.set_memory(0x1000, instruction).unwrap();
+            state.registers[4] = 0x12345687;
+            state.registers[5] = 4;
+
+            let mut instrumented =
+                InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+            let step_witness = instrumented.step(true).unwrap().unwrap();
+
+            let evm_post = mips_evm.step(step_witness).unwrap();
+            let rust_post = instrumented.state.encode_witness().unwrap();
+            assert_eq!(evm_post, rust_post, "{instruction:08x}");
+        }
+
+        // The contract rejects the SPECIAL3 instructions, which the emulator only executes with
+        // MIPS32r2 enabled.
+        for instruction in [0x7C041420, 0x7C041620, 0x7C0410A0, 0x7C823900, 0x7C827A04] {
+            let mut state = State {
+                next_pc: 4,
+                ..Default::default()
+            };
+            state.memory.set_memory(0, instruction).unwrap();
+            let instruction_proof = state.memory.merkle_proof(0).unwrap();
+            let step_witness = StepWitness {
+                state: state.encode_witness().unwrap(),
+                mem_proof: instruction_proof.to_vec(),
+                preimage_key: None,
+                preimage_value: None,
+                preimage_offset: None,
+                precompile_call: None,
+                mem_accesses: None,
+            };
+            let err = mips_evm.step(step_witness).unwrap_err();
+            assert!(
+                matches!(err, CannonError::EvmRevert { .. }),
+                "{instruction:08x}"
+            );
+
+            let mut instrumented =
+                InstrumentedState::new(state, StaticOracle::default(), io::sink(), io::sink());
+            assert!(matches!(
+                instrumented.step(false),
+                Err(CannonError::InvalidInstruction { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn evm_fault_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let trace_path = dir.path().join("evm_fault_trace.jsonl");
+        let mut mips_evm = MipsEVM::new().with_trace_path(&trace_path);
+        mips_evm.try_init().unwrap();
+
+        let mut state = State {
+            next_pc: 4,
+            ..Default::default()
+        };
+        state.memory.set_memory(0, 0xFF_FF_FF_FF).unwrap();
+        let instruction_proof = state.memory.merkle_proof(0).unwrap();
+        let step_witness = StepWitness {
+            state: state.encode_witness().unwrap(),
+            mem_proof: instruction_proof.to_vec(),
+            preimage_key: None,
+            preimage_value: None,
+            preimage_offset: None,
+            precompile_call: None,
+            mem_accesses: None,
+        };
+        assert!(mips_evm.step(step_witness).is_err());
+
+        // Every line of the trace is a JSON object, and the trace ends with the summary.
+        let trace = fs::read_to_string(&trace_path).unwrap();
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert!(lines.len() > 1);
+        for line in lines {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
         }
     }
 
@@ -414,8 +1326,6 @@ mod test {
         ];
 
         for (name, next_pc, instruction) in cases {
-            println!(" -> Running test: {name}");
-
             let mut state = State {
                 next_pc: next_pc as Address,
                 ..Default::default()
@@ -431,7 +1341,10 @@ mod test {
                 io::stdout(),
                 io::stderr(),
             );
-            assert!(instrumented.step(true).is_err());
+            assert!(matches!(
+                instrumented.step(true),
+                Err(CannonError::InvalidInstruction { .. } | CannonError::InvalidDelaySlot { .. })
+            ));
 
             let mut initial_state = State {
                 next_pc: next_pc as Address,
@@ -445,8 +1358,12 @@ mod test {
                 preimage_key: None,
                 preimage_value: None,
                 preimage_offset: None,
+                precompile_call: None,
+                mem_accesses: None,
             };
-            assert!(mips_evm.step(step_witness).is_err());
+            let err = mips_evm.step(step_witness).unwrap_err();
+            assert!(matches!(err, CannonError::EvmRevert { .. }));
+            assert!(err.revert_reason().is_some(), "{name}: {err}");
         }
     }
 
@@ -463,22 +1380,22 @@ mod test {
         let mut instrumented =
             InstrumentedState::new(state, StaticOracle::default(), io::stdout(), io::stderr());
 
-        for i in 0..400_000 {
+        for _ in 0..400_000 {
             if instrumented.state.exited {
                 break;
             }
 
-            if i % 1000 == 0 {
-                let instruction = instrumented
+            crate::traces::trace!(
+                target: "mipsevm::evm",
+                "step: {} pc: 0x{:08x} instruction: {:08x}",
+                instrumented.state.step,
+                instrumented.state.pc,
+                instrumented
                     .state
                     .memory
                     .get_memory(instrumented.state.pc as Address)
-                    .unwrap();
-                println!(
-                    "step: {} pc: 0x{:08x} instruction: {:08x}",
-                    instrumented.state.step, instrumented.state.pc, instruction
-                );
-            }
+                    .unwrap()
+            );
 
             let step_witness = instrumented.step(true).unwrap().unwrap();
 
@@ -491,6 +1408,45 @@ mod test {
         assert_eq!(instrumented.state.exit_code, 0, "Must exit with 0");
     }
 
+    #[test]
+    fn test_hello_evm_batched() {
+        let mut mips_evm = MipsEVM::new();
+
+        let elf_bytes = include_bytes!("../../../../example/bin/hello.elf");
+        let mut state = patch::load_elf(elf_bytes).unwrap();
+        patch::patch_go(elf_bytes, &mut state).unwrap();
+        patch::patch_stack(&mut state).unwrap();
+
+        let mut instrumented =
+            InstrumentedState::new(state, StaticOracle::default(), io::stdout(), io::stderr());
+
+        // Step the contract in batches without initializing it first, which deploys the
+        // contracts on the first batch.
+        const BATCH_SIZE: usize = 4096;
+        while !instrumented.state.exited && instrumented.state.step < 400_000 {
+            let mut witnesses = Vec::with_capacity(BATCH_SIZE);
+            let mut rust_posts = Vec::with_capacity(BATCH_SIZE);
+            while witnesses.len() < BATCH_SIZE && !instrumented.state.exited {
+                witnesses.push(instrumented.step(true).unwrap().unwrap());
+                rust_posts.push(instrumented.state.encode_witness().unwrap());
+            }
+
+            let report = mips_evm.step_batch(witnesses).unwrap();
+            crate::traces::debug!(target: "mipsevm::evm", "step: {} {}", instrumented.state.step, report);
+            for (i, (evm_post, rust_post)) in report.post_states.iter().zip(&rust_posts).enumerate()
+            {
+                assert_eq!(
+                    evm_post, rust_post,
+                    "post state mismatch at batch index {i}"
+                );
+            }
+            assert_eq!(report.post_states.len(), rust_posts.len());
+        }
+
+        assert!(instrumented.state.exited, "Must complete program");
+        assert_eq!(instrumented.state.exit_code, 0, "Must exit with 0");
+    }
+
     #[test]
     fn test_claim_evm() {
         let mut mips_evm = MipsEVM::new();
@@ -507,22 +1463,22 @@ mod test {
         let mut instrumented =
             InstrumentedState::new(state, ClaimTestOracle::default(), out_buf, err_buf);
 
-        for i in 0..2_000_000 {
+        for _ in 0..2_000_000 {
             if instrumented.state.exited {
                 break;
             }
 
-            if i % 1000 == 0 {
-                let instruction = instrumented
+            crate::traces::trace!(
+                target: "mipsevm::evm",
+                "step: {} pc: 0x{:08x} instruction: {:08x}",
+                instrumented.state.step,
+                instrumented.state.pc,
+                instrumented
                     .state
                     .memory
                     .get_memory(instrumented.state.pc as Address)
-                    .unwrap();
-                println!(
-                    "step: {} pc: 0x{:08x} instruction: {:08x}",
-                    instrumented.state.step, instrumented.state.pc, instruction
-                );
-            }
+                    .unwrap()
+            );
 
             let step_witness = instrumented.step(true).unwrap().unwrap();
 
@@ -0,0 +1,169 @@
+//! This module contains the [EvmBackedState], which executes every step through the `MIPS`
+//! contract on the [MipsEVM] rather than the native VM, so that the contract implementation can
+//! serve as the source of truth for short reference runs and conformance harnesses.
+
+use super::evm::MipsEVM;
+use crate::{
+    CannonError, CannonResult, InstrumentedState, PreimageOracle, State, StateWitness,
+    StateWitnessFields, StepWitness, Stepper,
+};
+use alloy_primitives::B256;
+use revm::db::{CacheDB, EmptyDB};
+use std::io::Write;
+
+/// The [EvmBackedState] is a [Stepper] like the [InstrumentedState], but every step is executed by the
+/// `MIPS` contract. The native VM still produces the [StepWitness] of each step, which the
+/// contract needs, and tracks the contents of the memory, of which the contract only returns the
+/// merkle root.
+///
+/// After each step, the registers and all other fields of the post-state are taken from the
+/// contract. The native memory must match the memory root returned by the contract, as it cannot
+/// be reconstructed from the root alone; the step fails otherwise.
+///
+/// Every step is a transaction on the [MipsEVM], which is orders of magnitude slower than the
+/// native VM.
+pub struct EvmBackedState<O: Write, E: Write, P: PreimageOracle> {
+    /// The native VM, which produces the witnesses of the steps and tracks the memory.
+    pub native: InstrumentedState<O, E, P>,
+    /// The EVM that every step is executed on.
+    pub evm: MipsEVM<CacheDB<EmptyDB>>,
+}
+
+impl<O, E, P> EvmBackedState<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    /// Creates a new [EvmBackedState], deploying the contracts on a fresh [MipsEVM].
+    ///
+    /// ### Takes
+    /// - `state`: The initial [State].
+    /// - `oracle`: The [PreimageOracle] that preimages are read from.
+    /// - `std_out`: The writer of the guest's stdout.
+    /// - `std_err`: The writer of the guest's stderr.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the [EvmBackedState].
+    pub fn new(state: State, oracle: P, std_out: O, std_err: E) -> CannonResult<Self> {
+        Self::from_instrumented(InstrumentedState::new(state, oracle, std_out, std_err))
+    }
+
+    /// Creates a new [EvmBackedState] that continues from the given [InstrumentedState],
+    /// deploying the contracts on a fresh [MipsEVM].
+    pub fn from_instrumented(native: InstrumentedState<O, E, P>) -> CannonResult<Self> {
+        let mut evm = MipsEVM::new();
+        evm.try_init()?;
+        Ok(Self { native, evm })
+    }
+
+    /// Replaces the fields of the native [State] with those of the post-state returned by the
+    /// contract, after checking that the native memory matches its memory root.
+    fn apply(&mut self, post: &StateWitness) -> CannonResult<()> {
+        let state = &mut self.native.state;
+        let memory_root = state.memory_root()?;
+        if memory_root != post.memory_root() {
+            return Err(CannonError::Other(anyhow::anyhow!(
+                "The native memory root {} diverged from the memory root {} of the MIPS contract at step {}",
+                B256::from(memory_root),
+                B256::from(post.memory_root()),
+                post.step()
+            )));
+        }
+
+        state.preimage_key = post.preimage_key();
+        state.preimage_offset = post.preimage_offset();
+        state.pc = post.pc();
+        state.next_pc = post.next_pc();
+        state.lo = post.lo();
+        state.hi = post.hi();
+        state.heap = post.heap();
+        state.exit_code = post.exit_code();
+        state.exited = post.exited();
+        state.step = post.step();
+        state.registers = post.registers();
+        Ok(())
+    }
+}
+
+impl<O, E, P> Stepper for EvmBackedState<O, E, P>
+where
+    O: Write,
+    E: Write,
+    P: PreimageOracle,
+{
+    fn state(&self) -> &State {
+        &self.native.state
+    }
+
+    /// Step the `MIPS` contract forward one instruction.
+    ///
+    /// ### Takes
+    /// - `proof`: Whether to return the [StepWitness] of the step.
+    ///
+    /// ### Returns
+    /// - Ok(Some(witness)): The [StepWitness] of the step, if requested.
+    /// - Err(_): The native VM failed to produce the witness, the contract reverted, or the native
+    ///   memory diverged from the memory root returned by the contract.
+    fn step(&mut self, proof: bool) -> CannonResult<Option<StepWitness>> {
+        let witness = self
+            .native
+            .step(true)?
+            .expect("A witness is produced when a proof is requested");
+        let post = self.evm.step(witness.clone())?;
+        self.apply(&post)?;
+        Ok(proof.then_some(witness))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{patch, test_utils::StaticOracle};
+    use std::io;
+
+    #[test]
+    fn matches_native_execution() {
+        let elf_bytes = include_bytes!("../../../../example/bin/hello.elf");
+        let mut state = patch::load_elf(elf_bytes).unwrap();
+        patch::patch_go(elf_bytes, &mut state).unwrap();
+        patch::patch_stack(&mut state).unwrap();
+
+        let run = |stepper: &mut dyn Stepper| {
+            for _ in 0..1_000 {
+                stepper.step(false).unwrap();
+            }
+            stepper.state().clone().state_hash().unwrap()
+        };
+
+        let mut native = InstrumentedState::new(
+            state.clone(),
+            StaticOracle::default(),
+            io::sink(),
+            io::sink(),
+        );
+        let mut backed =
+            EvmBackedState::new(state, StaticOracle::default(), io::sink(), io::sink()).unwrap();
+        assert_eq!(run(&mut backed), run(&mut native));
+    }
+
+    #[test]
+    fn contract_is_the_source_of_truth() {
+        // 0x1000: rotr $t0, $t1, 4, which the contract executes as srl.
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        state.memory.set_memory(0x1000, 0x00294102).unwrap();
+        state.registers[9] = 0x1234_5678;
+        state.enable_mips32r2();
+
+        let mut backed =
+            EvmBackedState::new(state, StaticOracle::default(), io::sink(), io::sink()).unwrap();
+        let witness = backed.step(true).unwrap().unwrap();
+        assert_eq!(witness.state.step(), 0);
+        assert_eq!(backed.state().registers[8], 0x0123_4567);
+        assert_eq!(backed.state().pc, 0x1004);
+    }
+}
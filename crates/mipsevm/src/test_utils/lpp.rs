@@ -0,0 +1,282 @@
+//! This module contains the [LargePreimageProposal], which computes the inputs of the
+//! `PreimageOracle`'s large preimage proposal (LPP) flow: `initLPP`, `addLeavesLPP` and
+//! `squeezeLPP`.
+//!
+//! A large preimage is absorbed on chain one keccak256 block at a time. The proposer commits to the
+//! sponge state after each block, and the oracle merkleizes the blocks and commitments into leaves
+//! of a tree of depth [KECCAK_TREE_DEPTH]. Once the challenge period has elapsed, the proposal is
+//! squeezed by proving the last two leaves and the sponge state prior to the final block, which
+//! the oracle absorbs itself to finalize the preimage.
+
+use crate::utils::keccak256;
+use alloy_primitives::{B256, U256};
+use alloy_sol_types::{sol, SolType};
+
+/// The rate of the keccak256 sponge, in bytes.
+pub const KECCAK_RATE: usize = 136;
+
+/// The depth of the `PreimageOracle`'s merkle tree of proposal leaves.
+pub const KECCAK_TREE_DEPTH: usize = 16;
+
+sol! {
+    /// The keccak256 sponge state, as `LibKeccak` lays it out.
+    struct StateMatrix {
+        uint64[25] state;
+    }
+
+    /// A leaf of a large preimage proposal.
+    struct Leaf {
+        bytes input;
+        uint256 index;
+        bytes32 stateCommitment;
+    }
+
+    /// `PreimageOracle` MIN_BOND_SIZE getter.
+    function MIN_BOND_SIZE() external view returns (uint256);
+
+    /// `PreimageOracle` challengePeriod getter.
+    function challengePeriod() external view returns (uint256);
+
+    /// `PreimageOracle` initLPP function.
+    function initLPP(uint256,uint32,uint32) external payable;
+
+    /// `PreimageOracle` addLeavesLPP function.
+    function addLeavesLPP(uint256,uint256,bytes,bytes32[],bool) external;
+
+    /// `PreimageOracle` squeezeLPP function.
+    function squeezeLPP(address,uint256,StateMatrix,Leaf,bytes32[],Leaf,bytes32[]) external;
+}
+
+/// The round constants of the keccak-f[1600] permutation.
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// The rotation offsets of the keccak-f[1600] permutation, in the order of [PI_LANES].
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// The lane permutation of the keccak-f[1600] permutation.
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Applies the keccak-f[1600] permutation to the sponge state.
+pub fn keccak_f(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // θ
+        let c: [u64; 5] = core::array::from_fn(|x| {
+            state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20]
+        });
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+
+        // ρ and π
+        let mut last = state[1];
+        for (&lane, &rotation) in PI_LANES.iter().zip(RHO.iter()) {
+            let tmp = state[lane];
+            state[lane] = last.rotate_left(rotation);
+            last = tmp;
+        }
+
+        // χ
+        for y in 0..5 {
+            let row: [u64; 5] = core::array::from_fn(|x| state[x + 5 * y]);
+            for x in 0..5 {
+                state[x + 5 * y] ^= !row[(x + 1) % 5] & row[(x + 2) % 5];
+            }
+        }
+
+        // ι
+        state[0] ^= round_constant;
+    }
+}
+
+/// A [LargePreimageProposal] holds the blocks, sponge states and leaves of a preimage, as the
+/// `PreimageOracle` expects them to be proposed.
+#[derive(Debug, Clone)]
+pub struct LargePreimageProposal {
+    /// The unpadded preimage.
+    pub preimage: Vec<u8>,
+    /// The sponge state after absorbing each block, starting with the empty state.
+    pub states: Vec<[u64; 25]>,
+    /// The leaves of the proposal, one per padded block.
+    pub leaves: Vec<Leaf>,
+}
+
+impl LargePreimageProposal {
+    /// Creates a new [LargePreimageProposal] by absorbing the padded preimage block by block.
+    pub fn new(preimage: &[u8]) -> Self {
+        let mut padded = preimage.to_vec();
+        let pad_len = KECCAK_RATE - preimage.len() % KECCAK_RATE;
+        padded.resize(preimage.len() + pad_len, 0);
+        padded[preimage.len()] ^= 0x01;
+        *padded.last_mut().expect("Padding is non-empty") ^= 0x80;
+
+        let mut states = vec![[0u64; 25]];
+        let mut leaves = Vec::with_capacity(padded.len() / KECCAK_RATE);
+        for (index, block) in padded.chunks_exact(KECCAK_RATE).enumerate() {
+            let mut state = *states.last().expect("States are non-empty");
+            for (lane, bytes) in state.iter_mut().zip(block.chunks_exact(8)) {
+                *lane ^= u64::from_le_bytes(bytes.try_into().expect("Chunk is 8 bytes"));
+            }
+            keccak_f(&mut state);
+            states.push(state);
+            leaves.push(Leaf {
+                input: block.to_vec(),
+                index: U256::from(index),
+                stateCommitment: state_commitment(&state),
+            });
+        }
+
+        Self {
+            preimage: preimage.to_vec(),
+            states,
+            leaves,
+        }
+    }
+
+    /// Returns the keccak256 digest of the preimage, squeezed from the final sponge state.
+    pub fn digest(&self) -> [u8; 32] {
+        let state = self.states.last().expect("States are non-empty");
+        let mut digest = [0u8; 32];
+        for (bytes, lane) in digest.chunks_exact_mut(8).zip(state.iter()) {
+            bytes.copy_from_slice(&lane.to_le_bytes());
+        }
+        digest
+    }
+
+    /// Returns the state commitments of all leaves, as passed to `addLeavesLPP`.
+    pub fn state_commitments(&self) -> Vec<B256> {
+        self.leaves
+            .iter()
+            .map(|leaf| leaf.stateCommitment)
+            .collect()
+    }
+
+    /// Returns the merkle proof of the leaf at the given index, against the root of the tree of
+    /// all leaves of the proposal.
+    pub fn leaf_proof(&self, index: usize) -> Vec<B256> {
+        let mut zero_hash = B256::ZERO;
+        let mut level = self.leaves.iter().map(hash_leaf).collect::<Vec<_>>();
+        let mut index = index;
+        let mut proof = Vec::with_capacity(KECCAK_TREE_DEPTH);
+        for _ in 0..KECCAK_TREE_DEPTH {
+            proof.push(level.get(index ^ 1).copied().unwrap_or(zero_hash));
+            level = level
+                .chunks(2)
+                .map(|pair| hash_pair(pair[0], pair.get(1).copied().unwrap_or(zero_hash)))
+                .collect();
+            zero_hash = hash_pair(zero_hash, zero_hash);
+            index >>= 1;
+        }
+        proof
+    }
+
+    /// Returns the `squeezeLPP` arguments that follow the claimant and UUID: the sponge state
+    /// prior to the final block, the second to last leaf and its proof, and the last leaf and its
+    /// proof. Proposals of a single block prove the final leaf against an empty pre-state.
+    pub fn squeeze_args(&self) -> (StateMatrix, Leaf, Vec<B256>, Leaf, Vec<B256>) {
+        let last = self.leaves.len() - 1;
+        let (pre_state, pre_proof) = match last {
+            0 => (
+                Leaf {
+                    input: Vec::new(),
+                    index: U256::ZERO,
+                    stateCommitment: B256::ZERO,
+                },
+                vec![B256::ZERO; KECCAK_TREE_DEPTH],
+            ),
+            _ => (self.leaves[last - 1].clone(), self.leaf_proof(last - 1)),
+        };
+        (
+            StateMatrix {
+                state: self.states[last],
+            },
+            pre_state,
+            pre_proof,
+            self.leaves[last].clone(),
+            self.leaf_proof(last),
+        )
+    }
+}
+
+/// Returns the commitment to a sponge state, `keccak256(abi.encode(stateMatrix))`.
+fn state_commitment(state: &[u64; 25]) -> B256 {
+    keccak256(&StateMatrix::abi_encode(&StateMatrix { state: *state })).into()
+}
+
+/// Returns the hash of a [Leaf], `keccak256(abi.encodePacked(input, index, stateCommitment))`.
+fn hash_leaf(leaf: &Leaf) -> B256 {
+    let mut packed = leaf.input.clone();
+    packed.extend_from_slice(&leaf.index.to_be_bytes::<32>());
+    packed.extend_from_slice(leaf.stateCommitment.as_slice());
+    keccak256(packed).into()
+}
+
+/// Returns the hash of two sibling nodes.
+fn hash_pair(left: B256, right: B256) -> B256 {
+    keccak256([left.as_slice(), right.as_slice()].concat()).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sponge_matches_keccak256() {
+        for len in [0, 1, 135, 136, 137, 500] {
+            let preimage = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let proposal = LargePreimageProposal::new(&preimage);
+            assert_eq!(proposal.leaves.len(), len / KECCAK_RATE + 1);
+            assert_eq!(proposal.digest(), keccak256(&preimage).0, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn leaf_proofs() {
+        let proposal = LargePreimageProposal::new(&[0xAB; 1000]);
+        let root = |index: usize| {
+            let mut node = hash_leaf(&proposal.leaves[index]);
+            for (level, sibling) in proposal.leaf_proof(index).into_iter().enumerate() {
+                node = if (index >> level) & 1 == 1 {
+                    hash_pair(sibling, node)
+                } else {
+                    hash_pair(node, sibling)
+                };
+            }
+            node
+        };
+        for index in 1..proposal.leaves.len() {
+            assert_eq!(root(index), root(0));
+        }
+    }
+}
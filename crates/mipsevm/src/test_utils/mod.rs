@@ -1,12 +1,16 @@
 //! Testing utilities.
 
-use crate::{utils::concat_fixed, utils::keccak256, PreimageOracle};
+use crate::{
+    utils::concat_fixed, utils::keccak256, CannonError, CannonResult, PreimageOracle, State,
+};
 use alloy_primitives::hex;
-use anyhow::Result;
 use preimage_oracle::{Hint, Keccak256Key, Key, LocalIndexKey};
 use rustc_hash::FxHashMap;
 
 pub mod evm;
+pub mod evm_backed;
+pub mod lpp;
+pub mod open_mips;
 
 /// Used in tests to write the results to
 pub const BASE_ADDR_END: u32 = 0xBF_FF_FF_F0;
@@ -14,6 +18,33 @@ pub const BASE_ADDR_END: u32 = 0xBF_FF_FF_F0;
 /// Used as the return-address for tests
 pub const END_ADDR: u32 = 0xA7_EF_00_D0;
 
+/// Returns a state that increments `$t0` and stores it to `0x2000` the given number of times
+/// before exiting, after `4 * iterations + 1` steps.
+pub fn counting_state(iterations: u32) -> State {
+    // 0x1000: addiu $t0, $t0, 1
+    // 0x1004: sw $t0, 0x2000($zero)
+    // 0x1008: bne $t0, $t1, 0x1000
+    // 0x100c: nop
+    // 0x1010: syscall (exit_group)
+    let mut state = State {
+        pc: 0x1000,
+        next_pc: 0x1004,
+        ..Default::default()
+    };
+    for (i, instruction) in [0x25080001, 0xAC082000, 0x1509FFFD, 0, 0x0000000C]
+        .into_iter()
+        .enumerate()
+    {
+        state
+            .memory
+            .set_memory(0x1000 + i as u32 * 4, instruction)
+            .unwrap();
+    }
+    state.registers[2] = 4246;
+    state.registers[9] = iterations;
+    state
+}
+
 #[derive(Default)]
 pub struct StaticOracle {
     preimage_data: Vec<u8>,
@@ -26,14 +57,14 @@ impl StaticOracle {
 }
 
 impl PreimageOracle for StaticOracle {
-    fn hint(&mut self, _value: impl Hint) -> Result<()> {
+    fn hint(&mut self, _value: impl Hint) -> CannonResult<()> {
         // noop
         Ok(())
     }
 
-    fn get(&mut self, key: [u8; 32]) -> anyhow::Result<Vec<u8>> {
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
         if key != (key as Keccak256Key).preimage_key() {
-            anyhow::bail!("Invalid preimage ")
+            return Err(CannonError::OracleIo(anyhow::anyhow!("Invalid preimage")));
         }
         Ok(self.preimage_data.clone())
     }
@@ -91,7 +122,7 @@ impl Default for ClaimTestOracle {
 }
 
 impl PreimageOracle for ClaimTestOracle {
-    fn hint(&mut self, value: impl Hint) -> Result<()> {
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()> {
         let s = String::from_utf8(value.hint().to_vec()).unwrap();
         let parts: Vec<&str> = s.split(' ').collect();
 
@@ -139,11 +170,11 @@ impl PreimageOracle for ClaimTestOracle {
         Ok(())
     }
 
-    fn get(&mut self, key: [u8; 32]) -> anyhow::Result<Vec<u8>> {
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
         Ok(self
             .images
             .get(&key)
-            .ok_or(anyhow::anyhow!("No image for key"))?
+            .ok_or_else(|| CannonError::OracleIo(anyhow::anyhow!("No image for key")))?
             .to_vec())
     }
 }
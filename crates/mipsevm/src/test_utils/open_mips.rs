@@ -0,0 +1,293 @@
+//! This module contains the harness for the `open_mips_tests` golden test vectors.
+//!
+//! Each vector is a raw program in `open_mips_tests/test/bin/<name>.bin`, assembled from
+//! `open_mips_tests/test/<name>.asm`. The program is loaded at address `0` and returns to
+//! [END_ADDR] when it completes, after writing `1` to the `done` and `result` words at
+//! [BASE_ADDR_END] + 4 and [BASE_ADDR_END] + 8.
+//!
+//! Vectors that are expected to end differently declare so with `# expect:` directives in their
+//! assembly source, so that new vectors can be dropped into the directory without code changes:
+//! - `# expect: exit <code>` - The program exits with the given code before reaching [END_ADDR].
+//! - `# expect: reg <index> = <value>` - The register has the given value once the program ends.
+//! - `# expect: mem <address> = <value>` - The word at the address has the given value once the
+//!   program ends.
+//!
+//! Numbers may be given in decimal or as `0x`-prefixed hex.
+
+use super::{StaticOracle, BASE_ADDR_END, END_ADDR};
+use crate::{Address, InstrumentedState, State};
+use anyhow::{anyhow, ensure, Result};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The maximum number of steps that a vector may take before it is considered stuck.
+pub const MAX_VECTOR_STEPS: usize = 1000;
+
+/// The preimage served to the vectors that read from the preimage oracle.
+pub const VECTOR_PREIMAGE: &[u8] = b"hello world";
+
+/// The [InstrumentedState] that the vectors are run on.
+pub type VectorInstrumentedState = InstrumentedState<io::Sink, io::Sink, StaticOracle>;
+
+/// The [Expectation]s that a vector's final [State] is checked against.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expectation {
+    /// The exit code that the program is expected to exit with, if it is expected to exit rather
+    /// than return to [END_ADDR].
+    pub exit_code: Option<u8>,
+    /// The expected values of registers, by index.
+    pub registers: Vec<(usize, u32)>,
+    /// The expected values of memory words, by address.
+    pub memory: Vec<(Address, u32)>,
+}
+
+impl Expectation {
+    /// Parses the `# expect:` directives from the assembly source of a vector.
+    ///
+    /// ### Takes
+    /// - `source`: The assembly source of the vector.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the parsed [Expectation].
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut expectation = Self::default();
+        for line in source.lines() {
+            let Some((_, directive)) = line.split_once("# expect:") else {
+                continue;
+            };
+            let parts = directive.split_whitespace().collect::<Vec<_>>();
+            match parts.as_slice() {
+                ["exit", code] => expectation.exit_code = Some(parse_number(code)? as u8),
+                ["reg", index, "=", value] => {
+                    let index = parse_number(index)? as usize;
+                    ensure!(index < 32, "Invalid register index {index}");
+                    expectation.registers.push((index, parse_number(value)?));
+                }
+                ["mem", address, "=", value] => expectation
+                    .memory
+                    .push((parse_number(address)?, parse_number(value)?)),
+                _ => anyhow::bail!("Invalid directive: `{}`", directive.trim()),
+            }
+        }
+        Ok(expectation)
+    }
+
+    /// Checks the final [State] of a vector against the [Expectation].
+    ///
+    /// ### Takes
+    /// - `state`: The final [State] of the vector.
+    ///
+    /// ### Returns
+    /// - A [Result] describing the first mismatch, if any.
+    pub fn check(&self, state: &mut State) -> Result<()> {
+        match self.exit_code {
+            Some(exit_code) => {
+                ensure!(state.pc != END_ADDR, "must not reach end");
+                ensure!(state.exited, "must exit");
+                ensure!(
+                    state.exit_code == exit_code,
+                    "must exit with {exit_code}, got {}",
+                    state.exit_code
+                );
+            }
+            None => {
+                ensure!(state.pc == END_ADDR, "must reach end, pc: {:08x}", state.pc);
+                let done = state.memory.get_memory(BASE_ADDR_END + 4)?;
+                let result = state.memory.get_memory(BASE_ADDR_END + 8)?;
+                ensure!(done == 1, "must set done to 1");
+                ensure!(result == 1, "must have success result");
+            }
+        }
+
+        for &(index, expected) in self.registers.iter() {
+            let actual = state.registers[index];
+            ensure!(
+                actual == expected,
+                "register {index} must be {expected:08x}, got {actual:08x}"
+            );
+        }
+        for &(address, expected) in self.memory.iter() {
+            let actual = state.memory.get_memory(address)?;
+            ensure!(
+                actual == expected,
+                "word at {address:08x} must be {expected:08x}, got {actual:08x}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A golden test vector from the `open_mips_tests` directory.
+#[derive(Debug, Clone)]
+pub struct Vector {
+    /// The file name of the vector's program.
+    pub name: String,
+    /// The raw program, loaded at address `0`.
+    pub program: Vec<u8>,
+    /// The [Expectation] that the vector's final [State] is checked against.
+    pub expectation: Expectation,
+}
+
+impl Vector {
+    /// Returns the path of the directory containing the vectors' assembly sources. The assembled
+    /// programs live in its `bin` subdirectory.
+    pub fn dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("open_mips_tests")
+            .join("test")
+    }
+
+    /// Loads all vectors from the [Vector::dir], in order of their names.
+    ///
+    /// ### Returns
+    /// - A [Result] containing the loaded [Vector]s.
+    pub fn load_all() -> Result<Vec<Self>> {
+        let dir = Self::dir();
+        let mut paths = fs::read_dir(dir.join("bin"))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "bin"));
+        paths.sort();
+
+        paths.iter().map(|path| Self::load(path, &dir)).collect()
+    }
+
+    /// Loads a single vector, along with the [Expectation] declared in its assembly source in
+    /// `source_dir`, if the source exists.
+    fn load(path: &Path, source_dir: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Invalid vector path {}", path.display()))?
+            .to_string();
+        let source = source_dir.join(&name).with_extension("asm");
+        let expectation = match fs::read_to_string(&source) {
+            Ok(source) => Expectation::parse(&source).map_err(|e| anyhow!("{name}: {e}"))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Expectation::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            name,
+            program: fs::read(path)?,
+            expectation,
+        })
+    }
+
+    /// Creates the initial [State] of the vector, with the program loaded at address `0` and the
+    /// return address set to [END_ADDR].
+    pub fn state(&self) -> Result<State> {
+        let mut state = State {
+            pc: 0,
+            next_pc: 4,
+            ..Default::default()
+        };
+        state.memory.set_memory_range(0, self.program.as_slice())?;
+        state.registers[31] = END_ADDR;
+        Ok(state)
+    }
+
+    /// Runs the vector to completion and checks its final [State] against its [Expectation].
+    ///
+    /// ### Takes
+    /// - `step`: A function that advances the [InstrumentedState] by a single step. This allows
+    ///   harnesses to verify each step, e.g. against the `MIPS` contract.
+    ///
+    /// ### Returns
+    /// - A [Result] describing the failure of the vector, if any.
+    pub fn run<F>(&self, mut step: F) -> Result<()>
+    where
+        F: FnMut(&mut VectorInstrumentedState) -> Result<()>,
+    {
+        let mut ins = InstrumentedState::new(
+            self.state()?,
+            StaticOracle::new(VECTOR_PREIMAGE.to_vec()),
+            io::sink(),
+            io::sink(),
+        );
+
+        for _ in 0..MAX_VECTOR_STEPS {
+            if ins.state.pc == END_ADDR || ins.state.exited {
+                break;
+            }
+            step(&mut ins)?;
+        }
+
+        self.expectation.check(&mut ins.state)
+    }
+}
+
+/// Runs all vectors with the given step function, collecting the failures.
+///
+/// ### Takes
+/// - `step`: A function that advances the [InstrumentedState] by a single step.
+///
+/// ### Returns
+/// - A [Result] listing every failing vector, if any.
+pub fn run_all<F>(mut step: F) -> Result<()>
+where
+    F: FnMut(&mut VectorInstrumentedState) -> Result<()>,
+{
+    let vectors = Vector::load_all()?;
+    ensure!(
+        !vectors.is_empty(),
+        "No vectors found in {}",
+        Vector::dir().display()
+    );
+
+    let failures = vectors
+        .iter()
+        .filter_map(|vector| {
+            vector
+                .run(&mut step)
+                .err()
+                .map(|e| format!("{}: {e:#}", vector.name))
+        })
+        .collect::<Vec<_>>();
+    ensure!(
+        failures.is_empty(),
+        "{} of {} vectors failed:\n{}",
+        failures.len(),
+        vectors.len(),
+        failures.join("\n")
+    );
+    Ok(())
+}
+
+/// Parses a decimal or `0x`-prefixed hex number.
+fn parse_number(s: &str) -> Result<u32> {
+    let value = match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16)?,
+        None => s.parse()?,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_expectation() {
+        let source = "
+            li $a0, 1 # expect: exit 1
+            # expect: reg 4 = 0x1
+            # expect: mem 0xbffffff8 = 1
+        ";
+        let expectation = Expectation::parse(source).unwrap();
+        assert_eq!(
+            expectation,
+            Expectation {
+                exit_code: Some(1),
+                registers: vec![(4, 1)],
+                memory: vec![(0xbffffff8, 1)],
+            }
+        );
+
+        assert!(Expectation::parse("# expect: reg 32 = 0").is_err());
+        assert!(Expectation::parse("# expect: nothing").is_err());
+        assert_eq!(Expectation::parse("").unwrap(), Expectation::default());
+    }
+}
@@ -0,0 +1,125 @@
+//! This module contains the [PageTlb], a small direct-mapped cache of recently used pages that
+//! replaces the two-entry page cache of the [crate::Memory] when the `tlb` feature is enabled.
+
+use crate::{types::SharedCachedPageOf, PageIndex};
+use std::rc::Rc;
+
+/// The number of entries in the [PageTlb]. Must be a power of two.
+pub(crate) const TLB_SIZE: usize = 64;
+
+/// The [PageTlb] maps recently used page indices to their pages, so that most memory accesses do
+/// not need to consult the page map. Entries are indexed by the low bits of the page index.
+#[derive(Debug)]
+pub(crate) struct PageTlb<const P: usize>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The cached entries. `None` if the entry is empty.
+    entries: [Option<(PageIndex, SharedCachedPageOf<P>)>; TLB_SIZE],
+}
+
+impl<const P: usize> Default for PageTlb<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    fn default() -> Self {
+        Self {
+            entries: std::array::from_fn(|_| None),
+        }
+    }
+}
+
+impl<const P: usize> Clone for PageTlb<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The cache is rebuilt on demand, so a clone starts out empty.
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<const P: usize> PartialEq for PageTlb<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// The cache is not part of the observable state of the [crate::Memory], so all caches
+    /// compare equal.
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<const P: usize> Eq for PageTlb<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+}
+
+impl<const P: usize> PageTlb<P>
+where
+    [(); 1 << P]:,
+    [(); (1 << P) >> 5]:,
+{
+    /// Returns the slot of the given page index.
+    #[inline(always)]
+    fn slot(page_index: PageIndex) -> usize {
+        page_index as usize & (TLB_SIZE - 1)
+    }
+
+    /// Looks up a page in the cache.
+    #[inline(always)]
+    pub(crate) fn lookup(&self, page_index: PageIndex) -> Option<SharedCachedPageOf<P>> {
+        match &self.entries[Self::slot(page_index)] {
+            Some((index, page)) if *index == page_index => Some(Rc::clone(page)),
+            _ => None,
+        }
+    }
+
+    /// Caches a page, replacing the entry that shares its slot.
+    #[inline(always)]
+    pub(crate) fn insert(&mut self, page_index: PageIndex, page: &SharedCachedPageOf<P>) {
+        self.entries[Self::slot(page_index)] = Some((page_index, Rc::clone(page)));
+    }
+
+    /// Removes a page from the cache, if it is cached.
+    pub(crate) fn evict(&mut self, page_index: PageIndex) {
+        let entry = &mut self.entries[Self::slot(page_index)];
+        if matches!(entry, Some((index, _)) if *index == page_index) {
+            *entry = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::SharedCachedPage;
+
+    #[test]
+    fn lookup_insert_evict() {
+        let mut tlb = PageTlb::default();
+        let (a, b) = (SharedCachedPage::default(), SharedCachedPage::default());
+        assert!(tlb.lookup(1).is_none());
+
+        tlb.insert(1, &a);
+        tlb.insert(2, &b);
+        assert!(Rc::ptr_eq(&tlb.lookup(1).unwrap(), &a));
+        assert!(Rc::ptr_eq(&tlb.lookup(2).unwrap(), &b));
+
+        // Pages sharing a slot replace each other.
+        tlb.insert(1 + TLB_SIZE as PageIndex, &b);
+        assert!(tlb.lookup(1).is_none());
+
+        // Evicting a page that is not cached leaves its slot intact.
+        tlb.evict(1);
+        assert!(tlb.lookup(1 + TLB_SIZE as PageIndex).is_some());
+        tlb.evict(1 + TLB_SIZE as PageIndex);
+        assert!(tlb.lookup(1 + TLB_SIZE as PageIndex).is_none());
+    }
+}
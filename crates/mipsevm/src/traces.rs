@@ -1,5 +1,15 @@
 #![allow(unused_imports)]
 
+/// Performs a tracing trace if the `tracing` feature is enabled.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+pub use trace;
+
 /// Performs a tracing debug if the `tracing` feature is enabled.
 #[macro_export]
 macro_rules! debug {
@@ -40,6 +50,24 @@ macro_rules! warn {
 }
 pub use crate::warn;
 
+/// Enters a tracing span at the debug level if the `tracing` feature is enabled. The returned
+/// guard exits the span when dropped, so it must be bound to a named variable such as `_span`.
+#[macro_export]
+macro_rules! debug_span {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!($($arg)*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::traces::DisabledSpan;
+        span
+    }};
+}
+pub use debug_span;
+
+/// The guard returned by [debug_span] if the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct DisabledSpan;
+
 #[cfg(test)]
 mod tests {
     use super::*;
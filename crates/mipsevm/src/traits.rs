@@ -1,13 +1,58 @@
 //! This module contains the various traits used in this crate.
 
-use anyhow::Result;
-use preimage_oracle::Hint;
-
-/// A [StateWitnessHasher] is a trait describing the functionality of a type
-/// that computes a witness hash.
-pub trait StateWitnessHasher {
-    /// Compute the [crate::StateWitness] hash.
-    fn state_hash(&self) -> [u8; 32];
+use crate::{CannonResult, State, StepWitness};
+use preimage_oracle::{Hint, PrecompileKey};
+
+/// A [StateWitnessFields] is a trait describing typed accessors into the fields of an encoded
+/// [crate::StateWitness].
+pub trait StateWitnessFields {
+    /// Returns the merkle root of the memory.
+    fn memory_root(&self) -> [u8; 32];
+
+    /// Returns the key of the active preimage.
+    fn preimage_key(&self) -> [u8; 32];
+
+    /// Returns the read offset into the active preimage.
+    fn preimage_offset(&self) -> u32;
+
+    /// Returns the program counter.
+    fn pc(&self) -> u32;
+
+    /// Returns the next program counter.
+    fn next_pc(&self) -> u32;
+
+    /// Returns the `lo` register.
+    fn lo(&self) -> u32;
+
+    /// Returns the `hi` register.
+    fn hi(&self) -> u32;
+
+    /// Returns the heap pointer.
+    fn heap(&self) -> u32;
+
+    /// Returns the exit code of the VM.
+    fn exit_code(&self) -> u8;
+
+    /// Returns whether or not the VM has exited.
+    fn exited(&self) -> bool;
+
+    /// Returns the step count of the VM.
+    fn step(&self) -> u64;
+
+    /// Returns the general purpose register at the given index.
+    ///
+    /// ### Panics
+    /// - If `index` is not less than 32.
+    fn register(&self, index: usize) -> u32;
+
+    /// Returns all 32 general purpose registers.
+    fn registers(&self) -> [u32; 32] {
+        std::array::from_fn(|i| self.register(i))
+    }
+
+    /// Returns an object that implements [std::fmt::Display] for a human-readable dump of the
+    /// witness.
+    fn display(&self) -> crate::witness::StateWitnessDisplay<'_>;
 }
 
 /// A [PreimageOracle] is a trait describing the functionality of a preimage
@@ -17,7 +62,7 @@ pub trait PreimageOracle {
     ///
     /// ### Takes
     /// - `value`: The preimage to insert.
-    fn hint(&mut self, value: impl Hint) -> Result<()>;
+    fn hint(&mut self, value: impl Hint) -> CannonResult<()>;
 
     /// Fetch the preimage for the given key.
     ///
@@ -27,6 +72,60 @@ pub trait PreimageOracle {
     /// ### Returns
     /// - `Ok(Some(preimage))`: The preimage for the given key.
     /// - `Ok(None)`: The preimage for the given key does not exist.
-    /// - `Err(_)`: An error occurred while fetching the preimage.
-    fn get(&mut self, key: [u8; 32]) -> Result<Vec<u8>>;
+    /// - `Err(_)`: An error occurred while fetching the preimage. Implementations should return
+    ///   [crate::CannonError::OracleIo] for failures to communicate with the preimage server.
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>>;
+
+    /// Returns the precompile call that the given [preimage_oracle::KeyType::Precompile] key was
+    /// derived from, if the oracle knows it. The call is not part of the preimage, but is needed
+    /// to load the preimage into the `PreimageOracle` contract.
+    ///
+    /// ### Takes
+    /// - `key`: The precompile preimage key.
+    ///
+    /// ### Returns
+    /// - `Some(call)`: The address and input of the precompile call.
+    /// - `None`: The oracle does not know the call, which is the default.
+    fn precompile_call(&self, _key: [u8; 32]) -> Option<PrecompileKey> {
+        None
+    }
+
+    /// Notifies the oracle that the guest read a part of a preimage fetched with
+    /// [PreimageOracle::get]. Does nothing by default.
+    ///
+    /// ### Takes
+    /// - `key`: The key of the preimage.
+    /// - `length`: The length of the preimage, without its length prefix.
+    /// - `offset`: The offset of the part into the length-prefixed preimage.
+    /// - `part`: The part that the guest read.
+    ///
+    /// ### Returns
+    /// - `Err(_)`: The oracle failed to handle the read, which aborts the step.
+    fn on_read(
+        &mut self,
+        _key: [u8; 32],
+        _length: u64,
+        _offset: u32,
+        _part: &[u8],
+    ) -> CannonResult<()> {
+        Ok(())
+    }
+}
+
+/// A [Stepper] is a trait describing an emulator that steps a [State] forward one instruction at a
+/// time, so that reference runs and conformance harnesses can be generic over the native VM and
+/// other backends, such as the `MIPS` contract.
+pub trait Stepper {
+    /// Returns the current [State].
+    fn state(&self) -> &State;
+
+    /// Step the emulator forward one instruction.
+    ///
+    /// ### Takes
+    /// - `proof`: Whether to return the [StepWitness] of the step.
+    ///
+    /// ### Returns
+    /// - Ok(Some(witness)): The [StepWitness] of the step, if requested.
+    /// - Err(_): An error occurred while processing the instruction step.
+    fn step(&mut self, proof: bool) -> CannonResult<Option<StepWitness>>;
 }
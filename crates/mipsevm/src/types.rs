@@ -1,16 +1,20 @@
 //! This module contains all of the type aliases and enums used within this crate.
 
-use crate::CachedPage;
+use crate::page::{CachedPageOf, PAGE_ADDRESS_SIZE};
+pub use cannon_witness::{StateWitness, VMStatus};
 use std::{cell::RefCell, rc::Rc};
 
+/// A [PageOf] is a portion of memory of `2^P` bytes.
+pub type PageOf<const P: usize> = [u8; 1 << P];
+
 /// A [Page] is a portion of memory of size `PAGE_SIZE`.
-pub type Page = [u8; crate::page::PAGE_SIZE];
+pub type Page = PageOf<PAGE_ADDRESS_SIZE>;
 
-/// A [CachedPage] with shared ownership.
-pub type SharedCachedPage = Rc<RefCell<CachedPage>>;
+/// A [CachedPageOf] with shared ownership.
+pub type SharedCachedPageOf<const P: usize> = Rc<RefCell<CachedPageOf<P>>>;
 
-/// A [StateWitness] is an encoded commitment to the current [crate::State] of the MIPS emulator.
-pub type StateWitness = [u8; crate::witness::STATE_WITNESS_SIZE];
+/// A [crate::CachedPage] with shared ownership.
+pub type SharedCachedPage = SharedCachedPageOf<PAGE_ADDRESS_SIZE>;
 
 /// A [PageIndex] is the index of a [Page] within the [crate::Memory] mappings.
 pub type PageIndex = u64;
@@ -21,16 +25,6 @@ pub type Gindex = u64;
 /// An [Address] is a 32 bit address in the MIPS emulator's memory.
 pub type Address = u32;
 
-/// The [VMStatus] is an indicator within the [StateWitness] hash that indicates
-/// the current status of the MIPS emulator.
-#[repr(u8)]
-pub enum VMStatus {
-    Valid = 0,
-    Invalid = 1,
-    Panic = 2,
-    Unfinished = 3,
-}
-
 /// Identifiers for special file descriptors used by the MIPS emulator.
 #[repr(u8)]
 pub enum Fd {
@@ -63,12 +57,22 @@ impl TryFrom<u8> for Fd {
 /// A [Syscall] is a system call that can be made within the MIPS emulator.
 pub enum Syscall {
     Mmap = 4090,
+    Munmap = 4091,
     Brk = 4045,
     Clone = 4120,
     ExitGroup = 4246,
     Read = 4003,
     Write = 4004,
+    Open = 4005,
+    Close = 4006,
+    Lseek = 4019,
     Fcntl = 4055,
+    Openat = 4288,
+    Getrandom = 4353,
+    Gettimeofday = 4078,
+    ClockGettime = 4263,
+    ClockGettime64 = 4403,
+    Futex = 4238,
 }
 
 impl TryFrom<u32> for Syscall {
@@ -77,12 +81,22 @@ impl TryFrom<u32> for Syscall {
     fn try_from(n: u32) -> Result<Self, Self::Error> {
         match n {
             4090 => Ok(Syscall::Mmap),
+            4091 => Ok(Syscall::Munmap),
             4045 => Ok(Syscall::Brk),
             4120 => Ok(Syscall::Clone),
             4246 => Ok(Syscall::ExitGroup),
             4003 => Ok(Syscall::Read),
             4004 => Ok(Syscall::Write),
+            4005 => Ok(Syscall::Open),
+            4006 => Ok(Syscall::Close),
+            4019 => Ok(Syscall::Lseek),
             4055 => Ok(Syscall::Fcntl),
+            4288 => Ok(Syscall::Openat),
+            4353 => Ok(Syscall::Getrandom),
+            4078 => Ok(Syscall::Gettimeofday),
+            4263 => Ok(Syscall::ClockGettime),
+            4403 => Ok(Syscall::ClockGettime64),
+            4238 => Ok(Syscall::Futex),
             _ => anyhow::bail!("Failed to convert {} to Syscall", n),
         }
     }
@@ -29,9 +29,36 @@ pub(crate) fn keccak_concat_hashes(a: [u8; 32], b: [u8; 32]) -> B256 {
     keccak256(concat_fixed(a, b).as_slice())
 }
 
+/// Hashes the input with keccak256, using the assembly implementation of the `keccak-asm` crate
+/// if the `asm-keccak` feature is enabled, and the XKCP implementation otherwise. Both hash one
+/// message at a time.
 #[inline(always)]
 pub(crate) fn keccak256<T: AsRef<[u8]>>(input: T) -> B256 {
-    let mut out = B256::ZERO;
-    xkcp_rs::keccak256(input.as_ref(), out.as_mut());
-    out
+    #[cfg(feature = "asm-keccak")]
+    {
+        use keccak_asm::Digest;
+        B256::from_slice(&keccak_asm::Keccak256::digest(input.as_ref()))
+    }
+
+    #[cfg(not(feature = "asm-keccak"))]
+    {
+        let mut out = B256::ZERO;
+        xkcp_rs::keccak256(input.as_ref(), out.as_mut());
+        out
+    }
+}
+
+#[cfg(all(test, feature = "asm-keccak"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn asm_matches_xkcp() {
+        let data = (0..=408).map(|i| i as u8).collect::<Vec<_>>();
+        for len in 0..=data.len() {
+            let mut expected = B256::ZERO;
+            xkcp_rs::keccak256(&data[..len], expected.as_mut());
+            assert_eq!(keccak256(&data[..len]), expected, "length {}", len);
+        }
+    }
 }
@@ -0,0 +1,273 @@
+//! This module contains [verify_step], which executes a single step from its [StepWitness] data
+//! alone, mirroring the `MIPS` contract. It allows light verifiers to check a disputed step in
+//! pure Rust, without the full [State] or an EVM.
+//!
+//! [StepWitness]: crate::StepWitness
+
+use crate::{
+    proof::PROOF_SIZE, Address, CannonError, CannonResult, InstrumentedState, MerkleHasher,
+    PreimageOracle, State, StateWitness, StateWitnessFields, StateWitnessHasher, WitnessState,
+};
+use alloy_primitives::B256;
+use preimage_oracle::Hint;
+use std::io;
+
+/// Executes exactly one step from the given pre-state witness and memory proofs, as the `MIPS`
+/// contract's `step` function does, and returns the hash of the post-state.
+///
+/// Only the memory covered by the proofs is known: the instruction is read from the first proof,
+/// and the memory accessed by the step, if any, from the second. The memory root of the
+/// post-state is recomputed from the second proof.
+///
+/// ### Takes
+/// - `pre`: The [StateWitness] of the state before the step.
+/// - `proofs`: The memory proofs of the step, as in [crate::StepWitness::mem_proof].
+/// - `oracle_part`: The data that the `PreimageOracle` returns for the witness' preimage key and
+///   offset; up to 32 bytes of the length-prefixed preimage, starting at the offset, along with
+///   the length of the preimage without its prefix. Only required if the step reads from the
+///   preimage oracle.
+///
+/// ### Returns
+/// - A [CannonResult] containing the hash of the post-state, or an error if the proofs do not
+///   match the memory root or the step cannot be executed.
+pub fn verify_step(
+    pre: &StateWitness,
+    proofs: &[u8],
+    oracle_part: Option<(&[u8], u64)>,
+) -> CannonResult<B256> {
+    if pre.exited() {
+        return Ok(B256::from(pre.state_hash()));
+    }
+
+    let memory_root = pre.memory_root();
+    let instruction_proof = read_proof(proofs, 0)?;
+    verify_proof(pre.pc(), instruction_proof, memory_root)?;
+
+    // Discover the memory accessed by the step, which is only known once its instruction is
+    // decoded, before executing it with the accessed memory in place.
+    let mut ins = witness_state(pre, &[(pre.pc(), instruction_proof)], oracle_part)?;
+    ins.step(false)?;
+    let Some((address, _)) = ins.mem_access() else {
+        return Ok(post_state_hash(&ins.state, memory_root));
+    };
+
+    let mem_proof = read_proof(proofs, 1)?;
+    verify_proof(address, mem_proof, memory_root)?;
+    let mut ins = witness_state(
+        pre,
+        &[(pre.pc(), instruction_proof), (address, mem_proof)],
+        oracle_part,
+    )?;
+    ins.step(false)?;
+
+    // Recompute the memory root from the accessed leaf, which a store may have modified.
+    let mut post_proof = *mem_proof;
+    let leaf = address & !31;
+    for (i, chunk) in post_proof[..32].chunks_exact_mut(4).enumerate() {
+        let word = ins.state.memory.get_memory(leaf + 4 * i as Address)?;
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    let post_root = MerkleHasher::Keccak256.proof_root(address, &post_proof);
+    Ok(post_state_hash(&ins.state, post_root))
+}
+
+/// The [PreimageOracle] of a step executed from its witness, which only knows the preimage part
+/// that was passed to [verify_step].
+struct WitnessOracle;
+
+impl PreimageOracle for WitnessOracle {
+    fn hint(&mut self, _value: impl Hint) -> CannonResult<()> {
+        Ok(())
+    }
+
+    fn get(&mut self, key: [u8; 32]) -> CannonResult<Vec<u8>> {
+        Err(CannonError::OracleIo(anyhow::anyhow!(
+            "The preimage part for key {} is not part of the witness",
+            B256::from(key)
+        )))
+    }
+}
+
+/// Returns the proof at the given index within the concatenated memory proofs of a step.
+fn read_proof(proofs: &[u8], index: usize) -> CannonResult<&[u8; PROOF_SIZE]> {
+    proofs
+        .get(index * PROOF_SIZE..(index + 1) * PROOF_SIZE)
+        .and_then(|proof| proof.try_into().ok())
+        .ok_or_else(|| {
+            CannonError::Other(anyhow::anyhow!(
+                "Missing memory proof {index}, got {} bytes of proofs",
+                proofs.len()
+            ))
+        })
+}
+
+/// Checks that the proof for the given address commits to the memory root.
+fn verify_proof(address: Address, proof: &[u8; PROOF_SIZE], root: [u8; 32]) -> CannonResult<()> {
+    if MerkleHasher::Keccak256.proof_root(address, proof) != root {
+        return Err(CannonError::Other(anyhow::anyhow!(
+            "Invalid memory proof for address {address:08x}"
+        )));
+    }
+    Ok(())
+}
+
+/// Reconstructs an [InstrumentedState] from a [StateWitness], with only the leaves of the given
+/// proofs in memory.
+fn witness_state(
+    pre: &StateWitness,
+    leaves: &[(Address, &[u8; PROOF_SIZE])],
+    oracle_part: Option<(&[u8], u64)>,
+) -> CannonResult<InstrumentedState<io::Sink, io::Sink, WitnessOracle>> {
+    let mut state = State {
+        preimage_key: pre.preimage_key(),
+        preimage_offset: pre.preimage_offset(),
+        pc: pre.pc(),
+        next_pc: pre.next_pc(),
+        lo: pre.lo(),
+        hi: pre.hi(),
+        heap: pre.heap(),
+        exit_code: pre.exit_code(),
+        exited: pre.exited(),
+        step: pre.step(),
+        registers: pre.registers(),
+        ..Default::default()
+    };
+    for (address, proof) in leaves {
+        let leaf = address & !31;
+        for (i, word) in proof[..32].chunks_exact(4).enumerate() {
+            let word = u32::from_be_bytes(word.try_into().expect("Word is 4 bytes"));
+            state.memory.set_memory(leaf + 4 * i as Address, word)?;
+        }
+    }
+
+    let mut ins = InstrumentedState::new(state, WitnessOracle, io::sink(), io::sink());
+    ins.set_record_mem_access(true);
+    match oracle_part {
+        Some((part, preimage_len)) => {
+            // As in `MIPS.sol`, the offset may point at most to the end of the length-prefixed
+            // preimage. Check it before padding the part to the offset, which is untrusted.
+            let offset = pre.preimage_offset();
+            let prefixed_len = preimage_len.saturating_add(8);
+            if offset as u64 > prefixed_len {
+                return Err(CannonError::PreimageOutOfBounds {
+                    key: pre.preimage_key(),
+                    offset,
+                    length: prefixed_len.try_into().unwrap_or(usize::MAX),
+                });
+            }
+            let expected_len = (prefixed_len - offset as u64).min(32) as usize;
+            if part.len() != expected_len {
+                return Err(CannonError::Other(anyhow::anyhow!(
+                    "The preimage part is {} bytes, expected {expected_len}",
+                    part.len()
+                )));
+            }
+            // Only the part at the current offset can be read by a single step.
+            let offset = offset as usize;
+            ins.last_preimage_key = pre.preimage_key();
+            ins.last_preimage = vec![0; offset + part.len()];
+            ins.last_preimage[offset..].copy_from_slice(part);
+        }
+        None => {
+            // Make sure that any preimage read is directed to the [WitnessOracle].
+            ins.last_preimage_key = pre.preimage_key();
+            ins.last_preimage_key[0] ^= 0xFF;
+        }
+    }
+    Ok(ins)
+}
+
+/// Hashes the post-state of a step executed from its witness, with the given memory root.
+fn post_state_hash(state: &State, memory_root: [u8; 32]) -> B256 {
+    let witness = WitnessState {
+        memory_root,
+        preimage_key: state.preimage_key,
+        preimage_offset: state.preimage_offset,
+        pc: state.pc,
+        next_pc: state.next_pc,
+        lo: state.lo,
+        hi: state.hi,
+        heap: state.heap,
+        exit_code: state.exit_code,
+        exited: state.exited,
+        step: state.step,
+        registers: state.registers,
+    }
+    .encode();
+    B256::from(witness.state_hash())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::open_mips;
+
+    #[test]
+    fn verify_step_matches_emulator() {
+        open_mips::run_all(|ins| {
+            let witness = ins.step(true)?.expect("Witness must be generated");
+            let oracle_part = witness.preimage_value.as_ref().map(|value| {
+                let offset = witness.preimage_offset.unwrap_or_default() as usize;
+                (
+                    &value[offset.min(value.len())..(offset + 32).min(value.len())],
+                    value.len() as u64 - 8,
+                )
+            });
+
+            let post = verify_step(&witness.state, &witness.mem_proof, oracle_part)?;
+            anyhow::ensure!(
+                post == B256::from(ins.state.state_hash()?),
+                "post state mismatch at step {} (pc {:08x})",
+                ins.state.step,
+                ins.state.pc
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_proofs() {
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            ..Default::default()
+        };
+        // lw $t0, 0x2000($zero)
+        state.memory.set_memory(0x1000, 0x8C082000).unwrap();
+        state.memory.set_memory(0x2000, 0xDEADBEEF).unwrap();
+        let mut ins = InstrumentedState::new(state, WitnessOracle, io::sink(), io::sink());
+        let witness = ins.step(true).unwrap().unwrap();
+
+        let post = verify_step(&witness.state, &witness.mem_proof, None).unwrap();
+        assert_eq!(post, B256::from(ins.state.state_hash().unwrap()));
+
+        let mut tampered = witness.mem_proof.clone();
+        tampered[PROOF_SIZE] ^= 1;
+        assert!(verify_step(&witness.state, &tampered, None).is_err());
+        assert!(verify_step(&witness.state, &witness.mem_proof[..PROOF_SIZE], None).is_err());
+    }
+
+    #[test]
+    fn rejects_preimage_offsets_past_the_preimage() {
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            preimage_offset: u32::MAX,
+            ..Default::default()
+        };
+        // nop
+        state.memory.set_memory(0x1000, 0).unwrap();
+        let mut ins = InstrumentedState::new(state, WitnessOracle, io::sink(), io::sink());
+        let witness = ins.step(true).unwrap().unwrap();
+
+        assert!(matches!(
+            verify_step(&witness.state, &witness.mem_proof, Some((&[], 10))),
+            Err(CannonError::PreimageOutOfBounds {
+                offset: u32::MAX,
+                length: 18,
+                ..
+            })
+        ));
+    }
+}
@@ -1,39 +1,205 @@
 //! This module contains the various witness types.
 
-use crate::{utils::keccak256, State, StateWitness, StateWitnessHasher};
+use crate::{
+    Address, CannonError, CannonResult, StateWitness, StateWitnessFields, StateWitnessHasher,
+};
 use alloy_primitives::{B256, U256};
 use alloy_sol_types::{sol, SolCall};
 use preimage_oracle::KeyType;
 use revm::primitives::Bytes;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
-/// The size of an encoded [StateWitness] in bytes.
-pub const STATE_WITNESS_SIZE: usize = 226;
+pub use cannon_witness::{
+    layout::{self, Field},
+    Keccak256Hasher, STATE_WITNESS_SIZE,
+};
 
-impl StateWitnessHasher for StateWitness {
-    fn state_hash(&self) -> [u8; 32] {
-        let mut hash = keccak256(self);
-        let offset = 32 * 2 + 4 * 6;
-        let exit_code = self[offset];
-        let exited = self[offset + 1] == 1;
-        hash[0] = State::vm_status(exited, exit_code) as u8;
-        *hash
+/// Reads a big-endian [u32] from the given [Field] of the [StateWitness].
+#[inline(always)]
+fn read_u32(witness: &StateWitness, field: Field) -> u32 {
+    u32::from_be_bytes(witness[field.range()].try_into().expect("Field is 4 bytes"))
+}
+
+impl StateWitnessFields for StateWitness {
+    fn memory_root(&self) -> [u8; 32] {
+        self[layout::MEMORY_ROOT.range()]
+            .try_into()
+            .expect("Field is 32 bytes")
+    }
+
+    fn preimage_key(&self) -> [u8; 32] {
+        self[layout::PREIMAGE_KEY.range()]
+            .try_into()
+            .expect("Field is 32 bytes")
+    }
+
+    fn preimage_offset(&self) -> u32 {
+        read_u32(self, layout::PREIMAGE_OFFSET)
+    }
+
+    fn pc(&self) -> u32 {
+        read_u32(self, layout::PC)
+    }
+
+    fn next_pc(&self) -> u32 {
+        read_u32(self, layout::NEXT_PC)
+    }
+
+    fn lo(&self) -> u32 {
+        read_u32(self, layout::LO)
+    }
+
+    fn hi(&self) -> u32 {
+        read_u32(self, layout::HI)
+    }
+
+    fn heap(&self) -> u32 {
+        read_u32(self, layout::HEAP)
+    }
+
+    fn exit_code(&self) -> u8 {
+        self[layout::EXIT_CODE.offset]
+    }
+
+    fn exited(&self) -> bool {
+        self[layout::EXITED.offset] == 1
+    }
+
+    fn step(&self) -> u64 {
+        u64::from_be_bytes(
+            self[layout::STEP.range()]
+                .try_into()
+                .expect("Field is 8 bytes"),
+        )
+    }
+
+    fn register(&self, index: usize) -> u32 {
+        assert!(index < 32, "Register index out of bounds: {}", index);
+        read_u32(self, layout::register(index))
+    }
+
+    fn display(&self) -> StateWitnessDisplay<'_> {
+        StateWitnessDisplay(self)
+    }
+}
+
+/// Helper struct for printing a [StateWitness] with [fmt::Display], returned by
+/// [StateWitnessFields::display].
+pub struct StateWitnessDisplay<'a>(&'a StateWitness);
+
+impl fmt::Display for StateWitnessDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let w = self.0;
+        writeln!(f, "state hash:      {}", B256::from(w.state_hash()))?;
+        writeln!(f, "memory root:     {}", B256::from(w.memory_root()))?;
+        writeln!(f, "preimage key:    {}", B256::from(w.preimage_key()))?;
+        writeln!(f, "preimage offset: {}", w.preimage_offset())?;
+        writeln!(
+            f,
+            "pc: 0x{:08x} next_pc: 0x{:08x} lo: 0x{:08x} hi: 0x{:08x} heap: 0x{:08x}",
+            w.pc(),
+            w.next_pc(),
+            w.lo(),
+            w.hi(),
+            w.heap()
+        )?;
+        writeln!(
+            f,
+            "step: {} exited: {} exit_code: {}",
+            w.step(),
+            w.exited(),
+            w.exit_code()
+        )?;
+        for (i, chunk) in w.registers().chunks(8).enumerate() {
+            write!(f, "r{:02}-r{:02}:", i * 8, i * 8 + 7)?;
+            for r in chunk {
+                write!(f, " {:08x}", r)?;
+            }
+            if i < 3 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
     }
 }
 
 /// A [StepWitness] is produced after each instruction step of the MIPS emulator. It contains
 /// the encoded [StateWitness], the proof of memory access, and the preimage key, value, and
 /// offset.
+///
+/// The JSON representation of a [StepWitness] is stable, so that witnesses can be stored and
+/// exchanged between tools. It is an object with the following fields, where byte strings are
+/// `0x`-prefixed hex and the preimage fields are `null` if the step did not read a preimage:
+///
+/// | Field            | Type                                                  |
+/// |------------------|-------------------------------------------------------|
+/// | `state`          | [STATE_WITNESS_SIZE] bytes                            |
+/// | `memProof`       | bytes                                                 |
+/// | `preimageKey`    | 32 bytes, or `null`                                   |
+/// | `preimageValue`  | the value prefixed by its 8-byte length, or `null`    |
+/// | `preimageOffset` | number, or `null`                                     |
+/// | `memAccesses`    | array of [MemAccess] objects, omitted unless recorded |
+///
+/// Omitted preimage fields are read as `null`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct StepWitness {
     /// The encoded state witness
+    #[serde(with = "crate::ser::state_witness_hex")]
     pub state: StateWitness,
     /// The proof of memory access
+    #[serde(with = "crate::ser::vec_u8_hex")]
     pub mem_proof: Vec<u8>,
     /// The preimage key
+    #[serde(default, with = "crate::ser::option_fixed_32_hex")]
     pub preimage_key: Option<[u8; 32]>,
     /// The preimage value
+    #[serde(default, with = "crate::ser::option_vec_u8_hex")]
     pub preimage_value: Option<Vec<u8>>,
     /// The preimage offset
+    #[serde(default)]
     pub preimage_offset: Option<u32>,
+    /// The address of the precompile followed by its input, if the preimage is the result of a
+    /// precompile call that the [crate::PreimageOracle] knows the call of. Needed to load the
+    /// preimage into the `PreimageOracle` contract.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::ser::option_vec_u8_hex"
+    )]
+    pub precompile_call: Option<Vec<u8>>,
+    /// The memory accesses performed by the step, in order, if they were recorded with
+    /// [crate::InstrumentedState::set_record_access_log].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_accesses: Option<Vec<MemAccess>>,
+}
+
+/// The kind of a [MemAccess].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemAccessKind {
+    /// The fetch of the step's instruction.
+    Fetch,
+    /// A read of a memory word by a load or a syscall.
+    Read,
+    /// A write of a memory word by a store or a syscall.
+    Write,
+}
+
+/// A [MemAccess] records a single access to a memory word during a step, e.g. for consumers that
+/// need the read and write sets of a step. The old and new values are equal for reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemAccess {
+    /// The address of the accessed word.
+    pub address: Address,
+    /// The value of the word before the access.
+    pub old: u32,
+    /// The value of the word after the access.
+    pub new: u32,
+    /// The kind of the access.
+    pub kind: MemAccessKind,
 }
 
 impl Default for StepWitness {
@@ -44,6 +210,8 @@ impl Default for StepWitness {
             preimage_key: Default::default(),
             preimage_value: Default::default(),
             preimage_offset: Default::default(),
+            precompile_call: Default::default(),
+            mem_accesses: Default::default(),
         }
     }
 }
@@ -55,10 +223,27 @@ sol! {
     /// `PreimageOracle` loadKeccak256PreimagePart function.
     function loadKeccak256PreimagePart(uint256,bytes) external;
 
+    /// `PreimageOracle` loadPrecompilePreimagePart function.
+    function loadPrecompilePreimagePart(uint256,address,bytes) external;
+
     /// `MIPS` step function.
     function step(bytes,bytes) external returns (bytes32);
 }
 
+/// The `MIPS` step function and `PreimageOracle` loadLocalData function of contract versions that
+/// take the local context of the preimage oracle as an argument. Declared separately, as `sol!`
+/// does not support overloads within a single scope.
+mod with_local_context {
+    alloy_sol_types::sol! {
+        /// `MIPS` step function, with the local context.
+        function step(bytes,bytes,bytes32) external returns (bytes32);
+
+        /// `PreimageOracle` loadLocalData function, with the local context that the identifier is
+        /// localized with.
+        function loadLocalData(uint256,bytes32,bytes32,uint256,uint256) external returns (bytes32);
+    }
+}
+
 impl StepWitness {
     /// Returns `true` if the step witness has a preimage.
     pub fn has_preimage(&self) -> bool {
@@ -71,18 +256,43 @@ impl StepWitness {
     /// - `Some(input)` if the [StepWitness] has a preimage request.
     /// - `None` if the [StepWitness] does not have a preimage request.
     pub fn encode_preimage_oracle_input(&self) -> Option<Bytes> {
+        self.encode_oracle_input(None)
+    }
+
+    /// ABI encodes the input to the preimage oracle of contract versions that take the local
+    /// context as an argument, if the [StepWitness] has a preimage request. Local preimages are
+    /// loaded under the key localized with the local context, as the `MIPS` contract requests
+    /// them; all other preimages are encoded as by [StepWitness::encode_preimage_oracle_input].
+    ///
+    /// ### Takes
+    /// - `local_context`: The local context of the dispute game, which namespaces its local
+    ///   preimage keys.
+    ///
+    /// ### Returns
+    /// - `Some(input)` if the [StepWitness] has a preimage request.
+    /// - `None` if the [StepWitness] does not have a preimage request.
+    pub fn encode_preimage_oracle_input_with_local_context(
+        &self,
+        local_context: B256,
+    ) -> Option<Bytes> {
+        self.encode_oracle_input(Some(local_context))
+    }
+
+    /// ABI encodes the input to the preimage oracle, with the local context of the dispute game
+    /// if given.
+    fn encode_oracle_input(&self, local_context: Option<B256>) -> Option<Bytes> {
         let preimage_key = self.preimage_key?;
 
         match KeyType::from(preimage_key[0]) {
             KeyType::_Illegal => {
-                crate::error!(target: "mipsevm::step_witness", "Illegal key type");
+                crate::traces::error!(target: "mipsevm::step_witness", "Illegal key type");
                 None
             }
             KeyType::Local => {
                 let preimage_value = &self.preimage_value.clone()?;
 
                 if preimage_value.len() > 32 + 8 {
-                    crate::error!(target: "mipsevm::step_witness", "Local preimage value exceeds maximum size of 32 bytes with key 0x{:x}", B256::from(self.preimage_key?));
+                    crate::traces::error!(target: "mipsevm::step_witness", "Local preimage value exceeds maximum size of 32 bytes with key 0x{:x}", B256::from(self.preimage_key?));
                     return None;
                 }
 
@@ -90,23 +300,57 @@ impl StepWitness {
                 let mut tmp = [0u8; 32];
                 tmp[0..preimage_part.len()].copy_from_slice(preimage_part);
 
-                let call = loadLocalDataCall {
-                    _0: B256::from(preimage_key).into(),
-                    _1: B256::from(tmp),
-                    _2: U256::from(preimage_value.len() - 8),
-                    _3: U256::from(self.preimage_offset?),
-                };
-
-                Some(call.abi_encode().into())
+                let size = U256::from(preimage_value.len() - 8);
+                let offset = U256::from(self.preimage_offset?);
+                match local_context {
+                    Some(local_context) => {
+                        let call = with_local_context::loadLocalDataCall {
+                            _0: U256::from_be_slice(&preimage_key[1..]),
+                            _1: local_context,
+                            _2: B256::from(tmp),
+                            _3: size,
+                            _4: offset,
+                        };
+                        Some(call.abi_encode().into())
+                    }
+                    None => {
+                        let call = loadLocalDataCall {
+                            _0: B256::from(preimage_key).into(),
+                            _1: B256::from(tmp),
+                            _2: size,
+                            _3: offset,
+                        };
+                        Some(call.abi_encode().into())
+                    }
+                }
             }
-            KeyType::GlobalKeccak => {
-                let call = loadKeccak256PreimagePartCall {
-                    _0: U256::from(self.preimage_offset?),
-                    _1: self.preimage_value.clone()?[8..].to_vec(),
+            KeyType::Blob => {
+                // The part of a blob is loaded with a KZG proof of the field element, which is not
+                // part of the preimage.
+                crate::traces::error!(target: "mipsevm::step_witness", "Blob preimages cannot be encoded without a KZG proof");
+                None
+            }
+            KeyType::Precompile => {
+                // The part of a precompile result is loaded from the precompile's address and
+                // input, which are not part of the preimage.
+                let Some(call) = self
+                    .precompile_call
+                    .as_ref()
+                    .filter(|call| call.len() >= 20)
+                else {
+                    crate::traces::error!(target: "mipsevm::step_witness", "Precompile preimages cannot be encoded without the precompile call");
+                    return None;
                 };
-
-                Some(call.abi_encode().into())
+                Some(crate::encode_precompile_preimage_part(
+                    call[..20].try_into().expect("Slice is 20 bytes"),
+                    &call[20..],
+                    self.preimage_offset?,
+                ))
             }
+            KeyType::GlobalKeccak => Some(crate::encode_keccak256_preimage_part(
+                &self.preimage_value.as_ref()?[8..],
+                self.preimage_offset?,
+            )),
         }
     }
 
@@ -122,4 +366,304 @@ impl StepWitness {
 
         call.abi_encode().into()
     }
+
+    /// ABI encodes the input to the MIPS step function of contract versions that take the local
+    /// context of the preimage oracle as a third argument. The result is ready to be submitted as
+    /// the calldata of a `step` transaction, after the preimage oracle input, if any, has been
+    /// submitted to the `PreimageOracle`.
+    ///
+    /// ### Takes
+    /// - `local_context`: The local context of the dispute game, which namespaces its local
+    ///   preimage keys.
+    ///
+    /// ### Returns
+    /// - The ABI encoded input to the MIPS step function.
+    pub fn encode_with_local_context(&self, local_context: B256) -> Bytes {
+        let call = with_local_context::stepCall {
+            _0: self.state.to_vec(),
+            _1: self.mem_proof.to_vec(),
+            _2: local_context,
+        };
+
+        call.abi_encode().into()
+    }
+
+    /// Decodes the ABI encoded input to the MIPS step function back into a [StepWitness], e.g. to
+    /// inspect the calldata of a `step` transaction submitted to a dispute game by another
+    /// implementation. Both the plain and the local context variants of the step function are
+    /// accepted. The preimage key, value and offset are not part of the calldata and are left
+    /// empty; the preimage, if any, is passed to the `PreimageOracle` in a separate call.
+    ///
+    /// ### Takes
+    /// - `calldata`: The ABI encoded input to the MIPS step function, including the selector.
+    ///
+    /// ### Returns
+    /// - A [CannonResult] containing the decoded [StepWitness].
+    pub fn decode_step_input(calldata: &[u8]) -> CannonResult<Self> {
+        let (state, mem_proof) = match stepCall::abi_decode(calldata, true) {
+            Ok(call) => (call._0, call._1),
+            Err(_) => with_local_context::stepCall::abi_decode(calldata, true)
+                .map(|call| (call._0, call._1))
+                .map_err(|e| CannonError::Other(anyhow::anyhow!("Invalid step calldata: {e}")))?,
+        };
+
+        let state: StateWitness = state.as_slice().try_into().map_err(|_| {
+            CannonError::Other(anyhow::anyhow!(
+                "Invalid state witness length {}, expected {}",
+                state.len(),
+                STATE_WITNESS_SIZE
+            ))
+        })?;
+        if mem_proof.len() % 32 != 0 {
+            return Err(CannonError::Other(anyhow::anyhow!(
+                "Invalid memory proof length {}, expected a multiple of 32",
+                mem_proof.len()
+            )));
+        }
+
+        Ok(Self {
+            state,
+            mem_proof,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{State, WitnessHasher};
+
+    #[test]
+    fn state_witness_fields() {
+        let mut state = State {
+            preimage_key: [0xAA; 32],
+            preimage_offset: 0x11,
+            pc: 0x22,
+            next_pc: 0x26,
+            lo: 0x33,
+            hi: 0x44,
+            heap: 0x55,
+            exit_code: 0x66,
+            exited: true,
+            step: 0x77,
+            ..Default::default()
+        };
+        for (i, r) in state.registers.iter_mut().enumerate() {
+            *r = 0x1000 + i as u32;
+        }
+        let witness = state.encode_witness().unwrap();
+
+        assert_eq!(witness.memory_root(), state.memory.merkle_root().unwrap());
+        assert_eq!(witness.preimage_key(), state.preimage_key);
+        assert_eq!(witness.preimage_offset(), state.preimage_offset);
+        assert_eq!(witness.pc(), state.pc);
+        assert_eq!(witness.next_pc(), state.next_pc);
+        assert_eq!(witness.lo(), state.lo);
+        assert_eq!(witness.hi(), state.hi);
+        assert_eq!(witness.heap(), state.heap);
+        assert_eq!(witness.exit_code(), state.exit_code);
+        assert_eq!(witness.exited(), state.exited);
+        assert_eq!(witness.step(), state.step);
+        assert_eq!(witness.registers(), state.registers);
+
+        let dump = witness.display().to_string();
+        assert!(dump.contains("pc: 0x00000022 next_pc: 0x00000026"));
+        assert!(dump.contains("r24-r31: 00001018"));
+    }
+
+    #[test]
+    fn pluggable_hasher() {
+        /// A [WitnessHasher] that commits to the first 32 bytes of the witness, in reverse.
+        struct ReverseHasher;
+
+        impl WitnessHasher for ReverseHasher {
+            fn hash(data: &[u8]) -> [u8; 32] {
+                let mut out: [u8; 32] = data[..32].try_into().unwrap();
+                out.reverse();
+                out
+            }
+        }
+
+        let mut witness = [0u8; STATE_WITNESS_SIZE];
+        witness[..32].copy_from_slice(&[0xAB; 32]);
+        // Mark the witness as exited with code 1, i.e. with the invalid status.
+        witness[32 * 2 + 4 * 6] = 1;
+        witness[32 * 2 + 4 * 6 + 1] = 1;
+
+        assert_eq!(
+            witness.state_hash(),
+            witness.state_hash_with::<Keccak256Hasher>()
+        );
+        let hash = witness.state_hash_with::<ReverseHasher>();
+        assert_eq!(hash[0], crate::VMStatus::Invalid as u8);
+        assert_eq!(hash[1..], [0xAB; 31]);
+    }
+
+    #[test]
+    fn step_witness_json() {
+        let witness = StepWitness {
+            state: State::default().encode_witness().unwrap(),
+            mem_proof: vec![0xAB; 28 * 32],
+            preimage_key: Some([0x11; 32]),
+            preimage_value: Some(vec![0, 0, 0, 0, 0, 0, 0, 1, 0x22]),
+            preimage_offset: Some(4),
+            precompile_call: None,
+            mem_accesses: None,
+        };
+        let json = serde_json::to_value(&witness).unwrap();
+        assert_eq!(json["memProof"], format!("0x{}", "ab".repeat(28 * 32)));
+        assert_eq!(json["preimageKey"], format!("0x{}", "11".repeat(32)));
+        assert_eq!(json["preimageValue"], "0x000000000000000122");
+        assert_eq!(json["preimageOffset"], 4);
+        assert!(json.get("memAccesses").is_none());
+        assert_eq!(
+            serde_json::from_value::<StepWitness>(json).unwrap(),
+            witness
+        );
+
+        // Witnesses of steps without a preimage read may omit the preimage fields.
+        let json = serde_json::json!({
+            "state": format!("0x{}", alloy_primitives::hex::encode(witness.state)),
+            "memProof": "0x",
+        });
+        let witness = serde_json::from_value::<StepWitness>(json).unwrap();
+        assert!(witness.mem_proof.is_empty());
+        assert!(!witness.has_preimage());
+    }
+
+    #[test]
+    fn decode_step_input() {
+        let mut state = State {
+            pc: 0x1000,
+            next_pc: 0x1004,
+            step: 42,
+            ..Default::default()
+        };
+        let witness = StepWitness {
+            state: state.encode_witness().unwrap(),
+            mem_proof: vec![0xAB; 28 * 32],
+            preimage_key: Some([0x11; 32]),
+            preimage_value: Some(vec![0x22; 16]),
+            preimage_offset: Some(4),
+            precompile_call: None,
+            mem_accesses: None,
+        };
+
+        let decoded = StepWitness::decode_step_input(&witness.encode_step_input()).unwrap();
+        assert_eq!(decoded.state, witness.state);
+        assert_eq!(decoded.mem_proof, witness.mem_proof);
+        assert!(!decoded.has_preimage());
+        assert_eq!(decoded.state.step(), 42);
+        assert_eq!(decoded.encode_step_input(), witness.encode_step_input());
+
+        // Calldata for another function, a truncated state witness and a misaligned memory proof
+        // are rejected.
+        let mut calldata = witness.encode_step_input().to_vec();
+        calldata[0] ^= 0xFF;
+        assert!(StepWitness::decode_step_input(&calldata).is_err());
+        let call = stepCall {
+            _0: witness.state[..STATE_WITNESS_SIZE - 1].to_vec(),
+            _1: witness.mem_proof.clone(),
+        };
+        assert!(StepWitness::decode_step_input(&call.abi_encode()).is_err());
+        let call = stepCall {
+            _0: witness.state.to_vec(),
+            _1: vec![0; 31],
+        };
+        assert!(StepWitness::decode_step_input(&call.abi_encode()).is_err());
+    }
+
+    #[test]
+    fn step_input_layout() {
+        use layout::step_input;
+
+        let witness = StepWitness {
+            state: [0x01; STATE_WITNESS_SIZE],
+            mem_proof: vec![0xAB; 28 * 32],
+            ..Default::default()
+        };
+        let calldata = witness.encode_step_input();
+        let word = |field: Field| U256::from_be_slice(&calldata[field.range()]);
+
+        assert_eq!(calldata[step_input::SELECTOR.range()], stepCall::SELECTOR);
+        assert_eq!(
+            word(step_input::STATE_HEAD),
+            U256::from(step_input::STATE_LEN.offset - step_input::SELECTOR.len)
+        );
+        assert_eq!(
+            word(step_input::PROOF_HEAD),
+            U256::from(step_input::PROOF_LEN.offset - step_input::SELECTOR.len)
+        );
+        assert_eq!(word(step_input::STATE_LEN), U256::from(STATE_WITNESS_SIZE));
+        assert_eq!(calldata[step_input::STATE.range()], witness.state);
+        assert!(calldata[step_input::STATE_PADDING.range()]
+            .iter()
+            .all(|b| *b == 0));
+        assert_eq!(word(step_input::PROOF_LEN), U256::from(28 * 32));
+        assert_eq!(calldata[step_input::SIZE..], witness.mem_proof);
+    }
+
+    #[test]
+    fn encode_with_local_context() {
+        let witness = StepWitness {
+            state: [0x01; STATE_WITNESS_SIZE],
+            mem_proof: vec![0xAB; 28 * 32],
+            ..Default::default()
+        };
+        let context = B256::repeat_byte(0xCC);
+
+        let calldata = witness.encode_with_local_context(context);
+        assert_eq!(calldata[..4], with_local_context::stepCall::SELECTOR);
+        assert_ne!(calldata[..4], stepCall::SELECTOR);
+        // The local context is the third head word, after the offsets of the two dynamic arguments.
+        assert_eq!(calldata[4 + 64..4 + 96], context[..]);
+
+        let decoded = StepWitness::decode_step_input(&calldata).unwrap();
+        assert_eq!(decoded.state, witness.state);
+        assert_eq!(decoded.mem_proof, witness.mem_proof);
+    }
+
+    #[test]
+    fn encode_local_data_with_local_context() {
+        let mut preimage_key = [0u8; 32];
+        preimage_key[0] = KeyType::Local as u8;
+        preimage_key[31] = 2;
+        let mut preimage_value = 4u64.to_be_bytes().to_vec();
+        preimage_value.extend([0xDE, 0xAD, 0xBE, 0xEF]);
+        let witness = StepWitness {
+            preimage_key: Some(preimage_key),
+            preimage_value: Some(preimage_value),
+            preimage_offset: Some(0),
+            ..Default::default()
+        };
+        let context = B256::repeat_byte(0xCC);
+
+        let input = witness
+            .encode_preimage_oracle_input_with_local_context(context)
+            .unwrap();
+        let call = with_local_context::loadLocalDataCall::abi_decode(&input, true).unwrap();
+        assert_eq!(call._0, U256::from(2));
+        assert_eq!(call._1, context);
+        assert_eq!(call._2[..4], [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(call._3, U256::from(4));
+        assert_eq!(call._4, U256::ZERO);
+
+        // Without a local context, the full key is loaded.
+        let input = witness.encode_preimage_oracle_input().unwrap();
+        let call = loadLocalDataCall::abi_decode(&input, true).unwrap();
+        assert_eq!(call._0, U256::from_be_bytes(preimage_key));
+
+        // Other preimages are unaffected by the local context.
+        let witness = StepWitness {
+            preimage_key: Some([KeyType::GlobalKeccak as u8; 32]),
+            preimage_value: Some([8u64.to_be_bytes(), [0xAB; 8]].concat()),
+            preimage_offset: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            witness.encode_preimage_oracle_input_with_local_context(context),
+            witness.encode_preimage_oracle_input()
+        );
+    }
 }
@@ -0,0 +1,46 @@
+//! Feeds arbitrary byte streams to the framing of the Pre-image Oracle ABI, as received by either
+//! side of the channel. Every message must either be parsed or rejected without panicking, reading
+//! past the end of the stream, or allocating more than the stream holds; parsed messages must
+//! encode back to the bytes they were read from.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use preimage_oracle::wire;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&side, stream)) = data.split_first() else {
+        return;
+    };
+
+    let mut reader = Cursor::new(stream);
+    loop {
+        let start = reader.position() as usize;
+        let mut encoded = Vec::new();
+        let parsed = match side % 3 {
+            // The server reads pre-image requests from the client.
+            0 => wire::read_preimage_request(&mut reader).map(|request| {
+                request.map(|key| wire::write_preimage_request(&mut encoded, key).unwrap())
+            }),
+            // The client reads pre-image responses from the server.
+            1 => wire::read_preimage_response(&mut reader).map(|preimage| {
+                Some(wire::write_preimage_response(&mut encoded, &preimage).unwrap())
+            }),
+            // The hint reader reads hints from the hint writer.
+            _ => wire::read_hint(&mut reader)
+                .map(|hint| hint.map(|hint| wire::write_hint(&mut encoded, &hint).unwrap())),
+        };
+
+        let end = reader.position() as usize;
+        assert!(end <= stream.len());
+        match parsed {
+            Ok(Some(())) => {
+                assert_eq!(&stream[start..end], encoded.as_slice());
+                // Every parsed message consumes input, so the loop terminates.
+                assert!(end > start);
+            }
+            Ok(None) | Err(_) => break,
+        }
+    }
+});
@@ -0,0 +1,242 @@
+//! This module contains the types for serving the field elements of EIP-4844 blobs as pre-images.
+//!
+//! Guests such as `op-program` read a blob one field element at a time. Each field element is the
+//! pre-image of a [BlobKey], which commits to the blob's KZG commitment and to the root of unity
+//! that the field element is evaluated at, in the bit-reversed order of the blob's evaluation
+//! domain.
+
+use crate::{Key, KeyType, PreimageStore};
+use alloy_primitives::{keccak256, U256};
+use anyhow::{anyhow, ensure, Result};
+use std::sync::OnceLock;
+
+/// The number of field elements in a blob.
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+
+/// The size of a field element in bytes.
+pub const BYTES_PER_FIELD_ELEMENT: usize = 32;
+
+/// The size of a blob in bytes.
+pub const BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB * BYTES_PER_FIELD_ELEMENT;
+
+/// The size of a KZG commitment in bytes.
+pub const BYTES_PER_COMMITMENT: usize = 48;
+
+/// The modulus of the BLS12-381 scalar field, which every field element is reduced by.
+pub const BLS_MODULUS: U256 = U256::from_limbs([
+    0xffffffff00000001,
+    0x53bda402fffe5bfe,
+    0x3339d80809a1d805,
+    0x73eda753299d7d48,
+]);
+
+/// The generator of the multiplicative group of the BLS12-381 scalar field.
+const PRIMITIVE_ROOT: u64 = 7;
+
+/// A [BlobKey] identifies a field element of a blob, by the blob's KZG commitment and the index of
+/// the field element. The key is the keccak256 hash of the commitment followed by the field
+/// element's root of unity, and the pre-image is the big-endian field element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobKey {
+    /// The KZG commitment of the blob.
+    pub commitment: [u8; BYTES_PER_COMMITMENT],
+    /// The index of the field element, below [FIELD_ELEMENTS_PER_BLOB].
+    pub index: usize,
+}
+
+impl Key for BlobKey {
+    fn preimage_key(self) -> [u8; 32] {
+        let mut data = [0u8; BYTES_PER_COMMITMENT + 32];
+        data[..BYTES_PER_COMMITMENT].copy_from_slice(&self.commitment);
+        data[BYTES_PER_COMMITMENT..].copy_from_slice(&root_of_unity(self.index));
+
+        let mut key = keccak256(data).0;
+        key[0] = KeyType::Blob as u8;
+        key
+    }
+}
+
+/// Returns the root of unity that the field element at the given index of a blob is evaluated at,
+/// as a big-endian field element.
+///
+/// ### Panics
+/// - If the index is not below [FIELD_ELEMENTS_PER_BLOB].
+pub fn root_of_unity(index: usize) -> [u8; 32] {
+    static ROOTS: OnceLock<Vec<[u8; 32]>> = OnceLock::new();
+    let roots = ROOTS.get_or_init(|| {
+        let order = U256::from(FIELD_ELEMENTS_PER_BLOB);
+        let generator =
+            U256::from(PRIMITIVE_ROOT).pow_mod((BLS_MODULUS - U256::from(1)) / order, BLS_MODULUS);
+        let bits = FIELD_ELEMENTS_PER_BLOB.trailing_zeros();
+        (0..FIELD_ELEMENTS_PER_BLOB)
+            .map(|i| {
+                let exponent = i.reverse_bits() >> (usize::BITS - bits);
+                generator
+                    .pow_mod(U256::from(exponent), BLS_MODULUS)
+                    .to_be_bytes()
+            })
+            .collect()
+    });
+    roots[index]
+}
+
+/// Verifies that a blob is well formed and, with the `kzg` feature, that it matches its KZG
+/// commitment.
+///
+/// ### Takes
+/// - `commitment`: The KZG commitment of the blob.
+/// - `blob`: The blob.
+///
+/// ### Returns
+/// - A [Result] indicating whether the blob is valid.
+pub fn verify_blob(commitment: &[u8; BYTES_PER_COMMITMENT], blob: &[u8]) -> Result<()> {
+    ensure!(
+        blob.len() == BYTES_PER_BLOB,
+        "Blob is {} bytes, expected {}",
+        blob.len(),
+        BYTES_PER_BLOB
+    );
+    for (i, element) in blob.chunks_exact(BYTES_PER_FIELD_ELEMENT).enumerate() {
+        ensure!(
+            U256::from_be_slice(element) < BLS_MODULUS,
+            "Field element {} of the blob is not canonical",
+            i
+        );
+    }
+
+    #[cfg(feature = "kzg")]
+    {
+        let blob = c_kzg::Blob::from_bytes(blob).map_err(|e| anyhow!("Invalid blob: {:?}", e))?;
+        let expected =
+            c_kzg::KzgCommitment::blob_to_kzg_commitment(&blob, c_kzg::ethereum_kzg_settings())
+                .map_err(|e| anyhow!("Failed to compute the blob's commitment: {:?}", e))?;
+        ensure!(
+            expected.to_bytes().as_slice() == commitment.as_slice(),
+            "Blob does not match its commitment 0x{}",
+            alloy_primitives::hex::encode(commitment)
+        );
+    }
+    #[cfg(not(feature = "kzg"))]
+    crate::traces::warn!(target: "preimage::blob", "Storing a blob without verifying its commitment, which requires the `kzg` feature");
+
+    Ok(())
+}
+
+/// Verifies a blob and stores each of its field elements as the pre-image of its [BlobKey].
+pub(crate) fn put_blob<S: PreimageStore + ?Sized>(
+    store: &mut S,
+    commitment: &[u8; BYTES_PER_COMMITMENT],
+    blob: &[u8],
+) -> Result<()> {
+    verify_blob(commitment, blob)?;
+    for (index, element) in blob.chunks_exact(BYTES_PER_FIELD_ELEMENT).enumerate() {
+        let key = BlobKey {
+            commitment: *commitment,
+            index,
+        };
+        store.put(key.preimage_key(), element.to_vec())?;
+    }
+    Ok(())
+}
+
+/// Gets the field element at the given index of a stored blob.
+pub(crate) fn get_blob_field_element<S: PreimageStore + ?Sized>(
+    store: &mut S,
+    commitment: &[u8; BYTES_PER_COMMITMENT],
+    index: usize,
+) -> Result<Option<[u8; BYTES_PER_FIELD_ELEMENT]>> {
+    ensure!(
+        index < FIELD_ELEMENTS_PER_BLOB,
+        "Field element index {} is out of bounds",
+        index
+    );
+    let key = BlobKey {
+        commitment: *commitment,
+        index,
+    };
+    store
+        .get(key.preimage_key())?
+        .map(|element| {
+            element
+                .try_into()
+                .map_err(|_| anyhow!("Stored field element {} is not 32 bytes", index))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LruPreimageStore;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn roots_of_unity() {
+        // The first root is one, and the second, in bit-reversed order, is minus one.
+        assert_eq!(U256::from_be_bytes(root_of_unity(0)), U256::from(1));
+        assert_eq!(
+            U256::from_be_bytes(root_of_unity(1)),
+            BLS_MODULUS - U256::from(1)
+        );
+
+        // Every root is of an order that divides the size of the domain.
+        let root = U256::from_be_bytes(root_of_unity(FIELD_ELEMENTS_PER_BLOB - 1));
+        assert_eq!(
+            root.pow_mod(U256::from(FIELD_ELEMENTS_PER_BLOB), BLS_MODULUS),
+            U256::from(1)
+        );
+        assert_ne!(
+            root.pow_mod(U256::from(FIELD_ELEMENTS_PER_BLOB / 2), BLS_MODULUS),
+            U256::from(1)
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "kzg"))]
+    fn stores_blob_field_elements() {
+        let mut store = LruPreimageStore::new(NonZeroUsize::new(FIELD_ELEMENTS_PER_BLOB).unwrap());
+        let commitment = [0xC0; BYTES_PER_COMMITMENT];
+        let mut blob = vec![0u8; BYTES_PER_BLOB];
+        blob[31] = 1;
+        blob[BYTES_PER_BLOB - 1] = 0xFF;
+        store.put_blob(&commitment, &blob).unwrap();
+
+        let element = store.get_blob_field_element(&commitment, 0).unwrap();
+        assert_eq!(element.unwrap()[31], 1);
+        let element = store
+            .get_blob_field_element(&commitment, FIELD_ELEMENTS_PER_BLOB - 1)
+            .unwrap();
+        assert_eq!(element.unwrap()[31], 0xFF);
+        assert_eq!(store.get_blob_field_element(&[0; 48], 0).unwrap(), None);
+        assert!(store
+            .get_blob_field_element(&commitment, FIELD_ELEMENTS_PER_BLOB)
+            .is_err());
+
+        // Blobs of the wrong size or with non-canonical field elements are rejected.
+        assert!(store.put_blob(&commitment, &blob[1..]).is_err());
+        blob[..32].copy_from_slice(&BLS_MODULUS.to_be_bytes::<32>());
+        assert!(store.put_blob(&commitment, &blob).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "kzg")]
+    fn verifies_blob_commitments() {
+        let mut store = LruPreimageStore::new(NonZeroUsize::new(FIELD_ELEMENTS_PER_BLOB).unwrap());
+        let mut blob = vec![0u8; BYTES_PER_BLOB];
+        blob[31] = 1;
+        let commitment = c_kzg::KzgCommitment::blob_to_kzg_commitment(
+            &c_kzg::Blob::from_bytes(&blob).unwrap(),
+            c_kzg::ethereum_kzg_settings(),
+        )
+        .unwrap()
+        .to_bytes();
+        let commitment: [u8; BYTES_PER_COMMITMENT] = commitment.as_slice().try_into().unwrap();
+
+        assert!(store
+            .put_blob(&[0xC0; BYTES_PER_COMMITMENT], &blob)
+            .is_err());
+        store.put_blob(&commitment, &blob).unwrap();
+        let element = store.get_blob_field_element(&commitment, 0).unwrap();
+        assert_eq!(element.unwrap()[31], 1);
+    }
+}
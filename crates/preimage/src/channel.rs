@@ -0,0 +1,196 @@
+//! This module contains an in-process transport of the pre-image oracle protocol, backed by
+//! [std::sync::mpsc] channels rather than file descriptors.
+//!
+//! It allows a host and the emulator to run in the same process, e.g. in a monolithic challenger
+//! or in tests, without creating pipes whose file descriptors must outlive both sides. Create
+//! both ends with [create_oracle_channel], hand the [ChannelOracleClient] to the emulator and
+//! serve its requests from the [ChannelOracleServer] on another thread.
+
+use crate::{Hint, Hinter, Key, Oracle, PreimageSource};
+use anyhow::{anyhow, Result};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// A request sent from a [ChannelOracleClient] to its [ChannelOracleServer].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChannelRequest {
+    /// A hint for the host to prepare pre-images.
+    Hint(Vec<u8>),
+    /// A request for the pre-image of a 32-byte type-prefixed key.
+    Preimage([u8; 32]),
+}
+
+/// The response of a [ChannelOracleServer] to a [ChannelRequest]: the pre-image, which is empty
+/// for hints, or the error that the host failed with.
+type ChannelResponse = std::result::Result<Vec<u8>, String>;
+
+/// Creates both ends of an in-process pre-image oracle channel.
+///
+/// ### Returns
+/// - The [ChannelOracleClient] that requests hints and pre-images, and the [ChannelOracleServer]
+///   that serves them.
+pub fn create_oracle_channel() -> (ChannelOracleClient, ChannelOracleServer) {
+    let (request_tx, request_rx) = channel();
+    let (response_tx, response_rx) = channel();
+    (
+        ChannelOracleClient {
+            requests: request_tx,
+            responses: response_rx,
+        },
+        ChannelOracleServer {
+            requests: request_rx,
+            responses: response_tx,
+        },
+    )
+}
+
+/// The [ChannelOracleClient] sends hints and pre-image requests to a [ChannelOracleServer] in the
+/// same process, blocking until the server responds.
+#[derive(Debug)]
+pub struct ChannelOracleClient {
+    /// The sender of requests to the server.
+    requests: Sender<ChannelRequest>,
+    /// The receiver of the server's responses.
+    responses: Receiver<ChannelResponse>,
+}
+
+impl ChannelOracleClient {
+    /// Sends a request to the server and waits for its response.
+    fn request(&mut self, request: ChannelRequest) -> Result<Vec<u8>> {
+        self.requests
+            .send(request)
+            .map_err(|_| anyhow!("The pre-image oracle server was dropped"))?;
+        self.responses
+            .recv()
+            .map_err(|_| anyhow!("The pre-image oracle server was dropped"))?
+            .map_err(|e| anyhow!("The pre-image oracle server failed: {}", e))
+    }
+}
+
+impl Oracle for ChannelOracleClient {
+    fn get(&mut self, key: impl Key) -> Result<Vec<u8>> {
+        self.request(ChannelRequest::Preimage(key.preimage_key()))
+    }
+}
+
+impl Hinter for ChannelOracleClient {
+    fn hint(&mut self, hint: impl Hint) -> Result<()> {
+        self.request(ChannelRequest::Hint(hint.hint().to_vec()))
+            .map(|_| ())
+    }
+}
+
+/// The [ChannelOracleServer] serves the hints and pre-image requests of a [ChannelOracleClient] in
+/// the same process. Unlike the [crate::OracleServer], a failure to fetch a pre-image or to
+/// handle a hint is sent back to the client, and the server keeps serving.
+#[derive(Debug)]
+pub struct ChannelOracleServer {
+    /// The receiver of the client's requests.
+    requests: Receiver<ChannelRequest>,
+    /// The sender of responses to the client.
+    responses: Sender<ChannelResponse>,
+}
+
+impl ChannelOracleServer {
+    /// Serves a single request from the [ChannelOracleClient].
+    ///
+    /// ### Takes
+    /// - `getter` - The [PreimageSource] that pre-images are fetched from.
+    /// - `hinter` - The handler of hints.
+    ///
+    /// ### Returns
+    /// - `Ok(true)` if the client was dropped.
+    /// - `Ok(false)` if a request was served.
+    /// - `Err(_)` if the client was dropped before receiving the response.
+    pub fn next_request(
+        &mut self,
+        getter: &mut impl PreimageSource,
+        hinter: &mut impl FnMut(&[u8]) -> Result<()>,
+    ) -> Result<bool> {
+        let Ok(request) = self.requests.recv() else {
+            return Ok(true);
+        };
+
+        let response = match request {
+            ChannelRequest::Hint(hint) => {
+                crate::traces::debug!(target: "preimage::channel", len = hint.len(), "Routing hint");
+                hinter(&hint).map(|_| Vec::new())
+            }
+            ChannelRequest::Preimage(key) => {
+                crate::traces::debug!(
+                    target: "preimage::channel",
+                    "Serving pre-image for key {}",
+                    alloy_primitives::B256::from(key)
+                );
+                getter.get_preimage(key)
+            }
+        };
+        self.responses
+            .send(response.map_err(|e| {
+                crate::traces::error!(target: "preimage::channel", "Failed to serve request: {:?}", e);
+                e.to_string()
+            }))
+            .map_err(|_| anyhow!("The pre-image oracle client was dropped"))?;
+        Ok(false)
+    }
+
+    /// Serves requests from the [ChannelOracleClient] until it is dropped.
+    ///
+    /// ### Takes
+    /// - `getter` - The [PreimageSource] that pre-images are fetched from.
+    /// - `hinter` - The handler of hints.
+    pub fn serve(
+        &mut self,
+        mut getter: impl PreimageSource,
+        mut hinter: impl FnMut(&[u8]) -> Result<()>,
+    ) -> Result<()> {
+        while !self.next_request(&mut getter, &mut hinter)? {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Keccak256Key, RawKey};
+    use alloy_primitives::keccak256;
+    use std::thread;
+
+    #[test]
+    fn channel_roundtrip() {
+        let (mut client, mut server) = create_oracle_channel();
+        let host = thread::spawn(move || {
+            let mut hints = Vec::new();
+            server
+                .serve(
+                    |key: [u8; 32]| match key
+                        == (keccak256(b"hello").0 as Keccak256Key).preimage_key()
+                    {
+                        true => Ok(b"hello".to_vec()),
+                        false => Err(anyhow!("Missing pre-image")),
+                    },
+                    |hint: &[u8]| {
+                        hints.push(hint.to_vec());
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            hints
+        });
+
+        client.hint(b"l1-block 0x01".as_slice()).unwrap();
+        assert_eq!(
+            client.get(keccak256(b"hello").0 as Keccak256Key).unwrap(),
+            b"hello"
+        );
+        // Failures are reported to the client, and the server keeps serving.
+        assert!(client.get(RawKey([0xFF; 32])).is_err());
+        client.hint(b"l1-block 0x02".as_slice()).unwrap();
+
+        // Dropping the client stops the server.
+        drop(client);
+        assert_eq!(
+            host.join().unwrap(),
+            [b"l1-block 0x01".to_vec(), b"l1-block 0x02".to_vec()]
+        );
+    }
+}
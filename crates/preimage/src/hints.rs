@@ -1,6 +1,6 @@
 //! This module contains the [HintWriter] and [HintReader] structs and their implementations.
 
-use crate::{types::HintHandler, Hint, Hinter, ReadWritePair};
+use crate::{types::HintHandler, wire, Hint, Hinter, ReadWritePair};
 use anyhow::Result;
 use std::io::{Read, Write};
 
@@ -18,12 +18,7 @@ impl HintWriter {
 
 impl Hinter for HintWriter {
     fn hint(&mut self, value: impl Hint) -> Result<()> {
-        let hint = value.hint();
-        let mut hint_bytes = vec![0u8; 4 + hint.len()];
-        hint_bytes[0..4].copy_from_slice((hint.len() as u32).to_be_bytes().as_ref());
-        hint_bytes[4..].copy_from_slice(hint);
-
-        let _ = self.io.write(&hint_bytes)?;
+        wire::write_hint(&mut self.io, value.hint())?;
 
         self.io.read_exact(&mut [0])?;
         Ok(())
@@ -44,26 +39,16 @@ impl HintReader {
 
 impl HintReader {
     pub fn next_hint(&mut self, router: HintHandler) -> Result<bool> {
-        let mut length = [0u8; 4];
-        let n = self.io.read(&mut length)?;
-        if n < 4 {
+        let Some(payload) = wire::read_hint(&mut self.io)? else {
             // Return EOF
             return Ok(true);
-        }
-
-        let length = u32::from_be_bytes(length) as usize;
-        let payload = if length == 0 {
-            Vec::default()
-        } else {
-            let mut raw_payload = vec![0u8; length];
-            self.io.read_exact(&mut raw_payload)?;
-            raw_payload
         };
 
+        crate::traces::debug!(target: "preimage::hints", len = payload.len(), "Routing hint");
         if let Err(e) = router(&payload) {
             // Write back on error to unblock the hint writer.
             let _ = self.io.write(&[0])?;
-            crate::error!("Failed to handle hint: {:?}", e);
+            crate::traces::error!(target: "preimage::hints", "Failed to handle hint: {:?}", e);
             anyhow::bail!("Failed to handle hint: {:?}", e);
         }
 
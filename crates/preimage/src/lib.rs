@@ -5,14 +5,40 @@ pub(crate) mod traces;
 mod oracle;
 pub use oracle::{OracleClient, OracleServer};
 
+pub mod wire;
+pub use wire::{PreimageResponseError, MAX_PREIMAGE_LENGTH, PREIMAGE_LENGTH_PREFIX_SIZE};
+
 mod traits;
-pub use traits::{FileChannel, Hint, Hinter, Key, Oracle};
+pub use traits::{FileChannel, Hint, Hinter, Key, Oracle, PreimageSource};
 
 mod types;
-pub use types::{Keccak256Key, KeyType, LocalIndexKey, PreimageGetter, RawKey};
+#[allow(deprecated)]
+pub use types::PreimageGetter;
+pub use types::{Keccak256Key, KeyType, LocalIndexKey, PrecompileKey, RawKey};
+
+mod local;
+pub use local::{
+    LocalInputs, L1_HEAD_LOCAL_INDEX, L2_CHAIN_CONFIG_LOCAL_INDEX, L2_CHAIN_ID_LOCAL_INDEX,
+    L2_CLAIM_BLOCK_NUMBER_LOCAL_INDEX, L2_CLAIM_LOCAL_INDEX, L2_OUTPUT_ROOT_LOCAL_INDEX,
+    ROLLUP_CONFIG_LOCAL_INDEX,
+};
 
 mod hints;
 pub use hints::{HintReader, HintWriter};
 
+mod blob;
+pub use blob::{
+    root_of_unity, verify_blob, BlobKey, BLS_MODULUS, BYTES_PER_BLOB, BYTES_PER_COMMITMENT,
+    BYTES_PER_FIELD_ELEMENT, FIELD_ELEMENTS_PER_BLOB,
+};
+
+mod store;
+#[cfg(feature = "object-store")]
+pub use store::ObjectPreimageStore;
+pub use store::{DiskPreimageStore, LruPreimageStore, PreimageStore};
+
+mod channel;
+pub use channel::{create_oracle_channel, ChannelOracleClient, ChannelOracleServer};
+
 mod file_chan;
 pub use file_chan::{create_bidirectional_channel, ReadWritePair};
@@ -0,0 +1,126 @@
+//! This module contains the [LocalInputs] of a program run, which are served to the program through
+//! local pre-image keys, and the local key indices that `op-program` reads them from.
+
+use crate::{Key, LocalIndexKey, PreimageStore};
+use alloy_primitives::B256;
+use anyhow::Result;
+
+/// The local key index of the L1 head block hash.
+pub const L1_HEAD_LOCAL_INDEX: LocalIndexKey = 1;
+
+/// The local key index of the agreed upon L2 output root.
+pub const L2_OUTPUT_ROOT_LOCAL_INDEX: LocalIndexKey = 2;
+
+/// The local key index of the disputed L2 output root claim.
+pub const L2_CLAIM_LOCAL_INDEX: LocalIndexKey = 3;
+
+/// The local key index of the L2 block number of the disputed claim.
+pub const L2_CLAIM_BLOCK_NUMBER_LOCAL_INDEX: LocalIndexKey = 4;
+
+/// The local key index of the L2 chain ID.
+pub const L2_CHAIN_ID_LOCAL_INDEX: LocalIndexKey = 5;
+
+/// The local key index of the JSON L2 chain config, for chains unknown to the program.
+pub const L2_CHAIN_CONFIG_LOCAL_INDEX: LocalIndexKey = 6;
+
+/// The local key index of the JSON rollup config, for chains unknown to the program.
+pub const ROLLUP_CONFIG_LOCAL_INDEX: LocalIndexKey = 7;
+
+/// The [LocalInputs] that bootstrap a program run, as `op-program`'s host serves them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalInputs {
+    /// The L1 head block hash.
+    pub l1_head: B256,
+    /// The agreed upon L2 output root.
+    pub l2_output_root: B256,
+    /// The disputed L2 output root claim.
+    pub l2_claim: B256,
+    /// The L2 block number of the disputed claim.
+    pub l2_claim_block_number: u64,
+    /// The L2 chain ID.
+    pub l2_chain_id: u64,
+    /// The JSON L2 chain config, if the chain is unknown to the program.
+    pub l2_chain_config: Option<Vec<u8>>,
+    /// The JSON rollup config, if the chain is unknown to the program.
+    pub rollup_config: Option<Vec<u8>>,
+}
+
+impl LocalInputs {
+    /// Returns the local pre-images of the [LocalInputs], keyed by their type-prefixed pre-image
+    /// keys. Integers are encoded as 8 byte big-endian values.
+    pub fn preimages(&self) -> Vec<([u8; 32], Vec<u8>)> {
+        let mut preimages = vec![
+            (L1_HEAD_LOCAL_INDEX, self.l1_head.to_vec()),
+            (L2_OUTPUT_ROOT_LOCAL_INDEX, self.l2_output_root.to_vec()),
+            (L2_CLAIM_LOCAL_INDEX, self.l2_claim.to_vec()),
+            (
+                L2_CLAIM_BLOCK_NUMBER_LOCAL_INDEX,
+                self.l2_claim_block_number.to_be_bytes().to_vec(),
+            ),
+            (
+                L2_CHAIN_ID_LOCAL_INDEX,
+                self.l2_chain_id.to_be_bytes().to_vec(),
+            ),
+        ];
+        if let Some(ref config) = self.l2_chain_config {
+            preimages.push((L2_CHAIN_CONFIG_LOCAL_INDEX, config.clone()));
+        }
+        if let Some(ref config) = self.rollup_config {
+            preimages.push((ROLLUP_CONFIG_LOCAL_INDEX, config.clone()));
+        }
+
+        preimages
+            .into_iter()
+            .map(|(index, value)| (index.preimage_key(), value))
+            .collect()
+    }
+
+    /// Populates the local pre-images of the [LocalInputs] into a [PreimageStore], so that a run
+    /// can be served without a host.
+    ///
+    /// ### Takes
+    /// - `store` - The [PreimageStore] to populate.
+    ///
+    /// ### Returns
+    /// - A [Result] indicating whether the pre-images were stored successfully.
+    pub fn populate(&self, store: &mut impl PreimageStore) -> Result<()> {
+        for (key, value) in self.preimages() {
+            store.put(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LruPreimageStore;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn populate_local_keys() {
+        let inputs = LocalInputs {
+            l1_head: B256::repeat_byte(0x11),
+            l2_output_root: B256::repeat_byte(0x22),
+            l2_claim: B256::repeat_byte(0x33),
+            l2_claim_block_number: 0x0102,
+            l2_chain_id: 10,
+            l2_chain_config: None,
+            rollup_config: Some(b"{}".to_vec()),
+        };
+        let mut store = LruPreimageStore::new(NonZeroUsize::new(16).unwrap());
+        inputs.populate(&mut store).unwrap();
+        assert_eq!(store.len(), 6);
+
+        let mut key = [0u8; 32];
+        key[0] = 1;
+        key[31] = 4;
+        assert_eq!(store.get(key).unwrap(), Some(vec![0, 0, 0, 0, 0, 0, 1, 2]));
+        key[31] = 1;
+        assert_eq!(store.get(key).unwrap(), Some(vec![0x11; 32]));
+        key[31] = 6;
+        assert_eq!(store.get(key).unwrap(), None);
+        key[31] = 7;
+        assert_eq!(store.get(key).unwrap(), Some(b"{}".to_vec()));
+    }
+}
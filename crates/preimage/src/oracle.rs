@@ -1,8 +1,10 @@
-//! This module contains the [Client] struct and its implementation.
+//! This module contains the [OracleClient] and [OracleServer] structs and their implementations.
 
-use crate::{Key, Oracle, PreimageGetter, ReadWritePair};
+#[allow(deprecated)]
+use crate::PreimageGetter;
+use crate::{wire, Key, Oracle, PreimageSource, ReadWritePair};
 use anyhow::Result;
-use std::io::{Read, Write};
+use std::io::Write;
 
 /// The [OracleClient] is a client that can make requests and write to the [OracleServer].
 /// It contains a [ReadWritePair] that is one half of a bidirectional channel, with the other
@@ -18,22 +20,12 @@ impl OracleClient {
 }
 
 impl Oracle for OracleClient {
+    /// Requests the pre-image of a key, validating that the response consists of exactly an 8-byte
+    /// big-endian length prefix followed by that many bytes. Malformed responses are returned as
+    /// a [wire::PreimageResponseError].
     fn get(&mut self, key: impl Key) -> Result<Vec<u8>> {
-        let hash = key.preimage_key();
-        self.io.write_all(&hash)?;
-
-        let mut length = [0u8; 8];
-        self.io.read_exact(&mut length)?;
-        let length = u64::from_be_bytes(length) as usize;
-
-        let payload = if length == 0 {
-            Vec::default()
-        } else {
-            let mut payload = vec![0u8; length];
-            self.io.read_exact(&mut payload)?;
-            payload
-        };
-        Ok(payload)
+        wire::write_preimage_request(&mut self.io, key.preimage_key())?;
+        wire::read_preimage_response(&mut self.io)
     }
 }
 
@@ -51,17 +43,66 @@ impl OracleServer {
 }
 
 impl OracleServer {
-    pub fn new_preimage_request(&mut self, getter: PreimageGetter) -> Result<()> {
-        let mut key = [0u8; 32];
-        self.io.read_exact(&mut key)?;
+    /// Serves a single pre-image request from the [OracleClient]: reads the requested key, looks
+    /// up its pre-image through the [PreimageSource] and writes back the length-prefixed
+    /// pre-image.
+    ///
+    /// ### Takes
+    /// - `getter` - The [PreimageSource] that pre-images are fetched from.
+    ///
+    /// ### Returns
+    /// - `Ok(true)` if the client closed the channel before sending a request.
+    /// - `Ok(false)` if a request was served.
+    /// - `Err(_)` if the channel failed or the pre-image could not be fetched.
+    pub fn next_preimage_request(&mut self, getter: &mut impl PreimageSource) -> Result<bool> {
+        let Some(key) = wire::read_preimage_request(&mut self.io)? else {
+            // Return EOF
+            return Ok(true);
+        };
+
+        crate::traces::debug!(
+            target: "preimage::oracle",
+            "Serving pre-image for key {}",
+            alloy_primitives::B256::from(key)
+        );
+        let value = getter.get_preimage(key).map_err(|e| {
+            crate::traces::error!(target: "preimage::oracle", "Failed to fetch pre-image: {:?}", e);
+            e
+        })?;
+
+        wire::write_preimage_response(&mut self.io, &value)?;
+        self.io.flush()?;
 
-        let value = getter(key)?;
+        Ok(false)
+    }
 
-        self.io.write_all(&(value.len() as u64).to_be_bytes())?;
-        if !value.is_empty() {
-            self.io.write_all(&value)?;
+    /// Serves a single pre-image request from the [OracleClient].
+    ///
+    /// ### Takes
+    /// - `getter` - The [PreimageGetter] that pre-images are fetched from.
+    ///
+    /// ### Returns
+    /// - `Err(_)` if the client closed the channel, the channel failed or the pre-image could not
+    ///   be fetched.
+    #[deprecated(note = "use `OracleServer::next_preimage_request` or `OracleServer::serve`")]
+    #[allow(deprecated)]
+    pub fn new_preimage_request(&mut self, mut getter: PreimageGetter) -> Result<()> {
+        if self.next_preimage_request(&mut getter)? {
+            anyhow::bail!("Pre-image channel closed");
         }
+        Ok(())
+    }
 
+    /// Serves pre-image requests from the [OracleClient] until it closes the channel.
+    ///
+    /// ### Takes
+    /// - `getter` - The [PreimageSource] that pre-images are fetched from.
+    ///
+    /// ### Returns
+    /// - `Ok(())` once the client closed the channel.
+    /// - `Err(_)` if the channel failed or a pre-image could not be fetched.
+    pub fn serve(&mut self, mut getter: impl PreimageSource) -> Result<()> {
+        while !self.next_preimage_request(&mut getter)? {}
         Ok(())
     }
 }
@@ -112,12 +153,13 @@ mod test {
                 async move {
                     // Lock the server
                     let mut server = server.lock().await;
-                    server
-                        .new_preimage_request(Box::new(move |key: [u8; 32]| {
+                    let eof = server
+                        .next_preimage_request(&mut |key: [u8; 32]| {
                             let dat = preimage_by_hash.get(&key).unwrap();
                             Ok(dat.clone())
-                        }))
+                        })
                         .unwrap();
+                    assert!(!eof);
                 }
             });
 
@@ -125,6 +167,103 @@ mod test {
         }
     }
 
+    #[test]
+    fn serve_until_eof() {
+        let (a, b) = crate::create_bidirectional_channel().unwrap();
+        let preimages = [b"one".to_vec(), b"two".to_vec(), vec![]];
+
+        let server = std::thread::spawn(move || {
+            let mut served = 0;
+            OracleServer::new(b)
+                .serve(|key: [u8; 32]| {
+                    served += 1;
+                    Ok(vec![key[1]; key[1] as usize])
+                })
+                .map(|_| served)
+        });
+
+        let mut client = OracleClient::new(a);
+        for preimage in preimages.iter() {
+            let mut key = [0u8; 32];
+            key[1] = preimage.len() as u8;
+            let expected = vec![key[1]; key[1] as usize];
+            assert_eq!(client.get(crate::RawKey(key)).unwrap(), expected);
+        }
+        drop(client);
+
+        assert_eq!(server.join().unwrap().unwrap(), preimages.len());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_preimage_getter() {
+        let (a, b) = crate::create_bidirectional_channel().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let mut server = OracleServer::new(b);
+            server.new_preimage_request(Box::new(|key: [u8; 32]| Ok(key[..2].to_vec())))?;
+            // The channel closes before the second request.
+            server.new_preimage_request(Box::new(|_: [u8; 32]| Ok(vec![])))
+        });
+
+        let mut client = OracleClient::new(a);
+        assert_eq!(client.get(crate::RawKey([7u8; 32])).unwrap(), vec![7u8; 2]);
+        drop(client);
+        assert!(server.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn getter_error() {
+        let (a, b) = crate::create_bidirectional_channel().unwrap();
+
+        let server = std::thread::spawn(move || {
+            OracleServer::new(b).serve(|_: [u8; 32]| anyhow::bail!("not found"))
+        });
+
+        let mut client = OracleClient::new(a);
+        assert!(client.get(crate::RawKey([1u8; 32])).is_err());
+        assert!(server.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn malformed_responses() {
+        use crate::wire::{PreimageResponseError, MAX_PREIMAGE_LENGTH};
+        use std::io::Write;
+
+        let cases: [(&[u8], PreimageResponseError); 3] = [
+            (
+                &[0, 0, 0],
+                PreimageResponseError::ShortLengthPrefix { read: 3 },
+            ),
+            (
+                &(MAX_PREIMAGE_LENGTH + 1).to_be_bytes(),
+                PreimageResponseError::TooLong {
+                    length: MAX_PREIMAGE_LENGTH + 1,
+                },
+            ),
+            (
+                &[0, 0, 0, 0, 0, 0, 0, 4, 0xAA, 0xBB],
+                PreimageResponseError::ShortPayload {
+                    expected: 4,
+                    read: 2,
+                },
+            ),
+        ];
+        for (response, expected) in cases {
+            let (a, b) = crate::create_bidirectional_channel().unwrap();
+            // The host responds with a malformed response and hangs up, keeping its reader open so
+            // that the request can still be written.
+            b.writer().write_all(response).unwrap();
+            let _reader = b.reader().try_clone().unwrap();
+            drop(b);
+
+            let err = OracleClient::new(a)
+                .get(crate::RawKey([1u8; 32]))
+                .unwrap_err();
+            assert_eq!(err.downcast_ref::<PreimageResponseError>(), Some(&expected));
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn empty_preimage() {
         test_preimage(vec![vec![]]).await;
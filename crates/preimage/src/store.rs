@@ -0,0 +1,305 @@
+//! This module contains the [PreimageStore] trait and its implementations, which allow hosts to
+//! persist the pre-images that they fetch between runs.
+
+use crate::blob::{self, BYTES_PER_COMMITMENT, BYTES_PER_FIELD_ELEMENT};
+use alloy_primitives::hex;
+use anyhow::Result;
+use lru::LruCache;
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    num::NonZeroUsize,
+    path::PathBuf,
+};
+
+/// The [PreimageStore] trait describes a key-value store of pre-images, indexed by their 32-byte
+/// type-prefixed pre-image keys.
+pub trait PreimageStore {
+    /// Get the pre-image of a given pre-image key.
+    ///
+    /// ### Takes
+    /// - `key` - The pre-image key.
+    ///
+    /// ### Returns
+    /// - `Ok(Some(preimage))` if the pre-image is in the store.
+    /// - `Ok(None)` if it is not.
+    fn get(&mut self, key: [u8; 32]) -> Result<Option<Vec<u8>>>;
+
+    /// Stores the pre-image of a given pre-image key, replacing any existing pre-image.
+    ///
+    /// ### Takes
+    /// - `key` - The pre-image key.
+    /// - `value` - The pre-image.
+    fn put(&mut self, key: [u8; 32], value: Vec<u8>) -> Result<()>;
+
+    /// Verifies an EIP-4844 blob against its KZG commitment and stores each of its field elements
+    /// as the pre-image of its [BlobKey](crate::BlobKey). The commitment is only verified with the `kzg` feature.
+    ///
+    /// ### Takes
+    /// - `commitment` - The KZG commitment of the blob.
+    /// - `blob` - The blob.
+    fn put_blob(&mut self, commitment: &[u8; BYTES_PER_COMMITMENT], blob: &[u8]) -> Result<()> {
+        blob::put_blob(self, commitment, blob)
+    }
+
+    /// Get the field element at the given index of a blob stored with [PreimageStore::put_blob].
+    ///
+    /// ### Takes
+    /// - `commitment` - The KZG commitment of the blob.
+    /// - `index` - The index of the field element.
+    ///
+    /// ### Returns
+    /// - `Ok(Some(element))` if the blob is in the store.
+    /// - `Ok(None)` if it is not.
+    fn get_blob_field_element(
+        &mut self,
+        commitment: &[u8; BYTES_PER_COMMITMENT],
+        index: usize,
+    ) -> Result<Option<[u8; BYTES_PER_FIELD_ELEMENT]>> {
+        blob::get_blob_field_element(self, commitment, index)
+    }
+}
+
+/// The [DiskPreimageStore] stores every pre-image in its own file, using the same layout as
+/// `op-program`'s `--datadir`: the file is named after the `0x`-prefixed hex key with a `.txt`
+/// extension, and contains the hex-encoded pre-image.
+#[derive(Debug, Clone)]
+pub struct DiskPreimageStore {
+    /// The directory that pre-images are stored in.
+    path: PathBuf,
+}
+
+impl DiskPreimageStore {
+    /// Creates a new [DiskPreimageStore] in the given directory, creating the directory if it does
+    /// not exist.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Returns the path of the file that the pre-image of the given key is stored in.
+    fn path_for(&self, key: [u8; 32]) -> PathBuf {
+        self.path.join(format!("0x{}.txt", hex::encode(key)))
+    }
+}
+
+impl PreimageStore for DiskPreimageStore {
+    fn get(&mut self, key: [u8; 32]) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path_for(key)) {
+            Ok(raw) => Ok(Some(hex::decode(raw)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&mut self, key: [u8; 32], value: Vec<u8>) -> Result<()> {
+        // Write to a temporary file first, so that a concurrent reader never observes a partially
+        // written pre-image.
+        let path = self.path_for(key);
+        let tmp = path.with_extension(format!("txt.{}.tmp", std::process::id()));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(hex::encode(value).as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// The [LruPreimageStore] holds a bounded number of pre-images in memory, evicting the least
+/// recently used pre-image once full.
+#[derive(Debug)]
+pub struct LruPreimageStore {
+    /// The cached pre-images.
+    cache: LruCache<[u8; 32], Vec<u8>>,
+}
+
+impl LruPreimageStore {
+    /// Creates a new [LruPreimageStore] that holds at most `capacity` pre-images.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            cache: LruCache::new(capacity),
+        }
+    }
+
+    /// Returns the number of pre-images held by the store.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the store holds no pre-images.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+}
+
+impl PreimageStore for LruPreimageStore {
+    fn get(&mut self, key: [u8; 32]) -> Result<Option<Vec<u8>>> {
+        Ok(self.cache.get(&key).cloned())
+    }
+
+    fn put(&mut self, key: [u8; 32], value: Vec<u8>) -> Result<()> {
+        self.cache.put(key, value);
+        Ok(())
+    }
+}
+
+/// The [ObjectPreimageStore] stores pre-images in an object store such as S3, using the same key
+/// layout as the [DiskPreimageStore] beneath a common prefix.
+///
+/// Requests are driven by a runtime owned by the store, on its own thread, so the store may be
+/// used both from synchronous code and from within another Tokio runtime.
+#[cfg(feature = "object-store")]
+pub struct ObjectPreimageStore {
+    /// The object store.
+    store: std::sync::Arc<dyn object_store::ObjectStore>,
+    /// The prefix that pre-images are stored beneath.
+    prefix: object_store::path::Path,
+    /// The runtime that drives the object store's requests. Only taken when the store is dropped.
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "object-store")]
+impl ObjectPreimageStore {
+    /// Creates a new [ObjectPreimageStore].
+    ///
+    /// ### Takes
+    /// - `store` - The object store.
+    /// - `prefix` - The prefix that pre-images are stored beneath.
+    pub fn new(
+        store: std::sync::Arc<dyn object_store::ObjectStore>,
+        prefix: impl Into<object_store::path::Path>,
+    ) -> Result<Self> {
+        Ok(Self {
+            store,
+            prefix: prefix.into(),
+            runtime: Some(
+                tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()?,
+            ),
+        })
+    }
+
+    /// Returns the location of the object that the pre-image of the given key is stored in.
+    fn location_for(&self, key: [u8; 32]) -> object_store::path::Path {
+        self.prefix.child(format!("0x{}.txt", hex::encode(key)))
+    }
+
+    /// Runs a request on the store's runtime and waits for its result. The request is spawned
+    /// rather than driven with `block_on`, which panics when called from within a runtime.
+    fn run<T, F>(&self, request: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: std::future::Future<Output = Result<T>> + Send + 'static,
+    {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("Runtime is only taken on drop");
+        runtime.spawn(async move {
+            let _ = sender.send(request.await);
+        });
+        receiver
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Object store request was cancelled"))?
+    }
+}
+
+#[cfg(feature = "object-store")]
+impl Drop for ObjectPreimageStore {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks until its tasks finish, which panics within another runtime.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(feature = "object-store")]
+impl PreimageStore for ObjectPreimageStore {
+    fn get(&mut self, key: [u8; 32]) -> Result<Option<Vec<u8>>> {
+        let (store, location) = (self.store.clone(), self.location_for(key));
+        self.run(async move {
+            match store.get(&location).await {
+                Ok(object) => Ok(Some(hex::decode(object.bytes().await?)?)),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn put(&mut self, key: [u8; 32], value: Vec<u8>) -> Result<()> {
+        let (store, location) = (self.store.clone(), self.location_for(key));
+        self.run(async move {
+            store
+                .put(&location, hex::encode(value).into_bytes().into())
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disk_store() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("preimages");
+        let mut store = DiskPreimageStore::new(&dir).unwrap();
+
+        let mut key = [0u8; 32];
+        key[0] = 2;
+        key[31] = 0xAB;
+        assert_eq!(store.get(key).unwrap(), None);
+
+        store.put(key, b"hello".to_vec()).unwrap();
+        assert_eq!(store.get(key).unwrap(), Some(b"hello".to_vec()));
+
+        // Pre-images are laid out as in op-program's datadir.
+        let file = dir.join(format!("0x02{}ab.txt", "00".repeat(30)));
+        assert_eq!(fs::read_to_string(file).unwrap(), "68656c6c6f");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn lru_store() {
+        let mut store = LruPreimageStore::new(NonZeroUsize::new(2).unwrap());
+        store.put([1u8; 32], vec![1]).unwrap();
+        store.put([2u8; 32], vec![2]).unwrap();
+
+        // Touch the first pre-image so that the second is evicted.
+        assert_eq!(store.get([1u8; 32]).unwrap(), Some(vec![1]));
+        store.put([3u8; 32], vec![3]).unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get([2u8; 32]).unwrap(), None);
+        assert_eq!(store.get([1u8; 32]).unwrap(), Some(vec![1]));
+        assert_eq!(store.get([3u8; 32]).unwrap(), Some(vec![3]));
+    }
+
+    #[cfg(feature = "object-store")]
+    #[test]
+    fn object_store_within_runtime() {
+        // Hosts that serve pre-images asynchronously create, use and drop the store from within a
+        // runtime.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut store = ObjectPreimageStore::new(
+                std::sync::Arc::new(object_store::memory::InMemory::new()),
+                "preimages",
+            )
+            .unwrap();
+            assert_eq!(store.get([1u8; 32]).unwrap(), None);
+            store.put([1u8; 32], b"hello".to_vec()).unwrap();
+            assert_eq!(store.get([1u8; 32]).unwrap(), Some(b"hello".to_vec()));
+        });
+    }
+}
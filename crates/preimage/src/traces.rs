@@ -1,5 +1,15 @@
 #![allow(unused_imports)]
 
+/// Performs a tracing trace if the `tracing` feature is enabled.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+pub use trace;
+
 /// Performs a tracing debug if the `tracing` feature is enabled.
 #[macro_export]
 macro_rules! debug {
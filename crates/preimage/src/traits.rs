@@ -19,6 +19,28 @@ pub trait Oracle {
     fn get(&mut self, key: impl Key) -> Result<Vec<u8>>;
 }
 
+/// The [PreimageSource] trait describes the source of the pre-images served by a
+/// [crate::OracleServer] to its [crate::OracleClient].
+pub trait PreimageSource {
+    /// Get the full pre-image of a given 32-byte type-prefixed pre-image key.
+    ///
+    /// ### Takes
+    /// - `key` - The pre-image key requested by the client.
+    ///
+    /// ### Returns
+    /// - The pre-image, or an error if it is not available.
+    fn get_preimage(&mut self, key: [u8; 32]) -> Result<Vec<u8>>;
+}
+
+impl<F> PreimageSource for F
+where
+    F: FnMut([u8; 32]) -> Result<Vec<u8>>,
+{
+    fn get_preimage(&mut self, key: [u8; 32]) -> Result<Vec<u8>> {
+        self(key)
+    }
+}
+
 // [Hint] is an trait to enable any program type to function as a hint,
 // When passed to the Hinter interface, returning a string representation
 // of what data the host should prepare pre-images for.
@@ -1,9 +1,11 @@
 //! This module contains the types for the preimage-oracle crate.
 
 use crate::{Hint, Key};
+use alloy_primitives::keccak256;
 use anyhow::Result;
 
 /// A [PreimageGetter] is a function that can be used to fetch pre-images.
+#[deprecated(note = "implement `PreimageSource` instead, which any such function does")]
 pub type PreimageGetter = Box<dyn Fn([u8; 32]) -> Result<Vec<u8>>>;
 
 /// A [HintHandler] is a function that can be used to handle hints from a [crate::HintWriter].
@@ -18,6 +20,18 @@ pub struct RawKey(pub [u8; 32]);
 /// A [LocalIndexKey] is a key local to the program, indexing a special program input.
 pub type LocalIndexKey = u64;
 
+/// A [PrecompileKey] identifies the result of calling a precompile with an input, as the
+/// `PreimageOracle`'s `loadPrecompilePreimagePart` commits to it: the key is the keccak256 hash of
+/// the precompile's address followed by the input. The pre-image is a status byte, `1` if the call
+/// succeeded and `0` otherwise, followed by the output of the call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrecompileKey {
+    /// The address of the precompile.
+    pub address: [u8; 20],
+    /// The input of the call.
+    pub input: Vec<u8>,
+}
+
 /// The [KeyType] enum represents the different types of keys that can be used to index
 /// pre-images.
 #[repr(u8)]
@@ -28,6 +42,12 @@ pub enum KeyType {
     Local = 1,
     /// The global key type is used to index a global keccak256 preimage.
     GlobalKeccak = 2,
+    /// The blob key type is used to index a field element of an EIP-4844 blob; see
+    /// [crate::BlobKey].
+    Blob = 5,
+    /// The precompile key type is used to index the result of a precompile call; see
+    /// [PrecompileKey].
+    Precompile = 6,
 }
 
 /// The [PreimageFds] enum represents the file descriptors used for hinting and pre-image
@@ -45,6 +65,8 @@ impl From<u8> for KeyType {
         match n {
             1 => KeyType::Local,
             2 => KeyType::GlobalKeccak,
+            5 => KeyType::Blob,
+            6 => KeyType::Precompile,
             _ => KeyType::_Illegal,
         }
     }
@@ -66,6 +88,18 @@ impl Key for Keccak256Key {
     }
 }
 
+impl Key for PrecompileKey {
+    fn preimage_key(self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(20 + self.input.len());
+        data.extend_from_slice(&self.address);
+        data.extend_from_slice(&self.input);
+
+        let mut key = keccak256(data).0;
+        key[0] = KeyType::Precompile as u8;
+        key
+    }
+}
+
 impl Key for RawKey {
     fn preimage_key(self) -> [u8; 32] {
         self.0
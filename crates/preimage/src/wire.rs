@@ -0,0 +1,250 @@
+//! This module contains the framing of the messages exchanged over the Pre-image Oracle ABI, as
+//! pure functions over any [Read] or [Write]r, so that they can be tested and fuzzed without a
+//! channel.
+//!
+//! - A pre-image request is the 32-byte key of the pre-image.
+//! - A pre-image response is the pre-image, prefixed with its length as a big-endian `u64`.
+//! - A hint is the hint bytes, prefixed with their length as a big-endian `u32`. The reader of the
+//!   hint acknowledges it with a single byte once it was routed.
+//!
+//! Lengths are never trusted: payloads are read incrementally, so that a malformed length prefix
+//! cannot cause an allocation larger than the data that was actually received.
+
+use anyhow::Result;
+use std::{
+    fmt,
+    io::{ErrorKind, Read, Write},
+};
+
+/// The size of the big-endian length prefix of a pre-image response.
+pub const PREIMAGE_LENGTH_PREFIX_SIZE: usize = 8;
+
+/// The maximum length of a pre-image. Reads into a pre-image are addressed by a 32-bit offset into
+/// the length-prefixed pre-image, so longer pre-images could never be read to the end.
+pub const MAX_PREIMAGE_LENGTH: u64 = u32::MAX as u64 - PREIMAGE_LENGTH_PREFIX_SIZE as u64;
+
+/// The size of the big-endian length prefix of a hint.
+pub const HINT_LENGTH_PREFIX_SIZE: usize = 4;
+
+/// A [PreimageResponseError] describes a pre-image response from the host that does not follow the
+/// protocol. It is returned from [read_preimage_response] and [crate::OracleClient::get] wrapped
+/// in an [anyhow::Error], from which it can be recovered with [anyhow::Error::downcast_ref].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreimageResponseError {
+    /// The host closed the channel before sending the full 8-byte length prefix.
+    ShortLengthPrefix {
+        /// The number of bytes of the prefix that were received.
+        read: usize,
+    },
+    /// The host announced a pre-image longer than [MAX_PREIMAGE_LENGTH].
+    TooLong {
+        /// The announced length.
+        length: u64,
+    },
+    /// The host closed the channel before sending the announced number of bytes.
+    ShortPayload {
+        /// The announced length.
+        expected: usize,
+        /// The number of bytes that were received.
+        read: usize,
+    },
+}
+
+impl fmt::Display for PreimageResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShortLengthPrefix { read } => write!(
+                f,
+                "Short pre-image response: received {} of the {} bytes of the length prefix",
+                read, PREIMAGE_LENGTH_PREFIX_SIZE
+            ),
+            Self::TooLong { length } => write!(
+                f,
+                "Pre-image length {} exceeds the maximum of {}",
+                length, MAX_PREIMAGE_LENGTH
+            ),
+            Self::ShortPayload { expected, read } => write!(
+                f,
+                "Short pre-image response: received {} of {} announced bytes",
+                read, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PreimageResponseError {}
+
+/// Reads into the buffer until it is full or the reader reaches EOF.
+///
+/// ### Returns
+/// - The number of bytes read.
+pub fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(read)
+}
+
+/// Reads up to `length` bytes, growing the buffer only as data arrives.
+///
+/// ### Returns
+/// - The bytes read, which are fewer than `length` only if the reader reached EOF.
+fn read_payload(reader: &mut impl Read, length: u64) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    reader.take(length).read_to_end(&mut payload)?;
+    Ok(payload)
+}
+
+/// Reads a pre-image request.
+///
+/// ### Returns
+/// - `Ok(Some(key))` with the requested key.
+/// - `Ok(None)` if the channel was closed before the request.
+/// - `Err(_)` if the channel failed or was closed within the key.
+pub fn read_preimage_request(reader: &mut impl Read) -> Result<Option<[u8; 32]>> {
+    let mut key = [0u8; 32];
+    match read_full(reader, &mut key)? {
+        0 => Ok(None),
+        32 => Ok(Some(key)),
+        read => anyhow::bail!("Short pre-image request: received {} of 32 key bytes", read),
+    }
+}
+
+/// Writes a pre-image request for the given key.
+pub fn write_preimage_request(writer: &mut impl Write, key: [u8; 32]) -> Result<()> {
+    writer.write_all(&key)?;
+    Ok(())
+}
+
+/// Reads a pre-image response, validating that it consists of exactly an 8-byte big-endian
+/// length prefix followed by that many bytes.
+///
+/// ### Returns
+/// - `Ok(preimage)` with the pre-image, without its length prefix.
+/// - `Err(_)` if the channel failed, or with a [PreimageResponseError] if the response is
+///   malformed.
+pub fn read_preimage_response(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut length = [0u8; PREIMAGE_LENGTH_PREFIX_SIZE];
+    let read = read_full(reader, &mut length)?;
+    if read < length.len() {
+        return Err(PreimageResponseError::ShortLengthPrefix { read }.into());
+    }
+    let length = u64::from_be_bytes(length);
+    if length > MAX_PREIMAGE_LENGTH {
+        return Err(PreimageResponseError::TooLong { length }.into());
+    }
+
+    let payload = read_payload(reader, length)?;
+    if (payload.len() as u64) < length {
+        return Err(PreimageResponseError::ShortPayload {
+            expected: length as usize,
+            read: payload.len(),
+        }
+        .into());
+    }
+    Ok(payload)
+}
+
+/// Writes a pre-image response with the length-prefixed pre-image.
+pub fn write_preimage_response(writer: &mut impl Write, preimage: &[u8]) -> Result<()> {
+    writer.write_all(&(preimage.len() as u64).to_be_bytes())?;
+    if !preimage.is_empty() {
+        writer.write_all(preimage)?;
+    }
+    Ok(())
+}
+
+/// Reads a length-prefixed hint.
+///
+/// ### Returns
+/// - `Ok(Some(hint))` with the hint, without its length prefix.
+/// - `Ok(None)` if the channel was closed before the hint.
+/// - `Err(_)` if the channel failed or was closed within the hint.
+pub fn read_hint(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut length = [0u8; HINT_LENGTH_PREFIX_SIZE];
+    match read_full(reader, &mut length)? {
+        0 => return Ok(None),
+        HINT_LENGTH_PREFIX_SIZE => {}
+        read => anyhow::bail!(
+            "Short hint: received {} of the {} bytes of the length prefix",
+            read,
+            HINT_LENGTH_PREFIX_SIZE
+        ),
+    }
+
+    let length = u32::from_be_bytes(length);
+    let payload = read_payload(reader, length as u64)?;
+    if payload.len() < length as usize {
+        anyhow::bail!(
+            "Short hint: received {} of {} announced bytes",
+            payload.len(),
+            length
+        );
+    }
+    Ok(Some(payload))
+}
+
+/// Writes a length-prefixed hint.
+pub fn write_hint(writer: &mut impl Write, hint: &[u8]) -> Result<()> {
+    let length = u32::try_from(hint.len())
+        .map_err(|_| anyhow::anyhow!("Hint of {} bytes is too long", hint.len()))?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(hint)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn roundtrip() {
+        let mut buf = Vec::new();
+        write_preimage_request(&mut buf, [7u8; 32]).unwrap();
+        write_preimage_response(&mut buf, b"hello world").unwrap();
+        write_preimage_response(&mut buf, b"").unwrap();
+        write_hint(&mut buf, b"l1-block 0x1234").unwrap();
+        write_hint(&mut buf, b"").unwrap();
+
+        let mut reader = Cursor::new(buf);
+        assert_eq!(read_preimage_request(&mut reader).unwrap(), Some([7u8; 32]));
+        assert_eq!(read_preimage_response(&mut reader).unwrap(), b"hello world");
+        assert!(read_preimage_response(&mut reader).unwrap().is_empty());
+        assert_eq!(read_hint(&mut reader).unwrap().unwrap(), b"l1-block 0x1234");
+        assert_eq!(read_hint(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_hint(&mut reader).unwrap(), None);
+        assert_eq!(read_preimage_request(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_messages() {
+        assert!(read_preimage_request(&mut Cursor::new([1u8; 31])).is_err());
+        assert!(read_hint(&mut Cursor::new([0u8; 3])).is_err());
+        assert!(read_hint(&mut Cursor::new([0, 0, 0, 4, 0xAA])).is_err());
+    }
+
+    #[test]
+    fn untrusted_lengths() {
+        // The largest announced lengths only read what was received.
+        let mut response = (MAX_PREIMAGE_LENGTH).to_be_bytes().to_vec();
+        response.extend_from_slice(&[0xAA; 3]);
+        let err = read_preimage_response(&mut Cursor::new(response)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PreimageResponseError>(),
+            Some(&PreimageResponseError::ShortPayload {
+                expected: MAX_PREIMAGE_LENGTH as usize,
+                read: 3,
+            })
+        );
+
+        let hint = [0xFF, 0xFF, 0xFF, 0xFF, 0xAA];
+        assert!(read_hint(&mut Cursor::new(hint)).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! This module contains the [WitnessHasher] trait and the [Keccak256Hasher].
+
+/// A [WitnessHasher] is a trait describing a commitment scheme for [crate::StateWitness]es. The
+/// `MIPS` contract commits to states with [Keccak256Hasher], which is the default; other
+/// schemes, such as SHA-256 for non-EVM verifiers, may be plugged in by implementing this trait.
+pub trait WitnessHasher {
+    /// Hashes the given data.
+    ///
+    /// ### Takes
+    /// - `data`: The data to hash.
+    ///
+    /// ### Returns
+    /// - The 32 byte digest of the data.
+    fn hash(data: &[u8]) -> [u8; 32];
+}
+
+/// The [Keccak256Hasher] is the [WitnessHasher] used by the `MIPS` contract.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Keccak256Hasher;
+
+impl WitnessHasher for Keccak256Hasher {
+    #[inline(always)]
+    fn hash(data: &[u8]) -> [u8; 32] {
+        #[cfg(feature = "asm-keccak")]
+        {
+            use keccak_asm::Digest;
+            keccak_asm::Keccak256::digest(data).into()
+        }
+
+        #[cfg(not(feature = "asm-keccak"))]
+        {
+            use tiny_keccak::{Hasher, Keccak};
+            let mut out = [0u8; 32];
+            let mut keccak = Keccak::v256();
+            keccak.update(data);
+            keccak.finalize(&mut out);
+            out
+        }
+    }
+}
+
+/// The [Keccak256Stream] computes the same digest as the [Keccak256Hasher] over data that is fed to
+/// it in parts, so that the data need not be assembled into a single buffer first.
+pub struct Keccak256Stream {
+    /// The inner keccak256 state.
+    #[cfg(feature = "asm-keccak")]
+    inner: keccak_asm::Keccak256,
+    /// The inner keccak256 state.
+    #[cfg(not(feature = "asm-keccak"))]
+    inner: tiny_keccak::Keccak,
+}
+
+impl Default for Keccak256Stream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keccak256Stream {
+    /// Creates a new, empty [Keccak256Stream].
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "asm-keccak")]
+            inner: <keccak_asm::Keccak256 as keccak_asm::Digest>::new(),
+            #[cfg(not(feature = "asm-keccak"))]
+            inner: tiny_keccak::Keccak::v256(),
+        }
+    }
+
+    /// Feeds the next part of the data to the [Keccak256Stream].
+    #[inline(always)]
+    pub fn update(&mut self, data: &[u8]) {
+        #[cfg(feature = "asm-keccak")]
+        keccak_asm::Digest::update(&mut self.inner, data);
+
+        #[cfg(not(feature = "asm-keccak"))]
+        tiny_keccak::Hasher::update(&mut self.inner, data);
+    }
+
+    /// Returns the digest of all data fed to the [Keccak256Stream].
+    pub fn finalize(self) -> [u8; 32] {
+        #[cfg(feature = "asm-keccak")]
+        {
+            keccak_asm::Digest::finalize(self.inner).into()
+        }
+
+        #[cfg(not(feature = "asm-keccak"))]
+        {
+            let mut out = [0u8; 32];
+            tiny_keccak::Hasher::finalize(self.inner, &mut out);
+            out
+        }
+    }
+}
+
+#[cfg(all(test, feature = "asm-keccak"))]
+mod test {
+    use super::*;
+    use tiny_keccak::{Hasher, Keccak};
+
+    fn tiny_keccak256(data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        let mut keccak = Keccak::v256();
+        keccak.update(data);
+        keccak.finalize(&mut out);
+        out
+    }
+
+    #[test]
+    fn asm_matches_tiny_keccak() {
+        // The known digests of the empty string and of `abc`.
+        assert_eq!(
+            Keccak256Hasher::hash(b""),
+            [
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70
+            ]
+        );
+        assert_eq!(
+            Keccak256Hasher::hash(b"abc"),
+            [
+                0x4e, 0x03, 0x65, 0x7a, 0xea, 0x45, 0xa9, 0x4f, 0xc7, 0xd4, 0x7b, 0xa8, 0x26, 0xc8,
+                0xd6, 0x67, 0xc0, 0xd1, 0xe6, 0xe3, 0x3a, 0x64, 0xa0, 0x36, 0xec, 0x44, 0xf5, 0x8f,
+                0xa1, 0x2d, 0x6c, 0x45
+            ]
+        );
+
+        // Messages around the 136 byte rate of keccak256, up to three blocks.
+        let data: [u8; 409] = core::array::from_fn(|i| i as u8);
+        for len in 0..=data.len() {
+            let expected = tiny_keccak256(&data[..len]);
+            assert_eq!(
+                Keccak256Hasher::hash(&data[..len]),
+                expected,
+                "length {}",
+                len
+            );
+
+            let mut stream = Keccak256Stream::new();
+            for part in data[..len].chunks(61) {
+                stream.update(part);
+            }
+            assert_eq!(stream.finalize(), expected, "length {}", len);
+        }
+    }
+}
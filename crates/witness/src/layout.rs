@@ -0,0 +1,154 @@
+//! This module contains the byte layout of an encoded [crate::StateWitness] and of the ABI encoded
+//! input to the `MIPS` step function.
+//!
+//! Every [Field] is declared once, in order, with its length; its offset follows from the fields
+//! before it. The encoder, the decoder and the accessors of the witness all index it through these
+//! fields, so that they cannot drift apart.
+
+use core::ops::Range;
+
+/// A [Field] of an encoded layout, located by its byte offset and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The byte offset of the field.
+    pub offset: usize,
+    /// The length of the field in bytes.
+    pub len: usize,
+}
+
+impl Field {
+    /// Returns the byte offset right after the field.
+    pub const fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    /// Returns the byte range of the field.
+    pub const fn range(&self) -> Range<usize> {
+        self.offset..self.end()
+    }
+}
+
+/// Declares the [Field]s of a layout in order, along with the table of all of them (`FIELDS`) and
+/// the total size of the layout (`SIZE`).
+macro_rules! layout {
+    ($($(#[$doc:meta])* $name:ident = $len:expr;)*) => {
+        layout!(@field 0; $($(#[$doc])* $name = $len;)*);
+
+        /// All fields of the layout, in order.
+        pub const FIELDS: &[Field] = &[$($name),*];
+    };
+    (@field $offset:expr;) => {
+        /// The total size of the layout in bytes.
+        pub const SIZE: usize = $offset;
+    };
+    (@field $offset:expr; $(#[$doc:meta])* $name:ident = $len:expr; $($rest:tt)*) => {
+        $(#[$doc])*
+        pub const $name: Field = Field {
+            name: stringify!($name),
+            offset: $offset,
+            len: $len,
+        };
+        layout!(@field $name.end(); $($rest)*);
+    };
+}
+
+/// The size of a register in bytes.
+pub const REGISTER_SIZE: usize = 4;
+
+layout! {
+    /// The merkle root of the memory.
+    MEMORY_ROOT = 32;
+    /// The key of the active preimage.
+    PREIMAGE_KEY = 32;
+    /// The read offset into the active preimage.
+    PREIMAGE_OFFSET = 4;
+    /// The program counter.
+    PC = 4;
+    /// The next program counter.
+    NEXT_PC = 4;
+    /// The `lo` register.
+    LO = 4;
+    /// The `hi` register.
+    HI = 4;
+    /// The heap pointer.
+    HEAP = 4;
+    /// The exit code of the VM.
+    EXIT_CODE = 1;
+    /// Whether or not the VM has exited, `1` if it has.
+    EXITED = 1;
+    /// The step count of the VM.
+    STEP = 8;
+    /// The 32 general purpose registers; see [register].
+    REGISTERS = 32 * REGISTER_SIZE;
+}
+
+/// Returns the [Field] of the general purpose register with the given index.
+///
+/// ### Panics
+/// - If the index is not below 32.
+pub const fn register(index: usize) -> Field {
+    assert!(index < 32, "Register index out of bounds");
+    Field {
+        name: "REGISTER",
+        offset: REGISTERS.offset + index * REGISTER_SIZE,
+        len: REGISTER_SIZE,
+    }
+}
+
+/// The layout of the ABI encoded input to the `MIPS` step function, `step(bytes,bytes)`, with the
+/// [crate::StateWitness] as its first argument and the memory proof as its second. The memory
+/// proof, padded to a multiple of 32 bytes, starts at [SIZE](step_input::SIZE) and runs to the
+/// end of the input.
+pub mod step_input {
+    use super::Field;
+
+    /// The size of an ABI word in bytes.
+    pub const WORD_SIZE: usize = 32;
+
+    /// The length of the [crate::StateWitness] padded to a multiple of [WORD_SIZE].
+    pub const PADDED_STATE_SIZE: usize = super::SIZE.div_ceil(WORD_SIZE) * WORD_SIZE;
+
+    layout! {
+        /// The function selector.
+        SELECTOR = 4;
+        /// The head of the state argument, holding the offset of its tail.
+        STATE_HEAD = WORD_SIZE;
+        /// The head of the memory proof argument, holding the offset of its tail.
+        PROOF_HEAD = WORD_SIZE;
+        /// The length of the state witness.
+        STATE_LEN = WORD_SIZE;
+        /// The state witness.
+        STATE = super::SIZE;
+        /// The zero padding of the state witness.
+        STATE_PADDING = PADDED_STATE_SIZE - super::SIZE;
+        /// The length of the memory proof.
+        PROOF_LEN = WORD_SIZE;
+    }
+}
+
+const _: () = assert!(SIZE == 226, "The state witness is 226 bytes");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields_are_contiguous() {
+        for table in [FIELDS, step_input::FIELDS] {
+            assert_eq!(table[0].offset, 0);
+            for pair in table.windows(2) {
+                assert_eq!(pair[0].end(), pair[1].offset, "{}", pair[1].name);
+            }
+        }
+        assert_eq!(FIELDS.last().unwrap().end(), SIZE);
+        assert_eq!(EXIT_CODE.offset, 88);
+        assert_eq!(register(31).end(), SIZE);
+
+        // The heads hold the offsets of the tails, relative to the end of the selector.
+        assert_eq!(step_input::STATE_LEN.offset - step_input::SELECTOR.len, 64);
+        assert_eq!(step_input::PROOF_LEN.offset - step_input::SELECTOR.len, 352);
+        assert_eq!(step_input::SIZE % 32, 4);
+    }
+}
@@ -0,0 +1,13 @@
+#![doc = include_str!("../README.md")]
+#![no_std]
+
+pub mod layout;
+
+mod hasher;
+pub use hasher::{Keccak256Hasher, Keccak256Stream, WitnessHasher};
+
+mod status;
+pub use status::VMStatus;
+
+mod witness;
+pub use witness::{StateWitness, StateWitnessHasher, WitnessState, STATE_WITNESS_SIZE};
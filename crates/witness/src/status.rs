@@ -0,0 +1,45 @@
+//! This module contains the [VMStatus] enum.
+
+use core::fmt;
+
+/// The [VMStatus] is an indicator within the [crate::StateWitness] hash that indicates
+/// the current status of the MIPS emulator. It is derived from the exited flag and the exit code
+/// of the state; see [VMStatus::from_exit].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VMStatus {
+    /// The program exited with exit code 0.
+    Valid = 0,
+    /// The program exited with exit code 1.
+    Invalid = 1,
+    /// The program exited with any other exit code.
+    Panic = 2,
+    /// The program has not exited.
+    Unfinished = 3,
+}
+
+impl VMStatus {
+    /// Returns the [VMStatus] given `exited` and `exit_code` statuses.
+    pub fn from_exit(exited: bool, exit_code: u8) -> Self {
+        if !exited {
+            return VMStatus::Unfinished;
+        }
+
+        match exit_code {
+            0 => VMStatus::Valid,
+            1 => VMStatus::Invalid,
+            _ => VMStatus::Panic,
+        }
+    }
+}
+
+impl fmt::Display for VMStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VMStatus::Valid => write!(f, "valid"),
+            VMStatus::Invalid => write!(f, "invalid"),
+            VMStatus::Panic => write!(f, "panic"),
+            VMStatus::Unfinished => write!(f, "unfinished"),
+        }
+    }
+}
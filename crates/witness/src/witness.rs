@@ -0,0 +1,245 @@
+//! This module contains the [StateWitness] encoding and the [StateWitnessHasher] trait.
+
+use crate::{
+    layout::{self, Field},
+    Keccak256Hasher, Keccak256Stream, VMStatus, WitnessHasher,
+};
+
+/// The size of an encoded [StateWitness] in bytes; see [layout] for its fields.
+pub const STATE_WITNESS_SIZE: usize = layout::SIZE;
+
+/// A [StateWitness] is an encoded commitment to the current state of the MIPS emulator.
+pub type StateWitness = [u8; STATE_WITNESS_SIZE];
+
+/// The [WitnessState] holds the fields of the MIPS emulator's state that a [StateWitness] commits
+/// to. Memory is committed to by its merkle root only.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WitnessState {
+    /// The merkle root of the memory.
+    pub memory_root: [u8; 32],
+    /// The key of the active preimage.
+    pub preimage_key: [u8; 32],
+    /// The read offset into the active preimage.
+    pub preimage_offset: u32,
+    /// The program counter.
+    pub pc: u32,
+    /// The next program counter.
+    pub next_pc: u32,
+    /// The `lo` register.
+    pub lo: u32,
+    /// The `hi` register.
+    pub hi: u32,
+    /// The heap pointer.
+    pub heap: u32,
+    /// The exit code of the VM.
+    pub exit_code: u8,
+    /// Whether or not the VM has exited.
+    pub exited: bool,
+    /// The step count of the VM.
+    pub step: u64,
+    /// The general purpose registers.
+    pub registers: [u32; 32],
+}
+
+impl WitnessState {
+    /// Encodes the [WitnessState] into a [StateWitness], as the `MIPS` contract expects it.
+    pub fn encode(&self) -> StateWitness {
+        let mut witness: StateWitness = [0u8; STATE_WITNESS_SIZE];
+        witness[layout::MEMORY_ROOT.range()].copy_from_slice(&self.memory_root);
+        witness[layout::PREIMAGE_KEY.range()].copy_from_slice(&self.preimage_key);
+        witness[layout::PREIMAGE_OFFSET.range()]
+            .copy_from_slice(&self.preimage_offset.to_be_bytes());
+        witness[layout::PC.range()].copy_from_slice(&self.pc.to_be_bytes());
+        witness[layout::NEXT_PC.range()].copy_from_slice(&self.next_pc.to_be_bytes());
+        witness[layout::LO.range()].copy_from_slice(&self.lo.to_be_bytes());
+        witness[layout::HI.range()].copy_from_slice(&self.hi.to_be_bytes());
+        witness[layout::HEAP.range()].copy_from_slice(&self.heap.to_be_bytes());
+        witness[layout::EXIT_CODE.offset] = self.exit_code;
+        witness[layout::EXITED.offset] = self.exited as u8;
+        witness[layout::STEP.range()].copy_from_slice(&self.step.to_be_bytes());
+        for (i, r) in self.registers.iter().enumerate() {
+            witness[layout::register(i).range()].copy_from_slice(&r.to_be_bytes());
+        }
+        witness
+    }
+
+    /// Decodes a [WitnessState] from a [StateWitness].
+    ///
+    /// ### Takes
+    /// - `witness`: The encoded [StateWitness].
+    ///
+    /// ### Returns
+    /// - The decoded [WitnessState].
+    pub fn decode(witness: &StateWitness) -> Self {
+        let word = |field: Field| -> [u8; 32] {
+            witness[field.range()]
+                .try_into()
+                .expect("Field is 32 bytes")
+        };
+        let u32_at = |field: Field| -> u32 {
+            u32::from_be_bytes(witness[field.range()].try_into().expect("Field is 4 bytes"))
+        };
+
+        Self {
+            memory_root: word(layout::MEMORY_ROOT),
+            preimage_key: word(layout::PREIMAGE_KEY),
+            preimage_offset: u32_at(layout::PREIMAGE_OFFSET),
+            pc: u32_at(layout::PC),
+            next_pc: u32_at(layout::NEXT_PC),
+            lo: u32_at(layout::LO),
+            hi: u32_at(layout::HI),
+            heap: u32_at(layout::HEAP),
+            exit_code: witness[layout::EXIT_CODE.offset],
+            exited: witness[layout::EXITED.offset] == 1,
+            step: u64::from_be_bytes(
+                witness[layout::STEP.range()]
+                    .try_into()
+                    .expect("Field is 8 bytes"),
+            ),
+            registers: core::array::from_fn(|i| u32_at(layout::register(i))),
+        }
+    }
+
+    /// Returns the [VMStatus] of the [WitnessState].
+    pub fn status(&self) -> VMStatus {
+        VMStatus::from_exit(self.exited, self.exit_code)
+    }
+}
+
+/// A [StateWitnessHasher] is a trait describing the functionality of a type
+/// that computes a witness hash.
+///
+/// The trait is object safe, so that witnesses may be hashed through a `dyn StateWitnessHasher`,
+/// which only exposes [StateWitnessHasher::state_hash].
+pub trait StateWitnessHasher {
+    /// Compute the [StateWitness] hash with the [Keccak256Hasher], as the `MIPS` contract does.
+    fn state_hash(&self) -> [u8; 32];
+
+    /// Compute the [StateWitness] hash with the given [WitnessHasher]. The first byte of the hash
+    /// is replaced with the [VMStatus] of the state, regardless of the hasher.
+    fn state_hash_with<H: WitnessHasher>(&self) -> [u8; 32]
+    where
+        Self: Sized;
+}
+
+impl StateWitnessHasher for StateWitness {
+    fn state_hash(&self) -> [u8; 32] {
+        self.state_hash_with::<Keccak256Hasher>()
+    }
+
+    fn state_hash_with<H: WitnessHasher>(&self) -> [u8; 32] {
+        let mut hash = H::hash(self);
+        let exit_code = self[layout::EXIT_CODE.offset];
+        let exited = self[layout::EXITED.offset] == 1;
+        hash[0] = VMStatus::from_exit(exited, exit_code) as u8;
+        hash
+    }
+}
+
+impl StateWitnessHasher for WitnessState {
+    /// Computes the [StateWitness] hash by streaming the fields of the [WitnessState] through the
+    /// hash function in the order of the [layout], without encoding the [StateWitness] first.
+    fn state_hash(&self) -> [u8; 32] {
+        let mut stream = Keccak256Stream::new();
+        stream.update(&self.memory_root);
+        stream.update(&self.preimage_key);
+        for word in [
+            self.preimage_offset,
+            self.pc,
+            self.next_pc,
+            self.lo,
+            self.hi,
+            self.heap,
+        ] {
+            stream.update(&word.to_be_bytes());
+        }
+        stream.update(&[self.exit_code, self.exited as u8]);
+        stream.update(&self.step.to_be_bytes());
+        for register in self.registers {
+            stream.update(&register.to_be_bytes());
+        }
+
+        let mut hash = stream.finalize();
+        hash[0] = self.status() as u8;
+        hash
+    }
+
+    fn state_hash_with<H: WitnessHasher>(&self) -> [u8; 32] {
+        self.encode().state_hash_with::<H>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let state = WitnessState {
+            memory_root: [0xAA; 32],
+            preimage_key: [0xBB; 32],
+            preimage_offset: 0x11223344,
+            pc: 0x1000,
+            next_pc: 0x1004,
+            lo: 1,
+            hi: 2,
+            heap: 0x2000_0000,
+            exit_code: 3,
+            exited: true,
+            step: 0x0102030405060708,
+            registers: core::array::from_fn(|i| i as u32 * 0x01010101),
+        };
+        let witness = state.encode();
+
+        assert_eq!(&witness[64..68], &[0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(&witness[90..98], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(&witness[222..226], &[31, 31, 31, 31]);
+        assert_eq!(WitnessState::decode(&witness), state);
+        assert_eq!(state.state_hash(), witness.state_hash());
+    }
+
+    #[test]
+    fn state_hash_status() {
+        let mut state = WitnessState::default();
+        for (exited, exit_code, status) in [
+            (false, 0, VMStatus::Unfinished),
+            (true, 0, VMStatus::Valid),
+            (true, 1, VMStatus::Invalid),
+            (true, 7, VMStatus::Panic),
+        ] {
+            state.exited = exited;
+            state.exit_code = exit_code;
+            let witness = state.encode();
+            let hash = witness.state_hash();
+
+            assert_eq!(state.status(), status);
+            assert_eq!(hash[0], status as u8);
+            assert_eq!(hash[1..], Keccak256Hasher::hash(&witness)[1..]);
+            assert_eq!(state.state_hash(), hash);
+        }
+    }
+
+    #[test]
+    fn dyn_state_hash() {
+        let state = WitnessState {
+            pc: 0x1000,
+            ..Default::default()
+        };
+        let witness = state.encode();
+        let hashers: [&dyn StateWitnessHasher; 2] = [&state, &witness];
+        assert_eq!(hashers[0].state_hash(), hashers[1].state_hash());
+    }
+
+    #[test]
+    fn keccak256() {
+        // keccak256 of the empty string.
+        assert_eq!(
+            Keccak256Hasher::hash(&[]),
+            [
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70
+            ]
+        );
+    }
+}